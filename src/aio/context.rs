@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use redis::aio::MultiplexedConnection;
+
+use crate::{
+    clock::{Clock, SystemClock},
+    config::models::Config,
+    random::{RandomSource, ThreadRandom},
+    store::{acl::ConnectionPurpose, keys::KeyBuilder},
+};
+
+/// `ContextManager` for tokio services: the same config on one multiplexed connection,
+/// which clones share, so concurrent tasks neither block a thread nor open a connection
+/// each. Node bookkeeping, strategies and hooks stay with the blocking context.
+#[derive(Clone)]
+pub struct AsyncContextManager {
+    config: Config,
+    connection: MultiplexedConnection,
+    clock: Arc<dyn Clock>,
+    random: Arc<dyn RandomSource>,
+    purpose: ConnectionPurpose,
+}
+
+impl AsyncContextManager {
+    pub async fn new() -> Self {
+        let config = Config::get_config();
+        Self::from_config(&config).await
+    }
+
+    pub async fn from_config(config: &Config) -> Self {
+        Self::try_from_config(config)
+            .await
+            .expect("Redis connection could not be made")
+    }
+
+    pub async fn try_from_config(config: &Config) -> redis::RedisResult<Self> {
+        //! `from_config`, returning why redis couldn't be reached.
+        Self::try_from_config_for(config, ConnectionPurpose::Mutating).await
+    }
+
+    pub async fn try_from_config_for(
+        config: &Config,
+        purpose: ConnectionPurpose,
+    ) -> redis::RedisResult<Self> {
+        //! `try_from_config`, connecting as the ACL user configured for `purpose`.
+        let connection = config.get_async_connection(purpose).await?;
+        Ok(Self {
+            config: config.clone(),
+            connection,
+            clock: Arc::new(SystemClock),
+            random: Arc::new(ThreadRandom),
+            purpose,
+        })
+    }
+
+    pub fn get_connection(&mut self) -> &mut MultiplexedConnection {
+        &mut self.connection
+    }
+
+    pub fn get_config(&mut self) -> &mut Config {
+        &mut self.config
+    }
+
+    pub fn get_keys(&self) -> &KeyBuilder {
+        &self.config.keys
+    }
+
+    pub fn get_purpose(&self) -> ConnectionPurpose {
+        //! Which ACL user the connection logged in as, see `redis_conn.users`.
+        self.purpose
+    }
+
+    pub fn get_clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        //! Replaces the system clock, e.g. with a `FixedClock` in tests.
+        self.clock = Arc::from(clock);
+    }
+
+    pub fn get_random(&self) -> &dyn RandomSource {
+        self.random.as_ref()
+    }
+
+    pub fn set_random(&mut self, random: Box<dyn RandomSource>) {
+        //! Replaces thread-local randomness, e.g. with a `SeededRandom` in tests.
+        self.random = Arc::from(random);
+    }
+}
@@ -0,0 +1,181 @@
+use std::{collections::HashMap, future::Future};
+
+use redis::RedisError;
+
+use crate::store::{
+    entity::{RedisEntity, NOT_FOUND},
+    partial::PartialResult,
+};
+
+use super::context::AsyncContextManager;
+
+/// `RedisEntity`'s fetching, listing, saving and deleting on an `AsyncContextManager`,
+/// keyed and (de)serialized the same way. Types stored other than as a hash override
+/// `read_map_async` and `write_map_async` as they do the blocking ones.
+pub trait AsyncRedisEntity: RedisEntity<Error: Send> + Send {
+    fn read_map_async(
+        key: &str,
+        ctx: &mut AsyncContextManager,
+    ) -> impl Future<Output = Result<HashMap<String, String>, RedisError>> + Send {
+        //! Reads the raw fields stored at `key` (a hash by default).
+        async move {
+            redis::cmd("HGETALL")
+                .arg(key)
+                .query_async(ctx.get_connection())
+                .await
+        }
+    }
+
+    fn write_map_async(
+        key: &str,
+        map: HashMap<String, String>,
+        ctx: &mut AsyncContextManager,
+    ) -> impl Future<Output = Result<(), RedisError>> + Send {
+        //! Stores the raw fields at `key` (a hash by default).
+        async move {
+            redis::cmd("HSET")
+                .arg(key)
+                .arg(map)
+                .query_async(ctx.get_connection())
+                .await
+        }
+    }
+
+    fn get_async(
+        id: &str,
+        ctx: &mut AsyncContextManager,
+    ) -> impl Future<Output = Result<Self, Self::Error>> + Send {
+        async move {
+            let key = Self::get_key(id, ctx.get_keys());
+            Self::get_by_key_async(&key, ctx).await
+        }
+    }
+
+    fn get_by_key_async(
+        key: &str,
+        ctx: &mut AsyncContextManager,
+    ) -> impl Future<Output = Result<Self, Self::Error>> + Send {
+        async move {
+            let map = Self::read_map_async(key, ctx).await.map_err(|err| {
+                format!("Redis data for {:?} could not be retrieved: {:?}", key, err)
+            })?;
+            if map.is_empty() {
+                return Err(format!("{:?} {}", key, NOT_FOUND).into());
+            }
+            Self::from_map(map)
+        }
+    }
+
+    fn get_all_async(
+        ctx: &mut AsyncContextManager,
+    ) -> impl Future<Output = PartialResult<Self, Self::Error>> + Send {
+        async move {
+            let pattern = Self::get_pattern(ctx.get_keys());
+            Self::get_matching_async(pattern, ctx).await
+        }
+    }
+
+    fn get_matching_async(
+        pattern: String,
+        ctx: &mut AsyncContextManager,
+    ) -> impl Future<Output = PartialResult<Self, Self::Error>> + Send {
+        //! Every entity whose key matches `pattern`, scanned a page at a time. Entries that
+        //! fail to load are reported alongside the ones that did, see `EntityIter`.
+        async move {
+            let page_size = ctx.get_config().get_scan_count();
+            let mut result = PartialResult::default();
+            let mut cursor = 0;
+            loop {
+                let page: Result<(u64, Vec<String>), RedisError> = redis::cmd("SCAN")
+                    .arg(cursor)
+                    .arg("MATCH")
+                    .arg(&pattern)
+                    .arg("COUNT")
+                    .arg(page_size)
+                    .query_async(ctx.get_connection())
+                    .await;
+                let keys = match page {
+                    Ok((next, keys)) => {
+                        cursor = next;
+                        keys
+                    }
+                    Err(err) => {
+                        let err = format!("SCAN over {:?} failed: {:?}", pattern, err);
+                        result.failed.push((pattern.clone(), err.into()));
+                        return result;
+                    }
+                };
+                for key in keys {
+                    match Self::get_by_key_async(&key, ctx).await {
+                        Ok(entity) => result.ok.push(entity),
+                        Err(err) => result.failed.push((key, err)),
+                    }
+                }
+                if cursor == 0 {
+                    return result;
+                }
+            }
+        }
+    }
+
+    fn exists_async(id: &str, ctx: &mut AsyncContextManager) -> impl Future<Output = bool> + Send {
+        async move {
+            let key = Self::get_key(id, ctx.get_keys());
+            redis::cmd("EXISTS")
+                .arg(key)
+                .query_async::<_, bool>(ctx.get_connection())
+                .await
+                .unwrap_or(false)
+        }
+    }
+
+    fn save_async(
+        &self,
+        ctx: &mut AsyncContextManager,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send
+    where
+        Self: Sync,
+    {
+        //! Writes the entity and adds its id to the index set (if any).
+        async move {
+            let id = self.get_id();
+            let key = Self::get_key(&id, ctx.get_keys());
+            Self::write_map_async(&key, self.to_map(), ctx)
+                .await
+                .map_err(|err| format!("{:?} could not be saved: {:?}", key, err))?;
+            if let Some(set) = Self::index_set(ctx.get_keys()) {
+                redis::cmd("SADD")
+                    .arg(set)
+                    .arg(&id)
+                    .query_async::<_, ()>(ctx.get_connection())
+                    .await
+                    .map_err(|err| format!("{:?} could not be indexed: {:?}", id, err))?;
+            }
+            Ok(())
+        }
+    }
+
+    fn delete_async(
+        id: &str,
+        ctx: &mut AsyncContextManager,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        //! Removes the entity and its id from the index set (if any).
+        async move {
+            let key = Self::get_key(id, ctx.get_keys());
+            redis::cmd("DEL")
+                .arg(&key)
+                .query_async::<_, ()>(ctx.get_connection())
+                .await
+                .map_err(|err| format!("{:?} could not be deleted: {:?}", key, err))?;
+            if let Some(set) = Self::index_set(ctx.get_keys()) {
+                redis::cmd("SREM")
+                    .arg(set)
+                    .arg(id)
+                    .query_async::<_, ()>(ctx.get_connection())
+                    .await
+                    .map_err(|err| format!("{:?} could not be unindexed: {:?}", id, err))?;
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use redis::{ErrorKind, RedisError};
+
+use crate::{
+    error::parsing_error::ServerGroupParsingError,
+    events::{Event, EventKind},
+    game::options::GameOptions,
+    server::{
+        freeze::{self, CHECKSUMS_KEY, EXTERNAL_EDIT},
+        port::{allocate_port_section, PortReassignment},
+        server_group::{ServerGroup, ALIASES_KEY},
+    },
+    store::metrics,
+};
+
+use super::{context::AsyncContextManager, entity::AsyncRedisEntity};
+
+async fn check_external_edit(key: &str, ctx: &mut AsyncContextManager) -> Result<(), RedisError> {
+    //! `freeze::check` on the async connection.
+    let recorded: Option<String> = redis::cmd("HGET")
+        .arg(CHECKSUMS_KEY)
+        .arg(key)
+        .query_async(ctx.get_connection())
+        .await?;
+    let Some(recorded) = recorded else {
+        return Ok(());
+    };
+    let stored: HashMap<String, String> = redis::cmd("HGETALL")
+        .arg(key)
+        .query_async(ctx.get_connection())
+        .await?;
+    if stored.is_empty() || freeze::get_checksum(&stored) == recorded {
+        return Ok(());
+    }
+    Event::new(
+        EventKind::GroupEditedExternally,
+        key,
+        "changed outside the manager since its last write, not overwritten".into(),
+    )
+    .emit_async(ctx)
+    .await;
+    Err(RedisError::from((
+        ErrorKind::ClientError,
+        EXTERNAL_EDIT,
+        format!(
+            "{} changed outside the manager since its last write (--accept-external-changes to overwrite it)",
+            key
+        ),
+    )))
+}
+
+async fn record_checksum(key: &str, ctx: &mut AsyncContextManager) -> Result<(), RedisError> {
+    //! `freeze::record` on the async connection.
+    let stored: HashMap<String, String> = redis::cmd("HGETALL")
+        .arg(key)
+        .query_async(ctx.get_connection())
+        .await?;
+    redis::cmd("HSET")
+        .arg(CHECKSUMS_KEY)
+        .arg(key)
+        .arg(freeze::get_checksum(&stored))
+        .query_async(ctx.get_connection())
+        .await
+}
+
+impl AsyncRedisEntity for ServerGroup {
+    async fn read_map_async(
+        key: &str,
+        ctx: &mut AsyncContextManager,
+    ) -> Result<HashMap<String, String>, RedisError> {
+        metrics::record_group_access(key, false);
+        redis::cmd("HGETALL")
+            .arg(key)
+            .query_async(ctx.get_connection())
+            .await
+    }
+
+    async fn write_map_async(
+        key: &str,
+        map: HashMap<String, String>,
+        ctx: &mut AsyncContextManager,
+    ) -> Result<(), RedisError> {
+        check_external_edit(key, ctx).await?;
+        metrics::record_group_access(key, true);
+        redis::cmd("HSET")
+            .arg(key)
+            .arg(map)
+            .query_async::<_, ()>(ctx.get_connection())
+            .await?;
+        record_checksum(key, ctx).await
+    }
+}
+
+impl ServerGroup {
+    pub async fn from_str_async(
+        group: &str,
+        ctx: &mut AsyncContextManager,
+    ) -> Result<Self, ServerGroupParsingError> {
+        //! `from_str` on the async connection, following the alias of a renamed group.
+        match Self::get_async(group, ctx).await {
+            Ok(found) => Ok(found),
+            Err(err) => match Self::resolve_alias_async(group, ctx).await {
+                Some(renamed) => Self::get_async(&renamed, ctx).await,
+                None => Err(err),
+            },
+        }
+    }
+
+    pub async fn resolve_alias_async(group: &str, ctx: &mut AsyncContextManager) -> Option<String> {
+        redis::cmd("HGET")
+            .arg(ALIASES_KEY)
+            .arg(group)
+            .query_async::<_, Option<String>>(ctx.get_connection())
+            .await
+            .ok()
+            .flatten()
+    }
+
+    pub async fn create_async(&mut self, ctx: &mut AsyncContextManager) -> Result<(), RedisError> {
+        //! `create` on the async connection: validated against the config, moved to a free
+        //! port section if its own is taken, then saved. Groups already cached are only
+        //! re-indexed.
+        if Self::exists_async(&self.prefix, ctx).await {
+            return redis::cmd("SADD")
+                .arg(ctx.get_keys().group_index_key())
+                .arg(&self.prefix)
+                .query_async(ctx.get_connection())
+                .await;
+        }
+        self.validate(ctx.get_config())?;
+        self.eliminate_port_collisions_async(ctx).await?;
+        self.save_async(ctx).await?;
+        Ok(())
+    }
+
+    async fn eliminate_port_collisions_async(
+        &mut self,
+        ctx: &mut AsyncContextManager,
+    ) -> Result<(), ServerGroupParsingError> {
+        //! `eliminate_port_collisions` on the async connection.
+        let others: Vec<ServerGroup> = Self::get_all_async(ctx)
+            .await
+            .into_result()?
+            .into_iter()
+            .filter(|sg| sg.name != self.name)
+            .collect();
+        let port_sections: Vec<u16> = others.iter().map(|sg| sg.port_section).collect();
+        if !GameOptions::check_port_section_conflicts(self.port_section, &port_sections) {
+            return Ok(());
+        }
+        let conflicts: Vec<String> = others
+            .into_iter()
+            .filter(|sg| {
+                GameOptions::get_if_port_section_conflict(self.port_section, sg.port_section)
+            })
+            .map(|sg| sg.name)
+            .collect();
+        let old_port_section = self.port_section;
+        let info = ctx.get_config().ports.clone();
+        self.port_section = allocate_port_section(&info, &port_sections, ctx.get_random())
+            .map_err(|err| ServerGroupParsingError::new(err.to_string()))?;
+        let reassignment = PortReassignment::new(
+            old_port_section,
+            self.port_section,
+            format!("conflicted with {:?}", conflicts),
+        );
+        let recorded = match reassignment.get_record_cmds(&self.prefix) {
+            Ok(cmds) => super::query_all(&cmds, ctx).await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = recorded {
+            log::warn!(
+                "Could not record port reassignment of servergroups.{}: {:?}",
+                self.prefix,
+                err
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,32 @@
+pub mod context;
+pub mod entity;
+mod groups;
+mod statuses;
+
+use redis::RedisError;
+
+use crate::events::Event;
+
+use context::AsyncContextManager;
+
+async fn query_all(cmds: &[redis::Cmd], ctx: &mut AsyncContextManager) -> Result<(), RedisError> {
+    //! Runs `cmds` in order, stopping at the first failure.
+    for cmd in cmds {
+        cmd.query_async::<_, ()>(ctx.get_connection()).await?;
+    }
+    Ok(())
+}
+
+impl Event {
+    pub async fn emit_async(self, ctx: &mut AsyncContextManager) {
+        //! `emit` on the async connection.
+        log::info!(target: "event", "{}", self);
+        let recorded = match self.get_record_cmds() {
+            Ok(cmds) => query_all(&cmds, ctx).await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = recorded {
+            log::warn!(target: "event", "could not be stored: {:?}", err);
+        }
+    }
+}
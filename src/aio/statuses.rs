@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+use redis::RedisError;
+
+use crate::{
+    region::Region,
+    server::{
+        minecraft::{parse_raw_status, status_to_json, MinecraftServer, MinecraftServerError},
+        server_group::ServerGroup,
+    },
+    store::partial::PartialResult,
+};
+
+use super::{context::AsyncContextManager, entity::AsyncRedisEntity};
+
+impl AsyncRedisEntity for MinecraftServer {
+    async fn read_map_async(
+        key: &str,
+        ctx: &mut AsyncContextManager,
+    ) -> Result<HashMap<String, String>, RedisError> {
+        //! Statuses are stored as a single JSON string rather than a hash.
+        let raw: Option<String> = redis::cmd("GET")
+            .arg(key)
+            .query_async(ctx.get_connection())
+            .await?;
+        match raw {
+            Some(raw) => parse_raw_status(&raw, key, ctx.get_keys()),
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    async fn write_map_async(
+        key: &str,
+        map: HashMap<String, String>,
+        ctx: &mut AsyncContextManager,
+    ) -> Result<(), RedisError> {
+        redis::cmd("SET")
+            .arg(key)
+            .arg(status_to_json(map))
+            .query_async(ctx.get_connection())
+            .await
+    }
+}
+
+impl MinecraftServer {
+    pub async fn from_server_group_async(
+        server_group: &ServerGroup,
+        ctx: &mut AsyncContextManager,
+    ) -> PartialResult<Self, MinecraftServerError> {
+        let pattern = ctx.get_keys().status_pattern(
+            Some(&server_group.region.to_string()),
+            Some(&server_group.prefix),
+        );
+        Self::get_matching_async(pattern, ctx).await
+    }
+
+    pub async fn get_status_async(
+        server_name: &String,
+        region: &Region,
+        ctx: &mut AsyncContextManager,
+    ) -> Result<Self, MinecraftServerError> {
+        Self::get_async(&Self::get_status_id(server_name, region), ctx).await
+    }
+
+    pub async fn delete_status_async(
+        server_name: &String,
+        region: &Region,
+        ctx: &mut AsyncContextManager,
+    ) -> Result<(), MinecraftServerError> {
+        //! Removes the status entry of a server instance.
+        Self::delete_async(&Self::get_status_id(server_name, region), ctx).await
+    }
+}
@@ -0,0 +1,301 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+    fs,
+    path::Path,
+    str::FromStr,
+};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{
+    context_manager::ContextManager,
+    events::{Event, EventKind},
+    game::Game,
+    region::Region,
+    server::{
+        dedicated::labels::{self, LabelTarget, Labels},
+        diff,
+        ensure::EnsureOutcome,
+        presets::{Preset, SizeTier},
+        server_group::ServerGroup,
+    },
+    store::entity::RedisEntity,
+};
+
+#[derive(Error, Debug)]
+pub enum ApplyError {
+    #[error("Apply Error: Could not read `{0}`")]
+    ReadError(String),
+    #[error("Apply Error: Invalid desired state: `{0}`")]
+    ValidationError(String),
+    #[error("Apply Error: Redis Error: `{0}`")]
+    RedisError(String),
+}
+
+/// Desired state of the network, read from a TOML file:
+///
+/// ```toml
+/// [[groups]]
+/// name = "CW4"
+/// game = "CakeWars4"     # or: preset = "Arcade", region = "EU", tier = "L"
+/// total_servers = 4
+/// joinable_servers = 2
+/// fields = { maxPlayers = "16" } # raw hash fields, applied last
+///
+/// [nodes.node1]
+/// labels = { tier = "high-mem" }
+/// ```
+///
+/// or the same as YAML, from a `.yaml`/`.yml` file:
+///
+/// ```yaml
+/// groups:
+///   - name: CW4
+///     game: CakeWars4
+///     total_servers: 4
+///     joinable_servers: 2
+///     fields: { maxPlayers: "16" }
+/// nodes:
+///   node1:
+///     labels: { tier: high-mem }
+/// ```
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct NetworkSpec {
+    #[serde(default)]
+    pub groups: Vec<GroupSpec>,
+    #[serde(default)]
+    pub nodes: BTreeMap<String, NodeSpec>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct GroupSpec {
+    pub name: String,
+    #[serde(default)]
+    pub game: Option<String>,
+    #[serde(default)]
+    pub preset: Option<String>,
+    #[serde(default)]
+    pub region: Option<String>,
+    #[serde(default)]
+    pub tier: Option<String>,
+    pub total_servers: u8,
+    pub joinable_servers: u8,
+    #[serde(default)]
+    pub fields: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct NodeSpec {
+    #[serde(default)]
+    pub labels: Labels,
+}
+
+/// What applying (or planning) a spec did to each group and node.
+#[derive(Clone, Debug, Default)]
+pub struct ApplyReport {
+    pub dry_run: bool,
+    pub groups: Vec<(String, EnsureOutcome)>,
+    pub nodes: Vec<(String, EnsureOutcome)>,
+    pub pruned: Vec<String>,
+    pub failed: Vec<(String, String)>, // (group or node, why)
+}
+
+impl Display for ApplyReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let prefix = if self.dry_run { "(dry run) " } else { "" };
+        for (name, outcome) in self.groups.iter() {
+            writeln!(f, "{}servergroups.{} {}", prefix, name, outcome)?;
+        }
+        for (name, outcome) in self.nodes.iter() {
+            writeln!(f, "{}node {} labels {}", prefix, name, outcome)?;
+        }
+        for name in self.pruned.iter() {
+            writeln!(f, "{}servergroups.{} pruned", prefix, name)?;
+        }
+        for (name, err) in self.failed.iter() {
+            writeln!(f, "{} failed: {}", name, err)?;
+        }
+        Ok(())
+    }
+}
+
+impl ApplyReport {
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+impl NetworkSpec {
+    pub fn load(path: &str) -> Result<Self, ApplyError> {
+        //! Reads a spec, as YAML if the file ends in `.yaml` or `.yml` and as TOML otherwise.
+        let contents = fs::read_to_string(path)
+            .map_err(|err| ApplyError::ReadError(format!("{}: {}", path, err)))?;
+        let spec = match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") => serde_yaml::from_str(&contents).map_err(|err| err.to_string()),
+            _ => toml::from_str(&contents).map_err(|err| err.to_string()),
+        };
+        spec.map_err(|err| ApplyError::ReadError(format!("{}: {}", path, err)))
+    }
+
+    pub fn get_groups(&self, ctx: &mut ContextManager) -> Result<Vec<ServerGroup>, ApplyError> {
+        //! Builds and validates every desired group. Nothing is applied unless all of them are valid.
+        let mut names = HashSet::new();
+        let mut groups = Vec::new();
+        for spec in self.groups.iter() {
+            if !names.insert(spec.name.clone()) {
+                return Err(ApplyError::ValidationError(format!(
+                    "{} is declared twice",
+                    spec.name
+                )));
+            }
+            let group = spec
+                .to_server_group(ctx)
+                .map_err(|err| ApplyError::ValidationError(format!("{}: {}", spec.name, err)))?;
+            group
+                .validate(ctx.get_config())
+                .map_err(|err| ApplyError::ValidationError(format!("{}: {}", spec.name, err)))?;
+            groups.push(group);
+        }
+        for name in self.nodes.keys() {
+            if ctx.get_dedicated_servers().get_server(name).is_none() {
+                return Err(ApplyError::ValidationError(format!(
+                    "node {} is not configured",
+                    name
+                )));
+            }
+        }
+        Ok(groups)
+    }
+}
+
+impl GroupSpec {
+    fn to_server_group(&self, ctx: &mut ContextManager) -> Result<ServerGroup, String> {
+        if self.joinable_servers > self.total_servers {
+            return Err(format!(
+                "joinable_servers ({}) exceeds total_servers ({})",
+                self.joinable_servers, self.total_servers
+            ));
+        }
+        let mut group = match (&self.game, &self.preset) {
+            (Some(game), None) => {
+                ServerGroup::from_game(Game::from_str(game, ctx).map_err(|err| err.to_string())?)
+            }
+            (None, Some(preset)) => {
+                let preset =
+                    Preset::from_str(preset).map_err(|_| format!("unknown preset {:?}", preset))?;
+                let region = match &self.region {
+                    Some(region) => {
+                        Region::try_from(region.clone()).map_err(|err| err.to_string())?
+                    }
+                    None => Region::default(),
+                };
+                let tier = match &self.tier {
+                    Some(tier) => {
+                        SizeTier::from_str(tier).map_err(|_| format!("unknown tier {:?}", tier))?
+                    }
+                    None => SizeTier::default(),
+                };
+                preset.to_server_group(&self.name, region, tier)
+            }
+            _ => return Err("exactly one of game or preset is required".into()),
+        };
+        group.name = self.name.clone();
+        group.prefix = self.name.clone();
+        group.total_servers = self.total_servers;
+        group.joinable_servers = self.joinable_servers;
+        if !self.fields.contains_key("portSection") && ServerGroup::exists(&group.prefix, ctx) {
+            // picked when the group was created, only changed when `fields` asks for it
+            group.port_section = ServerGroup::get(&group.prefix, ctx)
+                .map_err(|err| err.to_string())?
+                .port_section;
+        }
+        if self.fields.is_empty() {
+            return Ok(group);
+        }
+        let mut map = group.to_hashmap();
+        map.extend(self.fields.clone());
+        ServerGroup::from_hashmap(map).map_err(|err| err.to_string())
+    }
+}
+
+pub fn apply(
+    spec: &NetworkSpec,
+    prune: bool,
+    force: bool,
+    allow_protected: bool,
+    dry_run: bool,
+    ctx: &mut ContextManager,
+) -> Result<ApplyReport, ApplyError> {
+    //! Diffs the desired state against redis and makes redis match it: groups are ensured
+    //! (changes to protected fields fail unless `allow_protected`), node labels replaced,
+    //! and with `prune` groups missing from the spec are deleted (unless they still have
+    //! live instances and `force` isn't given).
+    let groups = spec.get_groups(ctx)?;
+    let mut report = ApplyReport {
+        dry_run,
+        ..Default::default()
+    };
+    for group in groups.iter() {
+        let outcome = match dry_run {
+            true => ServerGroup::plan_ensure(group, allow_protected, ctx),
+            false => ServerGroup::ensure(group, allow_protected, ctx),
+        };
+        match outcome {
+            Ok(outcome) => report.groups.push((group.prefix.clone(), outcome)),
+            Err(err) => report.failed.push((group.prefix.clone(), err.to_string())),
+        }
+    }
+    for (node, node_spec) in spec.nodes.iter() {
+        let current = labels::get_labels(LabelTarget::Node, node, ctx)
+            .map_err(|err| ApplyError::RedisError(err.to_string()))?;
+        if current == node_spec.labels {
+            report.nodes.push((node.clone(), EnsureOutcome::Unchanged));
+            continue;
+        }
+        let changed = diff::diff_maps(&current, &node_spec.labels);
+        if !dry_run {
+            if let Err(err) = labels::set_labels(LabelTarget::Node, node, &node_spec.labels, ctx) {
+                report.failed.push((node.clone(), err.to_string()));
+                continue;
+            }
+        }
+        report
+            .nodes
+            .push((node.clone(), EnsureOutcome::Updated(changed)));
+    }
+    if prune {
+        let desired: HashSet<&String> = groups.iter().map(|group| &group.prefix).collect();
+        let cached = ServerGroup::get_all(ctx);
+        for (key, err) in cached.failed.iter() {
+            report.failed.push((
+                key.clone(),
+                format!("could not be read, not pruned: {}", err),
+            ));
+        }
+        for group in cached
+            .ok
+            .iter()
+            .filter(|group| !desired.contains(&group.prefix))
+        {
+            if dry_run {
+                report.pruned.push(group.prefix.clone());
+                continue;
+            }
+            match group.safe_delete(force, ctx) {
+                Ok(()) => {
+                    Event::new(
+                        EventKind::GroupDeleted,
+                        &group.prefix,
+                        "deleted (pruned by apply)".into(),
+                    )
+                    .emit(ctx);
+                    report.pruned.push(group.prefix.clone());
+                }
+                Err(err) => report.failed.push((group.prefix.clone(), err.to_string())),
+            }
+        }
+    }
+    Ok(report)
+}
@@ -0,0 +1,164 @@
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    panic::{self, AssertUnwindSafe},
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::context_manager::ContextManager;
+
+/// Threads `run` uses unless told otherwise.
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+/// An action picked at runtime, e.g. from a command line.
+pub type BatchAction<T> = Box<dyn Fn(&T, &mut ContextManager) -> Result<String, String> + Sync>;
+
+/// What an action did to one target.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BatchOutcome<T> {
+    pub target: T,
+    pub result: Result<String, String>, // what was done, or why it failed
+    pub took: Duration,
+}
+
+/// Outcomes of one batch, in the order the targets were given.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BatchReport<T> {
+    pub outcomes: Vec<BatchOutcome<T>>,
+}
+
+impl<T> Display for BatchReport<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} succeeded, {} failed",
+            self.outcomes.len() - self.get_failed_count(),
+            self.get_failed_count()
+        )
+    }
+}
+
+impl<T> BatchReport<T> {
+    pub fn get_failed_count(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|outcome| outcome.result.is_err())
+            .count()
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.get_failed_count() == 0
+    }
+}
+
+pub fn run<T: Send>(
+    targets: Vec<T>,
+    concurrency: usize,
+    action: impl Fn(&T, &mut ContextManager) -> Result<String, String> + Sync,
+    ctx: &mut ContextManager,
+) -> BatchReport<T> {
+    //! Runs `action` on every target using at most `concurrency` threads, each with its own
+    //! connection. Offline (on a snapshot), or with a concurrency of 1, targets are handled
+    //! one after another on `ctx`. A panicking action only fails its own target.
+    run_pool(targets, concurrency, None, action, ctx)
+}
+
+pub fn run_with_timeout<T: Send>(
+    targets: Vec<T>,
+    concurrency: usize,
+    timeout: Duration,
+    action: impl Fn(&T, &mut ContextManager) -> Result<String, String> + Sync,
+    ctx: &mut ContextManager,
+) -> BatchReport<T> {
+    //! `run`, with every redis reply given up on after `timeout` (see
+    //! `Connection::set_reply_timeout`). A target whose action took that long fails as
+    //! timed out, and the connection it ran on is reopened.
+    run_pool(targets, concurrency, Some(timeout), action, ctx)
+}
+
+fn run_pool<T: Send>(
+    targets: Vec<T>,
+    concurrency: usize,
+    timeout: Option<Duration>,
+    action: impl Fn(&T, &mut ContextManager) -> Result<String, String> + Sync,
+    ctx: &mut ContextManager,
+) -> BatchReport<T> {
+    let run_one = |target: T, ctx: &mut ContextManager| {
+        let started = Instant::now();
+        let mut result = panic::catch_unwind(AssertUnwindSafe(|| action(&target, ctx)))
+            .unwrap_or_else(|panic| Err(format!("panicked: {}", get_panic_message(&panic))));
+        let took = started.elapsed();
+        if let Some(timeout) = timeout.filter(|timeout| took >= *timeout) {
+            result = Err(format!("timed out after {}ms", timeout.as_millis()));
+            // the late reply would be read as the next command's
+            if let Err(err) = ctx.reconnect() {
+                log::warn!(
+                    "Connection could not be reopened after a timeout: {:?}",
+                    err
+                );
+            }
+            let _ = ctx.get_connection().set_reply_timeout(Some(timeout));
+        }
+        BatchOutcome {
+            target,
+            result,
+            took,
+        }
+    };
+    if concurrency <= 1 || targets.len() <= 1 || ctx.get_connection().is_offline() {
+        if timeout.is_none() {
+            return BatchReport {
+                outcomes: targets
+                    .into_iter()
+                    .map(|target| run_one(target, ctx))
+                    .collect(),
+            };
+        }
+        let _ = ctx.get_connection().set_reply_timeout(timeout);
+        let outcomes = targets
+            .into_iter()
+            .map(|target| run_one(target, ctx))
+            .collect();
+        let _ = ctx.get_connection().set_reply_timeout(None);
+        return BatchReport { outcomes };
+    }
+    let threads = concurrency.min(targets.len());
+    let queue: Mutex<VecDeque<(usize, T)>> = Mutex::new(targets.into_iter().enumerate().collect());
+    let done: Mutex<Vec<(usize, BatchOutcome<T>)>> = Mutex::new(Vec::new());
+    let build = ctx.builder();
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                let mut ctx = build();
+                if timeout.is_some() {
+                    let _ = ctx.get_connection().set_reply_timeout(timeout);
+                }
+                while let Some((i, target)) =
+                    queue.lock().ok().and_then(|mut queue| queue.pop_front())
+                {
+                    let outcome = run_one(target, &mut ctx);
+                    if let Ok(mut done) = done.lock() {
+                        done.push((i, outcome));
+                    }
+                }
+            });
+        }
+    });
+    let mut done = done
+        .into_inner()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    done.sort_by_key(|(i, _)| *i);
+    BatchReport {
+        outcomes: done.into_iter().map(|(_, outcome)| outcome).collect(),
+    }
+}
+
+pub fn get_panic_message(panic: &Box<dyn std::any::Any + Send>) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or("unknown panic".into())
+}
@@ -0,0 +1,621 @@
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+use super::{completion::BIN, Args, USAGE};
+
+/// Top-level shortcuts and the commands they stand for.
+const SHORTCUTS: [(&str, &[&str]); 4] = [
+    ("list-groups", &["group", "list"]),
+    ("create-group", &["group", "create"]),
+    ("delete-group", &["group", "delete"]),
+    ("status", &["group", "status"]),
+];
+
+fn value(name: &'static str) -> Arg {
+    //! `--name <value>`
+    Arg::new(name).long(name).value_name(name)
+}
+
+fn switch(name: &'static str) -> Arg {
+    //! `--name`, taking no value.
+    Arg::new(name).long(name).action(ArgAction::SetTrue)
+}
+
+fn positional(name: &'static str) -> Arg {
+    Arg::new(name).required(true)
+}
+
+fn optional(name: &'static str) -> Arg {
+    Arg::new(name)
+}
+
+fn many(name: &'static str) -> Arg {
+    //! One or more values, e.g. `<map>...`.
+    Arg::new(name).required(true).num_args(1..)
+}
+
+fn filters() -> [Arg; 6] {
+    //! Selectors for instances, see `Filters` and `select_instances`.
+    [
+        value("group"),
+        value("region"),
+        value("node"),
+        value("state"),
+        value("label"),
+        value("where"),
+    ]
+}
+
+fn group() -> Command {
+    Command::new("group")
+        .about("Create, list, change and delete server groups")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("create")
+                .about("Create a group from a game type's defaults (or a preset)")
+                .args([
+                    positional("name"),
+                    value("preset"),
+                    value("region"),
+                    value("tier"),
+                ]),
+        )
+        .subcommand(
+            Command::new("list")
+                .about("List groups with their desired and live instance counts")
+                .args([value("region"), value("group"), value("where")]),
+        )
+        .subcommand(
+            Command::new("scale")
+                .about("Set a group's desired instance counts")
+                .args([positional("name"), positional("total"), value("joinable")]),
+        )
+        .subcommand(Command::new("presets").about("List available presets"))
+        .subcommand(
+            Command::new("set")
+                .about("Set raw group hash fields (e.g. maxPlayers=24)")
+                .args([
+                    positional("name"),
+                    many("assignments"),
+                    switch("force"),
+                    switch("allow-protected"),
+                ]),
+        )
+        .subcommand(
+            Command::new("schema").about("Print every group field's type, default and constraints"),
+        )
+        .subcommand(
+            Command::new("rename")
+                .about("Rename a group, optionally relaunching its instances")
+                .args([
+                    positional("old"),
+                    positional("new"),
+                    switch("relaunch"),
+                    switch("force"),
+                ]),
+        )
+        .subcommand(
+            Command::new("delete")
+                .about("Delete a group, refusing while it has live instances unless forced")
+                .args([positional("name"), switch("force")]),
+        )
+        .subcommand(
+            Command::new("ports")
+                .about("Show a group's port section history")
+                .arg(positional("name")),
+        )
+        .subcommand(
+            Command::new("status")
+                .about("Show a group's instances, live statuses and recent events")
+                .arg(positional("name")),
+        )
+        .subcommand(
+            Command::new("plan")
+                .about("Show what the scaler would do for a group right now")
+                .arg(positional("name")),
+        )
+}
+
+fn event() -> Command {
+    Command::new("event")
+        .about("Run one-off event servers")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("create")
+                .about("Create a whitelisted event group, archived after the duration")
+                .args([
+                    positional("game"),
+                    positional("map"),
+                    value("host-rank").required(true),
+                    value("duration").required(true),
+                ]),
+        )
+        .subcommand(
+            Command::new("list")
+                .about("Show running (or archived) event servers")
+                .args([switch("archived"), value("group")]),
+        )
+        .subcommand(
+            Command::new("end")
+                .about("Archive an event server now")
+                .arg(positional("group")),
+        )
+}
+
+fn maps() -> Command {
+    Command::new("maps")
+        .about("List known maps (or manage catalogs and pools)")
+        .args_conflicts_with_subcommands(true)
+        .arg(optional("game"))
+        .subcommand(
+            Command::new("add")
+                .about("Add maps to a game's catalog set")
+                .args([positional("game"), many("maps")]),
+        )
+        .subcommand(
+            Command::new("pool")
+                .about("Write and show a group's map pool")
+                .arg(positional("group")),
+        )
+        .subcommand(
+            Command::new("enable")
+                .about("Turn a map on for a group")
+                .args([positional("group"), positional("map")]),
+        )
+        .subcommand(
+            Command::new("disable")
+                .about("Turn a map off for a group")
+                .args([positional("group"), positional("map")]),
+        )
+}
+
+fn foreach() -> Command {
+    //! Selectors and --concurrency are accepted before or after the action.
+    Command::new("foreach")
+        .about("Run an action on every selected instance, a few at a time")
+        .subcommand_required(true)
+        .args(filters().map(|arg| arg.global(true)))
+        .arg(value("concurrency").global(true))
+        .subcommand(Command::new("restart").about("Restart each instance"))
+        .subcommand(
+            Command::new("broadcast")
+                .about("Send a message to each instance")
+                .args([positional("message"), value("style")]),
+        )
+        .subcommand(
+            Command::new("join")
+                .about("Set who may join each instance")
+                .arg(positional("status")),
+        )
+}
+
+fn queue() -> Command {
+    Command::new("queue")
+        .about("Show queue lengths per group (or who waits for one group)")
+        .args_conflicts_with_subcommands(true)
+        .arg(optional("group"))
+        .subcommand(
+            Command::new("push")
+                .about("Add a player to a full group's queue")
+                .args([positional("group"), positional("player")]),
+        )
+        .subcommand(
+            Command::new("remove")
+                .about("Take a player out of a group's queue")
+                .args([positional("group"), positional("player")]),
+        )
+        .subcommand(
+            Command::new("pop")
+                .about("Take the next players off a group's queue")
+                .args([positional("group"), value("count")]),
+        )
+}
+
+fn restart() -> Command {
+    Command::new("restart")
+        .about("Schedule rolling restarts")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("schedule")
+                .about("Warn players, then drain and restart a group's instances")
+                .args([
+                    positional("group"),
+                    value("in").conflicts_with("at"),
+                    value("at"),
+                    value("warn"),
+                    value("message"),
+                ]),
+        )
+        .subcommand(
+            Command::new("cancel")
+                .about("Cancel a group's scheduled restart")
+                .arg(positional("group")),
+        )
+        .subcommand(Command::new("list").about("Show scheduled restarts"))
+}
+
+fn with_subcommands(name: &'static str, about: &'static str, subcommands: Vec<Command>) -> Command {
+    //! A command that does something on its own and more with a subcommand.
+    Command::new(name).about(about).subcommands(subcommands)
+}
+
+pub fn build() -> Command {
+    //! Every command the binary (and the shell) accepts, with its arguments.
+    //! Top-level help is the usage text; `<command> --help` is generated by clap.
+    Command::new(BIN)
+        .no_binary_name(true)
+        .bin_name(BIN)
+        .subcommand_required(true)
+        .disable_help_subcommand(true)
+        .override_help(USAGE)
+        .args([
+            value("sort").global(true),
+            value("snapshot").global(true),
+            switch("accept-external-changes").global(true),
+            switch("read-only").global(true),
+        ])
+        .subcommand(group())
+        .subcommand(
+            Command::new("list-groups")
+                .about("Same as `group list`")
+                .args([value("region"), value("group"), value("where")]),
+        )
+        .subcommand(
+            Command::new("create-group")
+                .about("Same as `group create`")
+                .args([positional("name"), value("preset"), value("region"), value("tier")]),
+        )
+        .subcommand(
+            Command::new("delete-group")
+                .about("Same as `group delete`")
+                .args([positional("name"), switch("force")]),
+        )
+        .subcommand(
+            Command::new("status")
+                .about("Same as `group status`")
+                .arg(positional("name")),
+        )
+        .subcommand(event())
+        .subcommand(maps())
+        .subcommand(
+            Command::new("apply")
+                .about("Make redis match a desired-state file")
+                .args([
+                    value("file").short('f').required(true),
+                    switch("prune"),
+                    switch("force").requires("prune"),
+                    switch("dry-run"),
+                    switch("allow-protected"),
+                ]),
+        )
+        .subcommand(
+            Command::new("instances")
+                .about("List instances on each node with their state and labels")
+                .args(filters())
+                .subcommand(
+                    Command::new("label")
+                        .about("Set labels on an instance (`key-` removes one)")
+                        .args([positional("instance"), many("changes")]),
+                ),
+        )
+        .subcommand(foreach())
+        .subcommand(
+            Command::new("nodes")
+                .about("List nodes with their instance counts, free resources and telemetry")
+                .args([value("region"), value("node")])
+                .subcommand(
+                    Command::new("sample")
+                        .about("Sample each instance's process on nodes without telemetry")
+                        .arg(value("region")),
+                ),
+        )
+        .subcommand(
+            Command::new("broadcast")
+                .about("Send a message to a group's servers (or all of them)")
+                .args([
+                    positional("message"),
+                    value("group"),
+                    value("style"),
+                    value("countdown"),
+                ]),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Report malformed groups and statuses, optionally repairing them")
+                .args([switch("fix"), switch("recreate").requires("fix")]),
+        )
+        .subcommand(Command::new("recover").about("Adopt running servers into node bookkeeping"))
+        .subcommand(
+            Command::new("events")
+                .about("Show recent events, newest first")
+                .arg(value("count")),
+        )
+        .subcommand(
+            Command::new("undo")
+                .about("Revert the newest (or n-th) group change from the event log")
+                .args_conflicts_with_subcommands(true)
+                .args([optional("n"), switch("force")])
+                .subcommand(
+                    Command::new("list")
+                        .about("List recent changes that can be undone")
+                        .arg(value("group")),
+                ),
+        )
+        .subcommand(
+            Command::new("kill")
+                .about("Kill an instance (e.g. MIN-3), refusing while players are online unless forced")
+                .args([positional("instance"), switch("force")]),
+        )
+        .subcommand(
+            Command::new("smoke")
+                .about("Run the launch smoke test against a live instance")
+                .args([positional("instance"), value("region")]),
+        )
+        .subcommand(
+            Command::new("rcon")
+                .about("Run a console command on a live instance over rcon")
+                .args([positional("instance"), many("command"), value("region")]),
+        )
+        .subcommand(with_subcommands(
+            "crashloops",
+            "Show groups with failed launches",
+            vec![Command::new("clear")
+                .about("Resume a group's launches")
+                .arg(positional("group"))],
+        ))
+        .subcommand(Command::new("services").about("Check external services and show reward-safe groups"))
+        .subcommand(with_subcommands(
+            "journal",
+            "Show operations interrupted by a crash",
+            vec![Command::new("replay").about("Recover interrupted operations")],
+        ))
+        .subcommand(
+            Command::new("monitor")
+                .about("Run the monitor loop until SIGINT/SIGTERM")
+                .args([value("ticks"), switch("ignore-topology")]),
+        )
+        .subcommand(with_subcommands(
+            "handshake",
+            "Compare plugin schema versions",
+            vec![Command::new("publish").about("Advertise this manager's schema versions")],
+        ))
+        .subcommand(with_subcommands(
+            "alerts",
+            "Show firing alerts",
+            vec![Command::new("test")
+                .about("Evaluate every rule once now")
+                .arg(value("state"))],
+        ))
+        .subcommand(
+            Command::new("summary")
+                .about("Show the published network.summary (or build it now)")
+                .arg(switch("live")),
+        )
+        .subcommand(queue())
+        .subcommand(Command::new("managers").about("List manager instances with a live heartbeat"))
+        .subcommand(Command::new("acl").about("Print ACL SETUSER rules for the configured redis users"))
+        .subcommand(Command::new("regions").about("Show the health of every manager's region workers"))
+        .subcommand(
+            Command::new("forecast")
+                .about("Show predicted demand per region and hour against node capacity")
+                .arg(value("region")),
+        )
+        .subcommand(
+            with_subcommands(
+                "rightsize",
+                "Suggest moving groups to another resource tier",
+                vec![
+                    Command::new("schedule")
+                        .about("Apply a group's suggested tier at its next rolling restart")
+                        .arg(positional("group")),
+                    Command::new("cancel")
+                        .about("Drop a group's queued tier change")
+                        .arg(positional("group")),
+                ],
+            )
+            .arg(value("group")),
+        )
+        .subcommand(with_subcommands(
+            "strays",
+            "List servers running untracked by any node",
+            vec![
+                Command::new("adopt")
+                    .about("Track a stray on its node, reserving resources")
+                    .arg(positional("server")),
+                Command::new("ignore")
+                    .about("Flag a stray as unmanaged")
+                    .arg(positional("server")),
+            ],
+        ))
+        .subcommand(with_subcommands(
+            "orphans",
+            "List server processes without a tracked instance or status",
+            vec![Command::new("sweep")
+                .about("Look for orphans now, optionally killing them")
+                .args([value("region"), switch("kill")])],
+        ))
+        .subcommand(
+            Command::new("prewarm")
+                .about("Stage world zips and jars on the nodes the next launches would use")
+                .args([value("region"), value("group"), switch("dry-run")]),
+        )
+        .subcommand(
+            Command::new("launch")
+                .about("Start the instances the scaler is missing (of one group, if given)")
+                .args([
+                    optional("group"),
+                    value("region"),
+                    value("group-flag").long("group").conflicts_with("group"),
+                    switch("dry-run"),
+                    value("concurrency"),
+                    value("per-node"),
+                ]),
+        )
+        .subcommand(
+            Command::new("recordings")
+                .about("List the reconciles region workers recorded")
+                .arg(value("region")),
+        )
+        .subcommand(
+            Command::new("replay")
+                .about("Re-run a recorded reconcile's decisions offline")
+                .arg(positional("file")),
+        )
+        .subcommand(
+            Command::new("stats")
+                .about("Show redis and booster statistics")
+                .subcommand_required(true)
+                .subcommand(Command::new("redis").about("Show per-command redis latency, payloads and errors"))
+                .subcommand(
+                    Command::new("boosters")
+                        .about("Show players per booster group")
+                        .arg(switch("live")),
+                ),
+        )
+        .subcommand(
+            Command::new("jars")
+                .about("Manage server jars")
+                .subcommand_required(true)
+                .subcommand(Command::new("sync").about("Fetch configured jars and copy them to every node")),
+        )
+        .subcommand(restart())
+        .subcommand(
+            Command::new("rotation")
+                .about("Preview or apply scheduled game rotations")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("preview")
+                        .about("Show which rotation slot each group has at a time")
+                        .arg(value("at")),
+                )
+                .subcommand(
+                    Command::new("apply")
+                        .about("Switch rotating groups to their scheduled games")
+                        .args([switch("dry-run"), value("in")]),
+                ),
+        )
+        .subcommand(
+            Command::new("backup")
+                .about("Save all redis data to a JSON snapshot")
+                .arg(positional("file")),
+        )
+        .subcommand(
+            Command::new("restore")
+                .about("Write a JSON snapshot back to redis in pipelined chunks")
+                .args([positional("file"), value("chunk")]),
+        )
+        .subcommand(
+            Command::new("mirror")
+                .about("Check a redis migration")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("verify")
+                        .about("Compare redis with redis_conn.mirror key by key")
+                        .arg(value("match")),
+                ),
+        )
+        .subcommand(Command::new("shell").about("Run commands interactively on one connection"))
+        .subcommand(
+            Command::new("completions")
+                .about("Print a completion script for a shell")
+                .arg(positional("shell").value_parser(["bash", "zsh", "fish"])),
+        )
+        .subcommand(
+            Command::new("__complete")
+                .hide(true)
+                .arg(positional("kind")),
+        )
+        .subcommand(
+            Command::new("mock")
+                .about("Fill redis with synthetic data (or clear it)")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("run")
+                        .about("Fill redis with synthetic groups and live statuses")
+                        .args([
+                            value("groups").required(true),
+                            value("servers").required(true),
+                            value("interval"),
+                            value("rounds"),
+                        ]),
+                )
+                .subcommand(Command::new("clear").about("Remove synthetic groups and statuses")),
+        )
+}
+
+pub(super) fn get_switches() -> Vec<String> {
+    //! Every flag that never takes a value, e.g. for completion scripts to skip over.
+    fn walk(command: &Command, switches: &mut Vec<String>) {
+        for arg in command.get_arguments() {
+            if let (ArgAction::SetTrue, Some(long)) = (arg.get_action(), arg.get_long()) {
+                switches.push(long.to_string());
+            }
+        }
+        for subcommand in command.get_subcommands() {
+            walk(subcommand, switches);
+        }
+    }
+    let mut switches = vec!["help".to_string()];
+    walk(&build(), &mut switches);
+    switches.sort();
+    switches.dedup();
+    switches
+}
+
+fn collect(command: &Command, matches: &ArgMatches, args: &mut Args) {
+    //! Appends `command`'s positionals in order and records its flags, then descends into
+    //! the subcommand that was given.
+    for arg in command.get_arguments() {
+        let id = arg.get_id().as_str();
+        if arg.is_positional() {
+            if let Some(values) = matches.get_many::<String>(id) {
+                args.positional.extend(values.cloned());
+            }
+            continue;
+        }
+        let name = arg.get_long().unwrap_or(id).to_string();
+        match arg.get_action() {
+            ArgAction::SetTrue if matches.get_flag(id) => {
+                args.flags.insert(name, None);
+            }
+            ArgAction::SetTrue => (),
+            _ => {
+                if let Some(value) = matches.get_one::<String>(id) {
+                    args.flags.insert(name, Some(value.clone()));
+                }
+            }
+        }
+    }
+    if let Some((name, matches)) = matches.subcommand() {
+        args.positional.push(name.to_string());
+        if let Some(subcommand) = command.find_subcommand(name) {
+            collect(subcommand, matches, args);
+        }
+    }
+}
+
+fn expand_shortcuts(mut args: Args) -> Args {
+    //! Rewrites the top-level shortcuts onto the commands they stand for, and a group given
+    //! to `launch` onto its --group.
+    if let Some((_, expanded)) = SHORTCUTS.iter().find(|(shortcut, _)| {
+        args.positional
+            .first()
+            .is_some_and(|first| first == shortcut)
+    }) {
+        args.positional
+            .splice(0..1, expanded.iter().map(|word| word.to_string()));
+    }
+    if args.positional.len() == 2 && args.positional[0] == "launch" {
+        let group = args.positional.pop();
+        args.flags.insert("group".into(), group);
+    }
+    args
+}
+
+pub fn parse(raw: impl IntoIterator<Item = String>) -> Result<Args, clap::Error> {
+    //! Parses arguments (without the binary name) into the command's words and its flags.
+    let command = build();
+    let matches = command.clone().try_get_matches_from(raw)?;
+    let mut args = Args::default();
+    collect(&command, &matches, &mut args);
+    Ok(expand_shortcuts(args))
+}
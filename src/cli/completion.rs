@@ -0,0 +1,375 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    str::FromStr,
+    time::Duration,
+};
+
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumString};
+
+use crate::{
+    config::models::Config,
+    region::Region,
+    server::server_group::ServerGroup,
+    store::{acl::ConnectionPurpose, entity::RedisEntity},
+};
+
+use super::{command, Args, CliError, USAGE};
+
+pub(super) const BIN: &str = "plex_redis_manager";
+/// How long `__complete groups` waits on redis, so a down redis doesn't hang the shell.
+const GROUPS_TIMEOUT: Duration = Duration::from_millis(300);
+/// Placeholders in the usage text that stand for an existing group.
+const GROUP_PLACEHOLDERS: [&str; 3] = ["<group>", "<name>", "<old>"];
+
+/// Shells `completions` writes a script for.
+#[derive(Clone, Copy, Debug, Display, EnumString, Eq, PartialEq)]
+#[strum(serialize_all = "lowercase")]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Names only known at completion time, printed by the hidden `__complete <kind>`.
+#[derive(Clone, Copy, Debug, Display, EnumString, Eq, PartialEq)]
+#[strum(serialize_all = "lowercase")]
+pub enum DynamicKind {
+    Groups, // read from redis
+    Nodes,  // read from config.toml
+}
+
+/// What may follow a run of command keywords, e.g. `maps` -> add, pool, enable, disable.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct Position {
+    keywords: BTreeSet<String>,
+    placeholders: BTreeSet<String>,
+}
+
+impl Position {
+    fn get_dynamic(&self) -> Option<DynamicKind> {
+        //! Groups, if every command continuing here takes an existing group next.
+        (!self.placeholders.is_empty()
+            && self
+                .placeholders
+                .iter()
+                .all(|placeholder| GROUP_PLACEHOLDERS.contains(&placeholder.as_str())))
+        .then_some(DynamicKind::Groups)
+    }
+}
+
+pub(super) fn get_command_lines() -> impl Iterator<Item = &'static str> {
+    //! The command lines of the usage text, without their continuation lines.
+    USAGE
+        .lines()
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.strip_prefix("  "))
+        .filter(|line| !line.starts_with(' '))
+}
+
+fn get_positions() -> BTreeMap<String, Position> {
+    //! Space-joined keywords -> what may follow them, from the usage text.
+    let mut positions: BTreeMap<String, Position> = BTreeMap::new();
+    for line in get_command_lines() {
+        let mut prefixes = vec![String::new()];
+        for word in line.split_whitespace() {
+            let word = word.trim_matches(|c| c == '[' || c == ']');
+            if word.starts_with('<') {
+                for prefix in prefixes.iter() {
+                    let position = positions.entry(prefix.clone()).or_default();
+                    position.placeholders.insert(word.into());
+                }
+                break;
+            }
+            if !word.starts_with(|c: char| c.is_ascii_lowercase()) {
+                break;
+            }
+            let alternatives: Vec<&str> = word.split('|').collect();
+            for prefix in prefixes.iter() {
+                let position = positions.entry(prefix.clone()).or_default();
+                position
+                    .keywords
+                    .extend(alternatives.iter().map(|keyword| keyword.to_string()));
+            }
+            prefixes = prefixes
+                .iter()
+                .flat_map(|prefix| {
+                    alternatives
+                        .iter()
+                        .map(move |keyword| match prefix.is_empty() {
+                            true => keyword.to_string(),
+                            false => format!("{} {}", prefix, keyword),
+                        })
+                })
+                .collect();
+        }
+    }
+    positions
+}
+
+fn get_flags() -> BTreeSet<String> {
+    //! Every `--flag` the usage text mentions.
+    USAGE
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+        .filter(|word| word.starts_with("--") && word.len() > 2)
+        .map(String::from)
+        .collect()
+}
+
+fn get_regions() -> Vec<String> {
+    Region::iter().map(|region| region.to_string()).collect()
+}
+
+fn bash_arms(positions: &BTreeMap<String, Position>, indent: &str) -> String {
+    //! `case "$prefix"` arms (bash and zsh share the syntax) setting `words` and `dynamic`.
+    positions
+        .iter()
+        .map(|(prefix, position)| {
+            let keywords: Vec<&str> = position.keywords.iter().map(|k| k.as_str()).collect();
+            let dynamic = position
+                .get_dynamic()
+                .map_or(String::new(), |kind| format!(" dynamic={};", kind));
+            format!(
+                "{}\"{}\") words=\"{}\";{};\n",
+                indent,
+                prefix,
+                keywords.join(" "),
+                dynamic
+            )
+        })
+        .collect()
+}
+
+fn bash_script() -> String {
+    let bool_flags = command::get_switches().join(" ");
+    let flags: Vec<String> = get_flags().into_iter().collect();
+    format!(
+        r#"# bash completion for {bin}, from `{bin} completions bash`
+# group names are read from redis (briefly) as they are completed, node names from config.toml
+_{bin}() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}" prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    case "$prev" in
+        --group) COMPREPLY=($(compgen -W "$({bin} __complete groups 2>/dev/null)" -- "$cur")); return;;
+        --node) COMPREPLY=($(compgen -W "$({bin} __complete nodes 2>/dev/null)" -- "$cur")); return;;
+        --region) COMPREPLY=($(compgen -W "{regions}" -- "$cur")); return;;
+    esac
+    if [[ "$cur" == -* ]]; then
+        COMPREPLY=($(compgen -W "{flags}" -- "$cur"))
+        return
+    fi
+    # the command's keywords so far, without flags and their values
+    local bool_flags=" {bool_flags} " prefix="" word skip=0 i
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        word="${{COMP_WORDS[i]}}"
+        if ((skip)); then skip=0; continue; fi
+        if [[ "$word" == --* ]]; then
+            [[ "$word" != *=* && "$bool_flags" != *" ${{word#--}} "* ]] && skip=1
+            continue
+        fi
+        prefix="${{prefix:+$prefix }}$word"
+    done
+    local words="" dynamic=""
+    case "$prefix" in
+{arms}    esac
+    [[ -n "$dynamic" ]] && words="$words $({bin} __complete $dynamic 2>/dev/null)"
+    COMPREPLY=($(compgen -W "$words" -- "$cur"))
+}}
+complete -o default -F _{bin} {bin}
+"#,
+        bin = BIN,
+        regions = get_regions().join(" "),
+        flags = flags.join(" "),
+        bool_flags = bool_flags,
+        arms = bash_arms(&get_positions(), "        "),
+    )
+}
+
+fn zsh_script() -> String {
+    let bool_flags = command::get_switches().join(" ");
+    let flags: Vec<String> = get_flags().into_iter().collect();
+    format!(
+        r#"#compdef {bin}
+# zsh completion for {bin}, from `{bin} completions zsh`
+# group names are read from redis (briefly) as they are completed, node names from config.toml
+_{bin}() {{
+    local cur="${{words[CURRENT]}}" prev="${{words[CURRENT-1]}}"
+    case "$prev" in
+        --group) compadd -- ${{(f)"$({bin} __complete groups 2>/dev/null)"}}; return;;
+        --node) compadd -- ${{(f)"$({bin} __complete nodes 2>/dev/null)"}}; return;;
+        --region) compadd -- {regions}; return;;
+    esac
+    if [[ "$cur" == -* ]]; then
+        compadd -- {flags}
+        return
+    fi
+    # the command's keywords so far, without flags and their values
+    local bool_flags=" {bool_flags} " prefix="" word skip=0 i
+    for ((i = 2; i < CURRENT; i++)); do
+        word="${{words[i]}}"
+        if ((skip)); then skip=0; continue; fi
+        if [[ "$word" == --* ]]; then
+            [[ "$word" != *=* && "$bool_flags" != *" ${{word#--}} "* ]] && skip=1
+            continue
+        fi
+        prefix="${{prefix:+$prefix }}$word"
+    done
+    local words_="" dynamic=""
+    local -a candidates
+    case "$prefix" in
+{arms}    esac
+    candidates=(${{=words_}})
+    [[ -n "$dynamic" ]] && candidates+=(${{(f)"$({bin} __complete $dynamic 2>/dev/null)"}})
+    if (( ${{#candidates}} )); then
+        compadd -a candidates
+    else
+        _files
+    fi
+}}
+compdef _{bin} {bin}
+"#,
+        bin = BIN,
+        regions = get_regions().join(" "),
+        flags = flags.join(" "),
+        bool_flags = bool_flags,
+        // `words` is zsh's array of the command line
+        arms = bash_arms(&get_positions(), "        ").replace(" words=", " words_="),
+    )
+}
+
+fn fish_script() -> String {
+    let positions = get_positions();
+    let arms: String = positions
+        .iter()
+        .map(|(prefix, position)| {
+            let keywords: Vec<&str> = position.keywords.iter().map(|k| k.as_str()).collect();
+            let mut arm = format!("        case '{}'\n", prefix);
+            if !keywords.is_empty() {
+                arm.push_str(&format!(
+                    "            printf '%s\\n' {}\n",
+                    keywords.join(" ")
+                ));
+            }
+            if let Some(kind) = position.get_dynamic() {
+                arm.push_str(&format!(
+                    "            {} __complete {} 2>/dev/null\n",
+                    BIN, kind
+                ));
+            }
+            arm
+        })
+        .collect();
+    let flags: Vec<String> = get_flags().into_iter().collect();
+    format!(
+        r#"# fish completion for {bin}, from `{bin} completions fish`
+# group names are read from redis (briefly) as they are completed, node names from config.toml
+function __{bin}_complete
+    set -l tokens (commandline -opc)
+    set -l cur (commandline -ct)
+    switch $tokens[-1]
+        case --group
+            {bin} __complete groups 2>/dev/null; return
+        case --node
+            {bin} __complete nodes 2>/dev/null; return
+        case --region
+            printf '%s\n' {regions}; return
+    end
+    if string match -q -- '-*' $cur
+        printf '%s\n' {flags}; return
+    end
+    # the command's keywords so far, without flags and their values
+    set -l bool_flags {bool_flags}
+    set -l prefix
+    set -l skip 0
+    set -e tokens[1]
+    for word in $tokens
+        if test $skip = 1
+            set skip 0
+            continue
+        end
+        if string match -q -- '--*' $word
+            if not string match -q -- '*=*' $word; and not contains -- (string sub -s 3 -- $word) $bool_flags
+                set skip 1
+            end
+            continue
+        end
+        set -a prefix $word
+    end
+    switch "$prefix"
+{arms}        case '*'
+            __fish_complete_path $cur
+    end
+end
+complete -c {bin} -f -a '(__{bin}_complete)'
+"#,
+        bin = BIN,
+        regions = get_regions().join(" "),
+        flags = flags.join(" "),
+        bool_flags = command::get_switches().join(" "),
+        arms = arms,
+    )
+}
+
+pub fn get_script(shell: CompletionShell) -> String {
+    //! A completion script for `shell`: commands and flags are completed from this build's
+    //! usage text, group and node names by calling back into `__complete`.
+    match shell {
+        CompletionShell::Bash => bash_script(),
+        CompletionShell::Zsh => zsh_script(),
+        CompletionShell::Fish => fish_script(),
+    }
+}
+
+pub fn get_names(kind: DynamicKind, config: &Config) -> Vec<String> {
+    //! Names for `__complete`, empty if they can't be read quickly: a completion that
+    //! hangs or prints errors is worse than none.
+    match kind {
+        DynamicKind::Nodes => config
+            .dedicated_servers
+            .servers
+            .iter()
+            .map(|ds| ds.name.clone())
+            .collect(),
+        DynamicKind::Groups => {
+            let Some(index) = ServerGroup::index_set(&config.keys) else {
+                return Vec::new();
+            };
+            let mut names: Vec<String> = config
+                .get_redis_connection_with_timeout(ConnectionPurpose::ReadOnly, GROUPS_TIMEOUT)
+                .and_then(|mut conn| redis::cmd("SMEMBERS").arg(index).query(&mut conn))
+                .unwrap_or_default();
+            names.sort();
+            names
+        }
+    }
+}
+
+pub fn run_early(args: &Args) -> Option<Result<(), CliError>> {
+    //! Runs `completions` and `__complete`, which mustn't wait on (or fail without) the
+    //! redis connection other commands open. None for every other command.
+    let positional: Vec<&str> = args.positional.iter().map(|arg| arg.as_str()).collect();
+    match positional.as_slice() {
+        ["completions", shell] => Some(print_script(shell)),
+        ["__complete", kind] => {
+            // run from wherever the shell is, where there may be no config to read
+            if let (Ok(kind), true) = (
+                DynamicKind::from_str(kind),
+                Path::new("config.toml").exists(),
+            ) {
+                for name in get_names(kind, &Config::get_config()) {
+                    println!("{}", name);
+                }
+            }
+            Some(Ok(()))
+        }
+        _ => None,
+    }
+}
+
+pub fn print_script(shell: &str) -> Result<(), CliError> {
+    let shell = CompletionShell::from_str(shell)
+        .map_err(|_| CliError::Usage(format!("Unknown shell {:?} (bash, zsh or fish)", shell)))?;
+    print!("{}", get_script(shell));
+    Ok(())
+}
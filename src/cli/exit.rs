@@ -0,0 +1,229 @@
+use redis::{ErrorKind, RedisError};
+
+use crate::{
+    apply::ApplyError,
+    error::parsing_error::ServerGroupParsingError,
+    maps::MapError,
+    monitor::recorder::RecorderError,
+    queue::QueueError,
+    safety::SafetyError,
+    server::{
+        dedicated::server::DedicatedServerError, event_server::EventServerError, freeze,
+        minecraft::MinecraftServerError, query::QueryError, rcon::RconError,
+        rightsizing::RightsizingError, smoke::SmokeTestError,
+    },
+    store::entity,
+    undo::UndoError,
+};
+
+use super::CliError;
+
+// Exit codes of the binary. Scripts rely on them, so existing codes never change meaning.
+pub const SUCCESS: i32 = 0;
+pub const FAILED: i32 = 1; // anything not covered below
+pub const USAGE: i32 = 2;
+pub const NOT_FOUND: i32 = 3; // group, server, node... doesn't exist
+pub const UNREACHABLE: i32 = 4; // redis (or the snapshot) couldn't be opened
+pub const INVALID: i32 = 5; // input or stored data failed validation
+pub const REFUSED: i32 = 6; // needs --force, plugins incompatible, group paused...
+
+impl CliError {
+    pub fn get_exit_code(&self) -> i32 {
+        match self {
+            Self::Usage(_) => USAGE,
+            Self::CommandFailed(_) => FAILED,
+            Self::NotFound(_) => NOT_FOUND,
+            Self::Unreachable(_) => UNREACHABLE,
+            Self::Invalid(_) => INVALID,
+            Self::Refused(_) => REFUSED,
+        }
+    }
+
+    pub fn from_connect_error(err: RedisError) -> Self {
+        //! Why redis (or the snapshot) couldn't be opened at all: unreachable, unless the
+        //! configuration (or an ACL user's password) itself is wrong.
+        match err.kind() {
+            ErrorKind::InvalidClientConfig | ErrorKind::AuthenticationFailed => {
+                Self::Invalid(err.to_string())
+            }
+            _ => Self::Unreachable(err.to_string()),
+        }
+    }
+
+    fn not_found_or(msg: String, other: fn(String) -> Self) -> Self {
+        // entities that aren't stored come back as errors ending in `entity::NOT_FOUND`,
+        // groups the manager won't overwrite mention `freeze::EXTERNAL_EDIT`
+        if msg.contains(entity::NOT_FOUND) {
+            Self::NotFound(msg)
+        } else if msg.contains(freeze::EXTERNAL_EDIT) {
+            Self::Refused(msg)
+        } else {
+            other(msg)
+        }
+    }
+}
+
+impl From<RedisError> for CliError {
+    fn from(err: RedisError) -> Self {
+        if err.is_connection_refusal()
+            || err.is_connection_dropped()
+            || err.is_io_error()
+            || err.is_timeout()
+            || matches!(err.kind(), ErrorKind::ClusterDown | ErrorKind::MasterDown)
+        {
+            return Self::Unreachable(err.to_string());
+        }
+        match err.kind() {
+            ErrorKind::InvalidClientConfig => Self::Invalid(err.to_string()),
+            // the ACL user this command connected as may not run it
+            _ if err.code() == Some("NOPERM") => Self::Refused(err.to_string()),
+            _ => Self::not_found_or(err.to_string(), Self::CommandFailed),
+        }
+    }
+}
+
+impl From<ServerGroupParsingError> for CliError {
+    fn from(err: ServerGroupParsingError) -> Self {
+        Self::not_found_or(err.to_string(), Self::Invalid)
+    }
+}
+
+impl From<MinecraftServerError> for CliError {
+    fn from(err: MinecraftServerError) -> Self {
+        Self::not_found_or(err.to_string(), Self::Invalid)
+    }
+}
+
+impl From<DedicatedServerError> for CliError {
+    fn from(err: DedicatedServerError) -> Self {
+        match err {
+            DedicatedServerError::NodeNotFound(_)
+            | DedicatedServerError::InstanceNotFound(_)
+            | DedicatedServerError::ZeroInstancesRunning(_) => Self::NotFound(err.to_string()),
+            DedicatedServerError::ParsingError(_) | DedicatedServerError::PortOutOfRange(_) => {
+                Self::Invalid(err.to_string())
+            }
+            DedicatedServerError::NodeInUse(_)
+            | DedicatedServerError::NodeUnhealthy(_)
+            | DedicatedServerError::DiskFull(_)
+            | DedicatedServerError::CrashLooping(_) => Self::Refused(err.to_string()),
+            DedicatedServerError::Unsafe(err) => Self::from(err),
+            _ => Self::CommandFailed(err.to_string()),
+        }
+    }
+}
+
+impl From<clap::Error> for CliError {
+    fn from(err: clap::Error) -> Self {
+        //! Keeps clap's reason (up to its own usage line); the usage text follows it anyway.
+        let message = err.to_string();
+        let reason: Vec<&str> = message
+            .lines()
+            .take_while(|line| !line.is_empty())
+            .map(str::trim)
+            .collect();
+        Self::Usage(reason.join(" ").trim_start_matches("error: ").to_string())
+    }
+}
+
+impl From<SafetyError> for CliError {
+    fn from(err: SafetyError) -> Self {
+        match err {
+            SafetyError::RequiresForce(..) | SafetyError::Incompatible(..) => {
+                Self::Refused(err.to_string())
+            }
+            _ => Self::CommandFailed(err.to_string()),
+        }
+    }
+}
+
+impl From<UndoError> for CliError {
+    fn from(err: UndoError) -> Self {
+        match err {
+            UndoError::NothingToUndo => Self::NotFound(err.to_string()),
+            UndoError::Irreversible(_) | UndoError::Conflict(_) | UndoError::Refused(_) => {
+                Self::Refused(err.to_string())
+            }
+            UndoError::RedisError(_) => Self::CommandFailed(err.to_string()),
+        }
+    }
+}
+
+impl From<ApplyError> for CliError {
+    fn from(err: ApplyError) -> Self {
+        match err {
+            ApplyError::ReadError(_) => Self::NotFound(err.to_string()),
+            ApplyError::ValidationError(_) => Self::Invalid(err.to_string()),
+            ApplyError::RedisError(_) => Self::CommandFailed(err.to_string()),
+        }
+    }
+}
+
+impl From<MapError> for CliError {
+    fn from(err: MapError) -> Self {
+        match err {
+            MapError::ReadError(_) => Self::NotFound(err.to_string()),
+            MapError::InvalidMap(_) => Self::Invalid(err.to_string()),
+            MapError::RedisError(_) => Self::CommandFailed(err.to_string()),
+        }
+    }
+}
+
+impl From<EventServerError> for CliError {
+    fn from(err: EventServerError) -> Self {
+        match err {
+            EventServerError::InvalidEvent(_) => Self::Invalid(err.to_string()),
+            EventServerError::RedisError(_) => Self::CommandFailed(err.to_string()),
+        }
+    }
+}
+
+impl From<QueueError> for CliError {
+    fn from(err: QueueError) -> Self {
+        match err {
+            QueueError::InvalidEntry(_) => Self::Invalid(err.to_string()),
+            QueueError::RedisError(_) => Self::CommandFailed(err.to_string()),
+        }
+    }
+}
+
+impl From<RightsizingError> for CliError {
+    fn from(err: RightsizingError) -> Self {
+        match err {
+            RightsizingError::NoSuggestion(_) => Self::NotFound(err.to_string()),
+            RightsizingError::InvalidResources(_) => Self::Invalid(err.to_string()),
+            RightsizingError::RedisError(_) => Self::CommandFailed(err.to_string()),
+        }
+    }
+}
+
+impl From<RconError> for CliError {
+    fn from(err: RconError) -> Self {
+        match err {
+            RconError::NotConfigured(_) => Self::Invalid(err.to_string()),
+            _ => Self::CommandFailed(err.to_string()),
+        }
+    }
+}
+
+impl From<RecorderError> for CliError {
+    fn from(err: RecorderError) -> Self {
+        match err {
+            RecorderError::ReadError(_) => Self::NotFound(err.to_string()),
+            _ => Self::CommandFailed(err.to_string()),
+        }
+    }
+}
+
+impl From<QueryError> for CliError {
+    fn from(err: QueryError) -> Self {
+        // a mistyped --where is an argument problem
+        Self::Usage(err.to_string())
+    }
+}
+
+impl From<SmokeTestError> for CliError {
+    fn from(err: SmokeTestError) -> Self {
+        Self::CommandFailed(err.to_string())
+    }
+}
@@ -0,0 +1,1822 @@
+pub mod command;
+pub mod completion;
+pub mod exit;
+pub mod output;
+pub mod shell;
+pub mod table;
+
+use std::{collections::HashMap, path::Path, str::FromStr, time::Duration};
+
+use chrono::{Local, Utc};
+use strum::IntoEnumIterator;
+use thiserror::Error;
+
+use crate::{
+    apply::{self, NetworkSpec},
+    batch::{self, BatchAction},
+    context_manager::ContextManager,
+    dev::mock::{self, MockNetwork},
+    doctor,
+    events::{Event, EventKind},
+    game::{r#type::GameType, Game},
+    handshake::{self, Handshake},
+    jars,
+    journal::JournalEntry,
+    maps::{self, MapPool},
+    monitor::{
+        alerts::AlertEngine,
+        forecast,
+        heartbeat::Heartbeat,
+        recorder::Recording,
+        region::{RegionHealth, RegionWorkers},
+        services::{self, RewardSafe},
+        shutdown,
+        summary::NetworkSummary,
+        Monitor,
+    },
+    queue,
+    region::Region,
+    safety,
+    server::{
+        broadcast::{Broadcast, BroadcastTarget, Countdown},
+        commands::{BroadcastStyle, ServerCommand},
+        counters,
+        crash_loop::CrashLoop,
+        dedicated::{
+            instance::MCSInstance,
+            labels::{self, LabelTarget, Labels},
+            launcher,
+            orphans::{Orphan, OrphanPolicy},
+            prewarm, refresh,
+            strays::{Stray, StrayDecision},
+        },
+        ensure,
+        event_server::{self, EventServer},
+        freeze,
+        minecraft::{GameJoinStatus, MinecraftServer},
+        port::PortReassignment,
+        presets::{Preset, SizeTier},
+        rcon,
+        restart::{self, ScheduledRestart},
+        rightsizing::{self, Suggestion},
+        rotation, schema,
+        server_group::ServerGroup,
+        view::{GroupPlanView, GroupStatusView},
+    },
+    store::{
+        acl::ConnectionPurpose, bulk::BulkWriter, entity::RedisEntity, metrics::RedisMetrics,
+        mirror, snapshot::Snapshot,
+    },
+    strategy,
+    undo::{self, GroupChange, UndoError},
+};
+
+use table::{Filters, Table};
+
+pub const USAGE: &str = "\
+Usage:
+  group create <game>                                  Create a group from a game type's defaults
+  group create <name> --preset <preset> [--region <region>] [--tier <S|M|L>]
+                                                       Create a group from a preset
+  group list [--region <region>] [--group <name>] [--where <query>]
+                                                       List groups with their desired and live instance counts
+  group scale <name> <total> [--joinable <n>]          Set a group's desired instance counts
+  group presets                                        List available presets
+  group set <name> <field=value>... [--force] [--allow-protected]
+                                                       Set raw group hash fields (e.g. maxPlayers=24), --force
+                                                       enables rewards while a service they depend on is down
+  group schema                                         Print every group field's type, default and constraints as JSON
+  group rename <old> <new> [--relaunch] [--force]      Rename a group, optionally relaunching its instances
+  group delete <name> [--force]                        Delete a group, asking first (or with --force) while it has
+                                                       live instances
+  group ports <name>                                   Show a group's port section history
+  group status <name>                                  Show a group's instances, live statuses and recent events
+  group plan <name>                                    Show what the scaler would do for a group right now (counts,
+                                                       policy inputs, target count and nodes) without changing anything
+  list-groups                                          Same as `group list`
+  create-group <game>                                  Same as `group create <game>`
+  delete-group <name> [--force]                        Same as `group delete <name>`
+  status <group>                                       Same as `group status <group>`
+  event create <game> <map> --host-rank <rank> --duration <mins>
+                                                       Create a whitelisted one-off event group, archived
+                                                       by the monitor after the duration
+  event list [--archived] [--group <name>]             Show running (or archived) event servers
+  event end <group>                                    Archive an event server now
+  maps [<game>]                                        List known maps (worlds path and catalog sets) per game
+  maps add <game> <map>...                             Add maps to a game's catalog set
+  maps pool <group>                                    Write and show a group's map pool
+  maps enable|disable <group> <map>                    Turn a map on or off for a group
+  apply -f <file> [--prune [--force]] [--dry-run] [--allow-protected]
+                                                       Make redis match a desired-state file (TOML, or YAML
+                                                       if it ends in .yaml/.yml: groups, desired instance
+                                                       counts, node labels), optionally deleting groups the
+                                                       file doesn't list
+  instances [--group <name>] [--region <region>] [--node <name>] [--state <state>] [--label <key=value,...>]
+            [--where <query>]
+                                                       List instances on each node with their state
+                                                       (online, offline, does_not_exist...) and labels
+  instances label <instance> <key=value | key->...     Set labels on an instance (`key-` removes one)
+  foreach <restart | broadcast <message> [--style <style>] | join <status>>
+          [--group <name>] [--region <region>] [--node <name>] [--state <state>] [--label <key=value,...>]
+          [--where <query>]
+          [--concurrency <n>]
+                                                       Run an action on every selected instance (at least one
+                                                       selector required), a few at a time
+  nodes [--region <region>] [--node <name>]            List nodes with their instance counts, free resources,
+                                                       reservations for critical groups and their agents' telemetry
+  nodes sample [--region <region>]                     Sample cpu and rss of each instance's process over ssh on nodes
+                                                       without telemetry, recording them for `rightsize`
+  broadcast <message> [--group <name>] [--style <chat|action_bar>] [--countdown <secs>]
+                                                       Send a message to a group's servers (or all of them),
+                                                       optionally repeated as a countdown ({server}, {group},
+                                                       {remaining} are filled in)
+  doctor [--fix [--recreate]]                          Report malformed groups and statuses, optionally
+                                                       repairing unknown booster groups and dangling
+                                                       references (or recreating missing team servers)
+  recover                                              Adopt running servers into node bookkeeping
+  events [--count <n>]                                 Show recent events, newest first
+  undo [<n>] [--force]                                 Revert the newest (or n-th) group change from the event log
+  undo list [--group <name>]                           List recent changes that can be undone
+  kill <instance> [--force]                            Kill an instance (e.g. MIN-3), asking first (or with --force)
+                                                       while players are online
+  smoke <instance> [--region <region>]                 Run the launch smoke test against a live instance
+  rcon <instance> <command>... [--region <region>]     Run a console command on a live instance over rcon
+  crashloops [clear <group>]                           Show groups with failed launches (or resume one's launches)
+  services                                             Check external services and show reward-safe groups
+  journal [replay]                                     Show (or recover) operations interrupted by a crash
+  monitor [--ticks <n>] [--ignore-topology]            Run the monitor loop until SIGINT/SIGTERM, after checking the
+                                                       configured nodes against redis (refusing on severe mismatches)
+  handshake [publish]                                  Compare plugin schema versions (or advertise this manager's)
+  alerts [test [--state <breached|ok>]]                Show firing alerts (or evaluate every rule once now)
+  summary [--live]                                     Show the published network.summary (or build it now)
+  queue [<group>]                                      Show queue lengths per group (or who waits for one group)
+  queue push|remove <group> <player>                   Add a player to a full group's queue (or take them out)
+  queue pop <group> [--count <n>]                      Take the next players off a group's queue
+  managers                                             List manager instances with a live heartbeat
+  acl                                                  Print ACL SETUSER rules for the configured redis users
+  regions                                              Show the health of every manager's region workers
+  forecast [--region <region>]                         Show predicted demand per region and hour against node capacity
+  rightsize [--group <name>]                           Suggest moving groups to another resource tier, with evidence
+  rightsize schedule <group>                           Apply a group's suggested tier at its next rolling restart
+  rightsize cancel <group>                             Drop a group's queued tier change
+  strays                                               List servers the monitor saw running untracked by any node
+  strays adopt <server>                                Have the monitor track a stray on its node, reserving resources
+  strays ignore <server>                               Flag a stray as unmanaged and stop warning about it
+  orphans                                              List server processes found on nodes without a tracked
+                                                       instance or status behind them
+  orphans sweep [--region <region>] [--kill]           Look for orphans now, killing those orphaned for
+                                                       [orphans] grace_secs with --kill
+  prewarm [--region <region>] [--group <name>] [--dry-run]
+                                                       Stage world zips and jars on the nodes the next launches would use
+  launch [<group>] [--region <region>] [--dry-run] [--concurrency <n>] [--per-node <n>]
+                                                       Start the instances the scaler is missing (of one group, or
+                                                       --group), priority groups (lobbies) first, a few at a time
+                                                       and per node
+  recordings [--region <region>]                       List the reconciles region workers recorded (see [recorder])
+  replay <file>                                        Re-run a recorded reconcile's decisions offline and compare
+                                                       them with what the worker decided
+  stats redis                                          Show per-command redis latency, payload sizes and errors
+                                                       of every running manager
+  stats boosters [--live]                              Show players per booster group from the published network.summary
+                                                       (or build it now)
+  jars sync                                            Fetch configured server jars and copy them to every node
+  restart schedule <group> (--in <secs> | --at <rfc3339>) [--warn <secs,...>] [--message <template>]
+                                                       Warn players, then drain and restart a group's instances
+  rotation preview [--at <rfc3339>]                    Show which rotation slot (and games) each group has at a time
+  rotation apply [--dry-run] [--in <secs>]             Switch rotating groups to their scheduled games, restarting
+                                                       those with `restart = true` (after a countdown of --in secs)
+  restart cancel <group>                               Cancel a group's scheduled restart
+  restart list                                         Show scheduled restarts
+  backup <file>                                        Save all redis data to a JSON snapshot
+  restore <file> [--chunk <n>]                         Write a JSON snapshot back to redis in pipelined chunks
+  mirror verify [--match <pattern>]                    Compare redis with redis_conn.mirror (the redis being
+                                                       migrated to) key by key
+  shell                                                Run commands interactively on one connection, with
+                                                       tab completion of commands and group names
+  completions bash|zsh|fish                            Print a completion script for a shell (commands, flags, and
+                                                       group and node names, e.g. `source <(... completions bash)`)
+  mock run --groups <n> --servers <m> [--interval <secs>] [--rounds <r>]
+                                                       Fill redis with synthetic groups and live statuses
+  mock clear                                           Remove synthetic groups and statuses
+
+Queries (--where) filter on group fields, e.g. \"region==EU && arcadeGroup && maxPlayers>=16\":
+  ==, !=, >, >=, <, <= and ~= (contains) compare a field, a bare field is true unless empty, false or 0,
+  combined with &&, ||, ! and parentheses
+
+Global options:
+  --sort <column>                                      Sort a listed table by one of its columns
+  --snapshot <file>                                    Work offline against a snapshot instead of redis
+  --accept-external-changes                            Let the command overwrite groups edited outside the manager
+  --read-only                                          Connect as the read-only ACL user, so the command can't write
+  --help                                               Show this help
+
+Exit codes:
+  0                                                    Success
+  1                                                    Command failed
+  2                                                    Usage error (unknown command, bad arguments)
+  3                                                    Not found (group, server, node, file...)
+  4                                                    Redis unreachable
+  5                                                    Validation failed
+  6                                                    Refused (needs --force, incompatible plugins, paused...)";
+
+#[derive(Error, Debug)]
+pub enum CliError {
+    #[error("{0}\n\n{USAGE}")]
+    Usage(String),
+    #[error("CLI Error: `{0}`")]
+    CommandFailed(String),
+    #[error("CLI Error: Not found: `{0}`")]
+    NotFound(String),
+    #[error("CLI Error: Redis unreachable: `{0}`")]
+    Unreachable(String),
+    #[error("CLI Error: Validation failed: `{0}`")]
+    Invalid(String),
+    #[error("CLI Error: Refused: `{0}`")]
+    Refused(String),
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Args {
+    pub positional: Vec<String>,
+    pub flags: HashMap<String, Option<String>>,
+}
+
+impl Args {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, clap::Error> {
+        //! Parses arguments (without the binary name) against the command tree in `command`.
+        command::parse(args)
+    }
+
+    pub fn get_flag(&self, name: &str) -> Option<&String> {
+        self.flags.get(name).and_then(|value| value.as_ref())
+    }
+
+    pub fn has_flag(&self, name: &str) -> bool {
+        self.flags.contains_key(name)
+    }
+
+    pub fn get_purpose(&self) -> ConnectionPurpose {
+        //! Commands that only read connect as `redis_conn.users.read_only`, as does anything
+        //! run with --read-only.
+        if self.has_flag("read-only") {
+            return ConnectionPurpose::ReadOnly;
+        }
+        let positional: Vec<&str> = self.positional.iter().map(|arg| arg.as_str()).collect();
+        match positional.as_slice() {
+            ["group", "list" | "status" | "plan" | "presets" | "schema", ..]
+            | ["event", "list"]
+            | ["maps"]
+            | ["instances" | "nodes" | "managers" | "regions" | "forecast" | "strays"]
+            | ["handshake" | "summary" | "alerts" | "events" | "crashloops" | "journal"]
+            | ["queue" | "orphans"]
+            | ["queue", _]
+            | ["stats", "redis" | "boosters"]
+            | ["recordings" | "acl"]
+            | ["replay", _]
+            | ["undo" | "restart", "list"]
+            | ["rotation", "preview"]
+            | ["backup", _]
+            | ["mirror", "verify"] => ConnectionPurpose::ReadOnly,
+            _ => ConnectionPurpose::Mutating,
+        }
+    }
+
+    fn parse_flag<T: FromStr>(&self, name: &str) -> Result<Option<T>, CliError> {
+        self.get_flag(name)
+            .map(|value| {
+                T::from_str(value).map_err(|_| {
+                    CliError::Usage(format!("Invalid value for --{}: {:?}", name, value))
+                })
+            })
+            .transpose()
+    }
+}
+
+pub fn run(args: &Args, ctx: &mut ContextManager) -> Result<(), CliError> {
+    if args.has_flag("accept-external-changes") {
+        for key in freeze::accept_all(ctx).map_err(CliError::from)? {
+            println!("Accepted external changes to {}", key);
+        }
+    }
+    let positional: Vec<&str> = args.positional.iter().map(|arg| arg.as_str()).collect();
+    match positional.as_slice() {
+        ["group", "create", name] => create_group(name, args, ctx),
+        ["group", "list"] => {
+            let groups = ServerGroup::get_all(ctx);
+            for (key, err) in groups.failed.iter() {
+                println!("{} could not be read: {}", key, err);
+            }
+            let filters = Filters::parse(args)?;
+            let mut table =
+                Table::new(&["group", "region", "total", "joinable", "running", "open"]);
+            for group in groups.ok.iter() {
+                if !filters.matches_group(&group.prefix)
+                    || !filters.matches_region(&group.region)
+                    || !filters.matches_query(group)?
+                {
+                    continue;
+                }
+                let counts = counters::get(&group.prefix, ctx).map_err(CliError::from)?;
+                table.add_row(vec![
+                    group.prefix.clone(),
+                    group.region.to_string(),
+                    group.total_servers.to_string(),
+                    group.joinable_servers.to_string(),
+                    counts.total.to_string(),
+                    counts.joinable.to_string(),
+                ]);
+            }
+            table.print(args, "No groups")
+        }
+        ["group", "scale", name, total] => {
+            let total = total
+                .parse::<u8>()
+                .map_err(|_| CliError::Usage(format!("Invalid instance count: {:?}", total)))?;
+            let mut group = ServerGroup::from_str(name, ctx).map_err(CliError::from)?;
+            let before = group.clone();
+            group.total_servers = total;
+            if let Some(joinable) = args.parse_flag::<u8>("joinable")? {
+                group.joinable_servers = joinable;
+            }
+            group.save(ctx).map_err(CliError::from)?;
+            if let Some(change) = GroupChange::between(&before, &group) {
+                change.emit("scaled", ctx);
+            }
+            println!(
+                "servergroups.{}: total {}, joinable {}",
+                group.prefix, group.total_servers, group.joinable_servers
+            );
+            Ok(())
+        }
+        ["group", "set", name, assignments @ ..] if !assignments.is_empty() => {
+            let group = ServerGroup::from_str(name, ctx).map_err(CliError::from)?;
+            let mut map = group.to_hashmap();
+            let fields = schema::get_group_schema(ctx.get_config());
+            for assignment in assignments {
+                let Some((field, value)) = assignment.split_once('=') else {
+                    return Err(CliError::Usage(format!(
+                        "Invalid assignment {:?} (expected field=value)",
+                        assignment
+                    )));
+                };
+                schema::check_value(&fields, field, value).map_err(CliError::Usage)?;
+                map.insert(field.into(), value.into());
+            }
+            let updated = ServerGroup::from_hashmap(map).map_err(CliError::from)?;
+            if updated.prefix != group.prefix {
+                return Err(CliError::Usage(
+                    "Use `group rename` to change a prefix".into(),
+                ));
+            }
+            let changed = group.diff(&updated);
+            if !args.has_flag("allow-protected") {
+                ensure::check_protected(&group.prefix, &changed, ctx).map_err(CliError::from)?;
+            }
+            if !args.has_flag("force") {
+                services::ensure_rewards_allowed(&group, &updated, ctx)
+                    .map_err(|err| CliError::Refused(format!("{} (--force to set anyway)", err)))?;
+            }
+            updated.save(ctx).map_err(CliError::from)?;
+            for diff in changed.iter() {
+                println!("servergroups.{} {}", group.prefix, diff);
+            }
+            match GroupChange::between(&group, &updated) {
+                Some(change) => change.emit("fields set", ctx),
+                None => println!("servergroups.{} unchanged", group.prefix),
+            }
+            Ok(())
+        }
+        ["group", "schema"] => {
+            let fields = schema::get_group_schema(ctx.get_config());
+            println!("{}", schema::to_json(&fields));
+            Ok(())
+        }
+        ["group", "rename", old, new] => {
+            let old_group = ServerGroup::get(old, ctx).map_err(CliError::from)?;
+            let renamed = ServerGroup::rename(old, new, ctx).map_err(CliError::from)?;
+            println!(
+                "Renamed servergroups.{} to servergroups.{}",
+                old, renamed.prefix
+            );
+            if args.has_flag("relaunch") {
+                let force = args.has_flag("force");
+                let relaunched = ctx
+                    .with_dedicated_servers(|servers, ctx| {
+                        servers.relaunch_group(&old_group, &renamed, force, ctx)
+                    })
+                    .map_err(CliError::from)?;
+                for name in relaunched {
+                    println!("Relaunched {}", name);
+                }
+            }
+            Ok(())
+        }
+        ["group", "delete", name] => {
+            let group = ServerGroup::from_str(name, ctx).map_err(CliError::from)?;
+            safety::confirm_or_force(args.has_flag("force"), |force| {
+                group.safe_delete(force, ctx)
+            })
+            .map_err(CliError::from)?;
+            Event::new(EventKind::GroupDeleted, &group.prefix, "deleted".into()).emit(ctx);
+            println!("Deleted servergroups.{}", group.prefix);
+            Ok(())
+        }
+        ["kill", name] => kill_instance(name, args, ctx),
+        ["group", "ports", name] => {
+            let history = PortReassignment::get_history(name, ctx).map_err(CliError::from)?;
+            if history.is_empty() {
+                println!("No port reassignments recorded for servergroups.{}", name);
+            }
+            for reassignment in history {
+                println!("{}", reassignment);
+            }
+            Ok(())
+        }
+        ["group", "plan", name] => {
+            let view = GroupPlanView::load(name, ctx).map_err(CliError::from)?;
+            print!("{}", view);
+            Ok(())
+        }
+        ["group", "status", name] => {
+            let view = GroupStatusView::load(name, ctx).map_err(CliError::from)?;
+            print!("{}", view);
+            Ok(())
+        }
+        ["group", "presets"] => {
+            for preset in Preset::iter() {
+                println!("{:<8} {}", preset.to_string(), preset.get_description());
+            }
+            Ok(())
+        }
+        ["event", "create", game, map] => {
+            let host_rank = args
+                .get_flag("host-rank")
+                .ok_or(CliError::Usage("--host-rank is required".into()))?;
+            let minutes = args
+                .parse_flag::<u64>("duration")?
+                .ok_or(CliError::Usage("--duration is required".into()))?;
+            let event_server = event_server::create_event_server(
+                game,
+                map,
+                host_rank,
+                Duration::from_secs(minutes * 60),
+                ctx,
+            )
+            .map_err(CliError::from)?;
+            println!("Created {}", event_server);
+            Ok(())
+        }
+        ["event", "list"] => {
+            let event_servers = match args.has_flag("archived") {
+                true => EventServer::get_archived(20, ctx),
+                false => EventServer::get_all(ctx),
+            }
+            .map_err(CliError::from)?;
+            let filters = Filters::parse(args)?;
+            let mut table = Table::new(&["group", "game", "map", "host rank", "address", "ends"]);
+            for event_server in event_servers
+                .iter()
+                .filter(|event_server| filters.matches_group(&event_server.group))
+            {
+                table.add_row(vec![
+                    event_server.group.clone(),
+                    event_server.game.clone(),
+                    event_server.map.clone(),
+                    event_server.host_rank.clone(),
+                    event_server.address.clone().unwrap_or_default(),
+                    table::format_time(event_server.archived_at.unwrap_or(event_server.expires_at)),
+                ]);
+            }
+            table.print(args, "No event servers")
+        }
+        ["event", "end", group] => {
+            let event_server = EventServer::get_all(ctx)
+                .map_err(CliError::from)?
+                .into_iter()
+                .find(|event_server| &event_server.group == group)
+                .ok_or(CliError::NotFound(format!(
+                    "{} is not a running event server",
+                    group
+                )))?;
+            let event_server = event_server.archive(ctx).map_err(CliError::from)?;
+            println!("Archived {}", event_server);
+            Ok(())
+        }
+        ["maps"] => {
+            let worlds_path = ctx.get_config().monitor_info.get_worlds_path().clone();
+            let scanned = maps::scan_worlds(Path::new(&worlds_path)).map_err(CliError::from)?;
+            for game in GameType::iter() {
+                let catalog = maps::get_catalog(&game, ctx).map_err(CliError::from)?;
+                if !catalog.is_empty() || scanned.contains_key(&game.to_string()) {
+                    println!(
+                        "{}: {}",
+                        game,
+                        catalog.into_iter().collect::<Vec<_>>().join(", ")
+                    );
+                }
+            }
+            Ok(())
+        }
+        ["maps", "add", game, names @ ..] if !names.is_empty() => {
+            let game = GameType::from_str(game)
+                .map_err(|_| CliError::Usage(format!("Unknown game: {:?}", game)))?;
+            let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+            maps::add_to_catalog(&game, &names, ctx).map_err(CliError::from)?;
+            println!("Added {} map(s) to {}", names.len(), game);
+            Ok(())
+        }
+        ["maps", "pool", group] => {
+            let group = ServerGroup::from_str(group, ctx).map_err(CliError::from)?;
+            let pool = maps::write_pool(&group, ctx).map_err(CliError::from)?;
+            print_map_pool(&group, &pool);
+            Ok(())
+        }
+        ["maps", action @ ("enable" | "disable"), group, map] => {
+            let group = ServerGroup::from_str(group, ctx).map_err(CliError::from)?;
+            let pool =
+                maps::set_enabled(&group, map, *action == "enable", ctx).map_err(CliError::from)?;
+            print_map_pool(&group, &pool);
+            Ok(())
+        }
+        ["maps", game] => {
+            let game = GameType::from_str(game)
+                .map_err(|_| CliError::Usage(format!("Unknown game: {:?}", game)))?;
+            for map in maps::get_catalog(&game, ctx).map_err(CliError::from)? {
+                println!("{}", map);
+            }
+            Ok(())
+        }
+        ["apply"] => {
+            let path = args.get_flag("file").ok_or(CliError::Usage(
+                "No desired-state file given (-f <file>)".into(),
+            ))?;
+            let spec = NetworkSpec::load(path).map_err(CliError::from)?;
+            let report = apply::apply(
+                &spec,
+                args.has_flag("prune"),
+                args.has_flag("force"),
+                args.has_flag("allow-protected"),
+                args.has_flag("dry-run"),
+                ctx,
+            )
+            .map_err(CliError::from)?;
+            print!("{}", report);
+            if !report.is_success() {
+                return Err(CliError::CommandFailed(
+                    "Some changes failed to apply".into(),
+                ));
+            }
+            Ok(())
+        }
+        ["completions", shell] => completion::print_script(shell),
+        ["instances"] => list_instances(args, ctx),
+        ["nodes"] => {
+            let filters = Filters::parse(args)?;
+            let unreadable =
+                ctx.with_dedicated_servers(|servers, ctx| servers.merge_telemetry(ctx));
+            for (key, err) in unreadable.iter() {
+                println!("{} could not be read: {}", key, err);
+            }
+            let mut table = Table::new(&[
+                "node",
+                "region",
+                "address",
+                "instances",
+                "ram free",
+                "cpu free",
+                "reserved",
+                "load",
+                "disk free",
+                "agent",
+            ]);
+            for ds in ctx.get_dedicated_servers().servers.iter() {
+                if !filters.matches_region(&ds.region) || !filters.matches_node(&ds.name) {
+                    continue;
+                }
+                let reserved: Vec<String> = ds.reservations.iter().map(|r| r.to_string()).collect();
+                table.add_row(vec![
+                    ds.name.clone(),
+                    ds.region.to_string(),
+                    ds.public_address.clone(),
+                    ds.get_total_instance_count().to_string(),
+                    ds.available_ram.to_string(),
+                    ds.available_cpu.to_string(),
+                    reserved.join(", "),
+                    ds.telemetry.as_ref().map_or("-".into(), |telemetry| {
+                        format!("{:.2}/{}", telemetry.load.1, telemetry.cpus)
+                    }),
+                    ds.telemetry
+                        .as_ref()
+                        .map_or("-".into(), |telemetry| telemetry.disk_free.to_string()),
+                    match (ds.telemetry.as_ref(), ds.get_telemetry_warning()) {
+                        (None, _) => "no fresh telemetry".into(),
+                        (Some(_), Some(reason)) => reason,
+                        (Some(telemetry), None) => {
+                            format!("ok ({}MB ram free)", telemetry.ram_free)
+                        }
+                    },
+                ]);
+            }
+            table.print(args, "No nodes")
+        }
+        ["nodes", "sample"] => {
+            let region = args
+                .get_flag("region")
+                .map(|region| Region::try_from(region.clone()))
+                .transpose()
+                .map_err(|err| CliError::Usage(err.to_string()))?;
+            ctx.with_dedicated_servers(|servers, ctx| servers.merge_telemetry(ctx));
+            let report = ctx
+                .with_dedicated_servers(|servers, ctx| servers.sample_usage(region.as_ref(), ctx))
+                .map_err(CliError::from)?;
+            for (subject, err) in report.failed.iter() {
+                println!("{} could not be sampled: {}", subject, err);
+            }
+            let groups = ServerGroup::get_all(ctx).ok;
+            rightsizing::record_processes(&groups, &report.samples, ctx).map_err(CliError::from)?;
+            let mut table = Table::new(&["server", "group", "node", "pid", "cpu %", "rss", "ram"]);
+            for sample in report.samples.iter() {
+                let ram = groups
+                    .iter()
+                    .find(|group| group.name == sample.group)
+                    .map_or("-".into(), |group| group.ram.to_string());
+                table.add_row(vec![
+                    sample.server.clone(),
+                    sample.group.clone(),
+                    sample.node.clone(),
+                    sample.pid.to_string(),
+                    format!("{:.1}", sample.cpu_percent),
+                    sample.rss_mb.to_string(),
+                    ram,
+                ]);
+            }
+            table.print(args, "No instances sampled (nodes with telemetry aren't)")
+        }
+        ["foreach", action @ ..] if !action.is_empty() => foreach(action, args, ctx),
+        ["instances", "label", name, changes @ ..] if !changes.is_empty() => {
+            let mut labels =
+                labels::get_labels(LabelTarget::Instance, name, ctx).map_err(CliError::from)?;
+            for change in changes {
+                if let Some(key) = change.strip_suffix('-') {
+                    labels.remove(key);
+                    continue;
+                }
+                let parsed = labels::parse_selector(change).map_err(CliError::Usage)?;
+                labels.extend(parsed);
+            }
+            labels::set_labels(LabelTarget::Instance, name, &labels, ctx)
+                .map_err(CliError::from)?;
+            println!("{} [{}]", name, labels::format_labels(&labels));
+            Ok(())
+        }
+        ["broadcast", message] => {
+            let target = match args.get_flag("group") {
+                Some(name) => BroadcastTarget::Group(Box::new(
+                    ServerGroup::from_str(name, ctx).map_err(CliError::from)?,
+                )),
+                None => BroadcastTarget::Network,
+            };
+            let style = args
+                .parse_flag::<BroadcastStyle>("style")?
+                .unwrap_or_default();
+            let broadcast = Broadcast::new(target, message, style);
+            match args.parse_flag::<u64>("countdown")? {
+                Some(secs) => {
+                    shutdown::install_signal_handlers();
+                    let length = Duration::from_secs(secs);
+                    let finished = broadcast
+                        .send(Some(length), ctx)
+                        .and_then(|_| {
+                            Countdown::default().run(
+                                &broadcast,
+                                length,
+                                Duration::ZERO,
+                                &mut |_| false,
+                                ctx,
+                            )
+                        })
+                        .map_err(CliError::from)?;
+                    if !finished {
+                        return Err(CliError::CommandFailed("Countdown interrupted".into()));
+                    }
+                }
+                None => {
+                    let received = broadcast.send(None, ctx).map_err(CliError::from)?;
+                    println!("Broadcast received by {} subscriber(s)", received);
+                }
+            }
+            Ok(())
+        }
+        ["doctor"] => {
+            if args.has_flag("fix") {
+                let fixes = doctor::fix_booster_groups(ctx).map_err(CliError::from)?;
+                for fix in fixes {
+                    println!("Fixed {}", fix);
+                }
+                let fixes = doctor::fix_references(args.has_flag("recreate"), ctx)
+                    .map_err(CliError::from)?;
+                for fix in fixes {
+                    println!("Fixed {}", fix);
+                }
+            }
+            let report = doctor::diagnose(ctx);
+            println!("{}", report);
+            if !report.is_healthy() {
+                return Err(CliError::Invalid("Doctor found errors".into()));
+            }
+            Ok(())
+        }
+        ["monitor"] => {
+            shutdown::install_signal_handlers();
+            let summary = Monitor::default()
+                .ignore_topology(args.has_flag("ignore-topology"))
+                .run(args.parse_flag::<usize>("ticks")?, ctx);
+            if let Some(reason) = summary.refused {
+                return Err(CliError::Refused(reason));
+            }
+            println!("{}", summary);
+            Ok(())
+        }
+        ["handshake"] => {
+            let report = handshake::check(ctx);
+            println!("{}", Handshake::of_manager());
+            for plugin in report.plugins.iter() {
+                println!("{}", plugin);
+            }
+            for mismatch in report.mismatches.iter() {
+                println!("Incompatible: {}", mismatch);
+            }
+            if !report.is_compatible() {
+                return Err(CliError::Refused(
+                    "Plugins are incompatible, destructive operations are refused".into(),
+                ));
+            }
+            Ok(())
+        }
+        ["handshake", "publish"] => {
+            handshake::publish(ctx).map_err(CliError::CommandFailed)?;
+            println!("Published {}", Handshake::of_manager());
+            Ok(())
+        }
+        ["stats", "redis"] => {
+            let published = RedisMetrics::get_published(ctx).map_err(CliError::from)?;
+            if published.is_empty() {
+                println!("No running manager has published redis metrics");
+            }
+            for (instance_id, metrics) in published {
+                println!("{}:\n{}", instance_id, metrics);
+            }
+            Ok(())
+        }
+        ["stats", "boosters"] => {
+            let Some(summary) = get_summary(args, ctx)? else {
+                println!("No summary published (see monitor_info.summary_ttl_secs)");
+                return Ok(());
+            };
+            let mut table = Table::new(&[
+                "booster",
+                "active",
+                "groups",
+                "servers",
+                "online",
+                "capacity",
+                "occupancy",
+            ]);
+            for (booster, counts) in summary.boosters.iter() {
+                table.add_row(vec![
+                    booster.clone(),
+                    counts.is_active().to_string(),
+                    counts.groups.join(","),
+                    counts.servers.to_string(),
+                    counts.online.to_string(),
+                    counts.capacity.to_string(),
+                    format!("{:.1}", counts.get_occupancy()),
+                ]);
+            }
+            table.print(args, "No booster groups")
+        }
+        ["acl"] => {
+            let users = ctx.get_config().get_redis_users().clone();
+            let rules = users.get_rules();
+            if rules.is_empty() {
+                println!("No ACL users configured (see [redis_conn.users])");
+            }
+            for rule in rules {
+                println!("{}", rule);
+            }
+            let purpose = ctx.get_purpose();
+            let user = users.get_user(purpose).map_err(CliError::from)?;
+            println!(
+                "This command connected for {} as {}",
+                purpose,
+                user.map_or("default", |user| user.username.as_str())
+            );
+            Ok(())
+        }
+        ["managers"] => {
+            let heartbeats = Heartbeat::get_all(ctx);
+            for (key, err) in heartbeats.failed.iter() {
+                println!("{} could not be read: {}", key, err);
+            }
+            if heartbeats.ok.is_empty() {
+                println!("No managers are heartbeating");
+            }
+            for heartbeat in heartbeats.ok.iter() {
+                println!("{}", heartbeat);
+            }
+            Ok(())
+        }
+        ["forecast"] => {
+            let regions = match args.get_flag("region") {
+                Some(region) => vec![Region::try_from(region.clone())
+                    .map_err(|err| CliError::Usage(err.to_string()))?],
+                None => RegionWorkers::get_regions(ctx),
+            };
+            let mut table = Table::new(&["region", "hour", "ram", "cpu", "capacity", "days"]);
+            for region in regions {
+                let forecasts =
+                    forecast::predict(&region, Utc::now(), ctx).map_err(CliError::from)?;
+                for forecast in forecasts {
+                    table.add_row(vec![
+                        region.to_string(),
+                        table::format_time(forecast.hour),
+                        format!("{}/{}MB", forecast.demand.ram, forecast.capacity.ram),
+                        format!("{}/{}", forecast.demand.cpu, forecast.capacity.cpu),
+                        format!("{}%", forecast.get_percent()),
+                        forecast.days.to_string(),
+                    ]);
+                }
+            }
+            table.print(args, "No demand recorded for the coming hours yet")
+        }
+        ["rightsize"] => {
+            let suggestions = rightsizing::advise(ctx).map_err(CliError::from)?;
+            let pending = Suggestion::get_pending(ctx).map_err(CliError::from)?;
+            let group = args.get_flag("group");
+            let mut table = Table::new(&["group", "current", "suggested", "queued", "evidence"]);
+            for suggestion in suggestions
+                .iter()
+                .filter(|suggestion| group.is_none_or(|group| &suggestion.group == group))
+            {
+                let queued = pending
+                    .iter()
+                    .find(|queued| queued.group == suggestion.group)
+                    .map_or("-".into(), |queued| queued.to.to_string());
+                table.add_row(vec![
+                    suggestion.group.clone(),
+                    suggestion.from.to_string(),
+                    suggestion.to.to_string(),
+                    queued,
+                    suggestion.evidence.clone(),
+                ]);
+            }
+            table.print(
+                args,
+                "No group needs another tier (or too little usage observed)",
+            )
+        }
+        ["rightsize", "schedule", group] => {
+            let suggestion = rightsizing::get_suggestion(group, ctx).map_err(CliError::from)?;
+            suggestion.schedule(ctx).map_err(CliError::from)?;
+            println!("Queued for the next rolling restart: {}", suggestion);
+            Ok(())
+        }
+        ["rightsize", "cancel", group] => {
+            let cancelled = Suggestion::cancel(group, ctx).map_err(CliError::from)?;
+            match cancelled {
+                true => println!("Cancelled the queued tier change of {}", group),
+                false => println!("{} has no queued tier change", group),
+            }
+            Ok(())
+        }
+        ["strays"] => {
+            let strays = Stray::get_all(ctx).map_err(CliError::from)?;
+            let mut table = Table::new(&[
+                "server", "group", "region", "address", "node", "detected", "decision", "error",
+            ]);
+            for stray in strays {
+                table.add_row(vec![
+                    stray.server,
+                    stray.group,
+                    stray.region.to_string(),
+                    format!("{}:{}", stray.address, stray.port),
+                    stray.node.unwrap_or("-".into()),
+                    table::format_time(stray.detected_at),
+                    stray.decision.to_string(),
+                    stray.last_error.unwrap_or_default(),
+                ]);
+            }
+            table.print(args, "No strays detected")
+        }
+        ["orphans"] => {
+            let orphans = Orphan::get_all(ctx).map_err(CliError::from)?;
+            let mut table = Table::new(&["server", "node", "pid", "since", "reason"]);
+            for orphan in orphans {
+                table.add_row(vec![
+                    orphan.server,
+                    orphan.node,
+                    orphan.pid.to_string(),
+                    table::format_time(orphan.first_seen),
+                    orphan.reason,
+                ]);
+            }
+            table.print(args, "No orphans found")
+        }
+        ["orphans", "sweep"] => {
+            let region = args
+                .get_flag("region")
+                .map(|region| Region::try_from(region.clone()))
+                .transpose()
+                .map_err(|err| CliError::Usage(err.to_string()))?;
+            let policy = match args.has_flag("kill") {
+                true => OrphanPolicy::Kill,
+                false => OrphanPolicy::Report,
+            };
+            let report = ctx
+                .with_dedicated_servers(|servers, ctx| {
+                    servers.sweep_orphans(region.as_ref(), policy, ctx)
+                })
+                .map_err(CliError::from)?;
+            for (subject, err) in report.failed.iter() {
+                println!("{} could not be swept: {}", subject, err);
+            }
+            for orphan in report.killed.iter() {
+                println!("killed {}", orphan);
+            }
+            for orphan in report.orphans.iter() {
+                println!("{}", orphan);
+            }
+            println!("{}", report);
+            Ok(())
+        }
+        ["strays", action @ ("adopt" | "ignore"), server] => {
+            let decision = match *action {
+                "adopt" => StrayDecision::Adopt,
+                _ => StrayDecision::Unmanaged,
+            };
+            match Stray::decide(server, decision, ctx).map_err(CliError::from)? {
+                Some(stray) if decision == StrayDecision::Adopt => {
+                    println!("{}: the monitor adopts it at its next reconcile", stray)
+                }
+                Some(stray) => println!("{}: left running untracked", stray),
+                None => {
+                    return Err(CliError::NotFound(format!(
+                        "{} is not a detected stray (see `strays`)",
+                        server
+                    )))
+                }
+            }
+            Ok(())
+        }
+        ["prewarm"] => {
+            let decisions = plan_selected_scaling(args, ctx)?;
+            let launches = prewarm::get_launches(&decisions, 100, ctx);
+            let report = prewarm::prewarm(&launches, args.has_flag("dry-run"), ctx);
+            println!("{}", report);
+            if !report.is_success() {
+                return Err(CliError::CommandFailed(
+                    "Some nodes could not be prewarmed".into(),
+                ));
+            }
+            Ok(())
+        }
+        ["launch"] => {
+            let decisions = plan_selected_scaling(args, ctx)?;
+            let missing = launcher::get_missing(&decisions, ctx);
+            let plan = launcher::plan(&missing, ctx);
+            if args.has_flag("dry-run") || plan.launches.is_empty() {
+                println!("{}", plan);
+                return Ok(());
+            }
+            for (node, reason) in plan.skipped.iter() {
+                println!("skipping {}: {}", node, reason);
+            }
+            for (group, count) in plan.unplaced.iter() {
+                println!("{}: no node has room for {} more instance(s)", group, count);
+            }
+            let info = ctx.get_config().launch.clone();
+            let concurrency = args
+                .parse_flag::<usize>("concurrency")?
+                .unwrap_or(info.concurrency);
+            let per_node = args
+                .parse_flag::<usize>("per-node")?
+                .unwrap_or(info.per_node);
+            let report = launcher::run(
+                plan,
+                concurrency,
+                per_node,
+                |done, total, outcome| match &outcome.result {
+                    Ok(_) => println!(
+                        "[{}/{}] {} ({}ms)",
+                        done,
+                        total,
+                        outcome.target,
+                        outcome.took.as_millis()
+                    ),
+                    Err(err) => println!("[{}/{}] {} failed: {}", done, total, outcome.target, err),
+                },
+                ctx,
+            );
+            println!("{}", report);
+            if !report.is_success() {
+                return Err(CliError::CommandFailed(format!(
+                    "{} of {} launch(es) failed",
+                    report.get_failed_count(),
+                    report.outcomes.len()
+                )));
+            }
+            Ok(())
+        }
+        ["recordings"] => {
+            let region = args
+                .get_flag("region")
+                .map(|region| Region::try_from(region.clone()))
+                .transpose()
+                .map_err(|err| CliError::Usage(err.to_string()))?;
+            let info = ctx.get_config().recorder.clone();
+            let paths = Recording::list(&info, region.as_ref()).map_err(CliError::from)?;
+            let mut table = Table::new(&["file", "region", "recorded", "groups", "off target"]);
+            for path in paths {
+                let recording = Recording::load(&path.to_string_lossy()).map_err(CliError::from)?;
+                table.add_row(vec![
+                    path.display().to_string(),
+                    recording
+                        .region
+                        .as_ref()
+                        .map_or("all".into(), |region| region.to_string()),
+                    table::format_time(recording.recorded_at / 1000),
+                    recording.decisions.len().to_string(),
+                    recording
+                        .decisions
+                        .iter()
+                        .filter(|decision| decision.running != decision.desired)
+                        .count()
+                        .to_string(),
+                ]);
+            }
+            table.print(args, "No reconciles recorded")
+        }
+        ["replay", file] => {
+            let recording = Recording::load(file).map_err(CliError::from)?;
+            println!(
+                "Replaying {} reconcile of {}",
+                recording
+                    .region
+                    .as_ref()
+                    .map_or("network".into(), |region| region.to_string()),
+                table::format_time(recording.recorded_at / 1000)
+            );
+            let replay = recording.replay(ctx);
+            println!("{}", replay);
+            Ok(())
+        }
+        ["regions"] => {
+            let workers = RegionHealth::get_all(ctx).map_err(CliError::from)?;
+            let mut table = Table::new(&[
+                "region",
+                "manager",
+                "ticks",
+                "last reconcile",
+                "took",
+                "missing",
+                "errors",
+                "redis errors",
+                "restarts",
+                "last error",
+            ]);
+            for worker in workers {
+                table.add_row(vec![
+                    worker.region,
+                    worker.instance_id,
+                    worker.ticks.to_string(),
+                    worker
+                        .last_reconcile
+                        .map(table::format_time)
+                        .unwrap_or_default(),
+                    format!("{}ms", worker.reconcile_ms),
+                    worker.missing.to_string(),
+                    worker.errors.to_string(),
+                    worker.redis_errors.to_string(),
+                    worker.restarts.to_string(),
+                    worker.last_error.unwrap_or_default(),
+                ]);
+            }
+            table.print(args, "No region workers are running")
+        }
+        ["summary"] => {
+            match get_summary(args, ctx)? {
+                Some(summary) => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&summary)
+                        .map_err(|err| CliError::CommandFailed(err.to_string()))?
+                ),
+                None => println!("No summary published (see monitor_info.summary_ttl_secs)"),
+            }
+            Ok(())
+        }
+        ["queue"] => {
+            let lengths = queue::get_lengths(ctx).map_err(CliError::from)?;
+            let mut table = Table::new(&["group", "queued"]);
+            for (group, length) in lengths {
+                table.add_row(vec![group, length.to_string()]);
+            }
+            table.print(args, "No players queued")
+        }
+        ["queue", "push", group, player] => {
+            let position = queue::push(group, player, ctx).map_err(CliError::from)?;
+            println!("{} is #{} in the queue for {}", player, position, group);
+            Ok(())
+        }
+        ["queue", "pop", group] => {
+            let count = args.parse_flag::<usize>("count")?.unwrap_or(1);
+            let players = queue::pop(group, count, ctx).map_err(CliError::from)?;
+            if players.is_empty() {
+                println!("Nobody is queued for {}", group);
+            }
+            for player in players {
+                println!("{}", player);
+            }
+            Ok(())
+        }
+        ["queue", "remove", group, player] => {
+            let removed = queue::remove(group, player, ctx).map_err(CliError::from)?;
+            match removed {
+                true => println!("Removed {} from the queue for {}", player, group),
+                false => println!("{} is not queued for {}", player, group),
+            }
+            Ok(())
+        }
+        ["queue", group] => {
+            let players = queue::get_players(group, ctx).map_err(CliError::from)?;
+            let mut table = Table::new(&["position", "player"]);
+            for (i, player) in players.into_iter().enumerate() {
+                table.add_row(vec![(i + 1).to_string(), player]);
+            }
+            table.print(args, &format!("Nobody is queued for {}", group))
+        }
+        ["alerts"] => {
+            let firing = AlertEngine::get_firing(ctx).map_err(CliError::from)?;
+            let mut table = Table::new(&["rule", "severity", "value", "since"]);
+            for alert in firing {
+                table.add_row(vec![
+                    alert.rule,
+                    alert.severity.to_string(),
+                    alert.value.to_string(),
+                    table::format_time(alert.since),
+                ]);
+            }
+            table.print(args, "No alerts firing")
+        }
+        ["alerts", "test"] => {
+            let rules = ctx.get_config().alerts.rules.clone();
+            if rules.is_empty() {
+                println!("No alert rules configured");
+                return Ok(());
+            }
+            let statuses = MinecraftServer::get_all(ctx).ok;
+            let sample = AlertEngine::default().sample(&statuses, ctx);
+            let filters = Filters::parse(args)?;
+            let mut table = Table::new(&["rule", "value", "state"]);
+            for check in AlertEngine::check(&rules, &sample) {
+                let state = if check.breached { "breached" } else { "ok" };
+                if filters.matches_state(state) {
+                    table.add_row(vec![check.rule, check.value.to_string(), state.into()]);
+                }
+            }
+            table.print(args, "No rules match")
+        }
+        ["recover"] => {
+            let report = ctx.recover_instances();
+            for (instance, node) in report.adopted.iter() {
+                println!("{} -> {}", instance, node);
+            }
+            for (instance, why) in report.unmatched.iter() {
+                println!("{} not adopted: {}", instance, why);
+            }
+            println!("{}", report);
+            Ok(())
+        }
+        ["events"] => {
+            let count = args.parse_flag::<usize>("count")?.unwrap_or(20);
+            let events = Event::get_recent(count, ctx).map_err(CliError::from)?;
+            for event in events.iter() {
+                println!("{}", event);
+            }
+            Ok(())
+        }
+        ["undo", "list"] => {
+            let undoable = undo::get_undoable(ctx).map_err(CliError::from)?;
+            let filters = Filters::parse(args)?;
+            let mut table = Table::new(&["n", "time", "subject", "change"]);
+            for (i, event) in undoable.iter().enumerate() {
+                if !filters.matches_group(&event.subject) {
+                    continue;
+                }
+                let change = match event.change.as_ref() {
+                    Some(change) => change.to_string(),
+                    None => format!("{} (irreversible)", event.kind),
+                };
+                table.add_row(vec![
+                    (i + 1).to_string(),
+                    table::format_time(event.timestamp),
+                    event.subject.clone(),
+                    change,
+                ]);
+            }
+            table.print(args, "Nothing to undo")
+        }
+        ["undo", rest @ ..] if rest.len() <= 1 => {
+            let position = match rest.first() {
+                Some(position) => position
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&position| position > 0)
+                    .ok_or(CliError::Usage(format!("Invalid position: {:?}", position)))?,
+                None => 1,
+            };
+            let undoable = undo::get_undoable(ctx).map_err(CliError::from)?;
+            let event = undoable
+                .get(position - 1)
+                .ok_or(CliError::from(UndoError::NothingToUndo))?;
+            let change = undo::undo(event, args.has_flag("force"), ctx).map_err(CliError::from)?;
+            println!("Reverted servergroups.{} ({})", change.group, change);
+            Ok(())
+        }
+        ["smoke", name] => {
+            let server = get_live_server(name, args, ctx)?;
+            let smoke_test = ctx.get_config().smoke_test.clone().unwrap_or_default();
+            let response = smoke_test.run(&server, ctx).map_err(CliError::from)?;
+            println!("{} passed: {}", name, response);
+            Ok(())
+        }
+        ["rcon", name, command @ ..] if !command.is_empty() => {
+            let server = get_live_server(name, args, ctx)?;
+            let output = rcon::run(&server, &[&command.join(" ")], ctx).map_err(CliError::from)?;
+            println!("{}", output.concat().trim_end());
+            Ok(())
+        }
+        ["crashloops"] => {
+            let crash_loops = CrashLoop::get_all(ctx).map_err(CliError::from)?;
+            let mut table = Table::new(&["group", "failures", "paused until", "last error"]);
+            for crash_loop in crash_loops {
+                let paused_until = match crash_loop.is_paused() {
+                    true => crash_loop.paused_until.map(table::format_time),
+                    false => None,
+                };
+                table.add_row(vec![
+                    crash_loop.group,
+                    crash_loop.failures.to_string(),
+                    paused_until.unwrap_or_default(),
+                    crash_loop.last_error,
+                ]);
+            }
+            table.print(args, "No failed launches")
+        }
+        ["crashloops", "clear", group] => {
+            let cleared = CrashLoop::clear(group, ctx).map_err(CliError::from)?;
+            match cleared {
+                true => println!("Cleared failed launches of {}", group),
+                false => println!("{} has no failed launches", group),
+            }
+            Ok(())
+        }
+        ["services"] => {
+            let mut table = Table::new(&["service", "state", "detail", "groups"]);
+            for (service, status) in ctx
+                .get_config()
+                .services
+                .clone()
+                .iter()
+                .zip(services::check_services(ctx))
+            {
+                let groups = match service.groups.is_empty() {
+                    true => "*".into(),
+                    false => service.groups.join(","),
+                };
+                let state = if status.up { "up" } else { "DOWN" };
+                table.add_row(vec![status.name, state.into(), status.detail, groups]);
+            }
+            table.print(args, "No services configured")?;
+            let safe = RewardSafe::get_all(ctx).map_err(CliError::from)?;
+            let mut table = Table::new(&["reward-safe group", "since", "services", "restores"]);
+            for entry in safe {
+                let restores = [("gems", entry.reward_gems), ("items", entry.reward_items)]
+                    .iter()
+                    .filter(|(_, enabled)| *enabled)
+                    .map(|(name, _)| *name)
+                    .collect::<Vec<_>>()
+                    .join(",");
+                table.add_row(vec![
+                    entry.group,
+                    table::format_time(entry.since),
+                    entry.services.join(","),
+                    restores,
+                ]);
+            }
+            table.print(args, "No groups in reward-safe mode")
+        }
+        ["journal"] => {
+            let pending = JournalEntry::get_pending(ctx).map_err(CliError::from)?;
+            if pending.is_empty() {
+                println!("No interrupted operations");
+            }
+            for entry in pending {
+                println!("{} {}", entry.started, entry.operation);
+            }
+            Ok(())
+        }
+        ["journal", "replay"] => {
+            let outcomes = JournalEntry::replay(ctx).map_err(CliError::from)?;
+            for outcome in outcomes {
+                println!("{}", outcome);
+            }
+            Ok(())
+        }
+        ["jars", "sync"] => {
+            let report = jars::sync(ctx);
+            for (jar, err) in report.failed.iter() {
+                println!("{}: {}", jar, err);
+            }
+            println!("{}", report);
+            if !report.failed.is_empty() {
+                return Err(CliError::CommandFailed(
+                    "Some jars could not be synced".into(),
+                ));
+            }
+            Ok(())
+        }
+        ["rotation", "preview"] => {
+            let at = match args.get_flag("at") {
+                Some(at) => chrono::DateTime::parse_from_rfc3339(at)
+                    .map_err(|err| CliError::Usage(format!("Invalid --at: {}", err)))?
+                    .to_utc(),
+                None => chrono::Utc::now(),
+            };
+            let mut table = Table::new(&["group", "slot", "games"]);
+            for rotation in ctx.get_config().rotations.iter() {
+                let slot = rotation.get_active(at).map(|slot| slot.name.clone());
+                table.add_row(vec![
+                    rotation.group.clone(),
+                    slot.unwrap_or("default".into()),
+                    rotation.get_games(at),
+                ]);
+            }
+            table.print(args, "No rotations configured")
+        }
+        ["rotation", "apply"] => {
+            let changes = rotation::plan_rotations(chrono::Utc::now(), ctx);
+            if changes.is_empty() {
+                println!("Every rotating group runs its scheduled games");
+            }
+            for change in changes {
+                if args.has_flag("dry-run") {
+                    println!("Would rotate {}", change);
+                    continue;
+                }
+                change.apply(ctx).map_err(CliError::CommandFailed)?;
+                println!("Rotated {}", change);
+                if change.restart {
+                    let at = Local::now().timestamp() + args.parse_flag::<i64>("in")?.unwrap_or(60);
+                    shutdown::install_signal_handlers();
+                    let report = ScheduledRestart::new(&change.group, at, None, None)
+                        .run(ctx)
+                        .map_err(CliError::from)?;
+                    println!("{}", report);
+                }
+            }
+            Ok(())
+        }
+        ["restart", "schedule", group] => {
+            let at = match (args.parse_flag::<i64>("in")?, args.get_flag("at")) {
+                (Some(secs), None) => Local::now().timestamp() + secs,
+                (None, Some(at)) => chrono::DateTime::parse_from_rfc3339(at)
+                    .map_err(|err| CliError::Usage(format!("Invalid --at: {}", err)))?
+                    .timestamp(),
+                _ => return Err(CliError::Usage("Pass one of --in or --at".into())),
+            };
+            let warnings = args
+                .get_flag("warn")
+                .map(|warn| {
+                    warn.split(',')
+                        .map(|secs| secs.trim().parse::<u64>())
+                        .collect::<Result<Vec<u64>, _>>()
+                        .map_err(|_| CliError::Usage(format!("Invalid --warn: {:?}", warn)))
+                })
+                .transpose()?;
+            let restart =
+                ScheduledRestart::new(group, at, warnings, args.get_flag("message").cloned());
+            shutdown::install_signal_handlers();
+            println!("Scheduled restart of {}", restart);
+            let report = restart.run(ctx).map_err(CliError::from)?;
+            for (instance, why) in report.failed.iter() {
+                println!("{} failed: {}", instance, why);
+            }
+            println!("{}", report);
+            Ok(())
+        }
+        ["restart", "cancel", group] => {
+            let cancelled = ScheduledRestart::cancel(group, ctx).map_err(CliError::from)?;
+            match cancelled {
+                true => println!("Cancelled restart of {}", group),
+                false => println!("No restart of {} is scheduled", group),
+            }
+            Ok(())
+        }
+        ["restart", "list"] => {
+            let restarts = ScheduledRestart::get_all(ctx).map_err(CliError::from)?;
+            if restarts.is_empty() {
+                println!("No restarts scheduled");
+            }
+            for restart in restarts {
+                println!("{}", restart);
+            }
+            Ok(())
+        }
+        ["backup", path] => {
+            let snapshot = Snapshot::capture(ctx).map_err(CliError::from)?;
+            snapshot.save(path).map_err(CliError::from)?;
+            println!(
+                "Saved {} keys to {}",
+                snapshot.strings.len()
+                    + snapshot.hashes.len()
+                    + snapshot.sets.len()
+                    + snapshot.lists.len(),
+                path
+            );
+            Ok(())
+        }
+        ["restore", path] => {
+            let snapshot = Snapshot::load(path).map_err(CliError::from)?;
+            let mut writer = BulkWriter::default();
+            if let Some(chunk_size) = args.parse_flag::<usize>("chunk")? {
+                writer.chunk_size = chunk_size;
+            }
+            let report = writer.write(
+                &snapshot.to_writes(),
+                &mut |progress| println!("{}", progress),
+                ctx,
+            );
+            for (key, err) in report.failed.iter() {
+                println!("{} could not be written: {}", key, err);
+            }
+            println!("{}", report);
+            if !report.failed.is_empty() {
+                return Err(CliError::CommandFailed("Restore was incomplete".into()));
+            }
+            Ok(())
+        }
+        ["mirror", "verify"] => {
+            let config = ctx.get_config().get_mirror_config().ok_or(CliError::Usage(
+                "redis_conn.mirror is not configured".into(),
+            ))?;
+            let mut mirror = ContextManager::try_from_config_for(&config, args.get_purpose())
+                .map_err(CliError::from)?;
+            let patterns = vec![args.get_flag("match").cloned().unwrap_or("*".into())];
+            let report = mirror::verify(&patterns, ctx, &mut mirror).map_err(CliError::from)?;
+            for key in report.missing.iter() {
+                println!("missing  {}", key);
+            }
+            for key in report.extra.iter() {
+                println!("extra    {}", key);
+            }
+            for key in report.differing.iter() {
+                println!("differs  {}", key);
+            }
+            println!("{}", report);
+            if !report.is_consistent() {
+                return Err(CliError::Invalid("Mirror differs from redis".into()));
+            }
+            Ok(())
+        }
+        ["mock", "run"] => {
+            let groups = args.parse_flag::<usize>("groups")?.unwrap_or(5);
+            let servers = args.parse_flag::<usize>("servers")?.unwrap_or(20);
+            let interval = args.parse_flag::<u64>("interval")?.unwrap_or(3);
+            let rounds = args.parse_flag::<usize>("rounds")?;
+            println!(
+                "Writing {} synthetic statuses over {} groups every {}s",
+                servers, groups, interval
+            );
+            MockNetwork::new(groups, servers, ctx.get_clock().now())
+                .run(Duration::from_secs(interval), rounds, ctx)
+                .map_err(CliError::from)
+        }
+        ["mock", "clear"] => {
+            let removed = mock::clear(ctx).map_err(CliError::from)?;
+            println!("Removed {} synthetic keys", removed);
+            Ok(())
+        }
+        ["shell"] => {
+            shell::run_shell(ctx);
+            Ok(())
+        }
+        [] => Err(CliError::Usage("No command given".into())),
+        _ => Err(CliError::Usage(format!(
+            "Unknown command: {:?}",
+            args.positional.join(" ")
+        ))),
+    }
+}
+
+fn create_group(name: &str, args: &Args, ctx: &mut ContextManager) -> Result<(), CliError> {
+    let mut group = match args.parse_flag::<Preset>("preset")? {
+        Some(preset) => {
+            let region = match args.get_flag("region") {
+                Some(region) => Region::try_from(region.clone())
+                    .map_err(|err| CliError::Usage(err.to_string()))?,
+                None => Region::default(),
+            };
+            let tier = args.parse_flag::<SizeTier>("tier")?.unwrap_or_default();
+            preset.to_server_group(name, region, tier)
+        }
+        None => ServerGroup::from_game(Game::from_str(name, ctx).map_err(CliError::from)?),
+    };
+    group.create(ctx).map_err(CliError::from)?;
+    println!(
+        "Created servergroups.{} (port section {}, {}MB, {} cpu)",
+        group.prefix, group.port_section, group.ram, group.cpu
+    );
+    Ok(())
+}
+
+fn get_summary(args: &Args, ctx: &mut ContextManager) -> Result<Option<NetworkSummary>, CliError> {
+    //! The published network.summary, or one built from live statuses with --live.
+    if !args.has_flag("live") {
+        return NetworkSummary::get(ctx).map_err(CliError::from);
+    }
+    let mut summary = NetworkSummary::from_statuses(&MinecraftServer::get_all(ctx).ok);
+    summary.add_queues(ctx).map_err(CliError::CommandFailed)?;
+    summary.add_boosters(&ServerGroup::get_all(ctx).ok);
+    Ok(Some(summary))
+}
+
+fn kill_instance(name: &str, args: &Args, ctx: &mut ContextManager) -> Result<(), CliError> {
+    //! Kills an instance on the node tracking it, see `DedicatedServer::kill_server`.
+    ctx.recover_instances();
+    let group = ctx
+        .get_dedicated_servers()
+        .servers
+        .iter()
+        .find_map(|ds| ds.get_instance(name))
+        .map(|instance| instance.get_group().clone())
+        .ok_or(CliError::NotFound(format!("No node runs {:?}", name)))?;
+    let group = ServerGroup::from_str(&group, ctx).map_err(CliError::from)?;
+    let server_num = MCSInstance::calculate_server_num(name);
+    let force = args.has_flag("force");
+    ctx.with_dedicated_servers(|servers, ctx| {
+        let ds = servers
+            .servers
+            .iter_mut()
+            .find(|ds| ds.get_instance(name).is_some())
+            .ok_or(CliError::NotFound(format!("No node runs {:?}", name)))?;
+        safety::confirm_or_force(force, |force| {
+            ds.kill_server(&group, server_num, force, ctx)
+        })
+        .map_err(CliError::from)?;
+        println!("Killed {} on {}", name, ds.name);
+        Ok(())
+    })
+}
+
+fn get_live_server(
+    name: &str,
+    args: &Args,
+    ctx: &mut ContextManager,
+) -> Result<MinecraftServer, CliError> {
+    //! Status of an instance in `--region` (US by default).
+    let region = args
+        .get_flag("region")
+        .map(|region| Region::try_from(region.clone()))
+        .transpose()
+        .map_err(|err| CliError::Usage(err.to_string()))?
+        .unwrap_or_default();
+    MinecraftServer::get_status(&name.to_string(), &region, ctx).map_err(CliError::from)
+}
+
+fn print_map_pool(group: &ServerGroup, pool: &MapPool) {
+    if pool.is_empty() {
+        println!("servergroups.{} runs no known game", group.prefix);
+    }
+    for (game, maps) in pool.iter() {
+        println!(
+            "{} {}: {}",
+            group.prefix,
+            game,
+            maps.iter().cloned().collect::<Vec<_>>().join(", ")
+        );
+    }
+}
+
+/// An instance picked by `--group`, `--region`, `--state` and `--label`.
+struct SelectedInstance {
+    instance: MCSInstance,
+    node: String,
+    state: String,
+    labels: Labels,
+}
+
+fn select_instances(
+    args: &Args,
+    ctx: &mut ContextManager,
+) -> Result<Vec<SelectedInstance>, CliError> {
+    let selector = match args.get_flag("label") {
+        Some(selector) => labels::parse_selector(selector).map_err(CliError::Usage)?,
+        None => Labels::new(),
+    };
+    let filters = Filters::parse(args)?;
+    let queried: Option<Vec<String>> = match filters.query.is_some() {
+        true => {
+            let mut names = Vec::new();
+            for group in ServerGroup::get_all(ctx).ok {
+                if filters.matches_query(&group)? {
+                    names.push(group.name);
+                }
+            }
+            Some(names)
+        }
+        false => None,
+    };
+    ctx.recover_instances();
+    let mut labels = labels::get_all_labels(LabelTarget::Instance, ctx).map_err(CliError::from)?;
+    let servers = ctx.get_dedicated_servers().servers.clone();
+    let mut matching = Vec::new();
+    for ds in servers.iter().filter(|ds| filters.matches_node(&ds.name)) {
+        for instance in ds.server_instances.values().flatten() {
+            let instance_labels = labels.remove(instance.get_name()).unwrap_or_default();
+            if !filters.matches_group(instance.get_group())
+                || !filters.matches_region(instance.get_region())
+                || queried
+                    .as_ref()
+                    .is_some_and(|names| !names.contains(instance.get_group()))
+                || !labels::matches_selector(&instance_labels, &selector)
+            {
+                continue;
+            }
+            matching.push((instance.clone(), ds.name.clone(), instance_labels));
+        }
+    }
+    let instances: Vec<MCSInstance> = matching
+        .iter()
+        .map(|(instance, ..)| instance.clone())
+        .collect();
+    let statuses = refresh::refresh_statuses(&instances, ctx);
+    let mut selected = Vec::new();
+    for ((instance, node, labels), status) in matching.into_iter().zip(statuses) {
+        let state = match status {
+            Ok(status) => format!("{:?}", status).to_lowercase(),
+            Err(err) => {
+                println!("{}: status unknown, {}", instance.get_name(), err);
+                "unknown".into()
+            }
+        };
+        if !filters.matches_state(&state) {
+            continue;
+        }
+        selected.push(SelectedInstance {
+            instance,
+            node,
+            state,
+            labels,
+        });
+    }
+    Ok(selected)
+}
+
+fn list_instances(args: &Args, ctx: &mut ContextManager) -> Result<(), CliError> {
+    let mut table = Table::new(&[
+        "instance", "group", "node", "region", "port", "state", "labels",
+    ]);
+    for selected in select_instances(args, ctx)? {
+        let instance = &selected.instance;
+        table.add_row(vec![
+            instance.get_name().clone(),
+            instance.get_group().clone(),
+            selected.node,
+            instance.get_region().to_string(),
+            instance.get_port().to_string(),
+            selected.state,
+            labels::format_labels(&selected.labels),
+        ]);
+    }
+    table.print(args, "No instances")
+}
+
+fn plan_selected_scaling(
+    args: &Args,
+    ctx: &mut ContextManager,
+) -> Result<Vec<strategy::ScalingDecision>, CliError> {
+    //! Scaling decisions for the groups --region and --group select (all if neither is given).
+    let region = match args.get_flag("region") {
+        Some(region) => {
+            Some(Region::try_from(region.clone()).map_err(|err| CliError::Usage(err.to_string()))?)
+        }
+        None => None,
+    };
+    let group = args.get_flag("group").cloned();
+    Ok(strategy::plan_scaling_where(
+        |candidate| {
+            region
+                .as_ref()
+                .is_none_or(|region| &candidate.region == region)
+                && group
+                    .as_ref()
+                    .is_none_or(|group| &candidate.prefix == group)
+        },
+        ctx,
+    ))
+}
+
+fn foreach(action: &[&str], args: &Args, ctx: &mut ContextManager) -> Result<(), CliError> {
+    //! Runs one action over the selected instances through the batch engine.
+    if !["group", "region", "node", "state", "label", "where"]
+        .iter()
+        .any(|flag| args.has_flag(flag))
+    {
+        return Err(CliError::Usage(
+            "foreach needs at least one of --group, --region, --node, --state, --label or --where"
+                .into(),
+        ));
+    }
+    let style = args
+        .parse_flag::<BroadcastStyle>("style")?
+        .unwrap_or_default();
+    let run: BatchAction<SelectedInstance> = match action {
+        ["restart"] => Box::new(|selected, ctx| {
+            let instance = &selected.instance;
+            let group =
+                ServerGroup::from_str(instance.get_group(), ctx).map_err(|err| err.to_string())?;
+            restart::restart_instance(&group, instance.get_server_num(), ctx)?;
+            Ok("restarted".into())
+        }),
+        ["broadcast", message] => {
+            let message = message.to_string();
+            Box::new(move |selected, ctx| {
+                let instance = &selected.instance;
+                let received = ServerCommand::Broadcast {
+                    target_server: Some(instance.get_name().clone()),
+                    message: message
+                        .replace("{server}", instance.get_name())
+                        .replace("{group}", instance.get_group()),
+                    style,
+                }
+                .publish(ctx)
+                .map_err(|err| err.to_string())?;
+                Ok(format!("received by {} subscriber(s)", received))
+            })
+        }
+        ["join", status] => {
+            let join_status = GameJoinStatus::from_str(&status.to_uppercase())
+                .map_err(|_| CliError::Usage(format!("Unknown join status: {:?}", status)))?;
+            Box::new(move |selected, ctx| {
+                let received = ServerCommand::JoinStatus {
+                    target_server: selected.instance.get_name().clone(),
+                    join_status: join_status.clone(),
+                }
+                .publish(ctx)
+                .map_err(|err| err.to_string())?;
+                Ok(format!("{} ({} subscriber(s))", join_status, received))
+            })
+        }
+        _ => {
+            return Err(CliError::Usage(format!(
+                "Unknown foreach action: {}",
+                action.join(" ")
+            )))
+        }
+    };
+    let concurrency = args
+        .parse_flag::<usize>("concurrency")?
+        .unwrap_or(batch::DEFAULT_CONCURRENCY);
+    let selected = select_instances(args, ctx)?;
+    if selected.is_empty() {
+        println!("No instances selected");
+        return Ok(());
+    }
+    let report = batch::run(selected, concurrency, run, ctx);
+    let mut table = Table::new(&["instance", "group", "node", "result", "detail", "took"]);
+    for outcome in report.outcomes.iter() {
+        let instance = &outcome.target.instance;
+        let (result, detail) = match &outcome.result {
+            Ok(detail) => ("ok", detail.clone()),
+            Err(err) => ("failed", err.clone()),
+        };
+        table.add_row(vec![
+            instance.get_name().clone(),
+            instance.get_group().clone(),
+            outcome.target.node.clone(),
+            result.into(),
+            detail,
+            format!("{}ms", outcome.took.as_millis()),
+        ]);
+    }
+    table.print(args, "No instances selected")?;
+    println!("{}", report);
+    if !report.is_success() {
+        return Err(CliError::CommandFailed(format!(
+            "{} of {} instance(s) failed",
+            report.get_failed_count(),
+            report.outcomes.len()
+        )));
+    }
+    Ok(())
+}
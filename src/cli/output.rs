@@ -0,0 +1,32 @@
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Prints the library's diagnostics to stdout, tagged with their target when it names a
+/// subsystem (`[event] ...`, `[redis] ...`) rather than a module.
+struct StdoutLogger;
+
+impl Log for StdoutLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match record.target().contains("::") || record.target() == env!("CARGO_CRATE_NAME") {
+            true => println!("{}", record.args()),
+            false => println!("[{}] {}", record.target(), record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StdoutLogger = StdoutLogger;
+
+pub fn install() {
+    //! Routes library diagnostics to stdout. Only the first call takes effect.
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+}
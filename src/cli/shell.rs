@@ -0,0 +1,236 @@
+use std::{
+    collections::BTreeSet,
+    io::{self, BufRead, Read, Write},
+};
+
+use crate::context_manager::ContextManager;
+
+use super::{completion, Args, CliError, USAGE};
+
+const PROMPT: &str = "plexr> ";
+const BUILTINS: [&str; 3] = ["exit", "help", "quit"];
+
+/// Puts the terminal in non-canonical, no-echo mode for as long as it lives.
+struct RawMode {
+    original: libc::termios,
+}
+
+impl RawMode {
+    fn enable() -> Option<Self> {
+        //! None if stdin isn't a terminal (e.g. commands piped in).
+        // SAFETY: termios is plain data, and both calls only touch the struct we pass.
+        unsafe {
+            if libc::isatty(libc::STDIN_FILENO) == 0 {
+                return None;
+            }
+            let mut termios: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+                return None;
+            }
+            let original = termios;
+            termios.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+            termios.c_cc[libc::VMIN] = 1;
+            termios.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) != 0 {
+                return None;
+            }
+            Some(Self { original })
+        }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        // SAFETY: restores the settings read in `enable`.
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+fn get_command_words() -> Vec<Vec<Vec<&'static str>>> {
+    //! Leading keywords of every command in the usage text, e.g. [["maps"], ["enable", "disable"]].
+    completion::get_command_lines()
+        .map(|line| {
+            line.split_whitespace()
+                .take_while(|word| word.starts_with(|c: char| c.is_ascii_lowercase()))
+                .map(|word| word.split('|').collect())
+                .collect()
+        })
+        .collect()
+}
+
+fn get_group_names(ctx: &mut ContextManager) -> Vec<String> {
+    redis::cmd("SMEMBERS")
+        .arg(ctx.get_keys().group_index_key())
+        .query(ctx.get_connection())
+        .unwrap_or_default()
+}
+
+fn get_candidates(before: &[&str], ctx: &mut ContextManager) -> BTreeSet<String> {
+    //! Words that may follow `before`: command keywords, then group names.
+    let mut candidates: BTreeSet<String> = get_command_words()
+        .into_iter()
+        .filter(|command| {
+            command.len() > before.len()
+                && command
+                    .iter()
+                    .zip(before)
+                    .all(|(alternatives, word)| alternatives.contains(word))
+        })
+        .flat_map(|command| command[before.len()].clone())
+        .map(String::from)
+        .collect();
+    match before.is_empty() {
+        true => candidates.extend(BUILTINS.iter().map(|builtin| builtin.to_string())),
+        false => candidates.extend(get_group_names(ctx)),
+    }
+    candidates
+}
+
+fn complete(line: &mut String, ctx: &mut ContextManager) -> Option<Vec<String>> {
+    //! Completes the word being typed. Returns the options if there are several to choose from.
+    let current = line.clone();
+    let words: Vec<&str> = current.split_whitespace().collect();
+    let (before, partial) = match current.ends_with(' ') || current.is_empty() {
+        true => (&words[..], ""),
+        false => (&words[..words.len() - 1], words[words.len() - 1]),
+    };
+    let matches: Vec<String> = get_candidates(before, ctx)
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(partial))
+        .collect();
+    let common = matches.iter().skip(1).fold(
+        matches.first().cloned().unwrap_or_default(),
+        |common, candidate| {
+            common
+                .chars()
+                .zip(candidate.chars())
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect()
+        },
+    );
+    line.push_str(&common[partial.len().min(common.len())..]);
+    match matches.len() {
+        0 => None,
+        1 => {
+            line.push(' ');
+            None
+        }
+        _ if common.len() > partial.len() => None,
+        _ => Some(matches),
+    }
+}
+
+fn redraw(line: &str) {
+    print!("\r\x1b[K{}{}", PROMPT, line);
+    let _ = io::stdout().flush();
+}
+
+fn read_line(history: &[String], ctx: &mut ContextManager) -> Option<String> {
+    //! Reads one line with tab completion and history (up/down). None on Ctrl-D or EOF.
+    let Some(_raw) = RawMode::enable() else {
+        let mut line = String::new();
+        return match io::stdin().lock().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim_end().to_string()),
+        };
+    };
+    let mut line = String::new();
+    let mut position = history.len(); // index into history while browsing it
+    let mut bytes = io::stdin().lock().bytes();
+    redraw(&line);
+    loop {
+        match bytes.next()?.ok()? {
+            b'\r' | b'\n' => {
+                println!();
+                return Some(line);
+            }
+            3 => {
+                // Ctrl-C drops the line
+                println!("^C");
+                line.clear();
+            }
+            4 if line.is_empty() => {
+                println!();
+                return None;
+            }
+            8 | 127 => {
+                line.pop();
+            }
+            b'\t' => {
+                if let Some(options) = complete(&mut line, ctx) {
+                    println!("\r\n{}", options.join("  "));
+                }
+            }
+            27 => {
+                // arrow keys arrive as ESC [ A/B/C/D
+                if bytes.next()?.ok()? != b'[' {
+                    continue;
+                }
+                match bytes.next()?.ok()? {
+                    b'A' if position > 0 => position -= 1,
+                    b'B' if position < history.len() => position += 1,
+                    _ => continue,
+                }
+                line = history.get(position).cloned().unwrap_or_default();
+            }
+            byte if byte.is_ascii() && !byte.is_ascii_control() => line.push(byte as char),
+            _ => (),
+        }
+        redraw(&line);
+    }
+}
+
+fn split_line(line: &str) -> Vec<String> {
+    //! Splits on whitespace, keeping "double quoted" parts together.
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+pub fn run_shell(ctx: &mut ContextManager) {
+    //! Runs commands typed one per line against one context (and redis connection)
+    //! until `exit`, Ctrl-D or end of input. Failed commands are reported, not fatal.
+    let mut history: Vec<String> = Vec::new();
+    while let Some(line) = read_line(&history, ctx) {
+        let words = split_line(&line);
+        let Some(first) = words.first() else {
+            continue;
+        };
+        if history.last() != Some(&line) {
+            history.push(line.clone());
+        }
+        match first.as_str() {
+            "exit" | "quit" => break,
+            "help" => println!("{}", USAGE),
+            "shell" => println!("Already in the shell"),
+            _ => match Args::parse(words) {
+                Ok(args) => {
+                    if let Err(err) = super::run(&args, ctx) {
+                        eprintln!("{}", err);
+                    }
+                }
+                // `<command> --help` is not an error
+                Err(err) if !err.use_stderr() => print!("{}", err),
+                Err(err) => eprintln!("{}", CliError::from(err)),
+            },
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use std::{cmp::Ordering, fmt::Display};
+
+use chrono::Local;
+
+use crate::{
+    region::Region,
+    server::{query::GroupQuery, server_group::ServerGroup},
+};
+
+use super::{Args, CliError};
+
+const COLUMN_GAP: &str = "  ";
+
+/// Rows printed under a header with aligned columns. Numeric columns are right-aligned,
+/// and rows are sorted (by `--sort <column>`, else by every column in order) so the
+/// same data always prints the same way.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Table {
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+/// `--region`, `--group`, `--node`, `--state` and `--where` filters shared by list commands. An
+/// unset filter matches everything.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Filters {
+    pub region: Option<Region>,
+    pub group: Option<String>,
+    pub node: Option<String>,
+    pub state: Option<String>,
+    pub query: Option<GroupQuery>, // over group fields, see `GroupQuery`
+}
+
+pub fn format_time(at: i64) -> String {
+    //! Seconds since epoch as local RFC 3339, or the raw number if out of range.
+    chrono::DateTime::from_timestamp(at, 0)
+        .map(|time| time.with_timezone(&Local).to_rfc3339())
+        .unwrap_or(at.to_string())
+}
+
+fn compare_cells(a: &str, b: &str) -> Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.total_cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
+impl Table {
+    pub fn new(headers: &[&'static str]) -> Self {
+        Self {
+            headers: headers.to_vec(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn add_row(&mut self, row: Vec<String>) {
+        debug_assert_eq!(row.len(), self.headers.len());
+        self.rows.push(row);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn sort(&mut self, column: Option<&str>) -> Result<(), CliError> {
+        //! Sorts by `column` (case-insensitive header name), ties broken by the whole row.
+        let index = column
+            .map(|column| {
+                self.headers
+                    .iter()
+                    .position(|header| header.eq_ignore_ascii_case(column))
+                    .ok_or(CliError::Usage(format!(
+                        "Unknown column {:?} (one of {})",
+                        column,
+                        self.headers.join(", ")
+                    )))
+            })
+            .transpose()?;
+        self.rows.sort_by(|a, b| {
+            let by_column = index.map_or(Ordering::Equal, |i| compare_cells(&a[i], &b[i]));
+            by_column.then_with(|| {
+                a.iter()
+                    .zip(b)
+                    .map(|(a, b)| compare_cells(a, b))
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            })
+        });
+        Ok(())
+    }
+
+    pub fn print(mut self, args: &Args, empty: &str) -> Result<(), CliError> {
+        //! Sorts by `--sort` and prints the table, or `empty` if it has no rows.
+        if self.is_empty() {
+            println!("{}", empty);
+            return Ok(());
+        }
+        self.sort(args.get_flag("sort").map(|column| column.as_str()))?;
+        print!("{}", self);
+        Ok(())
+    }
+
+    fn is_numeric(&self, column: usize) -> bool {
+        self.rows
+            .iter()
+            .all(|row| row[column].is_empty() || row[column].parse::<f64>().is_ok())
+    }
+}
+
+impl Display for Table {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let widths: Vec<usize> = (0..self.headers.len())
+            .map(|i| {
+                self.rows
+                    .iter()
+                    .map(|row| row[i].chars().count())
+                    .chain([self.headers[i].len()])
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+        let numeric: Vec<bool> = (0..self.headers.len())
+            .map(|i| self.is_numeric(i))
+            .collect();
+        let header: Vec<String> = self.headers.iter().map(|h| h.to_uppercase()).collect();
+        for row in std::iter::once(&header).chain(self.rows.iter()) {
+            let cells: Vec<String> = row
+                .iter()
+                .enumerate()
+                .map(|(i, cell)| match numeric[i] {
+                    true => format!("{:>width$}", cell, width = widths[i]),
+                    false => format!("{:<width$}", cell, width = widths[i]),
+                })
+                .collect();
+            // no trailing spaces, so output diffs cleanly
+            writeln!(f, "{}", cells.join(COLUMN_GAP).trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+impl Filters {
+    pub fn parse(args: &Args) -> Result<Self, CliError> {
+        let region = args
+            .get_flag("region")
+            .map(|region| Region::try_from(region.clone()))
+            .transpose()
+            .map_err(|err| CliError::Usage(err.to_string()))?;
+        Ok(Self {
+            region,
+            group: args.get_flag("group").cloned(),
+            node: args.get_flag("node").cloned(),
+            state: args.get_flag("state").map(|state| state.to_lowercase()),
+            query: args
+                .get_flag("where")
+                .map(|expr| expr.parse())
+                .transpose()
+                .map_err(CliError::from)?,
+        })
+    }
+
+    pub fn matches_region(&self, region: &Region) -> bool {
+        self.region.as_ref().is_none_or(|wanted| wanted == region)
+    }
+
+    pub fn matches_group(&self, group: &str) -> bool {
+        self.group.as_ref().is_none_or(|wanted| wanted == group)
+    }
+
+    pub fn matches_node(&self, node: &str) -> bool {
+        self.node.as_ref().is_none_or(|wanted| wanted == node)
+    }
+
+    pub fn matches_query(&self, group: &ServerGroup) -> Result<bool, CliError> {
+        self.query
+            .as_ref()
+            .map_or(Ok(true), |query| query.matches(group))
+            .map_err(CliError::from)
+    }
+
+    pub fn matches_state(&self, state: &str) -> bool {
+        self.state
+            .as_ref()
+            .is_none_or(|wanted| wanted.eq_ignore_ascii_case(state))
+    }
+}
@@ -0,0 +1,53 @@
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Local};
+
+/// Where the manager reads the current time from. The system clock by default; tests (and
+/// replays) register a `FixedClock` with `ContextManager::set_clock` to control it.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Local>;
+
+    fn now_millis(&self) -> u64 {
+        self.now().timestamp_millis() as u64
+    }
+}
+
+/// Built-in clock: the system's local time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time, so a test can keep
+/// one to advance after registering another.
+#[derive(Clone, Debug)]
+pub struct FixedClock {
+    now: Arc<Mutex<DateTime<Local>>>,
+}
+
+impl FixedClock {
+    pub fn new(now: DateTime<Local>) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    pub fn set(&self, now: DateTime<Local>) {
+        *self.now.lock().unwrap_or_else(|err| err.into_inner()) = now;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        let mut now = self.now.lock().unwrap_or_else(|err| err.into_inner());
+        *now += by;
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Local> {
+        *self.now.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
@@ -1,17 +1,57 @@
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::HashMap,
     fs::{self, File},
     io::Read,
+    time::Duration,
 };
 
-use lazy_static::lazy_static;
+use rand::Rng;
+use redis::ConnectionInfo;
 use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter};
 
+#[cfg(feature = "cluster")]
+use crate::store::cluster::ShardedConnection;
+#[cfg(feature = "sentinel")]
+use crate::store::sentinel::FailoverConnection;
 use crate::{
-    region::Region,
-    server::dedicated::{
-        collection::DedicatedServers, server::DedicatedServer, System, SystemName,
+    game::r#type::GameType,
+    jars::JarsInfo,
+    monitor::{
+        alerts::AlertsInfo, chaos::ChaosInfo, forecast::ForecastInfo, recorder::RecorderInfo,
+        services::ServiceCheck, stream::StreamInfo,
     },
+    server::{
+        crash_loop::CrashLoopInfo,
+        dedicated::{
+            collection::DedicatedServers, healing::HealingInfo, launcher::LaunchInfo,
+            orphans::OrphansInfo, prewarm::PrewarmInfo, refresh::RefreshInfo,
+            sampling::SamplingInfo, server::DedicatedServer, telemetry::TelemetryInfo, System,
+            SystemName,
+        },
+        ensure,
+        rcon::RconInfo,
+        recycling::RecyclingInfo,
+        rightsizing::RightsizingInfo,
+        rotation::Rotation,
+        smoke::SmokeTestInfo,
+        version::ProxyInfo,
+    },
+    store::{
+        acl::{self, AclUsers, ConnectionPurpose},
+        connection::Connection,
+        keys::KeyBuilder,
+        mirror::MirroredConnection,
+        retry::{RetryPolicy, RetryingConnection},
+        scan::DEFAULT_PAGE_SIZE,
+        snapshot::{Snapshot, SnapshotConnection},
+    },
+    strategy::ScalingInfo,
+};
+#[cfg(feature = "client-cache")]
+use crate::{
+    server::server_group::ServerGroup,
+    store::{cache::CachedConnection, entity::RedisEntity},
 };
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -19,6 +59,60 @@ pub struct Config {
     redis_conn: RedisConfig,
     pub sys_info: System,
     pub monitor_info: MonitorInfo,
+    #[serde(default)]
+    pub resources: ResourceDefaults,
+    #[serde(default)]
+    pub rebalance: RebalanceInfo,
+    #[serde(default)]
+    pub ports: PortAllocationInfo,
+    #[serde(default)]
+    pub keys: KeyBuilder,
+    #[serde(default)]
+    pub proxy: ProxyInfo,
+    #[serde(default)]
+    pub jars: JarsInfo,
+    #[serde(default)]
+    pub alerts: AlertsInfo,
+    #[serde(default)]
+    pub scaling: ScalingInfo,
+    #[serde(default)]
+    pub forecast: ForecastInfo,
+    #[serde(default)]
+    pub prewarm: PrewarmInfo,
+    #[serde(default)]
+    pub launch: LaunchInfo,
+    #[serde(default)]
+    pub recorder: RecorderInfo,
+    #[serde(default)]
+    pub stream: StreamInfo,
+    #[serde(default)]
+    pub telemetry: TelemetryInfo,
+    #[serde(default)]
+    pub recycling: RecyclingInfo,
+    #[serde(default)]
+    pub sampling: SamplingInfo,
+    #[serde(default)]
+    pub orphans: OrphansInfo,
+    #[serde(default)]
+    pub healing: HealingInfo,
+    #[serde(default)]
+    pub status_refresh: RefreshInfo,
+    #[serde(default)]
+    pub chaos: ChaosInfo,
+    #[serde(default)]
+    pub rightsizing: RightsizingInfo,
+    #[serde(default)]
+    pub rotations: Vec<Rotation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smoke_test: Option<SmokeTestInfo>, // run on launched instances before they open (off if unset)
+    #[serde(default)]
+    pub crash_loop: CrashLoopInfo,
+    #[serde(default)]
+    pub rcon: RconInfo,
+    #[serde(default)]
+    pub services: Vec<ServiceCheck>, // external services reward-granting groups depend on
+    #[serde(default = "ensure::default_protected_fields")]
+    pub protected_fields: Vec<String>, // group fields apply and `group set` won't change unless allowed
     pub dedicated_servers: DedicatedServers,
 }
 
@@ -26,6 +120,47 @@ pub struct Config {
 pub struct RedisConfig {
     pub address: String,
     pub port: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>, // serve data from this backup file instead of redis
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cluster_nodes: Vec<String>, // `host:port` seeds of a Redis Cluster, used instead of address/port
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sentinel: Option<SentinelInfo>, // find the master through sentinels instead of address/port
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror: Option<MirrorInfo>, // also apply every write here, while migrating to it
+    #[serde(default)]
+    pub users: AclUsers, // ACL users per connection purpose
+    #[serde(default = "default_scan_count")]
+    pub scan_count: usize, // keys asked for per SCAN step: fewer round trips vs shorter steps
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32, // reconnects tried when the connection was refused or dropped, 0 fails at once
+    #[serde(default = "default_retry_backoff_ms")]
+    pub retry_backoff_ms: u64, // wait before the first reconnect, doubled for each next one
+}
+
+fn default_scan_count() -> usize {
+    DEFAULT_PAGE_SIZE
+}
+
+fn default_max_retries() -> u32 {
+    5
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    100
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SentinelInfo {
+    pub addresses: Vec<String>, // `host:port` of each sentinel
+    pub master_name: String,
+}
+
+/// The redis being migrated to. It gets the same ACL users as the primary.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct MirrorInfo {
+    pub address: String,
+    pub port: String,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -33,6 +168,287 @@ pub struct MonitorInfo {
     scripts_path: String, // should be turned in to Path objects
     worlds_path: String,
     config_path: String,
+    #[serde(default)]
+    timing: MonitorTiming,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    instance_id: Option<String>, // defaults to <hostname>-<pid>
+    #[serde(default = "default_refetch_warning")]
+    refetch_warning: usize, // warn when one group hash is read more often than this per tick
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    summary_ttl_secs: Option<u64>, // publish `network.summary` with this expiry (off if unset)
+    #[serde(default)]
+    startup: StartupProbe,
+}
+
+fn default_refetch_warning() -> usize {
+    5
+}
+
+/// Work the monitor loop does on its own cadence.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Eq, Hash, PartialEq, EnumIter, Display)]
+pub enum MonitorTask {
+    Statuses,
+    Reconcile,
+    Rebalance,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct MonitorTiming {
+    pub interval_ms: u64, // how often the loop wakes up to check for due tasks
+    pub jitter_ms: u64,   // random extra delay per tick, so several managers don't sync up
+    pub statuses_secs: u64,
+    pub reconcile_secs: u64,
+    pub rebalance_secs: u64,
+    #[serde(default = "default_heartbeat_ttl")]
+    pub heartbeat_ttl_secs: u64, // a manager missing heartbeats this long is considered dead
+}
+
+fn default_heartbeat_ttl() -> u64 {
+    15
+}
+
+impl Default for MonitorTiming {
+    fn default() -> Self {
+        Self {
+            interval_ms: 1000,
+            jitter_ms: 250,
+            statuses_secs: 3,
+            reconcile_secs: 10,
+            rebalance_secs: 600,
+            heartbeat_ttl_secs: default_heartbeat_ttl(),
+        }
+    }
+}
+
+impl MonitorTiming {
+    pub fn get_cadence(&self, task: MonitorTask) -> Duration {
+        Duration::from_secs(match task {
+            MonitorTask::Statuses => self.statuses_secs,
+            MonitorTask::Reconcile => self.reconcile_secs,
+            MonitorTask::Rebalance => self.rebalance_secs,
+        })
+    }
+
+    pub fn set_cadence(&mut self, task: MonitorTask, cadence: Duration) {
+        let secs = cadence.as_secs().max(1);
+        match task {
+            MonitorTask::Statuses => self.statuses_secs = secs,
+            MonitorTask::Reconcile => self.reconcile_secs = secs,
+            MonitorTask::Rebalance => self.rebalance_secs = secs,
+        }
+    }
+
+    pub fn get_heartbeat_ttl(&self) -> Duration {
+        Duration::from_secs(self.heartbeat_ttl_secs)
+    }
+
+    pub fn get_interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms)
+    }
+
+    pub fn get_tick(&self) -> Duration {
+        //! Interval plus a random jitter, to sleep between loop iterations.
+        let jitter = match self.jitter_ms {
+            0 => 0,
+            jitter => rand::thread_rng().gen_range(0..=jitter),
+        };
+        Duration::from_millis(self.interval_ms + jitter)
+    }
+}
+
+/// When a launched instance counts as started.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, Eq, PartialEq, Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Readiness {
+    /// Its status key exists; the smoke test (if configured) runs once afterwards.
+    #[default]
+    StatusPresent,
+    /// Its status shows it open to joins; the smoke test runs once afterwards.
+    Joinable,
+    /// It passes the smoke test, retried every probe until the timeout.
+    /// Without a configured smoke test, the status being present is enough.
+    SmokeTest,
+}
+
+/// How a launch waits for the new instance to start.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct StartupProbe {
+    #[serde(default = "default_probe_interval")]
+    pub interval_secs: u64,
+    #[serde(default = "default_probe_timeout")]
+    pub timeout_secs: u64,
+    #[serde(default)]
+    pub ready_when: Readiness,
+}
+
+fn default_probe_interval() -> u64 {
+    5
+}
+
+fn default_probe_timeout() -> u64 {
+    40
+}
+
+impl Default for StartupProbe {
+    fn default() -> Self {
+        Self {
+            interval_secs: default_probe_interval(),
+            timeout_secs: default_probe_timeout(),
+            ready_when: Readiness::default(),
+        }
+    }
+}
+
+impl StartupProbe {
+    pub fn get_interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs.max(1))
+    }
+
+    pub fn get_timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Resources {
+    pub ram: u16, // in MB
+    pub cpu: u8,
+}
+
+/// Resources given to new ServerGroups.
+/// Game type specific defaults win over plugin specific ones, which win over the global default.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ResourceDefaults {
+    pub ram: u16,
+    pub cpu: u8,
+    #[serde(default = "default_player_counts")]
+    pub players: (u8, u8), // (min, max) for game types without explicit player counts
+    #[serde(default)]
+    pub games: HashMap<GameType, Resources>,
+    #[serde(default)]
+    pub plugins: HashMap<String, Resources>,
+}
+
+impl Default for ResourceDefaults {
+    fn default() -> Self {
+        Self {
+            ram: 512,
+            cpu: 1,
+            players: default_player_counts(),
+            games: HashMap::new(),
+            plugins: HashMap::new(),
+        }
+    }
+}
+
+fn default_player_counts() -> (u8, u8) {
+    (8, 16)
+}
+
+impl ResourceDefaults {
+    pub fn get_resources(&self, game: &GameType, plugin: &str) -> Resources {
+        self.games
+            .get(game)
+            .or(self.plugins.get(plugin))
+            .cloned()
+            .unwrap_or(Resources {
+                ram: self.ram,
+                cpu: self.cpu,
+            })
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct RebalanceInfo {
+    pub enabled: bool,
+    pub max_moves: usize, // instance moves per pass
+}
+
+impl Default for RebalanceInfo {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_moves: 2,
+        }
+    }
+}
+
+/// How new ServerGroups get their port section.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum PortAllocationStrategy {
+    /// Any free section in the range.
+    #[default]
+    Random,
+    /// The lowest free section in the range, so layouts are reproducible.
+    Sequential,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PortAllocationInfo {
+    pub strategy: PortAllocationStrategy,
+    pub range: (u16, u16), // inclusive range of port section starts
+}
+
+impl Default for PortAllocationInfo {
+    fn default() -> Self {
+        Self {
+            strategy: PortAllocationStrategy::default(),
+            range: (25566, 26000),
+        }
+    }
+}
+
+impl MonitorInfo {
+    pub fn get_scripts_path(&self) -> &String {
+        &self.scripts_path
+    }
+
+    pub fn set_scripts_path(&mut self, path: String) {
+        self.scripts_path = path;
+    }
+
+    pub fn get_worlds_path(&self) -> &String {
+        &self.worlds_path
+    }
+
+    pub fn get_timing(&self) -> &MonitorTiming {
+        &self.timing
+    }
+
+    pub fn get_instance_id(&self) -> String {
+        //! Identifies this manager process among others sharing the same redis.
+        self.instance_id.clone().unwrap_or_else(|| {
+            let host = std::env::var("HOSTNAME")
+                .ok()
+                .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+                .map(|host| host.trim().to_string())
+                .filter(|host| !host.is_empty())
+                .unwrap_or("manager".into());
+            format!("{}-{}", host, std::process::id())
+        })
+    }
+
+    pub fn get_refetch_warning(&self) -> usize {
+        self.refetch_warning
+    }
+
+    pub fn get_summary_ttl(&self) -> Option<Duration> {
+        self.summary_ttl_secs.map(Duration::from_secs)
+    }
+
+    pub fn get_startup_probe(&self) -> &StartupProbe {
+        &self.startup
+    }
+
+    pub fn get_startup_probe_mut(&mut self) -> &mut StartupProbe {
+        &mut self.startup
+    }
+
+    pub fn get_timing_mut(&mut self) -> &mut MonitorTiming {
+        //! Lets the running monitor's timing be adjusted without a restart.
+        &mut self.timing
+    }
 }
 
 impl Default for MonitorInfo {
@@ -41,6 +457,11 @@ impl Default for MonitorInfo {
             scripts_path: "/home/mineplex".into(),
             worlds_path: "/home/mineplex/worlds".into(),
             config_path: "/home/mineplex/configs".into(),
+            timing: MonitorTiming::default(),
+            instance_id: None,
+            refetch_warning: default_refetch_warning(),
+            summary_ttl_secs: None,
+            startup: StartupProbe::default(),
         }
     }
 }
@@ -50,6 +471,14 @@ impl Default for RedisConfig {
         Self {
             address: String::from("127.0.0.1"),
             port: String::from("6379"),
+            snapshot: None,
+            cluster_nodes: Vec::new(),
+            sentinel: None,
+            mirror: None,
+            users: AclUsers::default(),
+            scan_count: default_scan_count(),
+            max_retries: default_max_retries(),
+            retry_backoff_ms: default_retry_backoff_ms(),
         }
     }
 }
@@ -62,6 +491,33 @@ impl Default for Config {
                 system: SystemName::Linux,
             },
             monitor_info: MonitorInfo::default(),
+            resources: ResourceDefaults::default(),
+            rebalance: RebalanceInfo::default(),
+            ports: PortAllocationInfo::default(),
+            keys: KeyBuilder::default(),
+            proxy: ProxyInfo::default(),
+            jars: JarsInfo::default(),
+            alerts: AlertsInfo::default(),
+            scaling: ScalingInfo::default(),
+            forecast: ForecastInfo::default(),
+            prewarm: PrewarmInfo::default(),
+            launch: LaunchInfo::default(),
+            recorder: RecorderInfo::default(),
+            stream: StreamInfo::default(),
+            telemetry: TelemetryInfo::default(),
+            recycling: RecyclingInfo::default(),
+            sampling: SamplingInfo::default(),
+            orphans: OrphansInfo::default(),
+            healing: HealingInfo::default(),
+            status_refresh: RefreshInfo::default(),
+            chaos: ChaosInfo::default(),
+            rightsizing: RightsizingInfo::default(),
+            rotations: Vec::new(),
+            smoke_test: None,
+            crash_loop: CrashLoopInfo::default(),
+            rcon: RconInfo::default(),
+            services: Vec::new(),
+            protected_fields: ensure::default_protected_fields(),
             dedicated_servers: DedicatedServers {
                 servers: Vec::new(),
             },
@@ -77,14 +533,215 @@ fn dedicated_server_with_defaults(ds: &mut DedicatedServer) -> DedicatedServer {
 }
 
 impl Config {
-    pub fn get_redis_connection(&self) -> redis::Connection {
-        redis::Client::open(format!(
-            "redis://{}:{}",
-            self.redis_conn.address, self.redis_conn.port
-        ))
-        .expect("Redis connection could not be made")
-        .get_connection()
-        .expect("Redis client could not be opened")
+    pub fn get_redis_connection(
+        &self,
+        purpose: ConnectionPurpose,
+    ) -> redis::RedisResult<redis::Connection> {
+        redis::Client::open(self.get_connection_info(purpose)?)?.get_connection()
+    }
+
+    pub fn get_redis_connection_with_timeout(
+        &self,
+        purpose: ConnectionPurpose,
+        timeout: Duration,
+    ) -> redis::RedisResult<redis::Connection> {
+        //! `get_redis_connection`, giving up on connecting and on each reply after `timeout`.
+        let conn = redis::Client::open(self.get_connection_info(purpose)?)?
+            .get_connection_with_timeout(timeout)?;
+        conn.set_read_timeout(Some(timeout))?;
+        conn.set_write_timeout(Some(timeout))?;
+        Ok(conn)
+    }
+
+    fn get_connection_info(
+        &self,
+        purpose: ConnectionPurpose,
+    ) -> redis::RedisResult<ConnectionInfo> {
+        acl::get_connection_info(
+            &format!("{}:{}", self.redis_conn.address, self.redis_conn.port),
+            self.redis_conn.users.get_user(purpose)?,
+        )
+    }
+
+    pub fn get_scan_count(&self) -> usize {
+        //! Keys asked for per SCAN step, see `redis_conn.scan_count`.
+        self.redis_conn.scan_count.max(1)
+    }
+
+    pub fn get_retry_policy(&self) -> RetryPolicy {
+        //! How dropped connections are reopened, see `redis_conn.max_retries`.
+        RetryPolicy {
+            max_retries: self.redis_conn.max_retries,
+            backoff: Duration::from_millis(self.redis_conn.retry_backoff_ms),
+        }
+    }
+
+    pub fn get_redis_users(&self) -> &AclUsers {
+        &self.redis_conn.users
+    }
+
+    pub fn get_connection(&self) -> Connection {
+        //! Opens redis, or loads the configured snapshot when running offline.
+        self.try_get_connection()
+            .expect("Redis connection could not be made")
+    }
+
+    pub fn try_get_connection(&self) -> redis::RedisResult<Connection> {
+        //! `get_connection`, returning why redis (or the snapshot) couldn't be opened.
+        self.try_get_connection_for(ConnectionPurpose::Mutating)
+    }
+
+    pub fn try_get_connection_for(
+        &self,
+        purpose: ConnectionPurpose,
+    ) -> redis::RedisResult<Connection> {
+        //! `try_get_connection`, logging in as the ACL user configured for `purpose`.
+        //! Snapshots have no users. Writing connections are mirrored when `mirror` is set.
+        //! Live connections are reopened when they drop, up to `redis_conn.max_retries` times.
+        let primary = self.open_connection_for(purpose)?;
+        let policy = self.get_retry_policy();
+        let primary = match primary.is_offline() || policy.max_retries == 0 {
+            true => primary,
+            false => {
+                let config = self.clone();
+                Connection::Retrying(RetryingConnection::new(
+                    primary,
+                    Box::new(move || config.open_connection_for(purpose)),
+                    policy,
+                ))
+            }
+        };
+        match &self.redis_conn.mirror {
+            Some(mirror) if purpose == ConnectionPurpose::Mutating && !primary.is_offline() => {
+                let address = format!("{}:{}", mirror.address, mirror.port);
+                let client = redis::Client::open(acl::get_connection_info(
+                    &address,
+                    self.redis_conn.users.get_user(purpose)?,
+                )?)?;
+                Ok(Connection::Mirrored(MirroredConnection::new(
+                    primary, client, address,
+                )))
+            }
+            _ => Ok(primary),
+        }
+    }
+
+    fn open_connection_for(&self, purpose: ConnectionPurpose) -> redis::RedisResult<Connection> {
+        //! The snapshot, cluster, sentinel-managed master or single server configured.
+        match &self.redis_conn.snapshot {
+            Some(path) => Ok(Connection::Snapshot(SnapshotConnection {
+                snapshot: Snapshot::load(path)?,
+            })),
+            None if !self.redis_conn.cluster_nodes.is_empty() => {
+                self.get_cluster_connection(purpose)
+            }
+            None if self.redis_conn.sentinel.is_some() => self.get_sentinel_connection(purpose),
+            #[cfg(feature = "client-cache")]
+            None => self.get_cached_connection(purpose),
+            #[cfg(not(feature = "client-cache"))]
+            None => Ok(Connection::Redis(self.get_redis_connection(purpose)?)),
+        }
+    }
+
+    #[cfg(feature = "aio")]
+    pub async fn get_async_connection(
+        &self,
+        purpose: ConnectionPurpose,
+    ) -> redis::RedisResult<redis::aio::MultiplexedConnection> {
+        //! A multiplexed connection to redis for `AsyncContextManager`. Snapshots, cluster and
+        //! sentinel only have blocking connections, and writes wouldn't be mirrored.
+        let unsupported = match &self.redis_conn {
+            conn if conn.snapshot.is_some() => Some("redis_conn.snapshot"),
+            conn if !conn.cluster_nodes.is_empty() => Some("redis_conn.cluster_nodes"),
+            conn if conn.sentinel.is_some() => Some("redis_conn.sentinel"),
+            conn if conn.mirror.is_some() && purpose == ConnectionPurpose::Mutating => {
+                Some("redis_conn.mirror")
+            }
+            _ => None,
+        };
+        if let Some(setting) = unsupported {
+            return Err(redis::RedisError::from((
+                redis::ErrorKind::InvalidClientConfig,
+                "Async connections only reach a single redis",
+                format!("{} is set, use the blocking ContextManager", setting),
+            )));
+        }
+        redis::Client::open(self.get_connection_info(purpose)?)?
+            .get_multiplexed_tokio_connection()
+            .await
+    }
+
+    pub fn get_mirror_config(&self) -> Option<Config> {
+        //! This config pointed at the mirror alone, e.g. to read it back for verification.
+        let mirror = self.redis_conn.mirror.as_ref()?;
+        let mut config = self.clone();
+        config.redis_conn = RedisConfig {
+            address: mirror.address.clone(),
+            port: mirror.port.clone(),
+            users: self.redis_conn.users.clone(),
+            ..RedisConfig::default()
+        };
+        Some(config)
+    }
+
+    #[cfg(feature = "client-cache")]
+    fn get_cached_connection(&self, purpose: ConnectionPurpose) -> redis::RedisResult<Connection> {
+        //! Caches group hashes and the group index, or falls back to a plain connection
+        //! when the server can't track keys (redis < 6).
+        let client = redis::Client::open(self.get_connection_info(purpose)?)?;
+        let mut patterns = vec![self.keys.group_pattern()];
+        patterns.extend(ServerGroup::index_set(&self.keys));
+        match CachedConnection::new(&client, patterns) {
+            Ok(conn) => Ok(Connection::Cached(conn)),
+            Err(err) => {
+                log::warn!(target: "cache", "client-side caching unavailable: {:?}", err);
+                Ok(Connection::Redis(self.get_redis_connection(purpose)?))
+            }
+        }
+    }
+
+    #[cfg(feature = "cluster")]
+    fn get_cluster_connection(&self, purpose: ConnectionPurpose) -> redis::RedisResult<Connection> {
+        Ok(Connection::Cluster(ShardedConnection::new(
+            &self.redis_conn.cluster_nodes,
+            self.redis_conn.users.get_user(purpose)?,
+        )?))
+    }
+
+    #[cfg(not(feature = "cluster"))]
+    fn get_cluster_connection(&self, _: ConnectionPurpose) -> redis::RedisResult<Connection> {
+        Err(redis::RedisError::from((
+            redis::ErrorKind::InvalidClientConfig,
+            "redis_conn.cluster_nodes is set, but this build lacks the `cluster` feature",
+        )))
+    }
+
+    #[cfg(feature = "sentinel")]
+    fn get_sentinel_connection(
+        &self,
+        purpose: ConnectionPurpose,
+    ) -> redis::RedisResult<Connection> {
+        let info = self
+            .redis_conn
+            .sentinel
+            .as_ref()
+            .expect("sentinel is configured");
+        Ok(Connection::Sentinel(FailoverConnection::new(
+            info,
+            self.redis_conn.users.get_user(purpose)?,
+        )?))
+    }
+
+    #[cfg(not(feature = "sentinel"))]
+    fn get_sentinel_connection(&self, _: ConnectionPurpose) -> redis::RedisResult<Connection> {
+        Err(redis::RedisError::from((
+            redis::ErrorKind::InvalidClientConfig,
+            "redis_conn.sentinel is set, but this build lacks the `sentinel` feature",
+        )))
+    }
+
+    pub fn set_snapshot(&mut self, path: Option<String>) {
+        self.redis_conn.snapshot = path;
     }
 
     pub fn get_config() -> Self {
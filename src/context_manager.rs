@@ -1,8 +1,40 @@
-use crate::{config::models::Config, server::dedicated::collection::DedicatedServers};
+use std::sync::Arc;
+
+use crate::{
+    clock::{Clock, SystemClock},
+    config::models::Config,
+    monitor::hooks::MonitorHooks,
+    random::{RandomSource, ThreadRandom},
+    region::Region,
+    server::dedicated::{
+        collection::DedicatedServers,
+        rebalance::RebalanceReport,
+        recovery::RecoveryReport,
+        removal::{NodeRemovalPolicy, NodeRemovalReport},
+        resolver::NodeResolver,
+        server::DedicatedServerError,
+        spawn::{ScriptLauncher, ServerLauncher},
+    },
+    store::{
+        acl::ConnectionPurpose,
+        connection::Connection,
+        keys::KeyBuilder,
+        snapshot::{Snapshot, SnapshotConnection},
+    },
+    strategy::{JoinableScaling, PlacementStrategy, ScalingStrategy, SpreadPlacement},
+};
 
 pub struct ContextManager {
     config: Config,
-    connection: redis::Connection,
+    connection: Connection,
+    resolver: NodeResolver,
+    placement: Arc<dyn PlacementStrategy>,
+    scaling: Arc<dyn ScalingStrategy>,
+    hooks: Vec<Arc<dyn MonitorHooks>>,
+    clock: Arc<dyn Clock>,
+    random: Arc<dyn RandomSource>,
+    launcher: Arc<dyn ServerLauncher>,
+    purpose: ConnectionPurpose,
 }
 
 impl ContextManager {
@@ -10,25 +42,239 @@ impl ContextManager {
         &mut self.config.dedicated_servers
     }
 
+    pub fn with_dedicated_servers<T>(
+        &mut self,
+        f: impl FnOnce(&mut DedicatedServers, &mut Self) -> T,
+    ) -> T {
+        //! Lends out the dedicated servers together with the context,
+        //! so node operations that talk to redis can run on them.
+        let mut servers = std::mem::take(&mut self.config.dedicated_servers);
+        let res = f(&mut servers, self);
+        self.config.dedicated_servers = servers;
+        res
+    }
+
+    pub fn reload_config(
+        &mut self,
+        policy: NodeRemovalPolicy,
+    ) -> Result<Vec<NodeRemovalReport>, DedicatedServerError> {
+        //! Re-reads config.toml. Nodes removed from it are handled according to `policy`,
+        //! and instances on nodes that are still configured are carried over.
+        let mut updated = Config::get_config();
+        let removed = self
+            .config
+            .dedicated_servers
+            .get_removed_nodes(&updated.dedicated_servers);
+        let reports = self.with_dedicated_servers(|servers, ctx| {
+            removed
+                .iter()
+                .map(|name| servers.remove_node(name, policy, ctx))
+                .collect::<Result<Vec<_>, _>>()
+        })?;
+        updated
+            .dedicated_servers
+            .carry_over_instances(&self.config.dedicated_servers);
+        self.config = updated;
+        self.resolver = NodeResolver::new(&self.config.dedicated_servers);
+        Ok(reports)
+    }
+
+    pub fn recover_instances(&mut self) -> RecoveryReport {
+        //! Adopts servers that are already running; call before making placement decisions.
+        self.with_dedicated_servers(|servers, ctx| servers.recover(ctx))
+    }
+
+    pub fn run_rebalance_pass(&mut self) -> Result<Option<RebalanceReport>, DedicatedServerError> {
+        //! Runs one rebalance pass if it is enabled in config, bounded by its `max_moves`.
+        let rebalance = self.config.rebalance.clone();
+        if !rebalance.enabled {
+            return Ok(None);
+        }
+        self.with_dedicated_servers(|servers, ctx| servers.rebalance(rebalance.max_moves, ctx))
+            .map(Some)
+    }
+
+    pub fn get_placement_strategy(&self) -> &dyn PlacementStrategy {
+        self.placement.as_ref()
+    }
+
+    pub fn set_placement_strategy(&mut self, strategy: Box<dyn PlacementStrategy>) {
+        //! Replaces the built-in placement for every later placement decision.
+        self.placement = strategy.into();
+    }
+
+    pub fn get_scaling_strategy(&self) -> &dyn ScalingStrategy {
+        self.scaling.as_ref()
+    }
+
+    pub fn set_scaling_strategy(&mut self, strategy: Box<dyn ScalingStrategy>) {
+        //! Replaces the built-in scaling policy the monitor reconciles with.
+        self.scaling = strategy.into();
+    }
+
+    pub fn get_hooks(&self) -> &[Arc<dyn MonitorHooks>] {
+        &self.hooks
+    }
+
+    pub fn add_hooks(&mut self, hooks: Box<dyn MonitorHooks>) {
+        //! Registers lifecycle callbacks next to any registered before. Contexts built
+        //! afterwards (region workers, batches) share them.
+        self.hooks.push(hooks.into());
+    }
+
+    pub fn get_clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        //! Replaces the system clock, e.g. with a `FixedClock` in tests.
+        self.clock = Arc::from(clock);
+    }
+
+    pub fn get_random(&self) -> &dyn RandomSource {
+        self.random.as_ref()
+    }
+
+    pub fn set_random(&mut self, random: Box<dyn RandomSource>) {
+        //! Replaces thread-local randomness, e.g. with a `SeededRandom` in tests.
+        self.random = Arc::from(random);
+    }
+
+    pub fn get_server_launcher(&self) -> &dyn ServerLauncher {
+        self.launcher.as_ref()
+    }
+
+    pub fn set_server_launcher(&mut self, launcher: Box<dyn ServerLauncher>) {
+        //! Replaces the start script, e.g. with a launcher that only records launches in tests.
+        self.launcher = Arc::from(launcher);
+    }
+
     pub fn get_config(&mut self) -> &mut Config {
         &mut self.config
     }
 
-    pub fn get_connection(&mut self) -> &mut redis::Connection {
+    pub fn get_resolver(&self) -> &NodeResolver {
+        &self.resolver
+    }
+
+    pub fn get_keys(&self) -> &KeyBuilder {
+        &self.config.keys
+    }
+
+    pub fn get_purpose(&self) -> ConnectionPurpose {
+        //! Which ACL user the connection logged in as, see `redis_conn.users`.
+        self.purpose
+    }
+
+    pub fn get_connection(&mut self) -> &mut Connection {
+        //! Commands sent on it survive redis restarting: a refused or dropped connection is
+        //! reopened with backoff, and reads are sent again. A write whose connection dropped
+        //! may have been applied, so it fails instead, see `RetryingConnection`.
         &mut self.connection
     }
 
+    pub fn reconnect(&mut self) -> redis::RedisResult<()> {
+        //! Replaces the connection with a new one to the same server, as the same user.
+        //! Offline contexts keep their snapshot.
+        if self.connection.is_offline() {
+            return Ok(());
+        }
+        self.connection = self.config.try_get_connection_for(self.purpose)?;
+        Ok(())
+    }
+
     pub fn new() -> Self {
         let config = Config::get_config();
-        let connection = config.get_redis_connection();
-        Self { config, connection }
+        Self::from_config(&config)
     }
 
     pub fn from_config(config: &Config) -> Self {
-        let connection = config.get_redis_connection();
-        Self {
+        Self::try_from_config(config).expect("Redis connection could not be made")
+    }
+
+    pub fn try_from_config(config: &Config) -> redis::RedisResult<Self> {
+        //! `from_config`, returning why redis (or the snapshot) couldn't be opened.
+        Self::try_from_config_for(config, ConnectionPurpose::Mutating)
+    }
+
+    pub fn try_from_config_for(
+        config: &Config,
+        purpose: ConnectionPurpose,
+    ) -> redis::RedisResult<Self> {
+        //! `try_from_config`, connecting as the ACL user configured for `purpose`.
+        let connection = config.try_get_connection_for(purpose)?;
+        Ok(Self {
             config: config.clone(),
             connection,
+            resolver: NodeResolver::new(&config.dedicated_servers),
+            placement: Arc::new(SpreadPlacement),
+            scaling: Arc::new(JoinableScaling),
+            hooks: Vec::new(),
+            clock: Arc::new(SystemClock),
+            random: Arc::new(ThreadRandom),
+            launcher: Arc::new(ScriptLauncher),
+            purpose,
+        })
+    }
+
+    pub fn from_snapshot(&self, config: &Config, snapshot: Snapshot) -> Self {
+        //! An offline context serving `snapshot` under `config`, e.g. to replay a recorded
+        //! reconcile. Registered strategies, clock, randomness and launcher are shared, hooks
+        //! aren't.
+        Self {
+            config: config.clone(),
+            connection: Connection::Snapshot(SnapshotConnection { snapshot }),
+            resolver: NodeResolver::new(&config.dedicated_servers),
+            placement: self.placement.clone(),
+            scaling: self.scaling.clone(),
+            hooks: Vec::new(),
+            clock: self.clock.clone(),
+            random: self.random.clone(),
+            launcher: self.launcher.clone(),
+            purpose: self.purpose,
         }
     }
+
+    pub fn builder(&self) -> impl Fn() -> Self + Clone + Send + Sync + 'static {
+        //! Builds contexts with their own connection and the same config and purpose, for work
+        //! on other threads. Registered strategies, hooks, clock, randomness and launcher are
+        //! shared.
+        self.builder_for(self.config.clone())
+    }
+
+    pub fn region_builder(&self, region: &Region) -> impl Fn() -> Self + Clone + Send + 'static {
+        //! Builds contexts with their own connection that only know `region`'s nodes, for
+        //! region workers. Registered strategies, hooks, clock, randomness and launcher are shared. Call it on the worker's thread,
+        //! so a failing connection stays that worker's problem.
+        let mut config = self.config.clone();
+        config
+            .dedicated_servers
+            .servers
+            .retain(|server| &server.region == region);
+        self.builder_for(config)
+    }
+
+    fn builder_for(&self, config: Config) -> impl Fn() -> Self + Clone + Send + Sync + 'static {
+        let (placement, scaling) = (self.placement.clone(), self.scaling.clone());
+        let hooks = self.hooks.clone();
+        let (clock, random) = (self.clock.clone(), self.random.clone());
+        let launcher = self.launcher.clone();
+        let purpose = self.purpose;
+        move || Self {
+            placement: placement.clone(),
+            scaling: scaling.clone(),
+            hooks: hooks.clone(),
+            clock: clock.clone(),
+            random: random.clone(),
+            launcher: launcher.clone(),
+            ..Self::try_from_config_for(&config, purpose)
+                .expect("Redis connection could not be made")
+        }
+    }
+}
+
+impl Default for ContextManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
@@ -0,0 +1,140 @@
+use std::{thread, time::Duration};
+
+use chrono::{DateTime, Local};
+
+use crate::{
+    context_manager::ContextManager,
+    region::Region,
+    server::{
+        minecraft::{status_to_json, MinecraftServer, MinecraftServerError},
+        presets::{Preset, SizeTier},
+        server_group::ServerGroup,
+    },
+    store::{
+        bulk::{BulkWrite, BulkWriter},
+        entity::RedisEntity,
+    },
+};
+
+/// Prefix of every synthetic group, so they can be told apart from (and cleaned up without
+/// touching) real ones.
+pub const MOCK_PREFIX: &str = "Mock";
+
+/// Synthetic network used to load test the monitor, scaler and TUI without real servers.
+#[derive(Clone, Debug)]
+pub struct MockNetwork {
+    pub groups: Vec<ServerGroup>,
+    pub servers: Vec<(Region, MinecraftServer)>,
+}
+
+impl MockNetwork {
+    pub fn new(group_count: usize, server_count: usize, now: DateTime<Local>) -> Self {
+        //! Builds `group_count` arcade groups and spreads `server_count` statuses, started at
+        //! `now`, over them.
+        let groups: Vec<ServerGroup> = (1..=group_count)
+            .map(|i| {
+                let mut group = Preset::Arcade.to_server_group(
+                    &format!("{}{}", MOCK_PREFIX, i),
+                    Region::default(),
+                    SizeTier::S,
+                );
+                group.port_section = 40000 + (i as u16 - 1) * 11;
+                group
+            })
+            .collect();
+        let servers = (0..server_count)
+            .filter_map(|i| {
+                let group = groups.get(i % group_count.max(1))?;
+                let server_num = i / group_count + 1;
+                Some((
+                    group.region.clone(),
+                    MinecraftServer::synthetic(group, server_num, now),
+                ))
+            })
+            .collect();
+        Self { groups, servers }
+    }
+
+    pub fn populate(&mut self, ctx: &mut ContextManager) -> Result<(), MinecraftServerError> {
+        //! Writes the groups and a first round of statuses to redis.
+        let mut writes: Vec<BulkWrite> = self
+            .groups
+            .iter()
+            .map(|group| BulkWrite::Hash(ctx.get_keys().group_key(&group.prefix), group.to_map()))
+            .collect();
+        if let Some(set) = ServerGroup::index_set(ctx.get_keys()) {
+            let prefixes = self.groups.iter().map(|group| group.prefix.clone());
+            writes.push(BulkWrite::AddToSet(set, prefixes.collect()));
+        }
+        write_all(&writes, ctx)?;
+        self.refresh(ctx)
+    }
+
+    pub fn refresh(&mut self, ctx: &mut ContextManager) -> Result<(), MinecraftServerError> {
+        //! Randomizes player counts and ram, and heartbeats every status.
+        let now = ctx.get_clock().now();
+        let mut writes = Vec::new();
+        for (region, server) in self.servers.iter_mut() {
+            let random = ctx.get_random();
+            server.heartbeat(
+                random.gen_below(server.get_max_player_count() as u64 + 1) as u8,
+                random.gen_below(server.get_max_ram() as u64 + 1) as u16,
+                now,
+            );
+            let key = ctx
+                .get_keys()
+                .status_key(&region.to_string(), server.get_name());
+            writes.push(BulkWrite::String(key, status_to_json(server.to_map())));
+        }
+        write_all(&writes, ctx)
+    }
+
+    pub fn run(
+        &mut self,
+        interval: Duration,
+        rounds: Option<usize>,
+        ctx: &mut ContextManager,
+    ) -> Result<(), MinecraftServerError> {
+        //! Populates redis, then refreshes every `interval` (forever unless `rounds` is given).
+        self.populate(ctx)?;
+        let mut round = 0;
+        while rounds.is_none_or(|rounds| round < rounds) {
+            thread::sleep(interval);
+            self.refresh(ctx)?;
+            round += 1;
+        }
+        Ok(())
+    }
+}
+
+fn write_all(writes: &[BulkWrite], ctx: &mut ContextManager) -> Result<(), MinecraftServerError> {
+    let report = BulkWriter::default().write(writes, &mut |_| {}, ctx);
+    match report.failed.first() {
+        Some((key, err)) => Err(MinecraftServerError::from(format!(
+            "{:?} could not be written: {}",
+            key, err
+        ))),
+        None => Ok(()),
+    }
+}
+
+pub fn clear(ctx: &mut ContextManager) -> Result<usize, MinecraftServerError> {
+    //! Deletes every synthetic group and status, returning how many keys were removed.
+    let mut removed = 0;
+    let groups: Vec<ServerGroup> = ServerGroup::get_all(ctx)
+        .ok
+        .into_iter()
+        .filter(|group| group.prefix.starts_with(MOCK_PREFIX))
+        .collect();
+    for group in groups.iter() {
+        for server in MinecraftServer::from_server_group(group, ctx).ok {
+            MinecraftServer::delete_status(server.get_name(), &group.region, ctx)?;
+            removed += 1;
+        }
+        ServerGroup::delete(&group.prefix, ctx)
+            .map_err(|err| MinecraftServerError::from(err.to_string()))?;
+        ServerGroup::delete_leftovers(&group.prefix, ctx).map_err(MinecraftServerError::from)?;
+        removed += 1;
+    }
+    Ok(removed)
+}
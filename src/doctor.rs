@@ -0,0 +1,578 @@
+use std::{
+    collections::HashSet,
+    fmt::{Debug, Display},
+    str::FromStr,
+};
+
+use redis::RedisError;
+
+use strum_macros::Display;
+
+use crate::{
+    context_manager::ContextManager,
+    events::{Event, EventKind},
+    game::{
+        booster_group::BoosterGroup,
+        r#type::GameType,
+        utils::{has_explicit_player_count, SERVER_PREFIX_TO_GAME},
+        Game,
+    },
+    handshake,
+    server::{
+        dedicated::{
+            collection::DedicatedServers,
+            resolver::NodeResolver,
+            strays::{Stray, StrayDecision},
+        },
+        minecraft::MinecraftServer,
+        port::PortSection,
+        server_group::ServerGroup,
+    },
+    store::{entity::RedisEntity, keys::KeyBuilder, partial::PartialResult},
+};
+
+#[derive(Clone, Copy, Debug, Display, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A single problem found while inspecting the network's Redis state.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub subject: String, // usually the redis key at fault
+    pub message: String,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}: {}", self.severity, self.subject, self.message)
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DoctorReport {
+    pub findings: Vec<Finding>,
+}
+
+impl Display for DoctorReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.findings.is_empty() {
+            return write!(f, "No problems found");
+        }
+        let lines: Vec<String> = self.findings.iter().map(|x| x.to_string()).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl DoctorReport {
+    pub fn is_healthy(&self) -> bool {
+        self.findings
+            .iter()
+            .all(|finding| finding.severity < Severity::Error)
+    }
+
+    pub fn add_failures<T, E: Debug>(&mut self, severity: Severity, result: &PartialResult<T, E>) {
+        //! Adds a finding for every entry of a bulk read that could not be loaded.
+        for (key, err) in result.failed.iter() {
+            self.findings.push(Finding {
+                severity,
+                subject: key.clone(),
+                message: format!("could not be parsed: {:?}", err),
+            });
+        }
+    }
+}
+
+pub fn diagnose(ctx: &mut ContextManager) -> DoctorReport {
+    //! Inspects cached server groups and live statuses.
+    //! Malformed groups are errors since placement can't reason about them,
+    //! malformed statuses are warnings as the servers will usually rewrite them.
+    //! Plugin handshake mismatches are errors. Unknown booster groups, dangling references,
+    //! unplaceable hosts, servers outside their group's port section, strays (servers the
+    //! monitor saw running untracked) awaiting a decision, node reservations for unknown
+    //! groups or beyond the node's capacity and groups of game types without explicit
+    //! player counts are reported as warnings too. Mismatches between the configured nodes
+    //! and redis are reported as `check_topology` rates them.
+    let mut report = DoctorReport::default();
+    let groups = ServerGroup::get_all(ctx);
+    report.add_failures(Severity::Error, &groups);
+    let statuses = MinecraftServer::get_all(ctx);
+    report.add_failures(Severity::Warning, &statuses);
+    report
+        .findings
+        .extend(check_ports(&groups.ok, &statuses.ok, ctx.get_keys()));
+    if let Ok(strays) = Stray::get_all(ctx) {
+        report
+            .findings
+            .extend(check_strays(&strays, ctx.get_keys()));
+    }
+    report
+        .findings
+        .extend(check_reservations(&groups.ok, ctx.get_dedicated_servers()));
+    let keys = ctx.get_keys().clone();
+    report.findings.extend(check_topology(
+        &groups.ok,
+        &statuses.ok,
+        ctx.get_dedicated_servers(),
+        &keys,
+    ));
+    report
+        .findings
+        .extend(check_booster_groups(&groups.ok, ctx.get_keys()));
+    report
+        .findings
+        .extend(check_references(&groups, ctx.get_keys()));
+    for mismatch in handshake::check(ctx).mismatches {
+        report.findings.push(Finding {
+            severity: Severity::Error,
+            subject: "handshake".into(),
+            message: format!("{} (destructive operations are refused)", mismatch),
+        });
+    }
+    let (min, max) = ctx.get_config().resources.players;
+    for group in groups.ok.iter() {
+        if let Err(err) = group.validate_host(ctx.get_config()) {
+            report.findings.push(Finding {
+                severity: Severity::Warning,
+                subject: ctx.get_keys().group_key(&group.prefix),
+                message: format!("{} (its instances can't be placed)", err.msg),
+            });
+        }
+        let Some(game) = SERVER_PREFIX_TO_GAME.get(group.prefix.as_str()) else {
+            continue;
+        };
+        if !has_explicit_player_count(game) {
+            report.findings.push(Finding {
+                severity: Severity::Warning,
+                subject: ctx.get_keys().group_key(&group.prefix),
+                message: format!(
+                    "{} has no explicit player counts, new groups use the default {}-{}",
+                    game, min, max
+                ),
+            });
+        }
+    }
+    report
+}
+
+/// A live server reporting a port outside its group's port section, usually launched by
+/// hand or left over from before the section changed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PortMismatch {
+    pub server: String,
+    pub group: ServerGroup,
+    pub port: u16,
+    pub section: PortSection,
+}
+
+impl Display for PortMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} reports port {}, outside {}'s port section {} (manual launch or config drift)",
+            self.server, self.port, self.group.prefix, self.section
+        )
+    }
+}
+
+pub fn get_port_mismatches(
+    groups: &[ServerGroup],
+    statuses: &[MinecraftServer],
+) -> Vec<PortMismatch> {
+    //! Statuses of known groups whose port isn't in `port_section..=port_section + width`.
+    statuses
+        .iter()
+        .filter_map(|status| {
+            let group = groups
+                .iter()
+                .find(|group| &group.name == status.get_group())?;
+            let section = group.get_port_section();
+            if section.contains(status.get_port()) {
+                return None;
+            }
+            Some(PortMismatch {
+                server: status.get_name().clone(),
+                group: group.clone(),
+                port: status.get_port(),
+                section,
+            })
+        })
+        .collect()
+}
+
+pub fn check_ports(
+    groups: &[ServerGroup],
+    statuses: &[MinecraftServer],
+    keys: &KeyBuilder,
+) -> Vec<Finding> {
+    get_port_mismatches(groups, statuses)
+        .into_iter()
+        .map(|mismatch| Finding {
+            severity: Severity::Warning,
+            subject: keys.status_key(&mismatch.group.region.to_string(), &mismatch.server),
+            message: mismatch.to_string(),
+        })
+        .collect()
+}
+
+pub fn check_reservations(groups: &[ServerGroup], nodes: &DedicatedServers) -> Vec<Finding> {
+    //! Reports reservations for groups that don't exist and nodes whose reservations take
+    //! more than the node has, leaving nothing for other groups.
+    let mut findings = Vec::new();
+    for node in nodes.servers.iter() {
+        for reservation in node.reservations.iter() {
+            if !groups.iter().any(|group| group.name == reservation.group) {
+                findings.push(Finding {
+                    severity: Severity::Warning,
+                    subject: node.name.clone(),
+                    message: format!(
+                        "reserves capacity for unknown group {:?} (no other group can use it)",
+                        reservation.group
+                    ),
+                });
+            }
+        }
+        let reserved = node.get_reserved(None);
+        if reserved.ram > node.max_ram || reserved.cpu > node.max_cpu {
+            findings.push(Finding {
+                severity: Severity::Warning,
+                subject: node.name.clone(),
+                message: format!(
+                    "reserves {}MB ram and {} cpu but only has {}MB and {}",
+                    reserved.ram, reserved.cpu, node.max_ram, node.max_cpu
+                ),
+            });
+        }
+    }
+    findings
+}
+
+pub fn check_topology(
+    groups: &[ServerGroup],
+    statuses: &[MinecraftServer],
+    nodes: &DedicatedServers,
+    keys: &KeyBuilder,
+) -> Vec<Finding> {
+    //! Compares the configured nodes with what redis holds. Groups that want instances in a
+    //! region without nodes are errors, as are live servers that mostly report addresses
+    //! of no configured node (the config likely belongs to another network). Single servers
+    //! from unknown addresses and groups or nodes alone in their region are warnings.
+    let mut findings = Vec::new();
+    let regions: HashSet<String> = nodes
+        .servers
+        .iter()
+        .map(|ds| ds.region.to_string())
+        .collect();
+    for group in groups
+        .iter()
+        .filter(|group| !regions.contains(&group.region.to_string()))
+    {
+        let wanted = group.total_servers > 0 || group.joinable_servers > 0;
+        findings.push(Finding {
+            severity: if wanted {
+                Severity::Error
+            } else {
+                Severity::Warning
+            },
+            subject: keys.group_key(&group.prefix),
+            message: format!(
+                "is in {}, where no dedicated server is configured{}",
+                group.region,
+                if wanted {
+                    " (its instances can't be placed)"
+                } else {
+                    ""
+                }
+            ),
+        });
+    }
+    for ds in nodes
+        .servers
+        .iter()
+        .filter(|ds| !groups.iter().any(|group| group.region == ds.region))
+    {
+        findings.push(Finding {
+            severity: Severity::Warning,
+            subject: ds.name.clone(),
+            message: format!("is in {}, where no group exists", ds.region),
+        });
+    }
+    let resolver = NodeResolver::new(nodes);
+    let unknown: Vec<&MinecraftServer> = statuses
+        .iter()
+        .filter(|server| !resolver.is_known(server.get_public_address()))
+        .collect();
+    if !unknown.is_empty() && unknown.len() * 2 >= statuses.len() {
+        findings.push(Finding {
+            severity: Severity::Error,
+            subject: "statuses".into(),
+            message: format!(
+                "{} of {} live servers report addresses of no configured dedicated server",
+                unknown.len(),
+                statuses.len()
+            ),
+        });
+    } else {
+        for server in unknown {
+            findings.push(Finding {
+                severity: Severity::Warning,
+                subject: server.get_name().clone(),
+                message: format!(
+                    "reports address {}, which no configured dedicated server has",
+                    server.get_public_address()
+                ),
+            });
+        }
+    }
+    findings
+}
+
+pub fn check_startup_topology(ctx: &mut ContextManager) -> DoctorReport {
+    //! `check_topology` against live redis, plus groups and statuses that can't be read.
+    let mut report = DoctorReport::default();
+    let groups = ServerGroup::get_all(ctx);
+    report.add_failures(Severity::Warning, &groups);
+    let statuses = MinecraftServer::get_all(ctx);
+    let keys = ctx.get_keys().clone();
+    report.findings.extend(check_topology(
+        &groups.ok,
+        &statuses.ok,
+        ctx.get_dedicated_servers(),
+        &keys,
+    ));
+    report
+}
+
+pub fn check_strays(strays: &[Stray], keys: &KeyBuilder) -> Vec<Finding> {
+    //! Reports strays nobody decided about yet (`strays adopt` or `strays ignore`).
+    strays
+        .iter()
+        .filter(|stray| stray.decision == StrayDecision::Pending)
+        .map(|stray| Finding {
+            severity: Severity::Warning,
+            subject: keys.status_key(&stray.region.to_string(), &stray.server),
+            message: match &stray.last_error {
+                Some(err) => format!("stray, adoption failed: {}", err),
+                None => format!(
+                    "stray launched outside the manager, not tracked by any node ({})",
+                    stray
+                ),
+            },
+        })
+        .collect()
+}
+
+/// A booster group value rewritten by `fix_booster_groups`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BoosterFix {
+    pub group: String,
+    pub old: String,
+    pub new: Option<BoosterGroup>, // None when the value was cleared
+}
+
+impl Display for BoosterFix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.new {
+            Some(new) => write!(f, "{} boosterGroup: {:?} -> {}", self.group, self.old, new),
+            None => write!(f, "{} boosterGroup: {:?} cleared", self.group, self.old),
+        }
+    }
+}
+
+fn get_invalid_booster_group(group: &ServerGroup) -> Option<&String> {
+    group
+        .booster_group
+        .as_ref()
+        .filter(|value| BoosterGroup::from_str(value).is_err())
+}
+
+pub fn check_booster_groups(groups: &[ServerGroup], keys: &KeyBuilder) -> Vec<Finding> {
+    //! Reports booster groups that don't parse (they are silently dropped when games load).
+    groups
+        .iter()
+        .filter_map(|group| {
+            let value = get_invalid_booster_group(group)?;
+            let fix = match BoosterGroup::closest(value) {
+                Some(closest) => format!("--fix maps it to {}", closest),
+                None => "--fix clears it".into(),
+            };
+            Some(Finding {
+                severity: Severity::Warning,
+                subject: keys.group_key(&group.prefix),
+                message: format!("unknown boosterGroup {:?} ({})", value, fix),
+            })
+        })
+        .collect()
+}
+
+pub fn fix_booster_groups(ctx: &mut ContextManager) -> Result<Vec<BoosterFix>, RedisError> {
+    //! Maps unknown booster groups to their closest match, or clears them when there is none.
+    let groups: Vec<ServerGroup> = ServerGroup::get_all(ctx).into_result()?;
+    let mut fixes = Vec::new();
+    for mut group in groups {
+        let Some(old) = get_invalid_booster_group(&group).cloned() else {
+            continue;
+        };
+        let new = BoosterGroup::closest(&old);
+        group.booster_group = new.map(|new| new.to_string());
+        group.save(ctx)?;
+        let fix = BoosterFix {
+            group: ctx.get_keys().group_key(&group.prefix),
+            old,
+            new,
+        };
+        Event::new(EventKind::GroupUpdated, &group.prefix, fix.to_string()).emit(ctx);
+        fixes.push(fix);
+    }
+    Ok(fixes)
+}
+
+/// A field of one group that points at a group or game that doesn't exist.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DanglingReference {
+    pub group: String,
+    pub field: &'static str,
+    pub missing: Vec<String>,
+}
+
+/// How `fix_references` resolved a dangling reference.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ReferenceFix {
+    Cleared(DanglingReference),
+    Recreated(DanglingReference),
+}
+
+impl Display for DanglingReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}: {}",
+            self.group,
+            self.field,
+            self.missing.join(", ")
+        )
+    }
+}
+
+impl Display for ReferenceFix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReferenceFix::Cleared(reference) => write!(f, "{} removed", reference),
+            ReferenceFix::Recreated(reference) => write!(f, "{} recreated", reference),
+        }
+    }
+}
+
+fn get_dangling_references(
+    groups: &PartialResult<ServerGroup, impl Debug>,
+    keys: &KeyBuilder,
+) -> Vec<DanglingReference> {
+    //! Groups that exist but can't be parsed still count as existing.
+    let existing: HashSet<String> = groups
+        .ok
+        .iter()
+        .map(|group| keys.group_key(&group.prefix))
+        .chain(groups.failed.iter().map(|(key, _)| key.clone()))
+        .collect();
+    let mut dangling = Vec::new();
+    for group in groups.ok.iter() {
+        if let Some(team_server) = group.team_server_key.as_ref() {
+            if !existing.contains(&keys.group_key(team_server)) {
+                dangling.push(DanglingReference {
+                    group: group.prefix.clone(),
+                    field: "teamServerKey",
+                    missing: vec![team_server.clone()],
+                });
+            }
+        }
+        let unknown_games: Vec<String> = group
+            .games
+            .iter()
+            .flat_map(|games| games.split(','))
+            .filter(|game| !game.is_empty() && GameType::from_str(game).is_err())
+            .map(String::from)
+            .collect();
+        if !unknown_games.is_empty() {
+            dangling.push(DanglingReference {
+                group: group.prefix.clone(),
+                field: "games",
+                missing: unknown_games,
+            });
+        }
+    }
+    dangling
+}
+
+pub fn check_references(
+    groups: &PartialResult<ServerGroup, impl Debug>,
+    keys: &KeyBuilder,
+) -> Vec<Finding> {
+    //! Reports team servers that point at missing groups and unknown games in `games` lists.
+    //! Unknown booster groups are covered by `check_booster_groups`.
+    get_dangling_references(groups, keys)
+        .into_iter()
+        .map(|reference| Finding {
+            severity: Severity::Warning,
+            subject: keys.group_key(&reference.group),
+            message: format!(
+                "{} references missing {}",
+                reference.field,
+                reference.missing.join(", ")
+            ),
+        })
+        .collect()
+}
+
+pub fn fix_references(
+    recreate: bool,
+    ctx: &mut ContextManager,
+) -> Result<Vec<ReferenceFix>, RedisError> {
+    //! Removes dangling references from their groups. With `recreate`, a missing team server
+    //! group of a known game type is created from that game's defaults instead.
+    let groups = ServerGroup::get_all(ctx);
+    let dangling = get_dangling_references(&groups, ctx.get_keys());
+    let mut fixes = Vec::new();
+    for reference in dangling {
+        let Some(mut group) = groups
+            .ok
+            .iter()
+            .find(|group| group.prefix == reference.group)
+            .cloned()
+        else {
+            continue;
+        };
+        let missing_game = SERVER_PREFIX_TO_GAME.get(reference.missing[0].as_str());
+        if let (true, "teamServerKey", Some(game)) = (recreate, reference.field, missing_game) {
+            let mut team_server = ServerGroup::from_game(Game::from_game_type(*game, ctx)?);
+            team_server.create(ctx)?;
+            let fix = ReferenceFix::Recreated(reference);
+            Event::new(
+                EventKind::GroupUpdated,
+                &team_server.prefix,
+                fix.to_string(),
+            )
+            .emit(ctx);
+            fixes.push(fix);
+            continue;
+        }
+        match reference.field {
+            "teamServerKey" => group.team_server_key = None,
+            _ => {
+                let kept: Vec<&str> = group
+                    .games
+                    .iter()
+                    .flat_map(|games| games.split(','))
+                    .filter(|game| !reference.missing.iter().any(|missing| missing == game))
+                    .collect();
+                group.games = Some(kept.join(",")).filter(|games| !games.is_empty());
+            }
+        }
+        group.save(ctx)?;
+        let fix = ReferenceFix::Cleared(reference);
+        Event::new(EventKind::GroupUpdated, &group.prefix, fix.to_string()).emit(ctx);
+        fixes.push(fix);
+    }
+    Ok(fixes)
+}
@@ -13,6 +13,12 @@ impl Display for ServerGroupParsingError {
 
 impl Error for ServerGroupParsingError {}
 
+impl From<String> for ServerGroupParsingError {
+    fn from(msg: String) -> Self {
+        ServerGroupParsingError { msg }
+    }
+}
+
 impl ServerGroupParsingError {
     pub fn new(msg: String) -> Self {
         ServerGroupParsingError { msg }
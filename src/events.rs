@@ -0,0 +1,150 @@
+use std::fmt::Display;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+use crate::{
+    context_manager::ContextManager,
+    server::dedicated::outcome::{InstanceChange, InstanceOutcome},
+    undo::GroupChange,
+};
+
+/// List of recent events, newest first; doubles as the manager's audit trail.
+const EVENTS_KEY: &str = "manager.events";
+const EVENT_LOG_LENGTH: isize = 1000;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize, Display)]
+pub enum EventKind {
+    InstanceAdded,
+    InstanceRemoved,
+    GroupUpdated,
+    GroupDeleted,
+    GroupEditedExternally,
+    RestartScheduled,
+    RestartCancelled,
+    RestartFinished,
+    Warning,
+    AlertFired,
+    AlertCleared,
+    RedisReconnected,
+    SmokeTestFailed,
+    CrashLoop,
+    ServiceDown,
+    ServiceRecovered,
+    DiskLow,
+    DiskRecovered,
+    InstanceRecycled,
+    OrphanFound,
+    OrphanKilled,
+    ChaosInjected,
+    InstanceDead,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Event {
+    pub kind: EventKind,
+    pub subject: String,
+    pub message: String,
+    pub timestamp: i64, // seconds since epoch
+    #[serde(default)]
+    pub outcome: Option<InstanceOutcome>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub change: Option<GroupChange>, // lets `undo` revert it
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let time = chrono::DateTime::from_timestamp(self.timestamp, 0)
+            .map(|time| time.with_timezone(&Local).to_rfc3339())
+            .unwrap_or(self.timestamp.to_string());
+        write!(
+            f,
+            "{} {} {}: {}",
+            time, self.kind, self.subject, self.message
+        )
+    }
+}
+
+impl From<InstanceOutcome> for Event {
+    fn from(outcome: InstanceOutcome) -> Self {
+        let kind = match outcome.change {
+            InstanceChange::Added => EventKind::InstanceAdded,
+            InstanceChange::Removed => EventKind::InstanceRemoved,
+        };
+        let mut event = Self::new(kind, &outcome.instance, outcome.to_string());
+        event.outcome = Some(outcome);
+        event
+    }
+}
+
+impl Event {
+    pub fn new(kind: EventKind, subject: &str, message: String) -> Self {
+        Self {
+            kind,
+            subject: subject.into(),
+            message,
+            timestamp: Local::now().timestamp(),
+            outcome: None,
+            change: None,
+        }
+    }
+
+    pub fn emit(self, ctx: &mut ContextManager) {
+        //! Logs the event (target `event`) and prepends it to the event list, keeping the latest 1000.
+        //! Events are best-effort: failing to store one never fails the operation behind it.
+        log::info!(target: "event", "{}", self);
+        if let Err(err) = self.record(ctx) {
+            log::warn!(target: "event", "could not be stored: {:?}", err);
+        }
+    }
+
+    fn record(&self, ctx: &mut ContextManager) -> Result<(), redis::RedisError> {
+        for cmd in self.get_record_cmds()? {
+            cmd.query::<()>(ctx.get_connection())?;
+        }
+        Ok(())
+    }
+
+    pub fn get_record_cmds(&self) -> Result<[redis::Cmd; 2], redis::RedisError> {
+        //! The writes behind `emit`, in order, for connections other than the context's.
+        let entry = serde_json::to_string(self).map_err(|err| {
+            redis::RedisError::from((
+                redis::ErrorKind::ClientError,
+                "Event serialization error",
+                err.to_string(),
+            ))
+        })?;
+        let mut push = redis::cmd("LPUSH");
+        push.arg(EVENTS_KEY).arg(entry);
+        let mut trim = redis::cmd("LTRIM");
+        trim.arg(EVENTS_KEY).arg(0).arg(EVENT_LOG_LENGTH - 1);
+        Ok([push, trim])
+    }
+
+    pub fn get_recent(
+        count: usize,
+        ctx: &mut ContextManager,
+    ) -> Result<Vec<Self>, redis::RedisError> {
+        //! Returns up to `count` events, newest first. Unparsable entries are skipped.
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+        let entries: Vec<String> = Self::get_recent_cmd(count).query(ctx.get_connection())?;
+        Ok(Self::from_entries(&entries))
+    }
+
+    pub fn get_recent_cmd(count: usize) -> redis::Cmd {
+        //! The read behind `get_recent`, for batching with other reads (`count` > 0).
+        let mut cmd = redis::cmd("LRANGE");
+        cmd.arg(EVENTS_KEY).arg(0).arg(count as isize - 1);
+        cmd
+    }
+
+    pub fn from_entries(entries: &[String]) -> Vec<Self> {
+        entries
+            .iter()
+            .filter_map(|entry| serde_json::from_str(entry).ok())
+            .collect()
+    }
+}
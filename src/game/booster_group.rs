@@ -1,6 +1,11 @@
-use strum_macros::{Display, EnumString};
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter, EnumString};
 
-#[derive(Clone, Copy, Debug, Display, EnumString)]
+use crate::server::server_group::ServerGroup;
+
+use super::utils::{GAME_TO_BOOSTER_GROUP, SERVER_PREFIX_TO_GAME};
+
+#[derive(Clone, Copy, Debug, Display, EnumString, EnumIter, PartialEq, Eq)]
 #[allow(non_camel_case_types)]
 pub enum BoosterGroup {
     Arcade,
@@ -17,3 +22,45 @@ pub enum BoosterGroup {
     Champions,
     Nano_Games,
 }
+
+fn normalize(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+impl BoosterGroup {
+    pub fn closest(value: &str) -> Option<Self> {
+        //! Matches values that only differ in case, spacing or punctuation ("draw my thing"),
+        //! otherwise the single booster group whose name contains the value or vice versa.
+        let value = normalize(value);
+        if value.is_empty() {
+            return None;
+        }
+        if let Some(exact) = Self::iter().find(|group| normalize(&group.to_string()) == value) {
+            return Some(exact);
+        }
+        let partial: Vec<Self> = Self::iter()
+            .filter(|group| {
+                let name = normalize(&group.to_string());
+                name.contains(&value) || value.contains(&name)
+            })
+            .collect();
+        match partial.as_slice() {
+            [only] => Some(*only),
+            _ => None,
+        }
+    }
+    pub fn of_group(group: &ServerGroup) -> Option<String> {
+        //! The booster group `group` is boosted through: its boosterGroup, or its game's
+        //! (`GAME_TO_BOOSTER_GROUP`) when unset.
+        group.booster_group.clone().or_else(|| {
+            SERVER_PREFIX_TO_GAME
+                .get(group.prefix.as_str())
+                .and_then(|game| GAME_TO_BOOSTER_GROUP.get(game))
+                .map(|booster| booster.to_string())
+        })
+    }
+}
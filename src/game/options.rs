@@ -1,19 +1,25 @@
 use std::str::FromStr;
 
-use rand::Rng;
-use strum::IntoEnumIterator;
-
 use crate::{
-    context_manager::ContextManager, error::parsing_error::ServerGroupParsingError, region::Region,
-    server::server_group::ServerGroup,
+    context_manager::ContextManager,
+    error::parsing_error::ServerGroupParsingError,
+    events::{Event, EventKind},
+    region::Region,
+    server::{
+        port::{allocate_port_section, PortSection},
+        server_group::ServerGroup,
+        version::DEFAULT_MINECRAFT_VERSION,
+    },
+    store::entity::RedisEntity,
 };
 
 use super::{
     booster_group::BoosterGroup,
     r#type::GameType,
     utils::{
-        CUSTOM_GAME_OPTIONS, GAME_TO_BOOSTER_GROUP, GAME_TO_NPC, GAME_TO_PLAYER_COUNT,
-        GAME_TO_SERVER_PREFIX, GAME_TO_TEAM_SERVER, MIXED_ARCADE_GAMES, SERVER_PREFIX_TO_GAME,
+        get_games_without_player_count, CUSTOM_GAME_OPTIONS, GAME_TO_BOOSTER_GROUP, GAME_TO_NPC,
+        GAME_TO_PLAYER_COUNT, GAME_TO_SERVER_PREFIX, GAME_TO_TEAM_SERVER, MIXED_ARCADE_GAMES,
+        SERVER_PREFIX_TO_GAME,
     },
 };
 
@@ -26,6 +32,8 @@ pub struct GameOptions {
     pub min_players: u8,
     pub max_players: u8,
     pub port_section: u16,
+    pub ram: u16,
+    pub cpu: u8,
     pub arcade_group: bool,
     pub world_zip: String,
     pub plugin: String,
@@ -58,6 +66,7 @@ pub struct GameOptions {
     pub region: Region,
     pub portal_bottom_corner_location: Option<String>,
     pub portal_top_corner_location: Option<String>,
+    pub minecraft_version: String,
 }
 
 impl GameOptions {
@@ -71,35 +80,51 @@ impl GameOptions {
             // for custom game options
             if cached.is_none() {
                 let mut new = options.clone();
-                new.port_section = Self::rnd_port(ctx)?;
+                new.port_section = Self::allocate_port(ctx)?;
+                let resources = ctx.get_config().resources.get_resources(&game, &new.plugin);
+                new.ram = resources.ram;
+                new.cpu = resources.cpu;
                 return Ok(new);
             }
         }
-        let (min_players, max_players) = cached.map_or(
-            GAME_TO_PLAYER_COUNT.get(&game).cloned().unwrap_or((8, 16)),
-            |data| (data.min_players, data.max_players),
+        let plugin: String = cached.map_or("Arcade.jar".into(), |data| data.plugin.clone());
+        let (ram, cpu) = cached.map_or_else(
+            || {
+                let resources = ctx.get_config().resources.get_resources(&game, &plugin);
+                (resources.ram, resources.cpu)
+            },
+            |data| (data.ram, data.cpu),
         );
+        let (min_players, max_players) = match cached {
+            Some(data) => (data.min_players, data.max_players),
+            None => match GAME_TO_PLAYER_COUNT.get(&game) {
+                Some(&counts) => counts,
+                None => Self::get_fallback_player_counts(game, ctx),
+            },
+        };
         Ok(Self {
             prefix: GAME_TO_SERVER_PREFIX
                 .get(&game)
                 .cloned()
                 .unwrap_or_default()
                 .into(),
-            staff_only: cached.map_or(false, |data| data.staff_only),
-            whitelist: cached.map_or(false, |data| data.whitelist),
+            staff_only: cached.is_some_and(|data| data.staff_only),
+            whitelist: cached.is_some_and(|data| data.whitelist),
             host: cached
                 .and_then(|data| data.host.clone())
                 .filter(|x| !x.is_empty()),
             min_players,
             max_players,
-            port_section: cached.map_or(Self::rnd_port(ctx)?, |data| data.port_section),
-            arcade_group: cached.map_or(true, |data| data.arcade_group),
+            port_section: cached.map_or(Self::allocate_port(ctx)?, |data| data.port_section),
+            ram,
+            cpu,
+            arcade_group: cached.is_none_or(|data| data.arcade_group),
             world_zip: cached.map_or("arcade.zip".into(), |data| data.world_zip.clone()),
-            plugin: cached.map_or("Arcade.jar".into(), |data| data.plugin.clone()),
+            plugin,
             config_path: cached.map_or("plugins/Arcade".into(), |data| data.config_path.clone()),
-            pvp: cached.map_or(true, |data| data.pvp),
-            tournament: cached.map_or(false, |data| data.tournament),
-            tournament_points: cached.map_or(false, |data| data.tournament_points),
+            pvp: cached.is_none_or(|data| data.pvp),
+            tournament: cached.is_some_and(|data| data.tournament),
+            tournament_points: cached.is_some_and(|data| data.tournament_points),
             games: cached.map_or(
                 match Some(game) {
                     Some(GameType::MixedArcade) => Some(
@@ -117,22 +142,22 @@ impl GameOptions {
                 |data| data.games.clone().filter(|x| !x.is_empty() && x != "null"),
             ),
             server_type: cached.map_or("Minigames".into(), |data| data.server_type.clone()),
-            add_no_cheat: cached.map_or(true, |data| data.add_no_cheat),
-            add_world_edit: cached.map_or(false, |data| data.add_world_edit),
-            team_rejoin: cached.map_or(false, |data| data.team_rejoin),
-            team_auto_join: cached.map_or(true, |data| data.team_auto_join),
-            team_force_balance: cached.map_or(false, |data| data.team_force_balance),
-            game_auto_start: cached.map_or(true, |data| data.game_auto_start),
-            game_timeout: cached.map_or(true, |data| data.game_timeout),
-            game_voting: cached.map_or(false, |data| data.game_voting),
-            map_voting: cached.map_or(true, |data| data.map_voting),
-            reward_gems: cached.map_or(true, |data| data.reward_gems),
-            reward_items: cached.map_or(true, |data| data.reward_items),
-            reward_stats: cached.map_or(true, |data| data.reward_stats),
-            reward_achievements: cached.map_or(true, |data| data.reward_achievements),
-            hotbar_inventory: cached.map_or(true, |data| data.hotbar_inventory),
-            hotbar_hub_clock: cached.map_or(true, |data| data.hotbar_hub_clock),
-            player_kick_idle: cached.map_or(true, |data| data.player_kick_idle),
+            add_no_cheat: cached.is_none_or(|data| data.add_no_cheat),
+            add_world_edit: cached.is_some_and(|data| data.add_world_edit),
+            team_rejoin: cached.is_some_and(|data| data.team_rejoin),
+            team_auto_join: cached.is_none_or(|data| data.team_auto_join),
+            team_force_balance: cached.is_some_and(|data| data.team_force_balance),
+            game_auto_start: cached.is_none_or(|data| data.game_auto_start),
+            game_timeout: cached.is_none_or(|data| data.game_timeout),
+            game_voting: cached.is_some_and(|data| data.game_voting),
+            map_voting: cached.is_none_or(|data| data.map_voting),
+            reward_gems: cached.is_none_or(|data| data.reward_gems),
+            reward_items: cached.is_none_or(|data| data.reward_items),
+            reward_stats: cached.is_none_or(|data| data.reward_stats),
+            reward_achievements: cached.is_none_or(|data| data.reward_achievements),
+            hotbar_inventory: cached.is_none_or(|data| data.hotbar_inventory),
+            hotbar_hub_clock: cached.is_none_or(|data| data.hotbar_hub_clock),
+            player_kick_idle: cached.is_none_or(|data| data.player_kick_idle),
             team_server: cached.map_or(GAME_TO_TEAM_SERVER.get(&game).cloned(), |data| {
                 SERVER_PREFIX_TO_GAME
                     .get(&data.team_server_key.clone()?.as_ref())
@@ -155,20 +180,43 @@ impl GameOptions {
             portal_top_corner_location: cached
                 .and_then(|data| data.portal_top_corner_location.clone())
                 .filter(|x| !x.is_empty()),
+            minecraft_version: cached.map_or(DEFAULT_MINECRAFT_VERSION.into(), |data| {
+                data.minecraft_version.clone()
+            }),
         })
     }
 
+    fn get_fallback_player_counts(game: GameType, ctx: &mut ContextManager) -> (u8, u8) {
+        //! Default player counts for a game type without an entry, announced with a warning
+        //! event listing every game type in the same situation.
+        let (min, max) = ctx.get_config().resources.players;
+        let missing: Vec<String> = get_games_without_player_count()
+            .iter()
+            .map(|game| game.to_string())
+            .collect();
+        Event::new(
+            EventKind::Warning,
+            &game.to_string(),
+            format!(
+                "no explicit player counts, using default {}-{} (game types without entries: {})",
+                min,
+                max,
+                missing.join(", ")
+            ),
+        )
+        .emit(ctx);
+        (min, max)
+    }
+
     pub fn get_if_port_section_conflict(lhs: u16, rhs: u16) -> bool {
         //! Returns `true` if either left port section
         //! or right port section conflicts.
         // lhs = port_section,
         // rhs = cached_port
-        (lhs < rhs && rhs <= lhs + 10) // cache conflicts with NEW
-        || (rhs < lhs && lhs <= rhs + 10) // OR NEW conflicts with cache
-        || (rhs == lhs) // they're the same
+        PortSection::new(lhs).overlaps(&PortSection::new(rhs))
     }
 
-    pub fn check_port_section_conflicts(port_section: u16, cached_ports: &Vec<u16>) -> bool {
+    pub fn check_port_section_conflicts(port_section: u16, cached_ports: &[u16]) -> bool {
         //! Checks if new port section conflicts with other port sections in cache.
         //! Each port section is unique to their ServerGroup.
         //! A port section holds 10 values where a certain server instances's port can be made from.
@@ -178,22 +226,17 @@ impl GameOptions {
             .any(|&cached_port| Self::get_if_port_section_conflict(port_section, cached_port))
     }
 
-    fn rnd_port(ctx: &mut ContextManager) -> Result<u16, ServerGroupParsingError> {
+    fn allocate_port(ctx: &mut ContextManager) -> Result<u16, ServerGroupParsingError> {
         //! Returns non-conflicting port section
-        let mut rng = rand::thread_rng();
         let port_sections: Vec<u16> = ServerGroup::get_all_port_sections(ctx)?;
-        let mut port_section: u16 = rng.gen_range(25566..26001);
-        while Self::check_port_section_conflicts(port_section, &port_sections)
-        // port section conflict (either can be 10 above the other)
-        {
-            port_section = rng.gen_range(25566..26001);
-        }
-        Ok(port_section)
+        let info = ctx.get_config().ports.clone();
+        allocate_port_section(&info, &port_sections, ctx.get_random())
+            .map_err(|err| ServerGroupParsingError::new(err.to_string()))
     }
 
     fn load_from_cache(game: &GameType, ctx: &mut ContextManager) -> Option<ServerGroup> {
         //! Loads from pre-existing ServerGroup cache
         let prefix = GAME_TO_SERVER_PREFIX.get(game).cloned()?;
-        ServerGroup::get_server_group(&format!("servergroups.{}", prefix), ctx).ok()
+        ServerGroup::get(prefix, ctx).ok()
     }
 }
@@ -1,6 +1,9 @@
+use serde::{Deserialize, Serialize};
 use strum_macros::{Display, EnumIter, EnumString};
 
-#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Hash, EnumString, EnumIter)]
+#[derive(
+    Clone, Copy, Debug, Display, PartialEq, Eq, Hash, EnumString, EnumIter, Deserialize, Serialize,
+)]
 pub enum GameType {
     // synonymous for GameDisplay in Mineplex's code
     Micro,
@@ -1,10 +1,13 @@
 use std::collections::HashMap;
 
 use lazy_static::lazy_static;
+use strum::IntoEnumIterator;
 
 use crate::{
     region::Region,
-    server::{generic::GenericServer, server_group::ServerGroup},
+    server::{
+        generic::GenericServer, server_group::ServerGroup, version::DEFAULT_MINECRAFT_VERSION,
+    },
 };
 
 use super::{booster_group::BoosterGroup, options::GameOptions, r#type::GameType};
@@ -184,6 +187,7 @@ lazy_static! {
             portal_bottom_corner_location: None,
             portal_top_corner_location: None,
             npc_name: None,
+            minecraft_version: DEFAULT_MINECRAFT_VERSION.to_string(),
         })
     ]);
     pub static ref CUSTOM_GAME_OPTIONS: HashMap<GameType, GameOptions> = HashMap::from([
@@ -197,6 +201,8 @@ lazy_static! {
                 min_players: 1,
                 max_players: 50,
                 port_section: 25700, // changes automatically
+                ram: 512, // changes automatically
+                cpu: 1,
                 arcade_group: false,
                 world_zip: "clans.zip".to_string(),
                 plugin: "Clans.jar".to_string(),
@@ -225,6 +231,7 @@ lazy_static! {
                 team_server: None,
                 booster_group: None,
                 npc_name: Some("Clans".to_string()),
+                minecraft_version: DEFAULT_MINECRAFT_VERSION.to_string(),
                 resource_pack: None,
                 region: Region::US,
                 portal_bottom_corner_location: None,
@@ -241,6 +248,8 @@ lazy_static! {
                 min_players: 1,
                 max_players: 50,
                 port_section: 25700, // changes automatically
+                ram: 512, // changes automatically
+                cpu: 1,
                 arcade_group: false,
                 world_zip: "clanshub.zip".to_string(),
                 plugin: "ClansHub.jar".to_string(),
@@ -269,6 +278,7 @@ lazy_static! {
                 team_server: None,
                 booster_group: None,
                 npc_name: Some("Clans".to_string()),
+                minecraft_version: DEFAULT_MINECRAFT_VERSION.to_string(),
                 resource_pack: None,
                 region: Region::US,
                 portal_bottom_corner_location: None,
@@ -277,3 +287,14 @@ lazy_static! {
         )
     ]);
 }
+
+pub fn has_explicit_player_count(game: &GameType) -> bool {
+    GAME_TO_PLAYER_COUNT.contains_key(game) || CUSTOM_GAME_OPTIONS.contains_key(game)
+}
+
+pub fn get_games_without_player_count() -> Vec<GameType> {
+    //! Game types that fall back to the configured default player counts.
+    GameType::iter()
+        .filter(|game| !has_explicit_player_count(game))
+        .collect()
+}
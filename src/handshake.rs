@@ -0,0 +1,185 @@
+use std::{collections::HashMap, fmt::Display, ops::RangeInclusive};
+
+use crate::{
+    context_manager::ContextManager,
+    safety::SafetyError,
+    store::{entity::RedisEntity, keys::KeyBuilder},
+};
+
+const HANDSHAKE_PREFIX: &str = "servermonitor.handshake.";
+const MANAGER_ID: &str = "manager";
+
+/// Version of the redis layout (groups, statuses, commands) this manager reads and writes.
+pub const SCHEMA_VERSION: u32 = 1;
+/// Plugin schema versions this manager can work with.
+pub const SUPPORTED_PLUGIN_SCHEMAS: RangeInclusive<u32> = 1..=1;
+pub const CAPABILITIES: [&str; 5] = ["statuses", "commands", "journal", "events", "heartbeat"];
+
+/// What one side (the manager, or a plugin such as Arcade or Hub) says it speaks.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Handshake {
+    pub party: String,
+    pub schema_version: u32,
+    pub min_manager_schema: Option<u32>, // plugins may require a newer manager
+    pub capabilities: Vec<String>,
+}
+
+impl Display for Handshake {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} schema {} [{}]",
+            self.party,
+            self.schema_version,
+            self.capabilities.join(", ")
+        )
+    }
+}
+
+impl RedisEntity for Handshake {
+    type Error = String;
+
+    fn get_id(&self) -> String {
+        self.party.clone()
+    }
+
+    fn from_map(map: HashMap<String, String>) -> Result<Self, Self::Error> {
+        let party = map
+            .get("party")
+            .cloned()
+            .ok_or("handshake is missing \"party\"")?;
+        let parse = |key: &str| {
+            map.get(key)
+                .filter(|value| !value.is_empty())
+                .map(|value| {
+                    value
+                        .parse::<u32>()
+                        .map_err(|err| format!("handshake of {} {:?}: {:?}", party, key, err))
+                })
+                .transpose()
+        };
+        Ok(Self {
+            schema_version: parse("schemaVersion")?.ok_or(format!(
+                "handshake of {} is missing \"schemaVersion\"",
+                party
+            ))?,
+            min_manager_schema: parse("minManagerSchema")?,
+            capabilities: map
+                .get("capabilities")
+                .map(|value| {
+                    value
+                        .split(',')
+                        .filter(|x| !x.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            party,
+        })
+    }
+
+    fn to_map(&self) -> HashMap<String, String> {
+        HashMap::from([
+            ("party".into(), self.party.clone()),
+            ("schemaVersion".into(), self.schema_version.to_string()),
+            (
+                "minManagerSchema".into(),
+                self.min_manager_schema
+                    .map(|x| x.to_string())
+                    .unwrap_or_default(),
+            ),
+            ("capabilities".into(), self.capabilities.join(",")),
+        ])
+    }
+
+    fn get_key(id: &str, _keys: &KeyBuilder) -> String {
+        format!("{}{}", HANDSHAKE_PREFIX, id)
+    }
+
+    fn get_pattern(_keys: &KeyBuilder) -> String {
+        format!("{}*", HANDSHAKE_PREFIX)
+    }
+}
+
+impl Handshake {
+    pub fn of_manager() -> Self {
+        Self {
+            party: MANAGER_ID.into(),
+            schema_version: SCHEMA_VERSION,
+            min_manager_schema: None,
+            capabilities: CAPABILITIES.iter().map(|x| x.to_string()).collect(),
+        }
+    }
+
+    pub fn get_mismatch(&self) -> Option<String> {
+        //! Why this plugin can't work with this manager, if it can't.
+        if !SUPPORTED_PLUGIN_SCHEMAS.contains(&self.schema_version) {
+            return Some(format!(
+                "{} uses schema {}, this manager supports {}-{}",
+                self.party,
+                self.schema_version,
+                SUPPORTED_PLUGIN_SCHEMAS.start(),
+                SUPPORTED_PLUGIN_SCHEMAS.end()
+            ));
+        }
+        match self.min_manager_schema {
+            Some(min) if min > SCHEMA_VERSION => Some(format!(
+                "{} requires manager schema {}, this manager uses {}",
+                self.party, min, SCHEMA_VERSION
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// Outcome of comparing the manager's handshake with every plugin's.
+#[derive(Clone, Debug, Default)]
+pub struct HandshakeReport {
+    pub plugins: Vec<Handshake>,
+    pub mismatches: Vec<String>,
+}
+
+impl HandshakeReport {
+    pub fn is_compatible(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+pub fn publish(ctx: &mut ContextManager) -> Result<(), String> {
+    //! Advertises this manager's schema version and capabilities to the plugins.
+    Handshake::of_manager().save(ctx)
+}
+
+pub fn check(ctx: &mut ContextManager) -> HandshakeReport {
+    //! Reads every plugin handshake. Plugins that never wrote one are assumed compatible,
+    //! handshakes that can't be parsed are not.
+    let handshakes = Handshake::get_all(ctx);
+    let mut report = HandshakeReport::default();
+    for (key, err) in handshakes.failed {
+        report
+            .mismatches
+            .push(format!("{} could not be read: {}", key, err));
+    }
+    for handshake in handshakes.ok {
+        if handshake.party == MANAGER_ID {
+            continue;
+        }
+        if let Some(mismatch) = handshake.get_mismatch() {
+            report.mismatches.push(mismatch);
+        }
+        report.plugins.push(handshake);
+    }
+    report
+}
+
+pub fn ensure_compatible(operation: &str, ctx: &mut ContextManager) -> Result<(), SafetyError> {
+    //! Refuses destructive operations while a plugin speaks an incompatible schema.
+    let report = check(ctx);
+    if !report.is_compatible() {
+        return Err(SafetyError::Incompatible(
+            operation.into(),
+            report.mismatches.join("; "),
+        ));
+    }
+    Ok(())
+}
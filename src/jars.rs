@@ -0,0 +1,229 @@
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{context_manager::ContextManager, server::dedicated::server::DedicatedServer};
+
+#[derive(Error, Debug)]
+pub enum JarError {
+    #[error("Jar Error: No artifact configured for `{0}`")]
+    NotConfigured(String),
+    #[error("Jar Error: Could not fetch `{0}`")]
+    FetchError(String),
+    #[error("Jar Error: Checksum mismatch: `{0}`")]
+    ChecksumMismatch(String),
+    #[error("Jar Error: Could not copy jar to node: `{0}`")]
+    DistributionError(String),
+}
+
+/// A server jar for one version/flavor, read from a local path or downloaded from a URL.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct JarArtifact {
+    pub version: String,
+    #[serde(default = "default_flavor")]
+    pub flavor: String,
+    pub source: String, // path or http(s) URL
+    #[serde(default)]
+    pub sha1: Option<String>,
+}
+
+fn default_flavor() -> String {
+    "spigot".into()
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct JarsInfo {
+    pub cache_path: String,  // local download cache
+    pub remote_path: String, // where nodes expect their jars
+    #[serde(default)]
+    pub artifacts: Vec<JarArtifact>,
+}
+
+impl Default for JarsInfo {
+    fn default() -> Self {
+        Self {
+            cache_path: "jars".into(),
+            remote_path: "/home/mineplex/jars".into(),
+            artifacts: Vec::new(),
+        }
+    }
+}
+
+impl JarsInfo {
+    pub fn get_artifact(&self, file_name: &str) -> Option<&JarArtifact> {
+        self.artifacts
+            .iter()
+            .find(|artifact| artifact.get_file_name() == file_name)
+    }
+}
+
+impl JarArtifact {
+    pub fn get_file_name(&self) -> String {
+        format!("{}-{}.jar", self.flavor, self.version)
+    }
+
+    pub fn is_remote(&self) -> bool {
+        self.source.starts_with("http://") || self.source.starts_with("https://")
+    }
+
+    pub fn fetch(&self, cache_path: &str) -> Result<PathBuf, JarError> {
+        //! Returns the cached jar, downloading (or copying) it first if it is missing or
+        //! fails its checksum. A fetched jar only replaces the cached one once verified.
+        let path = Path::new(cache_path).join(self.get_file_name());
+        if path.exists() && self.verify(&path).is_ok() {
+            return Ok(path);
+        }
+        fs::create_dir_all(cache_path)
+            .map_err(|err| JarError::FetchError(format!("{}: {:?}", cache_path, err)))?;
+        let partial = path.with_extension("jar.part");
+        if self.is_remote() {
+            let status = Command::new("curl")
+                .args(["-fsSL", "-o"])
+                .arg(&partial)
+                .arg(&self.source)
+                .status()
+                .map_err(|err| JarError::FetchError(format!("{}: {:?}", self.source, err)))?;
+            if !status.success() {
+                return Err(JarError::FetchError(format!(
+                    "{}: curl exited with {}",
+                    self.source, status
+                )));
+            }
+        } else {
+            fs::copy(&self.source, &partial)
+                .map_err(|err| JarError::FetchError(format!("{}: {:?}", self.source, err)))?;
+        }
+        if let Err(err) = self.verify(&partial) {
+            let _ = fs::remove_file(&partial);
+            return Err(err);
+        }
+        fs::rename(&partial, &path)
+            .map_err(|err| JarError::FetchError(format!("{}: {:?}", path.display(), err)))?;
+        Ok(path)
+    }
+
+    pub fn verify(&self, path: &Path) -> Result<(), JarError> {
+        //! Compares the file's sha1 with the configured one (jars without a checksum always pass).
+        let Some(expected) = &self.sha1 else {
+            return Ok(());
+        };
+        let bytes = fs::read(path)
+            .map_err(|err| JarError::FetchError(format!("{}: {:?}", path.display(), err)))?;
+        let actual = sha1_smol::Sha1::from(bytes).digest().to_string();
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(JarError::ChecksumMismatch(format!(
+                "{} has sha1 {}, expected {}",
+                path.display(),
+                actual,
+                expected
+            )));
+        }
+        Ok(())
+    }
+}
+
+pub fn distribute(path: &Path, node: &DedicatedServer, remote_path: &str) -> Result<(), JarError> {
+    //! Copies a cached jar into `remote_path` on the node, directly when the node is this
+    //! host (see `DedicatedServer::is_local`).
+    if node.is_local() {
+        return copy_local(path, Path::new(remote_path));
+    }
+    let target = format!("{}:{}/", node.private_address, remote_path);
+    let status = Command::new("scp")
+        .args(["-q", "-p"])
+        .arg(path)
+        .arg(&target)
+        .status()
+        .map_err(|err| JarError::DistributionError(format!("{}: {:?}", target, err)))?;
+    if !status.success() {
+        return Err(JarError::DistributionError(format!(
+            "{}: scp exited with {}",
+            target, status
+        )));
+    }
+    Ok(())
+}
+
+pub fn ensure_on_node(
+    file_name: &str,
+    node: &DedicatedServer,
+    ctx: &mut ContextManager,
+) -> Result<(), JarError> {
+    //! Makes sure `node` has the jar a launch is about to use.
+    let jars = ctx.get_config().jars.clone();
+    let artifact = jars
+        .get_artifact(file_name)
+        .ok_or_else(|| JarError::NotConfigured(file_name.into()))?;
+    let path = artifact.fetch(&jars.cache_path)?;
+    distribute(&path, node, &jars.remote_path)
+}
+
+/// What a `jars sync` did.
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    pub fetched: Vec<String>,
+    pub distributed: usize,
+    pub failed: Vec<(String, JarError)>,
+}
+
+impl Display for SyncReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Synced {} jar(s) to {} node copies, {} failure(s)",
+            self.fetched.len(),
+            self.distributed,
+            self.failed.len()
+        )
+    }
+}
+
+pub fn sync(ctx: &mut ContextManager) -> SyncReport {
+    //! Fetches every configured jar and copies it to every node.
+    let jars = ctx.get_config().jars.clone();
+    let nodes = ctx.get_config().dedicated_servers.servers.clone();
+    let mut report = SyncReport::default();
+    for artifact in jars.artifacts.iter() {
+        let path = match artifact.fetch(&jars.cache_path) {
+            Ok(path) => path,
+            Err(err) => {
+                report.failed.push((artifact.get_file_name(), err));
+                continue;
+            }
+        };
+        report.fetched.push(artifact.get_file_name());
+        for node in nodes.iter() {
+            match distribute(&path, node, &jars.remote_path) {
+                Ok(()) => report.distributed += 1,
+                Err(err) => report.failed.push((
+                    format!("{} -> {}", artifact.get_file_name(), node.name),
+                    err,
+                )),
+            }
+        }
+    }
+    report
+}
+
+fn copy_local(path: &Path, remote_path: &Path) -> Result<(), JarError> {
+    let target = remote_path.join(path.file_name().unwrap_or_default());
+    let to_error = |err: std::io::Error| {
+        JarError::DistributionError(format!("{}: {:?}", target.display(), err))
+    };
+    fs::create_dir_all(remote_path).map_err(to_error)?;
+    // the cache may be the jar directory itself, and copying a file onto itself empties it
+    let same = fs::canonicalize(path)
+        .ok()
+        .zip(fs::canonicalize(&target).ok())
+        .is_some_and(|(path, target)| path == target);
+    if same {
+        return Ok(());
+    }
+    fs::copy(path, &target).map(|_| ()).map_err(to_error)
+}
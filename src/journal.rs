@@ -0,0 +1,203 @@
+use std::fmt::Display;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    context_manager::ContextManager,
+    events::Event,
+    server::{minecraft::MinecraftServer, server_group::ServerGroup},
+    store::entity::RedisEntity,
+};
+
+/// Hash of entry id -> intent for every operation that has started but not completed.
+const JOURNAL_KEY: &str = "manager.journal";
+
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub enum Operation {
+    Launch {
+        node: String,
+        group: String,
+        server_num: usize,
+    },
+    Kill {
+        node: String,
+        group: String,
+        server_num: usize,
+    },
+}
+
+impl Operation {
+    pub fn get_instance(&self) -> String {
+        match self {
+            Operation::Launch {
+                group, server_num, ..
+            }
+            | Operation::Kill {
+                group, server_num, ..
+            } => format!("{}-{}", group, server_num),
+        }
+    }
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operation::Launch { node, .. } => {
+                write!(f, "launch {} on {}", self.get_instance(), node)
+            }
+            Operation::Kill { node, .. } => write!(f, "kill {} on {}", self.get_instance(), node),
+        }
+    }
+}
+
+/// Write-ahead record of an operation, removed once the operation completes.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct JournalEntry {
+    pub id: String,
+    pub operation: Operation,
+    pub started: i64, // seconds since epoch
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ReplayOutcome {
+    RolledForward(JournalEntry),
+    RolledBack(JournalEntry),
+    Failed(JournalEntry, String),
+}
+
+impl Display for ReplayOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayOutcome::RolledForward(entry) => {
+                write!(f, "rolled forward: {}", entry.operation)
+            }
+            ReplayOutcome::RolledBack(entry) => write!(f, "rolled back: {}", entry.operation),
+            ReplayOutcome::Failed(entry, err) => {
+                write!(f, "could not recover: {} ({})", entry.operation, err)
+            }
+        }
+    }
+}
+
+impl JournalEntry {
+    pub fn begin(
+        operation: Operation,
+        ctx: &mut ContextManager,
+    ) -> Result<JournalEntry, redis::RedisError> {
+        //! Records the intent to run `operation`; must happen before any side effect.
+        let now = Local::now();
+        let entry = JournalEntry {
+            id: format!("{}:{}", now.timestamp_millis(), operation.get_instance()),
+            operation,
+            started: now.timestamp(),
+        };
+        let json = serde_json::to_string(&entry).map_err(|err| {
+            redis::RedisError::from((
+                redis::ErrorKind::ClientError,
+                "Journal serialization error",
+                err.to_string(),
+            ))
+        })?;
+        redis::cmd("HSET")
+            .arg(JOURNAL_KEY)
+            .arg(&entry.id)
+            .arg(json)
+            .query::<()>(ctx.get_connection())?;
+        Ok(entry)
+    }
+
+    pub fn complete(&self, ctx: &mut ContextManager) -> Result<(), redis::RedisError> {
+        redis::cmd("HDEL")
+            .arg(JOURNAL_KEY)
+            .arg(&self.id)
+            .query(ctx.get_connection())
+    }
+
+    pub fn get_pending(ctx: &mut ContextManager) -> Result<Vec<JournalEntry>, redis::RedisError> {
+        //! Operations that were started but never completed, oldest first.
+        //! Entries that can't be parsed are skipped.
+        let entries: Vec<String> = redis::cmd("HVALS")
+            .arg(JOURNAL_KEY)
+            .query(ctx.get_connection())?;
+        let mut pending: Vec<JournalEntry> = entries
+            .iter()
+            .filter_map(|entry| serde_json::from_str(entry).ok())
+            .collect();
+        pending.sort_by_key(|entry| entry.started);
+        Ok(pending)
+    }
+
+    fn recover(&self, ctx: &mut ContextManager) -> ReplayOutcome {
+        //! Launches whose server made it online are rolled forward (kept and tracked),
+        //! others are rolled back by killing whatever was half started.
+        //! Interrupted kills are always rolled forward by killing again.
+        let (node, group, server_num, is_launch) = match &self.operation {
+            Operation::Launch {
+                node,
+                group,
+                server_num,
+            } => (node, group, *server_num, true),
+            Operation::Kill {
+                node,
+                group,
+                server_num,
+            } => (node, group, *server_num, false),
+        };
+        let group = match ServerGroup::get(group, ctx) {
+            Ok(group) => group,
+            Err(err) => return ReplayOutcome::Failed(self.clone(), err.to_string()),
+        };
+        let instance = self.operation.get_instance();
+        let is_live = MinecraftServer::get_status(&instance, &group.region, ctx).is_ok();
+        ctx.with_dedicated_servers(|servers, ctx| {
+            let Some(ds) = servers.get_server_mut(node) else {
+                return ReplayOutcome::Failed(self.clone(), format!("unknown node {:?}", node));
+            };
+            if is_launch && is_live {
+                if !ds.get_server_nums(&group).contains(&server_num) {
+                    match ds.add_server(&group, server_num) {
+                        Ok(outcome) => Event::from(outcome).emit(ctx),
+                        Err(err) => return ReplayOutcome::Failed(self.clone(), err.to_string()),
+                    }
+                }
+                return ReplayOutcome::RolledForward(self.clone());
+            }
+            match ds.kill_server(&group, server_num, true, ctx) {
+                Ok(()) if is_launch => ReplayOutcome::RolledBack(self.clone()),
+                Ok(()) => ReplayOutcome::RolledForward(self.clone()),
+                Err(err) => ReplayOutcome::Failed(self.clone(), err.to_string()),
+            }
+        })
+    }
+
+    pub fn replay(ctx: &mut ContextManager) -> Result<Vec<ReplayOutcome>, redis::RedisError> {
+        //! Recovers every interrupted operation, then clears it from the journal.
+        //! Failed recoveries are cleared too (and reported) so they don't block every start.
+        let mut outcomes = Vec::new();
+        for entry in Self::get_pending(ctx)? {
+            let outcome = entry.recover(ctx);
+            entry.complete(ctx)?;
+            outcomes.push(outcome);
+        }
+        Ok(outcomes)
+    }
+
+    pub fn replay_instance(
+        instance: &str,
+        ctx: &mut ContextManager,
+    ) -> Result<Vec<ReplayOutcome>, redis::RedisError> {
+        //! `replay` for the operations on one instance, e.g. to roll back a launch that just
+        //! failed instead of holding its slot until the next start.
+        let mut outcomes = Vec::new();
+        for entry in Self::get_pending(ctx)?
+            .into_iter()
+            .filter(|entry| entry.operation.get_instance() == instance)
+        {
+            let outcome = entry.recover(ctx);
+            entry.complete(ctx)?;
+            outcomes.push(outcome);
+        }
+        Ok(outcomes)
+    }
+}
@@ -0,0 +1,26 @@
+#[cfg(feature = "aio")]
+pub mod aio;
+pub mod apply;
+pub mod batch;
+pub mod cli;
+pub mod clock;
+pub mod config;
+pub mod context_manager;
+pub mod dev;
+pub mod doctor;
+pub mod error;
+pub mod events;
+pub mod game;
+pub mod handshake;
+pub mod jars;
+pub mod journal;
+pub mod maps;
+pub mod monitor;
+pub mod queue;
+pub mod random;
+pub mod region;
+pub mod safety;
+pub mod server;
+pub mod store;
+pub mod strategy;
+pub mod undo;
@@ -1,51 +1,40 @@
-mod config;
-mod context_manager;
-mod error;
-mod game;
-mod region;
-mod server;
-
-use game::Game;
-
-use crate::{
+use plex_redis_manager::{
+    cli::{self, Args, CliError},
     config::models::Config,
     context_manager::ContextManager,
-    error::parsing_error::ServerGroupParsingError,
-    game::r#type::GameType,
-    region::Region,
-    server::{
-        dedicated::server::DedicatedServer, generic::GenericServer, minecraft::MinecraftServer,
-        server_group::ServerGroup,
-    },
 };
 
-fn get_best_server_test(group: &ServerGroup, ctx: &mut ContextManager) -> () {
-    let dedicated_servers = ctx.get_dedicated_servers();
-    dbg!(&dedicated_servers);
-    let server_num = dedicated_servers.get_next_server_num(group);
-    let next: Option<&mut DedicatedServer> = dedicated_servers.get_best_dedicated_server(group);
-    let _ = next.map(|dedi| {
-        let result = dedi.add_server(group, server_num);
-        dbg!(result);
-        dbg!(&dedi);
-    });
-    dbg!(&dedicated_servers);
-    let next_available = dedicated_servers.get_best_dedicated_server(group); // new server found
-    dbg!(&next_available);
-}
-
 fn main() {
-    let mut ctx: ContextManager = ContextManager::new();
-    //let mut lobby = GenericServer::Lobby
-    //    .to_server_group(&mut ctx)
-    //    .expect("Lobby expected.")
-    //    .expect("Should've been Some(Lobby)");
-    let arcade = ServerGroup::from_game(
-        Game::from_game_type(GameType::MixedArcade, &mut ctx)
-            .expect("Game type should've been found"),
-    );
-    dbg!(get_best_server_test(&arcade, &mut ctx))
-    //let _ = lobby.create(&mut ctx);
-    //dbg!(MinecraftServer::from_server_group(&arcade, &mut ctx));
-    //dbg!(lobby);
+    cli::output::install();
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) if !err.use_stderr() => {
+            // --help, which needs no redis
+            print!("{}", err);
+            return;
+        }
+        Err(err) => {
+            let err = CliError::from(err);
+            eprintln!("{}", err);
+            std::process::exit(err.get_exit_code());
+        }
+    };
+    if let Some(result) = cli::completion::run_early(&args) {
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            std::process::exit(err.get_exit_code());
+        }
+        return;
+    }
+    let mut config = Config::get_config();
+    if let Some(path) = args.get_flag("snapshot") {
+        config.set_snapshot(Some(path.clone()));
+    }
+    let result = ContextManager::try_from_config_for(&config, args.get_purpose())
+        .map_err(CliError::from_connect_error)
+        .and_then(|mut ctx| cli::run(&args, &mut ctx));
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        std::process::exit(err.get_exit_code());
+    }
 }
@@ -0,0 +1,218 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::Path,
+    str::FromStr,
+};
+
+use thiserror::Error;
+
+use crate::{
+    context_manager::ContextManager,
+    game::{r#type::GameType, utils::GAME_TO_SERVER_PREFIX},
+    server::server_group::ServerGroup,
+};
+
+/// Set of a game's maps, for networks that don't keep their worlds on the manager's host.
+const CATALOG_PREFIX: &str = "maps.catalog.";
+/// Hash of group -> JSON list of maps turned off for it.
+const DISABLED_KEY: &str = "servermonitor.disabledmaps";
+
+#[derive(Error, Debug)]
+pub enum MapError {
+    #[error("Map Error: Could not read `{0}`")]
+    ReadError(String),
+    #[error("Map Error: Invalid map: `{0}`")]
+    InvalidMap(String),
+    #[error("Map Error: Redis Error: `{0}`")]
+    RedisError(String),
+}
+
+impl From<redis::RedisError> for MapError {
+    fn from(err: redis::RedisError) -> Self {
+        MapError::RedisError(err.to_string())
+    }
+}
+
+pub type MapPool = BTreeMap<String, BTreeSet<String>>; // game -> maps
+
+fn get_map_name(path: &Path) -> Option<String> {
+    //! `Castle.zip` and `Castle/` are both the map `Castle`.
+    let name = path.file_name()?.to_str()?;
+    match path.is_dir() {
+        true => Some(name.to_string()),
+        false => name.strip_suffix(".zip").map(String::from),
+    }
+}
+
+fn read_dir(path: &Path) -> Result<impl Iterator<Item = fs::DirEntry>, MapError> {
+    Ok(fs::read_dir(path)
+        .map_err(|err| MapError::ReadError(format!("{:?}: {}", path, err)))?
+        .filter_map(Result::ok))
+}
+
+fn scan_game(game_path: &Path) -> Result<BTreeSet<String>, MapError> {
+    if !game_path.is_dir() {
+        return Ok(BTreeSet::new());
+    }
+    Ok(read_dir(game_path)?
+        .filter_map(|map| get_map_name(&map.path()))
+        .collect())
+}
+
+pub fn scan_worlds(worlds_path: &Path) -> Result<MapPool, MapError> {
+    //! Maps laid out as `<worlds_path>/<GameType>/<map>[.zip]`.
+    //! Folders not named after a game type are skipped; a missing worlds path has no maps.
+    let mut catalog = MapPool::new();
+    if !worlds_path.is_dir() {
+        return Ok(catalog);
+    }
+    for game_dir in read_dir(worlds_path)? {
+        let path = game_dir.path();
+        let Some(game) = get_map_name(&path).filter(|_| path.is_dir()) else {
+            continue;
+        };
+        if GameType::from_str(&game).is_ok() {
+            catalog.insert(game, scan_game(&path)?);
+        }
+    }
+    Ok(catalog)
+}
+
+pub fn get_catalog(
+    game: &GameType,
+    ctx: &mut ContextManager,
+) -> Result<BTreeSet<String>, MapError> {
+    //! A game's maps: those under the worlds path plus those added to its catalog set.
+    let worlds_path = ctx.get_config().monitor_info.get_worlds_path().clone();
+    let mut maps = scan_game(&Path::new(&worlds_path).join(game.to_string()))?;
+    let stored: Vec<String> = redis::cmd("SMEMBERS")
+        .arg(format!("{}{}", CATALOG_PREFIX, game))
+        .query(ctx.get_connection())?;
+    maps.extend(stored);
+    Ok(maps)
+}
+
+pub fn add_to_catalog(
+    game: &GameType,
+    maps: &[String],
+    ctx: &mut ContextManager,
+) -> Result<(), MapError> {
+    redis::cmd("SADD")
+        .arg(format!("{}{}", CATALOG_PREFIX, game))
+        .arg(maps)
+        .query::<()>(ctx.get_connection())?;
+    Ok(())
+}
+
+pub fn get_games(group: &ServerGroup) -> Vec<GameType> {
+    //! Game types a group runs, from its `games` field (unknown names are skipped), or the
+    //! game whose default prefix the group has if that field is empty.
+    let games: Vec<GameType> = group
+        .games
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|game| GameType::from_str(game.trim()).ok())
+        .collect();
+    if !games.is_empty() {
+        return games;
+    }
+    GAME_TO_SERVER_PREFIX
+        .iter()
+        .filter(|(_, prefix)| **prefix == group.prefix)
+        .map(|(game, _)| *game)
+        .collect()
+}
+
+pub fn get_disabled(group: &str, ctx: &mut ContextManager) -> Result<BTreeSet<String>, MapError> {
+    let raw: Option<String> = redis::cmd("HGET")
+        .arg(DISABLED_KEY)
+        .arg(group)
+        .query(ctx.get_connection())?;
+    Ok(raw
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default())
+}
+
+pub fn get_pool(group: &ServerGroup, ctx: &mut ContextManager) -> Result<MapPool, MapError> {
+    //! Catalog maps of each of the group's games, minus the ones disabled for it.
+    let disabled = get_disabled(&group.prefix, ctx)?;
+    let mut pool = MapPool::new();
+    for game in get_games(group) {
+        let maps = get_catalog(&game, ctx)?
+            .into_iter()
+            .filter(|map| !disabled.contains(map))
+            .collect();
+        pool.insert(game.to_string(), maps);
+    }
+    Ok(pool)
+}
+
+pub fn write_pool(group: &ServerGroup, ctx: &mut ContextManager) -> Result<MapPool, MapError> {
+    //! Replaces the group's map pool hash with its current pool.
+    let pool = get_pool(group, ctx)?;
+    let key = ctx.get_keys().map_pool_key(&group.prefix);
+    redis::cmd("DEL")
+        .arg(&key)
+        .query::<()>(ctx.get_connection())?;
+    for (game, maps) in pool.iter() {
+        redis::cmd("HSET")
+            .arg(&key)
+            .arg(game)
+            .arg(maps.iter().cloned().collect::<Vec<_>>().join(","))
+            .query::<()>(ctx.get_connection())?;
+    }
+    Ok(pool)
+}
+
+pub fn clear(group: &str, ctx: &mut ContextManager) -> Result<(), MapError> {
+    //! Drops a deleted group's map pool and the maps disabled for it.
+    let key = ctx.get_keys().map_pool_key(group);
+    redis::cmd("DEL")
+        .arg(key)
+        .query::<()>(ctx.get_connection())?;
+    redis::cmd("HDEL")
+        .arg(DISABLED_KEY)
+        .arg(group)
+        .query::<()>(ctx.get_connection())?;
+    Ok(())
+}
+
+pub fn set_enabled(
+    group: &ServerGroup,
+    map: &str,
+    enabled: bool,
+    ctx: &mut ContextManager,
+) -> Result<MapPool, MapError> {
+    //! Turns a map on or off for a group and rewrites its pool.
+    //! The map has to be in the catalog of one of the group's games.
+    let mut known = false;
+    for game in get_games(group) {
+        known |= get_catalog(&game, ctx)?.contains(map);
+    }
+    if !known {
+        return Err(MapError::InvalidMap(format!(
+            "{} is not a map of any game servergroups.{} runs",
+            map, group.prefix
+        )));
+    }
+    let mut disabled = get_disabled(&group.prefix, ctx)?;
+    match enabled {
+        true => disabled.remove(map),
+        false => disabled.insert(map.to_string()),
+    };
+    let mut cmd = match disabled.is_empty() {
+        true => redis::cmd("HDEL"),
+        false => redis::cmd("HSET"),
+    };
+    cmd.arg(DISABLED_KEY).arg(&group.prefix);
+    if !disabled.is_empty() {
+        cmd.arg(
+            serde_json::to_string(&disabled)
+                .map_err(|err| MapError::RedisError(err.to_string()))?,
+        );
+    }
+    cmd.query::<()>(ctx.get_connection())?;
+    write_pool(group, ctx)
+}
@@ -0,0 +1,387 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+};
+
+use chrono::{Local, Utc};
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+use crate::{
+    context_manager::ContextManager,
+    events::{Event, EventKind},
+    server::minecraft::MinecraftServer,
+    store::metrics::RedisMetrics,
+    strategy,
+};
+
+use super::{forecast, region::RegionWorkers, services, summary::NetworkSummary};
+
+/// Hash of rule name -> firing alert, so a restarted manager doesn't fire them again.
+const FIRING_KEY: &str = "servermonitor.alerts.firing";
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, Eq, PartialEq, Display)]
+pub enum Severity {
+    Info,
+    #[default]
+    Warning,
+    Critical,
+}
+
+/// What a rule watches. Group-level metrics are summed over the network when a rule
+/// names no group; `ForecastPercent` takes a region as its group, or the highest one, and
+/// `DiskFreeMb` a node, or the lowest one.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Eq, PartialEq, Display)]
+pub enum AlertMetric {
+    OnlinePlayers,
+    Servers,
+    MissingServers,  // desired instances (per the scaling strategy) minus running ones
+    RedisErrors,     // failed redis commands since the previous evaluation
+    ServicesDown,    // configured [[services]] whose check fails
+    ForecastPercent, // a region's predicted peak demand over the forecast horizon, % of capacity
+    DiskFreeMb,      // free disk a node's agent reports, see [telemetry]
+}
+
+/// Fires once `metric` has been above `above` (or below `below`) for `for_secs`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AlertRule {
+    pub name: String,
+    pub metric: AlertMetric,
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub above: Option<f64>,
+    #[serde(default)]
+    pub below: Option<f64>,
+    #[serde(default)]
+    pub for_secs: u64,
+    #[serde(default)]
+    pub severity: Severity,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct AlertsInfo {
+    #[serde(default)]
+    pub repeat_secs: u64, // re-notify a still-firing alert this often (0: only once)
+    #[serde(default)]
+    pub rules: Vec<AlertRule>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GroupSample {
+    pub online: f64,
+    pub servers: f64,
+    pub missing: f64,
+}
+
+/// Everything rules are evaluated against, gathered once per evaluation.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HealthSample {
+    pub groups: BTreeMap<String, GroupSample>,
+    pub redis_errors: f64,
+    pub services_down: f64,
+    pub forecast: BTreeMap<String, f64>, // region -> `ForecastPercent`
+    pub disk_free: BTreeMap<String, f64>, // node -> `DiskFreeMb`, for nodes with fresh telemetry
+}
+
+/// An alert whose condition currently holds long enough.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct FiringAlert {
+    pub rule: String,
+    pub severity: Severity,
+    pub value: f64,
+    pub since: i64,         // seconds since epoch
+    pub last_notified: i64, // seconds since epoch
+}
+
+/// One rule's result in an evaluation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuleCheck {
+    pub rule: String,
+    pub value: f64,
+    pub breached: bool,
+}
+
+/// Tracks how long each rule has been breached and which alerts are firing, so an alert
+/// fires once, is repeated at most every `repeat_secs` and is cleared when it recovers.
+#[derive(Clone, Debug, Default)]
+pub struct AlertEngine {
+    pending: HashMap<String, i64>, // rule -> breached since
+    firing: HashMap<String, FiringAlert>,
+    redis_errors: u64, // total at the previous evaluation
+    loaded: bool,
+}
+
+impl Display for FiringAlert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let since = chrono::DateTime::from_timestamp(self.since, 0)
+            .map(|time| time.with_timezone(&Local).to_rfc3339())
+            .unwrap_or(self.since.to_string());
+        write!(
+            f,
+            "[{}] {} (value {}) since {}",
+            self.severity, self.rule, self.value, since
+        )
+    }
+}
+
+impl Display for RuleCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} ({})",
+            self.rule,
+            self.value,
+            if self.breached { "breached" } else { "ok" }
+        )
+    }
+}
+
+impl AlertRule {
+    pub fn get_value(&self, sample: &HealthSample) -> f64 {
+        let group_value = |group: &GroupSample| match self.metric {
+            AlertMetric::OnlinePlayers => group.online,
+            AlertMetric::Servers => group.servers,
+            AlertMetric::MissingServers => group.missing,
+            AlertMetric::RedisErrors
+            | AlertMetric::ServicesDown
+            | AlertMetric::ForecastPercent
+            | AlertMetric::DiskFreeMb => 0.0,
+        };
+        match (self.metric, self.group.as_ref()) {
+            (AlertMetric::RedisErrors, _) => sample.redis_errors,
+            (AlertMetric::ServicesDown, _) => sample.services_down,
+            (AlertMetric::ForecastPercent, Some(region)) => {
+                sample.forecast.get(region).copied().unwrap_or(0.0)
+            }
+            (AlertMetric::ForecastPercent, None) => {
+                sample.forecast.values().copied().fold(0.0, f64::max)
+            }
+            // a node without telemetry is unknown, which never breaches
+            (AlertMetric::DiskFreeMb, Some(node)) => {
+                sample.disk_free.get(node).copied().unwrap_or(f64::NAN)
+            }
+            (AlertMetric::DiskFreeMb, None) => sample
+                .disk_free
+                .values()
+                .copied()
+                .reduce(f64::min)
+                .unwrap_or(f64::NAN),
+            (_, Some(group)) => sample.groups.get(group).map_or(0.0, group_value),
+            (_, None) => sample.groups.values().map(group_value).sum(),
+        }
+    }
+
+    pub fn is_breached(&self, value: f64) -> bool {
+        self.above.is_some_and(|above| value > above)
+            || self.below.is_some_and(|below| value < below)
+    }
+}
+
+impl HealthSample {
+    pub fn collect(
+        statuses: &[MinecraftServer],
+        redis_errors: f64,
+        ctx: &mut ContextManager,
+    ) -> Self {
+        //! Player and server counts from `statuses`, missing instances from the scaling strategy,
+        //! free disk from node telemetry.
+        let summary = NetworkSummary::from_statuses(statuses);
+        let mut groups: BTreeMap<String, GroupSample> = summary
+            .groups
+            .into_iter()
+            .map(|(name, group)| {
+                let sample = GroupSample {
+                    online: group.online as f64,
+                    servers: group.servers as f64,
+                    missing: 0.0,
+                };
+                (name, sample)
+            })
+            .collect();
+        for decision in strategy::plan_scaling(ctx) {
+            groups.entry(decision.group).or_default().missing =
+                decision.desired.saturating_sub(decision.running) as f64;
+        }
+        let now = Utc::now();
+        let forecast = RegionWorkers::get_regions(ctx)
+            .iter()
+            .filter_map(|region| {
+                let peak = forecast::get_peak(region, now, ctx).ok()??;
+                Some((region.to_string(), peak.get_percent() as f64))
+            })
+            .collect();
+        let mut nodes = ctx.get_dedicated_servers().clone();
+        nodes.merge_telemetry(ctx);
+        let disk_free = nodes
+            .servers
+            .iter()
+            .filter_map(|ds| Some((ds.name.clone(), ds.disk?.free_mb as f64)))
+            .collect();
+        Self {
+            groups,
+            redis_errors,
+            services_down: services::get_down(ctx).map_or(0, |down| down.len()) as f64,
+            forecast,
+            disk_free,
+        }
+    }
+}
+
+fn get_redis_errors() -> u64 {
+    RedisMetrics::get_current()
+        .commands
+        .values()
+        .map(|stats| stats.errors)
+        .sum()
+}
+
+impl AlertEngine {
+    pub fn get_firing(ctx: &mut ContextManager) -> Result<Vec<FiringAlert>, redis::RedisError> {
+        //! Alerts firing across all managers. Unparsable entries are skipped.
+        let raw: Vec<String> = redis::cmd("HVALS")
+            .arg(FIRING_KEY)
+            .query(ctx.get_connection())?;
+        let mut firing: Vec<FiringAlert> = raw
+            .iter()
+            .filter_map(|raw| serde_json::from_str(raw).ok())
+            .collect();
+        firing.sort_by_key(|alert| alert.since);
+        Ok(firing)
+    }
+
+    pub fn sample(
+        &mut self,
+        statuses: &[MinecraftServer],
+        ctx: &mut ContextManager,
+    ) -> HealthSample {
+        //! Collects a sample; redis errors are counted from the previous call on.
+        let total = get_redis_errors();
+        let errors = total.saturating_sub(self.redis_errors);
+        self.redis_errors = total;
+        HealthSample::collect(statuses, errors as f64, ctx)
+    }
+
+    pub fn check(rules: &[AlertRule], sample: &HealthSample) -> Vec<RuleCheck> {
+        //! Evaluates every rule once, ignoring durations, without notifying anyone.
+        rules
+            .iter()
+            .map(|rule| {
+                let value = rule.get_value(sample);
+                RuleCheck {
+                    rule: rule.name.clone(),
+                    value,
+                    breached: rule.is_breached(value),
+                }
+            })
+            .collect()
+    }
+
+    pub fn evaluate(&mut self, sample: &HealthSample, ctx: &mut ContextManager) {
+        //! Fires, repeats and clears alerts for the configured rules. Notifications are
+        //! AlertFired/AlertCleared events.
+        if !self.loaded {
+            self.firing = Self::get_firing(ctx)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|alert| (alert.rule.clone(), alert))
+                .collect();
+            self.loaded = true;
+        }
+        let info = ctx.get_config().alerts.clone();
+        let now = Local::now().timestamp();
+        for rule in info.rules.iter() {
+            let value = rule.get_value(sample);
+            if !rule.is_breached(value) {
+                self.pending.remove(&rule.name);
+                if let Some(alert) = self.firing.remove(&rule.name) {
+                    Self::clear(&alert, value, ctx);
+                }
+                continue;
+            }
+            let since = *self.pending.entry(rule.name.clone()).or_insert(now);
+            if now - since < rule.for_secs as i64 {
+                continue;
+            }
+            let alert = self.firing.entry(rule.name.clone()).or_insert(FiringAlert {
+                rule: rule.name.clone(),
+                severity: rule.severity,
+                value,
+                since,
+                last_notified: 0,
+            });
+            alert.value = value;
+            let repeat =
+                info.repeat_secs > 0 && now - alert.last_notified >= info.repeat_secs as i64;
+            if alert.last_notified == 0 || repeat {
+                alert.last_notified = now;
+                let alert = alert.clone();
+                Self::fire(rule, &alert, ctx);
+            }
+        }
+        // rules removed from the config can't recover on their own
+        let removed: Vec<String> = self
+            .firing
+            .keys()
+            .filter(|name| !info.rules.iter().any(|rule| &rule.name == *name))
+            .cloned()
+            .collect();
+        for name in removed {
+            if let Some(alert) = self.firing.remove(&name) {
+                Self::clear(&alert, alert.value, ctx);
+            }
+        }
+    }
+
+    fn fire(rule: &AlertRule, alert: &FiringAlert, ctx: &mut ContextManager) {
+        let threshold = match (rule.above, rule.below) {
+            (Some(above), _) if alert.value > above => format!("above {}", above),
+            (_, Some(below)) => format!("below {}", below),
+            _ => "breached".into(),
+        };
+        Event::new(
+            EventKind::AlertFired,
+            &rule.name,
+            format!(
+                "[{}] {}{} is {} ({})",
+                rule.severity,
+                rule.metric,
+                rule.group
+                    .as_ref()
+                    .map_or(String::new(), |group| format!(" of {}", group)),
+                alert.value,
+                threshold
+            ),
+        )
+        .emit(ctx);
+        if let Err(err) = serde_json::to_string(alert)
+            .map_err(|err| err.to_string())
+            .and_then(|raw| {
+                redis::cmd("HSET")
+                    .arg(FIRING_KEY)
+                    .arg(&alert.rule)
+                    .arg(raw)
+                    .query::<()>(ctx.get_connection())
+                    .map_err(|err| err.to_string())
+            })
+        {
+            println!("[alerts] {} could not be stored: {}", alert.rule, err);
+        }
+    }
+
+    fn clear(alert: &FiringAlert, value: f64, ctx: &mut ContextManager) {
+        Event::new(
+            EventKind::AlertCleared,
+            &alert.rule,
+            format!("[{}] recovered (value {})", alert.severity, value),
+        )
+        .emit(ctx);
+        if let Err(err) = redis::cmd("HDEL")
+            .arg(FIRING_KEY)
+            .arg(&alert.rule)
+            .query::<()>(ctx.get_connection())
+        {
+            println!("[alerts] {} could not be cleared: {:?}", alert.rule, err);
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::{thread, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    context_manager::ContextManager,
+    events::{Event, EventKind},
+    random,
+    region::Region,
+    store::connection::Connection,
+};
+
+/// Faults the monitor injects into its own network to check it recovers from them: a
+/// server process dying, redis dropping a worker's connection and slow launches. Only
+/// for test networks, off unless `enabled`. Every fault is recorded as a
+/// `ChaosInjected` event before it happens.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct ChaosInfo {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub kill_percent: u64, // chance each reconcile of killing one of the region's instances
+    #[serde(default)]
+    pub drop_connection_percent: u64, // chance each reconcile of redis closing the worker's connection
+    #[serde(default)]
+    pub delay_launch_percent: u64, // chance a launch is held back
+    #[serde(default = "default_max_delay")]
+    pub max_delay_secs: u64, // held back up to this long
+    #[serde(default)]
+    pub groups: Vec<String>, // only these groups' instances are killed, all if empty
+}
+
+fn default_max_delay() -> u64 {
+    30
+}
+
+impl Default for ChaosInfo {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            kill_percent: 0,
+            drop_connection_percent: 0,
+            delay_launch_percent: 0,
+            max_delay_secs: default_max_delay(),
+            groups: Vec::new(),
+        }
+    }
+}
+
+fn roll(percent: u64, ctx: &ContextManager) -> bool {
+    //! True `percent`% of the time.
+    ctx.get_random().gen_below(100) < percent
+}
+
+fn is_armed(ctx: &mut ContextManager) -> bool {
+    //! Chaos is enabled and the context is on a live network, not a snapshot.
+    ctx.get_config().chaos.enabled && !matches!(ctx.get_connection(), Connection::Snapshot(_))
+}
+
+pub fn inject(region: Option<&Region>, ctx: &mut ContextManager) -> Vec<String> {
+    //! Rolls for the reconcile faults, returning the ones injected: killing a random
+    //! instance of `region` (every region if None) and dropping the connection. The
+    //! instance is only killed on its node, its status and bookkeeping are left for the
+    //! monitor to notice. The connection is dropped last, so the fault shows up on the
+    //! worker's next reconcile.
+    if !is_armed(ctx) {
+        return Vec::new();
+    }
+    let info = ctx.get_config().chaos.clone();
+    let mut injected = Vec::new();
+    if roll(info.kill_percent, ctx) {
+        if let Some(killed) = kill_instance(region, &info, ctx) {
+            injected.push(killed);
+        }
+    }
+    if roll(info.drop_connection_percent, ctx) {
+        if let Some(dropped) = drop_connection(region, ctx) {
+            injected.push(dropped);
+        }
+    }
+    injected
+}
+
+fn kill_instance(
+    region: Option<&Region>,
+    info: &ChaosInfo,
+    ctx: &mut ContextManager,
+) -> Option<String> {
+    let candidates: Vec<(String, String)> = ctx
+        .get_dedicated_servers()
+        .servers
+        .iter()
+        .filter(|node| region.is_none_or(|region| &node.region == region))
+        .flat_map(|node| {
+            node.server_instances
+                .values()
+                .flatten()
+                .filter(|instance| {
+                    info.groups.is_empty() || info.groups.contains(instance.get_group())
+                })
+                .map(|instance| (node.name.clone(), instance.get_name().clone()))
+        })
+        .collect();
+    let (node, instance) = random::choose(ctx.get_random(), candidates)?;
+    Event::new(
+        EventKind::ChaosInjected,
+        &instance,
+        format!("killing its process on {}", node),
+    )
+    .emit(ctx);
+    let result = ctx.with_dedicated_servers(|servers, ctx| {
+        let node = servers.servers.iter().find(|ds| ds.name == node)?;
+        Some(node.run_kill_script(&instance, ctx))
+    });
+    match result {
+        Some(Ok(())) => Some(format!("killed {}", instance)),
+        Some(Err(err)) => {
+            println!("[chaos] {} could not be killed: {}", instance, err);
+            None
+        }
+        None => None,
+    }
+}
+
+fn drop_connection(region: Option<&Region>, ctx: &mut ContextManager) -> Option<String> {
+    //! Has redis close the context's connection (`CLIENT KILL`), as a restart or network
+    //! blip would. Errors are expected here, the connection is gone either way.
+    let subject = match region {
+        Some(region) => format!("servermonitor.regions.{}", region),
+        None => "servermonitor".into(),
+    };
+    Event::new(
+        EventKind::ChaosInjected,
+        &subject,
+        "dropping its redis connection".into(),
+    )
+    .emit(ctx);
+    let id: redis::RedisResult<i64> = redis::cmd("CLIENT").arg("ID").query(ctx.get_connection());
+    match id {
+        Ok(id) => {
+            let _: redis::RedisResult<()> = redis::cmd("CLIENT")
+                .arg("KILL")
+                .arg("ID")
+                .arg(id)
+                .arg("SKIPME")
+                .arg("no")
+                .query(ctx.get_connection());
+            Some(format!("dropped the connection of {}", subject))
+        }
+        Err(err) => {
+            println!("[chaos] connection could not be dropped: {:?}", err);
+            None
+        }
+    }
+}
+
+pub fn delay_launch(server_name: &str, ctx: &mut ContextManager) {
+    //! Holds a launch back for up to `max_delay_secs`, `delay_launch_percent`% of the time.
+    if !is_armed(ctx) {
+        return;
+    }
+    let info = ctx.get_config().chaos.clone();
+    if !roll(info.delay_launch_percent, ctx) {
+        return;
+    }
+    let secs = ctx.get_random().gen_below(info.max_delay_secs) + 1;
+    Event::new(
+        EventKind::ChaosInjected,
+        server_name,
+        format!("launch delayed by {}s", secs),
+    )
+    .emit(ctx);
+    thread::sleep(Duration::from_secs(secs));
+}
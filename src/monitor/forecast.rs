@@ -0,0 +1,248 @@
+use std::{collections::HashMap, fmt::Display};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+use crate::{context_manager::ContextManager, region::Region, strategy::ScalingDecision};
+
+/// Hash per region of hour (seconds since epoch, at the start of the hour) -> `DemandSample`.
+const DEMAND_PREFIX: &str = "servermonitor.demand.";
+const HOUR: i64 = 3600;
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, Eq, PartialEq, Display)]
+pub enum ForecastMethod {
+    #[default]
+    Peak, // highest demand seen at the same hour on the past days
+    MovingAverage, // mean demand at the same hour on the past days
+}
+
+/// How the monitor predicts each region's capacity needs from the demand it recorded.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct ForecastInfo {
+    #[serde(default)]
+    pub method: ForecastMethod,
+    #[serde(default = "default_days")]
+    pub days: u32, // history kept and looked at
+    #[serde(default = "default_horizon_hours")]
+    pub horizon_hours: u32, // how far ahead to warn
+    #[serde(default = "default_warn_percent")]
+    pub warn_percent: u32, // of node capacity
+}
+
+/// Highest resources the region's groups wanted during one hour (desired instances
+/// times each group's ram and cpu).
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DemandSample {
+    pub ram: u64, // in MB
+    pub cpu: u64,
+}
+
+/// Predicted demand of a region for one upcoming hour next to its nodes' capacity.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Forecast {
+    pub region: Region,
+    pub hour: i64, // seconds since epoch
+    pub demand: DemandSample,
+    pub capacity: DemandSample,
+    pub days: usize, // past days with a sample for this hour
+}
+
+fn default_days() -> u32 {
+    7
+}
+
+fn default_horizon_hours() -> u32 {
+    12
+}
+
+fn default_warn_percent() -> u32 {
+    90
+}
+
+impl Default for ForecastInfo {
+    fn default() -> Self {
+        Self {
+            method: ForecastMethod::default(),
+            days: default_days(),
+            horizon_hours: default_horizon_hours(),
+            warn_percent: default_warn_percent(),
+        }
+    }
+}
+
+impl Display for Forecast {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hour = DateTime::from_timestamp(self.hour, 0)
+            .map(|hour| hour.with_timezone(&chrono::Local).to_rfc3339())
+            .unwrap_or(self.hour.to_string());
+        write!(
+            f,
+            "{} at {}: {}/{}MB ram, {}/{} cpu ({}% of capacity, from {} days)",
+            self.region,
+            hour,
+            self.demand.ram,
+            self.capacity.ram,
+            self.demand.cpu,
+            self.capacity.cpu,
+            self.get_percent(),
+            self.days
+        )
+    }
+}
+
+impl DemandSample {
+    pub fn of(decisions: &[ScalingDecision]) -> Self {
+        decisions
+            .iter()
+            .fold(Self::default(), |sample, decision| Self {
+                ram: sample.ram + decision.desired as u64 * decision.ram as u64,
+                cpu: sample.cpu + decision.desired as u64 * decision.cpu as u64,
+            })
+    }
+
+    pub fn capacity_of(region: &Region, ctx: &mut ContextManager) -> Self {
+        //! Total ram and cpu of the region's nodes.
+        ctx.get_dedicated_servers()
+            .servers
+            .iter()
+            .filter(|server| &server.region == region)
+            .fold(Self::default(), |sample, server| Self {
+                ram: sample.ram + server.max_ram.max(0) as u64,
+                cpu: sample.cpu + server.max_cpu.max(0) as u64,
+            })
+    }
+}
+
+impl Forecast {
+    pub fn get_percent(&self) -> u64 {
+        //! Predicted demand in percent of capacity, by whichever of ram and cpu is tighter.
+        let percent = |demand: u64, capacity: u64| match capacity {
+            0 if demand > 0 => u64::MAX,
+            0 => 0,
+            capacity => demand * 100 / capacity,
+        };
+        percent(self.demand.ram, self.capacity.ram).max(percent(self.demand.cpu, self.capacity.cpu))
+    }
+}
+
+fn get_key(region: &Region) -> String {
+    format!("{}{}", DEMAND_PREFIX, region)
+}
+
+fn get_hour(at: DateTime<Utc>) -> i64 {
+    at.timestamp() - at.timestamp().rem_euclid(HOUR)
+}
+
+fn get_history(
+    region: &Region,
+    ctx: &mut ContextManager,
+) -> Result<HashMap<i64, DemandSample>, redis::RedisError> {
+    let raw: HashMap<String, String> = redis::cmd("HGETALL")
+        .arg(get_key(region))
+        .query(ctx.get_connection())?;
+    Ok(raw
+        .iter()
+        .filter_map(|(hour, sample)| Some((hour.parse().ok()?, serde_json::from_str(sample).ok()?)))
+        .collect())
+}
+
+pub fn record(
+    region: &Region,
+    sample: DemandSample,
+    now: DateTime<Utc>,
+    ctx: &mut ContextManager,
+) -> Result<(), redis::RedisError> {
+    //! Keeps the hour's highest demand and drops hours older than the configured days.
+    let hour = get_hour(now);
+    let history = get_history(region, ctx)?;
+    let current = history.get(&hour).copied().unwrap_or_default();
+    let peak = DemandSample {
+        ram: current.ram.max(sample.ram),
+        cpu: current.cpu.max(sample.cpu),
+    };
+    let raw = serde_json::to_string(&peak).map_err(|err| {
+        redis::RedisError::from((
+            redis::ErrorKind::ClientError,
+            "Demand serialization error",
+            err.to_string(),
+        ))
+    })?;
+    let oldest = hour - ctx.get_config().forecast.days as i64 * 24 * HOUR;
+    let expired: Vec<String> = history
+        .keys()
+        .filter(|&&past| past < oldest)
+        .map(|past| past.to_string())
+        .collect();
+    if !expired.is_empty() {
+        redis::cmd("HDEL")
+            .arg(get_key(region))
+            .arg(expired)
+            .query::<()>(ctx.get_connection())?;
+    }
+    if peak == current && history.contains_key(&hour) {
+        return Ok(());
+    }
+    redis::cmd("HSET")
+        .arg(get_key(region))
+        .arg(hour)
+        .arg(raw)
+        .query(ctx.get_connection())
+}
+
+pub fn predict(
+    region: &Region,
+    now: DateTime<Utc>,
+    ctx: &mut ContextManager,
+) -> Result<Vec<Forecast>, redis::RedisError> {
+    //! Demand for each hour of the horizon, from the same hour on the past days.
+    //! Hours without any history are left out.
+    let info = ctx.get_config().forecast.clone();
+    let history = get_history(region, ctx)?;
+    let capacity = DemandSample::capacity_of(region, ctx);
+    let first = get_hour(now);
+    let forecasts = (0..info.horizon_hours.max(1) as i64)
+        .map(|ahead| first + ahead * HOUR)
+        .filter_map(|hour| {
+            let past: Vec<DemandSample> = (1..=info.days as i64)
+                .filter_map(|days_ago| history.get(&(hour - days_ago * 24 * HOUR)).copied())
+                .collect();
+            if past.is_empty() {
+                return None;
+            }
+            let demand = match info.method {
+                ForecastMethod::Peak => DemandSample {
+                    ram: past.iter().map(|sample| sample.ram).max().unwrap_or(0),
+                    cpu: past.iter().map(|sample| sample.cpu).max().unwrap_or(0),
+                },
+                ForecastMethod::MovingAverage => DemandSample {
+                    ram: past.iter().map(|sample| sample.ram).sum::<u64>() / past.len() as u64,
+                    cpu: past.iter().map(|sample| sample.cpu).sum::<u64>() / past.len() as u64,
+                },
+            };
+            Some(Forecast {
+                region: region.clone(),
+                hour,
+                demand,
+                capacity,
+                days: past.len(),
+            })
+        })
+        .collect();
+    Ok(forecasts)
+}
+
+pub fn get_peak(
+    region: &Region,
+    now: DateTime<Utc>,
+    ctx: &mut ContextManager,
+) -> Result<Option<Forecast>, redis::RedisError> {
+    //! The horizon's tightest hour.
+    Ok(get_tightest(&predict(region, now, ctx)?).cloned())
+}
+
+pub fn get_tightest(forecasts: &[Forecast]) -> Option<&Forecast> {
+    forecasts
+        .iter()
+        .max_by_key(|forecast| (forecast.get_percent(), -forecast.hour))
+}
@@ -0,0 +1,126 @@
+use std::{collections::HashMap, fmt::Display, time::Duration};
+
+use chrono::Local;
+
+use crate::{
+    context_manager::ContextManager,
+    store::{entity::RedisEntity, keys::KeyBuilder},
+};
+
+const HEARTBEAT_PREFIX: &str = "servermonitor.heartbeat.";
+
+/// Proof of life a running manager refreshes every monitor tick.
+/// The key expires on its own if the manager dies without clearing it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Heartbeat {
+    pub instance_id: String,
+    pub version: String,
+    pub leader: bool,
+    pub last_reconcile: Option<i64>, // seconds since epoch
+    pub timestamp: i64,
+    pub connection: String, // what the manager's redis connection talks to, see `Connection::get_state`
+}
+
+impl Display for Heartbeat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let format_time = |timestamp: i64| {
+            chrono::DateTime::from_timestamp(timestamp, 0)
+                .map(|time| time.with_timezone(&Local).to_rfc3339())
+                .unwrap_or(timestamp.to_string())
+        };
+        write!(
+            f,
+            "{} v{}{} (seen {}, last reconcile {}){}",
+            self.instance_id,
+            self.version,
+            if self.leader { " [leader]" } else { "" },
+            format_time(self.timestamp),
+            self.last_reconcile.map_or("never".into(), format_time),
+            match self.connection.is_empty() {
+                true => String::new(),
+                false => format!(" via {}", self.connection),
+            }
+        )
+    }
+}
+
+impl RedisEntity for Heartbeat {
+    type Error = String;
+
+    fn get_id(&self) -> String {
+        self.instance_id.clone()
+    }
+
+    fn from_map(map: HashMap<String, String>) -> Result<Self, Self::Error> {
+        let get = |key: &str| {
+            map.get(key)
+                .cloned()
+                .ok_or(format!("heartbeat is missing {:?}", key))
+        };
+        let parse_time = |key: &str| {
+            get(key)?
+                .parse::<i64>()
+                .map_err(|err| format!("heartbeat {:?}: {:?}", key, err))
+        };
+        Ok(Self {
+            instance_id: get("instanceId")?,
+            version: get("version")?,
+            leader: get("leader")? == "true",
+            last_reconcile: parse_time("lastReconcile").ok(),
+            timestamp: parse_time("timestamp")?,
+            connection: map.get("connection").cloned().unwrap_or_default(),
+        })
+    }
+
+    fn to_map(&self) -> HashMap<String, String> {
+        HashMap::from([
+            ("instanceId".into(), self.instance_id.clone()),
+            ("version".into(), self.version.clone()),
+            ("leader".into(), self.leader.to_string()),
+            (
+                "lastReconcile".into(),
+                self.last_reconcile
+                    .map(|time| time.to_string())
+                    .unwrap_or_default(),
+            ),
+            ("timestamp".into(), self.timestamp.to_string()),
+            ("connection".into(), self.connection.clone()),
+        ])
+    }
+
+    fn get_key(id: &str, _keys: &KeyBuilder) -> String {
+        format!("{}{}", HEARTBEAT_PREFIX, id)
+    }
+
+    fn get_pattern(_keys: &KeyBuilder) -> String {
+        format!("{}*", HEARTBEAT_PREFIX)
+    }
+}
+
+impl Heartbeat {
+    pub fn new(
+        instance_id: &str,
+        leader: bool,
+        last_reconcile: Option<i64>,
+        connection: String,
+    ) -> Self {
+        Self {
+            instance_id: instance_id.into(),
+            version: env!("CARGO_PKG_VERSION").into(),
+            leader,
+            last_reconcile,
+            timestamp: Local::now().timestamp(),
+            connection,
+        }
+    }
+
+    pub fn beat(&self, ttl: Duration, ctx: &mut ContextManager) -> Result<(), String> {
+        //! Writes the heartbeat and (re)arms its expiry.
+        self.save(ctx)?;
+        redis::cmd("EXPIRE")
+            .arg(Self::get_key(&self.instance_id, ctx.get_keys()))
+            .arg(ttl.as_secs().max(1))
+            .query::<()>(ctx.get_connection())
+            .map_err(|err| format!("heartbeat expiry could not be set: {:?}", err))
+    }
+}
@@ -0,0 +1,24 @@
+use crate::{server::dedicated::outcome::InstanceOutcome, strategy::ScalingDecision};
+
+use super::region::RegionHealth;
+
+/// Callbacks for applications embedding the manager, e.g. to send their own notifications
+/// or keep accounting of instance hours. Every method defaults to doing nothing, so an
+/// implementation only overrides what it needs. Register one with `ContextManager::add_hooks`.
+/// Hooks run on whichever thread (region worker or CLI) did the work, so they should return
+/// quickly.
+pub trait MonitorHooks: Send + Sync {
+    /// An instance was started on a node and passed its smoke test, if one is configured.
+    fn on_launch(&self, _outcome: &InstanceOutcome) {}
+
+    /// An instance was killed and its resources returned to its node.
+    fn on_kill(&self, _outcome: &InstanceOutcome) {}
+
+    /// A reconcile decided how many instances a group should run; called for every group
+    /// of the reconciled region, including those already at their desired count.
+    fn on_scale_decision(&self, _decision: &ScalingDecision) {}
+
+    /// A region worker's health changed (missing instances, errors, restarts or forecast);
+    /// `previous` is None on the worker's first report.
+    fn on_health_change(&self, _previous: Option<&RegionHealth>, _health: &RegionHealth) {}
+}
@@ -0,0 +1,423 @@
+pub mod alerts;
+pub mod chaos;
+pub mod forecast;
+pub mod heartbeat;
+pub mod hooks;
+pub mod recorder;
+pub mod region;
+pub mod schedule;
+pub mod services;
+pub mod shutdown;
+pub mod stream;
+pub mod summary;
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    time::Instant,
+};
+
+use chrono::{Local, Utc};
+
+use crate::{
+    config::models::{MonitorTask, MonitorTiming},
+    context_manager::ContextManager,
+    doctor,
+    events::{Event, EventKind},
+    handshake,
+    journal::JournalEntry,
+    region::Region,
+    server::{
+        counters::{self, GroupCounts},
+        event_server::EventServer,
+        minecraft::MinecraftServer,
+        rightsizing, rotation, schema,
+        server_group::ServerGroup,
+    },
+    store::{
+        entity::RedisEntity,
+        metrics::{self, RedisMetrics},
+    },
+    strategy,
+};
+
+use alerts::AlertEngine;
+use heartbeat::Heartbeat;
+use region::{RegionWorker, RegionWorkers};
+use schedule::Schedule;
+use stream::StreamServer;
+use summary::NetworkSummary;
+
+/// What the monitor did before it stopped.
+#[derive(Clone, Debug, Default)]
+pub struct MonitorSummary {
+    pub ticks: usize,
+    pub tasks_run: HashMap<MonitorTask, usize>,
+    pub errors: Vec<String>,
+    pub stopped_by: Option<String>,
+    pub refused: Option<String>, // why the monitor didn't start, see `Monitor::ignore_topology`
+}
+
+/// Tasks the supervisor runs itself; region workers run the rest.
+const SUPERVISOR_TASKS: [MonitorTask; 2] = [MonitorTask::Statuses, MonitorTask::Reconcile];
+
+impl MonitorSummary {
+    pub fn merge(&mut self, worker: &str, other: MonitorSummary) {
+        //! Adds a region worker's tasks and errors.
+        for (task, count) in other.tasks_run {
+            *self.tasks_run.entry(task).or_default() += count;
+        }
+        self.errors.extend(
+            other
+                .errors
+                .into_iter()
+                .map(|err| format!("{}: {}", worker, err)),
+        );
+    }
+}
+
+impl Display for MonitorSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(reason) = self.refused.as_ref() {
+            return write!(f, "Monitor refused to start: {}", reason);
+        }
+        let mut tasks: Vec<String> = self
+            .tasks_run
+            .iter()
+            .map(|(task, count)| format!("{} x{}", task, count))
+            .collect();
+        tasks.sort();
+        write!(
+            f,
+            "Monitor stopped ({}) after {} ticks: [{}], {} error(s)",
+            self.stopped_by.as_deref().unwrap_or("finished"),
+            self.ticks,
+            tasks.join(", "),
+            self.errors.len()
+        )
+    }
+}
+
+/// Runs the manager's periodic tasks on their configured cadence until asked to stop.
+/// It supervises one worker thread per region with nodes, which reconciles and
+/// rebalances that region on its own connection, and runs network-wide work itself.
+#[derive(Clone, Debug, Default)]
+pub struct Monitor {
+    schedule: Schedule,
+    summary: MonitorSummary,
+    instance_id: String,
+    last_reconcile: Option<i64>, // seconds since epoch
+    refetched: HashSet<String>,  // groups already reported by `check_refetches`
+    misported: HashSet<String>,  // servers already reported by `check_ports`
+    alerts: AlertEngine,
+    worker_regions: Vec<Region>,
+    inline_worker: Option<RegionWorker>, // covers every region when running offline
+    stream: Option<StreamServer>,        // see [stream]
+    ignore_topology: bool,
+}
+
+impl Monitor {
+    pub fn ignore_topology(mut self, ignore: bool) -> Self {
+        //! Starts even if the startup topology check finds severe mismatches between the
+        //! configured nodes and redis.
+        self.ignore_topology = ignore;
+        self
+    }
+
+    pub fn run(mut self, max_ticks: Option<usize>, ctx: &mut ContextManager) -> MonitorSummary {
+        //! Loops until SIGINT/SIGTERM (or `max_ticks`). A shutdown request is only acted on
+        //! between tasks, so a task that has started (e.g. a launch) always runs to completion.
+        //! Running servers are adopted and operations interrupted by a previous crash are
+        //! recovered before the first tick. A heartbeat is written every tick.
+        //! Offline (on a snapshot), region work runs on this thread instead of workers.
+        //! Before anything else the configured topology is checked against redis, and the
+        //! monitor refuses to start on severe mismatches unless told to ignore them.
+        self.instance_id = ctx.get_config().monitor_info.get_instance_id();
+        if let Err(reason) = self.check_topology(ctx) {
+            self.summary.refused = Some(reason);
+            return self.summary;
+        }
+        if let Err(err) = handshake::publish(ctx) {
+            self.summary.errors.push(format!("handshake: {}", err));
+        }
+        let stream_info = ctx.get_config().stream.clone();
+        if stream_info.enabled {
+            let fields = schema::get_group_schema(ctx.get_config());
+            match StreamServer::start(&stream_info, schema::to_json(&fields)) {
+                Ok(stream) => {
+                    println!("[monitor] streaming updates on {}", stream_info.bind);
+                    self.stream = Some(stream);
+                }
+                Err(err) => self
+                    .summary
+                    .errors
+                    .push(format!("stream on {}: {}", stream_info.bind, err)),
+            }
+        }
+        let mut workers = None;
+        match ctx.get_connection().is_offline() {
+            true => {
+                println!("[monitor] {}", ctx.recover_instances());
+                self.inline_worker = Some(RegionWorker::new(&self.instance_id, None, 0));
+            }
+            false => {
+                self.worker_regions = RegionWorkers::get_regions(ctx);
+                workers = Some(RegionWorkers::spawn(
+                    &self.instance_id,
+                    &self.worker_regions,
+                    ctx,
+                ));
+            }
+        }
+        match JournalEntry::replay(ctx) {
+            Ok(outcomes) => outcomes
+                .iter()
+                .for_each(|outcome| println!("[monitor] journal {}", outcome)),
+            Err(err) => self
+                .summary
+                .errors
+                .push(format!("journal replay: {:?}", err)),
+        }
+        while max_ticks.is_none_or(|max| self.summary.ticks < max) {
+            let now = Instant::now();
+            let timing = ctx.get_config().monitor_info.get_timing().clone();
+            for task in self.schedule.get_due_tasks(&timing, now) {
+                if shutdown::is_shutdown_requested() {
+                    break;
+                }
+                if !SUPERVISOR_TASKS.contains(&task) {
+                    continue;
+                }
+                if let Err(err) = self.run_task(task, ctx) {
+                    println!("[monitor] {} failed: {}", task, err);
+                    self.summary.errors.push(format!("{}: {}", task, err));
+                }
+                self.schedule.mark_run(task, now);
+                *self.summary.tasks_run.entry(task).or_default() += 1;
+            }
+            if let Some(worker) = self.inline_worker.as_mut() {
+                worker.tick(&timing, ctx);
+            }
+            if let Some(workers) = workers.as_mut() {
+                workers.supervise(&mut self.summary, ctx);
+            }
+            self.check_refetches(ctx);
+            Self::report_reconnects(ctx);
+            self.beat(&timing, ctx);
+            self.summary.ticks += 1;
+            if !shutdown::sleep_unless_shutdown(timing.get_tick()) {
+                break;
+            }
+        }
+        if let Some(workers) = workers {
+            workers.stop(&mut self.summary);
+        }
+        if let Some(worker) = self.inline_worker.take() {
+            let summary = worker.finish(ctx);
+            self.summary.merge("network", summary);
+        }
+        self.shutdown(ctx)
+    }
+
+    fn run_task(&mut self, task: MonitorTask, ctx: &mut ContextManager) -> Result<(), String> {
+        match task {
+            MonitorTask::Statuses => {
+                let statuses = MinecraftServer::get_all(ctx);
+                for (key, err) in statuses.failed.iter() {
+                    println!("[monitor] {} could not be read: {:?}", key, err);
+                }
+                if let Some(stream) = self.stream.as_mut() {
+                    stream.publish_statuses(&statuses.ok);
+                    stream
+                        .publish_events(ctx)
+                        .map_err(|err| format!("stream: {}", err))?;
+                }
+                for change in services::enforce_reward_safety(ctx).map_err(|err| err.to_string())? {
+                    println!("[monitor] {}", change);
+                }
+                let groups = ServerGroup::get_all(ctx).ok;
+                self.check_ports(&groups, &statuses.ok, ctx);
+                Self::reconcile_counts(&groups, &statuses.ok, ctx)
+                    .map_err(|err| format!("counts: {}", err))?;
+                rightsizing::record(&groups, &statuses.ok, ctx).map_err(|err| err.to_string())?;
+                if ctx.get_config().rightsizing.auto_schedule {
+                    for suggestion in
+                        rightsizing::schedule_new(ctx).map_err(|err| err.to_string())?
+                    {
+                        println!(
+                            "[monitor] resize queued for the next restart: {}",
+                            suggestion
+                        );
+                    }
+                }
+                if !ctx.get_config().alerts.rules.is_empty() {
+                    let sample = self.alerts.sample(&statuses.ok, ctx);
+                    self.alerts.evaluate(&sample, ctx);
+                }
+                match ctx.get_config().monitor_info.get_summary_ttl() {
+                    Some(ttl) => {
+                        let mut summary = NetworkSummary::from_statuses(&statuses.ok);
+                        summary.add_queues(ctx)?;
+                        summary.add_boosters(&groups);
+                        summary.publish(ttl, ctx)
+                    }
+                    None => Ok(()),
+                }
+            }
+            MonitorTask::Reconcile => {
+                // groups in a region without nodes (and so without a worker)
+                let regions = self.worker_regions.clone();
+                let uncovered = match regions.is_empty() {
+                    true => Vec::new(),
+                    false => {
+                        strategy::plan_scaling_where(|group| !regions.contains(&group.region), ctx)
+                    }
+                };
+                for decision in uncovered {
+                    for hooks in ctx.get_hooks() {
+                        hooks.on_scale_decision(&decision);
+                    }
+                    if decision.running != decision.desired {
+                        println!(
+                            "[monitor] {} ({})",
+                            decision,
+                            ctx.get_scaling_strategy().get_name()
+                        );
+                    }
+                }
+                for change in rotation::plan_rotations(Utc::now(), ctx) {
+                    match change.restart {
+                        true => println!("[monitor] rotation needs `rotation apply`: {}", change),
+                        false => {
+                            change.apply(ctx)?;
+                            println!("[monitor] rotated {}", change);
+                        }
+                    }
+                }
+                let archived = EventServer::archive_expired(ctx).map_err(|err| err.to_string())?;
+                for event_server in archived {
+                    println!("[monitor] archived {}", event_server);
+                }
+                self.last_reconcile = Some(Local::now().timestamp());
+                Ok(())
+            }
+            MonitorTask::Rebalance => Ok(()), // run by region workers
+        }
+    }
+
+    fn check_refetches(&mut self, ctx: &mut ContextManager) {
+        //! Warns about group hashes read more often in one tick than the configured limit,
+        //! a sign of an O(n²) access pattern. Each group is only recorded as an event once.
+        let limit = ctx.get_config().monitor_info.get_refetch_warning();
+        for (key, reads) in metrics::begin_cycle() {
+            if reads <= limit {
+                continue;
+            }
+            let message = format!("read {} times in one tick (limit {})", reads, limit);
+            if self.refetched.insert(key.clone()) {
+                Event::new(EventKind::Warning, &key, message).emit(ctx);
+            } else {
+                println!("[monitor] {} {}", key, message);
+            }
+        }
+    }
+
+    fn check_ports(
+        &mut self,
+        groups: &[ServerGroup],
+        statuses: &[MinecraftServer],
+        ctx: &mut ContextManager,
+    ) {
+        //! Records live servers outside their group's port section, once per server while
+        //! it stays outside.
+        let mismatches = doctor::get_port_mismatches(groups, statuses);
+        self.misported
+            .retain(|server| mismatches.iter().any(|mismatch| &mismatch.server == server));
+        for mismatch in mismatches {
+            if self.misported.insert(mismatch.server.clone()) {
+                Event::new(EventKind::Warning, &mismatch.server, mismatch.to_string()).emit(ctx);
+            }
+        }
+    }
+
+    fn reconcile_counts(
+        groups: &[ServerGroup],
+        statuses: &[MinecraftServer],
+        ctx: &mut ContextManager,
+    ) -> Result<(), redis::RedisError> {
+        //! Resets every group's live counts to what its statuses show, in case a launch or
+        //! death on some manager was missed.
+        for group in groups {
+            let counts = GroupCounts::of(group, statuses);
+            let Some(drifted) = counters::reconcile(&group.prefix, counts, ctx)? else {
+                continue;
+            };
+            if drifted != GroupCounts::default() {
+                println!(
+                    "[monitor] {} counts drifted to {}, reset to {}",
+                    group.prefix, drifted, counts
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn report_reconnects(ctx: &mut ContextManager) {
+        //! Records master failovers the connection followed since the last tick.
+        for reconnect in ctx.get_connection().take_reconnects() {
+            let message = format!(
+                "moved from {} to {} after: {}",
+                reconnect.from, reconnect.to, reconnect.reason
+            );
+            Event::new(EventKind::RedisReconnected, &reconnect.master_name, message).emit(ctx);
+        }
+    }
+
+    fn beat(&mut self, timing: &MonitorTiming, ctx: &mut ContextManager) {
+        let connection = ctx.get_connection().get_state();
+        let heartbeat = Heartbeat::new(&self.instance_id, false, self.last_reconcile, connection);
+        if let Err(err) = heartbeat.beat(timing.get_heartbeat_ttl(), ctx) {
+            println!("[monitor] heartbeat failed: {}", err);
+            self.summary.errors.push(format!("heartbeat: {}", err));
+        }
+        if let Err(err) = RedisMetrics::publish(&self.instance_id, timing.get_heartbeat_ttl(), ctx)
+        {
+            self.summary.errors.push(format!("metrics: {}", err));
+        }
+    }
+
+    fn check_topology(&mut self, ctx: &mut ContextManager) -> Result<(), String> {
+        let report = doctor::check_startup_topology(ctx);
+        if report.findings.is_empty() {
+            println!("[monitor] topology matches the configuration");
+            return Ok(());
+        }
+        for finding in report.findings.iter() {
+            println!("[monitor] topology {}", finding);
+        }
+        let errors = report
+            .findings
+            .iter()
+            .filter(|finding| finding.severity == doctor::Severity::Error)
+            .count();
+        let message = format!(
+            "{} severe and {} other mismatch(es) between the configured nodes and redis",
+            errors,
+            report.findings.len() - errors
+        );
+        Event::new(EventKind::Warning, "topology", message.clone()).emit(ctx);
+        match report.is_healthy() || self.ignore_topology {
+            true => Ok(()),
+            false => Err(format!("{} (or run with --ignore-topology)", message)),
+        }
+    }
+
+    fn shutdown(mut self, ctx: &mut ContextManager) -> MonitorSummary {
+        //! Last step before the monitor returns; cleanup of state the loop owns goes here.
+        if let Err(err) = Heartbeat::delete(&self.instance_id, ctx) {
+            self.summary.errors.push(format!("heartbeat: {}", err));
+        }
+        self.summary.stopped_by = shutdown::get_signal_name().map(String::from);
+        self.summary
+    }
+}
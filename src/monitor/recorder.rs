@@ -0,0 +1,310 @@
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use redis::RedisError;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    context_manager::ContextManager,
+    region::Region,
+    server::dedicated::{
+        instance::MCSInstance,
+        launcher::{self, LaunchInfo, LaunchPlan},
+        server::DedicatedServer,
+    },
+    store::snapshot::Snapshot,
+    strategy::{self, ScalingDecision, ScalingInfo},
+};
+
+#[derive(Error, Debug)]
+pub enum RecorderError {
+    #[error("Recorder Error: Could not read recording `{0}`")]
+    ReadError(String),
+    #[error("Recorder Error: Could not write recording `{0}`")]
+    WriteError(String),
+    #[error("Recorder Error: `{0}`")]
+    RedisError(#[from] RedisError),
+}
+
+/// Where region workers record the inputs of their reconciles, to replay them later with
+/// `replay`. Off by default, a recording holds the region's groups and statuses.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct RecorderInfo {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_path")]
+    pub path: String,
+    #[serde(default = "default_keep")]
+    pub keep: usize, // recordings kept per region, older ones are deleted
+}
+
+fn default_path() -> String {
+    "recordings".into()
+}
+
+fn default_keep() -> usize {
+    200
+}
+
+impl Default for RecorderInfo {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: default_path(),
+            keep: default_keep(),
+        }
+    }
+}
+
+/// An instance a node was tracking when the cycle was recorded.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct RecordedInstance {
+    pub node: String,
+    pub name: String,
+    pub group: String,
+    pub port: u16,
+}
+
+/// Everything one reconcile decided from: the redis keys it read, the nodes as the worker
+/// saw them, and the config that shapes the decisions. The decisions it made are kept to
+/// compare a replay against.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Recording {
+    pub region: Option<Region>, // None for the worker covering every region
+    pub recorded_at: i64,       // milliseconds since epoch
+    pub scaling_strategy: String,
+    pub scaling: ScalingInfo,
+    pub launch: LaunchInfo,
+    pub snapshot: Snapshot,
+    pub nodes: Vec<DedicatedServer>,
+    pub instances: Vec<RecordedInstance>,
+    #[serde(default)]
+    pub decisions: Vec<ScalingDecision>,
+}
+
+/// What the decision logic makes of a recording today.
+#[derive(Debug)]
+pub struct Replay {
+    pub decisions: Vec<ScalingDecision>,
+    pub plan: LaunchPlan,
+    pub differences: Vec<String>, // where the replay disagrees with the recorded decisions
+}
+
+impl Display for Replay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for decision in self.decisions.iter() {
+            writeln!(f, "{}", decision)?;
+        }
+        writeln!(f, "{}", self.plan)?;
+        if self.differences.is_empty() {
+            return write!(f, "replay matches the recorded decisions");
+        }
+        for difference in self.differences.iter() {
+            writeln!(f, "differs: {}", difference)?;
+        }
+        write!(f, "{} difference(s)", self.differences.len())
+    }
+}
+
+fn get_file_prefix(region: Option<&Region>) -> String {
+    region.map_or("network".into(), |region| region.to_string())
+}
+
+fn list_prefixed(
+    info: &RecorderInfo,
+    prefix: Option<String>,
+) -> Result<Vec<PathBuf>, RecorderError> {
+    let dir = match fs::read_dir(&info.path) {
+        Ok(dir) => dir,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(RecorderError::ReadError(format!("{}: {}", info.path, err))),
+    };
+    let mut recordings: Vec<(i64, PathBuf)> = dir
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter_map(|path| {
+            let stem = path.file_stem()?.to_str()?.to_string();
+            if path.extension()? != "json"
+                || prefix
+                    .as_ref()
+                    .is_some_and(|prefix| !stem.starts_with(prefix))
+            {
+                return None;
+            }
+            let (_, millis) = stem.rsplit_once('-')?;
+            Some((millis.parse().ok()?, path))
+        })
+        .collect();
+    recordings.sort();
+    Ok(recordings.into_iter().map(|(_, path)| path).collect())
+}
+
+impl Recording {
+    pub fn capture(
+        region: Option<&Region>,
+        now: DateTime<Utc>,
+        ctx: &mut ContextManager,
+    ) -> Result<Self, RecorderError> {
+        //! Copies what a reconcile of `region` (every region if None) reads at `now`:
+        //! group hashes, the region's statuses, queues and the context's nodes.
+        let keys = ctx.get_keys();
+        let region_name = region.map(|region| region.to_string());
+        let patterns = [
+            keys.group_pattern(),
+            keys.group_index_key(),
+            keys.status_pattern(region_name.as_deref(), None),
+            keys.queue_key("*"),
+        ];
+        let snapshot = Snapshot::capture_matching(&patterns, ctx)?;
+        let nodes: Vec<DedicatedServer> = ctx
+            .get_dedicated_servers()
+            .servers
+            .iter()
+            .filter(|node| region.is_none_or(|region| &node.region == region))
+            .cloned()
+            .collect();
+        let instances = nodes
+            .iter()
+            .flat_map(|node| {
+                node.server_instances
+                    .values()
+                    .flatten()
+                    .map(|instance| RecordedInstance {
+                        node: node.name.clone(),
+                        name: instance.get_name().clone(),
+                        group: instance.get_group().clone(),
+                        port: instance.get_port(),
+                    })
+            })
+            .collect();
+        Ok(Self {
+            region: region.cloned(),
+            recorded_at: now.timestamp_millis(),
+            scaling_strategy: ctx.get_scaling_strategy().get_name().into(),
+            scaling: ctx.get_config().scaling.clone(),
+            launch: ctx.get_config().launch.clone(),
+            snapshot,
+            nodes,
+            instances,
+            decisions: Vec::new(),
+        })
+    }
+
+    pub fn get_recorded_at(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis(self.recorded_at).unwrap_or_default()
+    }
+
+    pub fn load(path: &str) -> Result<Self, RecorderError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| RecorderError::ReadError(format!("{}: {}", path, err)))?;
+        serde_json::from_str(&contents)
+            .map_err(|err| RecorderError::ReadError(format!("{}: {}", path, err)))
+    }
+
+    pub fn save(&self, info: &RecorderInfo) -> Result<PathBuf, RecorderError> {
+        //! Writes the recording into `info.path`, then deletes the region's oldest recordings
+        //! past `info.keep`.
+        fs::create_dir_all(&info.path)
+            .map_err(|err| RecorderError::WriteError(format!("{}: {}", info.path, err)))?;
+        let prefix = get_file_prefix(self.region.as_ref());
+        let path = Path::new(&info.path).join(format!("{}-{}.json", prefix, self.recorded_at));
+        let contents = serde_json::to_string(self)
+            .map_err(|err| RecorderError::WriteError(err.to_string()))?;
+        fs::write(&path, contents)
+            .map_err(|err| RecorderError::WriteError(format!("{}: {}", path.display(), err)))?;
+        let recordings = list_prefixed(info, Some(format!("{}-", prefix)))?;
+        for old in recordings
+            .iter()
+            .take(recordings.len().saturating_sub(info.keep))
+        {
+            let _ = fs::remove_file(old);
+        }
+        Ok(path)
+    }
+
+    pub fn list(
+        info: &RecorderInfo,
+        region: Option<&Region>,
+    ) -> Result<Vec<PathBuf>, RecorderError> {
+        //! Recordings of `region` (every region's if None), oldest first.
+        list_prefixed(
+            info,
+            region.map(|region| format!("{}-", get_file_prefix(Some(region)))),
+        )
+    }
+
+    pub fn replay(&self, ctx: &mut ContextManager) -> Replay {
+        //! Re-runs the reconcile's decisions offline against the recording, with the recorded
+        //! scaling and launch config and the clock set to when it was recorded. The placement
+        //! and scaling strategies are `ctx`'s, so a changed strategy can be tried on an old cycle.
+        let mut config = ctx.get_config().clone();
+        config.scaling = self.scaling.clone();
+        config.launch = self.launch.clone();
+        config.dedicated_servers.servers = self.nodes.clone();
+        for recorded in self.instances.iter() {
+            let Some(node) = config
+                .dedicated_servers
+                .servers
+                .iter_mut()
+                .find(|node| node.name == recorded.node)
+            else {
+                continue;
+            };
+            node.server_instances
+                .entry(recorded.group.clone())
+                .or_default()
+                .push(MCSInstance::new(
+                    recorded.name.clone(),
+                    recorded.group.clone(),
+                    recorded.port,
+                    node.region.clone(),
+                    None,
+                ));
+        }
+        let mut offline = ctx.from_snapshot(&config, self.snapshot.clone());
+        let region = self.region.clone();
+        let decisions = strategy::plan_scaling_at(
+            |group| region.as_ref().is_none_or(|region| &group.region == region),
+            self.get_recorded_at(),
+            &mut offline,
+        );
+        let missing = launcher::get_missing(&decisions, &mut offline);
+        let plan = launcher::plan(&missing, &mut offline);
+        let mut differences = Vec::new();
+        let strategy = offline.get_scaling_strategy().get_name();
+        if strategy != self.scaling_strategy {
+            differences.push(format!(
+                "recorded with {} scaling, replayed with {}",
+                self.scaling_strategy, strategy
+            ));
+        }
+        for recorded in self.decisions.iter() {
+            match decisions
+                .iter()
+                .find(|decision| decision.group == recorded.group)
+            {
+                Some(decision) if decision == recorded => {}
+                Some(decision) => differences.push(format!("{} -> {}", recorded, decision)),
+                None => differences.push(format!("{} -> no decision", recorded)),
+            }
+        }
+        for decision in decisions.iter() {
+            if !self
+                .decisions
+                .iter()
+                .any(|recorded| recorded.group == decision.group)
+            {
+                differences.push(format!("no decision -> {}", decision));
+            }
+        }
+        Replay {
+            decisions,
+            plan,
+            differences,
+        }
+    }
+}
@@ -0,0 +1,706 @@
+use std::{
+    fmt::Display,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    batch::get_panic_message,
+    config::models::{MonitorTask, MonitorTiming},
+    context_manager::ContextManager,
+    events::{Event, EventKind},
+    region::Region,
+    server::{
+        dedicated::{healing, orphans::OrphanPolicy, prewarm},
+        recycling, rightsizing,
+        server_group::ServerGroup,
+    },
+    store::{
+        entity::RedisEntity,
+        metrics::{self, RedisMetrics},
+        scan::scan_all_keys,
+    },
+    strategy::{self, ScalingDecision},
+};
+
+use super::{
+    chaos,
+    forecast::{self, DemandSample, Forecast},
+    recorder::Recording,
+    schedule::Schedule,
+    shutdown, MonitorSummary,
+};
+
+const HEALTH_PREFIX: &str = "servermonitor.regions.";
+/// Tasks a worker runs for its region; the supervisor runs the rest.
+const WORKER_TASKS: [MonitorTask; 2] = [MonitorTask::Reconcile, MonitorTask::Rebalance];
+/// Name of the worker that covers every region, used when the monitor runs offline.
+const NETWORK: &str = "network";
+
+/// What a region worker last reported; it expires with the manager's heartbeat, so a
+/// worker that stopped reporting drops out.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct RegionHealth {
+    pub instance_id: String,
+    pub region: String,
+    pub ticks: usize,
+    pub last_reconcile: Option<i64>, // seconds since epoch
+    pub reconcile_ms: u64,           // how long the last reconcile took
+    pub missing: usize,              // desired minus running instances of the region's groups
+    pub errors: usize,
+    pub last_error: Option<String>,
+    pub restarts: usize, // times the supervisor replaced a crashed worker
+    pub redis_errors: u64,
+    #[serde(default)]
+    pub forecast_percent: Option<u64>, // tightest upcoming hour's predicted demand, % of capacity
+    #[serde(default)]
+    pub forecast_at: Option<i64>, // that hour, seconds since epoch
+    pub timestamp: i64, // seconds since epoch
+}
+
+/// Reconciles and rebalances one region on its own connection, so a slow or failing
+/// region doesn't hold up the others.
+#[derive(Clone, Debug, Default)]
+pub struct RegionWorker {
+    region: Option<Region>, // None: every group and node
+    schedule: Schedule,
+    health: RegionHealth,
+    summary: MonitorSummary,
+    forecast_warned: Vec<Region>, // regions warned about until their forecast drops again
+    prewarmed: Vec<(Region, i64)>, // forecast peaks (region, hour) already prewarmed for
+    last_sampled: Option<i64>,    // seconds since epoch, see [sampling]
+    last_swept: Option<i64>,      // seconds since epoch, see [orphans]
+    rss_warned: Vec<String>,      // instances warned about until their rss drops again
+    reported: Option<RegionHealth>, // health hooks were last told about
+}
+
+/// Region workers running on their own threads, restarted by the supervisor when they crash.
+pub struct RegionWorkers {
+    instance_id: String,
+    stop: Arc<AtomicBool>,
+    running: Vec<RunningWorker>,
+}
+
+struct RunningWorker {
+    region: Region,
+    restarts: usize,
+    started: Instant,
+    handle: JoinHandle<MonitorSummary>,
+}
+
+impl Display for RegionHealth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let last_reconcile = self
+            .last_reconcile
+            .and_then(|at| chrono::DateTime::from_timestamp(at, 0))
+            .map_or("never".into(), |at| at.with_timezone(&Local).to_rfc3339());
+        write!(
+            f,
+            "{} on {}: {} ticks, last reconcile {} ({}ms), {} missing instances, {} errors",
+            self.region,
+            self.instance_id,
+            self.ticks,
+            last_reconcile,
+            self.reconcile_ms,
+            self.missing,
+            self.errors
+        )?;
+        if self.restarts > 0 {
+            write!(f, ", {} restarts", self.restarts)?;
+        }
+        Ok(())
+    }
+}
+
+impl RegionHealth {
+    pub fn get_all(ctx: &mut ContextManager) -> Result<Vec<Self>, redis::RedisError> {
+        //! Health of every running worker across managers. Unparsable entries are skipped.
+        let keys = scan_all_keys(&format!("{}*", HEALTH_PREFIX), ctx)?;
+        let mut health = Vec::new();
+        for key in keys {
+            let raw: Option<String> = redis::cmd("GET").arg(&key).query(ctx.get_connection())?;
+            if let Some(worker) = raw.and_then(|raw| serde_json::from_str(&raw).ok()) {
+                health.push(worker);
+            }
+        }
+        health.sort_by(|a: &Self, b: &Self| {
+            (&a.region, &a.instance_id).cmp(&(&b.region, &b.instance_id))
+        });
+        Ok(health)
+    }
+
+    fn get_key(&self) -> String {
+        format!("{}{}.{}", HEALTH_PREFIX, self.instance_id, self.region)
+    }
+
+    pub fn is_same_state(&self, other: &Self) -> bool {
+        //! Whether both report the same missing instances, errors, restarts and forecast,
+        //! regardless of ticks and timings.
+        (
+            self.missing,
+            self.errors,
+            &self.last_error,
+            self.restarts,
+            self.forecast_percent,
+            self.forecast_at,
+        ) == (
+            other.missing,
+            other.errors,
+            &other.last_error,
+            other.restarts,
+            other.forecast_percent,
+            other.forecast_at,
+        )
+    }
+}
+
+impl RegionWorker {
+    pub fn new(instance_id: &str, region: Option<Region>, restarts: usize) -> Self {
+        let name = region
+            .as_ref()
+            .map_or(NETWORK.into(), |region| region.to_string());
+        Self {
+            region,
+            health: RegionHealth {
+                instance_id: instance_id.into(),
+                region: name,
+                restarts,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    pub fn run(mut self, stop: &AtomicBool, ctx: &mut ContextManager) -> MonitorSummary {
+        //! Loop of a worker on its own thread, until `stop` is set or a shutdown is requested.
+        //! The region's running servers are adopted first.
+        metrics::set_region(&self.health.region);
+        println!(
+            "[monitor {}] {}",
+            self.health.region,
+            ctx.recover_instances()
+        );
+        while !stop.load(Ordering::SeqCst) {
+            let timing = ctx.get_config().monitor_info.get_timing().clone();
+            self.tick(&timing, ctx);
+            if !shutdown::sleep_unless_shutdown(timing.get_tick()) {
+                break;
+            }
+        }
+        self.finish(ctx)
+    }
+
+    pub fn finish(mut self, ctx: &mut ContextManager) -> MonitorSummary {
+        //! Withdraws the worker's health, returning what it did.
+        if let Err(err) = redis::cmd("DEL")
+            .arg(self.health.get_key())
+            .query::<()>(ctx.get_connection())
+        {
+            self.record_error(format!("health: {}", err));
+        }
+        self.summary
+    }
+
+    pub fn tick(&mut self, timing: &MonitorTiming, ctx: &mut ContextManager) {
+        //! Runs the worker's due tasks, then reports its health.
+        let now = Instant::now();
+        for task in self.schedule.get_due_tasks(timing, now) {
+            if !WORKER_TASKS.contains(&task) || shutdown::is_shutdown_requested() {
+                continue;
+            }
+            if let Err(err) = self.run_task(task, ctx) {
+                println!("[monitor {}] {} failed: {}", self.health.region, task, err);
+                self.record_error(format!("{}: {}", task, err));
+            }
+            self.schedule.mark_run(task, now);
+            *self.summary.tasks_run.entry(task).or_default() += 1;
+        }
+        self.summary.ticks += 1;
+        self.health.ticks += 1;
+        self.publish(timing.get_heartbeat_ttl(), ctx);
+    }
+
+    fn run_task(&mut self, task: MonitorTask, ctx: &mut ContextManager) -> Result<(), String> {
+        match task {
+            MonitorTask::Reconcile => {
+                let started = Instant::now();
+                let region = self.region.clone();
+                let now = Utc::now();
+                self.remove_dead(ctx);
+                let recording = self.capture(now, ctx);
+                let decisions = strategy::plan_scaling_at(
+                    |group| region.as_ref().is_none_or(|region| &group.region == region),
+                    now,
+                    ctx,
+                );
+                if let Some(recording) = recording {
+                    self.record(recording, &decisions, ctx);
+                }
+                for decision in decisions.iter() {
+                    for hooks in ctx.get_hooks() {
+                        hooks.on_scale_decision(decision);
+                    }
+                    if decision.running != decision.desired {
+                        println!(
+                            "[monitor {}] {} ({})",
+                            self.health.region,
+                            decision,
+                            ctx.get_scaling_strategy().get_name()
+                        );
+                    }
+                }
+                self.health.missing = decisions
+                    .iter()
+                    .map(|decision| decision.desired.saturating_sub(decision.running))
+                    .sum();
+                let previous = ctx.get_dedicated_servers().clone();
+                let unreadable = ctx.with_dedicated_servers(|servers, ctx| {
+                    let unreadable = servers.merge_telemetry(ctx);
+                    servers.report_disks(&previous, region.as_ref(), ctx);
+                    unreadable
+                });
+                for (key, err) in unreadable.iter() {
+                    println!(
+                        "[monitor {}] {} could not be read: {}",
+                        self.health.region, key, err
+                    );
+                }
+                self.sample(ctx)
+                    .map_err(|err| format!("sampling: {}", err))?;
+                self.sweep_orphans(ctx)
+                    .map_err(|err| format!("orphans: {}", err))?;
+                let strays = ctx
+                    .with_dedicated_servers(|servers, ctx| {
+                        servers.handle_strays(region.as_ref(), ctx)
+                    })
+                    .map_err(|err| format!("strays: {}", err))?;
+                for (server, node) in strays.adopted.iter() {
+                    println!(
+                        "[monitor {}] adopted stray {} on {}",
+                        self.health.region, server, node
+                    );
+                }
+                for (instance, result) in recycling::recycle(region.as_ref(), now, ctx) {
+                    match result {
+                        Ok(()) => {
+                            println!("[monitor {}] recycled {}", self.health.region, instance)
+                        }
+                        Err(err) => println!(
+                            "[monitor {}] {} could not be recycled: {}",
+                            self.health.region, instance, err
+                        ),
+                    }
+                }
+                self.launch_replacements(&decisions, ctx);
+                self.health.last_reconcile = Some(Local::now().timestamp());
+                self.health.reconcile_ms = started.elapsed().as_millis() as u64;
+                let forecast = self
+                    .forecast(&decisions, ctx)
+                    .map_err(|err| format!("forecast: {}", err));
+                for fault in chaos::inject(region.as_ref(), ctx) {
+                    println!("[monitor {}] chaos: {}", self.health.region, fault);
+                }
+                forecast
+            }
+            MonitorTask::Rebalance => ctx
+                .run_rebalance_pass()
+                .map(|_| ())
+                .map_err(|err| err.to_string()),
+            MonitorTask::Statuses => Ok(()),
+        }
+    }
+
+    fn sample(&mut self, ctx: &mut ContextManager) -> Result<(), redis::RedisError> {
+        //! Samples instance processes on nodes without telemetry every `interval_secs`,
+        //! recording them for rightsizing and warning (once) about instances using far more
+        //! memory than their group has.
+        let info = ctx.get_config().sampling.clone();
+        let now = ctx.get_clock().now().timestamp();
+        if !info.enabled
+            || self
+                .last_sampled
+                .is_some_and(|at| now - at < info.interval_secs)
+        {
+            return Ok(());
+        }
+        self.last_sampled = Some(now);
+        let region = self.region.clone();
+        let report =
+            ctx.with_dedicated_servers(|servers, ctx| servers.sample_usage(region.as_ref(), ctx))?;
+        for (subject, err) in report.failed.iter() {
+            println!(
+                "[monitor {}] {} could not be sampled: {}",
+                self.health.region, subject, err
+            );
+        }
+        let groups = ServerGroup::get_all(ctx).ok;
+        rightsizing::record_processes(&groups, &report.samples, ctx)?;
+        let over = rightsizing::get_over_ram(&groups, &report.samples, info.rss_warn_percent);
+        self.rss_warned
+            .retain(|server| over.iter().any(|(sample, _)| &sample.server == server));
+        for (sample, ram) in over {
+            if self.rss_warned.contains(&sample.server) {
+                continue;
+            }
+            let message = format!(
+                "process uses {}MB, over {}% of the group's {}MB",
+                sample.rss_mb, info.rss_warn_percent, ram
+            );
+            Event::new(EventKind::Warning, &sample.server, message).emit(ctx);
+            self.rss_warned.push(sample.server);
+        }
+        Ok(())
+    }
+
+    fn sweep_orphans(&mut self, ctx: &mut ContextManager) -> Result<(), redis::RedisError> {
+        //! Looks for orphaned server processes on the region's nodes every `interval_secs`.
+        let info = ctx.get_config().orphans.clone();
+        let now = ctx.get_clock().now().timestamp();
+        if info.policy == OrphanPolicy::Off
+            || self
+                .last_swept
+                .is_some_and(|at| now - at < info.interval_secs)
+        {
+            return Ok(());
+        }
+        self.last_swept = Some(now);
+        let region = self.region.clone();
+        let report = ctx.with_dedicated_servers(|servers, ctx| {
+            servers.sweep_orphans(region.as_ref(), info.policy, ctx)
+        })?;
+        for (subject, err) in report.failed.iter() {
+            println!(
+                "[monitor {}] {} could not be swept: {}",
+                self.health.region, subject, err
+            );
+        }
+        for orphan in report.killed.iter() {
+            println!("[monitor {}] killed orphan {}", self.health.region, orphan);
+        }
+        Ok(())
+    }
+
+    fn remove_dead(&mut self, ctx: &mut ContextManager) {
+        //! Removes the region's instances that stopped reporting, see [healing].
+        let info = ctx.get_config().healing.clone();
+        if !info.enabled || ctx.get_connection().is_offline() {
+            return;
+        }
+        let region = self.region.clone();
+        let report = ctx.with_dedicated_servers(|servers, ctx| {
+            servers.remove_dead(region.as_ref(), info.dead_after_secs, ctx)
+        });
+        for (server, node) in report.removed.iter() {
+            println!(
+                "[monitor {}] removed dead {} from {}",
+                self.health.region,
+                server,
+                node.as_deref().unwrap_or("no node")
+            );
+        }
+        for (server, err) in report.failed.iter() {
+            println!(
+                "[monitor {}] dead {} could not be removed: {}",
+                self.health.region, server, err
+            );
+        }
+    }
+
+    fn launch_replacements(&mut self, decisions: &[ScalingDecision], ctx: &mut ContextManager) {
+        //! Launches what the region's groups are short of, see [healing].
+        let info = ctx.get_config().healing.clone();
+        if !info.enabled || ctx.get_connection().is_offline() {
+            return;
+        }
+        let name = self.health.region.clone();
+        let (unplaced, report) = healing::launch_replacements(
+            decisions,
+            info.max_launches,
+            |outcome| match &outcome.result {
+                Ok(_) => println!("[monitor {}] launched {}", name, outcome.target),
+                Err(err) => println!(
+                    "[monitor {}] {} could not be launched: {}",
+                    name, outcome.target, err
+                ),
+            },
+            ctx,
+        );
+        for (group, count) in unplaced.iter() {
+            println!(
+                "[monitor {}] {}: no node has room for {} more instance(s)",
+                self.health.region, group, count
+            );
+        }
+        if !report.is_success() {
+            self.record_error(format!(
+                "launches: {} of {} failed",
+                report.get_failed_count(),
+                report.outcomes.len()
+            ));
+        }
+    }
+
+    fn forecast(
+        &mut self,
+        decisions: &[ScalingDecision],
+        ctx: &mut ContextManager,
+    ) -> Result<(), redis::RedisError> {
+        //! Records the demand behind `decisions`, then forecasts it and warns (once) about
+        //! regions heading above the configured share of their capacity.
+        let now = Utc::now();
+        let regions = match &self.region {
+            Some(region) => vec![region.clone()],
+            None => RegionWorkers::get_regions(ctx),
+        };
+        let warn_percent = ctx.get_config().forecast.warn_percent as u64;
+        let mut tightest: Option<Forecast> = None;
+        for region in regions {
+            let in_region: Vec<ScalingDecision> = decisions
+                .iter()
+                .filter(|decision| decision.region == region)
+                .cloned()
+                .collect();
+            forecast::record(&region, DemandSample::of(&in_region), now, ctx)?;
+            let forecasts = forecast::predict(&region, now, ctx)?;
+            self.prewarm(&region, &in_region, &forecasts, now, ctx);
+            let peak = forecast::get_tightest(&forecasts).cloned();
+            let over = peak
+                .as_ref()
+                .filter(|peak| peak.get_percent() >= warn_percent);
+            let warned = self.forecast_warned.contains(&region);
+            match (over, warned) {
+                (Some(peak), false) => {
+                    let message = format!(
+                        "projected demand reaches {}% of node capacity (warning at {}%): {}",
+                        peak.get_percent(),
+                        warn_percent,
+                        peak
+                    );
+                    Event::new(EventKind::Warning, &format!("forecast.{}", region), message)
+                        .emit(ctx);
+                    self.forecast_warned.push(region.clone());
+                }
+                (None, true) => self.forecast_warned.retain(|warned| warned != &region),
+                _ => (),
+            }
+            if let Some(peak) = peak {
+                if tightest
+                    .as_ref()
+                    .is_none_or(|tightest| peak.get_percent() > tightest.get_percent())
+                {
+                    tightest = Some(peak);
+                }
+            }
+        }
+        self.health.forecast_percent = tightest.as_ref().map(Forecast::get_percent);
+        self.health.forecast_at = tightest.map(|tightest| tightest.hour);
+        Ok(())
+    }
+
+    fn prewarm(
+        &mut self,
+        region: &Region,
+        decisions: &[ScalingDecision],
+        forecasts: &[Forecast],
+        now: DateTime<Utc>,
+        ctx: &mut ContextManager,
+    ) {
+        //! Prewarms the region's likely nodes once per forecast peak within the lead time,
+        //! sized by how far the peak's demand exceeds the current one.
+        let info = ctx.get_config().prewarm.clone();
+        self.prewarmed.retain(|(_, hour)| *hour > now.timestamp());
+        let current = DemandSample::of(decisions);
+        if !info.enabled || (current.ram == 0 && current.cpu == 0) {
+            return;
+        }
+        let percent = |demand: u64, current: u64| match current {
+            0 => 100,
+            current => demand * 100 / current,
+        };
+        let lead_end = now.timestamp() + info.lead_hours as i64 * 3600;
+        let peak = forecasts
+            .iter()
+            .filter(|forecast| forecast.hour > now.timestamp() && forecast.hour <= lead_end)
+            .map(|forecast| {
+                let increase = percent(forecast.demand.ram, current.ram)
+                    .max(percent(forecast.demand.cpu, current.cpu));
+                (increase, -forecast.hour)
+            })
+            .max();
+        let (increase, hour) = match peak {
+            Some((increase, hour)) if increase >= 100 + info.min_increase_percent as u64 => {
+                (increase, -hour)
+            }
+            _ => return,
+        };
+        if self.prewarmed.contains(&(region.clone(), hour)) {
+            return;
+        }
+        self.prewarmed.push((region.clone(), hour));
+        let launches = prewarm::get_launches(decisions, increase, ctx);
+        let report = prewarm::prewarm(&launches, false, ctx);
+        println!(
+            "[monitor {}] forecast peak at {}% of current demand: {}",
+            self.health.region, increase, report
+        );
+        for (node, err) in report.failed {
+            self.record_error(format!("prewarm {}: {}", node, err));
+        }
+    }
+
+    fn capture(&mut self, now: DateTime<Utc>, ctx: &mut ContextManager) -> Option<Recording> {
+        //! What the reconcile at `now` is about to read, if the recorder is on. A failed
+        //! recording is an error of the worker, not of the reconcile.
+        if !ctx.get_config().recorder.enabled {
+            return None;
+        }
+        Recording::capture(self.region.as_ref(), now, ctx)
+            .map_err(|err| self.record_error(format!("recorder: {}", err)))
+            .ok()
+    }
+
+    fn record(
+        &mut self,
+        mut recording: Recording,
+        decisions: &[ScalingDecision],
+        ctx: &mut ContextManager,
+    ) {
+        recording.decisions = decisions.to_vec();
+        if let Err(err) = recording.save(&ctx.get_config().recorder) {
+            self.record_error(format!("recorder: {}", err));
+        }
+    }
+
+    fn record_error(&mut self, err: String) {
+        self.health.errors += 1;
+        self.health.last_error = Some(err.clone());
+        self.summary.errors.push(err);
+    }
+
+    fn publish(&mut self, ttl: Duration, ctx: &mut ContextManager) {
+        self.health.timestamp = Local::now().timestamp();
+        self.health.redis_errors = RedisMetrics::get_current()
+            .regions
+            .get(&self.health.region)
+            .map_or(0, |stats| stats.errors);
+        if self
+            .reported
+            .as_ref()
+            .is_none_or(|reported| !reported.is_same_state(&self.health))
+        {
+            for hooks in ctx.get_hooks() {
+                hooks.on_health_change(self.reported.as_ref(), &self.health);
+            }
+            self.reported = Some(self.health.clone());
+        }
+        let raw = match serde_json::to_string(&self.health) {
+            Ok(raw) => raw,
+            Err(err) => return self.record_error(format!("health: {}", err)),
+        };
+        if let Err(err) = redis::cmd("SET")
+            .arg(self.health.get_key())
+            .arg(raw)
+            .arg("EX")
+            .arg(ttl.as_secs().max(1))
+            .query::<()>(ctx.get_connection())
+        {
+            self.record_error(format!("health: {}", err));
+        }
+    }
+}
+
+impl RegionWorkers {
+    pub fn get_regions(ctx: &mut ContextManager) -> Vec<Region> {
+        //! Regions with at least one configured node, each of which gets a worker.
+        let mut regions: Vec<Region> = Vec::new();
+        for server in ctx.get_dedicated_servers().servers.iter() {
+            if !regions.contains(&server.region) {
+                regions.push(server.region.clone());
+            }
+        }
+        regions
+    }
+
+    pub fn spawn(instance_id: &str, regions: &[Region], ctx: &ContextManager) -> Self {
+        let mut workers = Self {
+            instance_id: instance_id.into(),
+            stop: Arc::new(AtomicBool::new(false)),
+            running: Vec::new(),
+        };
+        for region in regions {
+            let worker = workers.start(region, 0, ctx);
+            workers.running.push(worker);
+        }
+        workers
+    }
+
+    fn start(&self, region: &Region, restarts: usize, ctx: &ContextManager) -> RunningWorker {
+        let build = ctx.region_builder(region);
+        let worker = RegionWorker::new(&self.instance_id, Some(region.clone()), restarts);
+        let stop = self.stop.clone();
+        let handle = thread::Builder::new()
+            .name(format!("monitor-{}", region))
+            .spawn(move || worker.run(&stop, &mut build()))
+            .expect("Region worker thread could not be started");
+        RunningWorker {
+            region: region.clone(),
+            restarts,
+            started: Instant::now(),
+            handle,
+        }
+    }
+
+    pub fn supervise(&mut self, summary: &mut MonitorSummary, ctx: &mut ContextManager) {
+        //! Replaces workers that stopped on their own (a panic, e.g. a lost connection),
+        //! at most once per reconcile cadence each.
+        if self.stop.load(Ordering::SeqCst) || shutdown::is_shutdown_requested() {
+            return;
+        }
+        let backoff = ctx
+            .get_config()
+            .monitor_info
+            .get_timing()
+            .get_cadence(MonitorTask::Reconcile);
+        for i in 0..self.running.len() {
+            let worker = &self.running[i];
+            if !worker.handle.is_finished() || worker.started.elapsed() < backoff {
+                continue;
+            }
+            let (region, restarts) = (self.running[i].region.clone(), self.running[i].restarts);
+            let replacement = self.start(&region, restarts + 1, ctx);
+            let stopped = std::mem::replace(&mut self.running[i], replacement);
+            let reason = match stopped.handle.join() {
+                Ok(worker) => {
+                    summary.merge(&region.to_string(), worker);
+                    "stopped".to_string()
+                }
+                Err(panic) => format!("crashed: {}", get_panic_message(&panic)),
+            };
+            let message = format!("region worker {}, restarted", reason);
+            summary.errors.push(format!("{}: {}", region, message));
+            Event::new(EventKind::Warning, &format!("monitor.{}", region), message).emit(ctx);
+        }
+    }
+
+    pub fn stop(self, summary: &mut MonitorSummary) {
+        //! Asks every worker to stop after its current task and waits for them.
+        self.stop.store(true, Ordering::SeqCst);
+        for worker in self.running {
+            match worker.handle.join() {
+                Ok(worker_summary) => summary.merge(&worker.region.to_string(), worker_summary),
+                Err(panic) => summary.errors.push(format!(
+                    "{}: region worker crashed: {}",
+                    worker.region,
+                    get_panic_message(&panic)
+                )),
+            }
+        }
+    }
+}
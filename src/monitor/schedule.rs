@@ -0,0 +1,28 @@
+use std::{collections::HashMap, time::Instant};
+
+use strum::IntoEnumIterator;
+
+use crate::config::models::{MonitorTask, MonitorTiming};
+
+/// Tracks when each monitor task last ran, to tell which ones are due.
+#[derive(Clone, Debug, Default)]
+pub struct Schedule {
+    last_run: HashMap<MonitorTask, Instant>,
+}
+
+impl Schedule {
+    pub fn get_due_tasks(&self, timing: &MonitorTiming, now: Instant) -> Vec<MonitorTask> {
+        //! Tasks whose cadence has passed since they last ran (or that never ran).
+        MonitorTask::iter()
+            .filter(|&task| {
+                self.last_run
+                    .get(&task)
+                    .is_none_or(|&last| now.duration_since(last) >= timing.get_cadence(task))
+            })
+            .collect()
+    }
+
+    pub fn mark_run(&mut self, task: MonitorTask, at: Instant) {
+        self.last_run.insert(task, at);
+    }
+}
@@ -0,0 +1,282 @@
+use std::{collections::BTreeSet, fmt::Display};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    context_manager::ContextManager,
+    events::{Event, EventKind},
+    server::server_group::ServerGroup,
+    store::entity::RedisEntity,
+    undo::GroupChange,
+};
+
+/// Set of services currently seen down, shared so every manager reports a change once.
+const DOWN_KEY: &str = "servermonitor.services.down";
+/// Hash of group -> `RewardSafe`, the reward flags a group had before they were turned off.
+const REWARD_SAFE_KEY: &str = "servermonitor.rewardsafe";
+
+#[derive(Error, Debug)]
+pub enum ServiceError {
+    #[error("Service Error: Rewards can't be enabled while a service is down: `{0}`")]
+    ServiceDown(String),
+    #[error("Service Error: Redis Error: `{0}`")]
+    RedisError(String),
+}
+
+impl From<redis::RedisError> for ServiceError {
+    fn from(err: redis::RedisError) -> Self {
+        ServiceError::RedisError(err.to_string())
+    }
+}
+
+/// An external service (e.g. the economy service) reward-granting groups depend on. It is
+/// up while its heartbeat key exists and, with `max_age_secs`, holds a recent timestamp.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct ServiceCheck {
+    pub name: String,
+    pub key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_secs: Option<u64>, // the key holds seconds (or millis) since epoch
+    #[serde(default)]
+    pub groups: Vec<String>, // groups that depend on it, every group if empty
+}
+
+/// Result of one service check.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ServiceStatus {
+    pub name: String,
+    pub up: bool,
+    pub detail: String,
+}
+
+/// A group whose gem and item rewards were turned off while a service it depends on was down.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct RewardSafe {
+    pub group: String,
+    pub services: Vec<String>,
+    pub reward_gems: bool, // as it was before
+    pub reward_items: bool,
+    pub since: i64, // seconds since epoch
+}
+
+impl Display for ServiceStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = if self.up { "up" } else { "DOWN" };
+        write!(f, "{} {} ({})", self.name, state, self.detail)
+    }
+}
+
+fn parse_timestamp(raw: &str) -> Option<i64> {
+    //! Seconds since epoch, from seconds or milliseconds.
+    let value: i64 = raw.trim().parse().ok()?;
+    Some(if value > 1_000_000_000_000 {
+        value / 1000
+    } else {
+        value
+    })
+}
+
+impl ServiceCheck {
+    pub fn is_depended_on_by(&self, group: &str) -> bool {
+        self.groups.is_empty() || self.groups.iter().any(|name| name == group)
+    }
+
+    pub fn check(&self, ctx: &mut ContextManager) -> ServiceStatus {
+        let raw: Result<Option<String>, _> =
+            redis::cmd("GET").arg(&self.key).query(ctx.get_connection());
+        let (up, detail) = match (raw, self.max_age_secs) {
+            (Err(err), _) => (false, format!("{} could not be read: {}", self.key, err)),
+            (Ok(None), _) => (false, format!("{} is missing", self.key)),
+            (Ok(Some(_)), None) => (true, format!("{} present", self.key)),
+            (Ok(Some(raw)), Some(max_age)) => match parse_timestamp(&raw) {
+                Some(at) => {
+                    let age = Local::now().timestamp() - at;
+                    (age <= max_age as i64, format!("heartbeat {}s old", age))
+                }
+                None => (false, format!("{} holds no timestamp: {:?}", self.key, raw)),
+            },
+        };
+        ServiceStatus {
+            name: self.name.clone(),
+            up,
+            detail,
+        }
+    }
+}
+
+impl RewardSafe {
+    pub fn get_all(ctx: &mut ContextManager) -> Result<Vec<Self>, redis::RedisError> {
+        let raw: Vec<String> = redis::cmd("HVALS")
+            .arg(REWARD_SAFE_KEY)
+            .query(ctx.get_connection())?;
+        Ok(raw
+            .iter()
+            .filter_map(|raw| serde_json::from_str(raw).ok())
+            .collect())
+    }
+
+    fn save(&self, ctx: &mut ContextManager) -> Result<(), redis::RedisError> {
+        let raw = serde_json::to_string(self).map_err(|err| {
+            redis::RedisError::from((
+                redis::ErrorKind::ClientError,
+                "Reward-safe serialization error",
+                err.to_string(),
+            ))
+        })?;
+        redis::cmd("HSET")
+            .arg(REWARD_SAFE_KEY)
+            .arg(&self.group)
+            .arg(raw)
+            .query(ctx.get_connection())
+    }
+
+    fn delete(&self, ctx: &mut ContextManager) -> Result<(), redis::RedisError> {
+        redis::cmd("HDEL")
+            .arg(REWARD_SAFE_KEY)
+            .arg(&self.group)
+            .query(ctx.get_connection())
+    }
+}
+
+pub fn check_services(ctx: &mut ContextManager) -> Vec<ServiceStatus> {
+    let services = ctx.get_config().services.clone();
+    services.iter().map(|service| service.check(ctx)).collect()
+}
+
+pub fn get_down(ctx: &mut ContextManager) -> Result<BTreeSet<String>, redis::RedisError> {
+    //! Services the monitor last saw down.
+    redis::cmd("SMEMBERS")
+        .arg(DOWN_KEY)
+        .query(ctx.get_connection())
+}
+
+fn get_down_for(group: &str, statuses: &[ServiceStatus], ctx: &mut ContextManager) -> Vec<String> {
+    //! Names of the down services `group` depends on.
+    ctx.get_config()
+        .services
+        .iter()
+        .filter(|service| service.is_depended_on_by(group))
+        .filter(|service| statuses.iter().any(|s| s.name == service.name && !s.up))
+        .map(|service| service.name.clone())
+        .collect()
+}
+
+fn report_changes(
+    statuses: &[ServiceStatus],
+    ctx: &mut ContextManager,
+) -> Result<(), ServiceError> {
+    //! ServiceDown/ServiceRecovered events for services whose state changed.
+    let known_down = get_down(ctx)?;
+    for status in statuses {
+        match (status.up, known_down.contains(&status.name)) {
+            (false, false) => {
+                redis::cmd("SADD")
+                    .arg(DOWN_KEY)
+                    .arg(&status.name)
+                    .query::<()>(ctx.get_connection())?;
+                Event::new(EventKind::ServiceDown, &status.name, status.detail.clone()).emit(ctx);
+            }
+            (true, true) => {
+                redis::cmd("SREM")
+                    .arg(DOWN_KEY)
+                    .arg(&status.name)
+                    .query::<()>(ctx.get_connection())?;
+                Event::new(
+                    EventKind::ServiceRecovered,
+                    &status.name,
+                    status.detail.clone(),
+                )
+                .emit(ctx);
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+pub fn enforce_reward_safety(ctx: &mut ContextManager) -> Result<Vec<String>, ServiceError> {
+    //! Turns off gem and item rewards of groups that depend on a down service, and turns
+    //! them back on (as they were) once every service they depend on is up again.
+    //! Returns what was changed.
+    if ctx.get_config().services.is_empty() {
+        return Ok(Vec::new());
+    }
+    let statuses = check_services(ctx);
+    report_changes(&statuses, ctx)?;
+    let mut changed = Vec::new();
+    let mut safe: Vec<RewardSafe> = RewardSafe::get_all(ctx)?;
+    for group in ServerGroup::get_all(ctx).ok {
+        let down = get_down_for(&group.prefix, &statuses, ctx);
+        let entry = safe.iter().position(|entry| entry.group == group.prefix);
+        match (down.is_empty(), entry) {
+            (false, None) if group.reward_gems || group.reward_items => {
+                let entry = RewardSafe {
+                    group: group.prefix.clone(),
+                    services: down.clone(),
+                    reward_gems: group.reward_gems,
+                    reward_items: group.reward_items,
+                    since: Local::now().timestamp(),
+                };
+                entry.save(ctx)?;
+                let mut updated = group.clone();
+                updated.reward_gems = false;
+                updated.reward_items = false;
+                updated
+                    .save(ctx)
+                    .map_err(|err| ServiceError::RedisError(err.to_string()))?;
+                let message = format!("reward-safe mode, {} down", down.join(", "));
+                if let Some(change) = GroupChange::between(&group, &updated) {
+                    change.emit(&message, ctx);
+                }
+                changed.push(format!("{}: {}", group.prefix, message));
+            }
+            (false, Some(i)) if safe[i].services != down => {
+                safe[i].services = down;
+                safe[i].save(ctx)?;
+            }
+            (true, Some(i)) => {
+                let entry = safe.remove(i);
+                let mut updated = group.clone();
+                updated.reward_gems |= entry.reward_gems;
+                updated.reward_items |= entry.reward_items;
+                updated
+                    .save(ctx)
+                    .map_err(|err| ServiceError::RedisError(err.to_string()))?;
+                entry.delete(ctx)?;
+                let message = format!("rewards restored, {} up", entry.services.join(", "));
+                if let Some(change) = GroupChange::between(&group, &updated) {
+                    change.emit(&message, ctx);
+                }
+                changed.push(format!("{}: {}", group.prefix, message));
+            }
+            _ => (),
+        }
+    }
+    Ok(changed)
+}
+
+pub fn ensure_rewards_allowed(
+    before: &ServerGroup,
+    after: &ServerGroup,
+    ctx: &mut ContextManager,
+) -> Result<(), ServiceError> {
+    //! Refuses a change that turns gem or item rewards on while a service the group
+    //! depends on is down.
+    let enables =
+        (after.reward_gems && !before.reward_gems) || (after.reward_items && !before.reward_items);
+    if !enables || ctx.get_config().services.is_empty() {
+        return Ok(());
+    }
+    let statuses = check_services(ctx);
+    let down = get_down_for(&after.prefix, &statuses, ctx);
+    match down.is_empty() {
+        true => Ok(()),
+        false => Err(ServiceError::ServiceDown(format!(
+            "servergroups.{} depends on {}",
+            after.prefix,
+            down.join(", ")
+        ))),
+    }
+}
@@ -0,0 +1,58 @@
+use std::{
+    sync::atomic::{AtomicI32, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Signal that asked the manager to stop (0 while running).
+static SHUTDOWN_SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+/// Granularity of interruptible sleeps.
+const SLEEP_SLICE: Duration = Duration::from_millis(100);
+
+extern "C" fn handle_signal(signal: libc::c_int) {
+    // only async-signal-safe work here: record the signal and return
+    SHUTDOWN_SIGNAL.store(signal, Ordering::SeqCst);
+}
+
+pub fn install_signal_handlers() {
+    //! Turns SIGINT/SIGTERM into a shutdown request instead of killing the process,
+    //! so the monitor can stop between operations rather than in the middle of one.
+    let handler = handle_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // SAFETY: the handler only stores into an atomic.
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+pub fn request_shutdown() {
+    SHUTDOWN_SIGNAL.store(libc::SIGTERM, Ordering::SeqCst);
+}
+
+pub fn is_shutdown_requested() -> bool {
+    SHUTDOWN_SIGNAL.load(Ordering::SeqCst) != 0
+}
+
+pub fn get_signal_name() -> Option<&'static str> {
+    match SHUTDOWN_SIGNAL.load(Ordering::SeqCst) {
+        0 => None,
+        libc::SIGINT => Some("SIGINT"),
+        libc::SIGTERM => Some("SIGTERM"),
+        _ => Some("signal"),
+    }
+}
+
+pub fn sleep_unless_shutdown(duration: Duration) -> bool {
+    //! Sleeps for `duration`, waking early on a shutdown request.
+    //! Returns `false` if it was interrupted.
+    let deadline = Instant::now() + duration;
+    while !is_shutdown_requested() {
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        thread::sleep(SLEEP_SLICE.min(deadline - now));
+    }
+    false
+}
@@ -0,0 +1,265 @@
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{context_manager::ContextManager, events::Event, server::minecraft::MinecraftServer};
+
+/// Newest events compared against the last tick's; more events than this between two
+/// ticks are streamed as the latest this many.
+const EVENT_WINDOW: usize = 50;
+const STREAM_PATH: &str = "/stream";
+const SCHEMA_PATH: &str = "/schema/groups";
+/// How long a client may take to send its request line before it is dropped.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Server-sent events endpoint the monitor pushes status changes and events to, so web
+/// dashboards can follow the network without polling. Off by default.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct StreamInfo {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_bind")]
+    pub bind: String, // `host:port` to listen on
+    #[serde(default = "default_max_clients")]
+    pub max_clients: usize,
+}
+
+fn default_bind() -> String {
+    "127.0.0.1:8470".into()
+}
+
+fn default_max_clients() -> usize {
+    32
+}
+
+impl Default for StreamInfo {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: default_bind(),
+            max_clients: default_max_clients(),
+        }
+    }
+}
+
+/// One message on the stream, sent as an SSE event named after its `type`.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamUpdate {
+    Started {
+        server: String,
+        status: serde_json::Value,
+    },
+    Changed {
+        server: String,
+        status: serde_json::Value,
+    },
+    Stopped {
+        server: String,
+    },
+    Event(Event),
+}
+
+impl StreamUpdate {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Self::Started { .. } => "started",
+            Self::Changed { .. } => "changed",
+            Self::Stopped { .. } => "stopped",
+            Self::Event(_) => "event",
+        }
+    }
+
+    fn to_message(&self) -> String {
+        let data = serde_json::to_string(self).unwrap_or_default();
+        format!("event: {}\ndata: {}\n\n", self.get_name(), data)
+    }
+}
+
+fn get_changes(
+    known: &BTreeMap<String, serde_json::Value>,
+    statuses: &[MinecraftServer],
+) -> (BTreeMap<String, serde_json::Value>, Vec<StreamUpdate>) {
+    //! Compares the statuses read this tick with the last tick's, keyed by server name.
+    let current: BTreeMap<String, serde_json::Value> = statuses
+        .iter()
+        .map(|server| (server.get_name().clone(), server.to_json()))
+        .collect();
+    let mut updates = Vec::new();
+    for (server, status) in current.iter() {
+        match known.get(server) {
+            None => updates.push(StreamUpdate::Started {
+                server: server.clone(),
+                status: status.clone(),
+            }),
+            Some(old) if !is_same_status(old, status) => updates.push(StreamUpdate::Changed {
+                server: server.clone(),
+                status: status.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for server in known.keys().filter(|server| !current.contains_key(*server)) {
+        updates.push(StreamUpdate::Stopped {
+            server: server.clone(),
+        });
+    }
+    (current, updates)
+}
+
+fn is_same_status(old: &serde_json::Value, new: &serde_json::Value) -> bool {
+    //! Statuses are rewritten every few seconds with a new time and fluctuating tps and
+    //! ram, which alone aren't changes; players, motd (join status) and address are.
+    let strip = |status: &serde_json::Value| {
+        let mut status = status.clone();
+        if let Some(object) = status.as_object_mut() {
+            for field in ["_currentTime", "_tps", "_ram"] {
+                object.remove(field);
+            }
+        }
+        status
+    };
+    strip(old) == strip(new)
+}
+
+fn accept(
+    stream: TcpStream,
+    clients: &Mutex<Vec<TcpStream>>,
+    known: &Mutex<BTreeMap<String, serde_json::Value>>,
+    max_clients: usize,
+    schema: &str,
+) -> std::io::Result<()> {
+    //! Answers one HTTP request: `GET /stream` subscribes, starting with a `started` event
+    //! per live server; `GET /schema/groups` is sent the group field schema, for editing
+    //! forms; anything else is refused.
+    let mut stream = stream;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut request_line = String::new();
+    BufReader::new(stream.try_clone()?).read_line(&mut request_line)?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+    let path = path.split('?').next().unwrap_or_default();
+    if request_line.starts_with("GET ") && path == SCHEMA_PATH {
+        let header = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+             Access-Control-Allow-Origin: *\r\nContent-Length: {}\r\n\r\n",
+            schema.len()
+        );
+        return stream.write_all(format!("{}{}", header, schema).as_bytes());
+    }
+    if !request_line.starts_with("GET ") || path != STREAM_PATH {
+        return stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n");
+    }
+    // locked in the order `publish_statuses` locks them, known servers first
+    let known = known.lock().unwrap_or_else(|err| err.into_inner());
+    let mut clients = clients.lock().unwrap_or_else(|err| err.into_inner());
+    if clients.len() >= max_clients {
+        return stream.write_all(b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n");
+    }
+    stream.set_write_timeout(Some(Duration::from_secs(1)))?;
+    stream.write_all(
+        b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\
+          Access-Control-Allow-Origin: *\r\nConnection: keep-alive\r\n\r\n",
+    )?;
+    for (server, status) in known.iter() {
+        let update = StreamUpdate::Started {
+            server: server.clone(),
+            status: status.clone(),
+        };
+        stream.write_all(update.to_message().as_bytes())?;
+    }
+    clients.push(stream);
+    Ok(())
+}
+
+/// Listens for dashboards on its own thread; the monitor feeds it from the statuses task.
+#[derive(Clone, Debug, Default)]
+pub struct StreamServer {
+    clients: Arc<Mutex<Vec<TcpStream>>>,
+    known: Arc<Mutex<BTreeMap<String, serde_json::Value>>>, // statuses as last streamed
+    last_event: Option<String>, // newest event entry already streamed, None before the first read
+    started: bool,              // whether the first statuses were read
+}
+
+impl StreamServer {
+    pub fn start(info: &StreamInfo, schema: String) -> std::io::Result<Self> {
+        //! `schema` is the group schema JSON served at /schema/groups, see `schema::to_json`.
+        let listener = TcpListener::bind(&info.bind)?;
+        let server = Self::default();
+        let (clients, known) = (server.clients.clone(), server.known.clone());
+        let max_clients = info.max_clients;
+        let schema: Arc<str> = schema.into();
+        std::thread::spawn(move || {
+            // each request on its own thread, so a slow client doesn't hold up the others
+            for stream in listener.incoming().filter_map(Result::ok) {
+                let (clients, known) = (clients.clone(), known.clone());
+                let schema = schema.clone();
+                std::thread::spawn(move || {
+                    if let Err(err) = accept(stream, &clients, &known, max_clients, &schema) {
+                        println!("[stream] request failed: {}", err);
+                    }
+                });
+            }
+        });
+        Ok(server)
+    }
+
+    pub fn publish_statuses(&mut self, statuses: &[MinecraftServer]) {
+        //! Streams servers that started, changed or stopped since the last call. The
+        //! first call only records what is running, which new clients are sent on connect.
+        //! Known servers stay locked while sending, so a client can't connect in between
+        //! and be sent a change twice.
+        let known = self.known.clone();
+        let mut known = known.lock().unwrap_or_else(|err| err.into_inner());
+        let (current, updates) = get_changes(&known, statuses);
+        *known = current;
+        if std::mem::replace(&mut self.started, true) {
+            self.send(&updates);
+        }
+    }
+
+    pub fn publish_events(&mut self, ctx: &mut ContextManager) -> Result<(), redis::RedisError> {
+        //! Streams events recorded since the last call, by any manager or command, oldest
+        //! first. Events from before the first call aren't streamed.
+        let entries: Vec<String> =
+            Event::get_recent_cmd(EVENT_WINDOW).query(ctx.get_connection())?;
+        let new = match self.last_event.as_ref() {
+            Some(last) => entries.iter().take_while(|entry| *entry != last).count(),
+            None => 0,
+        };
+        // an empty list at the start leaves "", so every event after it is new
+        match entries.first() {
+            Some(newest) => self.last_event = Some(newest.clone()),
+            None => self.last_event = self.last_event.take().or(Some(String::new())),
+        }
+        let mut events = Event::from_entries(&entries[..new]);
+        events.reverse();
+        if !events.is_empty() {
+            self.send(
+                &events
+                    .into_iter()
+                    .map(StreamUpdate::Event)
+                    .collect::<Vec<_>>(),
+            );
+        }
+        Ok(())
+    }
+
+    fn send(&self, updates: &[StreamUpdate]) {
+        //! Writes to every client, dropping those that went away. Sends a comment when there
+        //! is nothing new, so closed connections are still noticed.
+        let message = match updates.is_empty() {
+            true => ": keep-alive\n\n".to_string(),
+            false => updates.iter().map(StreamUpdate::to_message).collect(),
+        };
+        self.clients
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .retain_mut(|client| client.write_all(message.as_bytes()).is_ok());
+    }
+}
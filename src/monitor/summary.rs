@@ -0,0 +1,129 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use strum::IntoEnumIterator;
+
+use crate::{
+    context_manager::ContextManager,
+    game::booster_group::BoosterGroup,
+    queue,
+    server::{minecraft::MinecraftServer, server_group::ServerGroup},
+};
+
+/// Key websites and APIs read instead of scanning every server status.
+pub const SUMMARY_KEY: &str = "network.summary";
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct GroupSummary {
+    pub online: u32,
+    pub capacity: u32,
+    pub servers: u32,
+    #[serde(default)]
+    pub queued: u32, // players waiting for a slot, see `queue`
+}
+
+/// Players in the groups of one booster group, for deciding which boosters to sell.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct BoosterSummary {
+    pub online: u32,
+    pub capacity: u32,
+    pub servers: u32,
+    pub groups: Vec<String>,
+}
+
+impl BoosterSummary {
+    pub fn is_active(&self) -> bool {
+        self.servers > 0
+    }
+
+    pub fn get_occupancy(&self) -> f64 {
+        //! Percentage of its slots taken, 0 without servers.
+        match self.capacity {
+            0 => 0.0,
+            capacity => self.online as f64 * 100.0 / capacity as f64,
+        }
+    }
+}
+
+/// Compact player counts of the whole network, rolled up from live statuses.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct NetworkSummary {
+    pub online: u32,
+    pub servers: u32,
+    pub groups: BTreeMap<String, GroupSummary>,
+    #[serde(default)]
+    pub boosters: BTreeMap<String, BoosterSummary>, // see `add_boosters`
+    pub updated: i64, // seconds since epoch
+}
+
+impl NetworkSummary {
+    pub fn from_statuses(statuses: &[MinecraftServer]) -> Self {
+        let mut summary = Self {
+            updated: Local::now().timestamp(),
+            ..Default::default()
+        };
+        for server in statuses {
+            let group = summary
+                .groups
+                .entry(server.get_group().clone())
+                .or_default();
+            group.online += server.get_player_count() as u32;
+            group.capacity += server.get_max_player_count() as u32;
+            group.servers += 1;
+            summary.online += server.get_player_count() as u32;
+            summary.servers += 1;
+        }
+        summary
+    }
+
+    pub fn add_queues(&mut self, ctx: &mut ContextManager) -> Result<(), String> {
+        //! Fills in queue lengths, adding groups that have players waiting but no servers.
+        let lengths = queue::get_lengths(ctx).map_err(|err| err.to_string())?;
+        for (group, length) in lengths {
+            self.groups.entry(group).or_default().queued = length as u32;
+        }
+        Ok(())
+    }
+
+    pub fn add_boosters(&mut self, groups: &[ServerGroup]) {
+        //! Rolls the group counts up by booster group (`BoosterGroup::of_group`). Every
+        //! booster group is listed, ones without groups or servers with zeros.
+        for booster in BoosterGroup::iter() {
+            self.boosters.entry(booster.to_string()).or_default();
+        }
+        for group in groups {
+            let Some(booster) = BoosterGroup::of_group(group) else {
+                continue;
+            };
+            let entry = self.boosters.entry(booster).or_default();
+            entry.groups.push(group.prefix.clone());
+            if let Some(counts) = self.groups.get(&group.prefix) {
+                entry.online += counts.online;
+                entry.capacity += counts.capacity;
+                entry.servers += counts.servers;
+            }
+        }
+    }
+
+    pub fn publish(&self, ttl: Duration, ctx: &mut ContextManager) -> Result<(), String> {
+        let raw = serde_json::to_string(self)
+            .map_err(|err| format!("summary could not be serialized: {:?}", err))?;
+        redis::cmd("SET")
+            .arg(SUMMARY_KEY)
+            .arg(raw)
+            .arg("EX")
+            .arg(ttl.as_secs().max(1))
+            .query::<()>(ctx.get_connection())
+            .map_err(|err| format!("summary could not be stored: {:?}", err))
+    }
+
+    pub fn get(ctx: &mut ContextManager) -> Result<Option<Self>, redis::RedisError> {
+        //! The last published summary, if it hasn't expired.
+        let raw: Option<String> = redis::cmd("GET")
+            .arg(SUMMARY_KEY)
+            .query(ctx.get_connection())?;
+        Ok(raw.and_then(|raw| serde_json::from_str(&raw).ok()))
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+
+use thiserror::Error;
+
+use crate::context_manager::ContextManager;
+
+#[derive(Error, Debug)]
+pub enum QueueError {
+    #[error("Queue Error: Invalid entry: `{0}`")]
+    InvalidEntry(String),
+    #[error("Queue Error: Redis Error: `{0}`")]
+    RedisError(String),
+}
+
+impl From<redis::RedisError> for QueueError {
+    fn from(err: redis::RedisError) -> Self {
+        QueueError::RedisError(err.to_string())
+    }
+}
+
+pub fn push(group: &str, player: &str, ctx: &mut ContextManager) -> Result<usize, QueueError> {
+    //! Adds a player to the back of a group's waiting room (a list, oldest first, that hub
+    //! plugins may also use directly), or leaves them where they are if they already wait.
+    //! Returns their position (1 is next).
+    if player.is_empty() {
+        return Err(QueueError::InvalidEntry("player is empty".into()));
+    }
+    if let Some(position) = get_position(group, player, ctx)? {
+        return Ok(position);
+    }
+    let key = ctx.get_keys().queue_key(group);
+    let length: usize = redis::cmd("RPUSH")
+        .arg(key)
+        .arg(player)
+        .query(ctx.get_connection())?;
+    Ok(length)
+}
+
+pub fn pop(group: &str, count: usize, ctx: &mut ContextManager) -> Result<Vec<String>, QueueError> {
+    //! Takes up to `count` players from the front of a group's queue.
+    let key = ctx.get_keys().queue_key(group);
+    let mut players = Vec::new();
+    for _ in 0..count {
+        let player: Option<String> = redis::cmd("LPOP").arg(&key).query(ctx.get_connection())?;
+        match player {
+            Some(player) => players.push(player),
+            None => break,
+        }
+    }
+    Ok(players)
+}
+
+pub fn remove(group: &str, player: &str, ctx: &mut ContextManager) -> Result<bool, QueueError> {
+    //! Drops a player who gave up waiting. False if they weren't queued.
+    let key = ctx.get_keys().queue_key(group);
+    let removed: usize = redis::cmd("LREM")
+        .arg(key)
+        .arg(0)
+        .arg(player)
+        .query(ctx.get_connection())?;
+    Ok(removed > 0)
+}
+
+pub fn clear(group: &str, ctx: &mut ContextManager) -> Result<(), QueueError> {
+    //! Drops a deleted group's queue, with anyone still waiting in it.
+    let key = ctx.get_keys().queue_key(group);
+    Ok(redis::cmd("DEL").arg(key).query(ctx.get_connection())?)
+}
+
+pub fn get_players(group: &str, ctx: &mut ContextManager) -> Result<Vec<String>, QueueError> {
+    let key = ctx.get_keys().queue_key(group);
+    Ok(redis::cmd("LRANGE")
+        .arg(key)
+        .arg(0)
+        .arg(-1)
+        .query(ctx.get_connection())?)
+}
+
+pub fn get_position(
+    group: &str,
+    player: &str,
+    ctx: &mut ContextManager,
+) -> Result<Option<usize>, QueueError> {
+    Ok(get_players(group, ctx)?
+        .iter()
+        .position(|queued| queued == player)
+        .map(|i| i + 1))
+}
+
+pub fn get_length(group: &str, ctx: &mut ContextManager) -> Result<usize, QueueError> {
+    let key = ctx.get_keys().queue_key(group);
+    Ok(redis::cmd("LLEN").arg(key).query(ctx.get_connection())?)
+}
+
+pub fn get_lengths(ctx: &mut ContextManager) -> Result<BTreeMap<String, usize>, QueueError> {
+    //! Queue length of every group with players waiting.
+    let index = ctx.get_keys().group_index_key();
+    let groups: Vec<String> = redis::cmd("SMEMBERS")
+        .arg(index)
+        .query(ctx.get_connection())?;
+    let mut lengths = BTreeMap::new();
+    for group in groups {
+        let length = get_length(&group, ctx)?;
+        if length > 0 {
+            lengths.insert(group, length);
+        }
+    }
+    Ok(lengths)
+}
@@ -0,0 +1,60 @@
+use std::sync::{Arc, Mutex};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Where the manager draws random numbers from (random port sections, mock load...).
+/// Thread-local randomness by default; register a `SeededRandom` with
+/// `ContextManager::set_random` to get the same draws on every run.
+pub trait RandomSource: Send + Sync {
+    /// A number in `0..bound`, 0 if `bound` is 0.
+    fn gen_below(&self, bound: u64) -> u64;
+}
+
+pub fn choose<T>(random: &dyn RandomSource, mut items: Vec<T>) -> Option<T> {
+    //! One of `items` picked with `random`, None if there are none.
+    if items.is_empty() {
+        return None;
+    }
+    let index = random.gen_below(items.len() as u64) as usize;
+    Some(items.swap_remove(index))
+}
+
+/// Built-in source: the thread's random generator.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThreadRandom;
+
+impl RandomSource for ThreadRandom {
+    fn gen_below(&self, bound: u64) -> u64 {
+        match bound {
+            0 => 0,
+            bound => rand::thread_rng().gen_range(0..bound),
+        }
+    }
+}
+
+/// A generator seeded once, so a run's draws can be repeated. Clones share the generator.
+#[derive(Clone, Debug)]
+pub struct SeededRandom {
+    rng: Arc<Mutex<StdRng>>,
+}
+
+impl SeededRandom {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Arc::new(Mutex::new(StdRng::seed_from_u64(seed))),
+        }
+    }
+}
+
+impl RandomSource for SeededRandom {
+    fn gen_below(&self, bound: u64) -> u64 {
+        match bound {
+            0 => 0,
+            bound => self
+                .rng
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .gen_range(0..bound),
+        }
+    }
+}
@@ -1,21 +1,16 @@
 use serde::{Deserialize, Serialize};
-use strum_macros::Display;
+use strum_macros::{Display, EnumIter};
 
 use crate::error::parsing_error::ServerGroupParsingError;
 
-#[derive(Clone, Debug, Display, Hash, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Display, EnumIter, Hash, PartialEq, Eq, Deserialize, Serialize)]
 pub enum Region {
+    #[default]
     US,
     EU,
     ALL,
 }
 
-impl Default for Region {
-    fn default() -> Self {
-        Region::US
-    }
-}
-
 impl TryFrom<String> for Region {
     type Error = ServerGroupParsingError;
     fn try_from(value: String) -> Result<Self, Self::Error> {
@@ -0,0 +1,123 @@
+use std::{
+    fmt::Display,
+    io::{self, BufRead, IsTerminal, Write},
+};
+
+use thiserror::Error;
+
+use crate::{
+    context_manager::ContextManager,
+    region::Region,
+    server::{minecraft::MinecraftServer, server_group::ServerGroup},
+};
+
+/// What a destructive operation would take down with it.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Impact {
+    pub instances: Vec<String>,
+    pub players_online: u32,
+}
+
+#[derive(Error, Debug)]
+pub enum SafetyError {
+    #[error("Safety Error: `{0}` requires force ({1})")]
+    RequiresForce(String, Impact),
+    #[error("Safety Error: Impact could not be determined: `{0}`")]
+    ImpactUnavailable(String),
+    #[error("Safety Error: Operation failed: `{0}`")]
+    OperationFailed(String),
+    #[error("Safety Error: `{0}` refused, plugins are incompatible: `{1}`")]
+    Incompatible(String, String),
+}
+
+impl Display for Impact {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} live instance(s) [{}], {} player(s) online",
+            self.instances.len(),
+            self.instances.join(", "),
+            self.players_online
+        )
+    }
+}
+
+impl Impact {
+    pub fn from_servers(servers: &[MinecraftServer]) -> Self {
+        Self {
+            instances: servers.iter().map(|sv| sv.get_name().clone()).collect(),
+            players_online: servers.iter().map(|sv| sv.get_player_count() as u32).sum(),
+        }
+    }
+
+    pub fn of_group(group: &ServerGroup, ctx: &mut ContextManager) -> Result<Self, SafetyError> {
+        //! Impact of taking down every live instance of `group`.
+        //! Instances whose status can't be parsed still count as live (with unknown players).
+        let servers = MinecraftServer::from_server_group(group, ctx);
+        let mut impact = Self::from_servers(&servers.ok);
+        for (key, err) in servers.failed {
+            let Some((_, name)) = ctx
+                .get_keys()
+                .parse_status_key(&key)
+                .filter(|_| !key.contains('*'))
+            else {
+                // the scan itself failed
+                return Err(SafetyError::ImpactUnavailable(format!("{:?}", err)));
+            };
+            impact.instances.push(name);
+        }
+        Ok(impact)
+    }
+
+    pub fn of_instance(server_name: &String, region: &Region, ctx: &mut ContextManager) -> Self {
+        //! Impact of taking down a single instance.
+        //! An instance without a status entry isn't live, so it has no impact.
+        MinecraftServer::get_status(server_name, region, ctx)
+            .map(|server| Self::from_servers(&[server]))
+            .unwrap_or_default()
+    }
+
+    pub fn has_live_instances(&self) -> bool {
+        !self.instances.is_empty()
+    }
+
+    pub fn has_players(&self) -> bool {
+        self.players_online > 0
+    }
+}
+
+pub fn confirm(operation: &str, impact: &Impact) -> bool {
+    //! Asks the operator on stdin whether `operation` should go ahead.
+    //! Anything other than `y`/`yes` is treated as a refusal, and so is a stdin that isn't
+    //! a terminal: scripts must pass force.
+    if !io::stdin().is_terminal() {
+        return false;
+    }
+    print!("{} will affect {}. Continue? [y/N] ", operation, impact);
+    if io::stdout().flush().is_err() {
+        return false;
+    }
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+pub fn confirm_or_force<T>(
+    force: bool,
+    mut op: impl FnMut(bool) -> Result<T, SafetyError>,
+) -> Result<T, SafetyError> {
+    //! Runs a guarded operation, prompting the operator if it is refused for lack of `force`.
+    //! A confirmed prompt retries the operation with force.
+    match op(force) {
+        Err(SafetyError::RequiresForce(operation, impact)) => {
+            if confirm(&operation, &impact) {
+                op(true)
+            } else {
+                Err(SafetyError::RequiresForce(operation, impact))
+            }
+        }
+        res => res,
+    }
+}
@@ -0,0 +1,139 @@
+use std::time::Duration;
+
+use crate::{context_manager::ContextManager, monitor::shutdown};
+
+use super::{
+    commands::{BroadcastStyle, ServerCommand},
+    minecraft::{MinecraftServer, MinecraftServerError},
+    server_group::ServerGroup,
+};
+
+/// Warnings a countdown gives by default, as time left before it ends.
+pub const DEFAULT_WARNINGS: [u64; 9] = [600, 300, 120, 60, 30, 10, 3, 2, 1];
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BroadcastTarget {
+    Network,
+    Group(Box<ServerGroup>),
+}
+
+/// A message sent to players through the plugins' command channel.
+/// Templates may use `{server}`, `{group}` and `{remaining}` (time left in a countdown).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Broadcast {
+    pub target: BroadcastTarget,
+    pub template: String,
+    pub style: BroadcastStyle,
+}
+
+pub fn format_remaining(remaining: Duration) -> String {
+    let secs = remaining.as_secs();
+    let (amount, unit) = match secs {
+        0..=59 => (secs, "second"),
+        60..=3599 => (secs / 60, "minute"),
+        _ => (secs / 3600, "hour"),
+    };
+    format!("{} {}{}", amount, unit, if amount == 1 { "" } else { "s" })
+}
+
+impl Broadcast {
+    pub fn new(target: BroadcastTarget, template: &str, style: BroadcastStyle) -> Self {
+        Self {
+            target,
+            template: template.into(),
+            style,
+        }
+    }
+
+    pub fn render(&self, server: Option<&str>, remaining: Option<Duration>) -> String {
+        let group = match &self.target {
+            BroadcastTarget::Network => "network",
+            BroadcastTarget::Group(group) => &group.prefix,
+        };
+        self.template
+            .replace("{server}", server.unwrap_or(group))
+            .replace("{group}", group)
+            .replace(
+                "{remaining}",
+                &remaining.map(format_remaining).unwrap_or_default(),
+            )
+    }
+
+    pub fn send(
+        &self,
+        remaining: Option<Duration>,
+        ctx: &mut ContextManager,
+    ) -> Result<usize, MinecraftServerError> {
+        //! Publishes the message once for the whole network, or once per live instance of the
+        //! group. Returns how many subscribers received it.
+        let targets: Vec<Option<String>> = match &self.target {
+            BroadcastTarget::Network => vec![None],
+            BroadcastTarget::Group(group) => MinecraftServer::from_server_group(group, ctx)
+                .ok
+                .iter()
+                .map(|server| Some(server.get_name().clone()))
+                .collect(),
+        };
+        let mut received = 0;
+        for target_server in targets {
+            received += ServerCommand::Broadcast {
+                message: self.render(target_server.as_deref(), remaining),
+                target_server,
+                style: self.style,
+            }
+            .publish(ctx)?;
+        }
+        Ok(received)
+    }
+}
+
+/// A sequence of broadcasts counting down to a deadline ("Restarting in 5 minutes", ...).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Countdown {
+    pub warnings: Vec<Duration>, // time left at each warning
+}
+
+impl Default for Countdown {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_WARNINGS
+                .iter()
+                .map(|&secs| Duration::from_secs(secs)),
+        )
+    }
+}
+
+impl Countdown {
+    pub fn new(warnings: impl IntoIterator<Item = Duration>) -> Self {
+        let mut warnings: Vec<Duration> = warnings.into_iter().collect();
+        warnings.sort_by(|a, b| b.cmp(a));
+        warnings.dedup();
+        Self { warnings }
+    }
+
+    pub fn run(
+        &self,
+        broadcast: &Broadcast,
+        from: Duration,
+        until: Duration,
+        is_cancelled: &mut dyn FnMut(&mut ContextManager) -> bool,
+        ctx: &mut ContextManager,
+    ) -> Result<bool, MinecraftServerError> {
+        //! Counts down from `from` to `until` time left, broadcasting at every warning in
+        //! between (callers announce the start themselves). Returns `false` if the countdown
+        //! was cancelled or a shutdown request cut it short.
+        let mut remaining = from;
+        let warnings = self
+            .warnings
+            .iter()
+            .filter(|&&warning| warning < from && warning >= until);
+        for &warning in warnings {
+            if !shutdown::sleep_unless_shutdown(remaining - warning) || is_cancelled(ctx) {
+                return Ok(false);
+            }
+            remaining = warning;
+            broadcast.send(Some(remaining), ctx)?;
+        }
+        Ok(shutdown::sleep_unless_shutdown(remaining.saturating_sub(until)) && !is_cancelled(ctx))
+    }
+}
@@ -0,0 +1,97 @@
+use serde_json::json;
+use strum_macros::{Display, EnumString};
+
+use crate::context_manager::ContextManager;
+
+use super::minecraft::{GameDisplayStatus, GameJoinStatus, MinecraftServerError};
+
+/// Channel prefix the Arcade/Hub plugins subscribe to.
+/// Each command type is published on `commands.server:<CommandType>`.
+pub const COMMAND_CHANNEL: &str = "commands.server";
+
+/// Where a broadcast shows up for players.
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, Default, Display, EnumString, Eq, PartialEq)]
+#[strum(ascii_case_insensitive)]
+pub enum BroadcastStyle {
+    #[default]
+    CHAT,
+    ACTION_BAR,
+}
+
+/// Commands understood by the server plugins' command manager.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ServerCommand {
+    JoinStatus {
+        target_server: String,
+        join_status: GameJoinStatus,
+    },
+    DisplayStatus {
+        target_server: String,
+        display_status: GameDisplayStatus,
+    },
+    Broadcast {
+        target_server: Option<String>, // every server when None
+        message: String,
+        style: BroadcastStyle,
+    },
+}
+
+impl ServerCommand {
+    pub fn get_command_type(&self) -> &'static str {
+        match self {
+            ServerCommand::JoinStatus { .. } => "JoinStatusCommand",
+            ServerCommand::DisplayStatus { .. } => "DisplayStatusCommand",
+            ServerCommand::Broadcast { .. } => "BroadcastCommand",
+        }
+    }
+
+    pub fn get_channel(&self) -> String {
+        format!("{}:{}", COMMAND_CHANNEL, self.get_command_type())
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        //! Serializes the command the way the plugins' (Gson) command classes expect it.
+        match self {
+            ServerCommand::JoinStatus {
+                target_server,
+                join_status,
+            } => json!({
+                "_targetServer": target_server,
+                "_joinStatus": join_status.to_string(),
+            }),
+            ServerCommand::DisplayStatus {
+                target_server,
+                display_status,
+            } => json!({
+                "_targetServer": target_server,
+                "_displayStatus": display_status.to_string(),
+            }),
+            ServerCommand::Broadcast {
+                target_server,
+                message,
+                style,
+            } => json!({
+                "_targetServer": target_server,
+                "_message": message,
+                "_style": style.to_string(),
+            }),
+        }
+    }
+
+    pub fn publish(&self, ctx: &mut ContextManager) -> Result<usize, MinecraftServerError> {
+        //! Publishes the command, returning how many subscribers received it.
+        redis::cmd("PUBLISH")
+            .arg(self.get_channel())
+            .arg(self.to_json().to_string())
+            .query(ctx.get_connection())
+            .map_err(|err| {
+                format!(
+                    "{} could not be published: {:?}",
+                    self.get_command_type(),
+                    err
+                )
+                .into()
+            })
+    }
+}
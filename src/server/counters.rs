@@ -0,0 +1,137 @@
+use std::{collections::HashMap, fmt::Display};
+
+use redis::{ConnectionLike, RedisResult};
+
+use crate::context_manager::ContextManager;
+
+use super::{minecraft::MinecraftServer, server_group::ServerGroup};
+
+pub const TOTAL_FIELD: &str = "totalServers";
+pub const JOINABLE_FIELD: &str = "joinableServers";
+
+/// Live instance counts of a group, as opposed to the desired counts in its group hash.
+/// Launches and deaths adjust them with HINCRBY, so managers counting at the same time
+/// never overwrite each other; the monitor resets them from the statuses every pass.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct GroupCounts {
+    pub total: i64,
+    pub joinable: i64,
+}
+
+impl Display for GroupCounts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} running, {} joinable", self.total, self.joinable)
+    }
+}
+
+impl GroupCounts {
+    pub fn of(group: &ServerGroup, statuses: &[MinecraftServer]) -> Self {
+        //! Counts `group`'s instances among `statuses`.
+        let instances = statuses
+            .iter()
+            .filter(|server| server.get_group() == &group.prefix);
+        let (total, joinable) = instances.fold((0, 0), |(total, joinable), server| {
+            (total + 1, joinable + server.is_joinable() as i64)
+        });
+        Self { total, joinable }
+    }
+}
+
+fn adjust(
+    group: &str,
+    total: i64,
+    joinable: i64,
+    ctx: &mut ContextManager,
+) -> RedisResult<GroupCounts> {
+    //! Both HINCRBYs go in one MULTI/EXEC, so a manager dying between them can't leave only
+    //! one counted. Clusters send them as a plain pipeline and snapshots one at a time.
+    let key = ctx.get_keys().counts_key(group);
+    let count = |field: &str, by: i64| {
+        let mut cmd = redis::cmd("HINCRBY");
+        cmd.arg(&key).arg(field).arg(by);
+        cmd
+    };
+    let (total, joinable) = (count(TOTAL_FIELD, total), count(JOINABLE_FIELD, joinable));
+    let conn = ctx.get_connection();
+    if !conn.supports_pipelining() {
+        return Ok(GroupCounts {
+            total: total.query(conn)?,
+            joinable: joinable.query(conn)?,
+        });
+    }
+    let mut pipe = redis::pipe();
+    if conn.supports_transactions() {
+        pipe.atomic();
+    }
+    let (total, joinable) = pipe.add_command(total).add_command(joinable).query(conn)?;
+    Ok(GroupCounts { total, joinable })
+}
+
+pub fn increment(group: &str, ctx: &mut ContextManager) -> RedisResult<GroupCounts> {
+    //! Counts a launched instance, which is joinable until players fill it.
+    adjust(group, 1, 1, ctx)
+}
+
+pub fn decrement(
+    group: &str,
+    was_joinable: bool,
+    ctx: &mut ContextManager,
+) -> RedisResult<GroupCounts> {
+    //! Uncounts an instance that died or was killed.
+    adjust(group, -1, -(was_joinable as i64), ctx)
+}
+
+fn read(key: &str, conn: &mut impl ConnectionLike) -> RedisResult<GroupCounts> {
+    let counts: HashMap<String, i64> = redis::cmd("HGETALL").arg(key).query(conn)?;
+    Ok(GroupCounts {
+        total: counts.get(TOTAL_FIELD).copied().unwrap_or_default(),
+        joinable: counts.get(JOINABLE_FIELD).copied().unwrap_or_default(),
+    })
+}
+
+pub fn get(group: &str, ctx: &mut ContextManager) -> RedisResult<GroupCounts> {
+    let key = ctx.get_keys().counts_key(group);
+    read(&key, ctx.get_connection())
+}
+
+pub fn reconcile(
+    group: &str,
+    counts: GroupCounts,
+    ctx: &mut ContextManager,
+) -> RedisResult<Option<GroupCounts>> {
+    //! Overwrites the counts with absolute ones (counted from statuses), returning the
+    //! drifted counts they replaced, if any. The key is WATCHed while it is compared, so
+    //! a launch or death counted meanwhile makes it compare again instead of being lost.
+    //! Connections without transactions (clusters, snapshots) compare and set unwatched.
+    let key = ctx.get_keys().counts_key(group);
+    let conn = ctx.get_connection();
+    let mut overwrite = redis::cmd("HSET");
+    overwrite
+        .arg(&key)
+        .arg(TOTAL_FIELD)
+        .arg(counts.total)
+        .arg(JOINABLE_FIELD)
+        .arg(counts.joinable);
+    if !conn.supports_transactions() {
+        let current = read(&key, conn)?;
+        if current == counts {
+            return Ok(None);
+        }
+        overwrite.query::<()>(conn)?;
+        return Ok(Some(current));
+    }
+    redis::transaction(conn, &[&key], |conn, pipe| {
+        let current = read(&key, conn)?;
+        if current == counts {
+            return Ok(Some(None));
+        }
+        let applied: Option<()> = pipe.add_command(overwrite.clone()).ignore().query(conn)?;
+        Ok(applied.map(|()| Some(current)))
+    })
+}
+
+pub fn clear(group: &str, ctx: &mut ContextManager) -> RedisResult<()> {
+    //! Drops the counts of a deleted group.
+    let key = ctx.get_keys().counts_key(group);
+    redis::cmd("DEL").arg(key).query(ctx.get_connection())
+}
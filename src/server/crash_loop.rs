@@ -0,0 +1,122 @@
+use std::fmt::Display;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    context_manager::ContextManager,
+    events::{Event, EventKind},
+};
+
+/// Hash of group -> JSON `CrashLoop`, for groups whose recent launches failed.
+const CRASH_LOOPS_KEY: &str = "manager.crashloops";
+
+/// When launches of a group are paused because they keep failing.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct CrashLoopInfo {
+    pub max_failures: u32, // consecutive failed launches before the group is paused
+    pub cooldown_secs: u64,
+}
+
+/// Consecutive failed launches of a group.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct CrashLoop {
+    pub group: String,
+    pub failures: u32,
+    pub last_error: String,
+    pub paused_until: Option<i64>, // seconds since epoch
+}
+
+impl Default for CrashLoopInfo {
+    fn default() -> Self {
+        Self {
+            max_failures: 3,
+            cooldown_secs: 300,
+        }
+    }
+}
+
+impl Display for CrashLoop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} failed launch(es), last: {}",
+            self.failures, self.last_error
+        )
+    }
+}
+
+impl CrashLoop {
+    pub fn is_paused(&self) -> bool {
+        self.paused_until
+            .is_some_and(|until| until > Local::now().timestamp())
+    }
+
+    pub fn get(group: &str, ctx: &mut ContextManager) -> Result<Option<Self>, redis::RedisError> {
+        let json: Option<String> = redis::cmd("HGET")
+            .arg(CRASH_LOOPS_KEY)
+            .arg(group)
+            .query(ctx.get_connection())?;
+        Ok(json.and_then(|json| serde_json::from_str(&json).ok()))
+    }
+
+    pub fn get_all(ctx: &mut ContextManager) -> Result<Vec<Self>, redis::RedisError> {
+        let entries: Vec<String> = redis::cmd("HVALS")
+            .arg(CRASH_LOOPS_KEY)
+            .query(ctx.get_connection())?;
+        Ok(entries
+            .iter()
+            .filter_map(|entry| serde_json::from_str(entry).ok())
+            .collect())
+    }
+
+    pub fn record_failure(
+        group: &str,
+        error: &str,
+        ctx: &mut ContextManager,
+    ) -> Result<Self, redis::RedisError> {
+        //! Counts a failed launch. Reaching `max_failures` pauses the group's launches
+        //! for the cooldown; the count starts over once a launch succeeds or it is cleared.
+        let info = ctx.get_config().crash_loop.clone();
+        let mut crash_loop = Self::get(group, ctx)?.unwrap_or(Self {
+            group: group.into(),
+            ..Default::default()
+        });
+        crash_loop.failures += 1;
+        crash_loop.last_error = error.into();
+        if crash_loop.failures >= info.max_failures && !crash_loop.is_paused() {
+            crash_loop.paused_until = Some(Local::now().timestamp() + info.cooldown_secs as i64);
+            let message = format!(
+                "launches paused for {}s after {}",
+                info.cooldown_secs, crash_loop
+            );
+            Event::new(EventKind::CrashLoop, group, message).emit(ctx);
+        }
+        crash_loop.save(ctx)?;
+        Ok(crash_loop)
+    }
+
+    pub fn clear(group: &str, ctx: &mut ContextManager) -> Result<bool, redis::RedisError> {
+        //! Forgets a group's failures, resuming its launches. False if it had none.
+        let removed: usize = redis::cmd("HDEL")
+            .arg(CRASH_LOOPS_KEY)
+            .arg(group)
+            .query(ctx.get_connection())?;
+        Ok(removed > 0)
+    }
+
+    fn save(&self, ctx: &mut ContextManager) -> Result<(), redis::RedisError> {
+        let json = serde_json::to_string(self).map_err(|err| {
+            redis::RedisError::from((
+                redis::ErrorKind::ClientError,
+                "Crash loop serialization error",
+                err.to_string(),
+            ))
+        })?;
+        redis::cmd("HSET")
+            .arg(CRASH_LOOPS_KEY)
+            .arg(&self.group)
+            .arg(json)
+            .query(ctx.get_connection())
+    }
+}
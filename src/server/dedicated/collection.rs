@@ -1,12 +1,15 @@
-use std::usize;
-
 use serde::{Deserialize, Serialize};
 
 use crate::server::{minecraft::MinecraftServer, server_group::ServerGroup};
 
-use super::server::DedicatedServer;
+use crate::{
+    context_manager::ContextManager,
+    strategy::{PlacementStrategy, SpreadPlacement},
+};
+
+use super::server::{DedicatedServer, DedicatedServerError};
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct DedicatedServers {
     pub servers: Vec<DedicatedServer>,
 }
@@ -16,24 +19,81 @@ impl DedicatedServers {
         &mut self,
         group: &ServerGroup,
     ) -> Option<&mut DedicatedServer> {
-        //! Gets server with highest resources which can fulfill a servergroup's resource requirement.
-        //! Gets best server with highest resources and lowest server count for the specific group.
-        self.sort_servers();
+        //! Places with the built-in strategy: fewest instances of the group, then most resources.
+        self.place_with(group, &SpreadPlacement).ok()
+    }
+
+    pub fn place_with(
+        &mut self,
+        group: &ServerGroup,
+        strategy: &dyn PlacementStrategy,
+    ) -> Result<&mut DedicatedServer, DedicatedServerError> {
+        //! Lets `strategy` pick among the nodes in the group's region with room for another instance.
+        //! Groups pinned to a `host` always go there, or fail if it has no room.
+        if let Some(host) = group.host.as_ref() {
+            let node = self
+                .get_server_mut(host)
+                .ok_or(DedicatedServerError::NodeNotFound(host.clone()))?;
+            node.check_placement(group, None)?;
+            return Ok(node);
+        }
+        let candidates: Vec<&DedicatedServer> = self
+            .servers
+            .iter()
+            .filter(|ds| ds.region == group.region && ds.has_space_for(group))
+            .collect();
+        let name = strategy
+            .choose_node(group, &candidates)
+            .and_then(|choice| candidates.get(choice))
+            .map(|ds| ds.name.clone())
+            .ok_or(DedicatedServerError::StorageError(format!(
+                "No {} dedicated server has space for {:?}",
+                group.region, group.name
+            )))?;
+        self.get_server_mut(&name)
+            .ok_or(DedicatedServerError::NodeNotFound(name))
+    }
+
+    pub fn get_server(&self, name: &String) -> Option<&DedicatedServer> {
+        self.servers.iter().find(|ds| &ds.name == name)
+    }
 
-        let mut best_server: Option<&mut DedicatedServer> = None;
+    pub fn get_server_mut(&mut self, name: &String) -> Option<&mut DedicatedServer> {
+        self.servers.iter_mut().find(|ds| &ds.name == name)
+    }
+
+    pub fn carry_over_instances(&mut self, previous: &DedicatedServers) {
+        //! Copies instance bookkeeping from a previous copy of the nodes (e.g. before a config reload).
+        //! Resources in use on a node stay in use, even if its configured maximums changed.
         for ds in self.servers.iter_mut() {
-            if ds.region != group.region || !ds.has_space_for(group) {
+            let Some(prev) = previous.get_server(&ds.name) else {
                 continue;
+            };
+            ds.server_instances = prev.server_instances.clone();
+            ds.available_ram = ds.max_ram - (prev.max_ram - prev.available_ram);
+            ds.available_cpu = ds.max_cpu - (prev.max_cpu - prev.available_cpu);
+        }
+    }
+
+    pub fn relaunch_group(
+        &mut self,
+        old: &ServerGroup,
+        new: &ServerGroup,
+        force: bool,
+        ctx: &mut ContextManager,
+    ) -> Result<Vec<String>, DedicatedServerError> {
+        //! Restarts every instance of `old` as an instance of `new` with the same server number.
+        //! Each instance is drained first; without `force`, one that doesn't empty out in time
+        //! stops the relaunch, so running it again picks up where it stopped.
+        let mut relaunched: Vec<String> = Vec::new();
+        for ds in self.servers.iter_mut() {
+            for server_num in ds.get_server_nums(old) {
+                ds.drain_and_kill(old, server_num, force, ctx)?;
+                ds.start_server(new, server_num, ctx)?;
+                relaunched.push(format!("{}-{}", new.name, server_num));
             }
-            if let Some(best) = best_server.as_ref() {
-                // it isn't the best if it doesn't have a lower server count
-                if best.get_server_count(group) < ds.get_server_count(group) {
-                    continue;
-                }
-            }
-            best_server = Some(ds);
         }
-        best_server
+        Ok(relaunched)
     }
 
     pub fn get_running_servers(&mut self) -> Vec<MinecraftServer> {
@@ -64,9 +124,4 @@ impl DedicatedServers {
     pub fn get_next(&mut self) -> Option<DedicatedServer> {
         self.servers.clone().into_iter().next()
     }
-
-    fn sort_servers(&mut self) -> () {
-        //! Sorts DedicatedServers by highest resource first (ram more important, then cpu)
-        self.servers.sort();
-    }
 }
@@ -0,0 +1,108 @@
+use std::fmt::Display;
+
+use crate::{
+    context_manager::ContextManager,
+    events::{Event, EventKind},
+    region::Region,
+    server::server_group::ServerGroup,
+};
+
+use super::{
+    collection::DedicatedServers,
+    server::{DedicatedServer, DedicatedServerError},
+    telemetry::{NodeTelemetry, TelemetryInfo},
+};
+
+/// A node's free disk as its agent last reported it, counted down as instances are placed,
+/// since worlds and logs fill disks and a full disk corrupts worlds.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DiskSpace {
+    pub free_mb: i64,
+    pub min_free_mb: i64,     // never placed below
+    pub per_instance_mb: i64, // expected world and log size of a new instance
+}
+
+impl Display for DiskSpace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}MB disk free (min {}MB)",
+            self.free_mb, self.min_free_mb
+        )
+    }
+}
+
+impl DiskSpace {
+    pub fn from_telemetry(telemetry: &NodeTelemetry, info: &TelemetryInfo) -> Self {
+        Self {
+            free_mb: telemetry.disk_free,
+            min_free_mb: info.min_disk_free_mb,
+            per_instance_mb: info.disk_per_instance_mb,
+        }
+    }
+
+    pub fn is_low(&self) -> bool {
+        self.free_mb < self.min_free_mb
+    }
+
+    pub fn has_room(&self) -> bool {
+        self.free_mb - self.per_instance_mb >= self.min_free_mb
+    }
+}
+
+impl DedicatedServer {
+    pub fn check_disk(&self, group: &ServerGroup) -> Result<(), DedicatedServerError> {
+        //! Refuses another instance if it would leave the node under its minimum free disk.
+        //! Nodes without (fresh) telemetry aren't checked.
+        match self.disk {
+            Some(disk) if !disk.has_room() => Err(DedicatedServerError::DiskFull(format!(
+                "Dedicated Server ({:?}) has {}, {:?} needs {}MB more",
+                self.name, disk, group.name, disk.per_instance_mb
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn get_telemetry_warning(&self) -> Option<String> {
+        //! Why telemetry keeps every new instance off the node, if it does.
+        self.overloaded.clone().or_else(|| {
+            self.disk
+                .filter(|disk| !disk.has_room())
+                .map(|disk| disk.to_string())
+        })
+    }
+
+    pub fn is_disk_low(&self) -> bool {
+        self.disk.is_some_and(|disk| disk.is_low())
+    }
+}
+
+impl DedicatedServers {
+    pub fn report_disks(
+        &self,
+        previous: &DedicatedServers,
+        region: Option<&Region>,
+        ctx: &mut ContextManager,
+    ) {
+        //! Records an event for each node (in `region`) whose disk went under (or back over)
+        //! its minimum since `previous`, the same nodes before the latest telemetry was merged.
+        for ds in self
+            .servers
+            .iter()
+            .filter(|ds| region.is_none_or(|region| &ds.region == region))
+        {
+            let was_low = previous
+                .get_server(&ds.name)
+                .is_some_and(|prev| prev.is_disk_low());
+            match (was_low, ds.disk) {
+                (false, Some(disk)) if disk.is_low() => {
+                    Event::new(EventKind::DiskLow, &ds.name, disk.to_string()).emit(ctx)
+                }
+                (true, Some(disk)) if !disk.is_low() => {
+                    Event::new(EventKind::DiskRecovered, &ds.name, disk.to_string()).emit(ctx)
+                }
+                _ => {}
+            }
+        }
+    }
+}
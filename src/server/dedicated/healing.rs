@@ -0,0 +1,263 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    batch::{BatchOutcome, BatchReport},
+    context_manager::ContextManager,
+    events::{Event, EventKind},
+    journal::JournalEntry,
+    region::Region,
+    server::{counters, minecraft::MinecraftServer, server_group::ServerGroup},
+    strategy::ScalingDecision,
+};
+
+use super::{
+    collection::DedicatedServers,
+    instance::MCSInstance,
+    launcher::{self, Launch},
+    recovery::scan_live,
+    server::DedicatedServer,
+};
+
+/// Makes the monitor act on what it finds instead of only reporting it: instances whose
+/// status stopped being refreshed are removed from their node, and groups short of their
+/// desired count get replacements launched. Off by default, leaving both to `kill` and
+/// `launch`.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct HealingInfo {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_dead_after")]
+    pub dead_after_secs: i64, // status not refreshed this long
+    #[serde(default = "default_max_launches")]
+    pub max_launches: usize, // replacements each region worker launches per reconcile
+}
+
+fn default_dead_after() -> i64 {
+    60
+}
+
+fn default_max_launches() -> usize {
+    8
+}
+
+impl Default for HealingInfo {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dead_after_secs: default_dead_after(),
+            max_launches: default_max_launches(),
+        }
+    }
+}
+
+/// What one pass over a region's statuses removed.
+#[derive(Debug, Default)]
+pub struct DeadReport {
+    pub removed: Vec<(String, Option<String>)>, // (server, node that tracked it)
+    pub failed: Vec<(String, String)>,          // (server, why it couldn't be removed)
+}
+
+impl Display for DeadReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} dead instance(s) removed, {} failed",
+            self.removed.len(),
+            self.failed.len()
+        )
+    }
+}
+
+impl DedicatedServer {
+    pub fn remove_dead(
+        &mut self,
+        name: &str,
+        status: Option<&MinecraftServer>,
+        ctx: &mut ContextManager,
+    ) -> Result<(), String> {
+        //! Drops an instance that stopped reporting, `status` being its last status (None
+        //! once that expired): its process is killed in case it hangs rather than crashed,
+        //! then its status, count and slot on the node are released. Unlike `kill_server` a
+        //! failing kill script doesn't stop it, the process is usually gone already. An
+        //! expired status is left uncounted already, the monitor counts from the statuses.
+        let name = name.to_string();
+        let group = match status {
+            Some(server) => server.get_group().clone(),
+            None => self
+                .get_instance(&name)
+                .map(|instance| instance.get_group().clone())
+                .ok_or(format!("{} is not tracked on {}", name, self.name))?,
+        };
+        let group = ServerGroup::from_str(&group, ctx)
+            .map_err(|err| format!("group {:?}: {}", group, err))?;
+        let server_num = MCSInstance::calculate_server_num(&name);
+        if let Err(err) = self.run_kill_script(&name, ctx) {
+            log::warn!("{} was not killed, likely gone already: {}", name, err);
+        }
+        if let Some(server) = status {
+            MinecraftServer::delete_status(&name, &self.region, ctx)
+                .map_err(|err| err.to_string())?;
+            if let Err(err) = counters::decrement(&group.prefix, server.is_joinable(), ctx) {
+                log::warn!("{} was not uncounted: {}", name, err);
+            }
+        }
+        if self.get_server_nums(&group).contains(&server_num) {
+            let outcome = self
+                .remove_server(&group, server_num)
+                .map_err(|err| err.to_string())?;
+            Event::from(outcome.clone()).emit(ctx);
+            for hooks in ctx.get_hooks() {
+                hooks.on_kill(&outcome);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DedicatedServers {
+    pub fn remove_dead(
+        &mut self,
+        region: Option<&Region>,
+        after_secs: i64,
+        ctx: &mut ContextManager,
+    ) -> DeadReport {
+        //! Removes servers of the region (or all) whose status wasn't refreshed for
+        //! `after_secs`, from the node tracking them if any, and the tracked instances
+        //! whose status has been gone that long. Each one is recorded as an InstanceDead
+        //! event.
+        let now = ctx.get_clock().now();
+        let (live, unreadable) = scan_live(region, ctx);
+        let mut report = DeadReport::default();
+        let mut reported: Vec<String> = unreadable
+            .iter()
+            .filter_map(|key| ctx.get_keys().parse_status_key(key))
+            .map(|(_, name)| name)
+            .collect();
+        for (region, server) in live {
+            let name = server.get_name().clone();
+            reported.push(name.clone());
+            let silent_secs = server.get_silent_secs(now);
+            if silent_secs < after_secs {
+                continue;
+            }
+            let message = format!("status not refreshed for {}s, removing it", silent_secs);
+            Event::new(EventKind::InstanceDead, &name, message).emit(ctx);
+            let node = self
+                .servers
+                .iter_mut()
+                .find(|ds| ds.get_instance(&name).is_some());
+            let result = match node {
+                Some(ds) => ds
+                    .remove_dead(&name, Some(&server), ctx)
+                    .map(|_| Some(ds.name.clone())),
+                None => MinecraftServer::delete_status(&name, &region, ctx)
+                    .map(|_| None)
+                    .map_err(|err| err.to_string()),
+            };
+            match result {
+                Ok(node) => report.removed.push((name, node)),
+                Err(err) => report.failed.push((name, err)),
+            }
+        }
+        let pattern = ctx
+            .get_keys()
+            .status_pattern(region.map(|region| region.to_string()).as_deref(), None);
+        if unreadable.contains(&pattern) {
+            // the scan failed, so a missing status doesn't mean the instance is gone
+            return report;
+        }
+        for ds in self.servers.iter_mut() {
+            for name in ds.mark_missing(region, &reported, now.timestamp(), after_secs) {
+                let message = format!("status gone for over {}s, removing it", after_secs);
+                Event::new(EventKind::InstanceDead, &name, message).emit(ctx);
+                match ds.remove_dead(&name, None, ctx) {
+                    Ok(()) => report.removed.push((name, Some(ds.name.clone()))),
+                    Err(err) => report.failed.push((name, err)),
+                }
+            }
+        }
+        report
+    }
+}
+
+impl DedicatedServer {
+    fn mark_missing(
+        &mut self,
+        region: Option<&Region>,
+        reported: &[String],
+        now: i64,
+        after_secs: i64,
+    ) -> Vec<String> {
+        //! Notes when the node's instances (of the region, or all) were first found without
+        //! a status, clearing it for those `reported` again. Returns the ones missing theirs
+        //! for `after_secs` by `now` (seconds since epoch).
+        let instances = self
+            .server_instances
+            .values_mut()
+            .flatten()
+            .filter(|instance| region.is_none_or(|region| instance.get_region() == region));
+        let mut dead = Vec::new();
+        for instance in instances {
+            if reported.contains(instance.get_name()) {
+                instance.set_missing_since(None);
+                continue;
+            }
+            let since = instance.get_missing_since().unwrap_or(now);
+            instance.set_missing_since(Some(since));
+            if now - since >= after_secs {
+                dead.push(instance.get_name().clone());
+            }
+        }
+        dead
+    }
+}
+
+pub fn launch_replacements(
+    decisions: &[ScalingDecision],
+    max_launches: usize,
+    progress: impl Fn(&BatchOutcome<Launch>) + Sync,
+    ctx: &mut ContextManager,
+) -> (Vec<(String, usize)>, BatchReport<Launch>) {
+    //! Launches the instances `decisions` are short of, at most `max_launches` of them,
+    //! placed and started like `launch` does with the [launch] concurrency. Returns the
+    //! groups no node had room for along with the launches. Priority groups are served
+    //! first when there are more missing than that. Failed launches are rolled back right
+    //! away through the journal, so they don't hold their slot on the node.
+    let info = ctx.get_config().launch.clone();
+    let mut missing = launcher::get_missing(decisions, ctx);
+    missing.sort_by_key(|(group, _)| info.get_rank(group));
+    let mut budget = max_launches;
+    let missing: Vec<(ServerGroup, usize)> = missing
+        .into_iter()
+        .filter_map(|(group, count)| {
+            let count = count.min(budget);
+            budget -= count;
+            (count > 0).then_some((group, count))
+        })
+        .collect();
+    let plan = launcher::plan(&missing, ctx);
+    let unplaced = plan.unplaced.clone();
+    let report = launcher::run(
+        plan,
+        info.concurrency,
+        info.per_node,
+        |_, _, outcome| progress(outcome),
+        ctx,
+    );
+    for outcome in report
+        .outcomes
+        .iter()
+        .filter(|outcome| outcome.result.is_err())
+    {
+        let instance = outcome.target.get_name();
+        match JournalEntry::replay_instance(&instance, ctx) {
+            Ok(replayed) => replayed
+                .iter()
+                .for_each(|replayed| log::info!("{}: {}", instance, replayed)),
+            Err(err) => log::warn!("{} could not be rolled back: {:?}", instance, err),
+        }
+    }
+    (unplaced, report)
+}
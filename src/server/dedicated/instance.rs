@@ -4,6 +4,8 @@ use crate::{
     server::minecraft::{MinecraftServer, ServerStatus},
 };
 
+use super::labels::Labels;
+
 /// Intermediate between ServerStatus cache
 /// And DedicatedServer
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -14,6 +16,9 @@ pub struct MCSInstance {
     port: u16,
     region: Region,
     server: Option<MinecraftServer>,
+    labels: Labels, // copy of the instance's labels in redis, see `labels::LabelTarget`
+    pid: Option<u32>, // its process on the node, once the start script reported it
+    missing_since: Option<i64>, // seconds since epoch, since the monitor found no status for it
 }
 
 impl MCSInstance {
@@ -31,27 +36,70 @@ impl MCSInstance {
             port,
             region,
             server,
+            labels: Labels::new(),
+            pid: None,
+            missing_since: None,
         }
     }
 
-    fn calculate_server_num(name: &String) -> usize {
+    pub fn calculate_server_num(name: &str) -> usize {
         name.split_once('-')
-            .unwrap_or((name.as_str(), "0"))
+            .unwrap_or((name, "0"))
             .1
             .parse::<usize>()
             .ok()
             .unwrap_or(0)
     }
 
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_group(&self) -> &String {
+        &self.group
+    }
+
     pub fn get_server_num(&self) -> usize {
         self.server_num
     }
 
+    pub fn get_port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn get_region(&self) -> &Region {
+        &self.region
+    }
+
+    pub fn get_labels(&self) -> &Labels {
+        &self.labels
+    }
+
+    pub fn set_labels(&mut self, labels: Labels) {
+        self.labels = labels;
+    }
+
+    pub fn get_pid(&self) -> Option<u32> {
+        self.pid
+    }
+
+    pub fn set_pid(&mut self, pid: Option<u32>) {
+        self.pid = pid;
+    }
+
+    pub fn get_missing_since(&self) -> Option<i64> {
+        self.missing_since
+    }
+
+    pub fn set_missing_since(&mut self, missing_since: Option<i64>) {
+        self.missing_since = missing_since;
+    }
+
     pub fn get_status(&mut self, ctx: &mut ContextManager) -> ServerStatus {
         if let Some(sv) = self.server.as_mut() {
             return sv.update(ctx);
         }
-        match MinecraftServer::get(&self.name, &self.region, ctx) {
+        match MinecraftServer::get_status(&self.name, &self.region, ctx) {
             Ok(mut server) => {
                 let status = server.update(ctx);
                 self.server = Some(server);
@@ -61,7 +109,7 @@ impl MCSInstance {
         }
     }
 
-    pub fn get_mcs(&mut self, ctx: &mut ContextManager) -> Option<&mut MinecraftServer> {
+    pub fn get_mcs(&mut self, _ctx: &mut ContextManager) -> Option<&mut MinecraftServer> {
         self.server.as_mut()
     }
 }
@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+
+use crate::context_manager::ContextManager;
+
+pub type Labels = BTreeMap<String, String>;
+
+/// What a set of labels is attached to. Each kind is one hash of name -> JSON labels
+/// (e.g. `{"tier": "high-mem"}`), kept beside the config-defined nodes and in-memory instances.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LabelTarget {
+    Node,
+    Instance, // keyed by instance name, so labels survive restarts and relocations
+}
+
+impl LabelTarget {
+    pub fn get_key(&self) -> &'static str {
+        match self {
+            LabelTarget::Node => "servermonitor.nodelabels",
+            LabelTarget::Instance => "servermonitor.instancelabels",
+        }
+    }
+}
+
+pub fn get_labels(
+    target: LabelTarget,
+    name: &str,
+    ctx: &mut ContextManager,
+) -> Result<Labels, redis::RedisError> {
+    //! A node's or instance's labels; ones that were never labelled have none.
+    let raw: Option<String> = redis::cmd("HGET")
+        .arg(target.get_key())
+        .arg(name)
+        .query(ctx.get_connection())?;
+    Ok(raw
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default())
+}
+
+pub fn get_all_labels(
+    target: LabelTarget,
+    ctx: &mut ContextManager,
+) -> Result<BTreeMap<String, Labels>, redis::RedisError> {
+    //! Labels of every labelled node or instance. Unparsable entries are skipped.
+    let raw: BTreeMap<String, String> = redis::cmd("HGETALL")
+        .arg(target.get_key())
+        .query(ctx.get_connection())?;
+    Ok(parse_all_labels(raw))
+}
+
+pub fn parse_all_labels(raw: BTreeMap<String, String>) -> BTreeMap<String, Labels> {
+    //! Labels from the raw hash of a `LabelTarget`'s key.
+    raw.into_iter()
+        .filter_map(|(name, raw)| Some((name, serde_json::from_str(&raw).ok()?)))
+        .collect()
+}
+
+pub fn set_labels(
+    target: LabelTarget,
+    name: &str,
+    labels: &Labels,
+    ctx: &mut ContextManager,
+) -> Result<(), redis::RedisError> {
+    //! Replaces a node's or instance's labels; an empty set removes the entry.
+    if labels.is_empty() {
+        return redis::cmd("HDEL")
+            .arg(target.get_key())
+            .arg(name)
+            .query(ctx.get_connection());
+    }
+    let raw = serde_json::to_string(labels).map_err(|err| {
+        redis::RedisError::from((
+            redis::ErrorKind::ClientError,
+            "Label serialization error",
+            err.to_string(),
+        ))
+    })?;
+    redis::cmd("HSET")
+        .arg(target.get_key())
+        .arg(name)
+        .arg(raw)
+        .query(ctx.get_connection())
+}
+
+pub fn parse_selector(selector: &str) -> Result<Labels, String> {
+    //! Parses `key=value[,key=value...]`.
+    selector
+        .split(',')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) if !key.is_empty() => Ok((key.into(), value.into())),
+            _ => Err(format!("invalid label {:?} (expected key=value)", pair)),
+        })
+        .collect()
+}
+
+pub fn matches_selector(labels: &Labels, selector: &Labels) -> bool {
+    selector
+        .iter()
+        .all(|(key, value)| labels.get(key) == Some(value))
+}
+
+pub fn format_labels(labels: &Labels) -> String {
+    labels
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(",")
+}
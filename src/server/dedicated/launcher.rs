@@ -0,0 +1,287 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    panic::{self, AssertUnwindSafe},
+    sync::{Condvar, Mutex},
+    thread,
+    time::Instant,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    batch::{get_panic_message, BatchOutcome, BatchReport},
+    context_manager::ContextManager,
+    server::server_group::ServerGroup,
+    store::entity::RedisEntity,
+    strategy::ScalingDecision,
+};
+
+use super::logwatch::{self, FatalPattern};
+
+/// How many launches run at once when the scaler asks for several instances.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct LaunchInfo {
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize, // launches in flight across all nodes
+    #[serde(default = "default_per_node")]
+    pub per_node: usize, // launches in flight on one node
+    #[serde(default = "default_priority")]
+    pub priority: Vec<String>, // groups launched first, in this order
+    #[serde(default = "logwatch::default_fatal_patterns")]
+    pub fatal_patterns: Vec<FatalPattern>, // log lines that end a launch's wait early
+}
+
+/// One instance to start, already placed on a node.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Launch {
+    pub group: ServerGroup,
+    pub server_num: usize,
+    pub node: String,
+}
+
+/// Launches placed in the order they will be started.
+#[derive(Debug, Default)]
+pub struct LaunchPlan {
+    pub launches: Vec<Launch>,
+    pub unplaced: Vec<(String, usize)>, // (group, launches no node has room for)
+    pub skipped: Vec<(String, String)>, // (node, why its telemetry rules it out)
+}
+
+fn default_concurrency() -> usize {
+    4
+}
+
+fn default_per_node() -> usize {
+    2
+}
+
+fn default_priority() -> Vec<String> {
+    vec!["Lobby".into(), "ClansHub".into()]
+}
+
+impl Default for LaunchInfo {
+    fn default() -> Self {
+        Self {
+            concurrency: default_concurrency(),
+            per_node: default_per_node(),
+            priority: default_priority(),
+            fatal_patterns: logwatch::default_fatal_patterns(),
+        }
+    }
+}
+
+impl Display for Launch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} on {}", self.get_name(), self.node)
+    }
+}
+
+impl Display for LaunchPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (node, reason) in self.skipped.iter() {
+            writeln!(f, "skip {}: {}", node, reason)?;
+        }
+        for launch in self.launches.iter() {
+            writeln!(f, "launch {}", launch)?;
+        }
+        for (group, count) in self.unplaced.iter() {
+            writeln!(
+                f,
+                "{}: no node has room for {} more instance(s)",
+                group, count
+            )?;
+        }
+        write!(f, "{} launch(es) planned", self.launches.len())
+    }
+}
+
+impl Launch {
+    pub fn get_name(&self) -> String {
+        format!("{}-{}", self.group.name, self.server_num)
+    }
+}
+
+impl LaunchInfo {
+    pub fn get_rank(&self, group: &ServerGroup) -> usize {
+        //! Position of the group in `priority`, groups that aren't listed come after all of them.
+        self.priority
+            .iter()
+            .position(|name| name == &group.name)
+            .unwrap_or(self.priority.len())
+    }
+}
+
+pub fn get_missing(
+    decisions: &[ScalingDecision],
+    ctx: &mut ContextManager,
+) -> Vec<(ServerGroup, usize)> {
+    //! Instances each group is short of its desired count.
+    let mut missing = Vec::new();
+    for decision in decisions
+        .iter()
+        .filter(|decision| decision.desired > decision.running)
+    {
+        if let Ok(group) = ServerGroup::get(&decision.group, ctx) {
+            missing.push((group, decision.desired - decision.running));
+        }
+    }
+    missing
+}
+
+pub fn plan(requests: &[(ServerGroup, usize)], ctx: &mut ContextManager) -> LaunchPlan {
+    //! Places every requested instance with the placement strategy, each one counted against
+    //! its node before the next is placed. Priority groups come first, the rest keep the
+    //! order they were asked for in.
+    let info = ctx.get_config().launch.clone();
+    let mut requests: Vec<&(ServerGroup, usize)> = requests.iter().collect();
+    requests.sort_by_key(|(group, _)| info.get_rank(group));
+    let mut nodes = ctx.get_dedicated_servers().clone();
+    // nodes whose telemetry can't be read are placed on bookkeeping alone
+    nodes.merge_telemetry(ctx);
+    let strategy = ctx.get_placement_strategy();
+    let mut plan = LaunchPlan {
+        skipped: nodes
+            .servers
+            .iter()
+            .filter_map(|ds| Some((ds.name.clone(), ds.get_telemetry_warning()?)))
+            .collect(),
+        ..Default::default()
+    };
+    for (group, count) in requests {
+        for placed in 0..*count {
+            let server_num = nodes.get_next_server_num(group);
+            let reserved = nodes
+                .place_with(group, strategy)
+                .and_then(|node| node.add_server(group, server_num));
+            match reserved {
+                Ok(outcome) => plan.launches.push(Launch {
+                    group: group.clone(),
+                    server_num,
+                    node: outcome.node,
+                }),
+                Err(_) => {
+                    plan.unplaced.push((group.name.clone(), count - placed));
+                    break;
+                }
+            }
+        }
+    }
+    plan
+}
+
+fn start(launch: &Launch, ctx: &mut ContextManager) -> (Result<String, String>, bool) {
+    // also tells whether the node tracks the instance afterwards, launched or not
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        ctx.with_dedicated_servers(|servers, ctx| {
+            servers
+                .get_server_mut(&launch.node)
+                .ok_or(format!("node {} not found", launch.node))?
+                .start_server(&launch.group, launch.server_num, ctx)
+                .map_err(|err| err.to_string())
+        })
+    }))
+    .unwrap_or_else(|panic| Err(format!("panicked: {}", get_panic_message(&panic))))
+    .map(|_| format!("launched on {}", launch.node));
+    let tracked = ctx.get_dedicated_servers().is_tracked(&launch.get_name());
+    (result, tracked)
+}
+
+pub fn run(
+    plan: LaunchPlan,
+    concurrency: usize,
+    per_node: usize,
+    progress: impl Fn(usize, usize, &BatchOutcome<Launch>) + Sync,
+    ctx: &mut ContextManager,
+) -> BatchReport<Launch> {
+    //! Starts the planned launches in order with at most `concurrency` in flight, and at
+    //! most `per_node` on any one node; a launch whose node is busy waits for it while later
+    //! launches on other nodes go ahead. `progress` is told about each finished launch with
+    //! how many are done out of how many. Offline, or with a concurrency of 1, launches run
+    //! one after another on `ctx`.
+    let total = plan.launches.len();
+    let per_node = per_node.max(1);
+    if concurrency <= 1 || total <= 1 || ctx.get_connection().is_offline() {
+        let mut outcomes = Vec::new();
+        for launch in plan.launches {
+            let started = Instant::now();
+            let (result, _) = start(&launch, ctx);
+            let outcome = BatchOutcome {
+                target: launch,
+                result,
+                took: started.elapsed(),
+            };
+            progress(outcomes.len() + 1, total, &outcome);
+            outcomes.push(outcome);
+        }
+        return BatchReport { outcomes };
+    }
+    struct State {
+        queue: Vec<(usize, Launch)>,
+        in_flight: HashMap<String, usize>,
+        done: Vec<(usize, BatchOutcome<Launch>, bool)>,
+    }
+    let state = Mutex::new(State {
+        queue: plan.launches.into_iter().enumerate().collect(),
+        in_flight: HashMap::new(),
+        done: Vec::new(),
+    });
+    let changed = Condvar::new();
+    let build = ctx.builder();
+    let next = || {
+        let mut state = state.lock().ok()?;
+        loop {
+            if state.queue.is_empty() {
+                return None;
+            }
+            let ready = state.queue.iter().position(|(_, launch)| {
+                state.in_flight.get(&launch.node).copied().unwrap_or(0) < per_node
+            });
+            if let Some(ready) = ready {
+                let (i, launch) = state.queue.remove(ready);
+                *state.in_flight.entry(launch.node.clone()).or_default() += 1;
+                return Some((i, launch));
+            }
+            state = changed.wait(state).ok()?;
+        }
+    };
+    thread::scope(|scope| {
+        for _ in 0..concurrency.min(total) {
+            scope.spawn(|| {
+                let mut ctx = build();
+                while let Some((i, launch)) = next() {
+                    let started = Instant::now();
+                    let (result, tracked) = start(&launch, &mut ctx);
+                    let outcome = BatchOutcome {
+                        target: launch,
+                        result,
+                        took: started.elapsed(),
+                    };
+                    if let Ok(mut state) = state.lock() {
+                        if let Some(count) = state.in_flight.get_mut(&outcome.target.node) {
+                            *count -= 1;
+                        }
+                        progress(state.done.len() + 1, total, &outcome);
+                        state.done.push((i, outcome, tracked));
+                    }
+                    changed.notify_all();
+                }
+            });
+        }
+    });
+    let mut done = state
+        .into_inner()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .done;
+    done.sort_by_key(|(i, _, _)| *i);
+    // the launches were bookkept on the workers' copies of the nodes
+    for (_, outcome, _) in done.iter().filter(|(_, _, tracked)| *tracked) {
+        let launch = &outcome.target;
+        if let Some(node) = ctx.get_dedicated_servers().get_server_mut(&launch.node) {
+            let _ = node.add_server(&launch.group, launch.server_num);
+        }
+    }
+    BatchReport {
+        outcomes: done.into_iter().map(|(_, outcome, _)| outcome).collect(),
+    }
+}
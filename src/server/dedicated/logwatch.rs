@@ -0,0 +1,109 @@
+use std::{fmt::Display, path::Path, process::Command};
+
+use serde::{Deserialize, Serialize};
+
+use super::server::DedicatedServer;
+
+/// Prints a server's log on a node from a byte offset: `<address> <server> <offset>`.
+const TAIL_SCRIPT: &str = "easyRemoteTailLog.sh";
+
+/// A log line that means the server won't come up, matched as a plain substring.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct FatalPattern {
+    pub pattern: String,
+    pub reason: String, // what the launch error says went wrong
+}
+
+impl FatalPattern {
+    fn new(pattern: &str, reason: &str) -> Self {
+        Self {
+            pattern: pattern.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+pub fn default_fatal_patterns() -> Vec<FatalPattern> {
+    vec![
+        FatalPattern::new("FAILED TO BIND TO PORT", "port already in use"),
+        FatalPattern::new("Unable to bind", "port already in use"),
+        FatalPattern::new("OutOfMemoryError", "out of memory"),
+        FatalPattern::new("Could not load 'plugins/", "plugin failed to load"),
+        FatalPattern::new("Error occurred while enabling", "plugin failed to enable"),
+    ]
+}
+
+/// The first fatal line found in a starting server's log.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FatalLine {
+    pub reason: String,
+    pub line: String,
+}
+
+impl Display for FatalLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.reason, self.line.trim())
+    }
+}
+
+pub fn find_fatal<'a>(
+    lines: impl IntoIterator<Item = &'a str>,
+    patterns: &[FatalPattern],
+) -> Option<FatalLine> {
+    lines.into_iter().find_map(|line| {
+        patterns
+            .iter()
+            .find(|pattern| line.contains(&pattern.pattern))
+            .map(|pattern| FatalLine {
+                reason: pattern.reason.clone(),
+                line: line.into(),
+            })
+    })
+}
+
+/// Follows a starting server's log through the tail script, remembering how far it read.
+#[derive(Clone, Debug)]
+pub struct LogWatcher {
+    script: String,
+    address: String,
+    server_name: String,
+    offset: usize,
+    partial: String, // last line, until the server finishes writing it
+}
+
+impl LogWatcher {
+    pub fn new(scripts_path: &str, node: &DedicatedServer, server_name: &str) -> Self {
+        Self {
+            script: Path::new(scripts_path)
+                .join(TAIL_SCRIPT)
+                .to_string_lossy()
+                .into(),
+            address: node.private_address.clone(),
+            server_name: server_name.into(),
+            offset: 0,
+            partial: String::new(),
+        }
+    }
+
+    pub fn poll(&mut self, patterns: &[FatalPattern]) -> Option<FatalLine> {
+        //! Checks the lines written since the last poll. Watching is best-effort: a log that
+        //! doesn't exist yet or a failing script reads as nothing new, and the launch
+        //! timeout still applies.
+        let output = Command::new("/bin/sh")
+            .arg(&self.script)
+            .arg(&self.address)
+            .arg(&self.server_name)
+            .arg(self.offset.to_string())
+            .output()
+            .ok()
+            .filter(|output| output.status.success())?;
+        self.offset += output.stdout.len();
+        self.partial
+            .push_str(&String::from_utf8_lossy(&output.stdout));
+        let complete = match self.partial.rfind('\n') {
+            Some(end) => self.partial.drain(..=end).collect::<String>(),
+            None => return None,
+        };
+        find_fatal(complete.lines(), patterns)
+    }
+}
@@ -1,8 +1,27 @@
 use serde::{Deserialize, Serialize};
 
 pub mod collection;
+pub mod disk;
+pub mod healing;
 pub mod instance;
+pub mod labels;
+pub mod launcher;
+pub mod logwatch;
+pub mod orphans;
+pub mod outcome;
+pub mod prewarm;
+pub mod rebalance;
+pub mod recovery;
+pub mod refresh;
+pub mod relocation;
+pub mod removal;
+pub mod reservation;
+pub mod resolver;
+pub mod sampling;
 pub mod server;
+pub mod spawn;
+pub mod strays;
+pub mod telemetry;
 
 #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
 pub struct System {
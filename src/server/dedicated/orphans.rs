@@ -0,0 +1,332 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+use crate::{
+    context_manager::ContextManager,
+    events::{Event, EventKind},
+    region::Region,
+};
+
+use super::{
+    collection::DedicatedServers,
+    recovery::scan_live,
+    sampling::{run_script, InstancePid},
+    server::{DedicatedServer, DedicatedServerError},
+};
+
+/// Prints `<pid> <server>` for every server jvm running on a node: `<address>`.
+const LIST_SCRIPT: &str = "easyRemoteListServers.sh";
+/// Kills one process on a node: `<address> <pid>`.
+const KILL_PID_SCRIPT: &str = "easyRemoteKillPid.sh";
+/// Hash of `<node>:<pid>` -> `Orphan`.
+const ORPHANS_KEY: &str = "servermonitor.orphans";
+
+/// What the sweep does with server processes nothing accounts for.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, Eq, PartialEq, Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum OrphanPolicy {
+    #[default]
+    Off,
+    Report, // record them and emit an event
+    Kill,   // also kill those still orphaned after `grace_secs`
+}
+
+/// Sweeps nodes for server jvms no node tracks and no status reports, e.g. left running
+/// after a failed kill or by a crashed manager, still holding ram and a port.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct OrphansInfo {
+    #[serde(default)]
+    pub policy: OrphanPolicy,
+    #[serde(default = "default_interval")]
+    pub interval_secs: i64, // one ssh call per node each time
+    #[serde(default = "default_grace")]
+    pub grace_secs: i64, // orphaned this long before being killed, longer than a launch takes
+}
+
+fn default_interval() -> i64 {
+    300
+}
+
+fn default_grace() -> i64 {
+    600
+}
+
+impl Default for OrphansInfo {
+    fn default() -> Self {
+        Self {
+            policy: OrphanPolicy::default(),
+            interval_secs: default_interval(),
+            grace_secs: default_grace(),
+        }
+    }
+}
+
+/// A server process found on a node without a tracked instance or live status behind it.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct Orphan {
+    pub node: String,
+    pub pid: u32,
+    pub server: String, // the name it was started as
+    pub reason: String,
+    pub first_seen: i64, // seconds since epoch
+}
+
+/// What one sweep found and did.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct OrphanReport {
+    pub orphans: Vec<Orphan>, // every orphan still running
+    pub found: usize,         // of those, first seen by this sweep
+    pub killed: Vec<Orphan>,
+    pub failed: Vec<(String, String)>, // (node or orphan, why)
+    pub gone: usize,                   // orphans seen before that aren't running anymore
+}
+
+impl Display for Orphan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.server, self.describe())
+    }
+}
+
+impl Display for OrphanReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} orphan(s), {} new, {} killed, {} failed, {} gone",
+            self.orphans.len(),
+            self.found,
+            self.killed.len(),
+            self.failed.len(),
+            self.gone
+        )
+    }
+}
+
+impl Orphan {
+    fn describe(&self) -> String {
+        //! Without the server's name, for events about it.
+        format!("pid {} on {}, {}", self.pid, self.node, self.reason)
+    }
+
+    pub fn get_id(&self) -> String {
+        format!("{}:{}", self.node, self.pid)
+    }
+
+    pub fn get_all(ctx: &mut ContextManager) -> Result<Vec<Self>, redis::RedisError> {
+        let raw: Vec<String> = redis::cmd("HVALS")
+            .arg(ORPHANS_KEY)
+            .query(ctx.get_connection())?;
+        let mut orphans: Vec<Self> = raw
+            .iter()
+            .filter_map(|raw| serde_json::from_str(raw).ok())
+            .collect();
+        orphans.sort_by(|a, b| (&a.node, a.pid).cmp(&(&b.node, b.pid)));
+        Ok(orphans)
+    }
+
+    fn save(&self, ctx: &mut ContextManager) -> Result<(), redis::RedisError> {
+        let raw = serde_json::to_string(self).map_err(|err| {
+            redis::RedisError::from((
+                redis::ErrorKind::ClientError,
+                "Orphan serialization error",
+                err.to_string(),
+            ))
+        })?;
+        redis::cmd("HSET")
+            .arg(ORPHANS_KEY)
+            .arg(self.get_id())
+            .arg(raw)
+            .query(ctx.get_connection())
+    }
+
+    fn delete(&self, ctx: &mut ContextManager) -> Result<(), redis::RedisError> {
+        redis::cmd("HDEL")
+            .arg(ORPHANS_KEY)
+            .arg(self.get_id())
+            .query(ctx.get_connection())
+    }
+}
+
+pub fn parse_processes(output: &str) -> Vec<(u32, String)> {
+    //! `<pid> <server>` lines. Lines that don't parse are skipped.
+    output
+        .lines()
+        .filter_map(|line| {
+            let (pid, server) = line.trim().split_once(char::is_whitespace)?;
+            Some((pid.parse().ok()?, server.trim().to_string()))
+        })
+        .collect()
+}
+
+fn is_server_name(name: &str) -> bool {
+    //! Whether `name` follows the `<group>-<num>` naming of the manager's instances.
+    name.rsplit_once('-')
+        .is_some_and(|(group, num)| !group.is_empty() && num.parse::<usize>().is_ok())
+}
+
+impl DedicatedServer {
+    pub fn list_server_processes(
+        &self,
+        ctx: &mut ContextManager,
+    ) -> Result<Vec<(u32, String)>, DedicatedServerError> {
+        let script = Path::new(ctx.get_config().monitor_info.get_scripts_path()).join(LIST_SCRIPT);
+        run_script(&script, std::slice::from_ref(&self.private_address))
+            .map(|output| parse_processes(&output))
+            .map_err(|err| DedicatedServerError::ProcessError(format!("{}: {}", self.name, err)))
+    }
+
+    pub fn kill_process(
+        &self,
+        pid: u32,
+        ctx: &mut ContextManager,
+    ) -> Result<(), DedicatedServerError> {
+        let script =
+            Path::new(ctx.get_config().monitor_info.get_scripts_path()).join(KILL_PID_SCRIPT);
+        run_script(&script, &[self.private_address.clone(), pid.to_string()])
+            .map(|_| ())
+            .map_err(|err| {
+                DedicatedServerError::ProcessError(format!("pid {} on {}: {}", pid, self.name, err))
+            })
+    }
+
+    pub fn find_orphans(
+        &self,
+        processes: &[(u32, String)],
+        live: &HashSet<String>,
+        pids: &HashMap<String, InstancePid>,
+    ) -> Vec<(u32, String, String)> {
+        //! (pid, server, why) of each of this node's `processes` named like an instance
+        //! that isn't tracked here and reports no status, or that runs beside the process
+        //! recorded for its instance.
+        let running: HashSet<u32> = processes.iter().map(|(pid, _)| *pid).collect();
+        processes
+            .iter()
+            .filter(|(_, server)| is_server_name(server))
+            .filter_map(|(pid, server)| {
+                let instance = self.get_instance(server);
+                if instance.is_none() && !live.contains(server) {
+                    return Some((
+                        *pid,
+                        server.clone(),
+                        "not tracked by the node and reports no status".into(),
+                    ));
+                }
+                let recorded = instance.and_then(|instance| instance.get_pid()).or(pids
+                    .get(server)
+                    .filter(|recorded| recorded.node == self.name)
+                    .map(|recorded| recorded.pid))?;
+                (recorded != *pid && running.contains(&recorded)).then(|| {
+                    (
+                        *pid,
+                        server.clone(),
+                        format!("duplicate of the instance's process (pid {})", recorded),
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+impl DedicatedServers {
+    pub fn sweep_orphans(
+        &self,
+        region: Option<&Region>,
+        policy: OrphanPolicy,
+        ctx: &mut ContextManager,
+    ) -> Result<OrphanReport, redis::RedisError> {
+        //! Lists the server processes on every node (in `region`) and records those that
+        //! are orphaned, each new one as an event. With `OrphanPolicy::Kill`, orphans first
+        //! seen `grace_secs` ago are killed. Orphans that stopped are forgotten.
+        let grace_secs = ctx.get_config().orphans.grace_secs;
+        let now = ctx.get_clock().now().timestamp();
+        let (live, unreadable) = scan_live(region, ctx);
+        let mut report = OrphanReport::default();
+        if !unreadable.is_empty() {
+            // a server whose status can't be read would look orphaned
+            report.failed.extend(
+                unreadable
+                    .into_iter()
+                    .map(|key| (key, "status could not be read".into())),
+            );
+            return Ok(report);
+        }
+        let live: HashSet<String> = live
+            .into_iter()
+            .map(|(_, server)| server.get_name().clone())
+            .collect();
+        let pids = InstancePid::get_all(ctx)?;
+        let mut known: HashMap<String, Orphan> = Orphan::get_all(ctx)?
+            .into_iter()
+            .map(|orphan| (orphan.get_id(), orphan))
+            .collect();
+        for ds in self
+            .servers
+            .iter()
+            .filter(|ds| region.is_none_or(|region| &ds.region == region))
+        {
+            let processes = match ds.list_server_processes(ctx) {
+                Ok(processes) => processes,
+                Err(err) => {
+                    // its orphans stay recorded until the node can be listed again
+                    known.retain(|_, orphan| orphan.node != ds.name);
+                    report.failed.push((ds.name.clone(), err.to_string()));
+                    continue;
+                }
+            };
+            for (pid, server, reason) in ds.find_orphans(&processes, &live, &pids) {
+                let id = format!("{}:{}", ds.name, pid);
+                let orphan = match known.remove(&id) {
+                    Some(orphan) => orphan,
+                    None => {
+                        let orphan = Orphan {
+                            node: ds.name.clone(),
+                            pid,
+                            server,
+                            reason,
+                            first_seen: now,
+                        };
+                        orphan.save(ctx)?;
+                        Event::new(EventKind::OrphanFound, &orphan.server, orphan.describe())
+                            .emit(ctx);
+                        report.found += 1;
+                        orphan
+                    }
+                };
+                if policy != OrphanPolicy::Kill || now - orphan.first_seen < grace_secs {
+                    report.orphans.push(orphan);
+                    continue;
+                }
+                match ds.kill_process(orphan.pid, ctx) {
+                    Ok(()) => {
+                        orphan.delete(ctx)?;
+                        Event::new(EventKind::OrphanKilled, &orphan.server, orphan.describe())
+                            .emit(ctx);
+                        report.killed.push(orphan);
+                    }
+                    Err(err) => {
+                        report.failed.push((orphan.get_id(), err.to_string()));
+                        report.orphans.push(orphan);
+                    }
+                }
+            }
+        }
+        // what's left stopped, was on a node that's no longer configured, or runs on a
+        // node outside `region`
+        for orphan in known.values() {
+            if self
+                .get_server(&orphan.node)
+                .is_none_or(|ds| region.is_none_or(|region| &ds.region == region))
+            {
+                orphan.delete(ctx)?;
+                report.gone += 1;
+            }
+        }
+        Ok(report)
+    }
+}
@@ -0,0 +1,47 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+use crate::server::port::Port;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct NodeResources {
+    pub ram: i16,
+    pub cpu: i16,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize, Display)]
+pub enum InstanceChange {
+    Added,
+    Removed,
+}
+
+/// What `add_server`/`remove_server` did to a node's bookkeeping.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct InstanceOutcome {
+    pub change: InstanceChange,
+    pub instance: String,
+    pub group: String,
+    pub port: Port,
+    pub node: String,
+    pub before: NodeResources, // available on the node
+    pub after: NodeResources,
+}
+
+impl Display for InstanceOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} (port {}) on {}: ram {} -> {}, cpu {} -> {}",
+            self.change,
+            self.instance,
+            self.port,
+            self.node,
+            self.before.ram,
+            self.after.ram,
+            self.before.cpu,
+            self.after.cpu
+        )
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    path::Path,
+    process::Command,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    context_manager::ContextManager, jars, server::server_group::ServerGroup,
+    store::entity::RedisEntity, strategy::ScalingDecision,
+};
+
+use super::{
+    instance::MCSInstance,
+    server::{DedicatedServer, DedicatedServerError},
+};
+
+const PREWARM_SCRIPT: &str = "easyRemotePrewarm.sh";
+
+/// Staging of world zips and jars on the nodes upcoming launches will likely land on,
+/// done by the monitor ahead of the peaks it forecasts.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct PrewarmInfo {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_lead_hours")]
+    pub lead_hours: u32, // how far ahead of a forecast peak to prewarm
+    #[serde(default = "default_min_increase_percent")]
+    pub min_increase_percent: u32, // forecast rise over current demand that counts as a peak
+}
+
+/// What one node gets staged.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PrewarmTarget {
+    pub node: String,
+    pub groups: BTreeMap<String, usize>, // group -> launches expected on the node
+    pub world_zips: BTreeSet<String>,
+    pub jars: BTreeSet<String>,
+}
+
+/// What a prewarm staged, or would have with `dry_run`.
+#[derive(Debug, Default)]
+pub struct PrewarmReport {
+    pub dry_run: bool,
+    pub targets: Vec<PrewarmTarget>,
+    pub unplaced: Vec<(String, usize)>, // (group, launches no node has room for)
+    pub failed: Vec<(String, String)>,  // (node, why)
+}
+
+fn default_lead_hours() -> u32 {
+    2
+}
+
+fn default_min_increase_percent() -> u32 {
+    20
+}
+
+impl Default for PrewarmInfo {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            lead_hours: default_lead_hours(),
+            min_increase_percent: default_min_increase_percent(),
+        }
+    }
+}
+
+impl Display for PrewarmTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let groups: Vec<String> = self
+            .groups
+            .iter()
+            .map(|(group, count)| format!("{} x{}", group, count))
+            .collect();
+        write!(
+            f,
+            "{}: {} (worlds: {}; jars: {})",
+            self.node,
+            groups.join(", "),
+            self.world_zips
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.jars.iter().cloned().collect::<Vec<_>>().join(", ")
+        )
+    }
+}
+
+impl Display for PrewarmReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let prefix = if self.dry_run { "(dry run) " } else { "" };
+        for target in self.targets.iter() {
+            writeln!(f, "{}prewarm {}", prefix, target)?;
+        }
+        for (group, count) in self.unplaced.iter() {
+            writeln!(
+                f,
+                "{}: no node has room for {} more instance(s)",
+                group, count
+            )?;
+        }
+        for (node, err) in self.failed.iter() {
+            writeln!(f, "{} failed: {}", node, err)?;
+        }
+        write!(
+            f,
+            "{}Prewarmed {} node(s), {} failure(s)",
+            prefix,
+            self.targets.len(),
+            self.failed.len()
+        )
+    }
+}
+
+impl PrewarmReport {
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+pub fn get_launches(
+    decisions: &[ScalingDecision],
+    demand_percent: u64,
+    ctx: &mut ContextManager,
+) -> Vec<(ServerGroup, usize)> {
+    //! Launches each group is expected to need once its desired count is scaled to
+    //! `demand_percent` (100: as it is now), at least one for every group that wants instances.
+    let mut launches = Vec::new();
+    for decision in decisions.iter().filter(|decision| decision.desired > 0) {
+        let desired = (decision.desired as u64 * demand_percent).div_ceil(100) as usize;
+        let count = desired.saturating_sub(decision.running).max(1);
+        if let Ok(group) = ServerGroup::get(&decision.group, ctx) {
+            launches.push((group, count));
+        }
+    }
+    launches
+}
+
+pub fn plan(launches: &[(ServerGroup, usize)], ctx: &mut ContextManager) -> PrewarmReport {
+    //! Nodes the placement strategy would pick for the launches, each one counted against
+    //! the node's room before the next is placed.
+    let mut nodes = ctx.get_dedicated_servers().clone();
+    // nodes whose telemetry can't be read are placed on bookkeeping alone
+    nodes.merge_telemetry(ctx);
+    let strategy = ctx.get_placement_strategy();
+    let mut targets: BTreeMap<String, PrewarmTarget> = BTreeMap::new();
+    let mut report = PrewarmReport::default();
+    for (group, count) in launches.iter() {
+        for placed in 0..*count {
+            let node = match nodes.place_with(group, strategy) {
+                Ok(node) => node,
+                Err(_) => {
+                    report.unplaced.push((group.name.clone(), count - placed));
+                    break;
+                }
+            };
+            reserve(node, group);
+            let target = targets
+                .entry(node.name.clone())
+                .or_insert_with(|| PrewarmTarget {
+                    node: node.name.clone(),
+                    ..Default::default()
+                });
+            *target.groups.entry(group.name.clone()).or_default() += 1;
+            target.world_zips.insert(group.world_zip.clone());
+            target.jars.insert(group.get_server_jar());
+        }
+    }
+    report.targets = targets.into_values().collect();
+    report
+}
+
+fn reserve(node: &mut DedicatedServer, group: &ServerGroup) {
+    // stands in for the instance until the launch happens, only on the planning copy
+    let instance = MCSInstance::new(
+        format!("{}-prewarm", group.name),
+        group.name.clone(),
+        0,
+        group.region.clone(),
+        None,
+    );
+    node.server_instances
+        .entry(group.name.clone())
+        .or_default()
+        .push(instance);
+    node.available_ram -= group.ram as i16;
+    node.available_cpu -= group.cpu as i16;
+}
+
+pub fn prewarm(
+    launches: &[(ServerGroup, usize)],
+    dry_run: bool,
+    ctx: &mut ContextManager,
+) -> PrewarmReport {
+    //! Copies the jars and extracts the world zips the launches need on the nodes `plan`
+    //! picks, so the launches themselves only have to start the server.
+    let mut report = plan(launches, ctx);
+    report.dry_run = dry_run;
+    if dry_run {
+        return report;
+    }
+    for target in report.targets.iter() {
+        let node = match ctx.get_dedicated_servers().get_server(&target.node) {
+            Some(node) => node.clone(),
+            None => {
+                report
+                    .failed
+                    .push((target.node.clone(), "node not found".into()));
+                continue;
+            }
+        };
+        for jar in target.jars.iter() {
+            if let Err(err) = jars::ensure_on_node(jar, &node, ctx) {
+                report.failed.push((target.node.clone(), err.to_string()));
+            }
+        }
+        for world_zip in target.world_zips.iter() {
+            if let Err(err) = run_prewarm_script(&node, world_zip, ctx) {
+                report.failed.push((target.node.clone(), err.to_string()));
+            }
+        }
+    }
+    report
+}
+
+fn run_prewarm_script(
+    node: &DedicatedServer,
+    world_zip: &String,
+    ctx: &mut ContextManager,
+) -> Result<(), DedicatedServerError> {
+    let script = Path::new(ctx.get_config().monitor_info.get_scripts_path()).join(PREWARM_SCRIPT);
+    let status = Command::new("/bin/sh")
+        .arg(script)
+        .arg(&node.private_address)
+        .arg(world_zip)
+        .status()
+        .map_err(|err| DedicatedServerError::ProcessError(format!("{}: {:?}", world_zip, err)))?;
+    if !status.success() {
+        return Err(DedicatedServerError::ProcessError(format!(
+            "{}: prewarm script exited with {}",
+            world_zip, status
+        )));
+    }
+    Ok(())
+}
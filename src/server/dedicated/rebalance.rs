@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use crate::{context_manager::ContextManager, region::Region, server::server_group::ServerGroup};
+
+use super::{
+    collection::DedicatedServers,
+    relocation::Relocation,
+    server::{DedicatedServer, DedicatedServerError},
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeUtilization {
+    pub node: String,
+    pub region: Region,
+    pub ram: f64, // fraction of max ram in use
+    pub cpu: f64, // fraction of max cpu in use
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlannedMove {
+    pub instance: String,
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RebalanceReport {
+    pub before: Vec<NodeUtilization>,
+    pub after: Vec<NodeUtilization>,
+    pub variance_before: f64,
+    pub variance_after: f64,
+    pub moves: Vec<PlannedMove>,
+    pub relocations: Vec<Relocation>, // empty unless the moves were executed
+}
+
+fn fraction_used(available: i16, max: i16) -> f64 {
+    if max <= 0 {
+        return 0.0;
+    }
+    (max - available) as f64 / max as f64
+}
+
+fn variance(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64
+}
+
+impl DedicatedServer {
+    pub fn get_utilization(&self) -> NodeUtilization {
+        NodeUtilization {
+            node: self.name.clone(),
+            region: self.region.clone(),
+            ram: fraction_used(self.available_ram, self.max_ram),
+            cpu: fraction_used(self.available_cpu, self.max_cpu),
+        }
+    }
+}
+
+impl DedicatedServers {
+    pub fn get_utilization(&self) -> Vec<NodeUtilization> {
+        self.servers.iter().map(|ds| ds.get_utilization()).collect()
+    }
+
+    pub fn get_utilization_variance(&self) -> f64 {
+        //! Variance of ram utilization across nodes (ram is what placement runs out of first).
+        variance(
+            &self
+                .get_utilization()
+                .iter()
+                .map(|util| util.ram)
+                .collect::<Vec<f64>>(),
+        )
+    }
+
+    pub fn plan_rebalance(&self, max_moves: usize, ctx: &mut ContextManager) -> RebalanceReport {
+        //! Proposes up to `max_moves` instance moves that lower the utilization variance.
+        //! Each move is the single best one given the moves before it; planning stops early
+        //! once no move improves the spread. Instances only move within their region.
+        let mut simulated = self.clone();
+        let mut groups: HashMap<String, Option<ServerGroup>> = HashMap::new();
+        let mut moves: Vec<PlannedMove> = Vec::new();
+        while moves.len() < max_moves {
+            let current = simulated.get_utilization_variance();
+            let mut utils: Vec<f64> = simulated
+                .get_utilization()
+                .iter()
+                .map(|util| util.ram)
+                .collect();
+            let mut best: Option<(f64, usize, usize, String, usize, ServerGroup)> = None;
+            for (src_idx, source) in simulated.servers.iter().enumerate() {
+                for mcs in source.server_instances.values().flatten() {
+                    let Some(group) = groups
+                        .entry(mcs.get_group().clone())
+                        .or_insert_with(|| ServerGroup::from_str(mcs.get_group(), ctx).ok())
+                        .clone()
+                    else {
+                        continue;
+                    };
+                    for (dst_idx, target) in simulated.servers.iter().enumerate() {
+                        if dst_idx == src_idx
+                            || target.region != source.region
+                            || !group.allows_node(&target.name)
+                            || !target.has_space_for(&group)
+                        {
+                            continue;
+                        }
+                        let (src_before, dst_before) = (utils[src_idx], utils[dst_idx]);
+                        utils[src_idx] -= group.ram as f64 / source.max_ram.max(1) as f64;
+                        utils[dst_idx] += group.ram as f64 / target.max_ram.max(1) as f64;
+                        let candidate = variance(&utils);
+                        (utils[src_idx], utils[dst_idx]) = (src_before, dst_before);
+                        if candidate < best.as_ref().map_or(current, |b| b.0) {
+                            best = Some((
+                                candidate,
+                                src_idx,
+                                dst_idx,
+                                mcs.get_name().clone(),
+                                mcs.get_server_num(),
+                                group.clone(),
+                            ));
+                        }
+                    }
+                }
+            }
+            let Some((_, src_idx, dst_idx, instance, server_num, group)) = best else {
+                break;
+            };
+            if simulated.servers[src_idx]
+                .remove_server(&group, server_num)
+                .is_err()
+                || simulated.servers[dst_idx]
+                    .add_server(&group, server_num)
+                    .is_err()
+            {
+                break;
+            }
+            moves.push(PlannedMove {
+                instance,
+                from: simulated.servers[src_idx].name.clone(),
+                to: simulated.servers[dst_idx].name.clone(),
+            });
+        }
+        RebalanceReport {
+            before: self.get_utilization(),
+            after: simulated.get_utilization(),
+            variance_before: self.get_utilization_variance(),
+            variance_after: simulated.get_utilization_variance(),
+            moves,
+            relocations: Vec::new(),
+        }
+    }
+
+    pub fn rebalance(
+        &mut self,
+        max_moves: usize,
+        ctx: &mut ContextManager,
+    ) -> Result<RebalanceReport, DedicatedServerError> {
+        //! Plans a rebalance and carries out its moves with `relocate_instance`.
+        //! The `after` utilization in the report reflects the actual outcome.
+        let mut report = self.plan_rebalance(max_moves, ctx);
+        for planned in report.moves.iter() {
+            report
+                .relocations
+                .push(self.relocate_instance(&planned.instance, &planned.to, false, ctx)?);
+        }
+        report.after = self.get_utilization();
+        report.variance_after = self.get_utilization_variance();
+        Ok(report)
+    }
+}
@@ -0,0 +1,125 @@
+use std::fmt::Display;
+
+use crate::{
+    context_manager::ContextManager,
+    events::Event,
+    region::Region,
+    server::{minecraft::MinecraftServer, server_group::ServerGroup},
+    store::{entity::RedisEntity, scan::scan_keys},
+};
+
+use super::{collection::DedicatedServers, instance::MCSInstance};
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RecoveryReport {
+    pub adopted: Vec<(String, String)>,   // (instance, node)
+    pub unmatched: Vec<(String, String)>, // (instance, why it wasn't adopted)
+    pub unreadable: Vec<String>,          // status keys that couldn't be parsed
+}
+
+impl Display for RecoveryReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Recovered {} instance(s), {} unmatched, {} unreadable",
+            self.adopted.len(),
+            self.unmatched.len(),
+            self.unreadable.len()
+        )
+    }
+}
+
+pub fn scan_live(
+    region: Option<&Region>,
+    ctx: &mut ContextManager,
+) -> (Vec<(Region, MinecraftServer)>, Vec<String>) {
+    //! Live statuses (of one region, or all), along with the keys that couldn't be read.
+    let mut live = Vec::new();
+    let mut unreadable = Vec::new();
+    let region_name = region.map(|region| region.to_string());
+    let pattern = ctx.get_keys().status_pattern(region_name.as_deref(), None);
+    let page_size = ctx.get_config().get_scan_count();
+    let mut cursor: u64 = 0;
+    loop {
+        let Ok((next, keys)) = scan_keys(&pattern, cursor, page_size, ctx) else {
+            unreadable.push(pattern);
+            return (live, unreadable);
+        };
+        for key in keys {
+            let region = ctx
+                .get_keys()
+                .parse_status_key(&key)
+                .and_then(|(region, _)| Region::try_from(region).ok());
+            match (region, MinecraftServer::get_by_key(&key, ctx)) {
+                (Some(region), Ok(server)) => live.push((region, server)),
+                _ => unreadable.push(key),
+            }
+        }
+        if next == 0 {
+            return (live, unreadable);
+        }
+        cursor = next;
+    }
+}
+
+impl DedicatedServers {
+    pub(super) fn adopt(
+        &mut self,
+        server: &MinecraftServer,
+        region: &Region,
+        ctx: &mut ContextManager,
+    ) -> Result<String, String> {
+        //! Tracks a live server on the node it runs on, returning the node's name.
+        let group = ServerGroup::from_str(server.get_group(), ctx)
+            .map_err(|err| format!("group {:?}: {}", server.get_group(), err))?;
+        let server_num = MCSInstance::calculate_server_num(server.get_name());
+        let port = group
+            .get_port_section()
+            .port_for(server_num)
+            .map_err(|err| err.to_string())?;
+        if port != server.get_port() {
+            return Err(format!(
+                "listens on {} but its port section gives {}",
+                server.get_port(),
+                port
+            ));
+        }
+        let node = ctx
+            .get_resolver()
+            .resolve(server.get_public_address(), region)
+            .cloned()
+            .ok_or(format!(
+                "no {} node has address {}",
+                region,
+                server.get_public_address()
+            ))?;
+        let ds = self
+            .get_server_mut(&node)
+            .ok_or(format!("node {:?} is no longer configured", node))?;
+        if !ds.get_server_nums(&group).contains(&server_num) {
+            let outcome = ds
+                .add_server(&group, server_num)
+                .map_err(|err| err.to_string())?;
+            Event::from(outcome).emit(ctx);
+        }
+        Ok(ds.name.clone())
+    }
+
+    pub fn recover(&mut self, ctx: &mut ContextManager) -> RecoveryReport {
+        //! Rebuilds instance bookkeeping from the live statuses in redis, so nodes account for
+        //! servers started before this manager did. Servers are matched to nodes by address and
+        //! checked against their group's port section. Already tracked instances are left alone.
+        let (live, unreadable) = scan_live(None, ctx);
+        let mut report = RecoveryReport {
+            unreadable,
+            ..Default::default()
+        };
+        for (region, server) in live {
+            match self.adopt(&server, &region, ctx) {
+                Ok(node) => report.adopted.push((server.get_name().clone(), node)),
+                Err(why) => report.unmatched.push((server.get_name().clone(), why)),
+            }
+        }
+        report
+    }
+}
@@ -0,0 +1,78 @@
+use std::{sync::Mutex, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{batch, context_manager::ContextManager, server::minecraft::ServerStatus};
+
+use super::instance::MCSInstance;
+
+/// How many instance statuses `refresh_statuses` reads at once, for commands listing or
+/// selecting instances.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct RefreshInfo {
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize, // threads, each with its own connection
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64, // per redis reply, a status taking longer is reported as unknown
+}
+
+fn default_concurrency() -> usize {
+    8
+}
+
+fn default_timeout_ms() -> u64 {
+    2000
+}
+
+impl Default for RefreshInfo {
+    fn default() -> Self {
+        Self {
+            concurrency: default_concurrency(),
+            timeout_ms: default_timeout_ms(),
+        }
+    }
+}
+
+impl RefreshInfo {
+    pub fn get_timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms.max(1))
+    }
+}
+
+pub fn refresh_statuses(
+    instances: &[MCSInstance],
+    ctx: &mut ContextManager,
+) -> Vec<Result<ServerStatus, String>> {
+    //! Reads the status of every instance (`MCSInstance::get_status`) with the [status_refresh]
+    //! concurrency, in the order given whichever finishes first. An instance whose status
+    //! timed out or couldn't be read gets why instead.
+    let info = ctx.get_config().status_refresh.clone();
+    let statuses: Mutex<Vec<Option<ServerStatus>>> = Mutex::new(vec![None; instances.len()]);
+    let report = batch::run_with_timeout(
+        (0..instances.len()).collect(),
+        info.concurrency,
+        info.get_timeout(),
+        |i, ctx| {
+            let status = instances[*i].clone().get_status(ctx);
+            let name = format!("{:?}", status);
+            if let Ok(mut statuses) = statuses.lock() {
+                statuses[*i] = Some(status);
+            }
+            Ok(name)
+        },
+        ctx,
+    );
+    let mut statuses = statuses
+        .into_inner()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    report
+        .outcomes
+        .into_iter()
+        .map(|outcome| {
+            outcome.result?;
+            statuses[outcome.target]
+                .take()
+                .ok_or("status was not read".to_string())
+        })
+        .collect()
+}
@@ -0,0 +1,206 @@
+use std::{thread, time::Duration};
+
+use crate::{
+    context_manager::ContextManager,
+    region::Region,
+    safety::{Impact, SafetyError},
+    server::{
+        minecraft::{GameDisplayStatus, GameJoinStatus, MinecraftServer},
+        rcon,
+        server_group::ServerGroup,
+    },
+};
+
+use super::{
+    collection::DedicatedServers,
+    server::{DedicatedServer, DedicatedServerError},
+};
+
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Relocation {
+    pub instance: String,
+    pub replacement: String,
+    pub from: String,
+    pub to: String,
+}
+
+pub fn drain_instance(
+    server_name: &String,
+    region: &Region,
+    timeout: Duration,
+    ctx: &mut ContextManager,
+) -> bool {
+    //! Closes a live instance to new joins, then waits for it to empty out,
+    //! polling its status every 5 seconds.
+    //! Returns `true` once it has no players (or no status), `false` if `timeout` passed first.
+    //! If no plugin listens on the command channel, joins are closed over rcon instead.
+    if let Ok(server) = MinecraftServer::get_status(server_name, region, ctx) {
+        let received = server
+            .set_join_status(GameJoinStatus::CLOSED, ctx)
+            .unwrap_or(0);
+        let _ = server.set_display_status(GameDisplayStatus::CLOSING, ctx);
+        if received == 0 {
+            if let Err(err) = rcon::run(&server, &["whitelist on"], ctx) {
+                log::warn!("{} could not be closed to joins: {}", server_name, err);
+            }
+        }
+    }
+    let mut waited = Duration::ZERO;
+    loop {
+        match MinecraftServer::get_status(server_name, region, ctx) {
+            Ok(server) if server.get_player_count() > 0 => (),
+            _ => return true,
+        }
+        if waited >= timeout {
+            return false;
+        }
+        thread::sleep(DRAIN_POLL_INTERVAL);
+        waited += DRAIN_POLL_INTERVAL;
+    }
+}
+
+impl DedicatedServer {
+    pub fn drain_and_kill(
+        &mut self,
+        group: &ServerGroup,
+        server_num: usize,
+        force: bool,
+        ctx: &mut ContextManager,
+    ) -> Result<(), DedicatedServerError> {
+        //! Drains an instance of `group` on this node, then kills it.
+        //! Without `force`, an instance still holding players once the drain times out is
+        //! reopened to joins and left running.
+        let server_name = format!("{}-{}", group.name, server_num);
+        if !drain_instance(&server_name, &group.region, DRAIN_TIMEOUT, ctx) && !force {
+            let impact = Impact::of_instance(&server_name, &group.region, ctx);
+            if let Ok(server) = MinecraftServer::get_status(&server_name, &group.region, ctx) {
+                let _ = server.set_join_status(GameJoinStatus::OPEN, ctx);
+            }
+            return Err(
+                SafetyError::RequiresForce(format!("Killing {}", server_name), impact).into(),
+            );
+        }
+        Ok(self.kill_server(group, server_num, force, ctx)?)
+    }
+}
+
+impl DedicatedServers {
+    pub fn find_instance_node(&self, instance: &String) -> Option<String> {
+        //! Name of the node `instance` is assigned to.
+        self.servers
+            .iter()
+            .find(|ds| {
+                ds.server_instances
+                    .values()
+                    .flatten()
+                    .any(|mcs| mcs.get_name() == instance)
+            })
+            .map(|ds| ds.name.clone())
+    }
+
+    pub fn relocate_instance(
+        &mut self,
+        instance: &String,
+        target_node: &String,
+        force: bool,
+        ctx: &mut ContextManager,
+    ) -> Result<Relocation, DedicatedServerError> {
+        //! Moves an instance onto `target_node`: drains it, stops it on its current node,
+        //! then launches a replacement on the target.
+        //! Without `force`, an instance still holding players once the drain times out is
+        //! left in place, see `drain_and_kill`.
+        //! The replacement keeps the instance's server number (and so its port) unless the target
+        //! already runs that number, in which case the next free number is used.
+        let source_node = self
+            .find_instance_node(instance)
+            .ok_or(DedicatedServerError::InstanceNotFound(instance.clone()))?;
+        if &source_node == target_node {
+            return Err(DedicatedServerError::RelocationError(format!(
+                "{:?} already runs on {:?}",
+                instance, target_node
+            )));
+        }
+        let (group_name, server_num) = {
+            let source = self
+                .get_server(&source_node)
+                .ok_or(DedicatedServerError::NodeNotFound(source_node.clone()))?;
+            let mcs = source
+                .server_instances
+                .values()
+                .flatten()
+                .find(|mcs| mcs.get_name() == instance)
+                .ok_or(DedicatedServerError::InstanceNotFound(instance.clone()))?;
+            (mcs.get_group().clone(), mcs.get_server_num())
+        };
+        let group = ServerGroup::from_str(&group_name, ctx).map_err(|err| {
+            DedicatedServerError::ParsingError(format!("{}: {:?}", group_name, err))
+        })?;
+        let target = self
+            .get_server(target_node)
+            .ok_or(DedicatedServerError::NodeNotFound(target_node.clone()))?;
+        if !group.allows_node(target_node) {
+            return Err(DedicatedServerError::StorageError(format!(
+                "{:?} is pinned to {:?}",
+                group.name,
+                group.host.clone().unwrap_or_default()
+            )));
+        }
+        if target.region != group.region || !target.has_space_for(&group) {
+            return Err(DedicatedServerError::StorageError(format!(
+                "Dedicated Server ({:?}) cannot take {:?}",
+                target_node, instance
+            )));
+        }
+
+        self.get_server_mut(&source_node)
+            .ok_or(DedicatedServerError::NodeNotFound(source_node.clone()))?
+            .drain_and_kill(&group, server_num, force, ctx)?;
+
+        let num_taken = self
+            .get_server(target_node)
+            .is_some_and(|target| target.get_server_nums(&group).contains(&server_num));
+        let replacement_num = if num_taken {
+            self.get_next_server_num(&group)
+        } else {
+            server_num
+        };
+        let target = self
+            .get_server_mut(target_node)
+            .ok_or(DedicatedServerError::NodeNotFound(target_node.clone()))?;
+        target.start_server(&group, replacement_num, ctx)?;
+        Ok(Relocation {
+            instance: instance.clone(),
+            replacement: format!("{}-{}", group.name, replacement_num),
+            from: source_node,
+            to: target_node.clone(),
+        })
+    }
+
+    pub fn drain_node(
+        &mut self,
+        name: &String,
+        force: bool,
+        ctx: &mut ContextManager,
+    ) -> Result<Vec<Relocation>, DedicatedServerError> {
+        //! Relocates every instance off `name` using the planned targets from `check_node_removal`.
+        //! The node itself stays configured.
+        let report = self.check_node_removal(name, ctx)?;
+        if !report.can_relocate() {
+            return Err(DedicatedServerError::NodeInUse(format!(
+                "{:?} runs instances no other node has space for",
+                name
+            )));
+        }
+        report
+            .relocations
+            .iter()
+            .map(|plan| {
+                let target = plan.target.clone().unwrap_or_default();
+                self.relocate_instance(&plan.instance, &target, force, ctx)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{context_manager::ContextManager, server::server_group::ServerGroup};
+
+use super::{collection::DedicatedServers, server::DedicatedServerError};
+
+/// What happens to instances still assigned to a dedicated server that is removed from config.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub enum NodeRemovalPolicy {
+    /// Refuse to remove the node while it has instances.
+    #[default]
+    Block,
+    /// Move every instance onto the remaining nodes first.
+    Relocate,
+    /// Drop the node and leave its instances running unmanaged.
+    Orphan,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlannedRelocation {
+    pub instance: String,
+    pub group: String,
+    pub server_num: usize,
+    pub target: Option<String>, // None if no remaining node can fit it
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NodeRemovalReport {
+    pub node: String,
+    pub freed_ram: i16,
+    pub freed_cpu: i16,
+    pub relocations: Vec<PlannedRelocation>,
+}
+
+impl NodeRemovalReport {
+    pub fn get_instances(&self) -> Vec<&String> {
+        self.relocations.iter().map(|plan| &plan.instance).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.relocations.is_empty()
+    }
+
+    pub fn can_relocate(&self) -> bool {
+        self.relocations.iter().all(|plan| plan.target.is_some())
+    }
+}
+
+impl DedicatedServers {
+    pub fn get_removed_nodes(&self, updated: &DedicatedServers) -> Vec<String> {
+        //! Names of nodes present in `self` but missing from `updated`.
+        self.servers
+            .iter()
+            .filter(|ds| updated.get_server(&ds.name).is_none())
+            .map(|ds| ds.name.clone())
+            .collect()
+    }
+
+    pub fn check_node_removal(
+        &self,
+        name: &String,
+        ctx: &mut ContextManager,
+    ) -> Result<NodeRemovalReport, DedicatedServerError> {
+        //! Reports which instances are still assigned to `name` and where each of them could go.
+        //! Placement is simulated on a copy of the remaining nodes so planned moves don't overlap.
+        let node = self
+            .get_server(name)
+            .ok_or(DedicatedServerError::NodeNotFound(name.clone()))?;
+        let mut remaining = DedicatedServers {
+            servers: self
+                .servers
+                .iter()
+                .filter(|ds| &ds.name != name)
+                .cloned()
+                .collect(),
+        };
+        let mut relocations: Vec<PlannedRelocation> = Vec::new();
+        for instance in node.server_instances.values().flatten() {
+            let target = ServerGroup::from_str(instance.get_group(), ctx)
+                .ok()
+                .and_then(|group| {
+                    let target = remaining
+                        .place_with(&group, ctx.get_placement_strategy())
+                        .ok()?;
+                    target.add_server(&group, instance.get_server_num()).ok()?;
+                    Some(target.name.clone())
+                });
+            relocations.push(PlannedRelocation {
+                instance: instance.get_name().clone(),
+                group: instance.get_group().clone(),
+                server_num: instance.get_server_num(),
+                target,
+            });
+        }
+        Ok(NodeRemovalReport {
+            node: name.clone(),
+            freed_ram: node.max_ram - node.available_ram,
+            freed_cpu: node.max_cpu - node.available_cpu,
+            relocations,
+        })
+    }
+
+    pub fn remove_node(
+        &mut self,
+        name: &String,
+        policy: NodeRemovalPolicy,
+        ctx: &mut ContextManager,
+    ) -> Result<NodeRemovalReport, DedicatedServerError> {
+        //! Removes a node from the managed set, handling its instances according to `policy`.
+        let report = self.check_node_removal(name, ctx)?;
+        match policy {
+            NodeRemovalPolicy::Block if !report.is_empty() => {
+                return Err(DedicatedServerError::NodeInUse(format!(
+                    "{:?} still runs {:?}",
+                    name,
+                    report.get_instances()
+                )));
+            }
+            NodeRemovalPolicy::Relocate if !report.can_relocate() => {
+                return Err(DedicatedServerError::NodeInUse(format!(
+                    "{:?} runs instances no remaining node has space for",
+                    name
+                )));
+            }
+            _ => (),
+        }
+        if policy == NodeRemovalPolicy::Relocate {
+            self.drain_node(name, false, ctx)?;
+        }
+        self.servers.retain(|ds| &ds.name != name);
+        Ok(report)
+    }
+}
@@ -0,0 +1,50 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::server::server_group::ServerGroup;
+
+use super::{outcome::NodeResources, server::DedicatedServer};
+
+/// Headroom a node keeps free for a critical group (e.g. 2GB for Lobby), so bursts of other
+/// groups can't take the capacity it needs. Only the group itself can be placed into it.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct Reservation {
+    pub group: String,
+    #[serde(default)]
+    pub ram: i16, // in MB
+    #[serde(default)]
+    pub cpu: i16,
+}
+
+impl Display for Reservation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}MB/{}cpu", self.group, self.ram, self.cpu)
+    }
+}
+
+impl DedicatedServer {
+    pub fn get_reserved(&self, group: Option<&ServerGroup>) -> NodeResources {
+        //! Headroom held back from `group`: every reservation but its own, or all of them
+        //! without a group.
+        self.reservations
+            .iter()
+            .filter(|reservation| group.is_none_or(|group| reservation.group != group.name))
+            .fold(NodeResources { ram: 0, cpu: 0 }, |held, reservation| {
+                NodeResources {
+                    ram: held.ram + reservation.ram.max(0),
+                    cpu: held.cpu + reservation.cpu.max(0),
+                }
+            })
+    }
+
+    pub fn get_available_resources_for(&self, group: &ServerGroup) -> NodeResources {
+        //! What `group` may use of the node's free resources once other groups' reservations
+        //! are held back.
+        let held = self.get_reserved(Some(group));
+        NodeResources {
+            ram: self.available_ram - held.ram,
+            cpu: self.available_cpu - held.cpu,
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+
+use crate::region::Region;
+
+use super::collection::DedicatedServers;
+
+/// Maps the addresses a node is known by (public, private and configured aliases)
+/// to the node's name, so servers can be matched to the node they run on.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct NodeResolver {
+    addresses: HashMap<String, Vec<(Region, String)>>, // address -> (region, node)
+}
+
+impl NodeResolver {
+    pub fn new(servers: &DedicatedServers) -> Self {
+        let mut resolver = Self::default();
+        for ds in servers.servers.iter() {
+            let addresses = [&ds.public_address, &ds.private_address]
+                .into_iter()
+                .chain(ds.aliases.iter());
+            for address in addresses {
+                let nodes = resolver.addresses.entry(address.clone()).or_default();
+                let entry = (ds.region.clone(), ds.name.clone());
+                if !nodes.contains(&entry) {
+                    nodes.push(entry);
+                }
+            }
+        }
+        resolver
+    }
+
+    pub fn resolve(&self, address: &str, region: &Region) -> Option<&String> {
+        //! Name of the node in `region` known by `address`.
+        //! If several nodes share the address (e.g. local testing), the first configured wins.
+        self.addresses
+            .get(address)?
+            .iter()
+            .find(|(node_region, _)| node_region == region)
+            .map(|(_, node)| node)
+    }
+
+    pub fn is_known(&self, address: &str) -> bool {
+        //! Whether any node, in any region, is known by `address`.
+        self.addresses.contains_key(address)
+    }
+
+    pub fn get_addresses(&self, node: &str) -> Vec<&String> {
+        let mut addresses: Vec<&String> = self
+            .addresses
+            .iter()
+            .filter(|(_, nodes)| nodes.iter().any(|(_, name)| name == node))
+            .map(|(address, _)| address)
+            .collect();
+        addresses.sort();
+        addresses
+    }
+}
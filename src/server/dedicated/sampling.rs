@@ -0,0 +1,355 @@
+use std::{collections::HashMap, fmt::Display, path::Path, process::Command};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{context_manager::ContextManager, region::Region, server::server_group::ServerGroup};
+
+use super::{
+    collection::DedicatedServers,
+    recovery,
+    server::{DedicatedServer, DedicatedServerError},
+};
+
+/// Prints the pid of a server's process on a node: `<address> <server>`.
+const PID_SCRIPT: &str = "easyRemoteServerPid.sh";
+/// Prints `<pid> <cpu %> <rss KB>` for each of the given pids still running on a node
+/// (`ps -o pid=,pcpu=,rss= -p ...` over ssh): `<address> <pid>...`.
+const STATS_SCRIPT: &str = "easyRemoteProcessStats.sh";
+/// Hash of server -> `InstancePid`.
+const PIDS_KEY: &str = "servermonitor.pids";
+
+/// Per-process sampling of instances on nodes without a telemetry agent, which only
+/// reports node totals when it runs at all.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct SamplingInfo {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_interval")]
+    pub interval_secs: i64, // one ssh call per node each time
+    #[serde(default = "default_rss_warn_percent")]
+    pub rss_warn_percent: u32, // rss, of the group's ram, an instance is warned about at
+}
+
+fn default_interval() -> i64 {
+    60
+}
+
+fn default_rss_warn_percent() -> u32 {
+    150
+}
+
+impl Default for SamplingInfo {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: default_interval(),
+            rss_warn_percent: default_rss_warn_percent(),
+        }
+    }
+}
+
+/// The process an instance runs as, recorded at launch (or looked up on first sample).
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct InstancePid {
+    pub server: String,
+    pub node: String,
+    pub pid: u32,
+}
+
+/// One instance's cpu and resident memory as `ps` reported them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProcessSample {
+    pub server: String,
+    pub group: String,
+    pub node: String,
+    pub pid: u32,
+    pub cpu_percent: f64, // of one core, so up to 100 times the cpus it uses
+    pub rss_mb: u64,
+}
+
+// cpu is parsed from `ps` output and never NaN
+impl Eq for ProcessSample {}
+
+/// What a sampling pass read, and the nodes or instances it couldn't.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SamplingReport {
+    pub samples: Vec<ProcessSample>,
+    pub failed: Vec<(String, String)>, // (node or server, why)
+}
+
+impl Display for ProcessSample {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (pid {} on {}): {:.1}% cpu, {}MB rss",
+            self.server, self.pid, self.node, self.cpu_percent, self.rss_mb
+        )
+    }
+}
+
+impl InstancePid {
+    pub fn get_all(ctx: &mut ContextManager) -> Result<HashMap<String, Self>, redis::RedisError> {
+        let raw: Vec<String> = redis::cmd("HVALS")
+            .arg(PIDS_KEY)
+            .query(ctx.get_connection())?;
+        Ok(raw
+            .iter()
+            .filter_map(|raw| serde_json::from_str::<Self>(raw).ok())
+            .map(|pid| (pid.server.clone(), pid))
+            .collect())
+    }
+
+    pub fn save(&self, ctx: &mut ContextManager) -> Result<(), redis::RedisError> {
+        let raw = serde_json::to_string(self).map_err(|err| {
+            redis::RedisError::from((
+                redis::ErrorKind::ClientError,
+                "Pid serialization error",
+                err.to_string(),
+            ))
+        })?;
+        redis::cmd("HSET")
+            .arg(PIDS_KEY)
+            .arg(&self.server)
+            .arg(raw)
+            .query(ctx.get_connection())
+    }
+
+    pub fn forget(servers: &[String], ctx: &mut ContextManager) -> Result<(), redis::RedisError> {
+        if servers.is_empty() {
+            return Ok(());
+        }
+        redis::cmd("HDEL")
+            .arg(PIDS_KEY)
+            .arg(servers)
+            .query(ctx.get_connection())
+    }
+}
+
+pub fn parse_stats(output: &str) -> HashMap<u32, (f64, u64)> {
+    //! `ps` lines as pid -> (cpu %, rss MB). Lines that don't parse are skipped.
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse::<u32>().ok()?;
+            let cpu = fields
+                .next()?
+                .parse::<f64>()
+                .ok()
+                .filter(|cpu| cpu.is_finite())?;
+            let rss_kb = fields.next()?.parse::<u64>().ok()?;
+            Some((pid, (cpu, rss_kb / 1024)))
+        })
+        .collect()
+}
+
+pub fn parse_start_output(output: &str) -> Option<u32> {
+    //! The pid from the start script's last `pid=<pid>` line.
+    output
+        .lines()
+        .rev()
+        .find_map(|line| line.trim().strip_prefix("pid=")?.parse().ok())
+}
+
+pub(super) fn run_script(script: &Path, args: &[String]) -> Result<String, String> {
+    let output = Command::new("/bin/sh")
+        .arg(script)
+        .args(args)
+        .output()
+        .map_err(|err| format!("{:?}", err))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} exited with {}",
+            script.display(),
+            output.status
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+impl DedicatedServer {
+    pub fn find_pid(
+        &self,
+        server_name: &str,
+        ctx: &mut ContextManager,
+    ) -> Result<InstancePid, DedicatedServerError> {
+        //! Looks up the pid of `server_name`'s process on this node.
+        let script = Path::new(ctx.get_config().monitor_info.get_scripts_path()).join(PID_SCRIPT);
+        let output = run_script(&script, &[self.private_address.clone(), server_name.into()])
+            .map_err(|err| {
+                DedicatedServerError::ProcessError(format!("{}: {}", server_name, err))
+            })?;
+        let pid = output.trim().parse::<u32>().map_err(|_| {
+            DedicatedServerError::InstanceNotFound(format!(
+                "{}: no process on {} ({:?})",
+                server_name,
+                self.name,
+                output.trim()
+            ))
+        })?;
+        Ok(InstancePid {
+            server: server_name.into(),
+            node: self.name.clone(),
+            pid,
+        })
+    }
+
+    pub fn record_pid(
+        &self,
+        server_name: &str,
+        ctx: &mut ContextManager,
+    ) -> Result<InstancePid, DedicatedServerError> {
+        //! `find_pid`, remembered for sampling.
+        let pid = self.find_pid(server_name, ctx)?;
+        pid.save(ctx)
+            .map_err(|err| DedicatedServerError::StorageError(err.to_string()))?;
+        Ok(pid)
+    }
+
+    pub fn record_launched_pid(
+        &mut self,
+        group: &ServerGroup,
+        server_num: usize,
+        start_output: &str,
+        ctx: &mut ContextManager,
+    ) {
+        //! Remembers the pid the start script printed, on the instance and in redis, looking
+        //! it up on the node if it printed none. A pid that can't be recorded is looked up
+        //! again when the instance is first sampled.
+        let server_name = format!("{}-{}", group.name, server_num);
+        let recorded = match parse_start_output(start_output) {
+            Some(pid) => {
+                let pid = InstancePid {
+                    server: server_name.clone(),
+                    node: self.name.clone(),
+                    pid,
+                };
+                pid.save(ctx)
+                    .map(|_| pid)
+                    .map_err(|err| DedicatedServerError::StorageError(err.to_string()))
+            }
+            None => self.record_pid(&server_name, ctx),
+        };
+        match recorded {
+            Ok(recorded) => {
+                if let Some(instance) =
+                    self.server_instances
+                        .get_mut(&group.name)
+                        .and_then(|instances| {
+                            instances
+                                .iter_mut()
+                                .find(|instance| instance.get_server_num() == server_num)
+                        })
+                {
+                    instance.set_pid(Some(recorded.pid));
+                }
+            }
+            Err(err) => log::warn!("{} pid was not recorded: {}", server_name, err),
+        }
+    }
+
+    pub fn sample_processes(
+        &self,
+        servers: &[(String, String)],
+        pids: &HashMap<String, InstancePid>,
+        report: &mut SamplingReport,
+        ctx: &mut ContextManager,
+    ) -> Vec<String> {
+        //! Samples `servers` (server, group), all running on this node, looking up pids not
+        //! recorded yet. Returns the servers whose recorded pid is stale: the server stopped,
+        //! or restarted as another process.
+        let mut stale: Vec<String> = pids
+            .values()
+            .filter(|pid| {
+                pid.node == self.name && !servers.iter().any(|(server, _)| server == &pid.server)
+            })
+            .map(|pid| pid.server.clone())
+            .collect();
+        let mut sampled: Vec<(InstancePid, String)> = Vec::new();
+        for (server, group) in servers {
+            let pid = match pids.get(server).filter(|pid| pid.node == self.name) {
+                Some(pid) => pid.clone(),
+                None => match self.record_pid(server, ctx) {
+                    Ok(pid) => pid,
+                    Err(err) => {
+                        report.failed.push((server.clone(), err.to_string()));
+                        continue;
+                    }
+                },
+            };
+            sampled.push((pid, group.clone()));
+        }
+        if sampled.is_empty() {
+            return stale;
+        }
+        let script = Path::new(ctx.get_config().monitor_info.get_scripts_path()).join(STATS_SCRIPT);
+        let mut args = vec![self.private_address.clone()];
+        args.extend(sampled.iter().map(|(pid, _)| pid.pid.to_string()));
+        let stats = match run_script(&script, &args) {
+            Ok(output) => parse_stats(&output),
+            Err(err) => {
+                report.failed.push((self.name.clone(), err));
+                return stale;
+            }
+        };
+        for (pid, group) in sampled {
+            match stats.get(&pid.pid) {
+                Some(&(cpu_percent, rss_mb)) => report.samples.push(ProcessSample {
+                    server: pid.server,
+                    group,
+                    node: self.name.clone(),
+                    pid: pid.pid,
+                    cpu_percent,
+                    rss_mb,
+                }),
+                // restarted since: looked up again next time
+                None => stale.push(pid.server),
+            }
+        }
+        stale
+    }
+}
+
+impl DedicatedServers {
+    pub fn sample_usage(
+        &self,
+        region: Option<&Region>,
+        ctx: &mut ContextManager,
+    ) -> Result<SamplingReport, redis::RedisError> {
+        //! Samples the live servers (in `region`) on every node without fresh telemetry, see
+        //! `merge_telemetry`, so call that first.
+        let (live, unreadable) = recovery::scan_live(region, ctx);
+        let mut report = SamplingReport {
+            failed: unreadable
+                .into_iter()
+                .map(|key| (key, "status could not be read".into()))
+                .collect(),
+            ..Default::default()
+        };
+        let mut by_node: HashMap<String, Vec<(String, String)>> = HashMap::new();
+        for (region, server) in live {
+            if let Some(node) = ctx
+                .get_resolver()
+                .resolve(server.get_public_address(), &region)
+            {
+                by_node
+                    .entry(node.clone())
+                    .or_default()
+                    .push((server.get_name().clone(), server.get_group().clone()));
+            }
+        }
+        let pids = InstancePid::get_all(ctx)?;
+        let mut stale = Vec::new();
+        for ds in self
+            .servers
+            .iter()
+            .filter(|ds| ds.telemetry.is_none() && region.is_none_or(|region| &ds.region == region))
+        {
+            let servers = by_node.remove(&ds.name).unwrap_or_default();
+            stale.extend(ds.sample_processes(&servers, &pids, &mut report, ctx));
+        }
+        InstancePid::forget(&stale, ctx)?;
+        report.samples.sort_by(|a, b| a.server.cmp(&b.server));
+        Ok(report)
+    }
+}
@@ -1,21 +1,47 @@
-use std::{cmp::Ordering, collections::HashMap, iter::Map};
+use std::{
+    cmp::Ordering, collections::HashMap, path::Path, process::Command, thread, time::Duration,
+};
 
+use redis::RedisError;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::{
+    config::models::Readiness,
     context_manager::ContextManager,
+    events::{Event, EventKind},
+    handshake, jars,
+    journal::{JournalEntry, Operation},
+    monitor::chaos,
     region::Region,
-    server::{minecraft::MinecraftServer, server_group::ServerGroup},
+    safety::{Impact, SafetyError},
+    server::{
+        counters,
+        crash_loop::CrashLoop,
+        minecraft::{GameJoinStatus, MinecraftServer},
+        rcon::{self, RconError},
+        server_group::ServerGroup,
+        smoke::SmokeTestError,
+    },
 };
 
-use super::instance::MCSInstance;
+use super::{
+    disk::DiskSpace,
+    instance::MCSInstance,
+    logwatch::LogWatcher,
+    outcome::{InstanceChange, InstanceOutcome, NodeResources},
+    reservation::Reservation,
+    spawn::SpawnRequest,
+    telemetry::NodeTelemetry,
+};
 
 #[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
 pub struct DedicatedServer {
     pub name: String,
     pub public_address: String,
     pub private_address: String,
+    #[serde(default)]
+    pub aliases: Vec<String>, // other addresses servers may report for this node
     pub region: Region,
     #[serde(rename = "cpu")]
     pub available_cpu: i16,
@@ -25,8 +51,20 @@ pub struct DedicatedServer {
     pub max_cpu: i16,
     #[serde(default = "ram_or_cpu_default")]
     pub max_ram: i16,
+    #[serde(default)]
+    pub max_instances: Option<usize>,
+    #[serde(default)]
+    pub port_range: Option<(u16, u16)>, // inclusive
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reservations: Vec<Reservation>, // headroom kept free for critical groups
     #[serde(skip)]
     pub server_instances: HashMap<String, Vec<MCSInstance>>,
+    #[serde(skip)]
+    pub telemetry: Option<NodeTelemetry>, // latest fresh reading of the node's agent
+    #[serde(skip)]
+    pub overloaded: Option<String>, // why the telemetry rules the node out for new instances
+    #[serde(skip)]
+    pub disk: Option<DiskSpace>,
     // pub waiting_to_start: Vec<MinecraftServer>,
     // ADD THIS so we can filter out for these
 }
@@ -35,6 +73,8 @@ fn ram_or_cpu_default() -> i16 {
     0
 }
 
+const KILL_SCRIPT: &str = "easyRemoteKillServer.sh";
+
 #[derive(Error, Debug)]
 pub enum DedicatedServerError {
     #[error("Dedicated Server Parsing Error: `{0}`")]
@@ -43,14 +83,49 @@ pub enum DedicatedServerError {
     StorageError(String),
     #[error("Dedicated Server Error: Bungee Not Found")]
     BungeeNotFoundError,
-    #[error("Dedicated Server Error: Minecraft Server Not Running (took > 40 seconds): `{0}`")]
+    #[error("Dedicated Server Error: Minecraft Server Not Running (startup timed out): `{0}`")]
     MinecraftServerNotRunning(String),
+    #[error("Dedicated Server Error: Minecraft Server failed to start: `{0}`")]
+    StartupFailed(String),
     #[error("Dedicated Server Error: Duplicate instance of running: `{0}`")]
     DuplicateInstanceRunning(String),
     #[error("Dedicated Server Error: Minecraft Server Instance Not Found: `{0}`")]
     InstanceNotFound(String),
     #[error("Dedicated Server Error: Zero instances of ServerGroup online: `{0}`")]
     ZeroInstancesRunning(String),
+    #[error("Dedicated Server Error: Server process could not be managed: `{0}`")]
+    ProcessError(String),
+    #[error("Dedicated Server Error: Instance limit reached: `{0}`")]
+    InstanceLimitReached(String),
+    #[error("Dedicated Server Error: Port outside of node's port range: `{0}`")]
+    PortOutOfRange(String),
+    #[error("Dedicated Server Error: Node not found: `{0}`")]
+    NodeNotFound(String),
+    #[error("Dedicated Server Error: Node overloaded: `{0}`")]
+    NodeUnhealthy(String),
+    #[error("Dedicated Server Error: Not enough disk space: `{0}`")]
+    DiskFull(String),
+    #[error("Dedicated Server Error: Node still in use: `{0}`")]
+    NodeInUse(String),
+    #[error("Dedicated Server Error: Instance could not be relocated: `{0}`")]
+    RelocationError(String),
+    #[error("Dedicated Server Error: Smoke test failed: `{0}`")]
+    SmokeTestFailed(String),
+    #[error("Dedicated Server Error: Launches paused, group is crash looping: `{0}`")]
+    CrashLooping(String),
+    #[error("{0}")]
+    Unsafe(#[from] SafetyError),
+}
+
+impl From<DedicatedServerError> for RedisError {
+    fn from(err: DedicatedServerError) -> Self {
+        (
+            redis::ErrorKind::ClientError,
+            "Dedicated server error",
+            err.to_string(),
+        )
+            .into()
+    }
 }
 
 impl Ord for DedicatedServer {
@@ -67,10 +142,6 @@ impl PartialOrd for DedicatedServer {
 }
 
 impl DedicatedServer {
-    fn get() -> Result<Vec<Self>, DedicatedServerError> {
-        todo!()
-    }
-
     pub fn get_instances(&self, group: &ServerGroup) -> Option<&Vec<MCSInstance>> {
         self.server_instances.get(&group.name)
     }
@@ -78,11 +149,17 @@ impl DedicatedServer {
     pub fn get_server_count(&self, group: &ServerGroup) -> i16 {
         self.get_instances(group)
             .map(|vec| vec.len() as i16)
-            .clone()
             .unwrap_or(0)
     }
 
-    fn get_server_nums(&self, group: &ServerGroup) -> Vec<usize> {
+    pub fn get_instance(&self, server_name: &str) -> Option<&MCSInstance> {
+        self.server_instances
+            .values()
+            .flatten()
+            .find(|instance| instance.get_name() == server_name)
+    }
+
+    pub fn get_server_nums(&self, group: &ServerGroup) -> Vec<usize> {
         self.server_instances
             .get(&group.name)
             .unwrap_or(&Vec::new())
@@ -91,54 +168,233 @@ impl DedicatedServer {
             .collect()
     }
 
+    pub fn start_server(
+        &mut self,
+        group: &ServerGroup,
+        server_num: usize,
+        ctx: &mut ContextManager,
+    ) -> Result<(), DedicatedServerError> {
+        //! Reserves a slot for the instance and launches it, journaled so an interrupted
+        //! launch can be recovered on the next start. Groups paused for crash looping
+        //! aren't launched until their cooldown is over.
+        if let Some(crash_loop) = CrashLoop::get(&group.name, ctx)
+            .map_err(|err| DedicatedServerError::StorageError(err.to_string()))?
+            .filter(|crash_loop| crash_loop.is_paused())
+        {
+            return Err(DedicatedServerError::CrashLooping(format!(
+                "{}: {}",
+                group.name, crash_loop
+            )));
+        }
+        let entry = JournalEntry::begin(
+            Operation::Launch {
+                node: self.name.clone(),
+                group: group.name.clone(),
+                server_num,
+            },
+            ctx,
+        )
+        .map_err(|err| DedicatedServerError::StorageError(err.to_string()))?;
+        let outcome = match self.add_server(group, server_num) {
+            Ok(outcome) => {
+                Event::from(outcome.clone()).emit(ctx);
+                outcome
+            }
+            Err(err) => {
+                // nothing happened yet, so there is nothing to recover
+                let _ = entry.complete(ctx);
+                return Err(err);
+            }
+        };
+        if let Err(err) = self.launch_server(group, server_num, ctx) {
+            if let DedicatedServerError::SmokeTestFailed(_) = err {
+                // the instance was already killed
+                let _ = entry.complete(ctx);
+            }
+            return Err(err);
+        }
+        if let Err(err) = counters::increment(&group.prefix, ctx) {
+            // the monitor recounts from statuses on its next pass
+            log::warn!("{}-{} was not counted: {}", group.name, server_num, err);
+        }
+        for hooks in ctx.get_hooks() {
+            hooks.on_launch(&outcome);
+        }
+        entry
+            .complete(ctx)
+            .map_err(|err| DedicatedServerError::StorageError(err.to_string()))
+    }
+
     pub fn launch_server(
         &mut self,
         group: &ServerGroup,
         server_num: usize,
         ctx: &mut ContextManager,
     ) -> Result<(), DedicatedServerError> {
-        //! Launches server and waits every 5 seconds for the server to go online
-        //! Times out after 40 seconds if it is not found in redis.
+        //! Launches server through the context's `ServerLauncher` (the start script by
+        //! default) and waits for it to come up, see `wait_until_ready`. Failed launches
+        //! count towards the group's crash loop.
         assert_eq!(group.region, self.region);
-        let mut server_name = group.name.clone();
-        server_name.push_str(server_num.to_string().as_str());
-        // now call shell script to run server
-        let ticks = 0;
+        let server_name = format!("{}-{}", group.name, server_num);
+        chaos::delay_launch(&server_name, ctx);
+        let jar = group.get_server_jar();
+        jars::ensure_on_node(&jar, self, ctx)
+            .map_err(|err| DedicatedServerError::ProcessError(err.to_string()))?;
+        let port = self
+            .get_instance(&server_name)
+            .map(|instance| instance.get_port())
+            .ok_or(DedicatedServerError::InstanceNotFound(server_name.clone()))?;
+        let scripts_path = ctx.get_config().monitor_info.get_scripts_path().clone();
+        let mut log = LogWatcher::new(&scripts_path, self, &server_name);
+        let request = SpawnRequest::new(self, group, &server_name, port, &scripts_path);
+        let start_output = match ctx.get_server_launcher().spawn(&request) {
+            Ok(output) => output,
+            Err(err) => {
+                let err = DedicatedServerError::ProcessError(format!("{}: {}", server_name, err));
+                let _ = CrashLoop::record_failure(&group.name, &err.to_string(), ctx);
+                return Err(err);
+            }
+        };
+        self.record_launched_pid(group, server_num, &start_output, ctx);
+        self.wait_until_ready(group, server_num, &mut log, ctx)
+    }
+
+    pub fn wait_until_ready(
+        &mut self,
+        group: &ServerGroup,
+        server_num: usize,
+        log: &mut LogWatcher,
+        ctx: &mut ContextManager,
+    ) -> Result<(), DedicatedServerError> {
+        //! Probes a just-started instance every `monitor_info.startup.interval_secs` until it
+        //! is ready by `startup.ready_when`. It stays closed to joins until it passes the
+        //! smoke test, if one is configured. Fails after `startup.timeout_secs`, or as soon
+        //! as its log shows one of `launch.fatal_patterns`.
+        let server_name = format!("{}-{}", group.name, server_num);
+        let probe = ctx.get_config().monitor_info.get_startup_probe().clone();
+        let patterns = ctx.get_config().launch.fatal_patterns.clone();
+        let smoke_test = ctx.get_config().smoke_test.clone();
+        let mut waited = Duration::ZERO;
+        let mut smoke_test_error = None;
         loop {
-            // todo: figure out how to increment tick
-            todo!();
-            if MinecraftServer::get(&server_name, &self.region, ctx).is_ok() {
-                break;
-            } else if ticks > 40 {
-                return Err(DedicatedServerError::MinecraftServerNotRunning(server_name));
+            if let Ok(server) = MinecraftServer::get_status(&server_name, &self.region, ctx) {
+                match (probe.ready_when, smoke_test.as_ref()) {
+                    (Readiness::SmokeTest, Some(smoke_test)) => {
+                        let _ = server.set_join_status(GameJoinStatus::CLOSED, ctx);
+                        match smoke_test.run(&server, ctx) {
+                            Ok(_) => {
+                                self.open_to_players(group, &server, ctx);
+                                return Ok(());
+                            }
+                            Err(err) => smoke_test_error = Some(err),
+                        }
+                    }
+                    (Readiness::Joinable, _) if !server.is_joinable() => (),
+                    _ => return self.smoke_test(group, server_num, ctx),
+                }
             }
+            if let Some(fatal) = log.poll(&patterns) {
+                let err =
+                    DedicatedServerError::StartupFailed(format!("{}: {}", server_name, fatal));
+                let _ = CrashLoop::record_failure(&group.name, &err.to_string(), ctx);
+                return Err(err);
+            }
+            if waited >= probe.get_timeout() {
+                if let Some(err) = smoke_test_error {
+                    return Err(self.fail_smoke_test(group, server_num, err, ctx));
+                }
+                let err = DedicatedServerError::MinecraftServerNotRunning(format!(
+                    "{} not {} after {}s",
+                    server_name,
+                    probe.ready_when,
+                    waited.as_secs()
+                ));
+                let _ = CrashLoop::record_failure(&group.name, &err.to_string(), ctx);
+                return Err(err);
+            }
+            thread::sleep(probe.get_interval());
+            waited += probe.get_interval();
         }
+    }
+
+    fn smoke_test(
+        &mut self,
+        group: &ServerGroup,
+        server_num: usize,
+        ctx: &mut ContextManager,
+    ) -> Result<(), DedicatedServerError> {
+        //! Opens an online instance to players if it passes the smoke test, otherwise
+        //! kills it rather than let players be routed to it.
+        let server_name = format!("{}-{}", group.name, server_num);
+        let server = MinecraftServer::get_status(&server_name, &self.region, ctx)
+            .map_err(|err| DedicatedServerError::MinecraftServerNotRunning(err.to_string()))?;
+        if let Some(smoke_test) = ctx.get_config().smoke_test.clone() {
+            let _ = server.set_join_status(GameJoinStatus::CLOSED, ctx);
+            if let Err(err) = smoke_test.run(&server, ctx) {
+                return Err(self.fail_smoke_test(group, server_num, err, ctx));
+            }
+        }
+        self.open_to_players(group, &server, ctx);
         Ok(())
     }
 
+    fn open_to_players(
+        &self,
+        group: &ServerGroup,
+        server: &MinecraftServer,
+        ctx: &mut ContextManager,
+    ) {
+        let _ = CrashLoop::clear(&group.name, ctx);
+        let _ = server.set_join_status(GameJoinStatus::OPEN, ctx);
+    }
+
+    fn fail_smoke_test(
+        &mut self,
+        group: &ServerGroup,
+        server_num: usize,
+        err: SmokeTestError,
+        ctx: &mut ContextManager,
+    ) -> DedicatedServerError {
+        //! Kills an instance that failed the smoke test, returning why the launch failed.
+        let server_name = format!("{}-{}", group.name, server_num);
+        Event::new(EventKind::SmokeTestFailed, &server_name, err.to_string()).emit(ctx);
+        let _ = CrashLoop::record_failure(&group.name, &err.to_string(), ctx);
+        if let Err(err) = self.kill_server(group, server_num, true, ctx) {
+            return DedicatedServerError::ProcessError(err.to_string());
+        }
+        DedicatedServerError::SmokeTestFailed(format!("{}: {}", server_name, err))
+    }
+
+    pub fn get_available_resources(&self) -> NodeResources {
+        NodeResources {
+            ram: self.available_ram,
+            cpu: self.available_cpu,
+        }
+    }
+
     pub fn add_server(
         &mut self,
         group: &ServerGroup,
         server_num: usize,
-    ) -> Result<(), DedicatedServerError> {
-        if !self.has_space_for(group) {
-            return Err(DedicatedServerError::StorageError(
-                format!("Dedicated Server ({:?}) has no space for server {:?} (try another dedicated server)",
-                self.name, group.name
-            )));
-        } else if self.get_server_nums(group).contains(&server_num) {
+    ) -> Result<InstanceOutcome, DedicatedServerError> {
+        self.check_placement(group, Some(server_num))?;
+        if self.get_server_nums(group).contains(&server_num) {
             return Err(DedicatedServerError::DuplicateInstanceRunning(
                 format!("Dedicated Server ({:?}) cannot run {:?} because this server instance is already running 
                 (try another server number)",
                 self.name, group.name
             )));
         }
+        let port = group
+            .get_port_section()
+            .port_for(server_num)
+            .map_err(|err| DedicatedServerError::PortOutOfRange(err.to_string()))?;
         let group_name = group.name.clone();
         let server_name = format!("{}-{}", &group_name, &server_num);
         let instance: MCSInstance = MCSInstance::new(
-            server_name,
+            server_name.clone(),
             group.name.clone(),
-            group.port_section + (server_num as u16),
+            port,
             group.region.clone(),
             None,
         );
@@ -148,42 +404,207 @@ impl DedicatedServer {
             let new_vec: Vec<MCSInstance> = Vec::from([instance]);
             self.server_instances.insert(group_name, new_vec);
         }
+        let before = self.get_available_resources();
         self.available_ram -= group.ram as i16;
         self.available_cpu -= group.cpu as i16;
-        Ok(())
+        if let Some(disk) = self.disk.as_mut() {
+            disk.free_mb -= disk.per_instance_mb;
+        }
+        Ok(InstanceOutcome {
+            change: InstanceChange::Added,
+            instance: server_name,
+            group: group.name.clone(),
+            port,
+            node: self.name.clone(),
+            before,
+            after: self.get_available_resources(),
+        })
     }
 
     pub fn remove_server(
         &mut self,
         group: &ServerGroup,
         server_num: usize,
-    ) -> Result<(), DedicatedServerError> {
+    ) -> Result<InstanceOutcome, DedicatedServerError> {
         let Some(vec) = self.server_instances.get_mut(&group.name) else {
             return Err(DedicatedServerError::ZeroInstancesRunning(
                 format!("Dedicated Server ({:?}) cannot remove server because zero instances under {:?} were found",
                 self.name, group.name
             )));
         };
-        vec.sort_by(|a, b| a.get_server_num().cmp(&b.get_server_num()));
+        vec.sort_by_key(|mcs| mcs.get_server_num());
 
-        if let Some(idx) = vec
+        let removed = if let Some(idx) = vec
             .iter()
             .position(|mcs| mcs.get_server_num() == server_num)
         {
-            vec.swap_remove(idx);
+            vec.swap_remove(idx)
         } else {
             let server_name: String = format!("{}-{}", group.name, server_num);
             return Err(DedicatedServerError::InstanceNotFound(format!(
                 "Dedicated Server ({:?}) cannot remove {:?} because this instance was not found",
                 self.name, server_name
             )));
-        }
+        };
+        let before = self.get_available_resources();
         self.available_ram += group.ram as i16;
         self.available_cpu += group.cpu as i16;
+        if let Some(disk) = self.disk.as_mut() {
+            disk.free_mb += disk.per_instance_mb;
+        }
+        Ok(InstanceOutcome {
+            change: InstanceChange::Removed,
+            instance: removed.get_name().clone(),
+            group: group.name.clone(),
+            port: removed.get_port(),
+            node: self.name.clone(),
+            before,
+            after: self.get_available_resources(),
+        })
+    }
+
+    pub fn kill_server(
+        &mut self,
+        group: &ServerGroup,
+        server_num: usize,
+        force: bool,
+        ctx: &mut ContextManager,
+    ) -> Result<(), SafetyError> {
+        //! Kills a server instance running on this dedicated server and drops its status.
+        //! Servers with players online are only killed when `force` is passed.
+        //! With rcon configured for the group, the server saves its worlds first.
+        let server_name = format!("{}-{}", group.name, server_num);
+        handshake::ensure_compatible(&format!("Killing {}", server_name), ctx)?;
+        let impact = Impact::of_instance(&server_name, &self.region, ctx);
+        if !force && impact.has_players() {
+            return Err(SafetyError::RequiresForce(
+                format!("Killing {}", server_name),
+                impact,
+            ));
+        }
+        let entry = JournalEntry::begin(
+            Operation::Kill {
+                node: self.name.clone(),
+                group: group.name.clone(),
+                server_num,
+            },
+            ctx,
+        )
+        .map_err(|err| SafetyError::OperationFailed(err.to_string()))?;
+        let status = MinecraftServer::get_status(&server_name, &self.region, ctx).ok();
+        if let Some(server) = status.as_ref() {
+            // save worlds first when the console is reachable
+            match rcon::run(server, &["save-all"], ctx) {
+                Ok(_) | Err(RconError::NotConfigured(_)) => {}
+                Err(err) => log::warn!("{} was not saved before the kill: {}", server_name, err),
+            }
+        }
+        self.run_kill_script(&server_name, ctx)
+            .map_err(|err| SafetyError::OperationFailed(err.to_string()))?;
+        MinecraftServer::delete_status(&server_name, &self.region, ctx)
+            .map_err(|err| SafetyError::OperationFailed(err.to_string()))?;
+        if let Some(server) = status.as_ref() {
+            if let Err(err) = counters::decrement(&group.prefix, server.is_joinable(), ctx) {
+                log::warn!("{} was not uncounted: {}", server_name, err);
+            }
+        }
+        if self.get_server_nums(group).contains(&server_num) {
+            let outcome = self
+                .remove_server(group, server_num)
+                .map_err(|err| SafetyError::OperationFailed(err.to_string()))?;
+            Event::from(outcome.clone()).emit(ctx);
+            for hooks in ctx.get_hooks() {
+                hooks.on_kill(&outcome);
+            }
+        }
+        entry
+            .complete(ctx)
+            .map_err(|err| SafetyError::OperationFailed(err.to_string()))
+    }
+
+    pub(crate) fn run_kill_script(
+        &self,
+        server_name: &String,
+        ctx: &mut ContextManager,
+    ) -> Result<(), DedicatedServerError> {
+        let script = Path::new(ctx.get_config().monitor_info.get_scripts_path()).join(KILL_SCRIPT);
+        let status = Command::new("/bin/sh")
+            .arg(script)
+            .arg(&self.private_address)
+            .arg(server_name)
+            .status()
+            .map_err(|err| {
+                DedicatedServerError::ProcessError(format!("{}: {:?}", server_name, err))
+            })?;
+        if !status.success() {
+            return Err(DedicatedServerError::ProcessError(format!(
+                "{}: kill script exited with {}",
+                server_name, status
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn get_total_instance_count(&self) -> usize {
+        self.server_instances.values().map(|vec| vec.len()).sum()
+    }
+
+    pub fn check_placement(
+        &self,
+        group: &ServerGroup,
+        server_num: Option<usize>,
+    ) -> Result<(), DedicatedServerError> {
+        //! Checks whether another instance of `group` can be placed on this dedicated server.
+        //! With a `server_num`, its exact port is checked against the node's port range,
+        //! otherwise the group's port section only has to overlap it.
+        //! Resources reserved for other groups don't count as free, and neither does ram
+        //! the node's agent reports in use. The instance must leave the minimum free disk.
+        let available = self.get_available_resources_for(group);
+        if available.ram < (group.ram as i16) || available.cpu < (group.cpu as i16) {
+            let reserved = match self.get_reserved(Some(group)) {
+                NodeResources { ram: 0, cpu: 0 } => String::new(),
+                held => format!(
+                    ", {}MB ram and {} cpu are reserved for other groups",
+                    held.ram, held.cpu
+                ),
+            };
+            return Err(DedicatedServerError::StorageError(format!(
+                "Dedicated Server ({:?}) has no space for server {:?}{} (try another dedicated server)",
+                self.name, group.name, reserved
+            )));
+        }
+        self.check_telemetry(group)?;
+        self.check_disk(group)?;
+        if let Some(max) = self.max_instances {
+            let count = self.get_total_instance_count();
+            if count >= max {
+                return Err(DedicatedServerError::InstanceLimitReached(format!(
+                    "Dedicated Server ({:?}) already runs {} instances (max {})",
+                    self.name, count, max
+                )));
+            }
+        }
+        let section = group.get_port_section();
+        let port = server_num
+            .map(|num| section.port_for(num))
+            .transpose()
+            .map_err(|err| DedicatedServerError::PortOutOfRange(err.to_string()))?;
+        if let Some((low, high)) = self.port_range {
+            let fits = match port {
+                Some(port) => low <= port && port <= high,
+                None => section.overlaps_range(low, high),
+            };
+            if !fits {
+                return Err(DedicatedServerError::PortOutOfRange(format!(
+                    "Dedicated Server ({:?}) only hosts ports {}-{}, {:?} uses port section {}",
+                    self.name, low, high, group.name, section
+                )));
+            }
+        }
         Ok(())
     }
 
     pub fn has_space_for(&self, group: &ServerGroup) -> bool {
-        self.available_ram >= (group.ram as i16) && self.available_cpu >= (group.cpu as i16)
+        self.check_placement(group, None).is_ok()
     }
 }
@@ -0,0 +1,135 @@
+use std::{
+    net::IpAddr,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::server::server_group::ServerGroup;
+
+use super::server::DedicatedServer;
+
+/// Starts a server on the node it runs on and detaches it, printing `pid=<pid>`:
+/// `<server> <group> <port> <ram MB> <jar> <world zip> <plugin> <config path>`.
+pub const START_SCRIPT: &str = "easyStartServer.sh";
+
+/// What a launch hands the `ServerLauncher` once the instance has its slot on the node.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SpawnRequest {
+    pub node: String,
+    pub address: String, // the node's private address
+    pub local: bool,     // the node is this host, see `DedicatedServer::is_local`
+    pub script: PathBuf, // start script, under `monitor_info.scripts_path`
+    pub server_name: String,
+    pub group: String,
+    pub port: u16,
+    pub ram: u16,
+    pub jar: String,
+    pub world_zip: String,
+    pub plugin: String,
+    pub config_path: String,
+}
+
+impl SpawnRequest {
+    pub fn new(
+        node: &DedicatedServer,
+        group: &ServerGroup,
+        server_name: &str,
+        port: u16,
+        scripts_path: &str,
+    ) -> Self {
+        Self {
+            node: node.name.clone(),
+            address: node.private_address.clone(),
+            local: node.is_local(),
+            script: Path::new(scripts_path).join(START_SCRIPT),
+            server_name: server_name.into(),
+            group: group.name.clone(),
+            port,
+            ram: group.ram,
+            jar: group.get_server_jar(),
+            world_zip: group.world_zip.clone(),
+            plugin: group.plugin.clone(),
+            config_path: group.config_path.clone(),
+        }
+    }
+
+    pub fn get_args(&self) -> Vec<String> {
+        //! The start script's arguments, see `START_SCRIPT`.
+        vec![
+            self.server_name.clone(),
+            self.group.clone(),
+            self.port.to_string(),
+            self.ram.to_string(),
+            self.jar.clone(),
+            self.world_zip.clone(),
+            self.plugin.clone(),
+            self.config_path.clone(),
+        ]
+    }
+}
+
+/// Spawns the process of a server being launched and returns what it printed, which
+/// should include `pid=<pid>`. The built-in `ScriptLauncher` runs the start script;
+/// tests register their own with `ContextManager::set_server_launcher` to launch
+/// without processes.
+pub trait ServerLauncher: Send + Sync {
+    fn spawn(&self, request: &SpawnRequest) -> Result<String, String>;
+}
+
+/// Built-in launcher: the start script, run directly for the local node and over ssh
+/// (with the same path) for the others.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ScriptLauncher;
+
+impl ServerLauncher for ScriptLauncher {
+    fn spawn(&self, request: &SpawnRequest) -> Result<String, String> {
+        let output = match request.local {
+            true => Command::new("/bin/sh")
+                .arg(&request.script)
+                .args(request.get_args())
+                .output(),
+            false => {
+                // ssh joins the remote command into one line for the node's shell
+                let remote: Vec<String> = [
+                    "/bin/sh".to_string(),
+                    request.script.to_string_lossy().into(),
+                ]
+                .into_iter()
+                .chain(request.get_args())
+                .map(|arg| shell_quote(&arg))
+                .collect();
+                Command::new("ssh")
+                    .args(["-o", "BatchMode=yes"])
+                    .arg(&request.address)
+                    .arg(remote.join(" "))
+                    .output()
+            }
+        }
+        .map_err(|err| format!("{}: {:?}", request.script.display(), err))?;
+        if !output.status.success() {
+            return Err(format!(
+                "{} on {} exited with {}: {}",
+                request.script.display(),
+                request.node,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+}
+
+fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+impl DedicatedServer {
+    pub fn is_local(&self) -> bool {
+        //! Whether the node is the host the manager runs on, by its private address.
+        self.private_address == "localhost"
+            || self
+                .private_address
+                .parse::<IpAddr>()
+                .is_ok_and(|address| address.is_loopback())
+    }
+}
@@ -0,0 +1,213 @@
+use std::{collections::HashMap, fmt::Display};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+use crate::{
+    context_manager::ContextManager,
+    events::{Event, EventKind},
+    region::Region,
+};
+
+use super::{collection::DedicatedServers, recovery::scan_live};
+
+/// Hash of server name -> `Stray`, live servers no node's bookkeeping tracks.
+const STRAYS_KEY: &str = "servermonitor.strays";
+
+/// What an operator decided to do with a stray.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, Eq, PartialEq, Display)]
+pub enum StrayDecision {
+    #[default]
+    Pending,
+    Adopt,     // track it on its node (reserving its resources) at the next reconcile
+    Unmanaged, // leave it running untracked, without further warnings
+}
+
+/// A server that reports a status but was launched outside the manager (usually by hand),
+/// so no node accounts for it.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct Stray {
+    pub server: String,
+    pub group: String,
+    pub region: Region,
+    pub address: String,
+    pub port: u16,
+    pub node: Option<String>, // matched by address
+    pub decision: StrayDecision,
+    pub detected_at: i64, // seconds since epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>, // why the last adoption failed
+}
+
+/// What one pass over a region's statuses found.
+#[derive(Debug, Default)]
+pub struct StrayReport {
+    pub detected: Vec<Stray>,
+    pub adopted: Vec<(String, String)>, // (server, node)
+    pub failed: Vec<(String, String)>,  // (server, why it couldn't be adopted)
+    pub gone: Vec<String>,              // strays that stopped or got tracked since
+}
+
+impl Display for Stray {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}) at {}:{} on {}, {}",
+            self.server,
+            self.group,
+            self.address,
+            self.port,
+            self.node.as_deref().unwrap_or("no known node"),
+            self.decision
+        )
+    }
+}
+
+impl Display for StrayReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} stray(s) detected, {} adopted, {} failed, {} gone",
+            self.detected.len(),
+            self.adopted.len(),
+            self.failed.len(),
+            self.gone.len()
+        )
+    }
+}
+
+impl Stray {
+    pub fn get_all(ctx: &mut ContextManager) -> Result<Vec<Self>, redis::RedisError> {
+        let raw: Vec<String> = redis::cmd("HVALS")
+            .arg(STRAYS_KEY)
+            .query(ctx.get_connection())?;
+        let mut strays: Vec<Self> = raw
+            .iter()
+            .filter_map(|raw| serde_json::from_str(raw).ok())
+            .collect();
+        strays.sort_by(|a, b| a.server.cmp(&b.server));
+        Ok(strays)
+    }
+
+    fn save(&self, ctx: &mut ContextManager) -> Result<(), redis::RedisError> {
+        let raw = serde_json::to_string(self).map_err(|err| {
+            redis::RedisError::from((
+                redis::ErrorKind::ClientError,
+                "Stray serialization error",
+                err.to_string(),
+            ))
+        })?;
+        redis::cmd("HSET")
+            .arg(STRAYS_KEY)
+            .arg(&self.server)
+            .arg(raw)
+            .query(ctx.get_connection())
+    }
+
+    fn delete(server: &str, ctx: &mut ContextManager) -> Result<(), redis::RedisError> {
+        redis::cmd("HDEL")
+            .arg(STRAYS_KEY)
+            .arg(server)
+            .query(ctx.get_connection())
+    }
+
+    pub fn decide(
+        server: &str,
+        decision: StrayDecision,
+        ctx: &mut ContextManager,
+    ) -> Result<Option<Self>, redis::RedisError> {
+        //! Records what to do with a detected stray; the monitor acts on it at its next
+        //! reconcile. None if no such stray was detected.
+        let mut strays = Self::get_all(ctx)?;
+        let Some(stray) = strays.iter_mut().find(|stray| stray.server == server) else {
+            return Ok(None);
+        };
+        stray.decision = decision;
+        stray.last_error = None;
+        stray.save(ctx)?;
+        Ok(Some(stray.clone()))
+    }
+}
+
+impl DedicatedServers {
+    pub fn is_tracked(&self, server: &str) -> bool {
+        self.servers.iter().any(|ds| {
+            ds.server_instances
+                .values()
+                .flatten()
+                .any(|instance| instance.get_name() == server)
+        })
+    }
+
+    pub fn handle_strays(
+        &mut self,
+        region: Option<&Region>,
+        ctx: &mut ContextManager,
+    ) -> Result<StrayReport, redis::RedisError> {
+        //! Records live servers of the region (or all) that no node tracks, adopts the ones
+        //! an operator chose to adopt and forgets strays that stopped or got tracked since.
+        //! Each newly detected stray is reported as a Warning event.
+        let (live, _) = scan_live(region, ctx);
+        let mut known: HashMap<String, Stray> = Stray::get_all(ctx)?
+            .into_iter()
+            .filter(|stray| region.is_none_or(|region| &stray.region == region))
+            .map(|stray| (stray.server.clone(), stray))
+            .collect();
+        let mut report = StrayReport::default();
+        for (region, server) in live {
+            let name = server.get_name().clone();
+            if self.is_tracked(&name) {
+                continue;
+            }
+            match known.remove(&name) {
+                Some(mut stray) if stray.decision == StrayDecision::Adopt => {
+                    match self.adopt(&server, &region, ctx) {
+                        Ok(node) => {
+                            Stray::delete(&name, ctx)?;
+                            report.adopted.push((name, node));
+                        }
+                        Err(why) => {
+                            // back to the operator rather than retrying every reconcile
+                            stray.decision = StrayDecision::Pending;
+                            stray.last_error = Some(why.clone());
+                            stray.save(ctx)?;
+                            let message = format!("could not be adopted: {}", why);
+                            Event::new(EventKind::Warning, &name, message).emit(ctx);
+                            report.failed.push((name, why));
+                        }
+                    }
+                }
+                Some(_) => (),
+                None => {
+                    let stray = Stray {
+                        server: name.clone(),
+                        group: server.get_group().clone(),
+                        node: ctx
+                            .get_resolver()
+                            .resolve(server.get_public_address(), &region)
+                            .cloned(),
+                        region,
+                        address: server.get_public_address().clone(),
+                        port: server.get_port(),
+                        decision: StrayDecision::Pending,
+                        detected_at: Local::now().timestamp(),
+                        last_error: None,
+                    };
+                    stray.save(ctx)?;
+                    let message = format!(
+                        "running but not tracked by any node, launched outside the manager ({})",
+                        stray
+                    );
+                    Event::new(EventKind::Warning, &name, message).emit(ctx);
+                    report.detected.push(stray);
+                }
+            }
+        }
+        for server in known.into_keys() {
+            Stray::delete(&server, ctx)?;
+            report.gone.push(server);
+        }
+        Ok(report)
+    }
+}
@@ -0,0 +1,238 @@
+use std::{collections::HashMap, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    context_manager::ContextManager,
+    server::server_group::ServerGroup,
+    store::{entity::RedisEntity, keys::KeyBuilder},
+};
+
+use super::{
+    collection::DedicatedServers,
+    disk::DiskSpace,
+    server::{DedicatedServer, DedicatedServerError},
+};
+
+const TELEMETRY_PREFIX: &str = "servermonitor.telemetry.";
+
+/// Limits on what a node's agent reports before placement stops using it. Nodes without
+/// (fresh) telemetry are placed on bookkeeping alone, as before.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct TelemetryInfo {
+    #[serde(default = "default_max_age")]
+    pub max_age_secs: i64, // older readings are ignored, the agent is assumed dead
+    #[serde(default = "default_max_load_per_cpu")]
+    pub max_load_per_cpu: f64, // 5 minute load average over the node's cpu count
+    #[serde(default = "default_min_disk_free")]
+    pub min_disk_free_mb: i64,
+    #[serde(default = "default_disk_per_instance")]
+    pub disk_per_instance_mb: i64, // counted against free disk for each instance placed
+}
+
+fn default_max_age() -> i64 {
+    90
+}
+
+fn default_max_load_per_cpu() -> f64 {
+    1.5
+}
+
+fn default_min_disk_free() -> i64 {
+    2048
+}
+
+fn default_disk_per_instance() -> i64 {
+    512
+}
+
+impl Default for TelemetryInfo {
+    fn default() -> Self {
+        Self {
+            max_age_secs: default_max_age(),
+            max_load_per_cpu: default_max_load_per_cpu(),
+            min_disk_free_mb: default_min_disk_free(),
+            disk_per_instance_mb: default_disk_per_instance(),
+        }
+    }
+}
+
+/// What the agent on a node last reported, read from `servermonitor.telemetry.<node>`.
+///
+/// The agent (a daemon, or just a cron script) writes the hash fields `node`, `cpus`,
+/// `ramTotal`, `ramFree`, `diskTotal`, `diskFree` (MB), `load1`, `load5`, `load15` and
+/// `timestamp` (seconds since epoch), optionally `agent` (its version), then sets an
+/// expiry a few times its interval, e.g. every 30 seconds:
+///
+/// `redis-cli HSET servermonitor.telemetry.$NODE node $NODE cpus $(nproc) ... timestamp $(date +%s)`
+/// `redis-cli EXPIRE servermonitor.telemetry.$NODE 90`
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeTelemetry {
+    pub node: String, // the node's name in config.toml
+    pub cpus: i64,
+    pub ram_total: i64,
+    pub ram_free: i64,
+    pub disk_total: i64,
+    pub disk_free: i64,
+    pub load: (f64, f64, f64), // 1, 5 and 15 minute load averages
+    pub timestamp: i64,
+    pub agent: String,
+}
+
+// loads are parsed from the agent's numbers and never NaN
+impl Eq for NodeTelemetry {}
+
+impl Display for NodeTelemetry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: load {:.2}/{:.2}/{:.2} on {} cpus, {}/{}MB ram free, {}/{}MB disk free",
+            self.node,
+            self.load.0,
+            self.load.1,
+            self.load.2,
+            self.cpus,
+            self.ram_free,
+            self.ram_total,
+            self.disk_free,
+            self.disk_total
+        )
+    }
+}
+
+impl RedisEntity for NodeTelemetry {
+    type Error = String;
+
+    fn get_id(&self) -> String {
+        self.node.clone()
+    }
+
+    fn from_map(map: HashMap<String, String>) -> Result<Self, Self::Error> {
+        let get = |key: &str| {
+            map.get(key)
+                .cloned()
+                .ok_or(format!("telemetry is missing {:?}", key))
+        };
+        let parse_int = |key: &str| {
+            get(key)?
+                .trim()
+                .parse::<i64>()
+                .map_err(|err| format!("telemetry {:?}: {:?}", key, err))
+        };
+        let parse_load = |key: &str| {
+            get(key)?
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|load| load.is_finite() && *load >= 0.0)
+                .ok_or(format!("telemetry {:?} is not a load average", key))
+        };
+        Ok(Self {
+            node: get("node")?,
+            cpus: parse_int("cpus")?.max(1),
+            ram_total: parse_int("ramTotal")?,
+            ram_free: parse_int("ramFree")?,
+            disk_total: parse_int("diskTotal")?,
+            disk_free: parse_int("diskFree")?,
+            load: (
+                parse_load("load1")?,
+                parse_load("load5")?,
+                parse_load("load15")?,
+            ),
+            timestamp: parse_int("timestamp")?,
+            agent: map.get("agent").cloned().unwrap_or_default(),
+        })
+    }
+
+    fn to_map(&self) -> HashMap<String, String> {
+        HashMap::from([
+            ("node".into(), self.node.clone()),
+            ("cpus".into(), self.cpus.to_string()),
+            ("ramTotal".into(), self.ram_total.to_string()),
+            ("ramFree".into(), self.ram_free.to_string()),
+            ("diskTotal".into(), self.disk_total.to_string()),
+            ("diskFree".into(), self.disk_free.to_string()),
+            ("load1".into(), self.load.0.to_string()),
+            ("load5".into(), self.load.1.to_string()),
+            ("load15".into(), self.load.2.to_string()),
+            ("timestamp".into(), self.timestamp.to_string()),
+            ("agent".into(), self.agent.clone()),
+        ])
+    }
+
+    fn get_key(id: &str, _keys: &KeyBuilder) -> String {
+        format!("{}{}", TELEMETRY_PREFIX, id)
+    }
+
+    fn get_pattern(_keys: &KeyBuilder) -> String {
+        format!("{}*", TELEMETRY_PREFIX)
+    }
+}
+
+impl NodeTelemetry {
+    pub fn get_load_per_cpu(&self) -> f64 {
+        self.load.1 / self.cpus as f64
+    }
+
+    pub fn check(&self, info: &TelemetryInfo) -> Option<String> {
+        //! Why the node shouldn't get new instances, if its load is past the limit.
+        //! Disk space is checked per placement, see `DiskSpace`.
+        match self.get_load_per_cpu() > info.max_load_per_cpu {
+            true => Some(format!(
+                "load {:.2} per cpu is over {:.2}",
+                self.get_load_per_cpu(),
+                info.max_load_per_cpu
+            )),
+            false => None,
+        }
+    }
+}
+
+impl DedicatedServer {
+    pub fn check_telemetry(&self, group: &ServerGroup) -> Result<(), DedicatedServerError> {
+        //! Refuses nodes whose agent reports them overloaded, or with less free ram than
+        //! the group needs, whatever the bookkeeping says (e.g. other processes use it).
+        if let Some(reason) = self.overloaded.as_ref() {
+            return Err(DedicatedServerError::NodeUnhealthy(format!(
+                "Dedicated Server ({:?}) {}",
+                self.name, reason
+            )));
+        }
+        match self.telemetry.as_ref() {
+            Some(telemetry) if telemetry.ram_free < (group.ram as i64) => {
+                Err(DedicatedServerError::StorageError(format!(
+                    "Dedicated Server ({:?}) has {}MB ram free according to its agent, {:?} needs {}MB",
+                    self.name, telemetry.ram_free, group.name, group.ram
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl DedicatedServers {
+    pub fn merge_telemetry(&mut self, ctx: &mut ContextManager) -> Vec<(String, String)> {
+        //! Attaches each node's latest telemetry, dropping readings older than `max_age_secs`
+        //! and those of nodes that aren't configured. Returns the keys that couldn't be read.
+        let info = ctx.get_config().telemetry.clone();
+        let now = ctx.get_clock().now().timestamp();
+        let readings = NodeTelemetry::get_all(ctx);
+        for ds in self.servers.iter_mut() {
+            ds.telemetry = readings
+                .ok
+                .iter()
+                .find(|telemetry| telemetry.node == ds.name)
+                .filter(|telemetry| now - telemetry.timestamp <= info.max_age_secs)
+                .cloned();
+            ds.overloaded = ds
+                .telemetry
+                .as_ref()
+                .and_then(|telemetry| telemetry.check(&info));
+            ds.disk = ds
+                .telemetry
+                .as_ref()
+                .map(|telemetry| DiskSpace::from_telemetry(telemetry, &info));
+        }
+        readings.failed
+    }
+}
@@ -0,0 +1,57 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+};
+
+use super::server_group::ServerGroup;
+
+/// A hash field (or label) that differs between two versions of a group or node, with its
+/// value on each side as stored in redis. Missing values are empty.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FieldDiff {
+    pub name: String,
+    pub left: String,
+    pub right: String,
+}
+
+impl Display for FieldDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {:?} -> {:?}", self.name, self.left, self.right)
+    }
+}
+
+pub fn diff_maps(
+    left: &BTreeMap<String, String>,
+    right: &BTreeMap<String, String>,
+) -> Vec<FieldDiff> {
+    //! Fields whose values differ between the two maps, sorted by name.
+    let names: BTreeSet<&String> = left.keys().chain(right.keys()).collect();
+    names
+        .into_iter()
+        .filter(|name| left.get(*name) != right.get(*name))
+        .map(|name| FieldDiff {
+            name: name.clone(),
+            left: left.get(name).cloned().unwrap_or_default(),
+            right: right.get(name).cloned().unwrap_or_default(),
+        })
+        .collect()
+}
+
+pub fn format_diffs(diffs: &[FieldDiff]) -> String {
+    //! The diffs on one line, e.g. for event messages.
+    diffs
+        .iter()
+        .map(|diff| diff.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+impl ServerGroup {
+    pub fn diff(&self, other: &ServerGroup) -> Vec<FieldDiff> {
+        //! Hash fields that differ from `self` (left) to `other` (right), sorted by name.
+        diff_maps(
+            &self.to_hashmap().into_iter().collect(),
+            &other.to_hashmap().into_iter().collect(),
+        )
+    }
+}
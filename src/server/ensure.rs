@@ -0,0 +1,134 @@
+use std::fmt::Display;
+
+use redis::{ErrorKind, RedisError};
+
+use crate::{
+    context_manager::ContextManager,
+    events::{Event, EventKind},
+    store::entity::RedisEntity,
+    undo::GroupChange,
+};
+
+use super::{
+    diff::{format_diffs, FieldDiff},
+    server_group::ServerGroup,
+};
+
+/// Fields `ensure` and `group set` refuse to change on an existing group unless allowed,
+/// when the config doesn't list its own.
+pub const DEFAULT_PROTECTED_FIELDS: [&str; 3] = ["portSection", "region", "plugin"];
+
+pub fn default_protected_fields() -> Vec<String> {
+    DEFAULT_PROTECTED_FIELDS
+        .iter()
+        .map(|field| field.to_string())
+        .collect()
+}
+
+pub fn check_protected(
+    prefix: &str,
+    changed: &[FieldDiff],
+    ctx: &mut ContextManager,
+) -> Result<(), RedisError> {
+    //! Refuses changes to the configured protected fields of an existing group.
+    let protected = &ctx.get_config().protected_fields;
+    let changed: Vec<FieldDiff> = changed
+        .iter()
+        .filter(|diff| protected.contains(&diff.name))
+        .cloned()
+        .collect();
+    if changed.is_empty() {
+        return Ok(());
+    }
+    Err(RedisError::from((
+        ErrorKind::ClientError,
+        "Protected fields",
+        format!(
+            "servergroups.{} {} (--allow-protected to change them)",
+            prefix,
+            format_diffs(&changed)
+        ),
+    )))
+}
+
+/// What `ServerGroup::ensure` had to do.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum EnsureOutcome {
+    Created,
+    Updated(Vec<FieldDiff>), // changed hash fields
+    Unchanged,
+}
+
+impl Display for EnsureOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnsureOutcome::Created => write!(f, "created"),
+            EnsureOutcome::Updated(diffs) => write!(f, "updated ({})", format_diffs(diffs)),
+            EnsureOutcome::Unchanged => write!(f, "unchanged"),
+        }
+    }
+}
+
+impl ServerGroup {
+    pub fn ensure(
+        desired: &ServerGroup,
+        allow_protected: bool,
+        ctx: &mut ContextManager,
+    ) -> Result<EnsureOutcome, RedisError> {
+        //! Makes the cached group match `desired`: creates it if missing, otherwise updates
+        //! the fields that differ. Changing a protected field is refused unless
+        //! `allow_protected`. Running it twice changes nothing.
+        //! A cached group that can't be parsed is an error rather than overwritten.
+        let (outcome, updated) = Self::merge_cached(desired, allow_protected, ctx)?;
+        match (&outcome, updated) {
+            (EnsureOutcome::Created, _) => {
+                desired.clone().create(ctx)?;
+                Event::new(EventKind::GroupUpdated, &desired.prefix, "created".into()).emit(ctx);
+            }
+            (EnsureOutcome::Updated(_), Some(updated)) => {
+                updated.validate(ctx.get_config())?;
+                let cached = Self::get(&desired.prefix, ctx)?;
+                updated.save(ctx)?;
+                match GroupChange::between(&cached, &updated) {
+                    Some(change) => change.emit("ensured", ctx),
+                    None => Event::new(
+                        EventKind::GroupUpdated,
+                        &desired.prefix,
+                        outcome.to_string(),
+                    )
+                    .emit(ctx),
+                }
+            }
+            _ => {}
+        }
+        Ok(outcome)
+    }
+
+    pub fn plan_ensure(
+        desired: &ServerGroup,
+        allow_protected: bool,
+        ctx: &mut ContextManager,
+    ) -> Result<EnsureOutcome, RedisError> {
+        //! What `ensure` would do, without writing anything.
+        Ok(Self::merge_cached(desired, allow_protected, ctx)?.0)
+    }
+
+    fn merge_cached(
+        desired: &ServerGroup,
+        allow_protected: bool,
+        ctx: &mut ContextManager,
+    ) -> Result<(EnsureOutcome, Option<ServerGroup>), RedisError> {
+        if !Self::exists(&desired.prefix, ctx) {
+            return Ok((EnsureOutcome::Created, None));
+        }
+        let changed = Self::get(&desired.prefix, ctx)?.diff(desired);
+        if !allow_protected {
+            check_protected(&desired.prefix, &changed, ctx)?;
+        }
+        if changed.is_empty() {
+            return Ok((EnsureOutcome::Unchanged, None));
+        }
+        let updated = Self::from_hashmap(desired.to_hashmap())?;
+        Ok((EnsureOutcome::Updated(changed), Some(updated)))
+    }
+}
@@ -0,0 +1,239 @@
+use std::{fmt::Display, str::FromStr, time::Duration};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    context_manager::ContextManager,
+    events::{Event, EventKind},
+    game::r#type::GameType,
+    region::Region,
+    store::entity::RedisEntity,
+};
+
+use super::{
+    presets::{Preset, SizeTier},
+    server_group::ServerGroup,
+};
+
+/// Hash of group -> running event server.
+const EVENT_SERVERS_KEY: &str = "servermonitor.eventservers";
+/// List of finished event servers, newest first.
+const ARCHIVE_KEY: &str = "servermonitor.eventservers.archive";
+const ARCHIVE_LENGTH: isize = 100;
+const NAME_PREFIX: &str = "EVT";
+
+#[derive(Error, Debug)]
+pub enum EventServerError {
+    #[error("Event Server Error: Invalid event: `{0}`")]
+    InvalidEvent(String),
+    #[error("Event Server Error: Redis Error: `{0}`")]
+    RedisError(String),
+}
+
+/// A one-off, whitelisted group for a hosted event. It is archived (its group deleted and
+/// the record moved to the archive) by the monitor once `expires_at` passes.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct EventServer {
+    pub group: String,
+    pub game: String,
+    pub map: String,
+    pub host_rank: String,
+    pub created_at: i64,         // seconds since epoch
+    pub expires_at: i64,         // seconds since epoch
+    pub address: Option<String>, // where players connect, if a node had room for it
+    #[serde(default)]
+    pub archived_at: Option<i64>,
+}
+
+impl Display for EventServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let time = |at: i64| {
+            chrono::DateTime::from_timestamp(at, 0)
+                .map(|time| time.with_timezone(&Local).to_rfc3339())
+                .unwrap_or(at.to_string())
+        };
+        write!(
+            f,
+            "{} ({} on {}, host rank {}) at {}",
+            self.group,
+            self.game,
+            self.map,
+            self.host_rank,
+            self.address.as_deref().unwrap_or("<no node available>")
+        )?;
+        match self.archived_at {
+            Some(at) => write!(f, ", archived {}", time(at)),
+            None => write!(f, ", until {}", time(self.expires_at)),
+        }
+    }
+}
+
+fn to_redis_error(err: redis::RedisError) -> EventServerError {
+    EventServerError::RedisError(err.to_string())
+}
+
+fn get_free_name(ctx: &mut ContextManager) -> String {
+    //! Lowest `EVT<n>` no group uses yet.
+    let mut num = 1;
+    loop {
+        let name = format!("{}{}", NAME_PREFIX, num);
+        if !ServerGroup::exists(&name, ctx) {
+            return name;
+        }
+        num += 1;
+    }
+}
+
+pub fn create_event_server(
+    game: &str,
+    map: &str,
+    host_rank: &str,
+    duration: Duration,
+    ctx: &mut ContextManager,
+) -> Result<EventServer, EventServerError> {
+    //! Creates a staff-whitelisted group running one instance of `game` on `map`, to be
+    //! archived after `duration`. The connect address is the node placement would pick
+    //! for its first instance right now.
+    let game = GameType::from_str(game)
+        .map_err(|_| EventServerError::InvalidEvent(format!("unknown game {:?}", game)))?;
+    if map.is_empty() || host_rank.is_empty() || duration.is_zero() {
+        return Err(EventServerError::InvalidEvent(
+            "map, host rank and duration are required".into(),
+        ));
+    }
+    let name = get_free_name(ctx);
+    let mut group = ServerGroup {
+        games: Some(game.to_string()),
+        world_zip: map.into(),
+        staff_only: true,
+        total_servers: 1,
+        joinable_servers: 1,
+        ..Preset::Event.to_server_group(&name, Region::default(), SizeTier::default())
+    };
+    group.create(ctx).map_err(to_redis_error)?;
+    let address = ctx.with_dedicated_servers(|servers, ctx| {
+        let server_num = servers.get_next_server_num(&group);
+        servers
+            .place_with(&group, ctx.get_placement_strategy())
+            .ok()
+            .map(|node| {
+                format!(
+                    "{}:{}",
+                    node.public_address,
+                    group.port_section as usize + server_num
+                )
+            })
+    });
+    let now = Local::now().timestamp();
+    let event_server = EventServer {
+        group: name,
+        game: game.to_string(),
+        map: map.into(),
+        host_rank: host_rank.into(),
+        created_at: now,
+        expires_at: now + duration.as_secs() as i64,
+        address,
+        archived_at: None,
+    };
+    event_server.save(ctx)?;
+    Event::new(
+        EventKind::GroupUpdated,
+        &event_server.group,
+        format!("event server created: {}", event_server),
+    )
+    .emit(ctx);
+    Ok(event_server)
+}
+
+impl EventServer {
+    fn to_json(&self) -> Result<String, EventServerError> {
+        serde_json::to_string(self).map_err(|err| EventServerError::RedisError(err.to_string()))
+    }
+
+    fn save(&self, ctx: &mut ContextManager) -> Result<(), EventServerError> {
+        redis::cmd("HSET")
+            .arg(EVENT_SERVERS_KEY)
+            .arg(&self.group)
+            .arg(self.to_json()?)
+            .query(ctx.get_connection())
+            .map_err(to_redis_error)
+    }
+
+    pub fn get_all(ctx: &mut ContextManager) -> Result<Vec<Self>, EventServerError> {
+        //! Running event servers, soonest to expire first.
+        let raw: Vec<String> = redis::cmd("HVALS")
+            .arg(EVENT_SERVERS_KEY)
+            .query(ctx.get_connection())
+            .map_err(to_redis_error)?;
+        let mut event_servers: Vec<Self> = raw
+            .iter()
+            .filter_map(|raw| serde_json::from_str(raw).ok())
+            .collect();
+        event_servers.sort_by_key(|event_server| event_server.expires_at);
+        Ok(event_servers)
+    }
+
+    pub fn get_archived(
+        count: usize,
+        ctx: &mut ContextManager,
+    ) -> Result<Vec<Self>, EventServerError> {
+        let raw: Vec<String> = redis::cmd("LRANGE")
+            .arg(ARCHIVE_KEY)
+            .arg(0)
+            .arg(count.saturating_sub(1))
+            .query(ctx.get_connection())
+            .map_err(to_redis_error)?;
+        Ok(raw
+            .iter()
+            .filter_map(|raw| serde_json::from_str(raw).ok())
+            .collect())
+    }
+
+    pub fn archive(mut self, ctx: &mut ContextManager) -> Result<Self, EventServerError> {
+        //! Deletes the event's group, even with players still on it, and moves its record
+        //! to the archive.
+        if ServerGroup::exists(&self.group, ctx) {
+            ServerGroup::from_str(&self.group, ctx)
+                .map_err(|err| EventServerError::RedisError(err.to_string()))?
+                .safe_delete(true, ctx)
+                .map_err(|err| EventServerError::RedisError(err.to_string()))?;
+        }
+        self.archived_at = Some(Local::now().timestamp());
+        let raw = self.to_json()?;
+        redis::cmd("LPUSH")
+            .arg(ARCHIVE_KEY)
+            .arg(raw)
+            .query::<()>(ctx.get_connection())
+            .map_err(to_redis_error)?;
+        redis::cmd("LTRIM")
+            .arg(ARCHIVE_KEY)
+            .arg(0)
+            .arg(ARCHIVE_LENGTH - 1)
+            .query::<()>(ctx.get_connection())
+            .map_err(to_redis_error)?;
+        redis::cmd("HDEL")
+            .arg(EVENT_SERVERS_KEY)
+            .arg(&self.group)
+            .query::<()>(ctx.get_connection())
+            .map_err(to_redis_error)?;
+        Event::new(
+            EventKind::GroupDeleted,
+            &self.group,
+            "event server archived".into(),
+        )
+        .emit(ctx);
+        Ok(self)
+    }
+
+    pub fn archive_expired(ctx: &mut ContextManager) -> Result<Vec<Self>, EventServerError> {
+        //! Archives every event server past its end time.
+        let now = Local::now().timestamp();
+        Self::get_all(ctx)?
+            .into_iter()
+            .filter(|event_server| event_server.expires_at <= now)
+            .map(|event_server| event_server.archive(ctx))
+            .collect()
+    }
+}
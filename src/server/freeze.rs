@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use redis::{ErrorKind, RedisError};
+
+use crate::{
+    context_manager::ContextManager,
+    events::{Event, EventKind},
+};
+
+/// Hash of group key -> checksum of the fields the manager last wrote to it.
+pub(crate) const CHECKSUMS_KEY: &str = "servermonitor.groupchecksums";
+/// Starts the error refusing to overwrite a group edited by another tool.
+pub const EXTERNAL_EDIT: &str = "Group edited externally";
+
+pub fn get_checksum(map: &HashMap<String, String>) -> String {
+    //! SHA-1 of the fields in name order, independent of how redis orders them.
+    let mut fields: Vec<(&String, &String)> = map.iter().collect();
+    fields.sort();
+    let mut sha1 = sha1_smol::Sha1::new();
+    for (field, value) in fields {
+        sha1.update(field.as_bytes());
+        sha1.update(b"=");
+        sha1.update(value.as_bytes());
+        sha1.update(b"\n");
+    }
+    sha1.digest().to_string()
+}
+
+fn get_stored(key: &str, ctx: &mut ContextManager) -> Result<HashMap<String, String>, RedisError> {
+    redis::cmd("HGETALL").arg(key).query(ctx.get_connection())
+}
+
+pub fn check(key: &str, ctx: &mut ContextManager) -> Result<(), RedisError> {
+    //! Refuses to overwrite the group at `key` if its hash changed since the manager last
+    //! wrote it, recording a GroupEditedExternally event. Groups the manager never wrote
+    //! (or that were deleted) can be written.
+    let recorded: Option<String> = redis::cmd("HGET")
+        .arg(CHECKSUMS_KEY)
+        .arg(key)
+        .query(ctx.get_connection())?;
+    let Some(recorded) = recorded else {
+        return Ok(());
+    };
+    let stored = get_stored(key, ctx)?;
+    if stored.is_empty() || get_checksum(&stored) == recorded {
+        return Ok(());
+    }
+    Event::new(
+        EventKind::GroupEditedExternally,
+        key,
+        "changed outside the manager since its last write, not overwritten".into(),
+    )
+    .emit(ctx);
+    Err(RedisError::from((
+        ErrorKind::ClientError,
+        EXTERNAL_EDIT,
+        format!(
+            "{} changed outside the manager since its last write (--accept-external-changes to overwrite it)",
+            key
+        ),
+    )))
+}
+
+pub fn record(key: &str, ctx: &mut ContextManager) -> Result<(), RedisError> {
+    //! Remembers the fields the manager just wrote to `key`, along with any it left alone.
+    let stored = get_stored(key, ctx)?;
+    redis::cmd("HSET")
+        .arg(CHECKSUMS_KEY)
+        .arg(key)
+        .arg(get_checksum(&stored))
+        .query(ctx.get_connection())
+}
+
+pub fn forget(key: &str, ctx: &mut ContextManager) -> Result<(), RedisError> {
+    //! Drops the checksum of the deleted group at `key`, so a new group there isn't frozen.
+    redis::cmd("HDEL")
+        .arg(CHECKSUMS_KEY)
+        .arg(key)
+        .query(ctx.get_connection())
+}
+
+pub fn accept_all(ctx: &mut ContextManager) -> Result<Vec<String>, RedisError> {
+    //! Takes the current fields of every group edited outside the manager as what it last
+    //! wrote, so the next write overwrites them. Returns the accepted group keys.
+    let recorded: HashMap<String, String> = redis::cmd("HGETALL")
+        .arg(CHECKSUMS_KEY)
+        .query(ctx.get_connection())?;
+    let mut accepted = Vec::new();
+    for (key, checksum) in recorded {
+        let stored = get_stored(&key, ctx)?;
+        if stored.is_empty() || get_checksum(&stored) == checksum {
+            continue;
+        }
+        record(&key, ctx)?;
+        accepted.push(key);
+    }
+    accepted.sort();
+    Ok(accepted)
+}
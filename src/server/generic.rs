@@ -20,7 +20,7 @@ impl GenericServer {
         //! Converts GenericServer to ServerGroup. Loads from cache if exists.
         GENERIC_TO_SERVER_GROUP
             .get(self)
-            .map(|sg| sg.clone())
+            .cloned()
             .map_or(Ok(None), |mut group| {
                 group.eliminate_port_collisions(ctx)?;
                 if group.is_cached(ctx) {
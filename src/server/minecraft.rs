@@ -1,13 +1,20 @@
 use std::{collections::HashMap, str::FromStr};
 
 use chrono::{DateTime, Local};
-use redis::{FromRedisValue, RedisError};
-use strum_macros::EnumString;
+use redis::RedisError;
+use strum_macros::{Display, EnumString};
 use thiserror::Error;
 
-use crate::{context_manager::ContextManager, game::r#type::GameType, region::Region};
+use serde_json::json;
 
-use super::server_group::ServerGroup;
+use crate::{
+    context_manager::ContextManager,
+    game::r#type::GameType,
+    region::Region,
+    store::{entity::RedisEntity, keys::KeyBuilder, partial::PartialResult, scan::EntityIter},
+};
+
+use super::{commands::ServerCommand, server_group::ServerGroup};
 
 #[derive(Error, Debug)]
 pub enum MinecraftServerError {
@@ -21,9 +28,9 @@ enum ServerMotd {
     Motd(String),
 }
 
-#[allow(non_camel_case_types)]
-#[derive(Clone, EnumString, Eq, Debug, PartialEq)]
-enum GameDisplayStatus {
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+#[derive(Clone, Display, EnumString, Eq, Debug, PartialEq)]
+pub enum GameDisplayStatus {
     ALWAYS_OPEN,
     STARTING,
     VOTING,
@@ -32,9 +39,9 @@ enum GameDisplayStatus {
     CLOSING,
 }
 
-#[allow(non_camel_case_types)]
-#[derive(Clone, EnumString, Eq, Debug, PartialEq)]
-enum GameJoinStatus {
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+#[derive(Clone, Display, EnumString, Eq, Debug, PartialEq)]
+pub enum GameJoinStatus {
     OPEN,
     RANKS_ONLY,
     CLOSED,
@@ -215,6 +222,24 @@ fn parse_join_status_from_map(
     )
 }
 
+impl ServerMotd {
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            ServerMotd::Motd(motd) => json!(motd),
+            ServerMotd::GameMotd(info) => json!({
+                "_game": info.game.to_string(),
+                "_mode": info.mode,
+                "_map": info.map,
+                "_timer": info.timer,
+                "_votingOn": info.voting_on,
+                "_hostRank": info.host_rank,
+                "_status": info.display_status.to_string(),
+                "_joinable": info.join_status.to_string(),
+            }),
+        }
+    }
+}
+
 impl GameInfo {
     fn parse_motd(
         map: serde_json::Map<String, serde_json::Value>,
@@ -247,21 +272,15 @@ pub struct MinecraftServer {
     donors_online: u8,
     start_up_date: u64, // seconds since epoch
     current_time: u64,  // ms since epoch
+    region: Region,     // from the key, servers don't report it
 }
 
 impl From<String> for MinecraftServerError {
     fn from(value: String) -> Self {
-        Self::ParsingError(value.into())
+        Self::ParsingError(value)
     }
 }
 
-fn parse_datetime(
-    map: &HashMap<String, String>,
-    key: &str,
-) -> Result<DateTime<Local>, MinecraftServerError> {
-    todo!()
-}
-
 fn parse_json_motd(
     map: &serde_json::Map<String, serde_json::Value>,
     key: &str,
@@ -300,10 +319,108 @@ impl TryFrom<serde_json::Value> for MinecraftServer {
             donors_online: parse_u8_from_map(&map, "_donorsOnline")?,
             start_up_date: parse_u64_from_map(&map, "_startUpDate")?,
             current_time: parse_u64_from_map(&map, "_currentTime")?,
+            region: parse_optional_string_from_map(&map, "_region")
+                .and_then(|region| Region::try_from(region).ok())
+                .unwrap_or_default(),
         })
     }
 }
 
+pub(crate) fn parse_raw_status(
+    raw: &str,
+    key: &str,
+    keys: &KeyBuilder,
+) -> Result<HashMap<String, String>, RedisError> {
+    //! Parses the JSON stored at `key`, adding the region the key was built with as `_region`.
+    let value: serde_json::Value = serde_json::from_str(raw).map_err(|_| {
+        RedisError::from(MinecraftServerError::from(
+            "Error parsing Minecraft Server cache".to_string(),
+        ))
+    })?;
+    Ok(json_to_map(&value)?
+        .into_iter()
+        .map(|(key, value)| (key, value.to_string()))
+        .chain(
+            keys.parse_status_key(key)
+                .map(|(region, _)| ("_region".to_string(), json!(region).to_string())),
+        )
+        .collect())
+}
+
+impl RedisEntity for MinecraftServer {
+    type Error = MinecraftServerError;
+
+    fn get_key(id: &str, keys: &KeyBuilder) -> String {
+        //! `id` is `<region>.<name>`, see `get_status_id`.
+        let (region, name) = id.split_once('.').unwrap_or(("*", id));
+        keys.status_key(region, name)
+    }
+
+    fn get_pattern(keys: &KeyBuilder) -> String {
+        keys.status_pattern(None, None)
+    }
+
+    fn get_id(&self) -> String {
+        Self::get_status_id(&self.name, &self.region)
+    }
+
+    fn from_map(map: HashMap<String, String>) -> Result<Self, Self::Error> {
+        //! Each value holds the JSON encoding of its field.
+        let map = map
+            .into_iter()
+            .map(|(key, value)| {
+                serde_json::from_str(&value)
+                    .map(|value| (key.clone(), value))
+                    .map_err(|_| format!("Could not parse `{}` as JSON", key))
+            })
+            .collect::<Result<serde_json::Map<String, serde_json::Value>, String>>()?;
+        serde_json::Value::Object(map).try_into()
+    }
+
+    fn to_map(&self) -> HashMap<String, String> {
+        json_to_map(&self.to_json())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(key, value)| (key, value.to_string()))
+            .collect()
+    }
+
+    fn read_map(
+        key: &str,
+        ctx: &mut ContextManager,
+    ) -> Result<HashMap<String, String>, RedisError> {
+        //! Statuses are stored as a single JSON string rather than a hash.
+        let raw: Option<String> = redis::cmd("GET").arg(key).query(ctx.get_connection())?;
+        match raw {
+            Some(raw) => parse_raw_status(&raw, key, ctx.get_keys()),
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    fn write_map(
+        key: &str,
+        map: HashMap<String, String>,
+        ctx: &mut ContextManager,
+    ) -> Result<(), RedisError> {
+        redis::cmd("SET")
+            .arg(key)
+            .arg(status_to_json(map))
+            .query(ctx.get_connection())
+    }
+}
+
+pub fn status_to_json(map: HashMap<String, String>) -> String {
+    //! The JSON string a status is stored as.
+    let object = map
+        .into_iter()
+        .map(|(key, value)| {
+            let value = serde_json::from_str(&value).unwrap_or(serde_json::Value::String(value));
+            (key, value)
+        })
+        .collect::<serde_json::Map<String, serde_json::Value>>();
+    serde_json::Value::Object(object).to_string()
+}
+
 impl From<MinecraftServerError> for RedisError {
     fn from(err: MinecraftServerError) -> Self {
         match err {
@@ -317,22 +434,8 @@ impl From<MinecraftServerError> for RedisError {
     }
 }
 
-impl FromRedisValue for MinecraftServer {
-    fn from_redis_value(v: &redis::Value) -> redis::RedisResult<Self> {
-        let map_str: String = redis::from_redis_value(v)?;
-        let map: serde_json::Value = serde_json::from_str(map_str.as_str()).map_err(|_| {
-            RedisError::from(MinecraftServerError::from(
-                "Error parsing Minecraft Server cache".to_string(),
-            ))
-        })?;
-        let res: Result<MinecraftServer, MinecraftServerError> = map.try_into();
-        let mapped_res: Result<MinecraftServer, RedisError> = res.map_err(|err| err.into());
-        mapped_res
-    }
-}
-
 #[allow(non_camel_case_types)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum ServerStatus {
     ONLINE,
     OFFLINE,
@@ -343,34 +446,24 @@ pub enum ServerStatus {
 
 impl MinecraftServer {
     fn get_server_group(&self, ctx: &mut ContextManager) -> Option<ServerGroup> {
-        let key: String = format!("servergroups.{}", self.group);
-        ServerGroup::from_str(key.as_str(), ctx).ok()
+        ServerGroup::from_str(&self.group, ctx).ok()
     }
 
     pub fn from_server_group(
         server_group: &ServerGroup,
         ctx: &mut ContextManager,
-    ) -> Result<Vec<Self>, MinecraftServerError> {
-        let server_statuses: Vec<String> = redis::cmd("KEYS")
-            .arg(format!(
-                "serverstatus.minecraft.{}.{}-*",
-                server_group.region, server_group.prefix
-            ))
-            .query(ctx.get_connection())
-            .map_err(|_| -> MinecraftServerError {
-                "Redis data for MinecraftServer could not be retrieved. MinecraftServer iteration failed."
-                    .to_string().into()
-            })?;
-        server_statuses
-            .iter()
-            .map(|sg| Self::get_from_raw_str(sg.as_str(), ctx))
-            .collect()
+    ) -> PartialResult<Self, MinecraftServerError> {
+        let pattern = ctx.get_keys().status_pattern(
+            Some(&server_group.region.to_string()),
+            Some(&server_group.prefix),
+        );
+        let page_size = ctx.get_config().get_scan_count();
+        EntityIter::new(pattern, page_size, ctx).collect()
     }
 
-    fn is_online(&self) -> bool {
-        //! Checks whether the current timestamp in milliseconds
+    fn is_online(&self, now: u64) -> bool {
+        //! Checks whether `now` (in milliseconds)
         //! falls between 5 seconds of `self.current_time`
-        let now = Local::now().timestamp_millis() as u64;
         let seconds = 5; // 5 * 2 = 10 seconds (happened just 5-10 seconds ago)
         let interval = seconds * 1000; // in milliseconds
         let seconds_before_curr = self.current_time - interval;
@@ -385,21 +478,89 @@ impl MinecraftServer {
         let Some(group) = self.get_server_group(ctx) else {
             return ServerStatus::GROUP_NOT_FOUND;
         };
-        let Ok(server) = Self::get(&self.name, &group.region, ctx) else {
+        let Ok(server) = Self::get_status(&self.name, &group.region, ctx) else {
             return ServerStatus::INSTANCE_NOT_FOUND;
         };
-        if self.current_time == server.current_time && !self.is_online() {
+        if self.current_time == server.current_time && !self.is_online(ctx.get_clock().now_millis())
+        {
             return ServerStatus::OFFLINE;
         }
         *self = server;
         ServerStatus::ONLINE
     }
 
-    fn get_uptime_as_seconds(&self) -> i64 {
-        return Local::now().timestamp() - (self.start_up_date as i64);
+    pub fn get_silent_secs(&self, now: DateTime<Local>) -> i64 {
+        //! Seconds since the server last refreshed its status.
+        (now.timestamp_millis() - self.current_time as i64) / 1000
     }
 
-    fn get_prefix(&self) -> u8 {
+    pub fn get_uptime_as_seconds(&self, now: DateTime<Local>) -> i64 {
+        now.timestamp() - (self.start_up_date as i64)
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_group(&self) -> &String {
+        &self.group
+    }
+
+    pub fn get_region(&self) -> &Region {
+        &self.region
+    }
+
+    pub fn get_public_address(&self) -> &String {
+        &self.public_address
+    }
+
+    pub fn get_port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn get_player_count(&self) -> u8 {
+        self.player_count
+    }
+
+    pub fn get_max_player_count(&self) -> u8 {
+        self.max_player_count
+    }
+
+    pub fn get_ram(&self) -> u16 {
+        self.ram
+    }
+
+    pub fn get_max_ram(&self) -> u16 {
+        self.max_ram
+    }
+
+    pub fn set_join_status(
+        &self,
+        join_status: GameJoinStatus,
+        ctx: &mut ContextManager,
+    ) -> Result<usize, MinecraftServerError> {
+        //! Asks the server to change who may join it (e.g. CLOSED while draining).
+        ServerCommand::JoinStatus {
+            target_server: self.name.clone(),
+            join_status,
+        }
+        .publish(ctx)
+    }
+
+    pub fn set_display_status(
+        &self,
+        display_status: GameDisplayStatus,
+        ctx: &mut ContextManager,
+    ) -> Result<usize, MinecraftServerError> {
+        //! Asks the server to change the status it shows to players.
+        ServerCommand::DisplayStatus {
+            target_server: self.name.clone(),
+            display_status,
+        }
+        .publish(ctx)
+    }
+
+    pub fn get_prefix(&self) -> u8 {
         let (_, prefix) = self
             .name
             .split_once('-')
@@ -408,56 +569,111 @@ impl MinecraftServer {
     }
 
     fn is_empty(&self) -> bool {
-        return self.player_count == 0;
+        self.player_count == 0
     }
 
-    fn is_dead_server(&self) -> bool {
-        //? Returns `true` if player_count is None and server has been online for over 2 minutes.
-        return self.is_empty() && self.get_uptime_as_seconds() >= 150;
+    pub fn is_joinable(&self) -> bool {
+        //! Whether players can still join: open for joins, not mid-game, and not full.
+        let open = match &self.motd {
+            ServerMotd::GameMotd(info) => {
+                info.join_status == GameJoinStatus::OPEN
+                    && info.display_status != GameDisplayStatus::IN_PROGRESS
+                    && info.display_status != GameDisplayStatus::CLOSING
+            }
+            ServerMotd::Motd(_) => true,
+        };
+        open && self.player_count < self.max_player_count
     }
 
-    pub fn get_empty_servers(ctx: &mut ContextManager) -> Result<Vec<Self>, MinecraftServerError> {
-        Ok(Self::get_all(ctx)?
-            .into_iter()
-            .filter(|sv| sv.is_dead_server()) // offline
-            .collect())
+    fn is_dead_server(&self, now: DateTime<Local>) -> bool {
+        //? Returns `true` if player_count is None and server has been online for over 2 minutes.
+        self.is_empty() && self.get_uptime_as_seconds(now) >= 150
     }
 
-    pub fn get_all(ctx: &mut ContextManager) -> Result<Vec<Self>, MinecraftServerError> {
-        let server_statuses: Vec<String> = redis::cmd("KEYS")
-            .arg("serverstatus.minecraft.*.*")
-            .query(ctx.get_connection())
-            .map_err(|_| -> MinecraftServerError {
-                "Redis data for MinecraftServer could not be retrieved. MinecraftServer iteration failed."
-                    .to_string().into()
-            })?;
-        server_statuses
-            .iter()
-            .map(|ss| Self::get_from_raw_str(ss.as_str(), ctx))
+    pub fn get_empty_servers(
+        ctx: &mut ContextManager,
+    ) -> PartialResult<Self, MinecraftServerError> {
+        //! Streams statuses page by page so only dead servers are kept in memory.
+        let now = ctx.get_clock().now();
+        let page_size = ctx.get_config().get_scan_count();
+        Self::iter(page_size, ctx)
+            .filter(|sv| sv.as_ref().map_or(true, |sv| sv.is_dead_server(now))) // offline
             .collect()
     }
 
-    fn get_from_raw_str(key: &str, ctx: &mut ContextManager) -> Result<Self, MinecraftServerError> {
-        redis::cmd("GET")
-            .arg(key)
-            .query(ctx.get_connection())
-            .map_err(|err| {
-                format!("Redis data for {:?} could not be retrieved: {:?}", key, err)
-                    .to_string()
-                    .into()
-            })
+    pub fn synthetic(group: &ServerGroup, server_num: usize, now: DateTime<Local>) -> Self {
+        //! A fake instance of `group` started at `now` (for load testing without real servers).
+        Self {
+            name: format!("{}-{}", group.prefix, server_num),
+            group: group.prefix.clone(),
+            motd: ServerMotd::Motd("Synthetic server".into()),
+            player_count: 0,
+            max_player_count: group.max_players,
+            tps: 20,
+            ram: 0,
+            max_ram: group.ram,
+            public_address: "127.0.0.1".into(),
+            port: group
+                .get_port_section()
+                .port_for(server_num)
+                .unwrap_or(group.port_section),
+            donors_online: 0,
+            start_up_date: now.timestamp() as u64,
+            current_time: now.timestamp_millis() as u64,
+            region: group.region.clone(),
+        }
     }
 
-    pub fn get(
+    pub fn heartbeat(&mut self, player_count: u8, ram: u16, now: DateTime<Local>) {
+        //! Updates the load figures and stamps the status with `now`.
+        self.player_count = player_count.min(self.max_player_count);
+        self.ram = ram.min(self.max_ram);
+        self.current_time = now.timestamp_millis() as u64;
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        //! Serializes the status the way the server plugins write it.
+        json!({
+            "_name": self.name,
+            "_group": self.group,
+            "_motd": self.motd.to_json(),
+            "_playerCount": self.player_count,
+            "_maxPlayerCount": self.max_player_count,
+            "_tps": self.tps,
+            "_ram": self.ram,
+            "_maxRam": self.max_ram,
+            "_publicAddress": self.public_address,
+            "_port": self.port,
+            "_donorsOnline": self.donors_online,
+            "_startUpDate": self.start_up_date,
+            "_currentTime": self.current_time,
+        })
+    }
+
+    pub(crate) fn get_status_id(server_name: &String, region: &Region) -> String {
+        format!("{}.{}", region, server_name)
+    }
+
+    pub fn get_status(
         server_name: &String,
         region: &Region,
         ctx: &mut ContextManager,
     ) -> Result<Self, MinecraftServerError> {
-        let key: String = format!(
-            "serverstatus.minecraft.{}.{}",
-            region.to_string(),
-            server_name
-        );
-        Self::get_from_raw_str(key.as_str(), ctx)
+        Self::get(&Self::get_status_id(server_name, region), ctx)
+    }
+
+    pub fn from_raw(raw: &str, key: &str, keys: &KeyBuilder) -> Result<Self, MinecraftServerError> {
+        //! Parses a status as stored at `key`, e.g. when read in a batch with other keys.
+        let map = parse_raw_status(raw, key, keys).map_err(|err| err.to_string())?;
+        Self::from_map(map)
+    }
+
+    pub fn delete_status(
+        server_name: &String,
+        region: &Region,
+        ctx: &mut ContextManager,
+    ) -> Result<(), MinecraftServerError> {
+        //! Removes the status entry of a server instance.
+        Self::delete(&Self::get_status_id(server_name, region), ctx)
     }
 }
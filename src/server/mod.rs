@@ -1,4 +1,24 @@
+pub mod broadcast;
+pub mod commands;
+pub mod counters;
+pub mod crash_loop;
 pub mod dedicated;
+pub mod diff;
+pub mod ensure;
+pub mod event_server;
+pub mod freeze;
 pub mod generic;
 pub mod minecraft;
+pub mod port;
+pub mod presets;
+pub mod query;
+pub mod rcon;
+pub mod recycling;
+pub mod restart;
+pub mod rightsizing;
+pub mod rotation;
+pub mod schema;
 pub mod server_group;
+pub mod smoke;
+pub mod version;
+pub mod view;
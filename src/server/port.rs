@@ -0,0 +1,197 @@
+use std::fmt::Display;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    config::models::{PortAllocationInfo, PortAllocationStrategy},
+    context_manager::ContextManager,
+    random::{self, RandomSource},
+};
+
+pub type Port = u16;
+
+/// Entries kept per group in its port history.
+const PORT_HISTORY_LENGTH: isize = 100;
+
+/// Number of ports above a section's start its instances can use.
+pub const PORT_SECTION_WIDTH: u16 = 10;
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum PortError {
+    #[error("Port Error: Server number {1} does not fit in port section {0}")]
+    OutsideSection(PortSection, usize),
+    #[error("Port Error: Port section {0} overflows the port range")]
+    Overflow(PortSection),
+    #[error("Port Error: No free port section starts within {0}-{1}")]
+    Exhausted(Port, Port),
+}
+
+pub fn allocate_port_section(
+    info: &PortAllocationInfo,
+    taken: &[Port],
+    random: &dyn RandomSource,
+) -> Result<Port, PortError> {
+    //! Picks a port section in the configured range that overlaps none of `taken`,
+    //! following the configured strategy (random ones are drawn from `random`).
+    let (low, high) = info.range;
+    let mut free = (low..=high).filter(|&start| {
+        let section = PortSection::new(start);
+        section.get_end().is_ok()
+            && !taken
+                .iter()
+                .any(|&other| section.overlaps(&PortSection::new(other)))
+    });
+    let start = match info.strategy {
+        PortAllocationStrategy::Sequential => free.next(),
+        PortAllocationStrategy::Random => random::choose(random, free.collect()),
+    };
+    start.ok_or(PortError::Exhausted(low, high))
+}
+
+/// The block of ports a ServerGroup's instances are assigned from.
+/// Instance `n` of a group listens on `start + n`, for `n` up to the section's width.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct PortSection {
+    start: Port,
+    width: u16,
+}
+
+impl Display for PortSection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start,
+            self.start as u32 + self.width as u32
+        )
+    }
+}
+
+impl PortSection {
+    pub fn new(start: Port) -> Self {
+        Self::with_width(start, PORT_SECTION_WIDTH)
+    }
+
+    pub fn with_width(start: Port, width: u16) -> Self {
+        Self { start, width }
+    }
+
+    pub fn get_start(&self) -> Port {
+        self.start
+    }
+
+    pub fn get_width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn get_end(&self) -> Result<Port, PortError> {
+        //! Highest port in the section.
+        self.start
+            .checked_add(self.width)
+            .ok_or(PortError::Overflow(*self))
+    }
+
+    pub fn port_for(&self, server_num: usize) -> Result<Port, PortError> {
+        //! Port of instance `server_num`, if it lies within the section.
+        let offset = u16::try_from(server_num)
+            .ok()
+            .filter(|&offset| offset <= self.width)
+            .ok_or(PortError::OutsideSection(*self, server_num))?;
+        self.start
+            .checked_add(offset)
+            .ok_or(PortError::Overflow(*self))
+    }
+
+    pub fn contains(&self, port: Port) -> bool {
+        self.start <= port && self.get_end().is_ok_and(|end| port <= end)
+    }
+
+    pub fn overlaps(&self, other: &PortSection) -> bool {
+        let end = |section: &PortSection| section.start as u32 + section.width as u32;
+        (self.start as u32) <= end(other) && (other.start as u32) <= end(self)
+    }
+
+    pub fn overlaps_range(&self, low: Port, high: Port) -> bool {
+        //! Returns `true` if any port of the section lies within `low..=high`.
+        self.overlaps(&PortSection::with_width(low, high.saturating_sub(low)))
+    }
+}
+
+/// A change of a group's port section, kept so operators can trace firewall rules
+/// back to the section a group used to have.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct PortReassignment {
+    pub old: Port,
+    pub new: Port,
+    pub reason: String,
+    pub timestamp: i64, // seconds since epoch
+}
+
+impl Display for PortReassignment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let time = chrono::DateTime::from_timestamp(self.timestamp, 0)
+            .map(|time| time.with_timezone(&Local).to_rfc3339())
+            .unwrap_or(self.timestamp.to_string());
+        write!(f, "{} {} -> {} ({})", time, self.old, self.new, self.reason)
+    }
+}
+
+impl PortReassignment {
+    pub fn new(old: Port, new: Port, reason: String) -> Self {
+        Self {
+            old,
+            new,
+            reason,
+            timestamp: Local::now().timestamp(),
+        }
+    }
+
+    fn get_key(group: &str) -> String {
+        format!("porthistory.{}", group)
+    }
+
+    pub fn record(&self, group: &str, ctx: &mut ContextManager) -> Result<(), redis::RedisError> {
+        //! Prepends the reassignment to the group's history, keeping the latest 100 entries.
+        for cmd in self.get_record_cmds(group)? {
+            cmd.query::<()>(ctx.get_connection())?;
+        }
+        Ok(())
+    }
+
+    pub fn get_record_cmds(&self, group: &str) -> Result<[redis::Cmd; 2], redis::RedisError> {
+        //! The writes behind `record`, in order, for connections other than the context's.
+        let entry = serde_json::to_string(self).map_err(|err| {
+            redis::RedisError::from((
+                redis::ErrorKind::ClientError,
+                "Port history serialization error",
+                err.to_string(),
+            ))
+        })?;
+        let mut push = redis::cmd("LPUSH");
+        push.arg(Self::get_key(group)).arg(entry);
+        let mut trim = redis::cmd("LTRIM");
+        trim.arg(Self::get_key(group))
+            .arg(0)
+            .arg(PORT_HISTORY_LENGTH - 1);
+        Ok([push, trim])
+    }
+
+    pub fn get_history(
+        group: &str,
+        ctx: &mut ContextManager,
+    ) -> Result<Vec<Self>, redis::RedisError> {
+        //! Returns a group's port reassignments, newest first.
+        //! Entries that can no longer be parsed are skipped.
+        let entries: Vec<String> = redis::cmd("LRANGE")
+            .arg(Self::get_key(group))
+            .arg(0)
+            .arg(-1)
+            .query(ctx.get_connection())?;
+        Ok(entries
+            .iter()
+            .filter_map(|entry| serde_json::from_str(entry).ok())
+            .collect())
+    }
+}
@@ -0,0 +1,165 @@
+use strum_macros::{Display, EnumIter, EnumString};
+
+use crate::{
+    game::{
+        booster_group::BoosterGroup,
+        utils::{GENERIC_TO_SERVER_GROUP, MIXED_ARCADE_GAMES},
+    },
+    region::Region,
+    server::{generic::GenericServer, server_group::ServerGroup},
+};
+
+/// Ready-made ServerGroup templates.
+#[derive(Clone, Copy, Debug, Display, EnumIter, EnumString, Eq, Hash, PartialEq)]
+#[strum(ascii_case_insensitive)]
+pub enum Preset {
+    Arcade,
+    Hub,
+    Clans,
+    Event,
+    Build,
+}
+
+#[derive(Clone, Copy, Debug, Default, Display, EnumIter, EnumString, Eq, Hash, PartialEq)]
+#[strum(ascii_case_insensitive)]
+pub enum SizeTier {
+    S,
+    #[default]
+    M,
+    L,
+}
+
+impl SizeTier {
+    pub fn get_ram(&self) -> u16 {
+        match self {
+            SizeTier::S => 512,
+            SizeTier::M => 1024,
+            SizeTier::L => 2048,
+        }
+    }
+
+    pub fn get_cpu(&self) -> u8 {
+        match self {
+            SizeTier::S => 1,
+            SizeTier::M => 2,
+            SizeTier::L => 4,
+        }
+    }
+
+    pub fn get_max_players(&self) -> u8 {
+        match self {
+            SizeTier::S => 16,
+            SizeTier::M => 24,
+            SizeTier::L => 50,
+        }
+    }
+}
+
+fn lobby() -> ServerGroup {
+    GENERIC_TO_SERVER_GROUP
+        .get(&GenericServer::Lobby)
+        .cloned()
+        .expect("Lobby should be a generic server group")
+}
+
+fn arcade() -> ServerGroup {
+    ServerGroup {
+        arcade_group: true,
+        world_zip: "arcade.zip".into(),
+        plugin: "Arcade.jar".into(),
+        config_path: "plugins/Arcade".into(),
+        min_players: 8,
+        pvp: true,
+        games: Some(
+            MIXED_ARCADE_GAMES
+                .iter()
+                .take(7)
+                .map(|g| g.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+        ),
+        booster_group: Some(BoosterGroup::Arcade.to_string()),
+        server_type: "Minigames".into(),
+        team_auto_join: true,
+        game_auto_start: true,
+        game_timeout: true,
+        map_voting: true,
+        reward_gems: true,
+        reward_items: true,
+        reward_stats: true,
+        reward_achievements: true,
+        hotbar_inventory: true,
+        hotbar_hub_clock: true,
+        player_kick_idle: true,
+        npc_name: Some("Mixed Arcade".into()),
+        ..lobby()
+    }
+}
+
+impl Preset {
+    pub fn get_description(&self) -> &'static str {
+        match self {
+            Preset::Arcade => "Standard rotating arcade minigames",
+            Preset::Hub => "Lobby/hub server",
+            Preset::Clans => "Persistent Clans server",
+            Preset::Event => "Whitelisted, host-controlled event server",
+            Preset::Build => "Staff build server with WorldEdit",
+        }
+    }
+
+    pub fn to_server_group(&self, name: &str, region: Region, tier: SizeTier) -> ServerGroup {
+        //! Builds a ServerGroup named `name` from this preset.
+        //! The port section is a placeholder; `ServerGroup::create` picks a free one.
+        let template = match self {
+            Preset::Arcade => arcade(),
+            Preset::Hub => lobby(),
+            Preset::Clans => ServerGroup {
+                world_zip: "clans.zip".into(),
+                plugin: "Clans.jar".into(),
+                config_path: "plugins/Clans".into(),
+                min_players: 1,
+                add_no_cheat: false,
+                add_world_edit: true,
+                game_timeout: true,
+                reward_gems: true,
+                reward_items: true,
+                reward_stats: true,
+                reward_achievements: true,
+                hotbar_hub_clock: true,
+                npc_name: Some("Clans".into()),
+                ..lobby()
+            },
+            Preset::Event => ServerGroup {
+                games: Some("Event".into()),
+                whitelist: true,
+                game_auto_start: false,
+                reward_gems: false,
+                reward_items: false,
+                reward_stats: false,
+                reward_achievements: false,
+                player_kick_idle: false,
+                booster_group: None,
+                npc_name: None,
+                ..arcade()
+            },
+            Preset::Build => ServerGroup {
+                world_zip: "build.zip".into(),
+                add_no_cheat: false,
+                add_world_edit: true,
+                whitelist: true,
+                staff_only: true,
+                ..lobby()
+            },
+        };
+        ServerGroup {
+            name: name.to_string(),
+            prefix: name.to_string(),
+            ram: tier.get_ram(),
+            cpu: tier.get_cpu(),
+            max_players: tier.get_max_players(),
+            min_players: template.min_players.min(tier.get_max_players()),
+            region,
+            ..template
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use std::{collections::HashMap, fmt::Display, iter::Peekable, str::FromStr, vec::IntoIter};
+
+use thiserror::Error;
+
+use crate::{server::server_group::ServerGroup, store::entity::RedisEntity};
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum QueryError {
+    #[error("Query Error: `{0}`")]
+    ParseError(String),
+    #[error("Query Error: Unknown group field: `{0}`")]
+    UnknownField(String),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Contains, // `~=`, case-insensitive substring
+}
+
+impl Comparison {
+    fn is_ordering(&self) -> bool {
+        matches!(self, Self::Gt | Self::Ge | Self::Lt | Self::Le)
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let op = match self {
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Contains => "~=",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+/// A filter over ServerGroup fields, e.g. `region==EU && arcadeGroup && maxPlayers>=16`.
+/// Fields are the group's hash fields (case-insensitive). A bare field is true unless it is
+/// empty, `false` or `0`. `&&` binds tighter than `||`; `!` and parentheses work as usual.
+/// Values with spaces or operators go in double quotes.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum GroupQuery {
+    Field(String),
+    Compare(String, Comparison, String),
+    Not(Box<GroupQuery>),
+    And(Box<GroupQuery>, Box<GroupQuery>),
+    Or(Box<GroupQuery>, Box<GroupQuery>),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(Comparison),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Word(word) => write!(f, "{}", word),
+            Self::Quoted(value) => write!(f, "{:?}", value),
+            Self::Op(op) => write!(f, "{}", op),
+            Self::And => write!(f, "&&"),
+            Self::Or => write!(f, "||"),
+            Self::Not => write!(f, "!"),
+            Self::Open => write!(f, "("),
+            Self::Close => write!(f, ")"),
+        }
+    }
+}
+
+fn describe(token: Option<Token>) -> String {
+    token.map_or("the end".into(), |token| token.to_string())
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':' | '/')
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = expr.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        if is_word_char(c) {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek().filter(|c| is_word_char(**c)) {
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Word(word));
+            continue;
+        }
+        chars.next();
+        let next = chars.peek().copied();
+        let (token, pair) = match (c, next) {
+            ('"', _) => {
+                let mut quoted = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => quoted.push(c),
+                        None => return Err(QueryError::ParseError("unterminated \"".into())),
+                    }
+                }
+                tokens.push(Token::Quoted(quoted));
+                continue;
+            }
+            ('=', Some('=')) => (Token::Op(Comparison::Eq), true),
+            ('!', Some('=')) => (Token::Op(Comparison::Ne), true),
+            ('>', Some('=')) => (Token::Op(Comparison::Ge), true),
+            ('<', Some('=')) => (Token::Op(Comparison::Le), true),
+            ('~', Some('=')) => (Token::Op(Comparison::Contains), true),
+            ('&', Some('&')) => (Token::And, true),
+            ('|', Some('|')) => (Token::Or, true),
+            ('>', _) => (Token::Op(Comparison::Gt), false),
+            ('<', _) => (Token::Op(Comparison::Lt), false),
+            ('!', _) => (Token::Not, false),
+            ('(', _) => (Token::Open, false),
+            (')', _) => (Token::Close, false),
+            _ => return Err(QueryError::ParseError(format!("unexpected {:?}", c))),
+        };
+        if pair {
+            chars.next();
+        }
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Peekable<IntoIter<Token>>,
+}
+
+impl Parser {
+    fn parse_or(&mut self) -> Result<GroupQuery, QueryError> {
+        let mut query = self.parse_and()?;
+        while self.tokens.next_if_eq(&Token::Or).is_some() {
+            query = GroupQuery::Or(Box::new(query), Box::new(self.parse_and()?));
+        }
+        Ok(query)
+    }
+
+    fn parse_and(&mut self) -> Result<GroupQuery, QueryError> {
+        let mut query = self.parse_unary()?;
+        while self.tokens.next_if_eq(&Token::And).is_some() {
+            query = GroupQuery::And(Box::new(query), Box::new(self.parse_unary()?));
+        }
+        Ok(query)
+    }
+
+    fn parse_unary(&mut self) -> Result<GroupQuery, QueryError> {
+        match self.tokens.next() {
+            Some(Token::Not) => Ok(GroupQuery::Not(Box::new(self.parse_unary()?))),
+            Some(Token::Open) => {
+                let query = self.parse_or()?;
+                match self.tokens.next() {
+                    Some(Token::Close) => Ok(query),
+                    _ => Err(QueryError::ParseError("missing )".into())),
+                }
+            }
+            Some(Token::Word(field)) => self.parse_comparison(field),
+            other => Err(QueryError::ParseError(format!(
+                "expected a field, found {}",
+                describe(other)
+            ))),
+        }
+    }
+
+    fn parse_comparison(&mut self, field: String) -> Result<GroupQuery, QueryError> {
+        let Some(Token::Op(op)) = self.tokens.peek().cloned() else {
+            return Ok(GroupQuery::Field(field));
+        };
+        self.tokens.next();
+        let value = match self.tokens.next() {
+            Some(Token::Word(value) | Token::Quoted(value)) => value,
+            other => {
+                return Err(QueryError::ParseError(format!(
+                    "expected a value after {}{}, found {}",
+                    field,
+                    op,
+                    describe(other)
+                )))
+            }
+        };
+        if op.is_ordering() && value.parse::<f64>().is_err() {
+            return Err(QueryError::ParseError(format!(
+                "{}{}{} compares with a non-number",
+                field, op, value
+            )));
+        }
+        Ok(GroupQuery::Compare(field, op, value))
+    }
+}
+
+fn get_field<'a>(map: &'a HashMap<String, String>, field: &str) -> Result<&'a str, QueryError> {
+    map.iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(field))
+        .map(|(_, value)| value.as_str())
+        .ok_or_else(|| QueryError::UnknownField(field.into()))
+}
+
+fn is_truthy(value: &str) -> bool {
+    !(value.is_empty() || value.eq_ignore_ascii_case("false") || value.parse() == Ok(0.0))
+}
+
+fn compare(actual: &str, op: Comparison, expected: &str) -> bool {
+    //! Numbers compare as numbers, everything else case-insensitively. A field that isn't
+    //! a number never matches an ordering comparison.
+    let numbers = (actual.parse::<f64>(), expected.parse::<f64>());
+    match (op, numbers) {
+        (Comparison::Contains, _) => actual.to_lowercase().contains(&expected.to_lowercase()),
+        (Comparison::Eq, (Ok(a), Ok(b))) => a == b,
+        (Comparison::Ne, (Ok(a), Ok(b))) => a != b,
+        (Comparison::Eq, _) => actual.eq_ignore_ascii_case(expected),
+        (Comparison::Ne, _) => !actual.eq_ignore_ascii_case(expected),
+        (Comparison::Gt, (Ok(a), Ok(b))) => a > b,
+        (Comparison::Ge, (Ok(a), Ok(b))) => a >= b,
+        (Comparison::Lt, (Ok(a), Ok(b))) => a < b,
+        (Comparison::Le, (Ok(a), Ok(b))) => a <= b,
+        _ => false,
+    }
+}
+
+impl FromStr for GroupQuery {
+    type Err = QueryError;
+
+    fn from_str(expr: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(expr)?.into_iter().peekable(),
+        };
+        let query = parser.parse_or()?;
+        match parser.tokens.next() {
+            None => Ok(query),
+            Some(token) => Err(QueryError::ParseError(format!(
+                "unexpected {} after {}",
+                token, query
+            ))),
+        }
+    }
+}
+
+impl Display for GroupQuery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Field(field) => write!(f, "{}", field),
+            Self::Compare(field, op, value) if value.chars().all(is_word_char) => {
+                write!(f, "{}{}{}", field, op, value)
+            }
+            Self::Compare(field, op, value) => write!(f, "{}{}{:?}", field, op, value),
+            Self::Not(query) => write!(f, "!({})", query),
+            Self::And(left, right) => write!(f, "({} && {})", left, right),
+            Self::Or(left, right) => write!(f, "({} || {})", left, right),
+        }
+    }
+}
+
+impl GroupQuery {
+    pub fn matches(&self, group: &ServerGroup) -> Result<bool, QueryError> {
+        self.eval(&group.to_map())
+    }
+
+    fn eval(&self, map: &HashMap<String, String>) -> Result<bool, QueryError> {
+        Ok(match self {
+            Self::Field(field) => is_truthy(get_field(map, field)?),
+            Self::Compare(field, op, value) => compare(get_field(map, field)?, *op, value),
+            Self::Not(query) => !query.eval(map)?,
+            Self::And(left, right) => left.eval(map)? && right.eval(map)?,
+            Self::Or(left, right) => left.eval(map)? || right.eval(map)?,
+        })
+    }
+
+    pub fn filter(&self, groups: Vec<ServerGroup>) -> Result<Vec<ServerGroup>, QueryError> {
+        //! The groups matching the query, in order. Fails on the first unknown field.
+        let mut matching = Vec::new();
+        for group in groups {
+            if self.matches(&group)? {
+                matching.push(group);
+            }
+        }
+        Ok(matching)
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::context_manager::ContextManager;
+
+use super::minecraft::MinecraftServer;
+
+const LOGIN: i32 = 3;
+const COMMAND: i32 = 2;
+const AUTH_RESPONSE: i32 = 2;
+const RESPONSE_VALUE: i32 = 0; // not a request, servers answer it after everything sent before
+const MAX_PACKET: usize = 4110; // largest packet a Minecraft server sends back
+
+#[derive(Error, Debug)]
+pub enum RconError {
+    #[error("Rcon Error: Not configured for group: `{0}`")]
+    NotConfigured(String),
+    #[error("Rcon Error: Could not connect: `{0}`")]
+    ConnectionError(String),
+    #[error("Rcon Error: Authentication failed: `{0}`")]
+    AuthenticationFailed(String),
+    #[error("Rcon Error: Invalid response: `{0}`")]
+    InvalidResponse(String),
+}
+
+impl From<std::io::Error> for RconError {
+    fn from(err: std::io::Error) -> Self {
+        RconError::ConnectionError(err.to_string())
+    }
+}
+
+/// How the manager reaches servers' consoles directly, as an alternative to the plugin
+/// command channel. The top-level port offset and password apply to every group
+/// without its own entry in `groups`.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct RconInfo {
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port_offset: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub groups: HashMap<String, RconSettings>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct RconSettings {
+    pub port_offset: u16, // rcon port = server port + offset
+    pub password: String, // {group} and {name} are filled in
+}
+
+/// Source RCON session with a Minecraft server. Replies longer than one packet (about
+/// 4KB) are split by the server and joined back together here.
+pub struct RconClient {
+    stream: TcpStream,
+    next_id: i32,
+}
+
+fn default_timeout_ms() -> u64 {
+    3000
+}
+
+impl Default for RconInfo {
+    fn default() -> Self {
+        Self {
+            timeout_ms: default_timeout_ms(),
+            port_offset: None,
+            password: None,
+            groups: HashMap::new(),
+        }
+    }
+}
+
+impl RconInfo {
+    pub fn get_timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    pub fn get_settings(&self, group: &str) -> Option<RconSettings> {
+        self.groups.get(group).cloned().or_else(|| {
+            Some(RconSettings {
+                port_offset: self.port_offset?,
+                password: self.password.clone()?,
+            })
+        })
+    }
+}
+
+impl RconSettings {
+    pub fn get_password(&self, server: &MinecraftServer) -> String {
+        self.password
+            .replace("{group}", server.get_group())
+            .replace("{name}", server.get_name())
+    }
+}
+
+pub fn run(
+    server: &MinecraftServer,
+    commands: &[&str],
+    ctx: &mut ContextManager,
+) -> Result<Vec<String>, RconError> {
+    //! Runs console commands on a live server in one session, returning their output.
+    let mut client = RconClient::for_server(server, ctx)?;
+    commands.iter().map(|command| client.run(command)).collect()
+}
+
+fn resolve(address: &str) -> Result<SocketAddr, RconError> {
+    address
+        .to_socket_addrs()?
+        .next()
+        .ok_or(RconError::ConnectionError(format!(
+            "{} did not resolve",
+            address
+        )))
+}
+
+impl RconClient {
+    pub fn connect(address: &str, password: &str, timeout: Duration) -> Result<Self, RconError> {
+        //! Connects to `host:port` and logs in. `timeout` applies to every read and write.
+        let stream = TcpStream::connect_timeout(&resolve(address)?, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        let mut client = Self { stream, next_id: 1 };
+        let id = client.send(LOGIN, password)?;
+        // some servers send an empty command response before the auth response
+        let (reply_id, kind, _) = match client.receive()? {
+            reply @ (_, AUTH_RESPONSE, _) => reply,
+            _ => client.receive()?,
+        };
+        match (reply_id, kind) {
+            (-1, _) => Err(RconError::AuthenticationFailed(address.into())),
+            (reply_id, AUTH_RESPONSE) if reply_id == id => Ok(client),
+            (reply_id, kind) => Err(RconError::InvalidResponse(format!(
+                "login reply {} of type {}",
+                reply_id, kind
+            ))),
+        }
+    }
+
+    pub fn for_server(
+        server: &MinecraftServer,
+        ctx: &mut ContextManager,
+    ) -> Result<Self, RconError> {
+        //! Connects to a live server with its group's rcon settings.
+        let info = &ctx.get_config().rcon;
+        let settings = info
+            .get_settings(server.get_group())
+            .ok_or(RconError::NotConfigured(server.get_group().clone()))?;
+        let address = format!(
+            "{}:{}",
+            server.get_public_address(),
+            server.get_port().saturating_add(settings.port_offset)
+        );
+        Self::connect(&address, &settings.get_password(server), info.get_timeout())
+    }
+
+    pub fn run(&mut self, command: &str) -> Result<String, RconError> {
+        //! Runs a console command (without the leading `/`) and returns its output. An
+        //! empty packet follows it: its reply comes after every packet of the command's,
+        //! so it marks where the output ends.
+        let id = self.send(COMMAND, command)?;
+        let end = self.send(RESPONSE_VALUE, "")?;
+        let mut output = String::new();
+        loop {
+            match self.receive()? {
+                (reply_id, _, body) if reply_id == id => output.push_str(&body),
+                (reply_id, _, _) if reply_id == end => return Ok(output),
+                // the rest of an earlier command's end marker
+                (reply_id, _, _) if (0..id).contains(&reply_id) => {}
+                (reply_id, _, _) => {
+                    return Err(RconError::InvalidResponse(format!(
+                        "reply to {} while waiting for {}",
+                        reply_id, id
+                    )))
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, kind: i32, body: &str) -> Result<i32, RconError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let length = (4 + 4 + body.len() + 2) as i32;
+        let mut packet = Vec::with_capacity(length as usize + 4);
+        packet.extend(length.to_le_bytes());
+        packet.extend(id.to_le_bytes());
+        packet.extend(kind.to_le_bytes());
+        packet.extend(body.as_bytes());
+        packet.extend([0, 0]);
+        self.stream.write_all(&packet)?;
+        Ok(id)
+    }
+
+    fn receive(&mut self) -> Result<(i32, i32, String), RconError> {
+        //! Reads one packet as (id, type, body).
+        let mut length = [0; 4];
+        self.stream.read_exact(&mut length)?;
+        let length = i32::from_le_bytes(length) as usize;
+        if !(10..=MAX_PACKET).contains(&length) {
+            return Err(RconError::InvalidResponse(format!(
+                "packet length {}",
+                length
+            )));
+        }
+        let mut packet = vec![0; length];
+        self.stream.read_exact(&mut packet)?;
+        let id = i32::from_le_bytes([packet[0], packet[1], packet[2], packet[3]]);
+        let kind = i32::from_le_bytes([packet[4], packet[5], packet[6], packet[7]]);
+        let body = String::from_utf8_lossy(&packet[8..length - 2]).into_owned();
+        Ok((id, kind, body))
+    }
+}
@@ -0,0 +1,147 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    context_manager::ContextManager,
+    events::{Event, EventKind},
+    region::Region,
+    store::entity::RedisEntity,
+};
+
+use super::{
+    crash_loop::CrashLoop, minecraft::MinecraftServer, restart, server_group::ServerGroup,
+};
+
+/// JVMs degrade after long uptimes, so the monitor drains and replaces instances older
+/// than their group's maximum age, outside of the region's `[scaling]` peak hours.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct RecyclingInfo {
+    #[serde(default)]
+    pub max_age_hours: BTreeMap<String, u64>, // group -> hours, groups not listed are never recycled
+    #[serde(default = "default_persistent")]
+    pub persistent: Vec<String>, // groups whose state lives in the instance, never recycled
+    #[serde(default = "default_per_reconcile")]
+    pub per_reconcile: usize, // instances a region worker recycles per reconcile
+}
+
+fn default_persistent() -> Vec<String> {
+    vec!["Clans".into()]
+}
+
+fn default_per_reconcile() -> usize {
+    1
+}
+
+impl Default for RecyclingInfo {
+    fn default() -> Self {
+        Self {
+            max_age_hours: BTreeMap::new(),
+            persistent: default_persistent(),
+            per_reconcile: default_per_reconcile(),
+        }
+    }
+}
+
+impl RecyclingInfo {
+    pub fn get_max_age(&self, group: &ServerGroup) -> Option<i64> {
+        //! Seconds an instance of `group` may run, None if it is never recycled.
+        if self.persistent.contains(&group.prefix) {
+            return None;
+        }
+        let hours = self.max_age_hours.get(&group.prefix)?;
+        Some(*hours as i64 * 3600)
+    }
+}
+
+/// An instance past its group's maximum age.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AgedInstance {
+    pub server: String,
+    pub group: ServerGroup,
+    pub server_num: usize,
+    pub age_secs: i64,
+}
+
+impl Display for AgedInstance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (up {}h{:02}m)",
+            self.server,
+            self.age_secs / 3600,
+            self.age_secs % 3600 / 60
+        )
+    }
+}
+
+pub fn get_aged(
+    region: Option<&Region>,
+    now: DateTime<Utc>,
+    ctx: &mut ContextManager,
+) -> Vec<AgedInstance> {
+    //! Instances (in `region`) past their group's maximum age, oldest first. Regions in
+    //! their peak hours and groups whose launches are paused are left alone.
+    let info = ctx.get_config().recycling.clone();
+    if info.max_age_hours.is_empty() {
+        return Vec::new();
+    }
+    let mut aged = Vec::new();
+    for group in ServerGroup::get_all(ctx).ok {
+        if region.is_some_and(|region| &group.region != region)
+            || ctx.get_config().scaling.get_multiplier(&group.region, now) > 1.0
+        {
+            continue;
+        }
+        let Some(max_age) = info.get_max_age(&group) else {
+            continue;
+        };
+        // replacements would fail to launch
+        if CrashLoop::get(&group.prefix, ctx)
+            .is_ok_and(|crash_loop| crash_loop.is_some_and(|crash_loop| crash_loop.is_paused()))
+        {
+            continue;
+        }
+        for server in MinecraftServer::from_server_group(&group, ctx).ok {
+            let age_secs = server.get_uptime_as_seconds(now.into());
+            let server_num = server
+                .get_name()
+                .rsplit_once('-')
+                .and_then(|(_, num)| num.parse::<usize>().ok());
+            if let (true, Some(server_num)) = (age_secs > max_age, server_num) {
+                aged.push(AgedInstance {
+                    server: server.get_name().clone(),
+                    group: group.clone(),
+                    server_num,
+                    age_secs,
+                });
+            }
+        }
+    }
+    aged.sort_by_key(|instance| std::cmp::Reverse(instance.age_secs));
+    aged
+}
+
+pub fn recycle(
+    region: Option<&Region>,
+    now: DateTime<Utc>,
+    ctx: &mut ContextManager,
+) -> Vec<(AgedInstance, Result<(), String>)> {
+    //! Drains and replaces up to `per_reconcile` of the oldest aged instances, one at a time.
+    let per_reconcile = ctx.get_config().recycling.per_reconcile;
+    let mut recycled = Vec::new();
+    for instance in get_aged(region, now, ctx).into_iter().take(per_reconcile) {
+        let result = restart::restart_instance(&instance.group, instance.server_num, ctx);
+        if result.is_ok() {
+            Event::new(
+                EventKind::InstanceRecycled,
+                &instance.server,
+                instance.to_string(),
+            )
+            .emit(ctx);
+        }
+        recycled.push((instance, result));
+    }
+    recycled
+}
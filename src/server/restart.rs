@@ -0,0 +1,268 @@
+use std::{fmt::Display, time::Duration};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    context_manager::ContextManager,
+    events::{Event, EventKind},
+};
+
+use super::{
+    broadcast::{format_remaining, Broadcast, BroadcastTarget, Countdown, DEFAULT_WARNINGS},
+    commands::BroadcastStyle,
+    dedicated::{relocation::drain_instance, server::DedicatedServer},
+    minecraft::{GameDisplayStatus, GameJoinStatus, MinecraftServer},
+    rightsizing,
+    server_group::ServerGroup,
+};
+
+/// Hash of group -> scheduled restart, so another process can see and cancel it.
+const RESTARTS_KEY: &str = "restarts.scheduled";
+pub const DEFAULT_RESTART_MESSAGE: &str = "{server} restarts in {remaining}";
+/// Joins are closed this long before the restart.
+const CLOSE_JOINS_BEFORE: Duration = Duration::from_secs(30);
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct ScheduledRestart {
+    pub group: String,
+    pub at: i64,            // seconds since epoch
+    pub warnings: Vec<u64>, // seconds before `at`
+    pub message: String,    // broadcast template
+}
+
+/// Instances a restart went through.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RestartReport {
+    pub restarted: Vec<String>,
+    pub failed: Vec<(String, String)>, // (instance, why)
+    pub cancelled: bool,
+}
+
+impl Display for ScheduledRestart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let time = chrono::DateTime::from_timestamp(self.at, 0)
+            .map(|time| time.with_timezone(&Local).to_rfc3339())
+            .unwrap_or(self.at.to_string());
+        write!(
+            f,
+            "{} at {} (warnings: {:?}s)",
+            self.group, time, self.warnings
+        )
+    }
+}
+
+impl Display for RestartReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.cancelled {
+            return write!(f, "Restart cancelled");
+        }
+        write!(
+            f,
+            "Restarted {} instance(s), {} failure(s)",
+            self.restarted.len(),
+            self.failed.len()
+        )
+    }
+}
+
+impl ScheduledRestart {
+    pub fn new(group: &str, at: i64, warnings: Option<Vec<u64>>, message: Option<String>) -> Self {
+        Self {
+            group: group.into(),
+            at,
+            warnings: warnings.unwrap_or(DEFAULT_WARNINGS.to_vec()),
+            message: message.unwrap_or(DEFAULT_RESTART_MESSAGE.into()),
+        }
+    }
+
+    pub fn get(group: &str, ctx: &mut ContextManager) -> Result<Option<Self>, redis::RedisError> {
+        let raw: Option<String> = redis::cmd("HGET")
+            .arg(RESTARTS_KEY)
+            .arg(group)
+            .query(ctx.get_connection())?;
+        Ok(raw.and_then(|raw| serde_json::from_str(&raw).ok()))
+    }
+
+    pub fn get_all(ctx: &mut ContextManager) -> Result<Vec<Self>, redis::RedisError> {
+        let raw: Vec<String> = redis::cmd("HVALS")
+            .arg(RESTARTS_KEY)
+            .query(ctx.get_connection())?;
+        let mut restarts: Vec<Self> = raw
+            .iter()
+            .filter_map(|raw| serde_json::from_str(raw).ok())
+            .collect();
+        restarts.sort_by_key(|restart| restart.at);
+        Ok(restarts)
+    }
+
+    pub fn save(&self, ctx: &mut ContextManager) -> Result<(), redis::RedisError> {
+        let raw = serde_json::to_string(self).map_err(|err| {
+            redis::RedisError::from((
+                redis::ErrorKind::ClientError,
+                "Restart serialization error",
+                err.to_string(),
+            ))
+        })?;
+        redis::cmd("HSET")
+            .arg(RESTARTS_KEY)
+            .arg(&self.group)
+            .arg(raw)
+            .query(ctx.get_connection())
+    }
+
+    pub fn cancel(group: &str, ctx: &mut ContextManager) -> Result<bool, redis::RedisError> {
+        //! Cancels a group's scheduled restart; the process running it stops at its next step.
+        let removed: usize = redis::cmd("HDEL")
+            .arg(RESTARTS_KEY)
+            .arg(group)
+            .query(ctx.get_connection())?;
+        if removed > 0 {
+            Event::new(
+                EventKind::RestartCancelled,
+                group,
+                "restart cancelled".into(),
+            )
+            .emit(ctx);
+        }
+        Ok(removed > 0)
+    }
+
+    fn is_cancelled(&self, ctx: &mut ContextManager) -> bool {
+        //! Cancelled, or replaced by a different schedule for the same group.
+        Self::get(&self.group, ctx).is_ok_and(|current| current.as_ref() != Some(self))
+    }
+
+    pub fn run(&self, ctx: &mut ContextManager) -> Result<RestartReport, redis::RedisError> {
+        //! Warns the group's players until the restart time, closes joins shortly before it,
+        //! then drains and restarts every instance one at a time, reopening each replacement.
+        let group = ServerGroup::from_str(&self.group, ctx)?;
+        self.save(ctx)?;
+        Event::new(EventKind::RestartScheduled, &self.group, self.to_string()).emit(ctx);
+        let broadcast = Broadcast::new(
+            BroadcastTarget::Group(Box::new(group.clone())),
+            &self.message,
+            BroadcastStyle::CHAT,
+        );
+        let countdown = Countdown::new(self.warnings.iter().map(|&secs| Duration::from_secs(secs)));
+        let remaining = Duration::from_secs((self.at - Local::now().timestamp()).max(0) as u64);
+        let mut is_cancelled = |ctx: &mut ContextManager| self.is_cancelled(ctx);
+        broadcast.send(Some(remaining), ctx)?;
+        let mut finished = countdown.run(
+            &broadcast,
+            remaining,
+            CLOSE_JOINS_BEFORE,
+            &mut is_cancelled,
+            ctx,
+        )?;
+        if finished {
+            set_joinable(&group, false, ctx);
+            finished = countdown.run(
+                &broadcast,
+                remaining.min(CLOSE_JOINS_BEFORE),
+                Duration::ZERO,
+                &mut is_cancelled,
+                ctx,
+            )?;
+        }
+        if !finished {
+            if !self.is_cancelled(ctx) {
+                // interrupted by a shutdown request rather than `cancel`
+                Self::cancel(&self.group, ctx)?;
+            }
+            set_joinable(&group, true, ctx);
+            return Ok(RestartReport {
+                cancelled: true,
+                ..Default::default()
+            });
+        }
+        let group = match rightsizing::apply_pending(&group, ctx) {
+            Ok(group) => group,
+            Err(err) => {
+                log::warn!("{} keeps its resources: {}", self.group, err);
+                group
+            }
+        };
+        let report = restart_instances(&group, ctx);
+        redis::cmd("HDEL")
+            .arg(RESTARTS_KEY)
+            .arg(&self.group)
+            .query::<()>(ctx.get_connection())?;
+        Event::new(EventKind::RestartFinished, &self.group, report.to_string()).emit(ctx);
+        Ok(report)
+    }
+}
+
+fn set_joinable(group: &ServerGroup, joinable: bool, ctx: &mut ContextManager) {
+    let (join_status, display_status) = match joinable {
+        true => (GameJoinStatus::OPEN, GameDisplayStatus::WAITING),
+        false => (GameJoinStatus::CLOSED, GameDisplayStatus::CLOSING),
+    };
+    for server in MinecraftServer::from_server_group(group, ctx).ok {
+        let _ = server.set_join_status(join_status.clone(), ctx);
+        let _ = server.set_display_status(display_status.clone(), ctx);
+    }
+}
+
+fn restart_instances(group: &ServerGroup, ctx: &mut ContextManager) -> RestartReport {
+    //! Drains, kills and relaunches each instance with the same server number.
+    ctx.recover_instances();
+    ctx.with_dedicated_servers(|servers, ctx| {
+        let mut report = RestartReport::default();
+        for ds in servers.servers.iter_mut() {
+            for server_num in ds.get_server_nums(group) {
+                let name = format!("{}-{}", group.name, server_num);
+                match restart_on(ds, group, server_num, ctx) {
+                    Ok(()) => report.restarted.push(name),
+                    Err(err) => report.failed.push((name, err)),
+                }
+            }
+        }
+        report
+    })
+}
+
+pub fn restart_instance(
+    group: &ServerGroup,
+    server_num: usize,
+    ctx: &mut ContextManager,
+) -> Result<(), String> {
+    //! Drains, kills and relaunches one instance of `group` on the node tracking it.
+    let name = format!("{}-{}", group.name, server_num);
+    if !ctx.get_dedicated_servers().is_tracked(&name) {
+        ctx.recover_instances();
+    }
+    ctx.with_dedicated_servers(|servers, ctx| {
+        let ds = servers
+            .servers
+            .iter_mut()
+            .find(|ds| ds.get_server_nums(group).contains(&server_num))
+            .ok_or(format!("{} is not tracked by any node", name))?;
+        restart_on(ds, group, server_num, ctx)
+    })
+}
+
+fn restart_on(
+    ds: &mut DedicatedServer,
+    group: &ServerGroup,
+    server_num: usize,
+    ctx: &mut ContextManager,
+) -> Result<(), String> {
+    let name = format!("{}-{}", group.name, server_num);
+    if !drain_instance(&name, &group.region, DRAIN_TIMEOUT, ctx) {
+        log::warn!(
+            "{} still has players after {}, restarting anyway",
+            name,
+            format_remaining(DRAIN_TIMEOUT)
+        );
+    }
+    ds.kill_server(group, server_num, true, ctx)
+        .map_err(|err| err.to_string())?;
+    ds.start_server(group, server_num, ctx)
+        .map_err(|err| err.to_string())?;
+    if let Ok(server) = MinecraftServer::get_status(&name, &group.region, ctx) {
+        let _ = server.set_join_status(GameJoinStatus::OPEN, ctx);
+    }
+    Ok(())
+}
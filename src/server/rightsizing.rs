@@ -0,0 +1,466 @@
+use std::{collections::HashMap, fmt::Display};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    context_manager::ContextManager,
+    events::{Event, EventKind},
+    store::entity::RedisEntity,
+    undo::GroupChange,
+};
+
+use super::{
+    dedicated::sampling::ProcessSample, minecraft::MinecraftServer, server_group::ServerGroup,
+};
+
+/// Hash of group -> `GroupUsage`, what the monitor observed of the group's instances.
+const USAGE_KEY: &str = "servermonitor.usage";
+/// Hash of group -> `Suggestion` waiting for the group's next rolling restart.
+const PENDING_KEY: &str = "servermonitor.rightsizing.pending";
+
+#[derive(Error, Debug)]
+pub enum RightsizingError {
+    #[error("Rightsizing Error: No suggestion for `{0}`")]
+    NoSuggestion(String),
+    #[error("Rightsizing Error: Invalid resources: `{0}`")]
+    InvalidResources(String),
+    #[error("Rightsizing Error: Redis Error: `{0}`")]
+    RedisError(String),
+}
+
+impl From<redis::RedisError> for RightsizingError {
+    fn from(err: redis::RedisError) -> Self {
+        RightsizingError::RedisError(err.to_string())
+    }
+}
+
+/// One step of the resource ladder groups are moved along.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ResourceTier {
+    pub ram: u16, // in MB
+    pub cpu: u8,
+}
+
+/// How the advisor decides a group should move to another tier.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct RightsizingInfo {
+    #[serde(default = "default_tiers")]
+    pub tiers: Vec<ResourceTier>,
+    #[serde(default = "default_min_samples")]
+    pub min_samples: u64, // instance statuses seen before suggesting anything
+    #[serde(default = "default_upsize_percent")]
+    pub upsize_percent: u32, // peak ram use, of the group's ram, that asks for the next tier
+    #[serde(default = "default_downsize_percent")]
+    pub downsize_percent: u32, // peak ram use, of the lower tier's ram, it must stay under
+    #[serde(default)]
+    pub auto_schedule: bool, // queue suggestions for the next restart without asking
+}
+
+/// Ram and player counts the monitor saw on a group's instances while it had `ram`.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct GroupUsage {
+    pub group: String,
+    pub ram: u16, // the group's ram when sampled; samples start over when it changes
+    pub samples: u64,
+    pub ram_sum: u64,
+    pub ram_peak: u16,
+    pub players_sum: u64,
+    pub players_peak: u8,
+    pub max_players: u8,
+    pub since: i64, // seconds since epoch
+    #[serde(default)]
+    pub rss_samples: u64, // processes sampled over ssh, see `dedicated::sampling`
+    #[serde(default)]
+    pub rss_peak: u64, // in MB
+    #[serde(default)]
+    pub cpu_peak: u32, // % of one core
+}
+
+/// A move of a group to another tier, with what it is based on.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct Suggestion {
+    pub group: String,
+    pub from: ResourceTier,
+    pub to: ResourceTier,
+    pub evidence: String,
+}
+
+fn default_tiers() -> Vec<ResourceTier> {
+    [
+        (512, 1),
+        (768, 1),
+        (1024, 2),
+        (1536, 2),
+        (2048, 4),
+        (3072, 4),
+    ]
+    .into_iter()
+    .map(|(ram, cpu)| ResourceTier { ram, cpu })
+    .collect()
+}
+
+fn default_min_samples() -> u64 {
+    120
+}
+
+fn default_upsize_percent() -> u32 {
+    90
+}
+
+fn default_downsize_percent() -> u32 {
+    70
+}
+
+impl Default for RightsizingInfo {
+    fn default() -> Self {
+        Self {
+            tiers: default_tiers(),
+            min_samples: default_min_samples(),
+            upsize_percent: default_upsize_percent(),
+            downsize_percent: default_downsize_percent(),
+            auto_schedule: false,
+        }
+    }
+}
+
+impl Display for ResourceTier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}MB/{}cpu", self.ram, self.cpu)
+    }
+}
+
+impl Display for Suggestion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {} ({})",
+            self.group, self.from, self.to, self.evidence
+        )
+    }
+}
+
+impl Display for GroupUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "peak {}MB of {}MB, avg {}MB; players avg {:.1}, peak {}/{}; {} samples",
+            self.ram_peak,
+            self.ram,
+            self.get_average_ram(),
+            self.get_average_players(),
+            self.players_peak,
+            self.max_players,
+            self.samples
+        )?;
+        if self.rss_samples > 0 {
+            write!(
+                f,
+                "; rss peak {}MB, cpu peak {}% over {} process samples",
+                self.rss_peak, self.cpu_peak, self.rss_samples
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl GroupUsage {
+    pub fn get_average_ram(&self) -> u64 {
+        self.ram_sum / self.samples.max(1)
+    }
+
+    pub fn get_peak_ram(&self) -> u64 {
+        //! The higher of what the instances reported and what their processes were seen
+        //! using, the jvm's own overhead included.
+        (self.ram_peak as u64).max(self.rss_peak)
+    }
+
+    pub fn get_average_players(&self) -> f64 {
+        self.players_sum as f64 / self.samples.max(1) as f64
+    }
+
+    pub fn get_all(ctx: &mut ContextManager) -> Result<HashMap<String, Self>, redis::RedisError> {
+        let raw: Vec<String> = redis::cmd("HVALS")
+            .arg(USAGE_KEY)
+            .query(ctx.get_connection())?;
+        Ok(raw
+            .iter()
+            .filter_map(|raw| serde_json::from_str::<Self>(raw).ok())
+            .map(|usage| (usage.group.clone(), usage))
+            .collect())
+    }
+
+    fn add(&mut self, status: &MinecraftServer) {
+        self.samples += 1;
+        self.ram_sum += status.get_ram() as u64;
+        self.ram_peak = self.ram_peak.max(status.get_ram());
+        self.players_sum += status.get_player_count() as u64;
+        self.players_peak = self.players_peak.max(status.get_player_count());
+        self.max_players = self.max_players.max(status.get_max_player_count());
+    }
+
+    fn add_process(&mut self, sample: &ProcessSample) {
+        self.rss_samples += 1;
+        self.rss_peak = self.rss_peak.max(sample.rss_mb);
+        self.cpu_peak = self.cpu_peak.max(sample.cpu_percent.round() as u32);
+    }
+}
+
+impl Suggestion {
+    pub fn get_pending(ctx: &mut ContextManager) -> Result<Vec<Self>, redis::RedisError> {
+        let raw: Vec<String> = redis::cmd("HVALS")
+            .arg(PENDING_KEY)
+            .query(ctx.get_connection())?;
+        let mut pending: Vec<Self> = raw
+            .iter()
+            .filter_map(|raw| serde_json::from_str(raw).ok())
+            .collect();
+        pending.sort_by(|a, b| a.group.cmp(&b.group));
+        Ok(pending)
+    }
+
+    pub fn schedule(&self, ctx: &mut ContextManager) -> Result<(), redis::RedisError> {
+        //! Queues the move for the group's next rolling restart.
+        let raw = serde_json::to_string(self).map_err(|err| {
+            redis::RedisError::from((
+                redis::ErrorKind::ClientError,
+                "Suggestion serialization error",
+                err.to_string(),
+            ))
+        })?;
+        redis::cmd("HSET")
+            .arg(PENDING_KEY)
+            .arg(&self.group)
+            .arg(raw)
+            .query(ctx.get_connection())
+    }
+
+    pub fn cancel(group: &str, ctx: &mut ContextManager) -> Result<bool, redis::RedisError> {
+        let removed: usize = redis::cmd("HDEL")
+            .arg(PENDING_KEY)
+            .arg(group)
+            .query(ctx.get_connection())?;
+        Ok(removed > 0)
+    }
+}
+
+fn get_usage<'a>(
+    usages: &'a mut HashMap<String, GroupUsage>,
+    group: &str,
+    ram: u16,
+) -> &'a mut GroupUsage {
+    //! The group's usage, started over when its ram changed since.
+    let usage = usages.entry(group.into()).or_default();
+    if usage.ram != ram || (usage.samples == 0 && usage.rss_samples == 0) {
+        *usage = GroupUsage {
+            group: group.into(),
+            ram,
+            since: Local::now().timestamp(),
+            ..Default::default()
+        };
+    }
+    usage
+}
+
+fn save_usages(
+    usages: &HashMap<String, GroupUsage>,
+    changed: Vec<String>,
+    ctx: &mut ContextManager,
+) -> Result<(), redis::RedisError> {
+    if changed.is_empty() {
+        return Ok(());
+    }
+    let mut cmd = redis::cmd("HSET");
+    cmd.arg(USAGE_KEY);
+    for group in changed {
+        let raw = serde_json::to_string(&usages[&group]).map_err(|err| {
+            redis::RedisError::from((
+                redis::ErrorKind::ClientError,
+                "Usage serialization error",
+                err.to_string(),
+            ))
+        })?;
+        cmd.arg(group).arg(raw);
+    }
+    cmd.query(ctx.get_connection())
+}
+
+pub fn record(
+    groups: &[ServerGroup],
+    statuses: &[MinecraftServer],
+    ctx: &mut ContextManager,
+) -> Result<(), redis::RedisError> {
+    //! Adds one sample per status to its group's usage, starting over for groups whose
+    //! ram changed since.
+    let groups: HashMap<&String, u16> = groups
+        .iter()
+        .map(|group| (&group.name, group.ram))
+        .collect();
+    let mut usages = GroupUsage::get_all(ctx)?;
+    let mut changed: Vec<String> = Vec::new();
+    for status in statuses.iter() {
+        let Some(&ram) = groups.get(status.get_group()) else {
+            continue;
+        };
+        get_usage(&mut usages, status.get_group(), ram).add(status);
+        if !changed.contains(status.get_group()) {
+            changed.push(status.get_group().clone());
+        }
+    }
+    save_usages(&usages, changed, ctx)
+}
+
+pub fn record_processes(
+    groups: &[ServerGroup],
+    samples: &[ProcessSample],
+    ctx: &mut ContextManager,
+) -> Result<(), redis::RedisError> {
+    //! `record` for process samples taken over ssh.
+    let groups: HashMap<&String, u16> = groups
+        .iter()
+        .map(|group| (&group.name, group.ram))
+        .collect();
+    let mut usages = GroupUsage::get_all(ctx)?;
+    let mut changed: Vec<String> = Vec::new();
+    for sample in samples.iter() {
+        let Some(&ram) = groups.get(&sample.group) else {
+            continue;
+        };
+        get_usage(&mut usages, &sample.group, ram).add_process(sample);
+        if !changed.contains(&sample.group) {
+            changed.push(sample.group.clone());
+        }
+    }
+    save_usages(&usages, changed, ctx)
+}
+
+pub fn get_over_ram(
+    groups: &[ServerGroup],
+    samples: &[ProcessSample],
+    percent: u32,
+) -> Vec<(ProcessSample, u16)> {
+    //! Sampled instances whose rss is over `percent` of their group's ram, with that ram.
+    samples
+        .iter()
+        .filter_map(|sample| {
+            let group = groups.iter().find(|group| group.name == sample.group)?;
+            (sample.rss_mb * 100 > group.ram as u64 * percent as u64)
+                .then(|| (sample.clone(), group.ram))
+        })
+        .collect()
+}
+
+pub fn suggest(
+    group: &ServerGroup,
+    usage: &GroupUsage,
+    info: &RightsizingInfo,
+) -> Option<Suggestion> {
+    //! The next tier up when the group's peak ram use nears its ram, or the next tier down
+    //! when the peak would still leave headroom there.
+    if usage.samples < info.min_samples || usage.ram != group.ram {
+        return None;
+    }
+    let from = ResourceTier {
+        ram: group.ram,
+        cpu: group.cpu,
+    };
+    let mut tiers = info.tiers.clone();
+    tiers.sort_by_key(|tier| tier.ram);
+    let peak = usage.get_peak_ram() * 100;
+    let to = if peak >= group.ram as u64 * info.upsize_percent as u64 {
+        tiers.into_iter().find(|tier| tier.ram > group.ram)?
+    } else {
+        tiers
+            .into_iter()
+            .rev()
+            .find(|tier| tier.ram < group.ram)
+            .filter(|tier| peak < tier.ram as u64 * info.downsize_percent as u64)?
+    };
+    Some(Suggestion {
+        group: group.prefix.clone(),
+        from,
+        to,
+        evidence: usage.to_string(),
+    })
+}
+
+pub fn advise(ctx: &mut ContextManager) -> Result<Vec<Suggestion>, redis::RedisError> {
+    //! Suggestions for every group with enough usage observed.
+    let info = ctx.get_config().rightsizing.clone();
+    let usages = GroupUsage::get_all(ctx)?;
+    let mut groups = ServerGroup::get_all(ctx).ok;
+    groups.sort_by(|a, b| a.prefix.cmp(&b.prefix));
+    Ok(groups
+        .iter()
+        .filter_map(|group| suggest(group, usages.get(&group.name)?, &info))
+        .collect())
+}
+
+pub fn schedule_new(ctx: &mut ContextManager) -> Result<Vec<Suggestion>, redis::RedisError> {
+    //! Queues suggestions for groups without a pending one, noting each as an event.
+    let pending: Vec<String> = Suggestion::get_pending(ctx)?
+        .into_iter()
+        .map(|suggestion| suggestion.group)
+        .collect();
+    let mut scheduled = Vec::new();
+    for suggestion in advise(ctx)? {
+        if pending.contains(&suggestion.group) {
+            continue;
+        }
+        suggestion.schedule(ctx)?;
+        Event::new(
+            EventKind::GroupUpdated,
+            &suggestion.group,
+            format!(
+                "resize to {} queued for the next restart ({})",
+                suggestion.to, suggestion.evidence
+            ),
+        )
+        .emit(ctx);
+        scheduled.push(suggestion);
+    }
+    Ok(scheduled)
+}
+
+pub fn apply_pending(
+    group: &ServerGroup,
+    ctx: &mut ContextManager,
+) -> Result<ServerGroup, RightsizingError> {
+    //! Applies the group's queued move, if any, returning the group as the restart should
+    //! launch it. A move whose starting tier no longer matches the group is dropped.
+    let raw: Option<String> = redis::cmd("HGET")
+        .arg(PENDING_KEY)
+        .arg(&group.prefix)
+        .query(ctx.get_connection())?;
+    let Some(suggestion) = raw.and_then(|raw| serde_json::from_str::<Suggestion>(&raw).ok()) else {
+        return Ok(group.clone());
+    };
+    Suggestion::cancel(&group.prefix, ctx)?;
+    if suggestion.from.ram != group.ram || suggestion.from.cpu != group.cpu {
+        return Ok(group.clone());
+    }
+    let mut resized = group.clone();
+    resized.ram = suggestion.to.ram;
+    resized.cpu = suggestion.to.cpu;
+    resized
+        .validate_resources(ctx.get_config())
+        .map_err(|err| RightsizingError::InvalidResources(err.to_string()))?;
+    resized
+        .save(ctx)
+        .map_err(|err| RightsizingError::RedisError(err.to_string()))?;
+    if let Some(change) = GroupChange::between(group, &resized) {
+        change.emit(&format!("resized to {} by rightsizing", suggestion.to), ctx);
+    }
+    Ok(resized)
+}
+
+pub fn get_suggestion(
+    group: &str,
+    ctx: &mut ContextManager,
+) -> Result<Suggestion, RightsizingError> {
+    advise(ctx)?
+        .into_iter()
+        .find(|suggestion| suggestion.group == group)
+        .ok_or(RightsizingError::NoSuggestion(group.into()))
+}
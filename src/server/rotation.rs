@@ -0,0 +1,167 @@
+use std::{fmt::Display, str::FromStr};
+
+use chrono::{DateTime, Datelike, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    context_manager::ContextManager,
+    store::entity::RedisEntity,
+    strategy::{TimeOfDay, UtcOffset},
+    undo::GroupChange,
+};
+
+use super::server_group::ServerGroup;
+
+/// Day of the week as "Mon" or "Monday".
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Eq, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Day(Weekday);
+
+/// Games a group runs during a window. A window without `start` and `end` lasts the
+/// whole day; one that wraps past midnight belongs to the day it starts on.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct RotationSlot {
+    pub name: String, // e.g. "Throwback Thursday"
+    #[serde(default)]
+    pub days: Vec<Day>, // every day if empty
+    #[serde(default)]
+    pub start: Option<TimeOfDay>,
+    #[serde(default)]
+    pub end: Option<TimeOfDay>,
+    pub games: Vec<String>,
+}
+
+/// Timetable of an arcade group's `games` list. The first slot active at a time wins;
+/// outside every slot the group runs `default_games`.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Rotation {
+    pub group: String,
+    #[serde(default)]
+    pub utc_offset: UtcOffset,
+    pub default_games: Vec<String>,
+    #[serde(default)]
+    pub slots: Vec<RotationSlot>,
+    #[serde(default)]
+    pub restart: bool, // restart the group's instances after a change, see `rotation apply`
+}
+
+/// A group whose games were (or would be) changed by its rotation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RotationChange {
+    pub group: String,
+    pub slot: Option<String>, // None for the default games
+    pub from: String,
+    pub to: String,
+    pub restart: bool,
+}
+
+impl TryFrom<String> for Day {
+    type Error = String;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Weekday::from_str(&value)
+            .map(Self)
+            .map_err(|_| format!("invalid day {:?} (expected e.g. Thu or Thursday)", value))
+    }
+}
+
+impl From<Day> for String {
+    fn from(day: Day) -> Self {
+        day.0.to_string()
+    }
+}
+
+impl Display for RotationChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {} ({})",
+            self.group,
+            if self.from.is_empty() {
+                "<none>"
+            } else {
+                &self.from
+            },
+            self.to,
+            self.slot.as_deref().unwrap_or("default")
+        )
+    }
+}
+
+impl RotationSlot {
+    pub fn is_active(&self, day: Weekday, time: TimeOfDay) -> bool {
+        let (start, end) = match (self.start, self.end) {
+            (None, None) => return self.is_on(day),
+            (start, end) => (start.unwrap_or_default(), end.unwrap_or_default()),
+        };
+        if !time.is_within(start, end) {
+            return false;
+        }
+        // past midnight in a window that started the day before
+        let wrapped = start >= end && time < end;
+        self.is_on(if wrapped { day.pred() } else { day })
+    }
+
+    fn is_on(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.iter().any(|Day(on)| *on == day)
+    }
+}
+
+impl Rotation {
+    pub fn get_active(&self, now: DateTime<Utc>) -> Option<&RotationSlot> {
+        let local = self.utc_offset.to_local(now);
+        let time = TimeOfDay::of(&local);
+        self.slots
+            .iter()
+            .find(|slot| slot.is_active(local.weekday(), time))
+    }
+
+    pub fn get_games(&self, now: DateTime<Utc>) -> String {
+        //! The `games` field the group should have at `now`.
+        self.get_active(now)
+            .map_or(&self.default_games, |slot| &slot.games)
+            .join(",")
+    }
+
+    pub fn plan(&self, now: DateTime<Utc>, ctx: &mut ContextManager) -> Option<RotationChange> {
+        //! The change this rotation calls for at `now`, None if the group already matches
+        //! (or can't be read).
+        let group = ServerGroup::get(&self.group, ctx).ok()?;
+        let from = group.games.unwrap_or_default();
+        let to = self.get_games(now);
+        (from != to).then(|| RotationChange {
+            group: self.group.clone(),
+            slot: self.get_active(now).map(|slot| slot.name.clone()),
+            from,
+            to,
+            restart: self.restart,
+        })
+    }
+}
+
+impl RotationChange {
+    pub fn apply(&self, ctx: &mut ContextManager) -> Result<(), String> {
+        //! Writes the new `games` list; Arcade servers pick it up at their next game.
+        let before = ServerGroup::get(&self.group, ctx).map_err(|err| err.to_string())?;
+        let mut group = before.clone();
+        group.games = Some(self.to.clone());
+        group.save(ctx).map_err(|err| err.to_string())?;
+        if let Some(change) = GroupChange::between(&before, &group) {
+            change.emit(
+                &format!(
+                    "rotated to {}",
+                    self.slot.as_deref().unwrap_or("default games")
+                ),
+                ctx,
+            );
+        }
+        Ok(())
+    }
+}
+
+pub fn plan_rotations(now: DateTime<Utc>, ctx: &mut ContextManager) -> Vec<RotationChange> {
+    let rotations = ctx.get_config().rotations.clone();
+    rotations
+        .iter()
+        .filter_map(|rotation| rotation.plan(now, ctx))
+        .collect()
+}
@@ -0,0 +1,266 @@
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+use crate::{
+    config::models::Config,
+    region::Region,
+    server::{
+        server_group::ServerGroup,
+        version::{self, DEFAULT_MINECRAFT_VERSION},
+    },
+};
+
+/// JSON type of a group field's value; the hash itself stores every value as a string.
+#[derive(Serialize, Clone, Copy, Debug, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    String,
+    Integer,
+    Boolean,
+}
+
+/// A `ServerGroup` field type's schema, looked up from the struct's own field types so
+/// the table in `get_group_schema` can't disagree with it.
+trait SchemaType {
+    const TYPE: FieldType;
+    const NULLABLE: bool = false;
+    const MAXIMUM: Option<u64> = None;
+}
+
+impl SchemaType for String {
+    const TYPE: FieldType = FieldType::String;
+}
+
+impl SchemaType for Option<String> {
+    const TYPE: FieldType = FieldType::String;
+    const NULLABLE: bool = true;
+}
+
+impl SchemaType for bool {
+    const TYPE: FieldType = FieldType::Boolean;
+}
+
+impl SchemaType for u8 {
+    const TYPE: FieldType = FieldType::Integer;
+    const MAXIMUM: Option<u64> = Some(u8::MAX as u64);
+}
+
+impl SchemaType for u16 {
+    const TYPE: FieldType = FieldType::Integer;
+    const MAXIMUM: Option<u64> = Some(u16::MAX as u64);
+}
+
+impl SchemaType for Region {
+    const TYPE: FieldType = FieldType::String;
+}
+
+/// One field of a `servergroups.<prefix>` hash, for UIs rendering edit forms and for
+/// checking values before they are written.
+#[derive(Serialize, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldSchema {
+    pub key: &'static str,   // hash field
+    pub field: &'static str, // `ServerGroup` field
+    #[serde(rename = "type")]
+    pub kind: FieldType,
+    pub nullable: bool, // an empty value (or "null") leaves it unset
+    pub required: bool, // a group whose hash lacks it can't be read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>, // read when the hash lacks it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minimum: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maximum: Option<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub one_of: Vec<String>, // the only values accepted
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub examples: Vec<String>, // common values, others may be accepted too
+    pub read_only: bool, // only changed through `group rename`
+    pub protected: bool, // see `protected_fields`
+}
+
+impl FieldSchema {
+    fn minimum(mut self, minimum: u64) -> Self {
+        self.minimum = Some(minimum);
+        self
+    }
+
+    fn default_value(mut self, default: &str) -> Self {
+        self.required = false;
+        self.default = Some(default.into());
+        self
+    }
+
+    fn one_of(mut self, values: Vec<String>) -> Self {
+        self.one_of = values;
+        self
+    }
+
+    fn examples(mut self, values: Vec<String>) -> Self {
+        self.examples = values;
+        self
+    }
+
+    fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    pub fn check(&self, value: &str) -> Result<(), String> {
+        //! Whether `value` may be written to this field, and why not.
+        if self.nullable && (value.is_empty() || value == "null") {
+            return Ok(());
+        }
+        match self.kind {
+            FieldType::Boolean if value != "true" && value != "false" => {
+                return Err(format!(
+                    "{} must be true or false, not {:?}",
+                    self.key, value
+                ));
+            }
+            FieldType::Integer => {
+                let number: u64 = value
+                    .parse()
+                    .map_err(|_| format!("{} must be a whole number, not {:?}", self.key, value))?;
+                if self.minimum.is_some_and(|minimum| number < minimum)
+                    || self.maximum.is_some_and(|maximum| number > maximum)
+                {
+                    return Err(format!(
+                        "{} must be between {} and {}, not {}",
+                        self.key,
+                        self.minimum.unwrap_or(0),
+                        self.maximum.unwrap_or(u64::MAX),
+                        number
+                    ));
+                }
+            }
+            _ => {}
+        }
+        if !self.one_of.is_empty() && !self.one_of.iter().any(|allowed| allowed == value) {
+            return Err(format!(
+                "{} must be one of {}, not {:?}",
+                self.key,
+                self.one_of.join(", "),
+                value
+            ));
+        }
+        if self.key == "minecraftVersion" && version::get_protocol(value).is_none() {
+            return Err(format!("{} {:?} is not a known release", self.key, value));
+        }
+        Ok(())
+    }
+}
+
+fn field<T: SchemaType>(
+    key: &'static str,
+    field: &'static str,
+    _: fn(&ServerGroup) -> &T,
+) -> FieldSchema {
+    //! Required unless it is a flag (unset reads as false) or nullable.
+    let default = match T::TYPE {
+        FieldType::Boolean => Some("false".into()),
+        _ => None,
+    };
+    FieldSchema {
+        key,
+        field,
+        kind: T::TYPE,
+        nullable: T::NULLABLE,
+        required: default.is_none() && !T::NULLABLE,
+        default,
+        minimum: (T::TYPE == FieldType::Integer).then_some(0),
+        maximum: T::MAXIMUM,
+        one_of: Vec::new(),
+        examples: Vec::new(),
+        read_only: false,
+        protected: false,
+    }
+}
+
+macro_rules! field {
+    ($field:ident, $key:literal) => {
+        field($key, stringify!($field), |group: &ServerGroup| {
+            &group.$field
+        })
+    };
+}
+
+pub fn get_group_schema(config: &Config) -> Vec<FieldSchema> {
+    //! Every field of a group hash in the order `ServerGroup` declares them, with the
+    //! config's protected fields marked.
+    let mut fields = vec![
+        field!(name, "name").read_only(),
+        field!(prefix, "prefix").read_only(),
+        field!(ram, "ram").minimum(1),
+        field!(cpu, "cpu").minimum(1),
+        field!(total_servers, "totalServers"),
+        field!(joinable_servers, "joinableServers"),
+        field!(port_section, "portSection"),
+        field!(uptimes, "uptimes"),
+        field!(arcade_group, "arcadeGroup"),
+        field!(world_zip, "worldZip"),
+        field!(plugin, "plugin"),
+        field!(config_path, "configPath"),
+        field!(host, "host"),
+        field!(min_players, "minPlayers"),
+        field!(max_players, "maxPlayers"),
+        field!(pvp, "pvp"),
+        field!(tournament, "tournament"),
+        field!(tournament_points, "tournamentPoints"),
+        field!(hard_max_player_cap, "hardMaxPlayerCap"),
+        field!(games, "games"),
+        field!(modes, "modes"),
+        field!(booster_group, "boosterGroup"),
+        field!(server_type, "serverType"),
+        field!(add_no_cheat, "addNoCheat"),
+        field!(add_world_edit, "addWorldEdit"),
+        field!(team_rejoin, "teamRejoin"),
+        field!(team_auto_join, "teamAutoJoin"),
+        field!(team_force_balance, "teamForceBalance"),
+        field!(game_auto_start, "gameAutoStart"),
+        field!(game_timeout, "gameTimeout"),
+        field!(game_voting, "gameVoting"),
+        field!(map_voting, "mapVoting"),
+        field!(reward_gems, "rewardGems"),
+        field!(reward_items, "rewardItems"),
+        field!(reward_stats, "rewardStats"),
+        field!(reward_achievements, "rewardAchievements"),
+        field!(hotbar_inventory, "hotbarInventory"),
+        field!(hotbar_hub_clock, "hotbarHubClock"),
+        field!(player_kick_idle, "playerKickIdle"),
+        field!(staff_only, "staffOnly"),
+        field!(whitelist, "whitelist"),
+        field!(resource_pack, "resourcePack"),
+        field!(region, "region")
+            .default_value(&Region::US.to_string())
+            .one_of(Region::iter().map(|region| region.to_string()).collect()),
+        field!(team_server_key, "teamServerKey"),
+        field!(portal_bottom_corner_location, "portalBottomCornerLocation"),
+        field!(portal_top_corner_location, "portalTopCornerLocation"),
+        field!(npc_name, "npcName"),
+        field!(minecraft_version, "minecraftVersion")
+            .default_value(DEFAULT_MINECRAFT_VERSION)
+            .examples(version::get_known_versions()),
+    ];
+    for field in fields.iter_mut() {
+        field.protected = config.protected_fields.iter().any(|key| key == field.key);
+    }
+    fields
+}
+
+pub fn check_value(schema: &[FieldSchema], key: &str, value: &str) -> Result<(), String> {
+    //! Whether `value` may be written to the group field `key`.
+    let field = schema
+        .iter()
+        .find(|field| field.key == key)
+        .ok_or(format!("Unknown group field: {:?}", key))?;
+    if field.read_only {
+        return Err(format!("{} can only be changed with `group rename`", key));
+    }
+    field.check(value)
+}
+
+pub fn to_json(schema: &[FieldSchema]) -> String {
+    //! The schema as served to UIs: `{"fields": [...]}`.
+    serde_json::json!({ "fields": schema }).to_string()
+}
@@ -1,6 +1,4 @@
-use rand::rngs::ThreadRng;
-use rand::Rng;
-use redis::RedisError;
+use redis::{ConnectionLike, RedisError};
 
 use crate::config::models::Config;
 use crate::context_manager::ContextManager;
@@ -8,11 +6,20 @@ use crate::error::parsing_error::ServerGroupParsingError;
 use crate::game::options::GameOptions;
 use crate::game::utils::GAME_TO_SERVER_PREFIX;
 use crate::game::Game;
+use crate::handshake;
+use crate::maps;
+use crate::queue;
 use crate::region::Region;
+use crate::safety::{Impact, SafetyError};
+use crate::server::counters;
+use crate::server::freeze;
+use crate::server::port::{allocate_port_section, PortReassignment, PortSection};
+use crate::server::version::{self, DEFAULT_MINECRAFT_VERSION};
+use crate::store::entity::RedisEntity;
+use crate::store::keys::KeyBuilder;
+use crate::store::metrics;
 use std::collections::HashMap;
 
-use super::minecraft::MinecraftServer;
-
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct ServerGroup {
     pub name: String,
@@ -62,12 +69,16 @@ pub struct ServerGroup {
     pub portal_bottom_corner_location: Option<String>,
     pub portal_top_corner_location: Option<String>,
     pub npc_name: Option<String>,
+    pub minecraft_version: String,
 }
 
-fn parse_value<'a>(
+/// Hash of renamed group -> new group name.
+pub(crate) const ALIASES_KEY: &str = "servergroupaliases";
+
+fn parse_value(
     prefix: &String,
     map: &HashMap<String, String>,
-    key: &'a str,
+    key: &str,
 ) -> Result<String, ServerGroupParsingError> {
     Ok(map
         .get(key)
@@ -78,10 +89,10 @@ fn parse_value<'a>(
         .to_string())
 }
 
-fn parse_bool_or_default<'a>(
+fn parse_bool_or_default(
     prefix: &String,
     map: &HashMap<String, String>,
-    key: &'a str,
+    key: &str,
 ) -> Result<bool, ServerGroupParsingError> {
     match map.get(key).unwrap_or(&String::new()).as_str() {
         "true" => Ok(true),
@@ -94,10 +105,10 @@ fn parse_bool_or_default<'a>(
     }
 }
 
-fn parse_u8<'a>(
+fn parse_u8(
     prefix: &String,
     map: &HashMap<String, String>,
-    key: &'a str,
+    key: &str,
 ) -> Result<u8, ServerGroupParsingError> {
     map.get(key)
         .ok_or(ServerGroupParsingError::new(format!(
@@ -113,10 +124,10 @@ fn parse_u8<'a>(
         })
 }
 
-fn parse_u16<'a>(
+fn parse_u16(
     prefix: &String,
     map: &HashMap<String, String>,
-    key: &'a str,
+    key: &str,
 ) -> Result<u16, ServerGroupParsingError> {
     map.get(key)
         .ok_or(ServerGroupParsingError::new(format!(
@@ -132,9 +143,9 @@ fn parse_u16<'a>(
         })
 }
 
-fn parse_optional_str<'a>(
+fn parse_optional_str(
     map: &HashMap<String, String>,
-    key: &'a str,
+    key: &str,
 ) -> Result<Option<String>, ServerGroupParsingError> {
     Ok(map
         .get(key)
@@ -153,13 +164,67 @@ impl From<ServerGroupParsingError> for RedisError {
     }
 }
 
+impl RedisEntity for ServerGroup {
+    type Error = ServerGroupParsingError;
+
+    fn get_key(id: &str, keys: &KeyBuilder) -> String {
+        keys.group_key(id)
+    }
+
+    fn get_pattern(keys: &KeyBuilder) -> String {
+        keys.group_pattern()
+    }
+
+    fn index_set(keys: &KeyBuilder) -> Option<String> {
+        Some(keys.group_index_key())
+    }
+
+    fn get_id(&self) -> String {
+        self.prefix.clone()
+    }
+
+    fn from_map(map: HashMap<String, String>) -> Result<Self, Self::Error> {
+        Self::from_hashmap(map)
+    }
+
+    fn to_map(&self) -> HashMap<String, String> {
+        self.to_hashmap()
+    }
+
+    fn read_map(
+        key: &str,
+        ctx: &mut ContextManager,
+    ) -> Result<HashMap<String, String>, RedisError> {
+        metrics::record_group_access(key, false);
+        redis::cmd("HGETALL").arg(key).query(ctx.get_connection())
+    }
+
+    fn write_map(
+        key: &str,
+        map: HashMap<String, String>,
+        ctx: &mut ContextManager,
+    ) -> Result<(), RedisError> {
+        freeze::check(key, ctx)?;
+        metrics::record_group_access(key, true);
+        redis::cmd("HSET")
+            .arg(key)
+            .arg(map)
+            .query::<()>(ctx.get_connection())?;
+        freeze::record(key, ctx)
+    }
+}
+
 impl ServerGroup {
+    pub fn get_port_section(&self) -> PortSection {
+        PortSection::new(self.port_section)
+    }
+
     pub fn from_game(game: Game) -> Self {
         Self {
             name: game.options.prefix.clone(),
             prefix: game.options.prefix,
-            ram: 512,
-            cpu: 1,
+            ram: game.options.ram,
+            cpu: game.options.cpu,
             total_servers: 0,
             joinable_servers: 0,
             port_section: game.options.port_section,
@@ -203,11 +268,12 @@ impl ServerGroup {
                 GAME_TO_SERVER_PREFIX
                     .get(&serv)
                     .cloned()
-                    .and_then(|g| Some(g.to_string()))
+                    .map(|g| g.to_string())
             }),
             portal_top_corner_location: game.options.portal_top_corner_location,
             portal_bottom_corner_location: game.options.portal_bottom_corner_location,
             npc_name: game.options.npc_name,
+            minecraft_version: game.options.minecraft_version,
         }
     }
 
@@ -279,16 +345,88 @@ impl ServerGroup {
             portal_bottom_corner_location: parse_optional_str(&map, "portalBottomCornerLocation")?,
             portal_top_corner_location: parse_optional_str(&map, "portalTopCornerLocation")?,
             npc_name: parse_optional_str(&map, "npcName")?,
+            minecraft_version: parse_optional_str(&map, "minecraftVersion")?
+                .unwrap_or(DEFAULT_MINECRAFT_VERSION.into()),
         };
         Ok(server_group)
     }
 
     /// Loads from cache or default
+    /// Follows the alias left behind by `rename` if `group` was renamed.
     pub fn from_str(
         group: &str,
         ctx: &mut ContextManager,
     ) -> Result<Self, ServerGroupParsingError> {
-        Self::get_server_group(&format!("servergroups.{}", group), ctx)
+        Self::get(group, ctx).or_else(|err| match Self::resolve_alias(group, ctx) {
+            Some(renamed) => Self::get(&renamed, ctx),
+            None => Err(err),
+        })
+    }
+
+    pub fn resolve_alias(group: &str, ctx: &mut ContextManager) -> Option<String> {
+        //! Returns the name a renamed group now goes by.
+        redis::cmd("HGET")
+            .arg(ALIASES_KEY)
+            .arg(group)
+            .query::<Option<String>>(ctx.get_connection())
+            .ok()
+            .flatten()
+    }
+
+    pub fn rename(
+        old: &str,
+        new: &str,
+        ctx: &mut ContextManager,
+    ) -> Result<ServerGroup, RedisError> {
+        //! Moves a group to a new name/prefix.
+        //! The hash is copied to the new key and the old one removed, leaving an alias behind
+        //! so readers still using the old name are redirected. All three go in one MULTI/EXEC;
+        //! clusters send them as a plain pipeline and snapshots one at a time.
+        //! Running instances keep the old prefix until relaunched, see `relaunch_group`.
+        let old_group = Self::get(old, ctx)?;
+        if Self::exists(new, ctx) {
+            return Err(ServerGroupParsingError::new(format!(
+                "servergroups.{} already exists",
+                new
+            ))
+            .into());
+        }
+        let renamed = ServerGroup {
+            name: new.to_string(),
+            prefix: new.to_string(),
+            ..old_group
+        };
+        let old_key = Self::get_key(old, ctx.get_keys());
+        let new_key = Self::get_key(&renamed.get_id(), ctx.get_keys());
+        let index = ctx.get_keys().group_index_key();
+        freeze::check(&new_key, ctx)?;
+
+        let mut commands = vec![redis::cmd("HSET"), redis::cmd("SADD")];
+        commands[0].arg(&new_key).arg(renamed.to_map());
+        commands[1].arg(&index).arg(renamed.get_id());
+        commands.extend([redis::cmd("DEL"), redis::cmd("SREM"), redis::cmd("HSET")]);
+        commands[2].arg(&old_key);
+        commands[3].arg(&index).arg(old);
+        commands[4].arg(ALIASES_KEY).arg(old).arg(new);
+        let conn = ctx.get_connection();
+        if conn.supports_pipelining() {
+            let mut pipe = redis::pipe();
+            if conn.supports_transactions() {
+                pipe.atomic();
+            }
+            for command in commands {
+                pipe.add_command(command).ignore();
+            }
+            pipe.query::<()>(conn)?;
+        } else {
+            for command in commands {
+                command.query::<()>(conn)?;
+            }
+        }
+        metrics::record_group_access(&new_key, true);
+        freeze::record(&new_key, ctx)?;
+        freeze::forget(&old_key, ctx)?;
+        Ok(renamed)
     }
 
     pub fn to_hashmap(&self) -> HashMap<String, String> {
@@ -300,15 +438,12 @@ impl ServerGroup {
             ("totalServers".into(), self.total_servers.to_string()),
             ("joinableServers".into(), self.joinable_servers.to_string()),
             ("portSection".into(), self.port_section.to_string()),
-            (
-                "uptimes".into(),
-                self.uptimes.clone().unwrap_or(String::new()),
-            ),
+            ("uptimes".into(), self.uptimes.clone().unwrap_or_default()),
             ("arcadeGroup".into(), self.arcade_group.to_string()),
             ("worldZip".into(), self.world_zip.clone()),
             ("plugin".into(), self.plugin.clone()),
             ("configPath".into(), self.config_path.clone()),
-            ("host".into(), self.host.clone().unwrap_or(String::new())),
+            ("host".into(), self.host.clone().unwrap_or_default()),
             ("minPlayers".into(), self.min_players.to_string()),
             ("maxPlayers".into(), self.max_players.to_string()),
             ("pvp".into(), self.pvp.to_string()),
@@ -321,11 +456,11 @@ impl ServerGroup {
                 "hardMaxPlayerCap".into(),
                 self.hard_max_player_cap.to_string(),
             ),
-            ("games".into(), self.games.clone().unwrap_or(String::new())),
-            ("modes".into(), self.modes.clone().unwrap_or(String::new())),
+            ("games".into(), self.games.clone().unwrap_or_default()),
+            ("modes".into(), self.modes.clone().unwrap_or_default()),
             (
                 "boosterGroup".into(),
-                self.booster_group.clone().unwrap_or(String::new()),
+                self.booster_group.clone().unwrap_or_default(),
             ),
             ("serverType".into(), self.server_type.clone()),
             ("addNoCheat".into(), self.add_no_cheat.to_string()),
@@ -354,64 +489,157 @@ impl ServerGroup {
             ("whitelist".into(), self.whitelist.to_string()),
             (
                 "resourcePack".into(),
-                self.resource_pack.clone().unwrap_or(String::new()),
+                self.resource_pack.clone().unwrap_or_default(),
             ),
             ("region".into(), self.region.clone().to_string()),
             (
                 "teamServerKey".into(),
-                self.team_server_key.clone().unwrap_or(String::new()),
+                self.team_server_key.clone().unwrap_or_default(),
             ),
             (
                 "portalBottomCornerLocation".into(),
                 self.portal_bottom_corner_location
                     .clone()
-                    .unwrap_or(String::new()),
+                    .unwrap_or_default(),
             ),
             (
                 "portalTopCornerLocation".into(),
-                self.portal_top_corner_location
-                    .clone()
-                    .unwrap_or(String::new()),
-            ),
-            (
-                "npcName".into(),
-                self.npc_name.clone().unwrap_or(String::new()),
+                self.portal_top_corner_location.clone().unwrap_or_default(),
             ),
+            ("npcName".into(), self.npc_name.clone().unwrap_or_default()),
+            ("minecraftVersion".into(), self.minecraft_version.clone()),
         ])
     }
 
-    pub fn load_existing_cache(&mut self, ctx: &mut ContextManager) -> () {
+    pub fn load_existing_cache(&mut self, ctx: &mut ContextManager) {
         //! ServerGroup returns to cached redis state if exists.
-        let redis_key: String = format!("servergroups.{}", self.prefix);
-        let cached: Option<ServerGroup> = Self::get_server_group(&redis_key, ctx).ok();
-        if cached.is_none() {
-            ()
-        }
-        *self = cached.unwrap().clone();
-        ()
+        let Some(cached) = Self::get(&self.prefix, ctx).ok() else {
+            return;
+        };
+        *self = cached;
     }
 
     pub fn is_cached(&self, ctx: &mut ContextManager) -> bool {
         //! Returns if ServerGroup was cached in redis.
-        let redis_key: String = format!("servergroups.{}", self.prefix);
-        Self::get_server_group(&redis_key, ctx).is_ok()
+        Self::exists(&self.prefix, ctx)
     }
 
-    pub fn delete(&self, ctx: &mut ContextManager) -> Result<(), redis::RedisError> {
-        //! Deletes ServerGroup from cache.
-        let redis_key: String = format!("servergroups.{}", self.prefix);
-        if self.is_cached(ctx) {
-            let _: () = redis::cmd("DEL")
-                .arg(redis_key)
-                .query(ctx.get_connection())?;
+    pub fn safe_delete(&self, force: bool, ctx: &mut ContextManager) -> Result<(), SafetyError> {
+        //! Deletes ServerGroup from cache unless it still has live instances.
+        //! Pass `force` to delete it anyway.
+        handshake::ensure_compatible(&format!("Deleting servergroups.{}", self.prefix), ctx)?;
+        let impact = Impact::of_group(self, ctx)?;
+        if !force && impact.has_live_instances() {
+            return Err(SafetyError::RequiresForce(
+                format!("Deleting servergroups.{}", self.prefix),
+                impact,
+            ));
+        }
+        Self::delete(&self.prefix, ctx)
+            .map_err(|err| SafetyError::OperationFailed(format!("{:?}", err)))?;
+        Self::delete_leftovers(&self.prefix, ctx).map_err(SafetyError::OperationFailed)
+    }
+
+    pub fn delete_leftovers(prefix: &str, ctx: &mut ContextManager) -> Result<(), String> {
+        //! Drops what is kept per group besides its hash: live counts, freeze checksum,
+        //! queue and map pool. Call it once the group itself is deleted.
+        let key = ctx.get_keys().group_key(prefix);
+        counters::clear(prefix, ctx).map_err(|err| err.to_string())?;
+        freeze::forget(&key, ctx).map_err(|err| err.to_string())?;
+        queue::clear(prefix, ctx).map_err(|err| err.to_string())?;
+        maps::clear(prefix, ctx).map_err(|err| err.to_string())
+    }
+
+    pub fn validate(&self, config: &Config) -> Result<(), ServerGroupParsingError> {
+        //! Every check against the configured nodes and proxy a new or changed group must pass.
+        self.validate_resources(config)?;
+        self.validate_host(config)?;
+        self.validate_version(config)
+    }
+
+    pub fn validate_resources(&self, config: &Config) -> Result<(), ServerGroupParsingError> {
+        //! Checks that the group asks for some ram and cpu, and that a configured node
+        //! in its region could ever host one of its instances.
+        if self.ram == 0 || self.cpu == 0 {
+            return Err(ServerGroupParsingError::new(format!(
+                "servergroups.{} needs non-zero ram and cpu (ram: {}, cpu: {})",
+                self.prefix, self.ram, self.cpu
+            )));
+        }
+        let nodes: Vec<(i16, i16)> = config
+            .dedicated_servers
+            .servers
+            .iter()
+            .filter(|ds| ds.region == self.region)
+            .map(|ds| (ds.max_ram, ds.max_cpu))
+            .collect();
+        if !nodes.is_empty()
+            && !nodes
+                .iter()
+                .any(|&(ram, cpu)| ram >= self.ram as i16 && cpu >= self.cpu as i16)
+        {
+            return Err(ServerGroupParsingError::new(format!(
+                "servergroups.{} (ram: {}, cpu: {}) does not fit on any {} dedicated server",
+                self.prefix, self.ram, self.cpu, self.region
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn validate_host(&self, config: &Config) -> Result<(), ServerGroupParsingError> {
+        //! Checks that a pinned group's host is a configured node in its region that could
+        //! ever host one of its instances.
+        let Some(host) = self.host.as_ref() else {
+            return Ok(());
+        };
+        let Some(node) = config.dedicated_servers.get_server(host) else {
+            return Err(ServerGroupParsingError::new(format!(
+                "servergroups.{} is pinned to {:?}, which is not a configured dedicated server",
+                self.prefix, host
+            )));
+        };
+        if node.region != self.region {
+            return Err(ServerGroupParsingError::new(format!(
+                "servergroups.{} ({}) is pinned to {:?}, which is in {}",
+                self.prefix, self.region, host, node.region
+            )));
+        }
+        if node.max_ram < self.ram as i16 || node.max_cpu < self.cpu as i16 {
+            return Err(ServerGroupParsingError::new(format!(
+                "servergroups.{} (ram: {}, cpu: {}) does not fit on its host {:?}",
+                self.prefix, self.ram, self.cpu, host
+            )));
         }
-        let _: () = redis::cmd("SREM")
-            .arg("servergroups")
-            .arg(&self.prefix)
-            .query(ctx.get_connection())?;
         Ok(())
     }
 
+    pub fn allows_node(&self, node: &str) -> bool {
+        //! Groups with a `host` only run on that node.
+        self.host.as_ref().is_none_or(|host| host == node)
+    }
+
+    pub fn validate_version(&self, config: &Config) -> Result<(), ServerGroupParsingError> {
+        //! Checks that the group's minecraft version is known and that the proxy accepts its protocol.
+        let protocol = version::get_protocol(&self.minecraft_version).ok_or_else(|| {
+            ServerGroupParsingError::new(format!(
+                "servergroups.{} has unknown minecraftVersion {:?}",
+                self.prefix, self.minecraft_version
+            ))
+        })?;
+        let proxy = &config.proxy;
+        if !proxy.accepts(protocol) {
+            return Err(ServerGroupParsingError::new(format!(
+                "servergroups.{} runs {} (protocol {}), the proxy only accepts protocols {}-{}",
+                self.prefix, self.minecraft_version, protocol, proxy.protocols.0, proxy.protocols.1
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn get_server_jar(&self) -> String {
+        version::get_server_jar(&self.minecraft_version)
+    }
+
     pub fn eliminate_port_collisions(
         &mut self,
         ctx: &mut ContextManager,
@@ -419,12 +647,29 @@ impl ServerGroup {
         //! Eliminates port collisions between `self` and cached `ServerGroup`s by generating a new
         //! port section.
         //! (Call this function before caching)
+        //! A reassignment is recorded in the group's port history.
+        let old_port_section = self.port_section;
+        let conflicts = self.find_port_conflicts(ctx)?;
         self.reset_port_section_if_invalid(ctx).map_err(|err| {
             ServerGroupParsingError::new(format!(
                 "Error while executing `eliminate_port_collisions` in ServerGroup (could not reset port): {:?}",
                 err
             ))
         })?;
+        if self.port_section != old_port_section {
+            let reassignment = PortReassignment::new(
+                old_port_section,
+                self.port_section,
+                format!("conflicted with {:?}", conflicts),
+            );
+            if let Err(err) = reassignment.record(&self.prefix, ctx) {
+                log::warn!(
+                    "Could not record port reassignment of servergroups.{}: {:?}",
+                    self.prefix,
+                    err
+                );
+            }
+        }
         Ok(())
     }
 
@@ -440,30 +685,29 @@ impl ServerGroup {
         ))
     }
 
-    fn get_random_port_section(&mut self, rng: &mut ThreadRng) -> () {
-        //! Generates any random port from 25566 to 25600.
-        self.port_section = rng.gen_range(25566..26001);
-    }
-
     fn reset_port_section_if_invalid(
         &mut self,
         ctx: &mut ContextManager,
     ) -> Result<(), ServerGroupParsingError> {
         //! Resets port section if it conflicts with another group's cached port section.
-        let mut rng = rand::thread_rng();
-        while self.get_port_section_is_invalid(ctx)? {
-            self.get_random_port_section(&mut rng);
+        //! The new section is picked with the configured allocation strategy.
+        if self.get_port_section_is_invalid(ctx)? {
+            let port_sections = self.get_all_other_port_sections(ctx)?;
+            let info = ctx.get_config().ports.clone();
+            self.port_section = allocate_port_section(&info, &port_sections, ctx.get_random())
+                .map_err(|err| ServerGroupParsingError::new(err.to_string()))?;
         }
         Ok(())
     }
 
-    fn find_port_conflicts(
+    pub fn find_port_conflicts(
         &mut self,
         ctx: &mut ContextManager,
     ) -> Result<Vec<String>, ServerGroupParsingError> {
         //! Filters for servergroups with conflicting ports to self.
         //! Returns a vec of their names.
-        let server_groups: Vec<ServerGroup> = Self::get_server_groups(ctx)?
+        let server_groups: Vec<ServerGroup> = Self::get_all(ctx)
+            .into_result()?
             .into_iter()
             .filter(|sg| sg.name != self.name)
             .collect();
@@ -485,7 +729,7 @@ impl ServerGroup {
         ctx: &mut ContextManager,
     ) -> Result<Vec<u16>, ServerGroupParsingError> {
         //! Returns a vec of cached port sections that don't include self (even if it is cached).
-        let server_groups: Vec<ServerGroup> = Self::get_server_groups(ctx)?;
+        let server_groups: Vec<ServerGroup> = Self::get_all(ctx).into_result()?;
         Ok(server_groups
             .into_iter()
             .filter_map(|sg| Some(sg.name != self.name).map(|_| sg.port_section))
@@ -493,69 +737,25 @@ impl ServerGroup {
     }
 
     pub fn create(&mut self, ctx: &mut ContextManager) -> Result<(), redis::RedisError> {
-        let redis_key: String = format!("servergroups.{}", self.prefix);
-        let sg = Self::get_server_group(&redis_key, ctx).ok();
-        if sg.is_some() {
+        if self.is_cached(ctx) {
             // if exists in redis already
+            let index = ctx.get_keys().group_index_key();
             let _: () = redis::cmd("SADD") // even if it exists in set
-                .arg("servergroups")
+                .arg(index)
                 .arg(&self.prefix)
                 .query(ctx.get_connection())?;
             return Ok(());
         }
+        self.validate(ctx.get_config())?;
         self.eliminate_port_collisions(ctx)?; // no more conflicting ports
-        let params: HashMap<String, String> = self.to_hashmap();
-        let _: () = redis::cmd("HSET")
-            .arg(redis_key)
-            .arg(params)
-            .query(ctx.get_connection())?;
-        let _: () = redis::cmd("SADD")
-            .arg("servergroups")
-            .arg(&self.prefix)
-            .query(ctx.get_connection())?;
+        self.save(ctx)?;
         Ok(())
     }
 
-    pub fn get_server_group(
-        redis_key: &String,
-        ctx: &mut ContextManager,
-    ) -> Result<ServerGroup, ServerGroupParsingError> {
-        let config: Config = Config::get_config();
-        let mut conn = ctx.get_connection();
-        let redis_data: HashMap<String, String> = redis::cmd("HGETALL")
-            .arg(redis_key)
-            .query(&mut conn)
-            .map_err(|_| {
-                ServerGroupParsingError::new(
-                    "Redis data for ServerGroup could not be retrieved".into(),
-                )
-            })?;
-        Ok(Self::from_hashmap(redis_data)?)
-    }
-
-    pub fn get_server_groups(
-        ctx: &mut ContextManager,
-    ) -> Result<Vec<ServerGroup>, ServerGroupParsingError> {
-        let mut conn = ctx.get_connection();
-        let server_groups: Vec<String> = redis::cmd("KEYS")
-            .arg("servergroups.*")
-            .query(&mut conn)
-            .map_err(|_| {
-                ServerGroupParsingError::new(
-                    "Redis data for ServerGroup could not be retrieved. ServerGroup iteration failed."
-                        .into(),
-                )
-            })?;
-        server_groups
-            .iter()
-            .map(|sg| Self::get_server_group(sg, ctx))
-            .collect()
-    }
-
     pub fn get_all_port_sections(
         ctx: &mut ContextManager,
     ) -> Result<Vec<u16>, ServerGroupParsingError> {
-        let server_groups: Vec<ServerGroup> = Self::get_server_groups(ctx)?;
+        let server_groups: Vec<ServerGroup> = Self::get_all(ctx).into_result()?;
         let ports: Vec<u16> = server_groups
             .iter()
             .map(|group| group.port_section)
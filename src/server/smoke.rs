@@ -0,0 +1,270 @@
+use std::{
+    fmt::Display,
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::context_manager::ContextManager;
+
+use super::{minecraft::MinecraftServer, rcon::RconClient};
+
+const PING_PROTOCOL: i32 = 47; // any version answers a status request
+const MAX_STATUS_LENGTH: usize = 1 << 16;
+
+#[derive(Error, Debug)]
+pub enum SmokeTestError {
+    #[error("Smoke Test Error: Server list ping failed: `{0}`")]
+    PingFailed(String),
+    #[error("Smoke Test Error: MOTD does not match: `{0}`")]
+    MotdMismatch(String),
+    #[error("Smoke Test Error: No player slots: `{0}`")]
+    NoSlots(String),
+    #[error("Smoke Test Error: Rcon command failed: `{0}`")]
+    RconFailed(String),
+}
+
+/// Checks a freshly launched instance must pass before it is opened to players.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct SmokeTestInfo {
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64, // per connection attempt, read and write
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motd: Option<String>, // pattern the MOTD must match, `*` matches any text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rcon: Option<SmokeRcon>,
+}
+
+/// Console command run over rcon (with the group's `[rcon]` settings); the test fails
+/// if it can't be run or its output doesn't match `expect`.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct SmokeRcon {
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expect: Option<String>, // pattern like `motd`
+}
+
+/// What a server answered to a server list ping.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PingResponse {
+    pub motd: String, // without formatting codes
+    pub online: i64,
+    pub max: i64,
+}
+
+fn default_timeout_ms() -> u64 {
+    3000
+}
+
+impl Default for SmokeTestInfo {
+    fn default() -> Self {
+        Self {
+            timeout_ms: default_timeout_ms(),
+            motd: None,
+            rcon: None,
+        }
+    }
+}
+
+impl Display for PingResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}, {}/{} players", self.motd, self.online, self.max)
+    }
+}
+
+pub fn matches_pattern(pattern: &str, text: &str) -> bool {
+    //! Whole-text match where `*` stands for any run of characters.
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if parts.len() == 1 {
+        return text == pattern;
+    }
+    if !text.starts_with(first) || !text[first.len()..].ends_with(last) {
+        return false;
+    }
+    let mut rest = &text[first.len()..text.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+fn strip_formatting(text: &str) -> String {
+    //! Drops `§x` color and style codes.
+    let mut plain = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '§' => {
+                chars.next();
+            }
+            c => plain.push(c),
+        }
+    }
+    plain
+}
+
+fn flatten_chat(component: &Value) -> String {
+    //! Plain text of a chat component: a string, or `text` followed by its `extra` parts.
+    match component {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts.iter().map(flatten_chat).collect(),
+        Value::Object(map) => {
+            let text = map.get("text").map(flatten_chat).unwrap_or_default();
+            let extra = map.get("extra").map(flatten_chat).unwrap_or_default();
+            text + &extra
+        }
+        _ => String::new(),
+    }
+}
+
+fn write_varint(buf: &mut Vec<u8>, value: i32) {
+    let mut value = value as u32;
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+fn read_varint(stream: &mut impl Read) -> std::io::Result<i32> {
+    let mut value: u32 = 0;
+    for i in 0..5 {
+        let mut byte = [0];
+        stream.read_exact(&mut byte)?;
+        value |= ((byte[0] & 0x7f) as u32) << (7 * i);
+        if byte[0] & 0x80 == 0 {
+            return Ok(value as i32);
+        }
+    }
+    Err(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        "varint too long",
+    ))
+}
+
+fn write_packet(stream: &mut impl Write, id: i32, data: &[u8]) -> std::io::Result<()> {
+    let mut body = Vec::new();
+    write_varint(&mut body, id);
+    body.extend(data);
+    let mut packet = Vec::new();
+    write_varint(&mut packet, body.len() as i32);
+    packet.extend(body);
+    stream.write_all(&packet)
+}
+
+pub fn ping(address: &str, port: u16, timeout: Duration) -> Result<PingResponse, SmokeTestError> {
+    //! Asks a server for its status the way the client's server list does.
+    let fail =
+        |err: &dyn Display| SmokeTestError::PingFailed(format!("{}:{}: {}", address, port, err));
+    let socket = (address, port)
+        .to_socket_addrs()
+        .map_err(|err| fail(&err))?
+        .next()
+        .ok_or(fail(&"address did not resolve"))?;
+    let mut stream = TcpStream::connect_timeout(&socket, timeout).map_err(|err| fail(&err))?;
+    stream
+        .set_read_timeout(Some(timeout))
+        .and_then(|_| stream.set_write_timeout(Some(timeout)))
+        .map_err(|err| fail(&err))?;
+    let mut handshake = Vec::new();
+    write_varint(&mut handshake, PING_PROTOCOL);
+    write_varint(&mut handshake, address.len() as i32);
+    handshake.extend(address.as_bytes());
+    handshake.extend(port.to_be_bytes());
+    write_varint(&mut handshake, 1); // next state: status
+    write_packet(&mut stream, 0, &handshake)
+        .and_then(|_| write_packet(&mut stream, 0, &[]))
+        .map_err(|err| fail(&err))?;
+    let json = (|| {
+        let _length = read_varint(&mut stream)?;
+        let _id = read_varint(&mut stream)?;
+        let length = read_varint(&mut stream)? as usize;
+        if length > MAX_STATUS_LENGTH {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("status of {} bytes", length),
+            ));
+        }
+        let mut json = vec![0; length];
+        stream.read_exact(&mut json)?;
+        Ok(json)
+    })()
+    .map_err(|err| fail(&err))?;
+    let status: Value = serde_json::from_slice(&json).map_err(|err| fail(&err))?;
+    Ok(PingResponse {
+        motd: strip_formatting(
+            &status
+                .get("description")
+                .map(flatten_chat)
+                .unwrap_or_default(),
+        ),
+        online: status["players"]["online"].as_i64().unwrap_or(0),
+        max: status["players"]["max"].as_i64().unwrap_or(0),
+    })
+}
+
+impl SmokeTestInfo {
+    pub fn get_timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    pub fn run(
+        &self,
+        server: &MinecraftServer,
+        ctx: &mut ContextManager,
+    ) -> Result<PingResponse, SmokeTestError> {
+        //! Pings the server, then checks its MOTD and player slots and runs the rcon
+        //! command, stopping at the first failure.
+        let (address, port) = (server.get_public_address(), server.get_port());
+        let response = ping(address, port, self.get_timeout())?;
+        if let Some(pattern) = &self.motd {
+            if !matches_pattern(pattern, &response.motd) {
+                return Err(SmokeTestError::MotdMismatch(format!(
+                    "{:?} (expected {:?})",
+                    response.motd, pattern
+                )));
+            }
+        }
+        if response.max <= 0 {
+            return Err(SmokeTestError::NoSlots(format!("{}:{}", address, port)));
+        }
+        if let Some(rcon) = &self.rcon {
+            rcon.run(server, ctx)?;
+        }
+        Ok(response)
+    }
+}
+
+impl SmokeRcon {
+    fn run(
+        &self,
+        server: &MinecraftServer,
+        ctx: &mut ContextManager,
+    ) -> Result<String, SmokeTestError> {
+        let fail =
+            |err: &dyn Display| SmokeTestError::RconFailed(format!("{}: {}", self.command, err));
+        let output = RconClient::for_server(server, ctx)
+            .and_then(|mut client| client.run(&self.command))
+            .map_err(|err| fail(&err))?;
+        let output = strip_formatting(output.trim());
+        match &self.expect {
+            Some(pattern) if !matches_pattern(pattern, &output) => Err(fail(&format!(
+                "output {:?} (expected {:?})",
+                output, pattern
+            ))),
+            _ => Ok(output),
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+
+/// Version servergroups without a `minecraftVersion` run on.
+pub const DEFAULT_MINECRAFT_VERSION: &str = "1.8.8";
+
+/// Protocol number of each supported release (a minor version's first entry covers its patches).
+const PROTOCOLS: [(&str, u32); 19] = [
+    ("1.8", 47),
+    ("1.9", 107),
+    ("1.9.4", 110),
+    ("1.10", 210),
+    ("1.11", 315),
+    ("1.12", 335),
+    ("1.12.2", 340),
+    ("1.13.2", 404),
+    ("1.14.4", 498),
+    ("1.15.2", 578),
+    ("1.16.5", 754),
+    ("1.17.1", 756),
+    ("1.18.2", 758),
+    ("1.19.4", 762),
+    ("1.20", 763),
+    ("1.20.1", 763),
+    ("1.20.4", 765),
+    ("1.20.6", 766),
+    ("1.21", 767),
+];
+
+/// Protocol range the proxy in front of the network accepts.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ProxyInfo {
+    pub protocols: (u32, u32), // inclusive
+}
+
+impl Default for ProxyInfo {
+    fn default() -> Self {
+        Self {
+            protocols: (47, 767),
+        }
+    }
+}
+
+impl ProxyInfo {
+    pub fn accepts(&self, protocol: u32) -> bool {
+        self.protocols.0 <= protocol && protocol <= self.protocols.1
+    }
+}
+
+pub fn get_protocol(version: &str) -> Option<u32> {
+    //! Looks up the protocol of a release, falling back to its minor version (1.8.8 -> 1.8).
+    let lookup = |version: &str| {
+        PROTOCOLS
+            .iter()
+            .find(|(name, _)| *name == version)
+            .map(|(_, protocol)| *protocol)
+    };
+    lookup(version).or_else(|| {
+        let minor: Vec<&str> = version.split('.').take(2).collect();
+        lookup(&minor.join("."))
+    })
+}
+
+pub fn get_known_versions() -> Vec<String> {
+    //! Releases with a known protocol, oldest first.
+    PROTOCOLS.iter().map(|(name, _)| name.to_string()).collect()
+}
+
+pub fn get_server_jar(version: &str) -> String {
+    //! Name of the server jar the start script runs for `version`.
+    format!("spigot-{}.jar", version)
+}
@@ -0,0 +1,317 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+};
+
+use chrono::Local;
+use redis::RedisError;
+
+use crate::{
+    context_manager::ContextManager,
+    error::parsing_error::ServerGroupParsingError,
+    events::Event,
+    store::{consistent::read_consistent, entity::RedisEntity, metrics, scan::scan_all_keys},
+    strategy::{self, ScalingDecision},
+};
+
+use super::{
+    crash_loop::CrashLoop,
+    dedicated::{
+        instance::MCSInstance,
+        labels::{self, LabelTarget},
+        launcher::{self, LaunchPlan},
+    },
+    minecraft::MinecraftServer,
+    server_group::ServerGroup,
+};
+
+/// How many of the latest events are searched for a group's recent events.
+const EVENT_SEARCH_DEPTH: usize = 200;
+const RECENT_EVENTS: usize = 10;
+
+/// Everything known about one group: its hash, where its instances are placed, what its
+/// servers report, and what happened to it lately. The redis data is read in one
+/// transaction, so the group, statuses, labels and events all show the same moment.
+#[derive(Clone, Debug)]
+pub struct GroupStatusView {
+    pub group: ServerGroup,
+    pub instances: BTreeMap<String, Vec<MCSInstance>>, // node -> instances assigned to it
+    pub statuses: Vec<MinecraftServer>,
+    pub unreadable: Vec<String>, // status keys that couldn't be parsed
+    pub events: Vec<Event>,      // newest first
+    pub read_at: i64,            // milliseconds since epoch
+    pub consistent: bool,        // false if the reads couldn't run as one transaction
+}
+
+impl GroupStatusView {
+    pub fn load(name: &str, ctx: &mut ContextManager) -> Result<Self, RedisError> {
+        //! Finds the group and its status keys first (SCAN can't run in a transaction),
+        //! then reads all of it in one round trip. A status that disappears in between
+        //! is left out, as if it had stopped reporting.
+        let found = ServerGroup::from_str(name, ctx)?;
+        let group_key = ServerGroup::get_key(&found.prefix, ctx.get_keys());
+        let pattern = ctx
+            .get_keys()
+            .status_pattern(Some(&found.region.to_string()), Some(&found.prefix));
+        let status_keys = scan_all_keys(&pattern, ctx)?;
+        let mut cmds = vec![
+            redis::cmd("HGETALL").arg(&group_key).clone(),
+            redis::cmd("HGETALL")
+                .arg(LabelTarget::Instance.get_key())
+                .clone(),
+            Event::get_recent_cmd(EVENT_SEARCH_DEPTH),
+        ];
+        cmds.extend(
+            status_keys
+                .iter()
+                .map(|key| redis::cmd("GET").arg(key).clone()),
+        );
+        metrics::record_group_access(&group_key, false);
+        let mut read = read_consistent(&cmds, ctx)?;
+        let group_map: HashMap<String, String> = read.take()?;
+        if group_map.is_empty() {
+            let err = ServerGroupParsingError::from(format!("{:?} does not exist", group_key));
+            return Err(err.into());
+        }
+        let group = ServerGroup::from_map(group_map)?;
+        let labels = labels::parse_all_labels(read.take()?);
+        let events: Vec<String> = read.take()?;
+        let mut statuses = Vec::new();
+        let mut unreadable = Vec::new();
+        for key in status_keys {
+            if let Some(raw) = read.take::<Option<String>>()? {
+                match MinecraftServer::from_raw(&raw, &key, ctx.get_keys()) {
+                    Ok(server) => statuses.push(server),
+                    Err(_) => unreadable.push(key),
+                }
+            }
+        }
+        let instances = ctx
+            .get_dedicated_servers()
+            .servers
+            .iter()
+            .filter_map(|ds| Some((ds.name.clone(), ds.get_instances(&group)?.clone())))
+            .filter(|(_, instances)| !instances.is_empty())
+            .map(|(node, mut instances)| {
+                for instance in instances.iter_mut() {
+                    if let Some(labels) = labels.get(instance.get_name()) {
+                        instance.set_labels(labels.clone());
+                    }
+                }
+                (node, instances)
+            })
+            .collect();
+        let instance_prefix = format!("{}-", group.prefix);
+        let events = Event::from_entries(&events)
+            .into_iter()
+            .filter(|event| {
+                event.subject == group.prefix || event.subject.starts_with(&instance_prefix)
+            })
+            .take(RECENT_EVENTS)
+            .collect();
+        Ok(Self {
+            group,
+            instances,
+            statuses,
+            unreadable,
+            events,
+            read_at: read.read_at,
+            consistent: read.atomic,
+        })
+    }
+
+    pub fn get_instance_count(&self) -> usize {
+        self.instances
+            .values()
+            .map(|instances| instances.len())
+            .sum()
+    }
+
+    pub fn get_player_count(&self) -> usize {
+        self.statuses
+            .iter()
+            .map(|server| server.get_player_count() as usize)
+            .sum()
+    }
+
+    pub fn get_missing_statuses(&self) -> Vec<&String> {
+        //! Instances assigned to a node that aren't reporting a status.
+        self.instances
+            .values()
+            .flatten()
+            .map(|instance| instance.get_name())
+            .filter(|name| {
+                !self
+                    .statuses
+                    .iter()
+                    .any(|server| server.get_name() == *name)
+            })
+            .collect()
+    }
+}
+
+impl Display for GroupStatusView {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} ({}, port section {}, {}MB, {} cpu)",
+            self.group.prefix,
+            self.group.region,
+            self.group.port_section,
+            self.group.ram,
+            self.group.cpu
+        )?;
+        writeln!(
+            f,
+            "Desired: {} total, {} joinable | Assigned: {} | Reporting: {} | Players: {}",
+            self.group.total_servers,
+            self.group.joinable_servers,
+            self.get_instance_count(),
+            self.statuses.len(),
+            self.get_player_count()
+        )?;
+        for (node, instances) in self.instances.iter() {
+            let names: Vec<String> = instances
+                .iter()
+                .map(|x| match x.get_labels().is_empty() {
+                    true => x.get_name().clone(),
+                    false => format!(
+                        "{} [{}]",
+                        x.get_name(),
+                        labels::format_labels(x.get_labels())
+                    ),
+                })
+                .collect();
+            writeln!(f, "  {}: {}", node, names.join(", "))?;
+        }
+        for server in self.statuses.iter() {
+            writeln!(
+                f,
+                "  {} {}:{} {}/{} players",
+                server.get_name(),
+                server.get_public_address(),
+                server.get_port(),
+                server.get_player_count(),
+                server.get_max_player_count()
+            )?;
+        }
+        for name in self.get_missing_statuses() {
+            writeln!(f, "  {} is assigned but not reporting", name)?;
+        }
+        for key in self.unreadable.iter() {
+            writeln!(f, "  {} could not be read", key)?;
+        }
+        let read_at = chrono::DateTime::from_timestamp_millis(self.read_at)
+            .map(|time| time.with_timezone(&Local).to_rfc3339())
+            .unwrap_or(self.read_at.to_string());
+        match self.consistent {
+            true => writeln!(f, "Read at {}", read_at)?,
+            false => writeln!(f, "Read at {} (not as one transaction)", read_at)?,
+        }
+        if !self.events.is_empty() {
+            writeln!(f, "Recent events:")?;
+            for event in self.events.iter() {
+                writeln!(f, "  {}", event)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What the scaler would do for one group right now, with the inputs it decides on.
+/// Nothing is changed: launches are only placed on a copy of the nodes.
+#[derive(Debug)]
+pub struct GroupPlanView {
+    pub group: ServerGroup,
+    pub strategy: String,      // name of the registered scaling strategy
+    pub placement: String,     // name of the registered placement strategy
+    pub joinable: usize,       // live instances open to players
+    pub policy_desired: usize, // the scaling strategy's answer, before peak hours and queues
+    pub decision: ScalingDecision,
+    pub crash_loop: Option<CrashLoop>,
+    pub launches: LaunchPlan, // where the missing instances would go
+}
+
+impl GroupPlanView {
+    pub fn load(name: &str, ctx: &mut ContextManager) -> Result<Self, RedisError> {
+        let group = ServerGroup::from_str(name, ctx)?;
+        let statuses = MinecraftServer::from_server_group(&group, ctx).ok;
+        let policy_desired = ctx
+            .get_scaling_strategy()
+            .get_desired_count(&group, &statuses);
+        let decision = strategy::plan_scaling_where(|other| other.prefix == group.prefix, ctx)
+            .pop()
+            .ok_or(ServerGroupParsingError::from(format!(
+                "{:?} could not be planned",
+                group.prefix
+            )))?;
+        let missing = decision.desired.saturating_sub(decision.running);
+        Ok(Self {
+            strategy: ctx.get_scaling_strategy().get_name().into(),
+            placement: ctx.get_placement_strategy().get_name().into(),
+            joinable: statuses
+                .iter()
+                .filter(|server| server.is_joinable())
+                .count(),
+            policy_desired,
+            crash_loop: CrashLoop::get(&group.prefix, ctx)?,
+            launches: launcher::plan(&[(group.clone(), missing)], ctx),
+            decision,
+            group,
+        })
+    }
+}
+
+impl Display for GroupPlanView {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let decision = &self.decision;
+        writeln!(
+            f,
+            "{} ({}, {}MB, {} cpu)",
+            self.group.prefix, self.group.region, self.group.ram, self.group.cpu
+        )?;
+        writeln!(
+            f,
+            "Now: {} running, {} joinable, {} queued",
+            decision.running, self.joinable, decision.queued
+        )?;
+        writeln!(
+            f,
+            "Policy ({}): totalServers {}, joinableServers {} -> {}",
+            self.strategy,
+            self.group.total_servers,
+            self.group.joinable_servers,
+            self.policy_desired
+        )?;
+        if decision.multiplier != 1.0 {
+            writeln!(f, "Peak hours: x{}", decision.multiplier)?;
+        }
+        let peak_desired = (self.policy_desired as f64 * decision.multiplier).ceil() as usize;
+        if decision.desired > peak_desired {
+            writeln!(
+                f,
+                "Queue: +{} for {} queued player(s), every instance is full",
+                decision.desired - peak_desired,
+                decision.queued
+            )?;
+        }
+        writeln!(f, "Target: {} instance(s)", decision.desired)?;
+        if decision.running > decision.desired {
+            writeln!(
+                f,
+                "{} more running than desired, the scaler doesn't stop instances",
+                decision.running - decision.desired
+            )?;
+        }
+        if let Some(crash_loop) = self.crash_loop.as_ref().filter(|loop_| loop_.is_paused()) {
+            writeln!(f, "Launches paused, crash looping: {}", crash_loop)?;
+        }
+        if decision.desired > decision.running {
+            writeln!(f, "Placement ({}):", self.placement)?;
+            for line in self.launches.to_string().lines() {
+                writeln!(f, "  {}", line)?;
+            }
+        }
+        Ok(())
+    }
+}
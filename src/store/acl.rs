@@ -0,0 +1,81 @@
+use redis::{ConnectionInfo, IntoConnectionInfo, RedisResult};
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+/// What a connection is opened for, deciding which ACL user it logs in as.
+#[derive(Display, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ConnectionPurpose {
+    #[default]
+    #[strum(serialize = "monitor")]
+    Mutating, // the monitor and every command that writes
+    #[strum(serialize = "read_only")]
+    ReadOnly, // listings, status views and metrics
+}
+
+/// A redis ACL user (redis >= 6) to log in as instead of `default`.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct AclUser {
+    pub username: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
+
+/// Per-purpose ACL users. A purpose without a user logs in as redis' `default` user,
+/// unless `least_privilege` is set, in which case its connection is refused.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct AclUsers {
+    #[serde(default)]
+    pub least_privilege: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<AclUser>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_only: Option<AclUser>,
+}
+
+impl AclUsers {
+    pub fn get_user(&self, purpose: ConnectionPurpose) -> RedisResult<Option<&AclUser>> {
+        //! The user `purpose` connects as, None for redis' `default` user.
+        let user = match purpose {
+            ConnectionPurpose::Mutating => self.monitor.as_ref(),
+            ConnectionPurpose::ReadOnly => self.read_only.as_ref(),
+        };
+        if user.is_none() && self.least_privilege {
+            return Err(redis::RedisError::from((
+                redis::ErrorKind::InvalidClientConfig,
+                "redis_conn.users.least_privilege is set, but no user is configured for",
+                purpose.to_string(),
+            )));
+        }
+        Ok(user)
+    }
+
+    pub fn get_rules(&self) -> Vec<String> {
+        //! `ACL SETUSER` commands granting each configured user what its purpose needs:
+        //! reads (and client-side caching) for read-only, everything but admin for the monitor.
+        let mut rules = Vec::new();
+        if let Some(user) = self.monitor.as_ref() {
+            rules.push(format!(
+                "ACL SETUSER {} on ><password> ~* &* +@all -@admin +info",
+                user.username
+            ));
+        }
+        if let Some(user) = self.read_only.as_ref() {
+            rules.push(format!(
+                "ACL SETUSER {} on ><password> ~* resetchannels -@all +@read +@connection +client|tracking +info",
+                user.username
+            ));
+        }
+        rules
+    }
+}
+
+pub fn get_connection_info(address: &str, user: Option<&AclUser>) -> RedisResult<ConnectionInfo> {
+    //! Connection info for `host:port`, logging in as `user` when given. Credentials are
+    //! set on the parsed info rather than the url, so passwords need no escaping.
+    let mut info = format!("redis://{}", address).into_connection_info()?;
+    if let Some(user) = user {
+        info.redis.username = Some(user.username.clone());
+        info.redis.password = user.password.clone();
+    }
+    Ok(info)
+}
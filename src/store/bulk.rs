@@ -0,0 +1,167 @@
+use std::{collections::HashMap, fmt::Display, thread, time::Duration};
+
+use redis::{ConnectionLike, Pipeline, RedisError};
+
+use crate::context_manager::ContextManager;
+
+/// One key to write. Every variant but `AddToSet` replaces whatever the key held.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BulkWrite {
+    Hash(String, HashMap<String, String>),
+    String(String, String),
+    Set(String, Vec<String>),
+    List(String, Vec<String>),
+    AddToSet(String, Vec<String>),
+}
+
+/// Where a bulk write is at, passed to the progress callback after every chunk.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BulkProgress {
+    pub written: usize,
+    pub failed: usize,
+    pub total: usize,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct BulkReport {
+    pub written: usize,
+    pub retries: usize,
+    pub failed: Vec<(String, String)>, // (key, why)
+}
+
+/// Writes many keys in pipelined chunks, retrying a failed chunk before giving up on it.
+/// Connections that can't pipeline (snapshots) get the same commands one at a time.
+#[derive(Clone, Copy, Debug)]
+pub struct BulkWriter {
+    pub chunk_size: usize,
+    pub retries: usize,
+    pub retry_delay: Duration,
+}
+
+impl Default for BulkWriter {
+    fn default() -> Self {
+        Self {
+            chunk_size: 100,
+            retries: 3,
+            retry_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+impl Display for BulkProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{} keys written", self.written, self.total)?;
+        if self.failed > 0 {
+            write!(f, ", {} failed", self.failed)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for BulkReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Wrote {} key(s), {} failed, {} chunk retries",
+            self.written,
+            self.failed.len(),
+            self.retries
+        )
+    }
+}
+
+impl BulkWrite {
+    pub fn get_key(&self) -> &String {
+        match self {
+            BulkWrite::Hash(key, _)
+            | BulkWrite::String(key, _)
+            | BulkWrite::Set(key, _)
+            | BulkWrite::List(key, _)
+            | BulkWrite::AddToSet(key, _) => key,
+        }
+    }
+
+    fn add_to(&self, pipe: &mut Pipeline) {
+        //! Appends this write's commands to `pipe`. Empty collections only clear the key.
+        if !matches!(self, BulkWrite::AddToSet(..)) {
+            pipe.cmd("DEL").arg(self.get_key()).ignore();
+        }
+        match self {
+            BulkWrite::Hash(key, map) if !map.is_empty() => {
+                pipe.cmd("HSET").arg(key).arg(map).ignore();
+            }
+            BulkWrite::String(key, value) => {
+                pipe.cmd("SET").arg(key).arg(value).ignore();
+            }
+            BulkWrite::Set(key, members) | BulkWrite::AddToSet(key, members)
+                if !members.is_empty() =>
+            {
+                pipe.cmd("SADD").arg(key).arg(members).ignore();
+            }
+            BulkWrite::List(key, values) if !values.is_empty() => {
+                pipe.cmd("RPUSH").arg(key).arg(values).ignore();
+            }
+            _ => (),
+        }
+    }
+}
+
+impl BulkWriter {
+    pub fn write(
+        &self,
+        writes: &[BulkWrite],
+        progress: &mut dyn FnMut(BulkProgress),
+        ctx: &mut ContextManager,
+    ) -> BulkReport {
+        //! Writes every entry; a chunk that still fails after all retries is reported per key
+        //! and the remaining chunks are written anyway.
+        let mut report = BulkReport::default();
+        for chunk in writes.chunks(self.chunk_size.max(1)) {
+            let mut attempt = 0;
+            let result = loop {
+                match self.write_chunk(chunk, ctx) {
+                    Err(err) if attempt < self.retries => {
+                        log::warn!(target: "bulk", "chunk failed, retrying: {:?}", err);
+                        attempt += 1;
+                        report.retries += 1;
+                        thread::sleep(self.retry_delay);
+                    }
+                    result => break result,
+                }
+            };
+            match result {
+                Ok(()) => report.written += chunk.len(),
+                Err(err) => report.failed.extend(
+                    chunk
+                        .iter()
+                        .map(|write| (write.get_key().clone(), format!("{:?}", err))),
+                ),
+            }
+            progress(BulkProgress {
+                written: report.written,
+                failed: report.failed.len(),
+                total: writes.len(),
+            });
+        }
+        report
+    }
+
+    fn write_chunk(&self, chunk: &[BulkWrite], ctx: &mut ContextManager) -> Result<(), RedisError> {
+        let conn = ctx.get_connection();
+        if conn.supports_pipelining() {
+            let mut pipe = redis::pipe();
+            for write in chunk {
+                write.add_to(&mut pipe);
+            }
+            return pipe.query(conn);
+        }
+        for write in chunk {
+            let mut pipe = redis::pipe();
+            write.add_to(&mut pipe);
+            for cmd in pipe.cmd_iter() {
+                cmd.query::<()>(conn)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,182 @@
+use std::{
+    collections::HashMap,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+use redis::{Cmd, ConnectionLike, RedisResult, Value};
+
+use super::snapshot::matches_pattern;
+
+const INVALIDATE_CHANNEL: &str = "__redis__:invalidate";
+/// Reads served from the cache; everything else goes straight to redis.
+const CACHED_COMMANDS: [&str; 2] = ["HGETALL", "SMEMBERS"];
+
+/// What the invalidation thread forwards: the keys that changed, or None to drop everything.
+type Invalidation = Option<Vec<String>>;
+
+/// Redis connection that keeps hot reads (group hashes, the group index) in memory.
+/// Redis tracks the keys this connection reads and pushes invalidations to a second
+/// connection, read by a background thread, whenever another client changes them.
+pub struct CachedConnection {
+    conn: redis::Connection,
+    patterns: Vec<String>,                     // keys worth caching
+    entries: HashMap<(String, String), Value>, // (command, key) -> reply
+    invalidations: Receiver<Invalidation>,
+    enabled: bool,
+}
+
+fn get_arg(cmd: &Cmd, index: usize) -> Option<String> {
+    match cmd.args_iter().nth(index)? {
+        redis::Arg::Simple(arg) => Some(String::from_utf8_lossy(arg).to_string()),
+        redis::Arg::Cursor => None,
+    }
+}
+
+fn parse_invalidation(message: Value) -> Option<Invalidation> {
+    //! Pub/sub messages look like ["message", channel, keys or nil].
+    let Value::Bulk(mut parts) = message else {
+        return None;
+    };
+    if parts.len() != 3 || parts[0] != Value::Data(b"message".to_vec()) {
+        return None;
+    }
+    match parts.pop()? {
+        Value::Bulk(keys) => Some(Some(
+            keys.into_iter()
+                .filter_map(|key| match key {
+                    Value::Data(key) => Some(String::from_utf8_lossy(&key).to_string()),
+                    _ => None,
+                })
+                .collect(),
+        )),
+        _ => Some(None),
+    }
+}
+
+impl CachedConnection {
+    pub fn new(client: &redis::Client, patterns: Vec<String>) -> RedisResult<Self> {
+        //! Opens the invalidation connection, then enables tracking on the main one with
+        //! invalidations redirected to it.
+        let mut listener = client.get_connection()?;
+        let listener_id: i64 = redis::cmd("CLIENT").arg("ID").query(&mut listener)?;
+        redis::cmd("SUBSCRIBE")
+            .arg(INVALIDATE_CHANNEL)
+            .query::<()>(&mut listener)?;
+        let (sender, invalidations) = mpsc::channel();
+        thread::spawn(move || {
+            // exits once the connection fails, or at the first message after the cache is dropped
+            while let Ok(message) = listener.recv_response() {
+                if let Some(invalidation) = parse_invalidation(message) {
+                    if sender.send(invalidation).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        let mut conn = client.get_connection()?;
+        redis::cmd("CLIENT")
+            .arg("TRACKING")
+            .arg("ON")
+            .arg("REDIRECT")
+            .arg(listener_id)
+            .query::<()>(&mut conn)?;
+        Ok(Self {
+            conn,
+            patterns,
+            entries: HashMap::new(),
+            invalidations,
+            enabled: true,
+        })
+    }
+
+    fn apply_invalidations(&mut self) {
+        //! Drops entries redis says changed. If the invalidation thread is gone nothing can
+        //! be trusted anymore, so caching stops for the rest of the connection's life.
+        loop {
+            match self.invalidations.try_recv() {
+                Ok(Some(keys)) => self.entries.retain(|(_, key), _| !keys.contains(key)),
+                Ok(None) => self.entries.clear(),
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => {
+                    if self.enabled {
+                        log::warn!(target: "cache", "invalidations stopped, client-side caching disabled");
+                    }
+                    self.enabled = false;
+                    self.entries.clear();
+                    return;
+                }
+            }
+        }
+    }
+
+    fn get_cache_key(&self, cmd: &Cmd) -> Option<(String, String)> {
+        let name = get_arg(cmd, 0)?.to_uppercase();
+        let key = get_arg(cmd, 1)?;
+        let cacheable = cmd.args_iter().count() == 2
+            && CACHED_COMMANDS.contains(&name.as_str())
+            && self
+                .patterns
+                .iter()
+                .any(|pattern| matches_pattern(pattern, &key));
+        cacheable.then_some((name, key))
+    }
+
+    fn forget_args(&mut self, cmd: &Cmd) {
+        //! Our own writes are dropped right away instead of waiting for their invalidation.
+        if self.entries.is_empty() {
+            return;
+        }
+        let args: Vec<String> = (1..cmd.args_iter().count())
+            .filter_map(|index| get_arg(cmd, index))
+            .collect();
+        self.entries.retain(|(_, key), _| !args.contains(key));
+    }
+}
+
+impl ConnectionLike for CachedConnection {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+        self.entries.clear();
+        self.conn.req_packed_command(cmd)
+    }
+
+    fn req_packed_commands(
+        &mut self,
+        cmd: &[u8],
+        offset: usize,
+        count: usize,
+    ) -> RedisResult<Vec<Value>> {
+        self.entries.clear();
+        self.conn.req_packed_commands(cmd, offset, count)
+    }
+
+    fn req_command(&mut self, cmd: &Cmd) -> RedisResult<Value> {
+        self.apply_invalidations();
+        let Some(cache_key) = self.get_cache_key(cmd).filter(|_| self.enabled) else {
+            self.forget_args(cmd);
+            return self.conn.req_command(cmd);
+        };
+        if let Some(value) = self.entries.get(&cache_key) {
+            return Ok(value.clone());
+        }
+        let value = self.conn.req_command(cmd)?;
+        self.entries.insert(cache_key, value.clone());
+        Ok(value)
+    }
+
+    fn get_db(&self) -> i64 {
+        self.conn.get_db()
+    }
+
+    fn supports_pipelining(&self) -> bool {
+        self.conn.supports_pipelining()
+    }
+
+    fn check_connection(&mut self) -> bool {
+        self.conn.check_connection()
+    }
+
+    fn is_open(&self) -> bool {
+        self.conn.is_open()
+    }
+}
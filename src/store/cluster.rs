@@ -0,0 +1,168 @@
+use redis::{
+    cluster::{ClusterClient, ClusterConnection},
+    Cmd, ConnectionLike, ErrorKind, RedisResult, Value,
+};
+
+use super::acl::{self, AclUser};
+
+/// Top bits of a cluster SCAN cursor hold the index of the master being scanned,
+/// the rest is that master's own cursor.
+const SHARD_BITS: u32 = 16;
+const NODE_CURSOR_MASK: u64 = (1 << (64 - SHARD_BITS)) - 1;
+
+/// Redis Cluster connection. Keyed commands are routed to the slot's master by the
+/// redis crate, which also fans KEYS out to every master; SCAN isn't routed there, so
+/// it is run against each master in turn here.
+pub struct ShardedConnection {
+    conn: ClusterConnection,
+    shards: Vec<redis::Connection>, // one per master, opened at the first SCAN
+    user: Option<AclUser>,          // logs in to every node as this user
+}
+
+fn get_args(cmd: &Cmd) -> Vec<Vec<u8>> {
+    cmd.args_iter()
+        .map(|arg| match arg {
+            redis::Arg::Simple(arg) => arg.to_vec(),
+            redis::Arg::Cursor => b"0".to_vec(),
+        })
+        .collect()
+}
+
+#[doc(hidden)]
+pub fn parse_masters(nodes: &str) -> Vec<String> {
+    //! Addresses of the healthy masters, sorted.
+    //! `CLUSTER NODES` lines look like `<id> <ip:port@cport> <flags> ...`.
+    let mut masters: Vec<String> = nodes
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let address = fields.nth(1)?;
+            let flags = fields.next()?;
+            let is_master = flags.split(',').any(|flag| flag == "master")
+                && !flags.split(',').any(|flag| flag == "fail");
+            is_master.then(|| address.split('@').next().unwrap_or(address).to_string())
+        })
+        .collect();
+    masters.sort();
+    masters
+}
+
+#[doc(hidden)]
+pub fn split_cursor(cursor: u64) -> (usize, u64) {
+    //! The master a cluster cursor points at, and that master's own cursor.
+    (
+        (cursor >> (64 - SHARD_BITS)) as usize,
+        cursor & NODE_CURSOR_MASK,
+    )
+}
+
+#[doc(hidden)]
+pub fn next_cursor(shard: usize, shard_count: usize, next: u64) -> RedisResult<u64> {
+    //! The cluster cursor following a SCAN step on `shard` that returned `next`. A finished
+    //! master moves on to the next one, and only the last one brings the cursor back to 0.
+    if next > NODE_CURSOR_MASK {
+        return Err((ErrorKind::ClientError, "SCAN cursor out of range").into());
+    }
+    Ok(match (next, shard + 1 < shard_count) {
+        (0, true) => ((shard + 1) as u64) << (64 - SHARD_BITS),
+        (0, false) => 0,
+        (next, _) => ((shard as u64) << (64 - SHARD_BITS)) | next,
+    })
+}
+
+impl ShardedConnection {
+    pub fn new(nodes: &[String], user: Option<&AclUser>) -> RedisResult<Self> {
+        //! Connects through any of the seed `host:port` nodes.
+        let infos = nodes
+            .iter()
+            .map(|node| acl::get_connection_info(node, user))
+            .collect::<RedisResult<Vec<_>>>()?;
+        Ok(Self {
+            conn: ClusterClient::new(infos)?.get_connection()?,
+            shards: Vec::new(),
+            user: user.cloned(),
+        })
+    }
+
+    fn get_shards(&mut self) -> RedisResult<&mut Vec<redis::Connection>> {
+        //! Connections to every master, in address order so cursors stay valid between calls.
+        if self.shards.is_empty() {
+            let nodes: String = redis::cmd("CLUSTER").arg("NODES").query(&mut self.conn)?;
+            for address in parse_masters(&nodes) {
+                let client =
+                    redis::Client::open(acl::get_connection_info(&address, self.user.as_ref())?)?;
+                self.shards.push(client.get_connection()?);
+            }
+        }
+        Ok(&mut self.shards)
+    }
+
+    fn scan(&mut self, args: &[Vec<u8>]) -> RedisResult<Value> {
+        //! Runs one SCAN step on the master the cursor points at. The cursor only goes
+        //! back to 0 once the last master is done.
+        let cursor: u64 = args
+            .get(1)
+            .and_then(|cursor| String::from_utf8_lossy(cursor).parse().ok())
+            .unwrap_or(0);
+        let (shard, node_cursor) = split_cursor(cursor);
+        let shards = self.get_shards()?;
+        let shard_count = shards.len();
+        let Some(conn) = shards.get_mut(shard) else {
+            return Ok(Value::Bulk(vec![
+                Value::Data(b"0".to_vec()),
+                Value::Bulk(vec![]),
+            ]));
+        };
+        let mut scan = redis::cmd("SCAN");
+        scan.arg(node_cursor);
+        for arg in args.iter().skip(2) {
+            scan.arg(arg.as_slice());
+        }
+        let (next, keys): (u64, Vec<Vec<u8>>) = scan.query(conn)?;
+        let next = next_cursor(shard, shard_count, next)?;
+        Ok(Value::Bulk(vec![
+            Value::Data(next.to_string().into_bytes()),
+            Value::Bulk(keys.into_iter().map(Value::Data).collect()),
+        ]))
+    }
+}
+
+impl ConnectionLike for ShardedConnection {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+        self.conn.req_packed_command(cmd)
+    }
+
+    fn req_packed_commands(
+        &mut self,
+        cmd: &[u8],
+        offset: usize,
+        count: usize,
+    ) -> RedisResult<Vec<Value>> {
+        self.conn.req_packed_commands(cmd, offset, count)
+    }
+
+    fn req_command(&mut self, cmd: &Cmd) -> RedisResult<Value> {
+        let args = get_args(cmd);
+        match args.first() {
+            Some(name) if name.eq_ignore_ascii_case(b"SCAN") => self.scan(&args),
+            _ => self.conn.req_command(cmd),
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        self.conn.get_db()
+    }
+
+    fn supports_pipelining(&self) -> bool {
+        // a pipeline goes to the node of its first key, so multi-slot ones would fail
+        self.conn.supports_pipelining()
+    }
+
+    fn check_connection(&mut self) -> bool {
+        self.conn.check_connection()
+    }
+
+    fn is_open(&self) -> bool {
+        self.conn.is_open()
+    }
+}
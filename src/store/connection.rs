@@ -0,0 +1,193 @@
+use std::time::Duration;
+
+use redis::{Cmd, ConnectionLike, RedisResult, Value};
+
+#[cfg(feature = "client-cache")]
+use super::cache::CachedConnection;
+#[cfg(feature = "cluster")]
+use super::cluster::ShardedConnection;
+#[cfg(feature = "sentinel")]
+use super::sentinel::FailoverConnection;
+use super::{
+    metrics,
+    mirror::{MirrorStats, MirroredConnection},
+    retry::RetryingConnection,
+    snapshot::SnapshotConnection,
+};
+
+/// Where commands are sent: a live Redis server, cluster or sentinel-managed master,
+/// one of those with writes mirrored to a second server or reopened when it drops, or
+/// an offline snapshot.
+pub enum Connection {
+    Redis(redis::Connection),
+    #[cfg(feature = "client-cache")]
+    Cached(CachedConnection),
+    #[cfg(feature = "cluster")]
+    Cluster(ShardedConnection),
+    #[cfg(feature = "sentinel")]
+    Sentinel(FailoverConnection),
+    Mirrored(MirroredConnection),
+    Retrying(RetryingConnection),
+    Snapshot(SnapshotConnection),
+}
+
+/// The connection moving to another master, to be reported as an event.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Reconnect {
+    pub master_name: String,
+    pub from: String,
+    pub to: String,
+    pub reason: String, // the error that made it look for the master again
+}
+
+impl Connection {
+    pub fn is_offline(&self) -> bool {
+        matches!(self, Connection::Snapshot(_))
+    }
+
+    pub fn supports_transactions(&self) -> bool {
+        //! Whether MULTI/EXEC works; a cluster can't run one across slots.
+        match self {
+            #[cfg(feature = "cluster")]
+            Connection::Cluster(_) => false,
+            Connection::Mirrored(conn) => conn.supports_transactions(),
+            Connection::Retrying(conn) => conn.get_inner().supports_transactions(),
+            Connection::Snapshot(_) => false,
+            _ => true,
+        }
+    }
+
+    pub fn get_state(&self) -> String {
+        //! What the connection is talking to, for health output.
+        match self {
+            Connection::Redis(_) => "redis".into(),
+            #[cfg(feature = "client-cache")]
+            Connection::Cached(_) => "redis (client-side cache)".into(),
+            #[cfg(feature = "cluster")]
+            Connection::Cluster(_) => "redis cluster".into(),
+            #[cfg(feature = "sentinel")]
+            Connection::Sentinel(conn) => conn.get_state(),
+            Connection::Mirrored(conn) => conn.get_state(),
+            Connection::Retrying(conn) => conn.get_state(),
+            Connection::Snapshot(_) => "snapshot (offline)".into(),
+        }
+    }
+
+    pub fn set_reply_timeout(&mut self, timeout: Option<Duration>) -> RedisResult<()> {
+        //! Gives up on a reply (or write) after `timeout`, None waits forever. Only single
+        //! redis connections (mirrored ones through their primary) support it, the others
+        //! keep waiting. A reply that timed out is still read by the next command, so the
+        //! connection has to be reopened after one, see `ContextManager::reconnect`.
+        match self {
+            Connection::Redis(conn) => {
+                conn.set_read_timeout(timeout)?;
+                conn.set_write_timeout(timeout)
+            }
+            Connection::Mirrored(conn) => conn.get_primary().set_reply_timeout(timeout),
+            Connection::Retrying(conn) => conn.set_reply_timeout(timeout),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn take_reconnects(&mut self) -> Vec<Reconnect> {
+        //! Master switches since the last call. Only sentinel connections switch.
+        match self {
+            #[cfg(feature = "sentinel")]
+            Connection::Sentinel(conn) => conn.take_reconnects(),
+            Connection::Mirrored(conn) => conn.get_primary().take_reconnects(),
+            Connection::Retrying(conn) => conn.take_reconnects(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn get_mirror_stats(&self) -> Option<&MirrorStats> {
+        match self {
+            Connection::Mirrored(conn) => Some(conn.get_stats()),
+            Connection::Retrying(conn) => conn.get_inner().get_mirror_stats(),
+            _ => None,
+        }
+    }
+
+    pub(super) fn inner(&mut self) -> &mut dyn ConnectionLike {
+        match self {
+            Connection::Redis(conn) => conn,
+            #[cfg(feature = "client-cache")]
+            Connection::Cached(conn) => conn,
+            #[cfg(feature = "cluster")]
+            Connection::Cluster(conn) => conn,
+            #[cfg(feature = "sentinel")]
+            Connection::Sentinel(conn) => conn,
+            Connection::Mirrored(conn) => conn,
+            Connection::Retrying(conn) => conn,
+            Connection::Snapshot(conn) => conn,
+        }
+    }
+}
+
+impl ConnectionLike for Connection {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+        self.inner().req_packed_command(cmd)
+    }
+
+    fn req_packed_commands(
+        &mut self,
+        cmd: &[u8],
+        offset: usize,
+        count: usize,
+    ) -> RedisResult<Vec<Value>> {
+        metrics::measure_packed(cmd, || self.inner().req_packed_commands(cmd, offset, count))
+    }
+
+    fn req_command(&mut self, cmd: &Cmd) -> RedisResult<Value> {
+        metrics::measure_command(cmd, || self.inner().req_command(cmd))
+    }
+
+    fn get_db(&self) -> i64 {
+        match self {
+            Connection::Redis(conn) => conn.get_db(),
+            #[cfg(feature = "client-cache")]
+            Connection::Cached(conn) => conn.get_db(),
+            #[cfg(feature = "cluster")]
+            Connection::Cluster(conn) => conn.get_db(),
+            #[cfg(feature = "sentinel")]
+            Connection::Sentinel(conn) => conn.get_db(),
+            Connection::Mirrored(conn) => conn.get_db(),
+            Connection::Retrying(conn) => conn.get_db(),
+            Connection::Snapshot(conn) => conn.get_db(),
+        }
+    }
+
+    fn supports_pipelining(&self) -> bool {
+        match self {
+            Connection::Redis(conn) => conn.supports_pipelining(),
+            #[cfg(feature = "client-cache")]
+            Connection::Cached(conn) => conn.supports_pipelining(),
+            #[cfg(feature = "cluster")]
+            Connection::Cluster(conn) => conn.supports_pipelining(),
+            #[cfg(feature = "sentinel")]
+            Connection::Sentinel(conn) => conn.supports_pipelining(),
+            Connection::Mirrored(conn) => conn.supports_pipelining(),
+            Connection::Retrying(conn) => conn.supports_pipelining(),
+            Connection::Snapshot(conn) => conn.supports_pipelining(),
+        }
+    }
+
+    fn check_connection(&mut self) -> bool {
+        self.inner().check_connection()
+    }
+
+    fn is_open(&self) -> bool {
+        match self {
+            Connection::Redis(conn) => conn.is_open(),
+            #[cfg(feature = "client-cache")]
+            Connection::Cached(conn) => conn.is_open(),
+            #[cfg(feature = "cluster")]
+            Connection::Cluster(conn) => conn.is_open(),
+            #[cfg(feature = "sentinel")]
+            Connection::Sentinel(conn) => conn.is_open(),
+            Connection::Mirrored(conn) => conn.is_open(),
+            Connection::Retrying(conn) => conn.is_open(),
+            Connection::Snapshot(conn) => conn.is_open(),
+        }
+    }
+}
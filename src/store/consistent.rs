@@ -0,0 +1,55 @@
+use std::collections::VecDeque;
+
+use redis::{from_redis_value, Cmd, FromRedisValue, RedisError, RedisResult, Value};
+
+use crate::context_manager::ContextManager;
+
+/// Replies of a `read_consistent` call, taken in the order the commands were given.
+#[derive(Clone, Debug)]
+pub struct ConsistentRead {
+    pub read_at: i64, // milliseconds since epoch, by the redis server's clock if it could be asked
+    pub atomic: bool, // false when the reads ran one after another
+    values: VecDeque<Value>,
+}
+
+impl ConsistentRead {
+    pub fn take<T: FromRedisValue>(&mut self) -> RedisResult<T> {
+        let value = self.values.pop_front().ok_or(RedisError::from((
+            redis::ErrorKind::ClientError,
+            "Consistent read has no more replies",
+        )))?;
+        from_redis_value(&value)
+    }
+}
+
+pub fn read_consistent(cmds: &[Cmd], ctx: &mut ContextManager) -> RedisResult<ConsistentRead> {
+    //! Runs read commands in one MULTI/EXEC round trip, so every reply reflects the same
+    //! moment, stamped with the server's TIME. Connections that can't run transactions
+    //! (snapshots, clusters) run them one at a time instead, which is only atomic for
+    //! a snapshot since nothing else writes to it, and stamp them with the context's clock.
+    let now = ctx.get_clock().now().timestamp_millis();
+    let conn = ctx.get_connection();
+    if !conn.supports_transactions() {
+        let values = cmds
+            .iter()
+            .map(|cmd| cmd.query::<Value>(conn))
+            .collect::<RedisResult<_>>()?;
+        return Ok(ConsistentRead {
+            read_at: now,
+            atomic: conn.is_offline(),
+            values,
+        });
+    }
+    let mut pipe = redis::pipe();
+    pipe.atomic().cmd("TIME");
+    for cmd in cmds {
+        pipe.add_command(cmd.clone());
+    }
+    let mut values: VecDeque<Value> = pipe.query::<Vec<Value>>(conn)?.into();
+    let (secs, micros): (i64, i64) = from_redis_value(&values.pop_front().unwrap_or(Value::Nil))?;
+    Ok(ConsistentRead {
+        read_at: secs * 1000 + micros / 1000,
+        atomic: true,
+        values,
+    })
+}
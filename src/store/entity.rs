@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use crate::context_manager::ContextManager;
+
+use super::keys::KeyBuilder;
+use super::partial::PartialResult;
+use super::scan::{scan_keys, EntityIter, Page};
+
+/// Ends the error `get` returns for an entity that isn't stored, so callers can tell it apart.
+pub const NOT_FOUND: &str = "does not exist";
+
+/// A model cached in Redis under a key built from its id.
+/// Implementors describe how to key and (de)serialize themselves; fetching, listing,
+/// saving and deleting come for free.
+pub trait RedisEntity: Sized {
+    type Error: From<String>;
+
+    /// Set the ids of every entity are kept in, if the type maintains one.
+    fn index_set(_keys: &KeyBuilder) -> Option<String> {
+        None
+    }
+
+    fn get_id(&self) -> String;
+
+    fn from_map(map: HashMap<String, String>) -> Result<Self, Self::Error>;
+
+    fn to_map(&self) -> HashMap<String, String>;
+
+    fn get_key(id: &str, keys: &KeyBuilder) -> String;
+
+    /// Pattern matching the key of every entity of this type.
+    fn get_pattern(keys: &KeyBuilder) -> String;
+
+    fn read_map(
+        key: &str,
+        ctx: &mut ContextManager,
+    ) -> Result<HashMap<String, String>, redis::RedisError> {
+        //! Reads the raw fields stored at `key` (a hash by default).
+        redis::cmd("HGETALL").arg(key).query(ctx.get_connection())
+    }
+
+    fn write_map(
+        key: &str,
+        map: HashMap<String, String>,
+        ctx: &mut ContextManager,
+    ) -> Result<(), redis::RedisError> {
+        //! Stores the raw fields at `key` (a hash by default).
+        redis::cmd("HSET")
+            .arg(key)
+            .arg(map)
+            .query(ctx.get_connection())
+    }
+
+    fn get(id: &str, ctx: &mut ContextManager) -> Result<Self, Self::Error> {
+        let key = Self::get_key(id, ctx.get_keys());
+        Self::get_by_key(&key, ctx)
+    }
+
+    fn get_by_key(key: &str, ctx: &mut ContextManager) -> Result<Self, Self::Error> {
+        let map = Self::read_map(key, ctx)
+            .map_err(|err| format!("Redis data for {:?} could not be retrieved: {:?}", key, err))?;
+        if map.is_empty() {
+            return Err(format!("{:?} {}", key, NOT_FOUND).into());
+        }
+        Self::from_map(map)
+    }
+
+    fn iter(page_size: usize, ctx: &mut ContextManager) -> EntityIter<'_, Self> {
+        //! Streams every entity, scanning `page_size` keys at a time.
+        let pattern = Self::get_pattern(ctx.get_keys());
+        EntityIter::new(pattern, page_size, ctx)
+    }
+
+    fn get_page(
+        cursor: u64,
+        page_size: usize,
+        ctx: &mut ContextManager,
+    ) -> Result<Page<Self>, Self::Error> {
+        //! Fetches one page of entities starting at `cursor` (0 for the first page).
+        let pattern = Self::get_pattern(ctx.get_keys());
+        let (cursor, keys) = scan_keys(&pattern, cursor, page_size, ctx)
+            .map_err(|err| format!("SCAN over {:?} failed: {:?}", pattern, err))?;
+        let entries = keys
+            .iter()
+            .map(|key| Self::get_by_key(key, ctx))
+            .collect::<Result<Vec<Self>, Self::Error>>()?;
+        Ok(Page { cursor, entries })
+    }
+
+    fn get_all(ctx: &mut ContextManager) -> PartialResult<Self, Self::Error> {
+        //! Loads every entity at once; prefer `iter` on large networks.
+        //! Entries that fail to load are reported alongside the ones that did.
+        let page_size = ctx.get_config().get_scan_count();
+        Self::iter(page_size, ctx).collect()
+    }
+
+    fn exists(id: &str, ctx: &mut ContextManager) -> bool {
+        let key = Self::get_key(id, ctx.get_keys());
+        redis::cmd("EXISTS")
+            .arg(key)
+            .query::<bool>(ctx.get_connection())
+            .unwrap_or(false)
+    }
+
+    fn save(&self, ctx: &mut ContextManager) -> Result<(), Self::Error> {
+        //! Writes the entity and adds its id to the index set (if any).
+        let id = self.get_id();
+        let key = Self::get_key(&id, ctx.get_keys());
+        Self::write_map(&key, self.to_map(), ctx)
+            .map_err(|err| format!("{:?} could not be saved: {:?}", key, err))?;
+        if let Some(set) = Self::index_set(ctx.get_keys()) {
+            redis::cmd("SADD")
+                .arg(set)
+                .arg(&id)
+                .query::<()>(ctx.get_connection())
+                .map_err(|err| format!("{:?} could not be indexed: {:?}", id, err))?;
+        }
+        Ok(())
+    }
+
+    fn delete(id: &str, ctx: &mut ContextManager) -> Result<(), Self::Error> {
+        //! Removes the entity and its id from the index set (if any).
+        let key = Self::get_key(id, ctx.get_keys());
+        redis::cmd("DEL")
+            .arg(&key)
+            .query::<()>(ctx.get_connection())
+            .map_err(|err| format!("{:?} could not be deleted: {:?}", key, err))?;
+        if let Some(set) = Self::index_set(ctx.get_keys()) {
+            redis::cmd("SREM")
+                .arg(set)
+                .arg(id)
+                .query::<()>(ctx.get_connection())
+                .map_err(|err| format!("{:?} could not be unindexed: {:?}", id, err))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Redis key layout, configurable for Mineplex forks that moved their keys around.
+/// Templates may use the `{region}`, `{group}` and `{name}` placeholders.
+/// On Redis Cluster, give related keys a hash tag so they share a slot.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct KeyBuilder {
+    pub status: String,
+    pub group: String,
+    #[serde(default = "default_map_pool")]
+    pub map_pool: String, // hash of game -> comma-separated maps, read by the Arcade plugin
+    #[serde(default = "default_queue")]
+    pub queue: String, // list of players waiting for a full group, pushed and popped by hubs
+    #[serde(default = "default_counts")]
+    pub counts: String, // hash of a group's live instance counts, see `counters`
+    #[serde(default = "default_group_index")]
+    pub group_index: String, // set of every group's prefix, which hub plugins list groups from
+}
+
+fn default_map_pool() -> String {
+    "mappools.{group}".into()
+}
+
+fn default_queue() -> String {
+    "queues.{group}".into()
+}
+
+fn default_counts() -> String {
+    "servercounts.{group}".into()
+}
+
+fn default_group_index() -> String {
+    "servergroups".into()
+}
+
+impl Default for KeyBuilder {
+    fn default() -> Self {
+        Self {
+            status: "serverstatus.minecraft.{region}.{name}".into(),
+            group: "servergroups.{group}".into(),
+            map_pool: default_map_pool(),
+            queue: default_queue(),
+            counts: default_counts(),
+            group_index: default_group_index(),
+        }
+    }
+}
+
+/// Placeholders templates may use. Any other `{...}` is kept as is, so templates can
+/// carry Redis Cluster hash tags, e.g. `{servergroups}.{group}`.
+const PLACEHOLDERS: [&str; 3] = ["region", "group", "name"];
+
+enum TemplatePart<'a> {
+    Literal(String),
+    Placeholder(&'a str),
+}
+
+fn split_template(template: &str) -> Vec<TemplatePart<'_>> {
+    //! Literal text and placeholders in order, adjacent literals merged.
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        literal.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+            break;
+        };
+        let name = &rest[start + 1..end];
+        match PLACEHOLDERS.contains(&name) {
+            true => {
+                if !literal.is_empty() {
+                    parts.push(TemplatePart::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push(TemplatePart::Placeholder(name));
+            }
+            false => literal.push_str(&rest[start..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        parts.push(TemplatePart::Literal(literal));
+    }
+    parts
+}
+
+fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    //! Replaces each `{placeholder}` with its value, and any placeholder left over with `*`.
+    split_template(template)
+        .into_iter()
+        .map(|part| match part {
+            TemplatePart::Literal(literal) => literal,
+            TemplatePart::Placeholder(name) => values
+                .iter()
+                .find(|(placeholder, _)| *placeholder == name)
+                .map_or("*".into(), |(_, value)| value.to_string()),
+        })
+        .collect()
+}
+
+fn parse(template: &str, key: &str) -> Option<HashMap<String, String>> {
+    //! Reads the placeholder values back out of a key built from `template`.
+    //! Each placeholder matches up to the next literal part of the template.
+    let mut values = HashMap::new();
+    let mut rest = key;
+    let mut parts = split_template(template).into_iter().peekable();
+    while let Some(part) = parts.next() {
+        match part {
+            TemplatePart::Literal(literal) => rest = rest.strip_prefix(literal.as_str())?,
+            TemplatePart::Placeholder(name) => {
+                let value = match parts.next_if(|part| matches!(part, TemplatePart::Literal(_))) {
+                    Some(TemplatePart::Literal(literal)) => {
+                        let (value, remainder) = rest.split_once(literal.as_str())?;
+                        rest = remainder;
+                        value
+                    }
+                    _ => std::mem::take(&mut rest),
+                };
+                values.insert(name.to_string(), value.to_string());
+            }
+        }
+    }
+    rest.is_empty().then_some(values)
+}
+
+pub fn get_group_of(server_name: &str) -> &str {
+    //! `Lobby-1` -> `Lobby`
+    server_name
+        .rsplit_once('-')
+        .map_or(server_name, |(group, _)| group)
+}
+
+impl KeyBuilder {
+    pub fn status_key(&self, region: &str, server_name: &str) -> String {
+        fill(
+            &self.status,
+            &[
+                ("region", region),
+                ("group", get_group_of(server_name)),
+                ("name", server_name),
+            ],
+        )
+    }
+
+    pub fn status_pattern(&self, region: Option<&str>, group: Option<&str>) -> String {
+        //! Pattern matching the statuses of a region and/or group (all of them if both are None).
+        let name = group.map(|group| format!("{}-*", group));
+        let mut values: Vec<(&str, &str)> = Vec::new();
+        if let Some(region) = region {
+            values.push(("region", region));
+        }
+        if let (Some(group), Some(name)) = (group, name.as_ref()) {
+            values.push(("group", group));
+            values.push(("name", name));
+        }
+        fill(&self.status, &values)
+    }
+
+    pub fn parse_status_key(&self, key: &str) -> Option<(String, String)> {
+        //! Returns the (region, server name) a status key was built from.
+        let mut values = parse(&self.status, key)?;
+        Some((
+            values.remove("region").unwrap_or_default(),
+            values.remove("name")?,
+        ))
+    }
+
+    pub fn group_key(&self, group: &str) -> String {
+        fill(&self.group, &[("group", group)])
+    }
+
+    pub fn group_pattern(&self) -> String {
+        fill(&self.group, &[])
+    }
+
+    pub fn map_pool_key(&self, group: &str) -> String {
+        fill(&self.map_pool, &[("group", group)])
+    }
+
+    pub fn queue_key(&self, group: &str) -> String {
+        fill(&self.queue, &[("group", group)])
+    }
+
+    pub fn counts_key(&self, group: &str) -> String {
+        fill(&self.counts, &[("group", group)])
+    }
+
+    pub fn group_index_key(&self) -> String {
+        fill(&self.group_index, &[])
+    }
+}
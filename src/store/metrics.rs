@@ -0,0 +1,271 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt::Display,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use redis::{Cmd, RedisResult, Value};
+use serde::{Deserialize, Serialize};
+
+use crate::context_manager::ContextManager;
+
+use super::scan::scan_all_keys;
+
+const METRICS_PREFIX: &str = "servermonitor.metrics.redis.";
+/// Label for pipelined batches, which are timed as a whole.
+const PIPELINE: &str = "PIPELINE";
+
+/// Every command this process sent, keyed by command name.
+static METRICS: Mutex<RedisMetrics> = Mutex::new(RedisMetrics {
+    commands: BTreeMap::new(),
+    groups: BTreeMap::new(),
+    regions: BTreeMap::new(),
+    cycle_reads: BTreeMap::new(),
+});
+
+thread_local! {
+    /// Region whose worker runs on this thread, see `set_region`.
+    static REGION: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct CommandStats {
+    pub calls: u64,
+    pub errors: u64,
+    pub total_micros: u64,
+    pub max_micros: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+/// How often one group hash was read and written.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct GroupAccess {
+    pub reads: u64,
+    pub writes: u64,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct RedisMetrics {
+    pub commands: BTreeMap<String, CommandStats>,
+    #[serde(default)]
+    pub groups: BTreeMap<String, GroupAccess>, // group key -> access counts
+    #[serde(default)]
+    pub regions: BTreeMap<String, CommandStats>, // region -> every command its worker sent
+    #[serde(skip)]
+    cycle_reads: BTreeMap<String, usize>, // group key -> reads since `begin_cycle`
+}
+
+impl CommandStats {
+    pub fn get_mean(&self) -> Duration {
+        Duration::from_micros(self.total_micros / self.calls.max(1))
+    }
+}
+
+impl Display for RedisMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:<12} {:>8} {:>7} {:>10} {:>10} {:>10} {:>10}",
+            "command", "calls", "errors", "mean", "max", "sent", "received"
+        )?;
+        for (name, stats) in self.commands.iter() {
+            writeln!(
+                f,
+                "{:<12} {:>8} {:>7} {:>10} {:>10} {:>10} {:>10}",
+                name,
+                stats.calls,
+                stats.errors,
+                format!("{:.2?}", stats.get_mean()),
+                format!("{:.2?}", Duration::from_micros(stats.max_micros)),
+                stats.bytes_sent,
+                stats.bytes_received
+            )?;
+        }
+        if !self.regions.is_empty() {
+            writeln!(
+                f,
+                "\n{:<12} {:>8} {:>7} {:>10} {:>10}",
+                "region", "calls", "errors", "mean", "max"
+            )?;
+            for (region, stats) in self.regions.iter() {
+                writeln!(
+                    f,
+                    "{:<12} {:>8} {:>7} {:>10} {:>10}",
+                    region,
+                    stats.calls,
+                    stats.errors,
+                    format!("{:.2?}", stats.get_mean()),
+                    format!("{:.2?}", Duration::from_micros(stats.max_micros)),
+                )?;
+            }
+        }
+        if self.groups.is_empty() {
+            return Ok(());
+        }
+        writeln!(f, "\n{:<32} {:>8} {:>8}", "group", "reads", "writes")?;
+        for (key, access) in self.groups.iter() {
+            writeln!(f, "{:<32} {:>8} {:>8}", key, access.reads, access.writes)?;
+        }
+        Ok(())
+    }
+}
+
+impl CommandStats {
+    fn record(&mut self, elapsed: Duration, sent: usize, result: Result<usize, ()>) {
+        let micros = elapsed.as_micros() as u64;
+        self.calls += 1;
+        self.total_micros += micros;
+        self.max_micros = self.max_micros.max(micros);
+        self.bytes_sent += sent as u64;
+        match result {
+            Ok(received) => self.bytes_received += received as u64,
+            Err(()) => self.errors += 1,
+        }
+    }
+}
+
+impl RedisMetrics {
+    fn record(&mut self, name: &str, elapsed: Duration, sent: usize, result: Result<usize, ()>) {
+        self.commands
+            .entry(name.into())
+            .or_default()
+            .record(elapsed, sent, result);
+        if let Some(region) = REGION.with(|region| region.borrow().clone()) {
+            self.regions
+                .entry(region)
+                .or_default()
+                .record(elapsed, sent, result);
+        }
+    }
+
+    pub fn get_current() -> Self {
+        //! What this process has sent so far.
+        METRICS
+            .lock()
+            .map(|metrics| metrics.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn publish(
+        instance_id: &str,
+        ttl: Duration,
+        ctx: &mut ContextManager,
+    ) -> Result<(), String> {
+        //! Stores this process's metrics for `stats redis`; they expire with the manager's heartbeat.
+        let raw = serde_json::to_string(&Self::get_current())
+            .map_err(|err| format!("metrics could not be serialized: {:?}", err))?;
+        redis::cmd("SET")
+            .arg(format!("{}{}", METRICS_PREFIX, instance_id))
+            .arg(raw)
+            .arg("EX")
+            .arg(ttl.as_secs().max(1))
+            .query::<()>(ctx.get_connection())
+            .map_err(|err| format!("metrics could not be stored: {:?}", err))
+    }
+
+    pub fn get_published(
+        ctx: &mut ContextManager,
+    ) -> Result<Vec<(String, Self)>, redis::RedisError> {
+        //! Metrics of every running manager, by instance id. Unparsable entries are skipped.
+        let keys = scan_all_keys(&format!("{}*", METRICS_PREFIX), ctx)?;
+        let mut published = Vec::new();
+        for key in keys {
+            let raw: Option<String> = redis::cmd("GET").arg(&key).query(ctx.get_connection())?;
+            if let Some(metrics) = raw.and_then(|raw| serde_json::from_str(&raw).ok()) {
+                published.push((key.trim_start_matches(METRICS_PREFIX).to_string(), metrics));
+            }
+        }
+        published.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(published)
+    }
+}
+
+pub fn set_region(region: &str) {
+    //! Counts every command later sent from this thread towards `region` as well.
+    REGION.with(|current| *current.borrow_mut() = Some(region.into()));
+}
+
+pub fn record_group_access(key: &str, write: bool) {
+    //! Counts a read or write of a group hash.
+    if let Ok(mut metrics) = METRICS.lock() {
+        let access = metrics.groups.entry(key.into()).or_default();
+        match write {
+            true => access.writes += 1,
+            false => access.reads += 1,
+        }
+        if !write {
+            *metrics.cycle_reads.entry(key.into()).or_default() += 1;
+        }
+    }
+}
+
+pub fn begin_cycle() -> Vec<(String, usize)> {
+    //! Starts a new cycle (a monitor tick), returning how often each group hash was read
+    //! during the one that just ended.
+    METRICS
+        .lock()
+        .map(|mut metrics| {
+            std::mem::take(&mut metrics.cycle_reads)
+                .into_iter()
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn get_value_size(value: &Value) -> usize {
+    match value {
+        Value::Data(data) => data.len(),
+        Value::Bulk(values) => values.iter().map(get_value_size).sum(),
+        Value::Status(status) => status.len(),
+        Value::Int(_) => 8,
+        Value::Nil | Value::Okay => 0,
+    }
+}
+
+fn record(name: &str, started: Instant, sent: usize, received: Result<usize, ()>) {
+    if let Ok(mut metrics) = METRICS.lock() {
+        metrics.record(name, started.elapsed(), sent, received);
+    }
+}
+
+pub fn measure_command(cmd: &Cmd, send: impl FnOnce() -> RedisResult<Value>) -> RedisResult<Value> {
+    //! Sends one command and records its latency, payload sizes and outcome.
+    let name = match cmd.args_iter().next() {
+        Some(redis::Arg::Simple(name)) => String::from_utf8_lossy(name).to_uppercase(),
+        _ => "UNKNOWN".into(),
+    };
+    let sent = cmd
+        .args_iter()
+        .map(|arg| match arg {
+            redis::Arg::Simple(arg) => arg.len(),
+            redis::Arg::Cursor => 8,
+        })
+        .sum();
+    let started = Instant::now();
+    let result = send();
+    record(
+        &name,
+        started,
+        sent,
+        result.as_ref().map(get_value_size).map_err(|_| ()),
+    );
+    result
+}
+
+pub fn measure_packed(
+    packed: &[u8],
+    send: impl FnOnce() -> RedisResult<Vec<Value>>,
+) -> RedisResult<Vec<Value>> {
+    //! Sends a pipelined batch and records it as one PIPELINE call.
+    let started = Instant::now();
+    let result = send();
+    let received = result
+        .as_ref()
+        .map(|values| values.iter().map(get_value_size).sum())
+        .map_err(|_| ());
+    record(PIPELINE, started, packed.len(), received);
+    result
+}
@@ -0,0 +1,324 @@
+use std::{collections::BTreeSet, fmt::Display};
+
+use redis::{Cmd, ConnectionLike, RedisError, RedisResult, Value};
+
+use crate::context_manager::ContextManager;
+
+use super::{connection::Connection, snapshot::Snapshot};
+
+/// Commands that change data, and so are applied to the mirror as well. Everything else
+/// (reads, PUBLISH, CLIENT...) only goes to the primary.
+const MIRRORED_COMMANDS: [&str; 32] = [
+    "APPEND", "DECR", "DECRBY", "DEL", "EVAL", "EVALSHA", "EXPIRE", "EXPIREAT", "HDEL", "HINCRBY",
+    "HMSET", "HSET", "HSETNX", "INCR", "INCRBY", "LPOP", "LPUSH", "LREM", "LSET", "LTRIM",
+    "PERSIST", "PEXPIRE", "RENAME", "RPOP", "RPUSH", "SADD", "SET", "SETEX", "SETNX", "SREM",
+    "UNLINK", "ZADD",
+];
+
+fn is_mirrored(name: &str) -> bool {
+    MIRRORED_COMMANDS.contains(&name.to_uppercase().as_str())
+}
+
+pub(super) fn get_name(cmd: &Cmd) -> Option<String> {
+    match cmd.args_iter().next()? {
+        redis::Arg::Simple(name) => Some(String::from_utf8_lossy(name).to_string()),
+        redis::Arg::Cursor => None,
+    }
+}
+
+fn read_line(packed: &[u8], pos: &mut usize) -> Option<usize> {
+    //! Parses the number after a `*` or `$` marker, up to its `\r\n`.
+    let end = *pos + packed[*pos..].windows(2).position(|pair| pair == b"\r\n")?;
+    let number = std::str::from_utf8(packed.get(*pos + 1..end)?)
+        .ok()?
+        .parse()
+        .ok()?;
+    *pos = end + 2;
+    Some(number)
+}
+
+pub(super) fn get_packed_names(packed: &[u8]) -> Option<Vec<String>> {
+    //! The name of every command in a packed (RESP encoded) request or pipeline, None if
+    //! it can't be parsed.
+    let mut names = Vec::new();
+    let mut pos = 0;
+    while pos < packed.len() {
+        if packed[pos] != b'*' {
+            return None;
+        }
+        let args = read_line(packed, &mut pos)?;
+        for index in 0..args {
+            if packed.get(pos) != Some(&b'$') {
+                return None;
+            }
+            let len = read_line(packed, &mut pos)?;
+            let arg = packed.get(pos..pos + len)?;
+            if index == 0 {
+                names.push(String::from_utf8_lossy(arg).to_string());
+            }
+            pos += len + 2;
+        }
+    }
+    Some(names)
+}
+
+fn has_mirrored(packed: &[u8]) -> bool {
+    //! Requests that can't be parsed are mirrored too, a mirrored read does no harm.
+    get_packed_names(packed).is_none_or(|names| names.iter().any(|name| is_mirrored(name)))
+}
+
+/// How many writes reached the mirror, for health output and `mirror verify`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MirrorStats {
+    pub mirrored: usize,
+    pub failed: usize,
+    pub last_error: Option<String>,
+}
+
+/// Dual-writing connection used while moving to a new redis: every command is served by
+/// the primary, and those that change data are applied to the mirror afterwards. A write
+/// the primary refused isn't mirrored; one the mirror refused is counted, not returned,
+/// so a struggling new instance never fails the orchestration. `mirror verify` finds
+/// what diverged.
+pub struct MirroredConnection {
+    primary: Box<Connection>,
+    client: redis::Client,
+    mirror: Option<redis::Connection>, // None after it dropped, reopened on the next write
+    address: String,
+    stats: MirrorStats,
+}
+
+impl MirroredConnection {
+    pub fn new(primary: Connection, client: redis::Client, address: String) -> Self {
+        //! Connects to the mirror lazily, so a mirror that is down doesn't keep the
+        //! primary from being used.
+        Self {
+            primary: Box::new(primary),
+            client,
+            mirror: None,
+            address,
+            stats: MirrorStats::default(),
+        }
+    }
+
+    pub fn get_stats(&self) -> &MirrorStats {
+        &self.stats
+    }
+
+    pub fn get_state(&self) -> String {
+        let mut state = format!(
+            "{}, mirrored to {} ({} writes",
+            self.primary.get_state(),
+            self.address,
+            self.stats.mirrored
+        );
+        if self.stats.failed > 0 {
+            state.push_str(&format!(", {} failed", self.stats.failed));
+        }
+        state.push(')');
+        state
+    }
+
+    pub fn supports_transactions(&self) -> bool {
+        //! MULTI/EXEC blocks are mirrored whole, so they work wherever the primary's do.
+        self.primary.supports_transactions()
+    }
+
+    pub fn get_primary(&mut self) -> &mut Connection {
+        &mut self.primary
+    }
+
+    fn mirror<T>(&mut self, send: impl FnOnce(&mut redis::Connection) -> RedisResult<T>) {
+        if self.mirror.is_none() {
+            match self.client.get_connection() {
+                Ok(conn) => self.mirror = Some(conn),
+                Err(err) => return self.record_failure(err),
+            }
+        }
+        let conn = self.mirror.as_mut().expect("mirror is connected");
+        match send(conn) {
+            Ok(_) => self.stats.mirrored += 1,
+            Err(err) => {
+                if err.is_connection_dropped() || err.is_io_error() {
+                    self.mirror = None;
+                }
+                self.record_failure(err);
+            }
+        }
+    }
+
+    fn record_failure(&mut self, err: RedisError) {
+        if self.stats.failed == 0 {
+            log::warn!(target: "mirror", "write to {} failed: {}", self.address, err);
+        }
+        self.stats.failed += 1;
+        self.stats.last_error = Some(err.to_string());
+    }
+}
+
+impl ConnectionLike for MirroredConnection {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+        let result = self.primary.inner().req_packed_command(cmd)?;
+        if has_mirrored(cmd) {
+            self.mirror(|conn| conn.req_packed_command(cmd));
+        }
+        Ok(result)
+    }
+
+    fn req_packed_commands(
+        &mut self,
+        cmd: &[u8],
+        offset: usize,
+        count: usize,
+    ) -> RedisResult<Vec<Value>> {
+        let result = self
+            .primary
+            .inner()
+            .req_packed_commands(cmd, offset, count)?;
+        if has_mirrored(cmd) {
+            self.mirror(|conn| conn.req_packed_commands(cmd, offset, count));
+        }
+        Ok(result)
+    }
+
+    fn req_command(&mut self, cmd: &Cmd) -> RedisResult<Value> {
+        let result = self.primary.inner().req_command(cmd)?;
+        if get_name(cmd).is_some_and(|name| is_mirrored(&name)) {
+            self.mirror(|conn| conn.req_command(cmd));
+        }
+        Ok(result)
+    }
+
+    fn get_db(&self) -> i64 {
+        self.primary.get_db()
+    }
+
+    fn supports_pipelining(&self) -> bool {
+        self.primary.supports_pipelining()
+    }
+
+    fn check_connection(&mut self) -> bool {
+        self.primary.check_connection()
+    }
+
+    fn is_open(&self) -> bool {
+        self.primary.is_open()
+    }
+}
+
+/// Where the mirror differs from the primary, by key.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MirrorReport {
+    pub checked: usize,         // keys on the primary
+    pub missing: Vec<String>,   // on the primary only
+    pub extra: Vec<String>,     // on the mirror only
+    pub differing: Vec<String>, // different type or value
+}
+
+impl Display for MirrorReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} keys checked: {} missing on the mirror, {} only on the mirror, {} differing",
+            self.checked,
+            self.missing.len(),
+            self.extra.len(),
+            self.differing.len()
+        )
+    }
+}
+
+impl MirrorReport {
+    pub fn is_consistent(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.differing.is_empty()
+    }
+
+    fn get_mismatched(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .missing
+            .iter()
+            .chain(self.extra.iter())
+            .chain(self.differing.iter())
+            .cloned()
+            .collect();
+        keys.sort();
+        keys
+    }
+}
+
+fn get_keys(snapshot: &Snapshot) -> BTreeSet<&String> {
+    snapshot
+        .strings
+        .keys()
+        .chain(snapshot.hashes.keys())
+        .chain(snapshot.sets.keys())
+        .chain(snapshot.lists.keys())
+        .collect()
+}
+
+fn is_same(key: &String, primary: &Snapshot, mirror: &Snapshot) -> bool {
+    primary.strings.get(key) == mirror.strings.get(key)
+        && primary.hashes.get(key) == mirror.hashes.get(key)
+        && primary.sets.get(key) == mirror.sets.get(key)
+        && primary.lists.get(key) == mirror.lists.get(key)
+}
+
+pub fn compare(primary: &Snapshot, mirror: &Snapshot) -> MirrorReport {
+    let primary_keys = get_keys(primary);
+    let mirror_keys = get_keys(mirror);
+    MirrorReport {
+        checked: primary_keys.len(),
+        missing: primary_keys
+            .difference(&mirror_keys)
+            .map(|key| key.to_string())
+            .collect(),
+        extra: mirror_keys
+            .difference(&primary_keys)
+            .map(|key| key.to_string())
+            .collect(),
+        differing: primary_keys
+            .intersection(&mirror_keys)
+            .filter(|key| !is_same(key, primary, mirror))
+            .map(|key| key.to_string())
+            .collect(),
+    }
+}
+
+fn escape_pattern(key: &str) -> String {
+    key.chars()
+        .flat_map(|c| match c {
+            '*' | '?' | '[' | ']' | '\\' => vec!['\\', c],
+            _ => vec![c],
+        })
+        .collect()
+}
+
+pub fn verify(
+    patterns: &[String],
+    primary: &mut ContextManager,
+    mirror: &mut ContextManager,
+) -> RedisResult<MirrorReport> {
+    //! Compares the keys matching `patterns` on both instances. Keys written while they
+    //! were being read can differ for a moment, so mismatches are read again (once) and
+    //! only those that still differ are reported.
+    let report = compare(
+        &Snapshot::capture_matching(patterns, primary)?,
+        &Snapshot::capture_matching(patterns, mirror)?,
+    );
+    if report.is_consistent() {
+        return Ok(report);
+    }
+    let keys: Vec<String> = report
+        .get_mismatched()
+        .iter()
+        .map(|key| escape_pattern(key))
+        .collect();
+    let recheck = compare(
+        &Snapshot::capture_matching(&keys, primary)?,
+        &Snapshot::capture_matching(&keys, mirror)?,
+    );
+    Ok(MirrorReport {
+        checked: report.checked,
+        ..recheck
+    })
+}
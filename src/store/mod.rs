@@ -0,0 +1,18 @@
+pub mod acl;
+pub mod bulk;
+#[cfg(feature = "client-cache")]
+pub mod cache;
+#[cfg(feature = "cluster")]
+pub mod cluster;
+pub mod connection;
+pub mod consistent;
+pub mod entity;
+pub mod keys;
+pub mod metrics;
+pub mod mirror;
+pub mod partial;
+pub mod retry;
+pub mod scan;
+#[cfg(feature = "sentinel")]
+pub mod sentinel;
+pub mod snapshot;
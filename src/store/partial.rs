@@ -0,0 +1,54 @@
+/// Outcome of a bulk read where single entries may fail without failing the rest.
+#[derive(Debug)]
+pub struct PartialResult<T, E> {
+    pub ok: Vec<T>,
+    pub failed: Vec<(String, E)>, // (redis key, why it could not be read)
+}
+
+impl<T, E> Default for PartialResult<T, E> {
+    fn default() -> Self {
+        Self {
+            ok: Vec::new(),
+            failed: Vec::new(),
+        }
+    }
+}
+
+impl<T, E> FromIterator<Result<T, (String, E)>> for PartialResult<T, E> {
+    fn from_iter<I: IntoIterator<Item = Result<T, (String, E)>>>(iter: I) -> Self {
+        let mut result = Self::default();
+        for entry in iter {
+            match entry {
+                Ok(value) => result.ok.push(value),
+                Err(failure) => result.failed.push(failure),
+            }
+        }
+        result
+    }
+}
+
+impl<T, E> PartialResult<T, E> {
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    pub fn get_failed_keys(&self) -> Vec<&String> {
+        self.failed.iter().map(|(key, _)| key).collect()
+    }
+
+    pub fn into_result(self) -> Result<Vec<T>, E> {
+        //! All entries, or the first failure for callers that can't work with partial data.
+        match self.failed.into_iter().next() {
+            Some((_, err)) => Err(err),
+            None => Ok(self.ok),
+        }
+    }
+
+    pub fn filter(self, predicate: impl Fn(&T) -> bool) -> Self {
+        //! Keeps the failures and the successful entries matching `predicate`.
+        Self {
+            ok: self.ok.into_iter().filter(predicate).collect(),
+            failed: self.failed,
+        }
+    }
+}
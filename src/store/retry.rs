@@ -0,0 +1,242 @@
+use std::{thread, time::Duration};
+
+use redis::{Cmd, ConnectionLike, RedisError, RedisResult, Value};
+
+use super::{
+    connection::{Connection, Reconnect},
+    mirror::{get_name, get_packed_names},
+};
+
+/// Longest wait between two attempts, however many have failed.
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Commands that can be sent again after the connection dropped before their reply: a
+/// write may have been applied already (a second HINCRBY or LPUSH would count twice),
+/// so only these are resent.
+const READ_ONLY_COMMANDS: [&str; 32] = [
+    "DBSIZE",
+    "ECHO",
+    "EXISTS",
+    "GET",
+    "HEXISTS",
+    "HGET",
+    "HGETALL",
+    "HKEYS",
+    "HLEN",
+    "HMGET",
+    "HSCAN",
+    "HVALS",
+    "INFO",
+    "KEYS",
+    "LINDEX",
+    "LLEN",
+    "LRANGE",
+    "MGET",
+    "PING",
+    "PTTL",
+    "SCAN",
+    "SCARD",
+    "SISMEMBER",
+    "SMEMBERS",
+    "SSCAN",
+    "STRLEN",
+    "TIME",
+    "TTL",
+    "TYPE",
+    "ZCARD",
+    "ZRANGE",
+    "ZSCORE",
+];
+
+fn is_read_only(names: &[String]) -> bool {
+    names
+        .iter()
+        .all(|name| READ_ONLY_COMMANDS.contains(&name.to_uppercase().as_str()))
+}
+
+fn has_command(names: &[String], commands: &[&str]) -> bool {
+    names
+        .iter()
+        .any(|name| commands.contains(&name.to_uppercase().as_str()))
+}
+
+/// How a dropped connection is reopened: up to `max_retries` times, waiting `backoff`
+/// before the first attempt and twice as long before each next one.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn get_delay(&self, attempt: u32) -> Duration {
+        //! Wait before retry `attempt` (from 0), doubling each time up to `MAX_BACKOFF`.
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_BACKOFF)
+    }
+}
+
+/// Opens a new connection to the same server as the same user.
+pub type Reopen = Box<dyn Fn() -> RedisResult<Connection> + Send + Sync>;
+
+/// Connection that survives redis restarting: once the connection was refused or dropped,
+/// it is reopened with the policy's backoff before the next command. A refused command was
+/// never sent and a dropped read-only one changed nothing, so those are sent again; a
+/// dropped write may have been applied, so its error is returned instead. Other errors,
+/// and the last one once the retries are used up, are returned as they are.
+pub struct RetryingConnection {
+    conn: Box<Connection>,
+    reopen: Reopen,
+    policy: RetryPolicy,
+    timeout: Option<Duration>, // applied again to every reopened connection
+    reconnects: usize,
+    pending: Vec<Reconnect>, // master switches of the connections replaced so far
+    broken: Option<String>,  // why `conn` has to be reopened before its next command
+    watching: bool,          // a WATCH is pending, which a reopened connection would lose
+}
+
+fn is_lost(err: &RedisError) -> bool {
+    err.is_connection_refusal() || err.is_connection_dropped()
+}
+
+impl RetryingConnection {
+    pub fn new(conn: Connection, reopen: Reopen, policy: RetryPolicy) -> Self {
+        Self {
+            conn: Box::new(conn),
+            reopen,
+            policy,
+            timeout: None,
+            reconnects: 0,
+            pending: Vec::new(),
+            broken: None,
+            watching: false,
+        }
+    }
+
+    pub fn get_state(&self) -> String {
+        match self.reconnects {
+            0 => self.conn.get_state(),
+            reconnects => format!("{}, {} reconnect(s)", self.conn.get_state(), reconnects),
+        }
+    }
+
+    pub fn get_inner(&self) -> &Connection {
+        &self.conn
+    }
+
+    pub fn set_reply_timeout(&mut self, timeout: Option<Duration>) -> RedisResult<()> {
+        self.timeout = timeout;
+        self.conn.set_reply_timeout(timeout)
+    }
+
+    pub fn take_reconnects(&mut self) -> Vec<Reconnect> {
+        let mut reconnects = std::mem::take(&mut self.pending);
+        reconnects.extend(self.conn.take_reconnects());
+        reconnects
+    }
+
+    fn reopen(&mut self, attempt: &mut u32, reason: String) -> RedisResult<()> {
+        //! Replaces the broken connection, waiting the policy's backoff before each try.
+        loop {
+            thread::sleep(self.policy.get_delay(*attempt));
+            *attempt += 1;
+            let opened = (self.reopen)()
+                .and_then(|mut conn| conn.set_reply_timeout(self.timeout).map(|()| conn));
+            match opened {
+                Ok(conn) => {
+                    self.pending.extend(self.conn.take_reconnects());
+                    *self.conn = conn;
+                    self.reconnects += 1;
+                    self.watching = false;
+                    log::info!(
+                        target: "redis",
+                        "reconnected after {} attempt(s): {}",
+                        attempt, reason
+                    );
+                    return Ok(());
+                }
+                Err(err) if is_lost(&err) && *attempt < self.policy.max_retries => {}
+                Err(err) => {
+                    self.broken = Some(reason);
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    fn with_retry<T>(
+        &mut self,
+        names: Option<Vec<String>>,
+        mut request: impl FnMut(&mut dyn ConnectionLike) -> RedisResult<T>,
+    ) -> RedisResult<T> {
+        //! Sends `request` (made of the commands `names`, None if unknown), reopening the
+        //! connection first if it broke and resending it when that is safe.
+        let names = names.unwrap_or_default();
+        let resendable = !names.is_empty() && is_read_only(&names);
+        let mut attempt = 0;
+        loop {
+            if let Some(reason) = self.broken.take() {
+                self.reopen(&mut attempt, reason)?;
+            }
+            let resendable = resendable && !self.watching;
+            let result = request(self.conn.inner());
+            match &result {
+                Err(err) if is_lost(err) => {
+                    self.broken = Some(err.to_string());
+                    if (err.is_connection_refusal() || resendable)
+                        && attempt < self.policy.max_retries
+                    {
+                        continue;
+                    }
+                }
+                Err(_) => {}
+                Ok(_) if has_command(&names, &["EXEC", "DISCARD", "UNWATCH"]) => {
+                    self.watching = false
+                }
+                Ok(_) if has_command(&names, &["WATCH"]) => self.watching = true,
+                Ok(_) => {}
+            }
+            return result;
+        }
+    }
+}
+
+impl ConnectionLike for RetryingConnection {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+        self.with_retry(get_packed_names(cmd), |conn| conn.req_packed_command(cmd))
+    }
+
+    fn req_packed_commands(
+        &mut self,
+        cmd: &[u8],
+        offset: usize,
+        count: usize,
+    ) -> RedisResult<Vec<Value>> {
+        self.with_retry(get_packed_names(cmd), |conn| {
+            conn.req_packed_commands(cmd, offset, count)
+        })
+    }
+
+    fn req_command(&mut self, cmd: &Cmd) -> RedisResult<Value> {
+        self.with_retry(get_name(cmd).map(|name| vec![name]), |conn| {
+            conn.req_command(cmd)
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        self.conn.get_db()
+    }
+
+    fn supports_pipelining(&self) -> bool {
+        self.conn.supports_pipelining()
+    }
+
+    fn check_connection(&mut self) -> bool {
+        self.conn.check_connection()
+    }
+
+    fn is_open(&self) -> bool {
+        self.conn.is_open()
+    }
+}
@@ -0,0 +1,127 @@
+use std::{collections::VecDeque, marker::PhantomData};
+
+use crate::context_manager::ContextManager;
+
+use super::entity::RedisEntity;
+
+/// Keys requested per SCAN call unless a page size is given.
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// One SCAN step over an entity type's keys.
+#[derive(Debug)]
+pub struct Page<T> {
+    pub cursor: u64, // 0 once the scan has completed
+    pub entries: Vec<T>,
+}
+
+impl<T> Page<T> {
+    pub fn is_last(&self) -> bool {
+        self.cursor == 0
+    }
+}
+
+pub fn scan_keys(
+    pattern: &str,
+    cursor: u64,
+    page_size: usize,
+    ctx: &mut ContextManager,
+) -> Result<(u64, Vec<String>), redis::RedisError> {
+    //! Runs a single `SCAN` step, returning the next cursor and the keys it found.
+    //! `page_size` is a hint, Redis may return a few more or fewer keys.
+    redis::cmd("SCAN")
+        .arg(cursor)
+        .arg("MATCH")
+        .arg(pattern)
+        .arg("COUNT")
+        .arg(page_size)
+        .query(ctx.get_connection())
+}
+
+pub fn scan_all_keys(
+    pattern: &str,
+    ctx: &mut ContextManager,
+) -> Result<Vec<String>, redis::RedisError> {
+    //! Every key matching `pattern`, scanned `redis_conn.scan_count` keys at a time so a
+    //! large dataset never blocks redis the way `KEYS` does.
+    let page_size = ctx.get_config().get_scan_count();
+    let mut keys = Vec::new();
+    let mut cursor: u64 = 0;
+    loop {
+        let (next, page) = scan_keys(pattern, cursor, page_size, ctx)?;
+        keys.extend(page);
+        if next == 0 {
+            return Ok(keys);
+        }
+        cursor = next;
+    }
+}
+
+/// Lazily walks every entity of a type, holding at most one page of keys in memory.
+/// Entries are parsed as they are yielded; a parsing error doesn't end the walk.
+/// Failures carry the key that could not be read (the pattern if the scan itself failed).
+pub struct EntityIter<'a, T: RedisEntity> {
+    ctx: &'a mut ContextManager,
+    pattern: String,
+    page_size: usize,
+    cursor: u64,
+    keys: VecDeque<String>,
+    started: bool,
+    pages: usize,
+    yielded: usize,
+    entity: PhantomData<T>,
+}
+
+impl<'a, T: RedisEntity> EntityIter<'a, T> {
+    pub fn new(pattern: String, page_size: usize, ctx: &'a mut ContextManager) -> Self {
+        Self {
+            ctx,
+            pattern,
+            page_size: page_size.max(1),
+            cursor: 0,
+            keys: VecDeque::new(),
+            started: false,
+            pages: 0,
+            yielded: 0,
+            entity: PhantomData,
+        }
+    }
+
+    pub fn get_pages(&self) -> usize {
+        //! Number of SCAN calls made so far.
+        self.pages
+    }
+
+    pub fn get_yielded(&self) -> usize {
+        self.yielded
+    }
+
+    fn fetch_page(&mut self) -> Result<(), T::Error> {
+        let (cursor, keys) = scan_keys(&self.pattern, self.cursor, self.page_size, self.ctx)
+            .map_err(|err| format!("SCAN over {:?} failed: {:?}", self.pattern, err))?;
+        self.started = true;
+        self.pages += 1;
+        self.cursor = cursor;
+        self.keys.extend(keys);
+        Ok(())
+    }
+}
+
+impl<T: RedisEntity> Iterator for EntityIter<'_, T> {
+    type Item = Result<T, (String, T::Error)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.keys.is_empty() {
+            if self.started && self.cursor == 0 {
+                return None;
+            }
+            if let Err(err) = self.fetch_page() {
+                self.started = true; // don't retry a failing scan forever
+                self.cursor = 0;
+                return Some(Err((self.pattern.clone(), err)));
+            }
+        }
+        let key = self.keys.pop_front()?;
+        self.yielded += 1;
+        Some(T::get_by_key(&key, self.ctx).map_err(|err| (key, err)))
+    }
+}
@@ -0,0 +1,147 @@
+use redis::{
+    sentinel::{Sentinel, SentinelNodeConnectionInfo},
+    Cmd, ConnectionLike, ErrorKind, RedisConnectionInfo, RedisError, RedisResult, Value,
+};
+
+use crate::config::models::SentinelInfo;
+
+use super::{acl::AclUser, connection::Reconnect};
+
+/// Connection to the master the sentinels currently agree on. When the master goes away
+/// or has been demoted (READONLY replies), the sentinels are asked again and the failed
+/// command is retried once on the new master.
+pub struct FailoverConnection {
+    sentinel: Sentinel,
+    master_name: String,
+    user: Option<AclUser>, // logs in to the master as this user
+    conn: redis::Connection,
+    master: String, // address of the master `conn` is connected to
+    failovers: usize,
+    pending: Vec<Reconnect>,
+}
+
+fn needs_reconnect(err: &RedisError) -> bool {
+    err.is_io_error()
+        || err.is_connection_dropped()
+        || err.is_connection_refusal()
+        || err.kind() == ErrorKind::ReadOnly
+}
+
+fn connect_to_master(
+    sentinel: &mut Sentinel,
+    master_name: &str,
+    user: Option<&AclUser>,
+) -> RedisResult<(redis::Connection, String)> {
+    let node_info = user.map(|user| SentinelNodeConnectionInfo {
+        tls_mode: None,
+        redis_connection_info: Some(RedisConnectionInfo {
+            username: Some(user.username.clone()),
+            password: user.password.clone(),
+            ..Default::default()
+        }),
+    });
+    let client = sentinel.master_for(master_name, node_info.as_ref())?;
+    let master = client.get_connection_info().addr.to_string();
+    Ok((client.get_connection()?, master))
+}
+
+impl FailoverConnection {
+    pub fn new(info: &SentinelInfo, user: Option<&AclUser>) -> RedisResult<Self> {
+        let addresses: Vec<String> = info
+            .addresses
+            .iter()
+            .map(|address| format!("redis://{}", address))
+            .collect();
+        let mut sentinel = Sentinel::build(addresses)?;
+        let (conn, master) = connect_to_master(&mut sentinel, &info.master_name, user)?;
+        Ok(Self {
+            sentinel,
+            master_name: info.master_name.clone(),
+            user: user.cloned(),
+            conn,
+            master,
+            failovers: 0,
+            pending: Vec::new(),
+        })
+    }
+
+    pub fn get_state(&self) -> String {
+        format!(
+            "sentinel master {} at {}, {} failover(s)",
+            self.master_name, self.master, self.failovers
+        )
+    }
+
+    pub fn take_reconnects(&mut self) -> Vec<Reconnect> {
+        std::mem::take(&mut self.pending)
+    }
+
+    fn reconnect(&mut self, reason: &RedisError) -> RedisResult<()> {
+        let (conn, master) =
+            connect_to_master(&mut self.sentinel, &self.master_name, self.user.as_ref())?;
+        log::info!(
+            target: "redis",
+            "reconnected to {} master {} (was {}): {}",
+            self.master_name, master, self.master, reason
+        );
+        self.conn = conn;
+        if master != self.master {
+            self.failovers += 1;
+        }
+        self.pending.push(Reconnect {
+            master_name: self.master_name.clone(),
+            from: std::mem::replace(&mut self.master, master.clone()),
+            to: master,
+            reason: reason.to_string(),
+        });
+        Ok(())
+    }
+
+    fn with_failover<T>(
+        &mut self,
+        mut request: impl FnMut(&mut redis::Connection) -> RedisResult<T>,
+    ) -> RedisResult<T> {
+        match request(&mut self.conn) {
+            Err(err) if needs_reconnect(&err) => {
+                self.reconnect(&err)?;
+                request(&mut self.conn)
+            }
+            result => result,
+        }
+    }
+}
+
+impl ConnectionLike for FailoverConnection {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+        self.with_failover(|conn| conn.req_packed_command(cmd))
+    }
+
+    fn req_packed_commands(
+        &mut self,
+        cmd: &[u8],
+        offset: usize,
+        count: usize,
+    ) -> RedisResult<Vec<Value>> {
+        self.with_failover(|conn| conn.req_packed_commands(cmd, offset, count))
+    }
+
+    fn req_command(&mut self, cmd: &Cmd) -> RedisResult<Value> {
+        self.with_failover(|conn| conn.req_command(cmd))
+    }
+
+    fn get_db(&self) -> i64 {
+        self.conn.get_db()
+    }
+
+    fn supports_pipelining(&self) -> bool {
+        self.conn.supports_pipelining()
+    }
+
+    fn check_connection(&mut self) -> bool {
+        self.conn.check_connection()
+    }
+
+    fn is_open(&self) -> bool {
+        self.conn.is_open()
+    }
+}
@@ -0,0 +1,432 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+};
+
+use redis::{Arg, Cmd, ConnectionLike, ErrorKind, RedisError, RedisResult, Value};
+use serde::{Deserialize, Serialize};
+
+use crate::context_manager::ContextManager;
+
+use super::{bulk::BulkWrite, scan::scan_keys};
+
+/// Copy of the network's Redis data, saved as JSON by `backup` and loadable in place of Redis.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct Snapshot {
+    #[serde(default)]
+    pub strings: BTreeMap<String, String>,
+    #[serde(default)]
+    pub hashes: BTreeMap<String, BTreeMap<String, String>>,
+    #[serde(default)]
+    pub sets: BTreeMap<String, BTreeSet<String>>,
+    #[serde(default)]
+    pub lists: BTreeMap<String, Vec<String>>,
+}
+
+fn snapshot_error(desc: &'static str, detail: String) -> RedisError {
+    (ErrorKind::ClientError, desc, detail).into()
+}
+
+fn data(value: &str) -> Value {
+    Value::Data(value.as_bytes().to_vec())
+}
+
+fn bulk<'a>(values: impl IntoIterator<Item = &'a String>) -> Value {
+    Value::Bulk(values.into_iter().map(|value| data(value)).collect())
+}
+
+pub fn matches_pattern(pattern: &str, key: &str) -> bool {
+    //! Redis glob matching, limited to `*` and `?`.
+    let (pattern, key): (Vec<char>, Vec<char>) = (pattern.chars().collect(), key.chars().collect());
+    let (mut p, mut k) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while k < key.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, k));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == key[k] => {
+                p += 1;
+                k += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    k = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+impl Snapshot {
+    pub fn load(path: &str) -> Result<Self, RedisError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| snapshot_error("Snapshot could not be read", err.to_string()))?;
+        serde_json::from_str(&contents)
+            .map_err(|err| snapshot_error("Snapshot could not be parsed", err.to_string()))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), RedisError> {
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|err| snapshot_error("Snapshot could not be serialized", err.to_string()))?;
+        fs::write(path, contents)
+            .map_err(|err| snapshot_error("Snapshot could not be written", err.to_string()))
+    }
+
+    pub fn to_writes(&self) -> Vec<BulkWrite> {
+        //! The writes that put every key of the snapshot back into redis.
+        let strings = self
+            .strings
+            .iter()
+            .map(|(key, value)| BulkWrite::String(key.clone(), value.clone()));
+        let hashes = self
+            .hashes
+            .iter()
+            .map(|(key, map)| BulkWrite::Hash(key.clone(), map.clone().into_iter().collect()));
+        let sets = self
+            .sets
+            .iter()
+            .map(|(key, members)| BulkWrite::Set(key.clone(), members.iter().cloned().collect()));
+        let lists = self
+            .lists
+            .iter()
+            .map(|(key, values)| BulkWrite::List(key.clone(), values.clone()));
+        strings.chain(hashes).chain(sets).chain(lists).collect()
+    }
+
+    pub fn capture(ctx: &mut ContextManager) -> Result<Self, RedisError> {
+        //! Copies every string, hash, set and list key from the current connection.
+        Self::capture_matching(&["*".into()], ctx)
+    }
+
+    pub fn capture_matching(
+        patterns: &[String],
+        ctx: &mut ContextManager,
+    ) -> Result<Self, RedisError> {
+        //! `capture`, limited to the keys matching any of `patterns`.
+        let mut snapshot = Self::default();
+        for pattern in patterns {
+            snapshot.capture_pattern(pattern, ctx)?;
+        }
+        Ok(snapshot)
+    }
+
+    fn capture_pattern(
+        &mut self,
+        pattern: &str,
+        ctx: &mut ContextManager,
+    ) -> Result<(), RedisError> {
+        let page_size = ctx.get_config().get_scan_count();
+        let mut cursor: u64 = 0;
+        loop {
+            let (next, keys) = scan_keys(pattern, cursor, page_size, ctx)?;
+            for key in keys {
+                let kind: String = redis::cmd("TYPE").arg(&key).query(ctx.get_connection())?;
+                let conn = ctx.get_connection();
+                match kind.as_str() {
+                    "string" => {
+                        self.strings
+                            .insert(key.clone(), redis::cmd("GET").arg(&key).query(conn)?);
+                    }
+                    "hash" => {
+                        self.hashes
+                            .insert(key.clone(), redis::cmd("HGETALL").arg(&key).query(conn)?);
+                    }
+                    "set" => {
+                        self.sets
+                            .insert(key.clone(), redis::cmd("SMEMBERS").arg(&key).query(conn)?);
+                    }
+                    "list" => {
+                        self.lists.insert(
+                            key.clone(),
+                            redis::cmd("LRANGE").arg(&key).arg(0).arg(-1).query(conn)?,
+                        );
+                    }
+                    _ => (), // streams, zsets etc. aren't used by the network
+                }
+            }
+            if next == 0 {
+                return Ok(());
+            }
+            cursor = next;
+        }
+    }
+
+    fn get_keys(&self) -> BTreeSet<&String> {
+        self.strings
+            .keys()
+            .chain(self.hashes.keys())
+            .chain(self.sets.keys())
+            .chain(self.lists.keys())
+            .collect()
+    }
+
+    fn remove(&mut self, key: &str) -> bool {
+        self.strings.remove(key).is_some()
+            | self.hashes.remove(key).is_some()
+            | self.sets.remove(key).is_some()
+            | self.lists.remove(key).is_some()
+    }
+
+    fn get_type(&self, key: &str) -> &'static str {
+        if self.strings.contains_key(key) {
+            "string"
+        } else if self.hashes.contains_key(key) {
+            "hash"
+        } else if self.sets.contains_key(key) {
+            "set"
+        } else if self.lists.contains_key(key) {
+            "list"
+        } else {
+            "none"
+        }
+    }
+
+    pub fn execute(&mut self, args: &[String]) -> RedisResult<Value> {
+        //! Answers a command from the snapshot. Writes only change the in-memory copy.
+        let Some((name, args)) = args.split_first() else {
+            return Err(snapshot_error("Empty command", String::new()));
+        };
+        let arg = |i: usize| -> RedisResult<&String> {
+            args.get(i).ok_or(snapshot_error(
+                "Missing command argument",
+                format!("{} expects argument {}", name, i + 1),
+            ))
+        };
+        let index = |i: usize| -> RedisResult<isize> {
+            arg(i)?.parse().map_err(|_| {
+                snapshot_error("Invalid integer argument", format!("{:?}", args.get(i)))
+            })
+        };
+        Ok(match name.to_uppercase().as_str() {
+            "PING" => Value::Status("PONG".into()),
+            "GET" => self.strings.get(arg(0)?).map_or(Value::Nil, |v| data(v)),
+            "SET" => {
+                let key = arg(0)?.clone();
+                self.remove(&key);
+                self.strings.insert(key, arg(1)?.clone());
+                Value::Okay
+            }
+            "DEL" => Value::Int(args.iter().filter(|key| self.remove(key)).count() as i64),
+            "EXISTS" => Value::Int(
+                args.iter()
+                    .filter(|key| self.get_type(key) != "none")
+                    .count() as i64,
+            ),
+            "TYPE" => Value::Status(self.get_type(arg(0)?).into()),
+            "KEYS" => bulk(
+                self.get_keys()
+                    .into_iter()
+                    .filter(|key| matches_pattern(arg(0).map_or("*", |p| p), key)),
+            ),
+            "SCAN" => {
+                // the whole keyspace fits in memory, so a single page ends the scan
+                let pattern = args
+                    .iter()
+                    .position(|a| a.eq_ignore_ascii_case("MATCH"))
+                    .and_then(|i| args.get(i + 1))
+                    .map_or("*", |p| p.as_str());
+                Value::Bulk(vec![
+                    data("0"),
+                    bulk(
+                        self.get_keys()
+                            .into_iter()
+                            .filter(|key| matches_pattern(pattern, key)),
+                    ),
+                ])
+            }
+            "HGETALL" => Value::Bulk(
+                self.hashes
+                    .get(arg(0)?)
+                    .map(|hash| {
+                        hash.iter()
+                            .flat_map(|(field, value)| [data(field), data(value)])
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            ),
+            "HKEYS" => bulk(self.hashes.get(arg(0)?).into_iter().flat_map(|h| h.keys())),
+            "HVALS" => bulk(
+                self.hashes
+                    .get(arg(0)?)
+                    .into_iter()
+                    .flat_map(|h| h.values()),
+            ),
+            "HGET" => self
+                .hashes
+                .get(arg(0)?)
+                .and_then(|hash| hash.get(arg(1).ok()?))
+                .map_or(Value::Nil, |v| data(v)),
+            "HSET" => {
+                let hash = self.hashes.entry(arg(0)?.clone()).or_default();
+                let mut added = 0;
+                for pair in args[1..].chunks(2) {
+                    if let [field, value] = pair {
+                        added += hash.insert(field.clone(), value.clone()).is_none() as i64;
+                    }
+                }
+                Value::Int(added)
+            }
+            "HINCRBY" => {
+                let by = index(2)? as i64;
+                let hash = self.hashes.entry(arg(0)?.clone()).or_default();
+                let value = match hash.get(arg(1)?) {
+                    Some(value) => value.parse::<i64>().map_err(|_| {
+                        snapshot_error("Hash value is not an integer", value.clone())
+                    })?,
+                    None => 0,
+                } + by;
+                hash.insert(arg(1)?.clone(), value.to_string());
+                Value::Int(value)
+            }
+            "HDEL" => {
+                let hash = self.hashes.entry(arg(0)?.clone()).or_default();
+                Value::Int(
+                    args[1..]
+                        .iter()
+                        .filter(|field| hash.remove(*field).is_some())
+                        .count() as i64,
+                )
+            }
+            "SADD" => {
+                let set = self.sets.entry(arg(0)?.clone()).or_default();
+                Value::Int(
+                    args[1..]
+                        .iter()
+                        .filter(|m| set.insert((*m).clone()))
+                        .count() as i64,
+                )
+            }
+            "SREM" => {
+                let set = self.sets.entry(arg(0)?.clone()).or_default();
+                Value::Int(args[1..].iter().filter(|m| set.remove(*m)).count() as i64)
+            }
+            "SMEMBERS" => bulk(self.sets.get(arg(0)?).into_iter().flatten()),
+            "SISMEMBER" => Value::Int(
+                self.sets
+                    .get(arg(0)?)
+                    .is_some_and(|set| set.contains(arg(1).map_or("", |m| m)))
+                    as i64,
+            ),
+            "LPUSH" | "RPUSH" => {
+                let front = name.eq_ignore_ascii_case("LPUSH");
+                let list = self.lists.entry(arg(0)?.clone()).or_default();
+                for value in args[1..].iter() {
+                    match front {
+                        true => list.insert(0, value.clone()),
+                        false => list.push(value.clone()),
+                    }
+                }
+                Value::Int(list.len() as i64)
+            }
+            "LRANGE" | "LTRIM" => {
+                let (start, stop) = (index(1)?, index(2)?);
+                let list = self.lists.entry(arg(0)?.clone()).or_default();
+                let len = list.len() as isize;
+                let resolve = |i: isize| if i < 0 { (len + i).max(0) } else { i.min(len) };
+                let (from, to) = (resolve(start), (resolve(stop) + 1).min(len));
+                let range: Vec<String> = match from < to {
+                    true => list[from as usize..to as usize].to_vec(),
+                    false => Vec::new(),
+                };
+                if name.eq_ignore_ascii_case("LTRIM") {
+                    *list = range;
+                    Value::Okay
+                } else {
+                    bulk(range.iter())
+                }
+            }
+            "LLEN" => Value::Int(self.lists.get(arg(0)?).map_or(0, |list| list.len()) as i64),
+            "LPOP" => match self.lists.get_mut(arg(0)?) {
+                Some(list) if !list.is_empty() => data(&list.remove(0)),
+                _ => Value::Nil,
+            },
+            "LREM" => {
+                // removes up to `count` matches from the head (from the tail if negative, all if 0)
+                let count = index(1)?;
+                let value = arg(2)?;
+                let list = self.lists.entry(arg(0)?.clone()).or_default();
+                let mut positions: Vec<usize> =
+                    (0..list.len()).filter(|&i| &list[i] == value).collect();
+                if count < 0 {
+                    positions.reverse();
+                }
+                if count != 0 {
+                    positions.truncate(count.unsigned_abs());
+                }
+                positions.sort_unstable();
+                for &i in positions.iter().rev() {
+                    list.remove(i);
+                }
+                Value::Int(positions.len() as i64)
+            }
+            "PUBLISH" => Value::Int(0), // nobody is listening offline
+            "EXPIRE" => Value::Int((self.get_type(arg(0)?) != "none") as i64), // keys never expire offline
+            _ => {
+                return Err(snapshot_error(
+                    "Command not supported in snapshot mode",
+                    name.clone(),
+                ))
+            }
+        })
+    }
+}
+
+/// Answers commands from a `Snapshot` instead of a Redis server.
+#[derive(Clone, Debug, Default)]
+pub struct SnapshotConnection {
+    pub snapshot: Snapshot,
+}
+
+impl ConnectionLike for SnapshotConnection {
+    fn req_packed_command(&mut self, _cmd: &[u8]) -> RedisResult<Value> {
+        Err(snapshot_error(
+            "Packed commands are not supported in snapshot mode",
+            String::new(),
+        ))
+    }
+
+    fn req_packed_commands(
+        &mut self,
+        _cmd: &[u8],
+        _offset: usize,
+        _count: usize,
+    ) -> RedisResult<Vec<Value>> {
+        Err(snapshot_error(
+            "Pipelines are not supported in snapshot mode",
+            String::new(),
+        ))
+    }
+
+    fn req_command(&mut self, cmd: &Cmd) -> RedisResult<Value> {
+        let args: Vec<String> = cmd
+            .args_iter()
+            .map(|arg| match arg {
+                Arg::Simple(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+                Arg::Cursor => "0".into(),
+            })
+            .collect();
+        self.snapshot.execute(&args)
+    }
+
+    fn get_db(&self) -> i64 {
+        0
+    }
+
+    fn supports_pipelining(&self) -> bool {
+        false
+    }
+
+    fn check_connection(&mut self) -> bool {
+        true
+    }
+
+    fn is_open(&self) -> bool {
+        true
+    }
+}
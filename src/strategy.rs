@@ -0,0 +1,289 @@
+use std::{collections::HashMap, fmt::Display};
+
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    context_manager::ContextManager,
+    queue,
+    region::Region,
+    server::{
+        dedicated::server::DedicatedServer, minecraft::MinecraftServer, server_group::ServerGroup,
+    },
+    store::entity::RedisEntity,
+};
+
+/// Decides which node a new instance of a group goes to.
+/// Implement it to plug in custom policies (pricing- or latency-aware placement, ...)
+/// and register it with `ContextManager::set_placement_strategy`.
+pub trait PlacementStrategy: Send + Sync {
+    fn get_name(&self) -> &str;
+
+    /// Index into `candidates` of the node to use, or None to place nothing.
+    /// Candidates are already in the group's region and have room for one more instance.
+    fn choose_node(&self, group: &ServerGroup, candidates: &[&DedicatedServer]) -> Option<usize>;
+}
+
+/// Decides how many instances a group should run.
+/// Register custom policies with `ContextManager::set_scaling_strategy`.
+pub trait ScalingStrategy: Send + Sync {
+    fn get_name(&self) -> &str;
+
+    /// Desired instance count given the group's live statuses.
+    fn get_desired_count(&self, group: &ServerGroup, statuses: &[MinecraftServer]) -> usize;
+}
+
+/// Built-in placement: the node running the fewest instances of the group, preferring
+/// nodes with the most free resources on ties.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SpreadPlacement;
+
+impl PlacementStrategy for SpreadPlacement {
+    fn get_name(&self) -> &str {
+        "spread"
+    }
+
+    fn choose_node(&self, group: &ServerGroup, candidates: &[&DedicatedServer]) -> Option<usize> {
+        let mut best: Option<usize> = None;
+        for (i, ds) in candidates.iter().enumerate() {
+            let better = best.is_none_or(|best| {
+                let best = candidates[best];
+                ds.get_server_count(group) < best.get_server_count(group)
+                    || (ds.get_server_count(group) == best.get_server_count(group) && *ds > best)
+            });
+            if better {
+                best = Some(i);
+            }
+        }
+        best
+    }
+}
+
+/// Built-in scaling: at least `totalServers` instances, plus enough to keep
+/// `joinableServers` of them open for players.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JoinableScaling;
+
+impl ScalingStrategy for JoinableScaling {
+    fn get_name(&self) -> &str {
+        "joinable"
+    }
+
+    fn get_desired_count(&self, group: &ServerGroup, statuses: &[MinecraftServer]) -> usize {
+        let joinable = statuses
+            .iter()
+            .filter(|server| server.is_joinable())
+            .count();
+        let unjoinable = statuses.len() - joinable;
+        (group.total_servers as usize).max(unjoinable + group.joinable_servers as usize)
+    }
+}
+
+/// Time of day as "HH:MM".
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeOfDay(u32); // minutes since midnight
+
+/// Fixed offset from UTC as "+HH:MM" or "-HH:MM". Daylight saving isn't applied.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct UtcOffset(i32); // seconds east of UTC
+
+/// Desired counts are multiplied by `multiplier` from `start` until `end` (local time).
+/// Windows may wrap past midnight.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct PeakWindow {
+    pub start: TimeOfDay,
+    pub end: TimeOfDay,
+    pub multiplier: f64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct RegionSchedule {
+    #[serde(default)]
+    pub utc_offset: UtcOffset,
+    #[serde(default)]
+    pub peaks: Vec<PeakWindow>,
+}
+
+/// Per-region peak hours, applied on top of the scaling strategy's desired counts.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct ScalingInfo {
+    #[serde(default)]
+    pub regions: HashMap<Region, RegionSchedule>,
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let (hours, minutes) = value
+            .split_once(':')
+            .and_then(|(hours, minutes)| {
+                Some((hours.parse::<u32>().ok()?, minutes.parse::<u32>().ok()?))
+            })
+            .filter(|&(hours, minutes)| {
+                hours <= 24 && minutes < 60 && hours * 60 + minutes <= 24 * 60
+            })
+            .ok_or(format!("invalid time of day {:?} (expected HH:MM)", value))?;
+        Ok(Self(hours * 60 + minutes))
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(time: TimeOfDay) -> Self {
+        format!("{:02}:{:02}", time.0 / 60, time.0 % 60)
+    }
+}
+
+impl TryFrom<String> for UtcOffset {
+    type Error = String;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("invalid UTC offset {:?} (expected +HH:MM or -HH:MM)", value);
+        let (sign, rest) = match value.split_at_checked(1) {
+            Some(("+", rest)) => (1, rest),
+            Some(("-", rest)) => (-1, rest),
+            _ => return Err(invalid()),
+        };
+        let time = TimeOfDay::try_from(rest.to_string()).map_err(|_| invalid())?;
+        Ok(Self(sign * time.0 as i32 * 60))
+    }
+}
+
+impl From<UtcOffset> for String {
+    fn from(offset: UtcOffset) -> Self {
+        let sign = if offset.0 < 0 { '-' } else { '+' };
+        let minutes = offset.0.unsigned_abs() / 60;
+        format!("{}{}", sign, String::from(TimeOfDay(minutes)))
+    }
+}
+
+impl TimeOfDay {
+    pub fn of(local: &NaiveDateTime) -> Self {
+        Self(local.hour() * 60 + local.minute())
+    }
+
+    pub fn is_within(self, start: Self, end: Self) -> bool {
+        //! In `start..end`, which wraps past midnight if `end` isn't after `start`.
+        match start <= end {
+            true => start <= self && self < end,
+            false => self >= start || self < end,
+        }
+    }
+}
+
+impl UtcOffset {
+    pub fn to_local(self, now: DateTime<Utc>) -> NaiveDateTime {
+        FixedOffset::east_opt(self.0).map_or(now.naive_utc(), |offset| {
+            now.with_timezone(&offset).naive_local()
+        })
+    }
+}
+
+impl PeakWindow {
+    pub fn contains(&self, time: TimeOfDay) -> bool {
+        time.is_within(self.start, self.end)
+    }
+}
+
+impl ScalingInfo {
+    pub fn get_multiplier(&self, region: &Region, now: DateTime<Utc>) -> f64 {
+        //! Largest multiplier of the region's peak windows active at `now`, 1 outside of them.
+        let Some(schedule) = self.regions.get(region) else {
+            return 1.0;
+        };
+        let time = TimeOfDay::of(&schedule.utc_offset.to_local(now));
+        schedule
+            .peaks
+            .iter()
+            .filter(|peak| peak.contains(time))
+            .map(|peak| peak.multiplier)
+            .fold(1.0, f64::max)
+    }
+}
+
+/// A group's live instance count next to what the scaling strategy wants.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct ScalingDecision {
+    pub group: String,
+    pub region: Region,
+    pub running: usize,
+    pub desired: usize,
+    pub multiplier: f64, // regional peak multiplier already applied to `desired`
+    pub queued: usize,   // players waiting for the group, see `queue`
+    pub ram: u16,        // per instance, in MB
+    pub cpu: u8,         // per instance
+}
+
+impl Display for ScalingDecision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} running, {} desired",
+            self.group, self.running, self.desired
+        )?;
+        if self.multiplier != 1.0 {
+            write!(f, " (peak x{})", self.multiplier)?;
+        }
+        if self.queued > 0 {
+            write!(f, " ({} queued)", self.queued)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn plan_scaling(ctx: &mut ContextManager) -> Vec<ScalingDecision> {
+    //! Asks the registered scaling strategy about every readable group, then scales its
+    //! answer by the group region's current peak multiplier (rounding up). A group whose
+    //! instances are all full gets enough extra instances for its queued players, and
+    //! groups with players queued come first.
+    plan_scaling_where(|_| true, ctx)
+}
+
+pub fn plan_scaling_where(
+    include: impl Fn(&ServerGroup) -> bool,
+    ctx: &mut ContextManager,
+) -> Vec<ScalingDecision> {
+    //! `plan_scaling` for the groups `include` accepts, e.g. one region's.
+    plan_scaling_at(include, Utc::now(), ctx)
+}
+
+pub fn plan_scaling_at(
+    include: impl Fn(&ServerGroup) -> bool,
+    now: DateTime<Utc>,
+    ctx: &mut ContextManager,
+) -> Vec<ScalingDecision> {
+    //! `plan_scaling_where` with peak hours looked up at `now`, e.g. to replay a recorded
+    //! reconcile.
+    let groups = ServerGroup::get_all(ctx).ok;
+    let mut decisions: Vec<ScalingDecision> = groups
+        .iter()
+        .filter(|group| include(group))
+        .map(|group| {
+            let statuses = MinecraftServer::from_server_group(group, ctx).ok;
+            let desired = ctx
+                .get_scaling_strategy()
+                .get_desired_count(group, &statuses);
+            let multiplier = ctx.get_config().scaling.get_multiplier(&group.region, now);
+            let queued = queue::get_length(&group.prefix, ctx).unwrap_or_default();
+            let full = statuses
+                .iter()
+                .all(|server| server.get_player_count() >= server.get_max_player_count());
+            let extra = match full {
+                true => queued.div_ceil(group.max_players.max(1) as usize),
+                false => 0,
+            };
+            ScalingDecision {
+                group: group.prefix.clone(),
+                region: group.region.clone(),
+                running: statuses.len(),
+                desired: (desired as f64 * multiplier).ceil() as usize + extra,
+                multiplier,
+                queued,
+                ram: group.ram,
+                cpu: group.cpu,
+            }
+        })
+        .collect();
+    decisions.sort_by_key(|decision| std::cmp::Reverse(decision.queued));
+    decisions
+}
@@ -0,0 +1,152 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    context_manager::ContextManager,
+    events::{Event, EventKind},
+    monitor::services,
+    server::server_group::ServerGroup,
+    store::entity::RedisEntity,
+};
+
+/// Events looked through for something to undo.
+const UNDO_DEPTH: usize = 200;
+
+#[derive(Error, Debug)]
+pub enum UndoError {
+    #[error("Undo Error: Nothing to undo")]
+    NothingToUndo,
+    #[error("Undo Error: Irreversible: `{0}`")]
+    Irreversible(String),
+    #[error("Undo Error: Changed since: `{0}`")]
+    Conflict(String),
+    #[error("Undo Error: Refused: `{0}`")]
+    Refused(String),
+    #[error("Undo Error: Redis Error: `{0}`")]
+    RedisError(String),
+}
+
+/// Group hash fields an operation changed, with their values before and after.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct GroupChange {
+    pub group: String,
+    pub before: BTreeMap<String, String>,
+    pub after: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub undoes: Option<i64>, // timestamp of the event this change reverted
+}
+
+impl Display for GroupChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields: Vec<String> = self
+            .after
+            .iter()
+            .map(|(field, after)| {
+                let before = self.before.get(field).map_or("", |before| before.as_str());
+                format!("{}: {:?} -> {:?}", field, before, after)
+            })
+            .collect();
+        write!(f, "{}", fields.join(", "))
+    }
+}
+
+impl GroupChange {
+    pub fn between(before: &ServerGroup, after: &ServerGroup) -> Option<Self> {
+        //! The fields that differ between two versions of a group, None if none do.
+        let mut change = Self {
+            group: after.prefix.clone(),
+            ..Default::default()
+        };
+        for diff in before.diff(after) {
+            change.before.insert(diff.name.clone(), diff.left);
+            change.after.insert(diff.name, diff.right);
+        }
+        (!change.after.is_empty()).then_some(change)
+    }
+
+    pub fn emit(self, message: &str, ctx: &mut ContextManager) {
+        //! Records the change as a GroupUpdated event, which is what `undo` reverts.
+        let message = format!("{} ({})", message, self);
+        let mut event = Event::new(EventKind::GroupUpdated, &self.group.clone(), message);
+        event.change = Some(self);
+        event.emit(ctx);
+    }
+
+    fn get_inverse(&self, undoes: i64) -> Self {
+        Self {
+            group: self.group.clone(),
+            before: self.after.clone(),
+            after: self.before.clone(),
+            undoes: Some(undoes),
+        }
+    }
+}
+
+fn is_mutation(event: &Event) -> bool {
+    event.change.is_some() || event.kind == EventKind::InstanceRemoved
+}
+
+pub fn get_undoable(ctx: &mut ContextManager) -> Result<Vec<Event>, UndoError> {
+    //! Recent mutations not undone yet, newest first. Undos themselves are left out.
+    let events =
+        Event::get_recent(UNDO_DEPTH, ctx).map_err(|err| UndoError::RedisError(err.to_string()))?;
+    let undos: Vec<GroupChange> = events
+        .iter()
+        .filter_map(|event| event.change.clone())
+        .filter(|change| change.undoes.is_some())
+        .collect();
+    Ok(events
+        .into_iter()
+        .filter(is_mutation)
+        .filter(|event| {
+            event.change.as_ref().is_none_or(|change| {
+                change.undoes.is_none() && !undos.contains(&change.get_inverse(event.timestamp))
+            })
+        })
+        .collect())
+}
+
+pub fn undo(
+    event: &Event,
+    force: bool,
+    ctx: &mut ContextManager,
+) -> Result<GroupChange, UndoError> {
+    //! Writes back the fields `event` changed. Refused if the group has been changed
+    //! again since (unless `force`), and for operations that can't be reverted.
+    let Some(change) = event.change.as_ref() else {
+        return Err(UndoError::Irreversible(format!(
+            "{} {} can't be undone (the instance was killed)",
+            event.kind, event.subject
+        )));
+    };
+    let group = ServerGroup::from_str(&change.group, ctx)
+        .map_err(|err| UndoError::RedisError(err.to_string()))?;
+    let mut map = group.to_hashmap();
+    let drifted: Vec<&String> = change
+        .after
+        .iter()
+        .filter(|(field, value)| map.get(*field) != Some(value))
+        .map(|(field, _)| field)
+        .collect();
+    if !drifted.is_empty() && !force {
+        return Err(UndoError::Conflict(format!(
+            "servergroups.{} fields {:?} were changed again, pass --force to overwrite them",
+            change.group, drifted
+        )));
+    }
+    map.extend(change.before.clone());
+    let reverted =
+        ServerGroup::from_hashmap(map).map_err(|err| UndoError::RedisError(err.to_string()))?;
+    if !force {
+        services::ensure_rewards_allowed(&group, &reverted, ctx)
+            .map_err(|err| UndoError::Refused(format!("{}, pass --force to undo anyway", err)))?;
+    }
+    reverted
+        .save(ctx)
+        .map_err(|err| UndoError::RedisError(err.to_string()))?;
+    let inverse = change.get_inverse(event.timestamp);
+    inverse.clone().emit("undo", ctx);
+    Ok(inverse)
+}
@@ -0,0 +1,233 @@
+use std::{fs, path::PathBuf};
+
+use chrono::Local;
+use plex_redis_manager::{
+    apply::{self, ApplyError, NetworkSpec},
+    config::models::Config,
+    context_manager::ContextManager,
+    region::Region,
+    server::{
+        ensure::EnsureOutcome,
+        minecraft::{status_to_json, MinecraftServer},
+        presets::{Preset, SizeTier},
+        server_group::ServerGroup,
+    },
+    store::entity::RedisEntity,
+};
+
+const TOML_SPEC: &str = r#"
+[[groups]]
+name = "CW4"
+game = "CakeWars4"
+total_servers = 4
+joinable_servers = 2
+fields = { maxPlayers = "16" }
+
+[nodes.node1]
+labels = { tier = "high-mem" }
+"#;
+
+const YAML_SPEC: &str = r#"
+groups:
+  - name: CW4
+    game: CakeWars4
+    total_servers: 4
+    joinable_servers: 2
+    fields: { maxPlayers: "16" }
+nodes:
+  node1:
+    labels: { tier: high-mem }
+"#;
+
+struct SpecDir {
+    dir: PathBuf,
+}
+
+impl Drop for SpecDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+impl SpecDir {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("plex_apply_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).expect("temp dir should be writable");
+        Self { dir }
+    }
+
+    fn write(&self, file: &str, contents: &str) -> String {
+        let path = self.dir.join(file);
+        fs::write(&path, contents).expect("spec should be writable");
+        path.to_string_lossy().into()
+    }
+}
+
+const PRESET_SPEC: &str = r#"
+[[groups]]
+name = "Micro"
+preset = "Arcade"
+region = "EU"
+total_servers = 3
+joinable_servers = 1
+"#;
+
+fn offline_context(dir: &SpecDir) -> ContextManager {
+    //! A context on an empty snapshot.
+    let snapshot = dir.write("snapshot.json", "{}");
+    let mut config = Config::default();
+    config.set_snapshot(Some(snapshot));
+    ContextManager::try_from_config(&config).expect("snapshot should load")
+}
+
+fn save_group(prefix: &str, live: bool, ctx: &mut ContextManager) -> ServerGroup {
+    //! Caches a group that isn't in `PRESET_SPEC`, with instance 1 reporting when `live`.
+    let group = Preset::Arcade.to_server_group(prefix, Region::US, SizeTier::S);
+    group.save(ctx).expect("group should be saved");
+    if live {
+        let server = MinecraftServer::synthetic(&group, 1, Local::now());
+        let key = ctx
+            .get_keys()
+            .status_key(&group.region.to_string(), server.get_name());
+        redis::cmd("SET")
+            .arg(key)
+            .arg(status_to_json(server.to_map()))
+            .query::<()>(ctx.get_connection())
+            .unwrap();
+    }
+    group
+}
+
+fn assert_cw4(spec: &NetworkSpec) {
+    assert_eq!(spec.groups.len(), 1);
+    let group = &spec.groups[0];
+    assert_eq!(group.name, "CW4");
+    assert_eq!(group.game.as_deref(), Some("CakeWars4"));
+    assert_eq!((group.total_servers, group.joinable_servers), (4, 2));
+    assert_eq!(
+        group.fields.get("maxPlayers").map(String::as_str),
+        Some("16")
+    );
+    let labels = &spec.nodes["node1"].labels;
+    assert_eq!(labels.get("tier").map(String::as_str), Some("high-mem"));
+}
+
+#[test]
+fn specs_are_read_by_extension() {
+    let dir = SpecDir::new("formats");
+    assert_cw4(&NetworkSpec::load(&dir.write("network.toml", TOML_SPEC)).unwrap());
+    assert_cw4(&NetworkSpec::load(&dir.write("network.yaml", YAML_SPEC)).unwrap());
+    assert_cw4(&NetworkSpec::load(&dir.write("network.yml", YAML_SPEC)).unwrap());
+    // anything else is TOML
+    assert_cw4(&NetworkSpec::load(&dir.write("network", TOML_SPEC)).unwrap());
+}
+
+#[test]
+fn specs_in_the_wrong_format_are_refused() {
+    let dir = SpecDir::new("mismatch");
+    for path in [
+        dir.write("network.yaml", TOML_SPEC),
+        dir.write("network.toml", YAML_SPEC),
+        dir.write("broken.yml", "groups: [{ name: CW4"),
+    ] {
+        let err = NetworkSpec::load(&path).unwrap_err();
+        assert!(matches!(err, ApplyError::ReadError(_)), "{}: {}", path, err);
+    }
+    let missing = dir.dir.join("missing.yaml");
+    assert!(matches!(
+        NetworkSpec::load(&missing.to_string_lossy()),
+        Err(ApplyError::ReadError(_))
+    ));
+}
+
+#[test]
+fn applying_twice_changes_nothing_the_second_time() {
+    let dir = SpecDir::new("twice");
+    let mut ctx = offline_context(&dir);
+    let spec = NetworkSpec::load(&dir.write("network.toml", PRESET_SPEC)).unwrap();
+
+    let plan = apply::apply(&spec, false, false, false, true, &mut ctx).unwrap();
+    assert!(matches!(plan.groups[..], [(_, EnsureOutcome::Created)]));
+    assert!(
+        !ServerGroup::exists("Micro", &mut ctx),
+        "dry runs write nothing"
+    );
+
+    let report = apply::apply(&spec, false, false, false, false, &mut ctx).unwrap();
+    assert!(report.is_success());
+    assert!(matches!(report.groups[..], [(_, EnsureOutcome::Created)]));
+    let group = ServerGroup::get("Micro", &mut ctx).unwrap();
+    assert_eq!(group.region, Region::EU);
+    assert_eq!((group.total_servers, group.joinable_servers), (3, 1));
+
+    let report = apply::apply(&spec, false, false, false, false, &mut ctx).unwrap();
+    assert!(matches!(report.groups[..], [(_, EnsureOutcome::Unchanged)]));
+}
+
+#[test]
+fn undeclared_groups_are_only_removed_when_pruning() {
+    let dir = SpecDir::new("prune");
+    let mut ctx = offline_context(&dir);
+    let spec = NetworkSpec::load(&dir.write("network.toml", PRESET_SPEC)).unwrap();
+    save_group("Old", false, &mut ctx);
+
+    let report = apply::apply(&spec, false, false, false, false, &mut ctx).unwrap();
+    assert!(report.pruned.is_empty());
+    assert!(ServerGroup::exists("Old", &mut ctx));
+
+    let plan = apply::apply(&spec, true, false, false, true, &mut ctx).unwrap();
+    assert_eq!(plan.pruned, ["Old"]);
+    assert!(
+        ServerGroup::exists("Old", &mut ctx),
+        "dry runs prune nothing"
+    );
+
+    let report = apply::apply(&spec, true, false, false, false, &mut ctx).unwrap();
+    assert_eq!(report.pruned, ["Old"]);
+    assert!(!ServerGroup::exists("Old", &mut ctx));
+    assert!(ServerGroup::exists("Micro", &mut ctx));
+}
+
+#[test]
+fn live_groups_are_only_pruned_with_force() {
+    let dir = SpecDir::new("live");
+    let mut ctx = offline_context(&dir);
+    let spec = NetworkSpec::load(&dir.write("network.toml", PRESET_SPEC)).unwrap();
+    save_group("Live", true, &mut ctx);
+
+    let report = apply::apply(&spec, true, false, false, false, &mut ctx).unwrap();
+    assert!(!report.is_success());
+    assert!(report.pruned.is_empty());
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].0, "Live");
+    assert!(
+        report.failed[0].1.contains("requires force"),
+        "{}",
+        report.failed[0].1
+    );
+    assert!(ServerGroup::exists("Live", &mut ctx));
+
+    let report = apply::apply(&spec, true, true, false, false, &mut ctx).unwrap();
+    assert!(report.is_success());
+    assert_eq!(report.pruned, ["Live"]);
+    assert!(!ServerGroup::exists("Live", &mut ctx));
+}
+
+#[test]
+fn invalid_specs_apply_nothing() {
+    let dir = SpecDir::new("invalid");
+    let mut ctx = offline_context(&dir);
+    // more joinable than total servers
+    let bad = r#"
+[[groups]]
+name = "Bad"
+preset = "Arcade"
+total_servers = 1
+joinable_servers = 2
+"#;
+    let spec = PRESET_SPEC.to_string() + bad;
+    let spec = NetworkSpec::load(&dir.write("network.toml", &spec)).unwrap();
+    let err = apply::apply(&spec, true, true, false, false, &mut ctx).unwrap_err();
+    assert!(matches!(err, ApplyError::ValidationError(_)), "{}", err);
+    assert!(!ServerGroup::exists("Micro", &mut ctx));
+}
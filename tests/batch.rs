@@ -0,0 +1,145 @@
+use std::{collections::HashMap, fs, thread, time::Duration};
+
+use plex_redis_manager::{
+    batch,
+    cli::{self, Args, CliError},
+    config::models::Config,
+    context_manager::ContextManager,
+    region::Region,
+    server::{
+        dedicated::server::DedicatedServer,
+        presets::{Preset, SizeTier},
+    },
+    store::entity::RedisEntity,
+};
+
+fn offline_context(name: &str) -> ContextManager {
+    //! A context on an empty snapshot.
+    let path =
+        std::env::temp_dir().join(format!("plex_batch_{}_{}.json", name, std::process::id()));
+    fs::write(&path, "{}").expect("snapshot should be writable");
+    let mut config = Config::default();
+    config.set_snapshot(Some(path.to_string_lossy().into()));
+    let ctx = ContextManager::try_from_config(&config).expect("snapshot should load");
+    let _ = fs::remove_file(&path);
+    ctx
+}
+
+fn run_cli(line: &str, ctx: &mut ContextManager) -> Result<(), CliError> {
+    let args = Args::parse(line.split_whitespace().map(String::from)).unwrap();
+    cli::run(&args, ctx)
+}
+
+#[test]
+fn outcomes_keep_the_order_of_their_targets() {
+    let mut ctx = offline_context("order");
+    let report = batch::run(
+        (1..=5).collect(),
+        3,
+        |n: &u32, _| match n % 2 {
+            0 => Err(format!("{} is even", n)),
+            _ => Ok(format!("{} is odd", n)),
+        },
+        &mut ctx,
+    );
+    let targets: Vec<u32> = report
+        .outcomes
+        .iter()
+        .map(|outcome| outcome.target)
+        .collect();
+    assert_eq!(targets, [1, 2, 3, 4, 5]);
+    assert_eq!(report.outcomes[1].result, Err("2 is even".into()));
+    assert_eq!(report.outcomes[2].result, Ok("3 is odd".into()));
+    assert_eq!(report.get_failed_count(), 2);
+    assert!(!report.is_success());
+    assert_eq!(report.to_string(), "3 succeeded, 2 failed");
+}
+
+#[test]
+fn panics_only_fail_their_own_target() {
+    let mut ctx = offline_context("panic");
+    let report = batch::run(
+        vec!["a", "boom", "c"],
+        2,
+        |target: &&str, _| match *target {
+            "boom" => panic!("{} went off", target),
+            target => Ok(target.to_string()),
+        },
+        &mut ctx,
+    );
+    assert_eq!(report.outcomes[0].result, Ok("a".into()));
+    assert_eq!(
+        report.outcomes[1].result,
+        Err("panicked: boom went off".into())
+    );
+    assert_eq!(report.outcomes[2].result, Ok("c".into()));
+}
+
+#[test]
+fn slow_targets_time_out() {
+    let mut ctx = offline_context("timeout");
+    let report = batch::run_with_timeout(
+        vec![0, 50],
+        1,
+        Duration::from_millis(20),
+        |sleep: &u64, _| {
+            thread::sleep(Duration::from_millis(*sleep));
+            Ok("done".into())
+        },
+        &mut ctx,
+    );
+    assert_eq!(report.outcomes[0].result, Ok("done".into()));
+    assert_eq!(
+        report.outcomes[1].result,
+        Err("timed out after 20ms".into())
+    );
+    assert!(report.outcomes[1].took >= Duration::from_millis(50));
+}
+
+#[test]
+fn empty_batches_succeed() {
+    let mut ctx = offline_context("empty");
+    let report = batch::run(Vec::<u32>::new(), 4, |_, _| Ok(String::new()), &mut ctx);
+    assert!(report.outcomes.is_empty() && report.is_success());
+}
+
+#[test]
+fn foreach_needs_a_selection_and_a_known_action() {
+    let mut ctx = offline_context("foreach");
+    let err = run_cli("foreach restart", &mut ctx).unwrap_err();
+    assert!(matches!(err, CliError::Usage(_)), "{}", err);
+    let words = "foreach --group Test frobnicate".split_whitespace();
+    assert!(Args::parse(words.map(String::from)).is_err());
+    // nothing selected is not an error
+    run_cli("foreach --group Test join closed", &mut ctx).unwrap();
+}
+
+#[test]
+fn foreach_runs_on_the_selected_instances() {
+    let mut ctx = offline_context("selected");
+    let group = Preset::Arcade.to_server_group("Test", Region::US, SizeTier::S);
+    group.save(&mut ctx).unwrap();
+    let mut node = DedicatedServer {
+        name: "local".into(),
+        public_address: "127.0.0.1".into(),
+        private_address: "127.0.0.1".into(),
+        aliases: Vec::new(),
+        region: Region::US,
+        available_cpu: 16,
+        available_ram: 16384,
+        max_cpu: 16,
+        max_ram: 16384,
+        max_instances: None,
+        port_range: None,
+        reservations: Vec::new(),
+        server_instances: HashMap::new(),
+        telemetry: None,
+        overloaded: None,
+        disk: None,
+    };
+    node.add_server(&group, 1).unwrap();
+    ctx.get_dedicated_servers().servers.push(node);
+    run_cli("foreach --group Test join closed", &mut ctx).unwrap();
+    let err = run_cli("foreach --group Test join sideways", &mut ctx).unwrap_err();
+    assert!(matches!(err, CliError::Usage(_)), "{}", err);
+}
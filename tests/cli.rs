@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+use clap::error::ErrorKind;
+use plex_redis_manager::cli::{Args, USAGE};
+
+fn parse(line: &str) -> Args {
+    Args::parse(line.split_whitespace().map(String::from))
+        .unwrap_or_else(|err| panic!("{:?} should parse: {}", line, err))
+}
+
+fn flags(pairs: &[(&str, Option<&str>)]) -> HashMap<String, Option<String>> {
+    pairs
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.map(String::from)))
+        .collect()
+}
+
+#[test]
+fn every_usage_command_is_known() {
+    //! Completions and the shell read commands from the usage text, so each must parse.
+    let lines = USAGE
+        .lines()
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.strip_prefix("  "))
+        .filter(|line| !line.starts_with(' '));
+    for line in lines {
+        let mut paths: Vec<Vec<String>> = vec![Vec::new()];
+        for word in line
+            .split_whitespace()
+            .map(|word| word.trim_matches(|c| c == '[' || c == ']'))
+            .take_while(|word| word.starts_with(|c: char| c.is_ascii_lowercase()))
+        {
+            paths = paths
+                .iter()
+                .flat_map(|path| {
+                    word.split('|').map(move |keyword| {
+                        let mut path = path.clone();
+                        path.push(keyword.to_string());
+                        path
+                    })
+                })
+                .collect();
+        }
+        for mut path in paths {
+            path.push("--help".into());
+            let err = Args::parse(path.clone()).expect_err("--help should stop parsing");
+            assert_eq!(err.kind(), ErrorKind::DisplayHelp, "{:?}: {}", path, err);
+        }
+    }
+}
+
+#[test]
+fn commands_keep_their_words_in_order() {
+    let args = parse("group set MIN maxPlayers=24 pvp=false --force");
+    assert_eq!(
+        args.positional,
+        ["group", "set", "MIN", "maxPlayers=24", "pvp=false"]
+    );
+    assert_eq!(args.flags, flags(&[("force", None)]));
+
+    let args = parse("maps enable MIN Skylands");
+    assert_eq!(args.positional, ["maps", "enable", "MIN", "Skylands"]);
+    assert_eq!(parse("maps Arcade").positional, ["maps", "Arcade"]);
+    assert_eq!(parse("maps").positional, ["maps"]);
+    assert_eq!(parse("queue MIN").positional, ["queue", "MIN"]);
+    assert_eq!(parse("queue pop MIN").positional, ["queue", "pop", "MIN"]);
+    assert_eq!(parse("undo 2").positional, ["undo", "2"]);
+    assert_eq!(parse("undo list").positional, ["undo", "list"]);
+}
+
+#[test]
+fn flags_keep_their_values() {
+    let args = parse("group list --region EU --where arcadeGroup --sort total");
+    assert_eq!(args.positional, ["group", "list"]);
+    assert_eq!(
+        args.flags,
+        flags(&[
+            ("region", Some("EU")),
+            ("where", Some("arcadeGroup")),
+            ("sort", Some("total")),
+        ])
+    );
+    let args = parse("apply -f network.toml --prune --force");
+    assert_eq!(args.positional, ["apply"]);
+    assert_eq!(
+        args.get_flag("file").map(String::as_str),
+        Some("network.toml")
+    );
+    assert!(args.has_flag("prune") && args.has_flag("force"));
+
+    // selectors go before or after a foreach action
+    let args = parse("foreach --group MIN broadcast hello --concurrency=2");
+    assert_eq!(args.positional, ["foreach", "broadcast", "hello"]);
+    assert_eq!(
+        args.flags,
+        flags(&[("group", Some("MIN")), ("concurrency", Some("2"))])
+    );
+}
+
+#[test]
+fn shortcuts_run_their_group_commands() {
+    assert_eq!(parse("list-groups").positional, ["group", "list"]);
+    assert_eq!(
+        parse("create-group Micro").positional,
+        ["group", "create", "Micro"]
+    );
+    let args = parse("delete-group MIN --force");
+    assert_eq!(args.positional, ["group", "delete", "MIN"]);
+    assert!(args.has_flag("force"));
+    assert_eq!(parse("status MIN").positional, ["group", "status", "MIN"]);
+
+    let args = parse("launch MIN --dry-run");
+    assert_eq!(args.positional, ["launch"]);
+    assert_eq!(args.get_flag("group").map(String::as_str), Some("MIN"));
+    assert_eq!(parse("kill MIN-3").positional, ["kill", "MIN-3"]);
+}
+
+#[test]
+fn bad_arguments_are_refused() {
+    for line in [
+        "frobnicate",
+        "group",
+        "group scale MIN",
+        "group list --frobnicate",
+        "kill MIN-1 MIN-2",
+        "apply --prune",
+        "completions tcsh",
+        "launch MIN --group LOBBY",
+    ] {
+        let words = line.split_whitespace().map(String::from);
+        assert!(Args::parse(words).is_err(), "{:?} should be refused", line);
+    }
+}
@@ -0,0 +1,61 @@
+#![cfg(feature = "cluster")]
+
+use plex_redis_manager::store::cluster::{next_cursor, parse_masters, split_cursor};
+
+const SHARD_BITS: u32 = 16;
+
+#[test]
+fn only_healthy_masters_are_scanned() {
+    let nodes = "\
+07c37dfe 127.0.0.1:30004@31004 slave e7d1eecc 0 1426238317239 4 connected
+67ed2db8 127.0.0.1:30002@31002 master - 0 1426238316232 2 connected 5461-10922
+292f8b36 127.0.0.1:30003@31003 master,fail - 1426238316232 0 3 disconnected 10923-16383
+e7d1eecc 127.0.0.1:30001@31001 myself,master - 0 0 1 connected 0-5460
+6ec23923 127.0.0.1:30005 master - 0 1426238316232 5 connected
+";
+    assert_eq!(
+        parse_masters(nodes),
+        ["127.0.0.1:30001", "127.0.0.1:30002", "127.0.0.1:30005"]
+    );
+    assert!(parse_masters("").is_empty());
+    assert!(parse_masters("truncated").is_empty());
+}
+
+#[test]
+fn cursors_carry_their_shard_in_the_top_bits() {
+    assert_eq!(split_cursor(0), (0, 0));
+    assert_eq!(split_cursor(42), (0, 42));
+    let last_node_cursor = (1 << (64 - SHARD_BITS)) - 1;
+    assert_eq!(split_cursor(last_node_cursor), (0, last_node_cursor));
+    assert_eq!(split_cursor(1 << (64 - SHARD_BITS)), (1, 0));
+    assert_eq!(split_cursor((3 << (64 - SHARD_BITS)) | 7), (3, 7));
+    assert_eq!(
+        split_cursor(u64::MAX),
+        (u16::MAX as usize, last_node_cursor)
+    );
+
+    // round trip
+    let cursor = next_cursor(2, 4, 99).unwrap();
+    assert_eq!(split_cursor(cursor), (2, 99));
+    let cursor = next_cursor(0, 1, last_node_cursor).unwrap();
+    assert_eq!(split_cursor(cursor), (0, last_node_cursor));
+}
+
+#[test]
+fn finished_masters_move_on_to_the_next() {
+    assert_eq!(
+        split_cursor(next_cursor(0, 3, 0).unwrap()),
+        (1, 0),
+        "the first master is done"
+    );
+    assert_eq!(split_cursor(next_cursor(1, 3, 0).unwrap()), (2, 0));
+    // only the last master ends the scan
+    assert_eq!(next_cursor(2, 3, 0).unwrap(), 0);
+    assert_eq!(next_cursor(0, 1, 0).unwrap(), 0);
+}
+
+#[test]
+fn node_cursors_past_the_shard_bits_are_refused() {
+    assert!(next_cursor(0, 2, 1 << (64 - SHARD_BITS)).is_err());
+    assert!(next_cursor(0, 2, u64::MAX).is_err());
+}
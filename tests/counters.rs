@@ -0,0 +1,94 @@
+use std::fs;
+
+use chrono::Local;
+use plex_redis_manager::{
+    config::models::Config,
+    context_manager::ContextManager,
+    region::Region,
+    server::{
+        counters::{self, GroupCounts},
+        minecraft::MinecraftServer,
+        presets::{Preset, SizeTier},
+    },
+};
+
+fn offline_context(name: &str) -> ContextManager {
+    //! A context on an empty snapshot.
+    let path = std::env::temp_dir().join(format!(
+        "plex_counters_{}_{}.json",
+        name,
+        std::process::id()
+    ));
+    fs::write(&path, "{}").expect("snapshot should be writable");
+    let mut config = Config::default();
+    config.set_snapshot(Some(path.to_string_lossy().into()));
+    let ctx = ContextManager::try_from_config(&config).expect("snapshot should load");
+    let _ = fs::remove_file(&path);
+    ctx
+}
+
+fn counts(total: i64, joinable: i64) -> GroupCounts {
+    GroupCounts { total, joinable }
+}
+
+#[test]
+fn launches_and_deaths_adjust_the_counts() {
+    let mut ctx = offline_context("adjust");
+    assert_eq!(counters::get("Test", &mut ctx).unwrap(), counts(0, 0));
+    counters::increment("Test", &mut ctx).unwrap();
+    assert_eq!(counters::increment("Test", &mut ctx).unwrap(), counts(2, 2));
+    assert_eq!(
+        counters::decrement("Test", false, &mut ctx).unwrap(),
+        counts(1, 2)
+    );
+    assert_eq!(
+        counters::decrement("Test", true, &mut ctx).unwrap(),
+        counts(0, 1)
+    );
+    assert_eq!(counters::get("Test", &mut ctx).unwrap(), counts(0, 1));
+    // other groups are counted apart
+    assert_eq!(counters::get("Other", &mut ctx).unwrap(), counts(0, 0));
+}
+
+#[test]
+fn statuses_are_counted_per_group() {
+    let mut group = Preset::Arcade.to_server_group("Test", Region::US, SizeTier::S);
+    group.max_players = 8;
+    let other = Preset::Arcade.to_server_group("Other", Region::US, SizeTier::S);
+    let mut full = MinecraftServer::synthetic(&group, 2, Local::now());
+    full.heartbeat(8, 0, Local::now());
+    let statuses = [
+        MinecraftServer::synthetic(&group, 1, Local::now()),
+        full,
+        MinecraftServer::synthetic(&other, 1, Local::now()),
+    ];
+    assert_eq!(GroupCounts::of(&group, &statuses), counts(2, 1));
+    assert_eq!(GroupCounts::of(&other, &statuses), counts(1, 1));
+    assert_eq!(GroupCounts::of(&group, &[]), counts(0, 0));
+}
+
+#[test]
+fn reconcile_reports_what_drifted() {
+    let mut ctx = offline_context("reconcile");
+    for _ in 0..3 {
+        counters::increment("Test", &mut ctx).unwrap();
+    }
+    // one died without being uncounted
+    let drifted = counters::reconcile("Test", counts(2, 1), &mut ctx).unwrap();
+    assert_eq!(drifted, Some(counts(3, 3)));
+    assert_eq!(counters::get("Test", &mut ctx).unwrap(), counts(2, 1));
+    // already right
+    assert_eq!(
+        counters::reconcile("Test", counts(2, 1), &mut ctx).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn cleared_counts_start_over() {
+    let mut ctx = offline_context("clear");
+    counters::increment("Test", &mut ctx).unwrap();
+    counters::clear("Test", &mut ctx).unwrap();
+    assert_eq!(counters::get("Test", &mut ctx).unwrap(), counts(0, 0));
+    assert_eq!(counters::increment("Test", &mut ctx).unwrap(), counts(1, 1));
+}
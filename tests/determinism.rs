@@ -0,0 +1,77 @@
+use std::fs;
+
+use chrono::{Duration, Local, TimeZone};
+use plex_redis_manager::{
+    clock::{Clock, FixedClock},
+    config::models::{Config, PortAllocationInfo, PortAllocationStrategy},
+    context_manager::ContextManager,
+    random::SeededRandom,
+    server::{
+        minecraft::{status_to_json, MinecraftServer},
+        port::allocate_port_section,
+        presets::{Preset, SizeTier},
+    },
+    store::{consistent::read_consistent, entity::RedisEntity},
+};
+
+fn offline_context(name: &str, clock: &FixedClock) -> ContextManager {
+    //! A context on an empty snapshot, reading time from `clock` and drawing from a seed.
+    let path = std::env::temp_dir().join(format!(
+        "plex_determinism_{}_{}.json",
+        name,
+        std::process::id()
+    ));
+    fs::write(&path, "{}").expect("snapshot should be writable");
+    let mut config = Config::default();
+    config.set_snapshot(Some(path.to_string_lossy().into()));
+    let mut ctx = ContextManager::try_from_config(&config).expect("snapshot should load");
+    let _ = fs::remove_file(&path);
+    ctx.set_clock(Box::new(clock.clone()));
+    ctx.set_random(Box::new(SeededRandom::new(7)));
+    ctx
+}
+
+#[test]
+fn seeded_port_sections_repeat() {
+    let info = PortAllocationInfo {
+        strategy: PortAllocationStrategy::Random,
+        range: (25000, 26000),
+    };
+    let draw = |seed| {
+        let random = SeededRandom::new(seed);
+        (0..5)
+            .map(|_| allocate_port_section(&info, &[], &random).unwrap())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(draw(42), draw(42));
+}
+
+#[test]
+fn empty_servers_follow_the_clock() {
+    let clock = FixedClock::new(Local.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap());
+    let mut ctx = offline_context("empty", &clock);
+    let group = Preset::Arcade.to_server_group("Test", Default::default(), SizeTier::S);
+    let server = MinecraftServer::synthetic(&group, 1, clock.now());
+    let key = ctx
+        .get_keys()
+        .status_key(&group.region.to_string(), server.get_name());
+    redis::cmd("SET")
+        .arg(key)
+        .arg(status_to_json(server.to_map()))
+        .query::<()>(ctx.get_connection())
+        .unwrap();
+
+    // just started, so not dead yet
+    assert!(MinecraftServer::get_empty_servers(&mut ctx).ok.is_empty());
+    clock.advance(Duration::minutes(3));
+    assert_eq!(MinecraftServer::get_empty_servers(&mut ctx).ok.len(), 1);
+}
+
+#[test]
+fn offline_reads_are_stamped_by_the_clock() {
+    let clock = FixedClock::new(Local.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap());
+    let mut ctx = offline_context("consistent", &clock);
+    let read = read_consistent(&[redis::cmd("GET").arg("missing").clone()], &mut ctx).unwrap();
+    assert_eq!(read.read_at, clock.now().timestamp_millis());
+    assert!(read.atomic);
+}
@@ -0,0 +1,83 @@
+use std::{fs, path::PathBuf, process::Command};
+
+use plex_redis_manager::cli::exit;
+
+fn setup(name: &str, edit: fn(String) -> String) -> PathBuf {
+    //! A working directory with the repo's config (changed by `edit`) pointed at a closed
+    //! redis port and an empty snapshot to run offline against.
+    let dir = std::env::temp_dir().join(format!("plex_exit_codes_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&dir).expect("test directory should be writable");
+    let config = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/config.toml"))
+        .expect("config.toml should be readable")
+        .replace("port = \"6379\"", "port = \"1\"");
+    fs::write(dir.join("config.toml"), edit(config)).expect("config should be writable");
+    fs::write(dir.join("empty.json"), "{}").expect("snapshot should be writable");
+    dir
+}
+
+fn run(name: &str, args: &[&str]) -> i32 {
+    run_with(name, args, |config| config)
+}
+
+fn run_with(name: &str, args: &[&str], edit: fn(String) -> String) -> i32 {
+    let dir = setup(name, edit);
+    let status = Command::new(env!("CARGO_BIN_EXE_plex_redis_manager"))
+        .args(args)
+        .current_dir(&dir)
+        .output()
+        .expect("binary should run")
+        .status;
+    let _ = fs::remove_dir_all(&dir);
+    status.code().expect("binary should exit on its own")
+}
+
+#[test]
+fn help_succeeds() {
+    // without redis too
+    assert_eq!(run("help", &["--help"]), exit::SUCCESS);
+}
+
+#[test]
+fn unknown_command_is_a_usage_error() {
+    assert_eq!(
+        run("usage", &["frobnicate", "--snapshot", "empty.json"]),
+        exit::USAGE
+    );
+}
+
+#[test]
+fn missing_group_is_not_found() {
+    assert_eq!(
+        run(
+            "not_found",
+            &["group", "status", "Nope", "--snapshot", "empty.json"]
+        ),
+        exit::NOT_FOUND
+    );
+}
+
+#[test]
+fn closed_redis_port_is_unreachable() {
+    assert_eq!(run("unreachable", &["group", "list"]), exit::UNREACHABLE);
+}
+
+#[test]
+fn missing_snapshot_is_unreachable() {
+    assert_eq!(
+        run("snapshot", &["group", "list", "--snapshot", "missing.json"]),
+        exit::UNREACHABLE
+    );
+}
+
+#[test]
+fn least_privilege_without_a_user_is_invalid() {
+    let least_privilege = |config: String| {
+        config
+            .replace("# [redis_conn.users]", "[redis_conn.users]")
+            .replace("# least_privilege = true", "least_privilege = true")
+    };
+    assert_eq!(
+        run_with("least_privilege", &["group", "list"], least_privilege),
+        exit::INVALID
+    );
+}
@@ -0,0 +1,121 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use chrono::Local;
+use plex_redis_manager::{
+    config::models::Config,
+    context_manager::ContextManager,
+    region::Region,
+    server::{
+        dedicated::{collection::DedicatedServers, server::DedicatedServer},
+        minecraft::{status_to_json, MinecraftServer},
+        presets::{Preset, SizeTier},
+        server_group::ServerGroup,
+    },
+    store::entity::RedisEntity,
+};
+
+struct Healing {
+    ctx: ContextManager,
+    servers: DedicatedServers,
+    group: ServerGroup,
+    dir: PathBuf,
+}
+
+impl Drop for Healing {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn healing(name: &str) -> Healing {
+    //! An offline context holding the group, with a node tracking instance 1 of it.
+    let dir = std::env::temp_dir().join(format!("plex_healing_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&dir).expect("temp dir should be writable");
+    let snapshot = dir.join("snapshot.json");
+    fs::write(&snapshot, "{}").expect("snapshot should be writable");
+
+    let mut config = Config::default();
+    config.set_snapshot(Some(snapshot.to_string_lossy().into()));
+    let mut ctx = ContextManager::try_from_config(&config).expect("snapshot should load");
+    let group = Preset::Arcade.to_server_group("Test", Region::US, SizeTier::S);
+    group.save(&mut ctx).expect("group should be saved");
+
+    let mut node = DedicatedServer {
+        name: "local".into(),
+        public_address: "127.0.0.1".into(),
+        private_address: "127.0.0.1".into(),
+        aliases: Vec::new(),
+        region: Region::US,
+        available_cpu: 16,
+        available_ram: 16384,
+        max_cpu: 16,
+        max_ram: 16384,
+        max_instances: None,
+        port_range: None,
+        reservations: Vec::new(),
+        server_instances: HashMap::new(),
+        telemetry: None,
+        overloaded: None,
+        disk: None,
+    };
+    node.add_server(&group, 1).expect("node should have room");
+    Healing {
+        ctx,
+        servers: DedicatedServers {
+            servers: vec![node],
+        },
+        group,
+        dir,
+    }
+}
+
+fn report_status(healing: &mut Healing) {
+    let server = MinecraftServer::synthetic(&healing.group, 1, Local::now());
+    let key = healing
+        .ctx
+        .get_keys()
+        .status_key(&healing.group.region.to_string(), server.get_name());
+    redis::cmd("SET")
+        .arg(key)
+        .arg(status_to_json(server.to_map()))
+        .query::<()>(healing.ctx.get_connection())
+        .unwrap();
+}
+
+#[test]
+fn instances_without_a_status_are_removed_after_a_while() {
+    let mut healing = healing("missing");
+    let Healing { ctx, servers, .. } = &mut healing;
+    let report = servers.remove_dead(Some(&Region::US), 60, ctx);
+    assert!(report.removed.is_empty());
+    assert!(servers.servers[0].get_instance("Test-1").is_some());
+
+    // gone for longer than that since
+    let instance = servers.servers[0].server_instances.get_mut("Test").unwrap();
+    let since = instance[0]
+        .get_missing_since()
+        .expect("should be marked missing");
+    instance[0].set_missing_since(Some(since - 61));
+    let report = servers.remove_dead(Some(&Region::US), 60, ctx);
+    assert_eq!(
+        report.removed,
+        vec![("Test-1".to_string(), Some("local".to_string()))]
+    );
+    assert!(servers.servers[0].get_instance("Test-1").is_none());
+}
+
+#[test]
+fn instances_reporting_again_are_kept() {
+    let mut healing = healing("reported");
+    let Healing { ctx, servers, .. } = &mut healing;
+    servers.remove_dead(Some(&Region::US), 60, ctx);
+    let instance = servers.servers[0].server_instances.get_mut("Test").unwrap();
+    instance[0].set_missing_since(Some(0));
+
+    report_status(&mut healing);
+    let Healing { ctx, servers, .. } = &mut healing;
+    let report = servers.remove_dead(Some(&Region::US), 60, ctx);
+    assert!(report.removed.is_empty() && report.failed.is_empty());
+    let instance = servers.servers[0].get_instance("Test-1").unwrap();
+    assert_eq!(instance.get_missing_since(), None);
+}
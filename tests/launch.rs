@@ -0,0 +1,174 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use chrono::Local;
+use plex_redis_manager::{
+    config::models::Config,
+    context_manager::ContextManager,
+    jars::{JarArtifact, JarsInfo},
+    region::Region,
+    server::{
+        dedicated::{
+            server::{DedicatedServer, DedicatedServerError},
+            spawn::{ServerLauncher, SpawnRequest},
+        },
+        minecraft::{status_to_json, MinecraftServer},
+        presets::{Preset, SizeTier},
+        server_group::ServerGroup,
+        version::DEFAULT_MINECRAFT_VERSION,
+    },
+    store::entity::RedisEntity,
+};
+
+/// Records what it was asked to spawn instead of starting a process.
+struct FakeLauncher {
+    spawned: Arc<Mutex<Vec<SpawnRequest>>>,
+    result: Result<String, String>,
+}
+
+impl ServerLauncher for FakeLauncher {
+    fn spawn(&self, request: &SpawnRequest) -> Result<String, String> {
+        self.spawned.lock().unwrap().push(request.clone());
+        self.result.clone()
+    }
+}
+
+struct Launch {
+    ctx: ContextManager,
+    node: DedicatedServer,
+    group: ServerGroup,
+    spawned: Arc<Mutex<Vec<SpawnRequest>>>,
+    dir: PathBuf,
+}
+
+impl Drop for Launch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn launch(name: &str, result: Result<String, String>) -> Launch {
+    //! An offline context with the group's jar in a local cache, a local node running
+    //! instance 1 of the group and a `FakeLauncher` returning `result`.
+    let dir = std::env::temp_dir().join(format!("plex_launch_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&dir).expect("temp dir should be writable");
+    let snapshot = dir.join("snapshot.json");
+    fs::write(&snapshot, "{}").expect("snapshot should be writable");
+    let source = dir.join("server.jar");
+    fs::write(&source, "jar").expect("jar should be writable");
+
+    let mut config = Config::default();
+    config.set_snapshot(Some(snapshot.to_string_lossy().into()));
+    config.jars = JarsInfo {
+        cache_path: dir.join("cache").to_string_lossy().into(),
+        remote_path: dir.join("node").to_string_lossy().into(),
+        artifacts: vec![JarArtifact {
+            version: DEFAULT_MINECRAFT_VERSION.into(),
+            flavor: "spigot".into(),
+            source: source.to_string_lossy().into(),
+            sha1: None,
+        }],
+    };
+    config.monitor_info.get_startup_probe_mut().timeout_secs = 0;
+    let mut ctx = ContextManager::try_from_config(&config).expect("snapshot should load");
+    let spawned = Arc::new(Mutex::new(Vec::new()));
+    ctx.set_server_launcher(Box::new(FakeLauncher {
+        spawned: spawned.clone(),
+        result,
+    }));
+
+    let group = Preset::Arcade.to_server_group("Test", Region::US, SizeTier::S);
+    let mut node = DedicatedServer {
+        name: "local".into(),
+        public_address: "127.0.0.1".into(),
+        private_address: "127.0.0.1".into(),
+        aliases: Vec::new(),
+        region: Region::US,
+        available_cpu: 16,
+        available_ram: 16384,
+        max_cpu: 16,
+        max_ram: 16384,
+        max_instances: None,
+        port_range: None,
+        reservations: Vec::new(),
+        server_instances: HashMap::new(),
+        telemetry: None,
+        overloaded: None,
+        disk: None,
+    };
+    node.add_server(&group, 1).expect("node should have room");
+    Launch {
+        ctx,
+        node,
+        group,
+        spawned,
+        dir,
+    }
+}
+
+fn report_status(launch: &mut Launch) {
+    //! Writes the status the server's plugin would once it is up.
+    let server = MinecraftServer::synthetic(&launch.group, 1, Local::now());
+    let key = launch
+        .ctx
+        .get_keys()
+        .status_key(&launch.group.region.to_string(), server.get_name());
+    redis::cmd("SET")
+        .arg(key)
+        .arg(status_to_json(server.to_map()))
+        .query::<()>(launch.ctx.get_connection())
+        .unwrap();
+}
+
+#[test]
+fn launches_once_the_status_is_up() {
+    let mut launch = launch("up", Ok("pid=4242\n".into()));
+    report_status(&mut launch);
+    let Launch {
+        ctx, node, group, ..
+    } = &mut launch;
+    node.launch_server(group, 1, ctx).unwrap();
+
+    let spawned = launch.spawned.lock().unwrap();
+    assert_eq!(spawned.len(), 1);
+    assert_eq!(spawned[0].server_name, "Test-1");
+    assert_eq!(
+        Some(spawned[0].port),
+        launch.node.get_instance("Test-1").map(|i| i.get_port())
+    );
+    assert!(spawned[0].local);
+    assert!(launch.dir.join("node").join(&spawned[0].jar).exists());
+}
+
+#[test]
+fn times_out_without_a_status() {
+    let mut launch = launch("timeout", Ok("pid=4242\n".into()));
+    let Launch {
+        ctx, node, group, ..
+    } = &mut launch;
+    let err = node.launch_server(group, 1, ctx).unwrap_err();
+    assert!(matches!(
+        err,
+        DedicatedServerError::MinecraftServerNotRunning(_)
+    ));
+    assert_eq!(launch.spawned.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn spawn_errors_are_returned() {
+    let mut launch = launch("spawn", Err("start script not found".into()));
+    report_status(&mut launch);
+    let Launch {
+        ctx, node, group, ..
+    } = &mut launch;
+    match node.launch_server(group, 1, ctx) {
+        Err(DedicatedServerError::ProcessError(err)) => {
+            assert!(err.contains("start script not found"), "{}", err)
+        }
+        other => panic!("expected the spawn error, got {:?}", other),
+    }
+}
@@ -0,0 +1,106 @@
+use plex_redis_manager::{
+    region::Region,
+    server::{
+        presets::{Preset, SizeTier},
+        query::{GroupQuery, QueryError},
+        server_group::ServerGroup,
+    },
+};
+
+fn group() -> ServerGroup {
+    //! An EU arcade group of 16 players.
+    let mut group = Preset::Arcade.to_server_group("Micro", Region::EU, SizeTier::S);
+    group.arcade_group = true;
+    group.max_players = 16;
+    group
+}
+
+fn matches(expr: &str) -> bool {
+    let query: GroupQuery = expr
+        .parse()
+        .unwrap_or_else(|err| panic!("{:?} should parse: {}", expr, err));
+    query.matches(&group()).unwrap()
+}
+
+fn parse_error(expr: &str) -> String {
+    match expr.parse::<GroupQuery>() {
+        Err(QueryError::ParseError(message)) => message,
+        other => panic!("{:?} should not parse, got {:?}", expr, other),
+    }
+}
+
+#[test]
+fn and_binds_tighter_than_or() {
+    let query: GroupQuery = "a || b && c".parse().unwrap();
+    assert_eq!(query.to_string(), "(a || (b && c))");
+    assert!(matches("region==US || arcadeGroup && maxPlayers>8"));
+    assert!(!matches("region==US || arcadeGroup && maxPlayers>16"));
+    assert!(matches("arcadeGroup && maxPlayers>16 || region==EU"));
+}
+
+#[test]
+fn not_and_parentheses_group_as_written() {
+    assert!(!matches("!arcadeGroup"));
+    assert!(matches("!!arcadeGroup"));
+    assert!(matches("!(region==US)"));
+    assert!(!matches("(region==US || arcadeGroup) && maxPlayers>16"));
+    assert!(matches("region==US || (arcadeGroup && maxPlayers==16)"));
+    assert_eq!(
+        "!(a || b) && c".parse::<GroupQuery>().unwrap().to_string(),
+        "(!((a || b)) && c)"
+    );
+}
+
+#[test]
+fn numbers_compare_as_numbers() {
+    assert!(matches("maxPlayers==16.0"));
+    assert!(matches("maxPlayers>9"));
+    assert!(matches("maxPlayers<=16 && maxPlayers>=16"));
+    assert!(!matches("maxPlayers<16"));
+    // strings compare case-insensitively, and never order
+    assert!(matches("region==eu && region!=US"));
+    assert!(!matches("name>=1"));
+    assert!(matches("name==\"Micro\""));
+}
+
+#[test]
+fn contains_is_a_case_insensitive_substring() {
+    assert!(matches("name~=micro"));
+    assert!(matches("name~=\"IC\""));
+    assert!(!matches("name~=mega"));
+}
+
+#[test]
+fn unknown_fields_fail_when_evaluated() {
+    let query: GroupQuery = "frobnicate==1".parse().unwrap();
+    assert_eq!(
+        query.matches(&group()),
+        Err(QueryError::UnknownField("frobnicate".into()))
+    );
+    // also behind a short circuit that doesn't happen
+    let query: GroupQuery = "arcadeGroup && frobnicate".parse().unwrap();
+    assert!(query.matches(&group()).is_err());
+    // fields are case-insensitive
+    assert!(matches("ARCADEGROUP && MaxPlayers==16"));
+}
+
+#[test]
+fn malformed_queries_are_refused() {
+    assert_eq!(parse_error("name==\"Micro"), "unterminated \"");
+    assert_eq!(parse_error("(arcadeGroup"), "missing )");
+    assert_eq!(parse_error(""), "expected a field, found the end");
+    assert_eq!(
+        parse_error("arcadeGroup &&"),
+        "expected a field, found the end"
+    );
+    assert_eq!(
+        parse_error("maxPlayers>="),
+        "expected a value after maxPlayers>=, found the end"
+    );
+    assert_eq!(
+        parse_error("maxPlayers>many"),
+        "maxPlayers>many compares with a non-number"
+    );
+    assert_eq!(parse_error("a b"), "unexpected b after a");
+    assert_eq!(parse_error("a & b"), "unexpected '&'");
+}
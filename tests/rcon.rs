@@ -0,0 +1,77 @@
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+    time::Duration,
+};
+
+use plex_redis_manager::server::rcon::RconClient;
+
+const PASSWORD: &str = "secret";
+const MAX_BODY: usize = 4096; // vanilla splits longer output over several packets
+
+fn read_packet(stream: &mut TcpStream) -> Option<(i32, i32, String)> {
+    let mut length = [0; 4];
+    stream.read_exact(&mut length).ok()?;
+    let mut packet = vec![0; i32::from_le_bytes(length) as usize];
+    stream.read_exact(&mut packet).ok()?;
+    let id = i32::from_le_bytes(packet[0..4].try_into().unwrap());
+    let kind = i32::from_le_bytes(packet[4..8].try_into().unwrap());
+    let body = String::from_utf8_lossy(&packet[8..packet.len() - 2]).into_owned();
+    Some((id, kind, body))
+}
+
+fn write_packet(stream: &mut TcpStream, id: i32, kind: i32, body: &str) {
+    let mut packet = Vec::new();
+    packet.extend(((4 + 4 + body.len() + 2) as i32).to_le_bytes());
+    packet.extend(id.to_le_bytes());
+    packet.extend(kind.to_le_bytes());
+    packet.extend(body.as_bytes());
+    packet.extend([0, 0]);
+    stream.write_all(&packet).unwrap();
+}
+
+fn start(output: String) -> String {
+    //! A console replying `output` to every command the way a vanilla server does: long
+    //! output over several packets, unknown packet types with an error.
+    let listener = TcpListener::bind("127.0.0.1:0").expect("port should be free");
+    let address = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        while let Some((id, kind, body)) = read_packet(&mut stream) {
+            match kind {
+                3 if body == PASSWORD => write_packet(&mut stream, id, 2, ""),
+                3 => write_packet(&mut stream, -1, 2, ""),
+                2 => {
+                    // at least one packet, empty if there is no output
+                    let mut chunks = output.as_bytes().chunks(MAX_BODY).peekable();
+                    if chunks.peek().is_none() {
+                        write_packet(&mut stream, id, 0, "");
+                    }
+                    for chunk in chunks {
+                        write_packet(&mut stream, id, 0, std::str::from_utf8(chunk).unwrap());
+                    }
+                }
+                kind => write_packet(&mut stream, id, 0, &format!("Unknown request {:x}", kind)),
+            }
+        }
+    });
+    address
+}
+
+#[test]
+fn long_replies_are_joined() {
+    let output = "a".repeat(MAX_BODY) + &"b".repeat(100);
+    let address = start(output.clone());
+    let mut client = RconClient::connect(&address, PASSWORD, Duration::from_secs(2)).unwrap();
+    assert_eq!(client.run("list").unwrap(), output);
+    // the next command only gets its own reply
+    assert_eq!(client.run("list").unwrap(), output);
+}
+
+#[test]
+fn empty_replies_are_empty() {
+    let address = start(String::new());
+    let mut client = RconClient::connect(&address, PASSWORD, Duration::from_secs(2)).unwrap();
+    assert_eq!(client.run("save-all").unwrap(), "");
+}
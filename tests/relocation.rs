@@ -0,0 +1,174 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use chrono::Local;
+use plex_redis_manager::{
+    config::models::Config,
+    context_manager::ContextManager,
+    jars::{JarArtifact, JarsInfo},
+    region::Region,
+    server::{
+        dedicated::{
+            collection::DedicatedServers,
+            server::{DedicatedServer, DedicatedServerError},
+            spawn::{ServerLauncher, SpawnRequest},
+        },
+        minecraft::{status_to_json, MinecraftServer},
+        presets::{Preset, SizeTier},
+        server_group::ServerGroup,
+        version::DEFAULT_MINECRAFT_VERSION,
+    },
+    store::entity::RedisEntity,
+};
+
+/// Records what it was asked to spawn instead of starting a process.
+struct FakeLauncher {
+    spawned: Arc<Mutex<Vec<SpawnRequest>>>,
+}
+
+impl ServerLauncher for FakeLauncher {
+    fn spawn(&self, request: &SpawnRequest) -> Result<String, String> {
+        self.spawned.lock().unwrap().push(request.clone());
+        Ok("pid=4242\n".into())
+    }
+}
+
+struct Relocating {
+    ctx: ContextManager,
+    servers: DedicatedServers,
+    group: ServerGroup,
+    spawned: Arc<Mutex<Vec<SpawnRequest>>>,
+    dir: PathBuf,
+}
+
+impl Drop for Relocating {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn node(name: &str) -> DedicatedServer {
+    DedicatedServer {
+        name: name.into(),
+        public_address: "127.0.0.1".into(),
+        private_address: "127.0.0.1".into(),
+        aliases: Vec::new(),
+        region: Region::US,
+        available_cpu: 16,
+        available_ram: 16384,
+        max_cpu: 16,
+        max_ram: 16384,
+        max_instances: None,
+        port_range: None,
+        reservations: Vec::new(),
+        server_instances: HashMap::new(),
+        telemetry: None,
+        overloaded: None,
+        disk: None,
+    }
+}
+
+fn relocating(name: &str) -> Relocating {
+    //! An offline context with nodes `a` and `b`, both tracking instance 1 of the group,
+    //! a kill script that always succeeds and a `FakeLauncher`.
+    let dir = std::env::temp_dir().join(format!("plex_relocation_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&dir).expect("temp dir should be writable");
+    let snapshot = dir.join("snapshot.json");
+    fs::write(&snapshot, "{}").expect("snapshot should be writable");
+    fs::write(dir.join("easyRemoteKillServer.sh"), "exit 0\n").expect("script should be writable");
+    let source = dir.join("server.jar");
+    fs::write(&source, "jar").expect("jar should be writable");
+
+    let mut config = Config::default();
+    config.set_snapshot(Some(snapshot.to_string_lossy().into()));
+    config
+        .monitor_info
+        .set_scripts_path(dir.to_string_lossy().into());
+    config.jars = JarsInfo {
+        cache_path: dir.join("cache").to_string_lossy().into(),
+        remote_path: dir.join("node").to_string_lossy().into(),
+        artifacts: vec![JarArtifact {
+            version: DEFAULT_MINECRAFT_VERSION.into(),
+            flavor: "spigot".into(),
+            source: source.to_string_lossy().into(),
+            sha1: None,
+        }],
+    };
+    config.monitor_info.get_startup_probe_mut().timeout_secs = 0;
+    let mut ctx = ContextManager::try_from_config(&config).expect("snapshot should load");
+    let spawned = Arc::new(Mutex::new(Vec::new()));
+    ctx.set_server_launcher(Box::new(FakeLauncher {
+        spawned: spawned.clone(),
+    }));
+    let group = Preset::Arcade.to_server_group("Test", Region::US, SizeTier::S);
+    group.save(&mut ctx).expect("group should be saved");
+
+    let mut servers = DedicatedServers {
+        servers: vec![node("a"), node("b")],
+    };
+    for node in servers.servers.iter_mut() {
+        node.add_server(&group, 1).expect("node should have room");
+    }
+    Relocating {
+        ctx,
+        servers,
+        group,
+        spawned,
+        dir,
+    }
+}
+
+fn report_status(relocating: &mut Relocating, server_num: usize) {
+    let server = MinecraftServer::synthetic(&relocating.group, server_num, Local::now());
+    let key = relocating
+        .ctx
+        .get_keys()
+        .status_key(&relocating.group.region.to_string(), server.get_name());
+    redis::cmd("SET")
+        .arg(key)
+        .arg(status_to_json(server.to_map()))
+        .query::<()>(relocating.ctx.get_connection())
+        .unwrap();
+}
+
+#[test]
+fn empty_instances_move_without_force() {
+    let mut relocating = relocating("empty");
+    // the replacement's status, since the target already runs number 1
+    report_status(&mut relocating, 2);
+    let Relocating { ctx, servers, .. } = &mut relocating;
+    let relocation = servers
+        .relocate_instance(&"Test-1".into(), &"b".into(), false, ctx)
+        .unwrap();
+    assert_eq!(relocation.from, "a");
+    assert_eq!(relocation.to, "b");
+    assert_eq!(relocation.replacement, "Test-2");
+    assert!(servers.servers[0].get_instance("Test-1").is_none());
+    assert!(servers.servers[1].get_instance("Test-2").is_some());
+
+    let spawned = relocating.spawned.lock().unwrap();
+    assert_eq!(spawned.len(), 1);
+    assert_eq!(spawned[0].node, "b");
+    assert_eq!(spawned[0].server_name, "Test-2");
+}
+
+#[test]
+fn instances_stay_on_their_node() {
+    let mut relocating = relocating("same");
+    let Relocating { ctx, servers, .. } = &mut relocating;
+    let err = servers
+        .relocate_instance(&"Test-1".into(), &"a".into(), true, ctx)
+        .unwrap_err();
+    assert!(matches!(err, DedicatedServerError::RelocationError(_)));
+
+    let err = servers
+        .relocate_instance(&"Test-9".into(), &"b".into(), true, ctx)
+        .unwrap_err();
+    assert!(matches!(err, DedicatedServerError::InstanceNotFound(_)));
+    assert!(servers.servers[0].get_instance("Test-1").is_some());
+    assert!(relocating.spawned.lock().unwrap().is_empty());
+}
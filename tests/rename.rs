@@ -0,0 +1,189 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use chrono::Local;
+use plex_redis_manager::{
+    config::models::Config,
+    context_manager::ContextManager,
+    jars::{JarArtifact, JarsInfo},
+    region::Region,
+    server::{
+        dedicated::{
+            collection::DedicatedServers,
+            server::DedicatedServer,
+            spawn::{ServerLauncher, SpawnRequest},
+        },
+        minecraft::{status_to_json, MinecraftServer},
+        presets::{Preset, SizeTier},
+        server_group::ServerGroup,
+        version::DEFAULT_MINECRAFT_VERSION,
+    },
+    store::entity::RedisEntity,
+};
+
+/// Records what it was asked to spawn instead of starting a process.
+struct FakeLauncher {
+    spawned: Arc<Mutex<Vec<SpawnRequest>>>,
+}
+
+impl ServerLauncher for FakeLauncher {
+    fn spawn(&self, request: &SpawnRequest) -> Result<String, String> {
+        self.spawned.lock().unwrap().push(request.clone());
+        Ok("pid=4242\n".into())
+    }
+}
+
+struct Renaming {
+    ctx: ContextManager,
+    group: ServerGroup,
+    spawned: Arc<Mutex<Vec<SpawnRequest>>>,
+    dir: PathBuf,
+}
+
+impl Drop for Renaming {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn renaming(name: &str) -> Renaming {
+    //! An offline context holding the group, with a kill script that always succeeds
+    //! and a `FakeLauncher`.
+    let dir = std::env::temp_dir().join(format!("plex_rename_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&dir).expect("temp dir should be writable");
+    let snapshot = dir.join("snapshot.json");
+    fs::write(&snapshot, "{}").expect("snapshot should be writable");
+    fs::write(dir.join("easyRemoteKillServer.sh"), "exit 0\n").expect("script should be writable");
+    let source = dir.join("server.jar");
+    fs::write(&source, "jar").expect("jar should be writable");
+
+    let mut config = Config::default();
+    config.set_snapshot(Some(snapshot.to_string_lossy().into()));
+    config
+        .monitor_info
+        .set_scripts_path(dir.to_string_lossy().into());
+    config.jars = JarsInfo {
+        cache_path: dir.join("cache").to_string_lossy().into(),
+        remote_path: dir.join("node").to_string_lossy().into(),
+        artifacts: vec![JarArtifact {
+            version: DEFAULT_MINECRAFT_VERSION.into(),
+            flavor: "spigot".into(),
+            source: source.to_string_lossy().into(),
+            sha1: None,
+        }],
+    };
+    config.monitor_info.get_startup_probe_mut().timeout_secs = 0;
+    let mut ctx = ContextManager::try_from_config(&config).expect("snapshot should load");
+    let spawned = Arc::new(Mutex::new(Vec::new()));
+    ctx.set_server_launcher(Box::new(FakeLauncher {
+        spawned: spawned.clone(),
+    }));
+    let group = Preset::Arcade.to_server_group("Test", Region::US, SizeTier::S);
+    group.save(&mut ctx).expect("group should be saved");
+    Renaming {
+        ctx,
+        group,
+        spawned,
+        dir,
+    }
+}
+
+fn report_status(renaming: &mut Renaming, group: &ServerGroup, server_num: usize) {
+    let server = MinecraftServer::synthetic(group, server_num, Local::now());
+    let key = renaming
+        .ctx
+        .get_keys()
+        .status_key(&group.region.to_string(), server.get_name());
+    redis::cmd("SET")
+        .arg(key)
+        .arg(status_to_json(server.to_map()))
+        .query::<()>(renaming.ctx.get_connection())
+        .unwrap();
+}
+
+fn get_index(ctx: &mut ContextManager) -> Vec<String> {
+    let mut index: Vec<String> = redis::cmd("SMEMBERS")
+        .arg(ctx.get_keys().group_index_key())
+        .query(ctx.get_connection())
+        .unwrap();
+    index.sort();
+    index
+}
+
+#[test]
+fn renamed_groups_leave_an_alias_behind() {
+    let mut renaming = renaming("alias");
+    let ctx = &mut renaming.ctx;
+    let renamed = ServerGroup::rename("Test", "Renamed", ctx).unwrap();
+    assert_eq!(renamed.prefix, "Renamed");
+    assert_eq!(renamed.ram, renaming.group.ram);
+
+    assert!(!ServerGroup::exists("Test", ctx));
+    assert_eq!(ServerGroup::get("Renamed", ctx).unwrap().name, "Renamed");
+    assert_eq!(get_index(ctx), vec!["Renamed".to_string()]);
+    assert_eq!(
+        ServerGroup::resolve_alias("Test", ctx),
+        Some("Renamed".into())
+    );
+    // readers still using the old name are redirected
+    assert_eq!(
+        ServerGroup::from_str("Test", ctx).unwrap().prefix,
+        "Renamed"
+    );
+}
+
+#[test]
+fn taken_names_are_refused() {
+    let mut renaming = renaming("taken");
+    let ctx = &mut renaming.ctx;
+    Preset::Arcade
+        .to_server_group("Other", Region::US, SizeTier::S)
+        .save(ctx)
+        .unwrap();
+    assert!(ServerGroup::rename("Test", "Other", ctx).is_err());
+    assert!(ServerGroup::exists("Test", ctx));
+    assert_eq!(ServerGroup::resolve_alias("Test", ctx), None);
+    assert!(ServerGroup::rename("Nope", "Renamed", ctx).is_err());
+}
+
+#[test]
+fn relaunched_instances_keep_their_number() {
+    let mut renaming = renaming("relaunch");
+    let renamed = ServerGroup::rename("Test", "Renamed", &mut renaming.ctx).unwrap();
+    // the replacement's status, as its plugin would report it
+    report_status(&mut renaming, &renamed, 3);
+
+    let mut node = DedicatedServer {
+        name: "local".into(),
+        public_address: "127.0.0.1".into(),
+        private_address: "127.0.0.1".into(),
+        aliases: Vec::new(),
+        region: Region::US,
+        available_cpu: 16,
+        available_ram: 16384,
+        max_cpu: 16,
+        max_ram: 16384,
+        max_instances: None,
+        port_range: None,
+        reservations: Vec::new(),
+        server_instances: HashMap::new(),
+        telemetry: None,
+        overloaded: None,
+        disk: None,
+    };
+    node.add_server(&renaming.group, 3).unwrap();
+    let mut servers = DedicatedServers {
+        servers: vec![node],
+    };
+    let relaunched = servers
+        .relaunch_group(&renaming.group, &renamed, false, &mut renaming.ctx)
+        .unwrap();
+    assert_eq!(relaunched, vec!["Renamed-3".to_string()]);
+    assert!(servers.servers[0].get_instance("Test-3").is_none());
+    assert!(servers.servers[0].get_instance("Renamed-3").is_some());
+    assert_eq!(renaming.spawned.lock().unwrap()[0].server_name, "Renamed-3");
+}
@@ -0,0 +1,128 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use plex_redis_manager::store::{
+    connection::Connection,
+    retry::{RetryPolicy, RetryingConnection},
+};
+
+/// A redis stand-in counting the commands it applied. With `drop_next` set, it applies
+/// the next command and closes the connection before replying, as a restart would.
+#[derive(Clone, Default)]
+struct FlakyServer {
+    applied: Arc<Mutex<Vec<String>>>,
+    drop_next: Arc<AtomicBool>,
+}
+
+fn read_command(reader: &mut BufReader<TcpStream>) -> Option<Vec<String>> {
+    //! One RESP array of bulk strings, None once the client hung up.
+    let mut line = String::new();
+    reader.read_line(&mut line).ok().filter(|read| *read > 0)?;
+    let args: usize = line.trim().strip_prefix('*')?.parse().ok()?;
+    let mut command = Vec::new();
+    for _ in 0..args {
+        line.clear();
+        reader.read_line(&mut line).ok()?;
+        line.clear();
+        reader.read_line(&mut line).ok()?;
+        command.push(line.trim_end().to_string());
+    }
+    Some(command)
+}
+
+impl FlakyServer {
+    fn start(&self) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("port should be free");
+        let address = listener.local_addr().unwrap().to_string();
+        let server = self.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let server = server.clone();
+                thread::spawn(move || server.serve(stream));
+            }
+        });
+        address
+    }
+
+    fn serve(&self, mut stream: TcpStream) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        while let Some(command) = read_command(&mut reader) {
+            let name = command[0].to_uppercase();
+            let count = {
+                let mut applied = self.applied.lock().unwrap();
+                applied.push(name.clone());
+                applied.iter().filter(|applied| **applied == name).count()
+            };
+            if self.drop_next.swap(false, Ordering::SeqCst) {
+                return;
+            }
+            let reply = match name.as_str() {
+                "HINCRBY" => format!(":{}\r\n", count),
+                "GET" => "$2\r\n42\r\n".into(),
+                _ => "+OK\r\n".into(),
+            };
+            let _ = stream.write_all(reply.as_bytes());
+        }
+    }
+
+    fn count(&self, name: &str) -> usize {
+        let applied = self.applied.lock().unwrap();
+        applied.iter().filter(|applied| *applied == name).count()
+    }
+}
+
+fn connect(address: &str) -> RetryingConnection {
+    let open = {
+        let url = format!("redis://{}", address);
+        move || {
+            let conn = redis::Client::open(url.as_str())?.get_connection()?;
+            Ok(Connection::Redis(conn))
+        }
+    };
+    let policy = RetryPolicy {
+        max_retries: 3,
+        backoff: Duration::from_millis(1),
+    };
+    RetryingConnection::new(open().unwrap(), Box::new(open), policy)
+}
+
+#[test]
+fn dropped_writes_are_not_resent() {
+    let server = FlakyServer::default();
+    let mut conn = connect(&server.start());
+    server.drop_next.store(true, Ordering::SeqCst);
+    let counted: redis::RedisResult<i64> = redis::cmd("HINCRBY")
+        .arg("servercounts.Test")
+        .arg("totalServers")
+        .arg(1)
+        .query(&mut conn);
+    assert!(counted.unwrap_err().is_connection_dropped());
+    assert_eq!(server.count("HINCRBY"), 1);
+
+    // the next command gets a reopened connection
+    let counted: i64 = redis::cmd("HINCRBY")
+        .arg("servercounts.Test")
+        .arg("totalServers")
+        .arg(1)
+        .query(&mut conn)
+        .unwrap();
+    assert_eq!(counted, 2);
+}
+
+#[test]
+fn dropped_reads_are_resent() {
+    let server = FlakyServer::default();
+    let mut conn = connect(&server.start());
+    server.drop_next.store(true, Ordering::SeqCst);
+    let value: String = redis::cmd("GET").arg("key").query(&mut conn).unwrap();
+    assert_eq!(value, "42");
+    assert_eq!(server.count("GET"), 2);
+}
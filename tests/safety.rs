@@ -0,0 +1,151 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use chrono::Local;
+use plex_redis_manager::{
+    config::models::Config,
+    context_manager::ContextManager,
+    region::Region,
+    safety::{Impact, SafetyError},
+    server::{
+        dedicated::server::DedicatedServer,
+        minecraft::{status_to_json, MinecraftServer},
+        presets::{Preset, SizeTier},
+        server_group::ServerGroup,
+    },
+    store::entity::RedisEntity,
+};
+
+struct Guarded {
+    ctx: ContextManager,
+    node: DedicatedServer,
+    group: ServerGroup,
+    dir: PathBuf,
+}
+
+impl Drop for Guarded {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn guarded(name: &str, players: u8) -> Guarded {
+    //! An offline context holding the group, with a node running instance 1 of it and
+    //! `players` online there. The kill script always succeeds.
+    let dir = std::env::temp_dir().join(format!("plex_safety_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&dir).expect("temp dir should be writable");
+    let snapshot = dir.join("snapshot.json");
+    fs::write(&snapshot, "{}").expect("snapshot should be writable");
+    fs::write(dir.join("easyRemoteKillServer.sh"), "exit 0\n").expect("script should be writable");
+
+    let mut config = Config::default();
+    config.set_snapshot(Some(snapshot.to_string_lossy().into()));
+    config
+        .monitor_info
+        .set_scripts_path(dir.to_string_lossy().into());
+    let mut ctx = ContextManager::try_from_config(&config).expect("snapshot should load");
+    let group = Preset::Arcade.to_server_group("Test", Region::US, SizeTier::S);
+    group.save(&mut ctx).expect("group should be saved");
+
+    let mut node = DedicatedServer {
+        name: "local".into(),
+        public_address: "127.0.0.1".into(),
+        private_address: "127.0.0.1".into(),
+        aliases: Vec::new(),
+        region: Region::US,
+        available_cpu: 16,
+        available_ram: 16384,
+        max_cpu: 16,
+        max_ram: 16384,
+        max_instances: None,
+        port_range: None,
+        reservations: Vec::new(),
+        server_instances: HashMap::new(),
+        telemetry: None,
+        overloaded: None,
+        disk: None,
+    };
+    node.add_server(&group, 1).expect("node should have room");
+    let mut server = MinecraftServer::synthetic(&group, 1, Local::now());
+    server.heartbeat(players, 0, Local::now());
+    let key = ctx
+        .get_keys()
+        .status_key(&group.region.to_string(), server.get_name());
+    redis::cmd("SET")
+        .arg(key)
+        .arg(status_to_json(server.to_map()))
+        .query::<()>(ctx.get_connection())
+        .unwrap();
+    Guarded {
+        ctx,
+        node,
+        group,
+        dir,
+    }
+}
+
+#[test]
+fn populated_instances_are_only_killed_with_force() {
+    let mut guarded = guarded("kill", 5);
+    let Guarded {
+        ctx, node, group, ..
+    } = &mut guarded;
+    let name = "Test-1".to_string();
+    match node.kill_server(group, 1, false, ctx) {
+        Err(SafetyError::RequiresForce(operation, impact)) => {
+            assert_eq!(operation, "Killing Test-1");
+            assert_eq!(impact.instances, ["Test-1"]);
+            assert_eq!(impact.players_online, 5);
+        }
+        other => panic!("the kill should need force, got {:?}", other),
+    }
+    assert!(node.get_instance(&name).is_some());
+    assert!(MinecraftServer::get_status(&name, &Region::US, ctx).is_ok());
+
+    node.kill_server(group, 1, true, ctx).unwrap();
+    assert!(node.get_instance(&name).is_none());
+    assert!(MinecraftServer::get_status(&name, &Region::US, ctx).is_err());
+}
+
+#[test]
+fn empty_instances_are_killed_without_force() {
+    let mut guarded = guarded("empty", 0);
+    let Guarded {
+        ctx, node, group, ..
+    } = &mut guarded;
+    node.kill_server(group, 1, false, ctx).unwrap();
+    assert!(node.get_instance("Test-1").is_none());
+}
+
+#[test]
+fn live_groups_are_only_deleted_with_force() {
+    let mut guarded = guarded("delete", 0);
+    let Guarded { ctx, group, .. } = &mut guarded;
+    match group.safe_delete(false, ctx) {
+        Err(SafetyError::RequiresForce(operation, impact)) => {
+            assert_eq!(operation, "Deleting servergroups.Test");
+            assert_eq!(impact.instances, ["Test-1"]);
+            assert!(!impact.has_players());
+        }
+        other => panic!("the delete should need force, got {:?}", other),
+    }
+    assert!(group.is_cached(ctx));
+
+    group.safe_delete(true, ctx).unwrap();
+    assert!(!group.is_cached(ctx));
+}
+
+#[test]
+fn player_counts_add_up_past_u16() {
+    let mut group = Preset::Arcade.to_server_group("Test", Region::US, SizeTier::S);
+    group.max_players = u8::MAX;
+    let servers: Vec<MinecraftServer> = (1..=300)
+        .map(|server_num| {
+            let mut server = MinecraftServer::synthetic(&group, server_num, Local::now());
+            server.heartbeat(u8::MAX, 0, Local::now());
+            server
+        })
+        .collect();
+    let impact = Impact::from_servers(&servers);
+    assert_eq!(impact.instances.len(), 300);
+    assert_eq!(impact.players_online, 300 * u8::MAX as u32);
+}
@@ -0,0 +1,74 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use plex_redis_manager::{
+    config::models::Config, context_manager::ContextManager, server::server_group::ServerGroup,
+    store::entity::RedisEntity,
+};
+
+fn read_command(reader: &mut BufReader<TcpStream>) -> Option<Vec<String>> {
+    //! One RESP array of bulk strings, None once the client hung up.
+    let mut line = String::new();
+    reader.read_line(&mut line).ok().filter(|read| *read > 0)?;
+    let args: usize = line.trim().strip_prefix('*')?.parse().ok()?;
+    let mut command = Vec::new();
+    for _ in 0..args {
+        line.clear();
+        reader.read_line(&mut line).ok()?;
+        line.clear();
+        reader.read_line(&mut line).ok()?;
+        command.push(line.trim_end().to_string());
+    }
+    Some(command)
+}
+
+fn start(scans: Arc<Mutex<usize>>) -> String {
+    //! A redis stand-in failing every SCAN (as a dropped connection would) and
+    //! acknowledging everything else.
+    let listener = TcpListener::bind("127.0.0.1:0").expect("port should be free");
+    let address = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let scans = scans.clone();
+            thread::spawn(move || {
+                let mut writer = stream.try_clone().unwrap();
+                let mut reader = BufReader::new(stream);
+                while let Some(command) = read_command(&mut reader) {
+                    let reply = match command[0].eq_ignore_ascii_case("SCAN") {
+                        true => {
+                            *scans.lock().unwrap() += 1;
+                            "-ERR scan is broken\r\n"
+                        }
+                        false => "+OK\r\n",
+                    };
+                    if writer.write_all(reply.as_bytes()).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    address.port().to_string()
+}
+
+#[test]
+fn failing_scans_end_the_iterator() {
+    let scans = Arc::new(Mutex::new(0));
+    let port = start(scans.clone());
+    let config =
+        include_str!("../config.toml").replace("port = \"6379\"", &format!("port = \"{}\"", port));
+    let config: Config = toml::from_str(&config).expect("config.toml should parse");
+    let mut ctx = ContextManager::try_from_config(&config).expect("stand-in should accept");
+
+    let mut groups = ServerGroup::iter(10, &mut ctx);
+    assert!(matches!(groups.next(), Some(Err(_))));
+    assert!(
+        groups.next().is_none(),
+        "the failed scan should not be retried"
+    );
+    assert_eq!(*scans.lock().unwrap(), 1);
+}
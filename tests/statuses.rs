@@ -0,0 +1,49 @@
+use std::fs;
+
+use chrono::Local;
+use plex_redis_manager::{
+    config::models::Config,
+    context_manager::ContextManager,
+    region::Region,
+    server::{
+        minecraft::MinecraftServer,
+        presets::{Preset, SizeTier},
+    },
+    store::entity::RedisEntity,
+};
+
+fn offline_context(name: &str) -> ContextManager {
+    //! A context on an empty snapshot.
+    let path = std::env::temp_dir().join(format!(
+        "plex_statuses_{}_{}.json",
+        name,
+        std::process::id()
+    ));
+    fs::write(&path, "{}").expect("snapshot should be writable");
+    let mut config = Config::default();
+    config.set_snapshot(Some(path.to_string_lossy().into()));
+    let ctx = ContextManager::try_from_config(&config).expect("snapshot should load");
+    let _ = fs::remove_file(&path);
+    ctx
+}
+
+#[test]
+fn statuses_keep_the_region_of_their_key() {
+    let mut ctx = offline_context("region");
+    let group = Preset::Arcade.to_server_group("Test", Region::EU, SizeTier::S);
+    let server = MinecraftServer::synthetic(&group, 1, Local::now());
+    assert_eq!(server.get_id(), "EU.Test-1");
+    server.save(&mut ctx).expect("status should be saved");
+
+    let name = server.get_name().clone();
+    assert!(MinecraftServer::get_status(&name, &Region::US, &mut ctx).is_err());
+    let read = MinecraftServer::get_status(&name, &Region::EU, &mut ctx).unwrap();
+    assert_eq!(read.get_region(), &Region::EU);
+    let listed = MinecraftServer::get_all(&mut ctx).ok;
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].get_id(), "EU.Test-1");
+
+    // deleting by id removes the EU status, not a US one
+    MinecraftServer::delete(&read.get_id(), &mut ctx).unwrap();
+    assert!(MinecraftServer::get_all(&mut ctx).ok.is_empty());
+}
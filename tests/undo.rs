@@ -0,0 +1,108 @@
+use std::fs;
+
+use plex_redis_manager::{
+    config::models::Config,
+    context_manager::ContextManager,
+    events::{Event, EventKind},
+    region::Region,
+    server::{
+        presets::{Preset, SizeTier},
+        server_group::ServerGroup,
+    },
+    store::entity::RedisEntity,
+    undo::{self, GroupChange, UndoError},
+};
+
+fn offline_context(name: &str) -> ContextManager {
+    //! A context on an empty snapshot.
+    let path = std::env::temp_dir().join(format!("plex_undo_{}_{}.json", name, std::process::id()));
+    fs::write(&path, "{}").expect("snapshot should be writable");
+    let mut config = Config::default();
+    config.set_snapshot(Some(path.to_string_lossy().into()));
+    let ctx = ContextManager::try_from_config(&config).expect("snapshot should load");
+    let _ = fs::remove_file(&path);
+    ctx
+}
+
+fn set_max_players(max_players: u8, recorded: bool, ctx: &mut ContextManager) {
+    //! Changes the group's maxPlayers, recording the change for undo when `recorded`.
+    let before = ServerGroup::get("Test", ctx).unwrap();
+    let mut after = before.clone();
+    after.max_players = max_players;
+    after.save(ctx).unwrap();
+    if recorded {
+        GroupChange::between(&before, &after)
+            .expect("maxPlayers should differ")
+            .emit("set", ctx);
+    }
+}
+
+fn setup(name: &str) -> ContextManager {
+    //! A group of 16 players whose maxPlayers was then set to 24.
+    let mut ctx = offline_context(name);
+    let mut group = Preset::Arcade.to_server_group("Test", Region::US, SizeTier::S);
+    group.max_players = 16;
+    group.save(&mut ctx).unwrap();
+    set_max_players(24, true, &mut ctx);
+    ctx
+}
+
+#[test]
+fn undone_changes_are_reverted_and_no_longer_listed() {
+    let mut ctx = setup("revert");
+    let undoable = undo::get_undoable(&mut ctx).unwrap();
+    assert_eq!(undoable.len(), 1);
+    let change = undoable[0].change.as_ref().unwrap();
+    assert_eq!(change.group, "Test");
+    assert_eq!(change.before["maxPlayers"], "16");
+    assert_eq!(change.after["maxPlayers"], "24");
+
+    let inverse = undo::undo(&undoable[0], false, &mut ctx).unwrap();
+    assert_eq!(inverse.after["maxPlayers"], "16");
+    assert_eq!(inverse.undoes, Some(undoable[0].timestamp));
+    assert_eq!(ServerGroup::get("Test", &mut ctx).unwrap().max_players, 16);
+    // neither the change nor its undo is left to undo
+    assert!(undo::get_undoable(&mut ctx).unwrap().is_empty());
+}
+
+#[test]
+fn changes_made_since_are_only_overwritten_with_force() {
+    let mut ctx = setup("conflict");
+    set_max_players(32, false, &mut ctx);
+    let undoable = undo::get_undoable(&mut ctx).unwrap();
+    let err = undo::undo(&undoable[0], false, &mut ctx).unwrap_err();
+    assert!(matches!(err, UndoError::Conflict(_)), "{}", err);
+    assert_eq!(ServerGroup::get("Test", &mut ctx).unwrap().max_players, 32);
+
+    undo::undo(&undoable[0], true, &mut ctx).unwrap();
+    assert_eq!(ServerGroup::get("Test", &mut ctx).unwrap().max_players, 16);
+}
+
+#[test]
+fn newest_changes_are_undone_first() {
+    let mut ctx = setup("order");
+    set_max_players(32, true, &mut ctx);
+    let undoable = undo::get_undoable(&mut ctx).unwrap();
+    assert_eq!(undoable.len(), 2);
+    assert_eq!(
+        undoable[0].change.as_ref().unwrap().after["maxPlayers"],
+        "32"
+    );
+    assert_eq!(
+        undoable[1].change.as_ref().unwrap().after["maxPlayers"],
+        "24"
+    );
+}
+
+#[test]
+fn removed_instances_cannot_be_undone() {
+    let mut ctx = offline_context("irreversible");
+    Event::new(EventKind::InstanceRemoved, "Test-1", "removed".into()).emit(&mut ctx);
+    let undoable = undo::get_undoable(&mut ctx).unwrap();
+    assert_eq!(undoable.len(), 1);
+    let err = undo::undo(&undoable[0], true, &mut ctx).unwrap_err();
+    assert!(matches!(err, UndoError::Irreversible(_)), "{}", err);
+
+    let mut ctx = offline_context("empty");
+    assert!(undo::get_undoable(&mut ctx).unwrap().is_empty());
+}